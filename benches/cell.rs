@@ -0,0 +1,37 @@
+//! Swap throughput of `Cell::swap` against `Cell::swap_copy`.
+//!
+//! Run with `cargo bench --bench cell`. The numbers are wall-clock times
+//! of a tight loop, so compare them with each other rather than across machines.
+
+use pointer::Cell;
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 10_000_000;
+
+fn bench(name: &str, mut f: impl FnMut()) {
+  // Warm up caches and the branch predictor first.
+  for _ in 0..ITERATIONS / 10 {
+    f();
+  }
+  let start = Instant::now();
+  for _ in 0..ITERATIONS {
+    f();
+  }
+  let per_iter = start.elapsed().as_secs_f64() / f64::from(ITERATIONS);
+  println!("{:<32} {:>8.2} ns/iter", name, per_iter * 1e9);
+}
+
+fn main() {
+  let (a, b) = (Cell::new(1u64), Cell::new(2u64));
+  bench("Cell<u64> swap", || black_box(&a).swap(black_box(&b)));
+  bench("Cell<u64> swap_copy", || {
+    black_box(&a).swap_copy(black_box(&b))
+  });
+
+  let (a, b) = (Cell::new([1u8; 16]), Cell::new([2u8; 16]));
+  bench("Cell<[u8; 16]> swap", || black_box(&a).swap(black_box(&b)));
+  bench("Cell<[u8; 16]> swap_copy", || {
+    black_box(&a).swap_copy(black_box(&b))
+  });
+}
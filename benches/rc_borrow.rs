@@ -0,0 +1,75 @@
+//! Traversal time of a deep `Rc` tree, passing children as clones, as
+//! `&Rc<T>` and as `RcBorrow<T>`.
+//!
+//! Run with `cargo bench --bench rc_borrow`. The numbers are wall-clock times
+//! of a tight loop, so compare them with each other rather than across machines.
+
+use pointer::{Rc, RcBorrow};
+
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 200;
+/// The tree is a full binary tree of this depth, about 260k nodes.
+const DEPTH: u32 = 17;
+
+struct Node {
+  value: u64,
+  children: Vec<Rc<Node>>,
+}
+
+fn tree(depth: u32) -> Rc<Node> {
+  let children = if depth == 0 {
+    Vec::new()
+  } else {
+    vec![tree(depth - 1), tree(depth - 1)]
+  };
+  Rc::new(Node {
+    value: u64::from(depth),
+    children,
+  })
+}
+
+fn sum_cloned(node: Rc<Node>) -> u64 {
+  let children = node.children.iter();
+  node.value + children.map(|c| sum_cloned(Rc::clone(c))).sum::<u64>()
+}
+
+fn sum_ref(node: &Rc<Node>) -> u64 {
+  node.value + node.children.iter().map(sum_ref).sum::<u64>()
+}
+
+fn sum_borrowed(node: RcBorrow<'_, Node>) -> u64 {
+  let children = node.get().children.iter();
+  node.value
+    + children
+      .map(|c| sum_borrowed(Rc::borrow_rc(c)))
+      .sum::<u64>()
+}
+
+fn bench(name: &str, mut f: impl FnMut()) {
+  // Warm up caches and the branch predictor first.
+  for _ in 0..ITERATIONS / 10 {
+    f();
+  }
+  let start = Instant::now();
+  for _ in 0..ITERATIONS {
+    f();
+  }
+  let per_iter = start.elapsed().as_secs_f64() / f64::from(ITERATIONS);
+  println!("{:<24} {:>8.2} us/iter", name, per_iter * 1e6);
+}
+
+fn main() {
+  let root = tree(DEPTH);
+
+  bench("clone per frame", || {
+    black_box(sum_cloned(Rc::clone(black_box(&root))));
+  });
+  bench("&Rc per frame", || {
+    black_box(sum_ref(black_box(&root)));
+  });
+  bench("RcBorrow per frame", || {
+    black_box(sum_borrowed(Rc::borrow_rc(black_box(&root))));
+  });
+}
@@ -0,0 +1,277 @@
+//! Derive macros for the `smart-pointer` crate.
+//!
+//! Use them through the `derive` feature of `smart-pointer`, which re-exports each macro next to
+//! the trait it implements; this crate isn't meant to be depended on directly.
+
+extern crate proc_macro;
+
+mod parse;
+
+use parse::{Data, Field, Item};
+use proc_macro::{TokenStream, TokenTree};
+
+/// Derives `pointer::gc::Trace` by tracing every field.
+///
+/// Every field's type must implement `Trace`, and every type parameter gets a
+/// `Trace` bound. Mark a field `#[trace(skip)]` to leave it out, e.g. when
+/// its type doesn't implement `Trace`. Skipping a field that holds a `Gc` is
+/// safe, but a cycle through it is never collected.
+///
+/// ```
+/// use pointer::gc::{self, Gc, Trace};
+/// use pointer::RefCell;
+///
+/// #[derive(Trace)]
+/// struct Node {
+///   next: RefCell<Option<Gc<Node>>>,
+///   #[trace(skip)]
+///   label: std::path::PathBuf,
+/// }
+///
+/// let node = Gc::new(Node {
+///   next: RefCell::new(None),
+///   label: "a".into(),
+/// });
+/// *node.next.borrow_mut() = Some(Gc::clone(&node));
+/// drop(node);
+///
+/// assert_eq!(gc::collect_cycles(), 1);
+/// ```
+#[proc_macro_derive(Trace, attributes(trace))]
+pub fn derive_trace(input: TokenStream) -> TokenStream {
+  match parse::parse(input).and_then(|item| expand_trace(&item)) {
+    Ok(code) => code.parse().unwrap(),
+    Err(message) => compile_error(&message),
+  }
+}
+
+fn expand_trace(item: &Item) -> Result<String, String> {
+  let name = &item.name;
+  let arms = match &item.data {
+    Data::Struct(fields) => vec![trace_arm(name, fields)?],
+    Data::Enum(variants) => variants
+      .iter()
+      .map(|v| trace_arm(&format!("{}::{}", name, v.name), &v.fields))
+      .collect::<Result<_, _>>()?,
+  };
+  // An empty enum can't be matched through the reference.
+  let body = if arms.is_empty() {
+    String::from("match *self {}")
+  } else {
+    format!("match self {{ {} }}", arms.join(" "))
+  };
+
+  let generics = &item.generics;
+  let bounds = generics
+    .type_params
+    .iter()
+    .map(|param| format!("{}: ::pointer::gc::Trace", param));
+  Ok(format!(
+    "#[automatically_derived]
+    unsafe impl{params} ::pointer::gc::Trace for {name}{args} {where_clause} {{
+      #[allow(unused_variables)]
+      fn trace(&self, tracer: &mut ::pointer::gc::Tracer<'_>) {{ {body} }}
+    }}",
+    params = generics.impl_params(),
+    name = name,
+    args = generics.type_args(),
+    where_clause = generics.where_clause(bounds),
+    body = body,
+  ))
+}
+
+/// Returns a match arm for `path { .. }` that traces the fields that aren't
+/// skipped.
+fn trace_arm(path: &str, fields: &[Field]) -> Result<String, String> {
+  let mut bindings = Vec::new();
+  let mut calls = Vec::new();
+  for (i, field) in fields.iter().enumerate() {
+    let mut skip = false;
+    for attr in field.attrs.iter().filter(|attr| attr.path == "trace") {
+      if attr.is("trace", "skip") {
+        skip = true;
+      } else {
+        return Err("expected `#[trace(skip)]`".into());
+      }
+    }
+    if !skip {
+      bindings.push(format!("{}: __field{}", field.member, i));
+      calls.push(format!(
+        "::pointer::gc::Trace::trace(__field{}, tracer);",
+        i
+      ));
+    }
+  }
+  bindings.push(String::from(".."));
+  Ok(format!(
+    "{} {{ {} }} => {{ {} }}",
+    path,
+    bindings.join(", "),
+    calls.join(" ")
+  ))
+}
+
+/// Derives `&self` accessors for the `Cell` and `RefCell` fields marked
+/// `#[interior]`.
+///
+/// For a field `name: Cell<T>`, it generates `name()`, which returns a copy
+/// of the value and so needs `T: Copy`, and `set_name(value)` and
+/// `update_name(f)`, like [`Cell::update`]. For a field `name: RefCell<T>`,
+/// it generates `name()` and `name_mut()` to borrow the value, `set_name(value)`
+/// and `update_name(f)`, which calls `f` with a mutable borrow and returns its
+/// result. The accessors have the visibility of their field. Both the cells of
+/// this crate and those of `std::cell` work.
+///
+/// [`Cell::update`]: ../pointer/cell/struct.Cell.html#method.update
+///
+/// ```
+/// use pointer::{Cell, InteriorMutable, RefCell};
+///
+/// #[derive(InteriorMutable)]
+/// pub struct Page {
+///   #[interior]
+///   pub views: Cell<u32>,
+///   #[interior]
+///   pub rendered: RefCell<Option<String>>,
+/// }
+///
+/// let page = Page {
+///   views: Cell::new(0),
+///   rendered: RefCell::new(None),
+/// };
+/// page.update_views(|n| n + 1);
+/// page.set_rendered(Some(String::from("<p>hi</p>")));
+///
+/// assert_eq!(page.views(), 1);
+/// assert_eq!(page.rendered().as_deref(), Some("<p>hi</p>"));
+/// ```
+#[proc_macro_derive(InteriorMutable, attributes(interior))]
+pub fn derive_interior_mutable(input: TokenStream) -> TokenStream {
+  match parse::parse(input).and_then(|item| expand_interior_mutable(&item)) {
+    Ok(code) => code.parse().unwrap(),
+    Err(message) => compile_error(&message),
+  }
+}
+
+fn expand_interior_mutable(item: &Item) -> Result<String, String> {
+  let fields = match &item.data {
+    Data::Struct(fields) => fields,
+    Data::Enum(_) => {
+      return Err("`InteriorMutable` can only be derived for structs".into())
+    }
+  };
+  let mut methods = Vec::new();
+  for field in fields {
+    let mut marked = false;
+    for attr in field.attrs.iter().filter(|attr| attr.path == "interior") {
+      if attr.args.is_some() {
+        return Err("expected `#[interior]`".into());
+      }
+      marked = true;
+    }
+    if !marked {
+      continue;
+    }
+    if field.member.parse::<usize>().is_ok() {
+      return Err("`#[interior]` fields must be named".into());
+    }
+    let (kind, value) = cell_type(&field.ty)
+      .ok_or("`#[interior]` fields must be a `Cell` or a `RefCell`")?;
+    methods.push(accessors(field, kind, &value));
+  }
+
+  let generics = &item.generics;
+  Ok(format!(
+    "#[automatically_derived]
+    #[allow(dead_code)]
+    impl{params} {name}{args} {where_clause} {{ {methods} }}",
+    params = generics.impl_params(),
+    name = item.name,
+    args = generics.type_args(),
+    where_clause = generics.where_clause(None),
+    methods = methods.join(" "),
+  ))
+}
+
+/// Returns the accessors of `field`, a `kind` holding a `value`.
+fn accessors(field: &Field, kind: &str, value: &str) -> String {
+  let (vis, name) = (&field.vis, &field.member);
+  if kind == "Cell" {
+    format!(
+      "#[inline] {vis} fn {name}(&self) -> {value} {{ self.{name}.get() }}
+      #[inline] {vis} fn set_{name}(&self, value: {value}) {{
+        self.{name}.set(value)
+      }}
+      #[inline] {vis} fn update_{name}(
+        &self,
+        f: impl ::core::ops::FnOnce({value}) -> {value},
+      ) -> {value} {{
+        let new = f(self.{name}.get());
+        self.{name}.set(new);
+        new
+      }}",
+      vis = vis,
+      name = name,
+      value = value,
+    )
+  } else {
+    format!(
+      "#[inline] {vis} fn {name}(&self)
+        -> impl ::core::ops::Deref<Target = {value}> + '_ {{
+        self.{name}.borrow()
+      }}
+      #[inline] {vis} fn {name}_mut(&self)
+        -> impl ::core::ops::DerefMut<Target = {value}> + '_ {{
+        self.{name}.borrow_mut()
+      }}
+      #[inline] {vis} fn set_{name}(&self, value: {value}) {{
+        *self.{name}.borrow_mut() = value;
+      }}
+      #[inline] {vis} fn update_{name}<R>(
+        &self,
+        f: impl ::core::ops::FnOnce(&mut {value}) -> R,
+      ) -> R {{
+        f(&mut *self.{name}.borrow_mut())
+      }}",
+      vis = vis,
+      name = name,
+      value = value,
+    )
+  }
+}
+
+/// Returns whether `ty` is a `Cell<T>` or a `RefCell<T>`, by the last
+/// segment of its path, and `T`.
+fn cell_type(ty: &[TokenTree]) -> Option<(&'static str, String)> {
+  let (start, kind) =
+    ty.iter().enumerate().find_map(|(i, token)| match token {
+      TokenTree::Ident(ident) => match ident.to_string().as_str() {
+        "Cell" => Some((i, "Cell")),
+        "RefCell" => Some((i, "RefCell")),
+        _ => None,
+      },
+      _ => None,
+    })?;
+  // Only `path::to::Cell<..>` itself, not a type that merely contains one.
+  let path = ty[..start].iter().all(|token| match token {
+    TokenTree::Ident(_) => true,
+    TokenTree::Punct(p) => p.as_char() == ':',
+    _ => false,
+  });
+  let angled = match (ty.get(start + 1), ty.last()) {
+    (Some(TokenTree::Punct(open)), Some(TokenTree::Punct(close))) => {
+      open.as_char() == '<' && close.as_char() == '>'
+    }
+    _ => false,
+  };
+  if !path || !angled || ty.len() < start + 4 {
+    return None;
+  }
+  Some((kind, parse::to_string(&ty[start + 2..ty.len() - 1])))
+}
+
+fn compile_error(message: &str) -> TokenStream {
+  format!("::core::compile_error!({:?});", message)
+    .parse()
+    .unwrap()
+}
@@ -0,0 +1,417 @@
+//! A small parser for the items the derives accept.
+//!
+//! Only the shape of an item is parsed: its name, generics, fields and their attributes. Bounds are
+//! kept as token strings and pasted back into the generated code unchanged.
+
+use proc_macro::{Delimiter, Spacing, TokenStream, TokenTree};
+
+/// A struct or enum a derive was applied to.
+pub struct Item {
+  pub name: String,
+  pub generics: Generics,
+  pub data: Data,
+}
+
+/// The generic parameters of an item, and its `where` clause.
+#[derive(Default)]
+pub struct Generics {
+  /// The parameters with their bounds and without defaults, for `impl<..>`.
+  pub params: Vec<String>,
+  /// The parameters as arguments, for `Name<..>`.
+  pub args: Vec<String>,
+  /// The names of the type parameters.
+  pub type_params: Vec<String>,
+  /// The predicates of the `where` clause.
+  pub predicates: Vec<String>,
+}
+
+pub enum Data {
+  Struct(Vec<Field>),
+  Enum(Vec<Variant>),
+}
+
+pub struct Variant {
+  pub name: String,
+  pub fields: Vec<Field>,
+}
+
+pub struct Field {
+  /// The field name, or its index in a tuple struct or variant.
+  pub member: String,
+  pub attrs: Vec<Attr>,
+  /// The visibility, such as `pub(crate)`, or nothing for a private field.
+  pub vis: String,
+  /// The tokens of the field type.
+  pub ty: Vec<TokenTree>,
+}
+
+/// An attribute such as `#[name(args)]`.
+pub struct Attr {
+  pub path: String,
+  pub args: Option<TokenStream>,
+}
+
+impl Generics {
+  /// Returns `<params>` for an `impl`, or nothing without parameters.
+  pub fn impl_params(&self) -> String {
+    angled(&self.params)
+  }
+
+  /// Returns `<args>` for the item type, or nothing without parameters.
+  pub fn type_args(&self) -> String {
+    angled(&self.args)
+  }
+
+  /// Returns the `where` clause with `extra` predicates added.
+  pub fn where_clause(
+    &self,
+    extra: impl IntoIterator<Item = String>,
+  ) -> String {
+    let predicates: Vec<String> =
+      self.predicates.iter().cloned().chain(extra).collect();
+    if predicates.is_empty() {
+      String::new()
+    } else {
+      format!("where {}", predicates.join(", "))
+    }
+  }
+}
+
+fn angled(list: &[String]) -> String {
+  if list.is_empty() {
+    String::new()
+  } else {
+    format!("<{}>", list.join(", "))
+  }
+}
+
+impl Attr {
+  /// Returns whether this is `#[path(word)]`.
+  pub fn is(&self, path: &str, word: &str) -> bool {
+    self.path == path
+      && self.args.as_ref().is_some_and(|args| {
+        let args: Vec<TokenTree> = args.clone().into_iter().collect();
+        matches!(&args[..], [TokenTree::Ident(i)] if i.to_string() == word)
+      })
+  }
+}
+
+/// Parses the input of a derive.
+pub fn parse(input: TokenStream) -> Result<Item, String> {
+  let mut cur = Cursor::new(input);
+  cur.attrs()?;
+  cur.visibility();
+
+  let kind = cur.ident().ok_or("expected `struct` or `enum`")?;
+  let name = cur.ident().ok_or("expected the item name")?;
+  let mut generics = cur.generics()?;
+
+  let data = match kind.as_str() {
+    "struct" => {
+      let tuple = match cur.peek() {
+        Some(TokenTree::Group(g))
+          if g.delimiter() == Delimiter::Parenthesis =>
+        {
+          let stream = g.stream();
+          cur.pos += 1;
+          Some(unnamed_fields(stream)?)
+        }
+        _ => None,
+      };
+      generics.predicates = cur.where_clause();
+      match (cur.next(), tuple) {
+        (Some(TokenTree::Group(g)), None)
+          if g.delimiter() == Delimiter::Brace =>
+        {
+          Data::Struct(named_fields(g.stream())?)
+        }
+        (Some(TokenTree::Punct(p)), tuple) if p.as_char() == ';' => {
+          Data::Struct(tuple.unwrap_or_default())
+        }
+        _ => return Err("expected the struct body".into()),
+      }
+    }
+    "enum" => {
+      generics.predicates = cur.where_clause();
+      match cur.next() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace => {
+          Data::Enum(variants(g.stream())?)
+        }
+        _ => return Err("expected the enum body".into()),
+      }
+    }
+    "union" => return Err("unions are not supported".into()),
+    _ => return Err("expected `struct` or `enum`".into()),
+  };
+
+  Ok(Item {
+    name,
+    generics,
+    data,
+  })
+}
+
+fn named_fields(stream: TokenStream) -> Result<Vec<Field>, String> {
+  split(stream, true)
+    .into_iter()
+    .map(|tokens| {
+      let mut cur = Cursor::from(tokens);
+      let attrs = cur.attrs()?;
+      let vis = cur.visibility();
+      let member = cur.ident().ok_or("expected a field name")?;
+      match cur.next() {
+        Some(TokenTree::Punct(p)) if p.as_char() == ':' => {}
+        _ => return Err("expected `:` after the field name".into()),
+      }
+      Ok(Field {
+        member,
+        attrs,
+        vis,
+        ty: cur.rest(),
+      })
+    })
+    .collect()
+}
+
+fn unnamed_fields(stream: TokenStream) -> Result<Vec<Field>, String> {
+  split(stream, true)
+    .into_iter()
+    .enumerate()
+    .map(|(index, tokens)| {
+      let mut cur = Cursor::from(tokens);
+      let attrs = cur.attrs()?;
+      let vis = cur.visibility();
+      Ok(Field {
+        member: index.to_string(),
+        attrs,
+        vis,
+        ty: cur.rest(),
+      })
+    })
+    .collect()
+}
+
+fn variants(stream: TokenStream) -> Result<Vec<Variant>, String> {
+  split(stream, false)
+    .into_iter()
+    .map(|tokens| {
+      let mut cur = Cursor::from(tokens);
+      cur.attrs()?;
+      let name = cur.ident().ok_or("expected a variant name")?;
+      let fields = match cur.next() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace => {
+          named_fields(g.stream())?
+        }
+        Some(TokenTree::Group(g))
+          if g.delimiter() == Delimiter::Parenthesis =>
+        {
+          unnamed_fields(g.stream())?
+        }
+        _ => Vec::new(),
+      };
+      Ok(Variant { name, fields })
+    })
+    .collect()
+}
+
+/// Splits `stream` on its top-level commas, dropping empty pieces.
+///
+/// With `angles`, commas inside `<..>` are not top-level, as in field types.
+fn split(stream: TokenStream, angles: bool) -> Vec<Vec<TokenTree>> {
+  let mut pieces = vec![Vec::new()];
+  let mut depth = 0usize;
+  let mut arrow = false;
+  for token in stream {
+    if let TokenTree::Punct(p) = &token {
+      match p.as_char() {
+        ',' if depth == 0 => {
+          pieces.push(Vec::new());
+          continue;
+        }
+        '<' if angles => depth += 1,
+        // The `>` of a `->` doesn't close an angle bracket.
+        '>' if angles && !arrow => depth = depth.saturating_sub(1),
+        _ => {}
+      }
+      arrow = p.as_char() == '-' && p.spacing() == Spacing::Joint;
+    } else {
+      arrow = false;
+    }
+    pieces.last_mut().unwrap().push(token);
+  }
+  pieces.retain(|piece| !piece.is_empty());
+  pieces
+}
+
+pub fn to_string(tokens: &[TokenTree]) -> String {
+  tokens.iter().cloned().collect::<TokenStream>().to_string()
+}
+
+struct Cursor {
+  tokens: Vec<TokenTree>,
+  pos: usize,
+}
+
+impl Cursor {
+  fn new(stream: TokenStream) -> Cursor {
+    Cursor::from(stream.into_iter().collect())
+  }
+
+  fn from(tokens: Vec<TokenTree>) -> Cursor {
+    Cursor { tokens, pos: 0 }
+  }
+
+  fn peek(&self) -> Option<&TokenTree> {
+    self.tokens.get(self.pos)
+  }
+
+  fn next(&mut self) -> Option<TokenTree> {
+    let token = self.tokens.get(self.pos).cloned();
+    self.pos += 1;
+    token
+  }
+
+  fn is_punct(&self, c: char) -> bool {
+    matches!(self.peek(), Some(TokenTree::Punct(p)) if p.as_char() == c)
+  }
+
+  fn ident(&mut self) -> Option<String> {
+    match self.peek() {
+      Some(TokenTree::Ident(i)) => {
+        let ident = i.to_string();
+        self.pos += 1;
+        Some(ident)
+      }
+      _ => None,
+    }
+  }
+
+  fn attrs(&mut self) -> Result<Vec<Attr>, String> {
+    let mut attrs = Vec::new();
+    while self.is_punct('#') {
+      self.pos += 1;
+      let group = match self.next() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket => g,
+        _ => return Err("expected an attribute".into()),
+      };
+      let mut inner = Cursor::new(group.stream());
+      let mut path = inner.ident().unwrap_or_default();
+      while inner.is_punct(':') {
+        inner.pos += 2;
+        path.push_str("::");
+        path.push_str(&inner.ident().unwrap_or_default());
+      }
+      let args = match inner.next() {
+        Some(TokenTree::Group(g))
+          if g.delimiter() == Delimiter::Parenthesis =>
+        {
+          Some(g.stream())
+        }
+        _ => None,
+      };
+      attrs.push(Attr { path, args });
+    }
+    Ok(attrs)
+  }
+
+  /// Skips a visibility, returning it.
+  fn visibility(&mut self) -> String {
+    let start = self.pos;
+    if matches!(self.peek(), Some(TokenTree::Ident(i)) if i.to_string() == "pub")
+    {
+      self.pos += 1;
+      if matches!(self.peek(), Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis)
+      {
+        self.pos += 1;
+      }
+    }
+    to_string(&self.tokens[start..self.pos])
+  }
+
+  /// Returns the tokens that are left.
+  fn rest(&mut self) -> Vec<TokenTree> {
+    let rest = self.tokens[self.pos..].to_vec();
+    self.pos = self.tokens.len();
+    rest
+  }
+
+  fn generics(&mut self) -> Result<Generics, String> {
+    let mut generics = Generics::default();
+    if !self.is_punct('<') {
+      return Ok(generics);
+    }
+    self.pos += 1;
+
+    let mut depth = 1usize;
+    let mut arrow = false;
+    let mut tokens = Vec::new();
+    loop {
+      let token = self.next().ok_or("unclosed generics")?;
+      if let TokenTree::Punct(p) = &token {
+        match p.as_char() {
+          '<' => depth += 1,
+          '>' if !arrow => {
+            depth -= 1;
+            if depth == 0 {
+              break;
+            }
+          }
+          _ => {}
+        }
+        arrow = p.as_char() == '-' && p.spacing() == Spacing::Joint;
+      } else {
+        arrow = false;
+      }
+      tokens.push(token);
+    }
+
+    for param in split(tokens.into_iter().collect(), true) {
+      // Defaults only belong on the item, not on the `impl`.
+      let end = param
+        .iter()
+        .position(|t| matches!(t, TokenTree::Punct(p) if p.as_char() == '='))
+        .unwrap_or(param.len());
+      let param = &param[..end];
+      match param {
+        [TokenTree::Punct(p), TokenTree::Ident(name), ..]
+          if p.as_char() == '\'' =>
+        {
+          generics.args.push(format!("'{}", name));
+        }
+        [TokenTree::Ident(kw), TokenTree::Ident(name), ..]
+          if kw.to_string() == "const" =>
+        {
+          generics.args.push(name.to_string());
+        }
+        [TokenTree::Ident(name), ..] => {
+          generics.args.push(name.to_string());
+          generics.type_params.push(name.to_string());
+        }
+        _ => return Err("unexpected generic parameter".into()),
+      }
+      generics.params.push(to_string(param));
+    }
+    Ok(generics)
+  }
+
+  /// Parses a `where` clause up to the item body, if there is one.
+  fn where_clause(&mut self) -> Vec<String> {
+    if !matches!(self.peek(), Some(TokenTree::Ident(i)) if i.to_string() == "where")
+    {
+      return Vec::new();
+    }
+    self.pos += 1;
+    let start = self.pos;
+    while let Some(token) = self.peek() {
+      match token {
+        TokenTree::Group(g) if g.delimiter() == Delimiter::Brace => break,
+        TokenTree::Punct(p) if p.as_char() == ';' => break,
+        _ => self.pos += 1,
+      }
+    }
+    split(self.tokens[start..self.pos].iter().cloned().collect(), true)
+      .iter()
+      .map(|predicate| to_string(predicate))
+      .collect()
+  }
+}
@@ -0,0 +1,114 @@
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#ifndef POINTER_H
+#define POINTER_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * A strong reference to an `Arc<[u8]>`, in a form C can hold.
+ *
+ * Unlike [`RcBytes`], it may be sent to and shared with other threads.
+ *
+ * See the [module-level documentation](./index.html) for more details.
+ */
+typedef struct PointerArcBytes {
+  /**
+   * The first byte of the buffer.
+   */
+  const uint8_t *data;
+  /**
+   * The length of the buffer in bytes.
+   */
+  size_t len;
+} PointerArcBytes;
+
+/**
+ * A strong reference to an `Rc<[u8]>`, in a form C can hold.
+ *
+ * See the [module-level documentation](./index.html) for more details.
+ */
+typedef struct PointerRcBytes {
+  /**
+   * The first byte of the buffer.
+   */
+  const uint8_t *data;
+  /**
+   * The length of the buffer in bytes.
+   */
+  size_t len;
+} PointerRcBytes;
+
+/**
+ * Takes another strong reference to the buffer of `bytes`, returning a
+ * handle that owns it.
+ *
+ * # Safety
+ *
+ * `bytes` must hold a reference that hasn't been given back, as for
+ * [`ArcBytes::into_arc`].
+ */
+struct PointerArcBytes pointer_arc_clone_raw(struct PointerArcBytes bytes);
+
+/**
+ * Returns a pointer to the first byte of the buffer of `bytes`.
+ *
+ * The bytes are valid for as long as the handle holds its reference.
+ */
+const uint8_t *pointer_arc_data(struct PointerArcBytes bytes);
+
+/**
+ * Returns the length of the buffer of `bytes`, in bytes.
+ */
+size_t pointer_arc_len(struct PointerArcBytes bytes);
+
+/**
+ * Gives back the strong reference held by `bytes`, freeing the buffer if it
+ * was the last one.
+ *
+ * # Safety
+ *
+ * As for [`ArcBytes::into_arc`]; the handle and its copies can't be used
+ * afterwards.
+ */
+void pointer_arc_release_raw(struct PointerArcBytes bytes);
+
+/**
+ * Takes another strong reference to the buffer of `bytes`, returning a
+ * handle that owns it.
+ *
+ * # Safety
+ *
+ * `bytes` must hold a reference that hasn't been given back, as for
+ * [`RcBytes::into_rc`].
+ */
+struct PointerRcBytes pointer_rc_clone_raw(struct PointerRcBytes bytes);
+
+/**
+ * Returns a pointer to the first byte of the buffer of `bytes`.
+ *
+ * The bytes are valid for as long as the handle holds its reference.
+ */
+const uint8_t *pointer_rc_data(struct PointerRcBytes bytes);
+
+/**
+ * Returns the length of the buffer of `bytes`, in bytes.
+ */
+size_t pointer_rc_len(struct PointerRcBytes bytes);
+
+/**
+ * Gives back the strong reference held by `bytes`, freeing the buffer if it
+ * was the last one.
+ *
+ * # Safety
+ *
+ * As for [`RcBytes::into_rc`]; the handle and its copies can't be used
+ * afterwards.
+ */
+void pointer_rc_release_raw(struct PointerRcBytes bytes);
+
+#endif /* POINTER_H */
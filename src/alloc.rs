@@ -0,0 +1,305 @@
+//! Memory allocators for reference-counted pointers.
+//!
+//! [`Rc`] and [`Arc`] allocate their counts and value from the global allocator by default. Their
+//! `new_in` constructors take an [`Allocator`] instead, such as an arena or a bump allocator that
+//! makes large numbers of short-lived allocations cheap. The allocator is kept in every pointer to
+//! the allocation, so the last one can hand the memory back to it; [`Global`] takes no space.
+//!
+//! [`Allocator`] mirrors the shape of the unstable `std::alloc::Allocator` trait, so it can be
+//! implemented for an existing allocator with a few lines of forwarding.
+//!
+//! ```
+//! use pointer::alloc::{AllocError, Allocator, Global};
+//! use pointer::Rc;
+//! use std::alloc::Layout;
+//! use std::cell::Cell;
+//! use std::ptr::NonNull;
+//!
+//! /// Counts the allocations it makes.
+//! #[derive(Default)]
+//! struct Counting {
+//!   live: Cell<usize>,
+//! }
+//!
+//! unsafe impl Allocator for Counting {
+//!   fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+//!     self.live.set(self.live.get() + 1);
+//!     Global.allocate(layout)
+//!   }
+//!
+//!   unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+//!     self.live.set(self.live.get() - 1);
+//!     Global.deallocate(ptr, layout)
+//!   }
+//! }
+//!
+//! let counting = Counting::default();
+//! let five = Rc::new_in(5, &counting);
+//! let also_five = Rc::clone(&five);
+//! assert_eq!(counting.live.get(), 1);
+//!
+//! drop((five, also_five));
+//! assert_eq!(counting.live.get(), 0);
+//! ```
+//!
+//! [`Wiping`] wraps an allocator so that each block is overwritten with zeros before it is freed,
+//! which is what [`SecretRc`] uses for key material.
+//!
+//! [`Rc`]: crate::Rc
+//! [`Arc`]: crate::sync::Arc
+//! [`SecretRc`]: crate::SecretRc
+
+use std::alloc::Layout;
+use std::ptr::NonNull;
+
+/// An allocator that backs the allocations of [`Rc`] and [`Arc`].
+///
+/// # Safety
+///
+/// A block returned by [`allocate`] must fit `layout` and stay valid until it
+/// is passed to [`deallocate`], on this allocator or on any copy of it made
+/// by `Clone`. Moving the allocator must not invalidate its blocks.
+///
+/// [`Rc`]: crate::Rc
+/// [`Arc`]: crate::sync::Arc
+/// [`allocate`]: Allocator::allocate
+/// [`deallocate`]: Allocator::deallocate
+pub unsafe trait Allocator {
+  /// Allocates a block of memory that fits `layout`.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`AllocError`] if the memory is exhausted or `layout` isn't
+  /// supported.
+  fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError>;
+
+  /// Deallocates the block at `ptr`.
+  ///
+  /// # Safety
+  ///
+  /// `ptr` must have been returned by [`allocate`] on this allocator with
+  /// the same `layout`, and not deallocated since.
+  ///
+  /// [`allocate`]: Allocator::allocate
+  unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+}
+
+unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+  #[inline]
+  fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+    (**self).allocate(layout)
+  }
+
+  #[inline]
+  unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+    (**self).deallocate(ptr, layout)
+  }
+}
+
+/// The global memory allocator, as used by [`std::alloc::alloc`].
+///
+/// This is the default allocator of [`Rc`] and [`Arc`].
+///
+/// [`Rc`]: crate::Rc
+/// [`Arc`]: crate::sync::Arc
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Global;
+
+unsafe impl Allocator for Global {
+  #[inline]
+  fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+    if layout.size() == 0 {
+      // SAFETY: An alignment is never zero.
+      return Ok(unsafe {
+        NonNull::new_unchecked(std::ptr::without_provenance_mut(layout.align()))
+      });
+    }
+    // SAFETY: `layout` has a non-zero size.
+    NonNull::new(unsafe { std::alloc::alloc(layout) }).ok_or(AllocError)
+  }
+
+  #[inline]
+  unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+    if layout.size() != 0 {
+      std::alloc::dealloc(ptr.as_ptr(), layout)
+    }
+  }
+}
+
+/// An allocator that overwrites each block with zeros before handing it back
+/// to `A`.
+///
+/// This is the allocator of [`SecretRc`], for values such as key material
+/// that shouldn't be left behind in freed memory. Only the block itself is
+/// wiped: the counts and the bytes of the value, after the value has been
+/// dropped. Memory that the value owns, like the buffer of a `Vec`, must be
+/// wiped by the value's own `Drop`.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::alloc::{Global, Wiping};
+/// use pointer::Rc;
+///
+/// let key = Rc::new_in([0x5au8; 32], Wiping::new(Global));
+/// assert_eq!(key[0], 0x5a);
+/// ```
+///
+/// [`SecretRc`]: crate::SecretRc
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Wiping<A = Global> {
+  alloc: A,
+}
+
+impl<A> Wiping<A> {
+  /// Wraps `alloc`, so that its blocks are wiped before they are
+  /// deallocated.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::alloc::{Global, Wiping};
+  ///
+  /// let wiping = Wiping::new(Global);
+  /// ```
+  pub const fn new(alloc: A) -> Wiping<A> {
+    Wiping { alloc }
+  }
+}
+
+unsafe impl<A: Allocator> Allocator for Wiping<A> {
+  #[inline]
+  fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+    self.alloc.allocate(layout)
+  }
+
+  unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+    // Volatile writes can't be left out as dead stores to memory that is
+    // about to be freed, and the fence keeps them before the deallocation.
+    for i in 0..layout.size() {
+      ptr.as_ptr().add(i).write_volatile(0);
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    self.alloc.deallocate(ptr, layout)
+  }
+}
+
+/// The error returned when an [`Allocator`] fails to allocate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocError;
+
+impl std::fmt::Display for AllocError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("memory allocation failed")
+  }
+}
+
+impl std::error::Error for AllocError {}
+
+/// Allocates memory for `layout` from `alloc`, and aborts through
+/// [`std::alloc::handle_alloc_error`] if it fails.
+pub(crate) fn allocate_or_abort<A: Allocator + ?Sized>(
+  alloc: &A,
+  layout: Layout,
+) -> NonNull<u8> {
+  match alloc.allocate(layout) {
+    Ok(ptr) => ptr,
+    Err(AllocError) => std::alloc::handle_alloc_error(layout),
+  }
+}
+
+/// Aborts the process because a reference count would overflow.
+///
+/// This is outlined and cold so that the count increments which check for
+/// overflow stay small enough to inline.
+#[cold]
+#[inline(never)]
+pub(crate) fn abort_on_overflow() -> ! {
+  std::process::abort()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+
+  #[derive(Default)]
+  struct Counting {
+    live: AtomicUsize,
+  }
+
+  unsafe impl Allocator for Counting {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+      self.live.fetch_add(1, SeqCst);
+      Global.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+      self.live.fetch_sub(1, SeqCst);
+      Global.deallocate(ptr, layout)
+    }
+  }
+
+  #[test]
+  fn rc_weak_keeps_allocation() {
+    let counting = Counting::default();
+    let mut rc = crate::Rc::new_in(String::from("a"), &counting);
+    let weak = crate::Rc::downgrade(&rc);
+
+    // Moving the value out of the shared allocation makes a new one.
+    crate::Rc::make_mut(&mut rc).push('b');
+    assert_eq!(counting.live.load(SeqCst), 2);
+    assert!(weak.upgrade().is_none());
+
+    drop(rc);
+    assert_eq!(counting.live.load(SeqCst), 1);
+    drop(weak);
+    assert_eq!(counting.live.load(SeqCst), 0);
+  }
+
+  #[test]
+  fn wiping_zeroes_blocks_before_deallocating() {
+    /// Checks that each block is all zeros when it is deallocated.
+    #[derive(Default)]
+    struct Checking {
+      wiped: AtomicUsize,
+    }
+
+    unsafe impl Allocator for Checking {
+      fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        Global.allocate(layout)
+      }
+
+      unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let block = std::slice::from_raw_parts(ptr.as_ptr(), layout.size());
+        if block.iter().all(|&b| b == 0) {
+          self.wiped.fetch_add(1, SeqCst);
+        }
+        Global.deallocate(ptr, layout)
+      }
+    }
+
+    let checking = Checking::default();
+    let key = crate::Rc::new_in([0xa5u8; 32], Wiping::new(&checking));
+    let weak = crate::Rc::downgrade(&key);
+    drop(key);
+    assert_eq!(checking.wiped.load(SeqCst), 0);
+    drop(weak);
+    assert_eq!(checking.wiped.load(SeqCst), 1);
+  }
+
+  #[cfg(not(loom))]
+  #[test]
+  fn arc_across_threads() {
+    let counting = Counting::default();
+    let arc = crate::sync::Arc::new_in(vec![1, 2, 3], &counting);
+    std::thread::scope(|s| {
+      for _ in 0..4 {
+        let arc = crate::sync::Arc::clone(&arc);
+        s.spawn(move || assert_eq!(arc.iter().sum::<i32>(), 6));
+      }
+    });
+    assert_eq!(crate::sync::Arc::try_unwrap(arc).unwrap(), [1, 2, 3]);
+    assert_eq!(counting.live.load(SeqCst), 0);
+  }
+}
@@ -0,0 +1,713 @@
+//! A generational arena: values owned by one collection and named by copyable handles.
+//!
+//! A graph built from [`Rc<RefCell<T>>`][Rc] nodes needs [`Weak`] back edges to avoid leaking
+//! cycles, and every traversal pays for reference counting and dynamic borrows. An [`Arena<T>`]
+//! owns all the nodes instead, and the edges are [`Handle<T>`]s: plain `Copy` keys that can point
+//! anywhere, cycles included, because they own nothing. Mutation goes through `&mut Arena`, so the
+//! borrow checker replaces the run-time borrow tracking of [`RefCell`].
+//!
+//! Each slot has a generation that is bumped when its value is removed. A handle remembers the
+//! generation it was made with, so a handle to a removed value stays detectably stale, even after
+//! its slot is reused: [`get`] returns [`None`] for it rather than some other value.
+//!
+//! ```
+//! use pointer::arena::{Arena, Handle};
+//!
+//! struct Node {
+//!   name: &'static str,
+//!   next: Option<Handle<Node>>,
+//! }
+//!
+//! let mut nodes = Arena::new();
+//! let a = nodes.insert(Node { name: "a", next: None });
+//! let b = nodes.insert(Node { name: "b", next: Some(a) });
+//! nodes[a].next = Some(b); // A cycle, and nothing leaks.
+//!
+//! let next = nodes[a].next.unwrap();
+//! assert_eq!(nodes[next].name, "b");
+//!
+//! nodes.remove(b);
+//! assert!(nodes.get(next).is_none());
+//! ```
+//!
+//! [Rc]: crate::Rc
+//! [`Weak`]: crate::Weak
+//! [`RefCell`]: crate::RefCell
+//! [`get`]: Arena::get
+
+/// A collection of values addressed by generational [`Handle`]s.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct Arena<T> {
+  slots: Vec<Slot<T>>,
+  /// The index of the first vacant slot, if any.
+  free_head: Option<usize>,
+  len: usize,
+}
+
+struct Slot<T> {
+  generation: u64,
+  entry: Entry<T>,
+}
+
+impl<T> Slot<T> {
+  /// Returns the value, if the slot holds one of `generation`.
+  fn value_mut(&mut self, generation: u64) -> Option<&mut T> {
+    match &mut self.entry {
+      Entry::Occupied(value) if self.generation == generation => Some(value),
+      _ => None,
+    }
+  }
+}
+
+enum Entry<T> {
+  Occupied(T),
+  Vacant { next_free: Option<usize> },
+}
+
+/// A key for a value in an [`Arena<T>`][Arena].
+///
+/// Handles are `Copy` and compare by identity. A handle whose value has been
+/// removed is stale: looking it up gives [`None`], whatever has been inserted
+/// since.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct Handle<T> {
+  index: usize,
+  generation: u64,
+  phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+  /// Returns the slot index of the handle in its arena.
+  ///
+  /// Indices are reused after removals, so they only identify a value
+  /// together with the generation the handle carries.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let mut arena = Arena::new();
+  /// let first = arena.insert('a');
+  /// assert_eq!(first.index(), 0);
+  /// ```
+  pub fn index(self) -> usize {
+    self.index
+  }
+}
+
+impl<T> Clone for Handle<T> {
+  fn clone(&self) -> Handle<T> {
+    *self
+  }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+  fn eq(&self, other: &Handle<T>) -> bool {
+    self.index == other.index && self.generation == other.generation
+  }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> PartialOrd for Handle<T> {
+  fn partial_cmp(&self, other: &Handle<T>) -> Option<std::cmp::Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl<T> Ord for Handle<T> {
+  fn cmp(&self, other: &Handle<T>) -> std::cmp::Ordering {
+    (self.index, self.generation).cmp(&(other.index, other.generation))
+  }
+}
+
+impl<T> std::hash::Hash for Handle<T> {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    self.index.hash(state);
+    self.generation.hash(state);
+  }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Handle({}v{})", self.index, self.generation)
+  }
+}
+
+impl<T> Arena<T> {
+  /// Constructs a new, empty `Arena`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let arena: Arena<i32> = Arena::new();
+  /// assert!(arena.is_empty());
+  /// ```
+  pub const fn new() -> Arena<T> {
+    Arena {
+      slots: Vec::new(),
+      free_head: None,
+      len: 0,
+    }
+  }
+
+  /// Constructs a new, empty `Arena` with room for `capacity` values.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let arena: Arena<i32> = Arena::with_capacity(10);
+  /// assert!(arena.capacity() >= 10);
+  /// ```
+  pub fn with_capacity(capacity: usize) -> Arena<T> {
+    Arena {
+      slots: Vec::with_capacity(capacity),
+      free_head: None,
+      len: 0,
+    }
+  }
+
+  /// Returns the number of values the arena can hold without reallocating.
+  pub fn capacity(&self) -> usize {
+    self.slots.capacity()
+  }
+
+  /// Returns the number of values in the arena.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let mut arena = Arena::new();
+  /// arena.insert(1);
+  /// assert_eq!(arena.len(), 1);
+  /// ```
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Returns `true` if the arena holds no values.
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Inserts `value`, reusing a vacant slot if there is one, and returns its
+  /// handle.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let mut arena = Arena::new();
+  /// let five = arena.insert(5);
+  /// assert_eq!(arena[five], 5);
+  /// ```
+  pub fn insert(&mut self, value: T) -> Handle<T> {
+    self.insert_with(|_| value)
+  }
+
+  /// Inserts the value returned by `f`, which is given the value's own
+  /// handle.
+  ///
+  /// This is how a value can refer to itself.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::arena::{Arena, Handle};
+  ///
+  /// struct Node {
+  ///   me: Handle<Node>,
+  /// }
+  ///
+  /// let mut arena = Arena::new();
+  /// let node = arena.insert_with(|me| Node { me });
+  /// assert_eq!(arena[node].me, node);
+  /// ```
+  pub fn insert_with(&mut self, f: impl FnOnce(Handle<T>) -> T) -> Handle<T> {
+    let index = match self.free_head {
+      Some(index) => index,
+      None => {
+        self.slots.push(Slot {
+          generation: 0,
+          entry: Entry::Vacant { next_free: None },
+        });
+        self.free_head = Some(self.slots.len() - 1);
+        self.slots.len() - 1
+      }
+    };
+    let handle = Handle {
+      index,
+      generation: self.slots[index].generation,
+      phantom: std::marker::PhantomData,
+    };
+    // Only take the slot once `f` has returned, so a panic leaves the arena
+    // as it was.
+    let value = f(handle);
+    let slot = &mut self.slots[index];
+    if let Entry::Vacant { next_free } = slot.entry {
+      self.free_head = next_free;
+    }
+    slot.entry = Entry::Occupied(value);
+    self.len += 1;
+    handle
+  }
+
+  /// Removes the value of `handle` and returns it, or [`None`] if the handle
+  /// is stale.
+  ///
+  /// Every handle to the value becomes stale.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let mut arena = Arena::new();
+  /// let five = arena.insert(5);
+  /// assert_eq!(arena.remove(five), Some(5));
+  /// assert_eq!(arena.remove(five), None);
+  /// ```
+  pub fn remove(&mut self, handle: Handle<T>) -> Option<T> {
+    if !self.contains(handle) {
+      return None;
+    }
+    let slot = &mut self.slots[handle.index];
+    slot.generation += 1;
+    let entry = std::mem::replace(
+      &mut slot.entry,
+      Entry::Vacant {
+        next_free: self.free_head,
+      },
+    );
+    self.free_head = Some(handle.index);
+    self.len -= 1;
+    match entry {
+      Entry::Occupied(value) => Some(value),
+      Entry::Vacant { .. } => unreachable!("`contains` checked the slot"),
+    }
+  }
+
+  /// Returns `true` if `handle` refers to a value in the arena, i.e. it
+  /// isn't stale.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let mut arena = Arena::new();
+  /// let five = arena.insert(5);
+  /// assert!(arena.contains(five));
+  ///
+  /// arena.remove(five);
+  /// assert!(!arena.contains(five));
+  /// ```
+  pub fn contains(&self, handle: Handle<T>) -> bool {
+    self.get(handle).is_some()
+  }
+
+  /// Returns a reference to the value of `handle`, or [`None`] if the handle
+  /// is stale.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let mut arena = Arena::new();
+  /// let five = arena.insert(5);
+  /// assert_eq!(arena.get(five), Some(&5));
+  /// ```
+  pub fn get(&self, handle: Handle<T>) -> Option<&T> {
+    match self.slots.get(handle.index)? {
+      Slot {
+        generation,
+        entry: Entry::Occupied(value),
+      } if *generation == handle.generation => Some(value),
+      _ => None,
+    }
+  }
+
+  /// Returns a mutable reference to the value of `handle`, or [`None`] if the
+  /// handle is stale.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let mut arena = Arena::new();
+  /// let five = arena.insert(5);
+  /// *arena.get_mut(five).unwrap() += 1;
+  /// assert_eq!(arena[five], 6);
+  /// ```
+  pub fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+    self
+      .slots
+      .get_mut(handle.index)?
+      .value_mut(handle.generation)
+  }
+
+  /// Returns mutable references to the values of two handles at once.
+  ///
+  /// Either is [`None`] if its handle is stale.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the two handles are equal.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let mut arena = Arena::new();
+  /// let a = arena.insert(1);
+  /// let b = arena.insert(2);
+  ///
+  /// if let (Some(a), Some(b)) = arena.get2_mut(a, b) {
+  ///   std::mem::swap(a, b);
+  /// }
+  /// assert_eq!((arena[a], arena[b]), (2, 1));
+  /// ```
+  #[track_caller]
+  pub fn get2_mut(
+    &mut self,
+    a: Handle<T>,
+    b: Handle<T>,
+  ) -> (Option<&mut T>, Option<&mut T>) {
+    if a.index == b.index {
+      assert!(a != b, "`get2_mut` called with the same handle twice");
+      // At most one of them can be current.
+      return if self.contains(a) {
+        (self.get_mut(a), None)
+      } else {
+        (None, self.get_mut(b))
+      };
+    }
+    let (low, high) = if a.index < b.index { (a, b) } else { (b, a) };
+    let (head, tail) = self.slots.split_at_mut(high.index);
+    let low_value = head[low.index].value_mut(low.generation);
+    let high_value = tail[0].value_mut(high.generation);
+    if a.index < b.index {
+      (low_value, high_value)
+    } else {
+      (high_value, low_value)
+    }
+  }
+
+  /// Removes every value, making all existing handles stale.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let mut arena = Arena::new();
+  /// let five = arena.insert(5);
+  /// arena.clear();
+  /// assert!(arena.is_empty());
+  /// assert!(arena.get(five).is_none());
+  /// ```
+  pub fn clear(&mut self) {
+    self.retain(|_, _| false);
+  }
+
+  /// Keeps only the values for which `f` returns `true`, removing the rest.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let mut arena = Arena::new();
+  /// for n in 0..6 {
+  ///   arena.insert(n);
+  /// }
+  /// arena.retain(|_, n| *n % 2 == 0);
+  /// assert_eq!(arena.values().copied().collect::<Vec<_>>(), [0, 2, 4]);
+  /// ```
+  pub fn retain(&mut self, mut f: impl FnMut(Handle<T>, &mut T) -> bool) {
+    for index in 0..self.slots.len() {
+      let slot = &mut self.slots[index];
+      let handle = Handle {
+        index,
+        generation: slot.generation,
+        phantom: std::marker::PhantomData,
+      };
+      if let Entry::Occupied(value) = &mut slot.entry {
+        if !f(handle, value) {
+          self.remove(handle);
+        }
+      }
+    }
+  }
+
+  /// Returns an iterator over the handles and values, in slot order.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let mut arena = Arena::new();
+  /// let a = arena.insert('a');
+  /// let b = arena.insert('b');
+  ///
+  /// let items: Vec<_> = arena.iter().collect();
+  /// assert_eq!(items, [(a, &'a'), (b, &'b')]);
+  /// ```
+  pub fn iter(&self) -> Iter<'_, T> {
+    Iter {
+      slots: self.slots.iter().enumerate(),
+      len: self.len,
+    }
+  }
+
+  /// Returns an iterator over the handles and mutable values, in slot order.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Arena;
+  ///
+  /// let mut arena = Arena::new();
+  /// arena.insert(1);
+  /// arena.insert(2);
+  ///
+  /// for (_, n) in arena.iter_mut() {
+  ///   *n *= 10;
+  /// }
+  /// assert_eq!(arena.values().sum::<i32>(), 30);
+  /// ```
+  pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+    IterMut {
+      slots: self.slots.iter_mut().enumerate(),
+      len: self.len,
+    }
+  }
+
+  /// Returns an iterator over the handles, in slot order.
+  pub fn handles(&self) -> impl Iterator<Item = Handle<T>> + '_ {
+    self.iter().map(|(handle, _)| handle)
+  }
+
+  /// Returns an iterator over the values, in slot order.
+  pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+    self.iter().map(|(_, value)| value)
+  }
+}
+
+impl<T> Default for Arena<T> {
+  fn default() -> Arena<T> {
+    Arena::new()
+  }
+}
+
+impl<T> std::ops::Index<Handle<T>> for Arena<T> {
+  type Output = T;
+
+  /// Returns a reference to the value of `handle`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the handle is stale.
+  #[track_caller]
+  fn index(&self, handle: Handle<T>) -> &T {
+    match self.get(handle) {
+      Some(value) => value,
+      None => panic!("stale arena handle {:?}", handle),
+    }
+  }
+}
+
+impl<T> std::ops::IndexMut<Handle<T>> for Arena<T> {
+  /// Returns a mutable reference to the value of `handle`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the handle is stale.
+  #[track_caller]
+  fn index_mut(&mut self, handle: Handle<T>) -> &mut T {
+    match self.get_mut(handle) {
+      Some(value) => value,
+      None => panic!("stale arena handle {:?}", handle),
+    }
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Arena<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_map().entries(self.iter()).finish()
+  }
+}
+
+impl<T> std::iter::FromIterator<T> for Arena<T> {
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Arena<T> {
+    let mut arena = Arena::new();
+    arena.extend(iter);
+    arena
+  }
+}
+
+impl<T> Extend<T> for Arena<T> {
+  fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+    for value in iter {
+      self.insert(value);
+    }
+  }
+}
+
+impl<'a, T> IntoIterator for &'a Arena<T> {
+  type Item = (Handle<T>, &'a T);
+  type IntoIter = Iter<'a, T>;
+
+  fn into_iter(self) -> Iter<'a, T> {
+    self.iter()
+  }
+}
+
+impl<'a, T> IntoIterator for &'a mut Arena<T> {
+  type Item = (Handle<T>, &'a mut T);
+  type IntoIter = IterMut<'a, T>;
+
+  fn into_iter(self) -> IterMut<'a, T> {
+    self.iter_mut()
+  }
+}
+
+/// An iterator over the handles and values of an [`Arena`].
+///
+/// This is created by [`Arena::iter`].
+pub struct Iter<'a, T> {
+  slots: std::iter::Enumerate<std::slice::Iter<'a, Slot<T>>>,
+  /// The number of values left.
+  len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+  type Item = (Handle<T>, &'a T);
+
+  fn next(&mut self) -> Option<(Handle<T>, &'a T)> {
+    for (index, slot) in &mut self.slots {
+      if let Entry::Occupied(value) = &slot.entry {
+        self.len -= 1;
+        let handle = Handle {
+          index,
+          generation: slot.generation,
+          phantom: std::marker::PhantomData,
+        };
+        return Some((handle, value));
+      }
+    }
+    None
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.len, Some(self.len))
+  }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> std::iter::FusedIterator for Iter<'_, T> {}
+
+/// An iterator over the handles and mutable values of an [`Arena`].
+///
+/// This is created by [`Arena::iter_mut`].
+pub struct IterMut<'a, T> {
+  slots: std::iter::Enumerate<std::slice::IterMut<'a, Slot<T>>>,
+  /// The number of values left.
+  len: usize,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+  type Item = (Handle<T>, &'a mut T);
+
+  fn next(&mut self) -> Option<(Handle<T>, &'a mut T)> {
+    for (index, slot) in &mut self.slots {
+      if let Entry::Occupied(value) = &mut slot.entry {
+        self.len -= 1;
+        let handle = Handle {
+          index,
+          generation: slot.generation,
+          phantom: std::marker::PhantomData,
+        };
+        return Some((handle, value));
+      }
+    }
+    None
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.len, Some(self.len))
+  }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+
+impl<T> std::iter::FusedIterator for IterMut<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reused_slot_keeps_old_handles_stale() {
+    let mut arena = Arena::new();
+    let a = arena.insert("a");
+    let b = arena.insert("b");
+    assert_eq!(arena.remove(a), Some("a"));
+
+    let c = arena.insert("c");
+    assert_eq!(c.index(), a.index());
+    assert_ne!(c, a);
+    assert_eq!(arena.get(a), None);
+    assert_eq!(arena[c], "c");
+    assert_eq!(arena.len(), 2);
+
+    arena.clear();
+    assert!(!arena.contains(b) && !arena.contains(c));
+    let d = arena.insert("d");
+    assert!(arena.get(b).is_none() && arena.get(c).is_none());
+    assert_eq!(arena.iter().collect::<Vec<_>>(), [(d, &"d")]);
+  }
+
+  #[test]
+  fn get2_mut_with_stale_and_reused_handles() {
+    let mut arena = Arena::new();
+    let a = arena.insert(1);
+    let b = arena.insert(2);
+    assert_eq!(arena.get2_mut(b, a), (Some(&mut 2), Some(&mut 1)));
+
+    arena.remove(a);
+    let c = arena.insert(3);
+    assert_eq!(arena.get2_mut(a, c), (None, Some(&mut 3)));
+    assert_eq!(arena.get2_mut(c, a), (Some(&mut 3), None));
+  }
+
+  #[test]
+  fn panicking_insert_with_leaves_arena_unchanged() {
+    let mut arena = Arena::new();
+    let a = arena.insert(1);
+    arena.remove(a);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      arena.insert_with(|_| panic!("no value"))
+    }));
+    assert!(result.is_err());
+    assert!(arena.is_empty());
+    assert_eq!(arena.insert(2).index(), a.index());
+  }
+}
@@ -0,0 +1,339 @@
+//! Coordination for single-threaded async code: [`Notify`].
+//!
+//! A [`Notify`] lets one task wait until another tells it to go on, without a value to go with it:
+//! a task awaits [`notified`], and another calls [`notify_one`] to wake the task that has waited
+//! longest, or [`notify_waiters`] to wake every task waiting. If `notify_one` finds no task
+//! waiting, it leaves a permit, so the next `notified` completes at once and the notification
+//! isn't lost. A task that gets the notification of `notify_one`, and then drops its future
+//! without having seen it, passes the notification on to the next task.
+//!
+//! As with the [`watch`] channel, everything here lives on one thread: the waiting tasks are kept
+//! in a [`RefCell`] and woken from the call that notifies them. The locks in [`sync`] do the same
+//! work between threads.
+//!
+//! ```
+//! use pointer::async_local::Notify;
+//!
+//! async fn consume(ready: &Notify, queue: &pointer::RefCell<Vec<u32>>) -> u32 {
+//!   loop {
+//!     if let Some(item) = queue.borrow_mut().pop() {
+//!       return item;
+//!     }
+//!     ready.notified().await;
+//!   }
+//! }
+//!
+//! let ready = Notify::new();
+//! let queue = pointer::RefCell::new(Vec::new());
+//! let _consumer = consume(&ready, &queue);
+//! queue.borrow_mut().push(1);
+//! ready.notify_one();
+//! ```
+//!
+//! [`notified`]: Notify::notified
+//! [`notify_one`]: Notify::notify_one
+//! [`notify_waiters`]: Notify::notify_waiters
+//! [`watch`]: crate::watch
+//! [`RefCell`]: crate::RefCell
+//! [`sync`]: crate::sync
+
+use crate::{Cell, Rc, RefCell};
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+/// A task waiting on a [`Notify`].
+struct Waiter {
+  waker: RefCell<Option<Waker>>,
+  /// Whether [`Notify::notify_one`] chose this waiter.
+  notified: Cell<bool>,
+}
+
+impl Waiter {
+  fn wake(&self) {
+    let waker = self.waker.borrow_mut().take();
+    if let Some(waker) = waker {
+      waker.wake();
+    }
+  }
+}
+
+/// Wakes waiting tasks when told to.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct Notify {
+  /// The waiting tasks, the longest waiting first.
+  waiters: RefCell<VecDeque<Rc<Waiter>>>,
+  /// Whether a [`notify_one`](Notify::notify_one) found no one to wake.
+  permit: Cell<bool>,
+  /// How many times [`notify_waiters`](Notify::notify_waiters) was called.
+  generation: Cell<u64>,
+}
+
+impl Notify {
+  /// Creates a `Notify` with no waiting tasks and no permit.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::async_local::Notify;
+  ///
+  /// let notify = Notify::new();
+  /// assert_eq!(notify.waiter_count(), 0);
+  /// ```
+  pub fn new() -> Notify {
+    Notify {
+      waiters: RefCell::new(VecDeque::new()),
+      permit: Cell::new(false),
+      generation: Cell::new(0),
+    }
+  }
+
+  /// Returns a future that completes once the task is notified.
+  ///
+  /// The future completes at once if there is a permit, which it uses up.
+  /// Otherwise it joins the queue of waiting tasks when it's first polled.
+  /// It also completes for a [`notify_waiters`](Notify::notify_waiters)
+  /// called after this call, even if that was before the future was polled.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::async_local::Notify;
+  ///
+  /// async fn wait_for_shutdown(shutdown: &Notify) {
+  ///   shutdown.notified().await;
+  ///   println!("shutting down");
+  /// }
+  ///
+  /// let shutdown = Notify::new();
+  /// let _task = wait_for_shutdown(&shutdown);
+  /// ```
+  pub fn notified(&self) -> Notified<'_> {
+    Notified {
+      notify: self,
+      generation: self.generation.get(),
+      waiter: None,
+      done: false,
+    }
+  }
+
+  /// Wakes the task that has waited longest, or leaves a permit for the
+  /// next [`notified`](Notify::notified) if no task is waiting.
+  ///
+  /// There is at most one permit: notifying twice with no task waiting lets
+  /// only one `notified` through.
+  pub fn notify_one(&self) {
+    let waiter = self.waiters.borrow_mut().pop_front();
+    match waiter {
+      Some(waiter) => {
+        waiter.notified.set(true);
+        waiter.wake();
+      }
+      None => self.permit.set(true),
+    }
+  }
+
+  /// Wakes every waiting task, without leaving a permit.
+  pub fn notify_waiters(&self) {
+    self.generation.set(self.generation.get() + 1);
+    let waiters = std::mem::take(&mut *self.waiters.borrow_mut());
+    for waiter in waiters {
+      waiter.wake();
+    }
+  }
+
+  /// Returns the number of tasks waiting.
+  pub fn waiter_count(&self) -> usize {
+    self.waiters.borrow().len()
+  }
+}
+
+impl Default for Notify {
+  fn default() -> Notify {
+    Notify::new()
+  }
+}
+
+impl std::fmt::Debug for Notify {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.waiters.try_borrow() {
+      Ok(waiters) => f
+        .debug_struct("Notify")
+        .field("waiters", &waiters.len())
+        .field("permit", &self.permit.get())
+        .finish(),
+      Err(_) => f.write_str("Notify { <borrowed> }"),
+    }
+  }
+}
+
+/// The future returned by [`Notify::notified`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Notified<'a> {
+  notify: &'a Notify,
+  /// The generation of the `Notify` when the future was made.
+  generation: u64,
+  /// The future's place in the queue, once it waits.
+  waiter: Option<Rc<Waiter>>,
+  done: bool,
+}
+
+impl Notified<'_> {
+  /// Takes the future's waiter out of the queue.
+  fn leave(&mut self) {
+    if let Some(waiter) = self.waiter.take() {
+      let mut waiters = self.notify.waiters.borrow_mut();
+      waiters.retain(|w| !Rc::ptr_eq(w, &waiter));
+    }
+  }
+}
+
+impl Future for Notified<'_> {
+  type Output = ();
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+    let this = self.get_mut();
+    if this.done {
+      return Poll::Ready(());
+    }
+    let chosen = this.waiter.as_ref().is_some_and(|w| w.notified.get());
+    if chosen || this.notify.generation.get() != this.generation {
+      this.leave();
+      this.done = true;
+      return Poll::Ready(());
+    }
+    match &this.waiter {
+      Some(waiter) => {
+        let mut waker = waiter.waker.borrow_mut();
+        if !waker.as_ref().is_some_and(|w| w.will_wake(cx.waker())) {
+          *waker = Some(cx.waker().clone());
+        }
+      }
+      None => {
+        if this.notify.permit.replace(false) {
+          this.done = true;
+          return Poll::Ready(());
+        }
+        let waiter = Rc::new(Waiter {
+          waker: RefCell::new(Some(cx.waker().clone())),
+          notified: Cell::new(false),
+        });
+        this
+          .notify
+          .waiters
+          .borrow_mut()
+          .push_back(Rc::clone(&waiter));
+        this.waiter = Some(waiter);
+      }
+    }
+    Poll::Pending
+  }
+}
+
+impl Drop for Notified<'_> {
+  fn drop(&mut self) {
+    if self.done {
+      return;
+    }
+    let chosen = self.waiter.as_ref().is_some_and(|w| w.notified.get());
+    if chosen {
+      // The notification was meant for a task that no longer waits.
+      self.waiter = None;
+      self.notify.notify_one();
+    } else {
+      self.leave();
+    }
+  }
+}
+
+impl std::fmt::Debug for Notified<'_> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Notified")
+      .field("waiting", &self.waiter.is_some())
+      .finish()
+  }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+  use crate::watch::{counting, wakes};
+
+  #[test]
+  fn notify_one_wakes_the_longest_waiting() {
+    let notify = Notify::new();
+    let (first_counter, first_waker) = counting();
+    let (second_counter, second_waker) = counting();
+    let mut first_cx = Context::from_waker(&first_waker);
+    let mut second_cx = Context::from_waker(&second_waker);
+
+    let mut first = std::pin::pin!(notify.notified());
+    let mut second = std::pin::pin!(notify.notified());
+    assert!(first.as_mut().poll(&mut first_cx).is_pending());
+    assert!(second.as_mut().poll(&mut second_cx).is_pending());
+    assert_eq!(notify.waiter_count(), 2);
+
+    notify.notify_one();
+    assert_eq!((wakes(&first_counter), wakes(&second_counter)), (1, 0));
+    assert!(first.as_mut().poll(&mut first_cx).is_ready());
+    assert!(second.as_mut().poll(&mut second_cx).is_pending());
+    assert_eq!(notify.waiter_count(), 1);
+  }
+
+  #[test]
+  fn an_unclaimed_notification_leaves_one_permit() {
+    let notify = Notify::new();
+    notify.notify_one();
+    notify.notify_one();
+    let (_, waker) = counting();
+    let mut cx = Context::from_waker(&waker);
+
+    assert!(std::pin::pin!(notify.notified()).poll(&mut cx).is_ready());
+    assert!(std::pin::pin!(notify.notified()).poll(&mut cx).is_pending());
+    // The pending future left the queue when it was dropped.
+    assert_eq!(notify.waiter_count(), 0);
+
+    // `notify_waiters` leaves no permit.
+    notify.notify_waiters();
+    assert!(std::pin::pin!(notify.notified()).poll(&mut cx).is_pending());
+  }
+
+  #[test]
+  fn notify_waiters_wakes_everyone_created_before_it() {
+    let notify = Notify::new();
+    let (counter, waker) = counting();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut waiting = std::pin::pin!(notify.notified());
+    assert!(waiting.as_mut().poll(&mut cx).is_pending());
+    // Not polled yet, but made before the notification.
+    let mut unpolled = std::pin::pin!(notify.notified());
+
+    notify.notify_waiters();
+    assert_eq!(wakes(&counter), 1);
+    assert!(waiting.as_mut().poll(&mut cx).is_ready());
+    assert!(unpolled.as_mut().poll(&mut cx).is_ready());
+    assert_eq!(notify.waiter_count(), 0);
+  }
+
+  #[test]
+  fn a_dropped_waiter_passes_its_notification_on() {
+    let notify = Notify::new();
+    let (counter, waker) = counting();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut second = std::pin::pin!(notify.notified());
+    {
+      let mut first = std::pin::pin!(notify.notified());
+      assert!(first.as_mut().poll(&mut cx).is_pending());
+      assert!(second.as_mut().poll(&mut cx).is_pending());
+      notify.notify_one();
+    }
+    // `first` was chosen, but dropped without completing.
+    assert_eq!(wakes(&counter), 2);
+    assert!(second.as_mut().poll(&mut cx).is_ready());
+  }
+}
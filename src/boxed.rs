@@ -0,0 +1,475 @@
+//! A pointer type for uniquely owned heap allocation.
+//!
+//! [`Boxed<T>`][Boxed] is the simplest smart pointer in the crate: it owns a single heap
+//! allocation holding a `T`, gives out `&T` and `&mut T` through [`Deref`] and [`DerefMut`], and
+//! frees the allocation when it is dropped. There is no reference count, so there is nothing to
+//! share. It is what [`Box<T>`] does, spelled out in the open.
+//!
+//! `T` may be unsized. On stable Rust the unsizing coercion only exists for the standard pointer
+//! types, so an unsized `Boxed` is made from a [`Box`] that has been coerced already, or from a
+//! [`Vec`] or [`String`]. The allocation is taken over as it is.
+//!
+//! ```
+//! use pointer::Boxed;
+//! use std::fmt::Display;
+//!
+//! let mut five = Boxed::new(5);
+//! *five += 1;
+//! assert_eq!(*five, 6);
+//!
+//! let shown: Boxed<dyn Display> = Boxed::from(Box::new(*five) as Box<dyn Display>);
+//! assert_eq!(shown.to_string(), "6");
+//! ```
+//!
+//! A `Boxed<T>` converts into an [`Rc<T>`][rc] or an [`Arc<T>`][arc], which is the way to make
+//! shared pointers to unsized values.
+//!
+//! [`Deref`]: std::ops::Deref
+//! [`DerefMut`]: std::ops::DerefMut
+//! [rc]: crate::Rc
+//! [arc]: crate::sync::Arc
+
+/// A pointer type that uniquely owns a heap allocation of type `T`.
+///
+/// See the [module-level documentation](./index.html) for more details.
+///
+/// The inherent methods of `Boxed` are associated functions, so they don't
+/// shadow methods of `T`: call them as e.g. [`Boxed::leak(b)`][leak].
+///
+/// # Memory layout
+///
+/// A `Boxed<T>` is a single non-null pointer to the value, so
+/// `Option<Boxed<T>>` is the same size as `Boxed<T>`. This is a guarantee.
+///
+/// ```
+/// use pointer::Boxed;
+/// use std::mem::size_of;
+///
+/// assert_eq!(size_of::<Option<Boxed<u64>>>(), size_of::<usize>());
+/// assert_eq!(size_of::<Option<Boxed<str>>>(), size_of::<Boxed<str>>());
+/// ```
+///
+/// [leak]: Boxed::leak
+pub struct Boxed<T: ?Sized> {
+  ptr: std::ptr::NonNull<T>,
+  // Tells dropck that we own a `T`.
+  phantom: std::marker::PhantomData<T>,
+}
+
+// The memory layout that the docs of `Boxed` guarantee.
+const _: () = {
+  use std::mem::size_of;
+  assert!(size_of::<Boxed<u8>>() == size_of::<usize>());
+  assert!(size_of::<Option<Boxed<u8>>>() == size_of::<usize>());
+  assert!(size_of::<Option<Boxed<[u8]>>>() == size_of::<Boxed<[u8]>>());
+  assert!(size_of::<Option<Boxed<dyn Fn()>>>() == size_of::<Boxed<dyn Fn()>>());
+};
+
+unsafe impl<T: ?Sized + Send> Send for Boxed<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for Boxed<T> {}
+
+// impl<T: ?Sized + std::marker::Unsize<U>, U: ?Sized> std::ops::CoerceUnsized<Boxed<U>> for Boxed<T> {}
+
+impl<T> Boxed<T> {
+  /// Allocates memory on the heap and then places `value` into it.
+  ///
+  /// Zero-sized values don't allocate.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Boxed;
+  ///
+  /// let five = Boxed::new(5);
+  /// ```
+  pub fn new(value: T) -> Boxed<T> {
+    let layout = std::alloc::Layout::new::<T>();
+    let ptr = if layout.size() == 0 {
+      std::ptr::NonNull::dangling()
+    } else {
+      // SAFETY: The layout has a non-zero size.
+      let mem = unsafe { std::alloc::alloc(layout) } as *mut T;
+      match std::ptr::NonNull::new(mem) {
+        Some(ptr) => ptr,
+        None => std::alloc::handle_alloc_error(layout),
+      }
+    };
+    // SAFETY: `ptr` is valid for writes of a `T`.
+    unsafe { ptr.as_ptr().write(value) };
+    Boxed::from_inner(ptr)
+  }
+
+  /// Moves the value out of the box, freeing the allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Boxed;
+  ///
+  /// let boxed = Boxed::new(String::from("hello"));
+  /// assert_eq!(Boxed::into_inner(boxed), "hello");
+  /// ```
+  pub fn into_inner(this: Self) -> T {
+    let ptr = Boxed::into_raw(this);
+    // SAFETY: `ptr` is valid and uniquely owned. The value is moved out
+    // before its memory is freed, and it isn't dropped in place.
+    unsafe {
+      let value = ptr.read();
+      dealloc(ptr);
+      value
+    }
+  }
+}
+
+impl<T: ?Sized> Boxed<T> {
+  fn from_inner(ptr: std::ptr::NonNull<T>) -> Self {
+    Boxed {
+      ptr,
+      phantom: std::marker::PhantomData,
+    }
+  }
+
+  /// Constructs a box from a raw pointer.
+  ///
+  /// # Safety
+  ///
+  /// `ptr` must come from [`Boxed::into_raw`] or [`Box::into_raw`], and must
+  /// not be used again afterwards except through the returned box.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Boxed;
+  ///
+  /// let ptr = Boxed::into_raw(Boxed::new(5));
+  /// let five = unsafe { Boxed::from_raw(ptr) };
+  /// assert_eq!(*five, 5);
+  /// ```
+  pub unsafe fn from_raw(ptr: *mut T) -> Boxed<T> {
+    Boxed::from_inner(std::ptr::NonNull::new_unchecked(ptr))
+  }
+
+  /// Consumes the box, returning the wrapped raw pointer.
+  ///
+  /// The caller becomes responsible for the value and its memory. Turn the
+  /// pointer back into a `Boxed` with [`Boxed::from_raw`] to free them.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Boxed;
+  ///
+  /// let ptr = Boxed::into_raw(Boxed::new(String::from("hello")));
+  /// assert_eq!(unsafe { &*ptr }, "hello");
+  /// # drop(unsafe { Boxed::from_raw(ptr) });
+  /// ```
+  pub fn into_raw(this: Self) -> *mut T {
+    let ptr = this.ptr.as_ptr();
+    std::mem::forget(this);
+    ptr
+  }
+
+  /// Consumes and leaks the box, returning a mutable reference that lives
+  /// for as long as the caller wants.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Boxed;
+  ///
+  /// let x: &'static mut usize = Boxed::leak(Boxed::new(41));
+  /// *x += 1;
+  /// assert_eq!(*x, 42);
+  /// ```
+  pub fn leak<'a>(this: Self) -> &'a mut T
+  where
+    T: 'a,
+  {
+    // SAFETY: The allocation is never freed, and nothing else points to it.
+    unsafe { &mut *Boxed::into_raw(this) }
+  }
+
+  /// Converts the box into a [`Box`] that owns the same allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Boxed;
+  ///
+  /// let slice: Boxed<[u8]> = Boxed::from(vec![1, 2]);
+  /// let boxed: Box<[u8]> = Boxed::into_box(slice);
+  /// assert_eq!(*boxed, [1, 2]);
+  /// ```
+  pub fn into_box(this: Self) -> Box<T> {
+    // SAFETY: `Boxed` allocates with the global allocator and the layout of
+    // the value, exactly like `Box`.
+    unsafe { Box::from_raw(Boxed::into_raw(this)) }
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for Boxed<T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    // SAFETY: The box owns a valid value for as long as it lives.
+    unsafe { self.ptr.as_ref() }
+  }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for Boxed<T> {
+  fn deref_mut(&mut self) -> &mut T {
+    // SAFETY: As above, and the value is only reachable through this box.
+    unsafe { self.ptr.as_mut() }
+  }
+}
+
+impl<T: ?Sized> Drop for Boxed<T> {
+  fn drop(&mut self) {
+    // SAFETY: The value is dropped once, then its memory is freed.
+    unsafe {
+      std::ptr::drop_in_place(self.ptr.as_ptr());
+      dealloc(self.ptr.as_ptr());
+    }
+  }
+}
+
+impl<T: Clone> Clone for Boxed<T> {
+  fn clone(&self) -> Boxed<T> {
+    Boxed::new((**self).clone())
+  }
+}
+
+impl<T: Default> Default for Boxed<T> {
+  fn default() -> Boxed<T> {
+    Boxed::new(Default::default())
+  }
+}
+
+impl<T: ?Sized + PartialEq> PartialEq for Boxed<T> {
+  fn eq(&self, other: &Boxed<T>) -> bool {
+    **self == **other
+  }
+}
+
+impl<T: ?Sized + Eq> Eq for Boxed<T> {}
+
+impl<T: ?Sized + std::hash::Hash> std::hash::Hash for Boxed<T> {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    (**self).hash(state)
+  }
+}
+
+impl<T> From<T> for Boxed<T> {
+  fn from(t: T) -> Boxed<T> {
+    Boxed::new(t)
+  }
+}
+
+impl<T: ?Sized> From<Box<T>> for Boxed<T> {
+  /// Takes over the allocation of a [`Box`], which may hold an unsized value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Boxed;
+  ///
+  /// let add: Boxed<dyn Fn(u32) -> u32> = Boxed::from(Box::new(|n: u32| n + 1) as Box<dyn Fn(u32) -> u32>);
+  /// assert_eq!(add(1), 2);
+  /// ```
+  fn from(b: Box<T>) -> Boxed<T> {
+    // SAFETY: `Box::into_raw` hands over a valid, uniquely owned value.
+    unsafe { Boxed::from_raw(Box::into_raw(b)) }
+  }
+}
+
+impl<T> From<Vec<T>> for Boxed<[T]> {
+  fn from(v: Vec<T>) -> Boxed<[T]> {
+    Boxed::from(v.into_boxed_slice())
+  }
+}
+
+impl From<String> for Boxed<str> {
+  fn from(s: String) -> Boxed<str> {
+    Boxed::from(s.into_boxed_str())
+  }
+}
+
+impl<T: ?Sized> std::borrow::Borrow<T> for Boxed<T> {
+  fn borrow(&self) -> &T {
+    self
+  }
+}
+
+impl<T: ?Sized> std::borrow::BorrowMut<T> for Boxed<T> {
+  fn borrow_mut(&mut self) -> &mut T {
+    self
+  }
+}
+
+impl<T: ?Sized> AsRef<T> for Boxed<T> {
+  fn as_ref(&self) -> &T {
+    self
+  }
+}
+
+impl<T: ?Sized> AsMut<T> for Boxed<T> {
+  fn as_mut(&mut self) -> &mut T {
+    self
+  }
+}
+
+// The value never moves while the box does.
+impl<T: ?Sized> Unpin for Boxed<T> {}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display for Boxed<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::error::Error> std::error::Error for Boxed<T> {
+  #[allow(deprecated)]
+  fn description(&self) -> &str {
+    std::error::Error::description(&**self)
+  }
+
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    std::error::Error::source(&**self)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for Boxed<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized> std::fmt::Pointer for Boxed<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Pointer::fmt(&self.ptr, f)
+  }
+}
+
+/// Frees the memory behind `ptr` without dropping the value.
+///
+/// # Safety
+///
+/// `ptr` must own an allocation made for the layout of its value, and the
+/// value must be dropped or moved out already (its metadata still has to be
+/// valid, to compute the layout).
+unsafe fn dealloc<T: ?Sized>(ptr: *mut T) {
+  let layout = std::alloc::Layout::for_value(&*ptr);
+  if layout.size() != 0 {
+    std::alloc::dealloc(ptr.cast::<u8>(), layout);
+  }
+}
+
+/// Replaces the address of a (possibly fat) pointer with `data`, keeping its
+/// metadata (slice length or vtable).
+pub(crate) fn set_data_ptr<T: ?Sized, U>(
+  mut ptr: *mut T,
+  data: *mut U,
+) -> *mut T {
+  // SAFETY: The data pointer is always the first word of a (fat) pointer, so
+  // this overwrites only the address and keeps the metadata.
+  unsafe {
+    std::ptr::addr_of_mut!(ptr)
+      .cast::<*mut u8>()
+      .write(data.cast::<u8>());
+  }
+  ptr
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Cell;
+
+  struct Droppable<'a>(&'a Cell<i32>);
+
+  impl Drop for Droppable<'_> {
+    fn drop(&mut self) {
+      self.0.set(self.0.get() + 1);
+    }
+  }
+
+  #[test]
+  fn new_and_mutate() {
+    let mut boxed = Boxed::new(vec![1]);
+    boxed.push(2);
+    assert_eq!(*boxed, [1, 2]);
+    assert_eq!(Boxed::into_inner(boxed), [1, 2]);
+  }
+
+  #[test]
+  fn drops_unsized_value_once() {
+    trait Marker {}
+    impl Marker for Droppable<'_> {}
+
+    let dropped = Cell::new(0);
+    {
+      let boxed: Boxed<dyn Marker> =
+        Boxed::from(Box::new(Droppable(&dropped)) as Box<dyn Marker>);
+      let _moved = boxed;
+    }
+    assert_eq!(dropped.get(), 1);
+
+    let slice: Boxed<[_]> =
+      Boxed::from(vec![Droppable(&dropped), Droppable(&dropped)]);
+    drop(slice);
+    assert_eq!(dropped.get(), 3);
+  }
+
+  #[test]
+  fn raw_round_trip_and_zero_sized() {
+    let unit = Boxed::new(());
+    let ptr = Boxed::into_raw(unit);
+    // SAFETY: `ptr` came from `into_raw` and is reclaimed once.
+    let unit = unsafe { Boxed::from_raw(ptr) };
+    assert_eq!(*unit, ());
+
+    let empty: Boxed<[u64]> = Boxed::from(Vec::new());
+    assert!(empty.is_empty());
+    let text: Boxed<str> = Boxed::from(String::from("hi"));
+    assert_eq!(&*Boxed::into_box(text), "hi");
+  }
+
+  #[test]
+  fn into_shared_pointers() {
+    let dropped = Cell::new(0);
+    let rc: crate::Rc<[Droppable<'_>]> =
+      crate::Rc::from(Boxed::from(vec![Droppable(&dropped)]));
+    assert_eq!(rc.len(), 1);
+    assert_eq!(dropped.get(), 0);
+    drop(rc);
+    assert_eq!(dropped.get(), 1);
+
+    let unit: crate::Rc<[()]> = crate::Rc::from(Boxed::from(vec![(); 3]));
+    assert_eq!(unit.len(), 3);
+
+    let text: crate::sync::Arc<str> =
+      crate::sync::Arc::from(Boxed::from(String::from("shared")));
+    assert_eq!(&*text, "shared");
+  }
+
+  #[test]
+  fn forwards_the_error_source() {
+    #[derive(Debug)]
+    struct Failed(crate::alloc::AllocError);
+    impl std::fmt::Display for Failed {
+      fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed")
+      }
+    }
+    impl std::error::Error for Failed {
+      fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+      }
+    }
+
+    let err: Box<dyn std::error::Error> =
+      Box::new(Boxed::new(Failed(crate::alloc::AllocError)));
+    assert_eq!(err.to_string(), "failed");
+    assert!(err.source().unwrap().is::<crate::alloc::AllocError>());
+  }
+}
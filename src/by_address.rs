@@ -0,0 +1,150 @@
+//! A wrapper that compares and hashes pointers by the address they point to.
+//!
+//! [`Rc`] and [`Arc`] compare by value, which is what most code wants but not what graph
+//! algorithms and observer registries want: two nodes holding equal values are still two nodes.
+//! [`ByAddress<P>`][ByAddress] wraps any pointer `P` that derefs to its value and gives it
+//! [`Eq`], [`Hash`] and [`Ord`] by the address of that value, so handles can key a
+//! [`HashMap`] or [`BTreeSet`] by identity. Two handles are equal exactly when
+//! [`Rc::ptr_eq`] would say so.
+//!
+//! ```
+//! use pointer::{ByAddress, Rc};
+//! use std::collections::HashSet;
+//!
+//! let a = Rc::new(5);
+//! let b = Rc::new(5);
+//!
+//! let mut seen = HashSet::new();
+//! assert!(seen.insert(ByAddress(Rc::clone(&a))));
+//! assert!(seen.insert(ByAddress(Rc::clone(&b))));
+//! assert!(!seen.insert(ByAddress(Rc::clone(&a))));
+//! assert_eq!(seen.len(), 2);
+//! ```
+//!
+//! For unsized values, only the address is compared, not the length or vtable.
+//!
+//! [`Rc`]: crate::Rc
+//! [`Arc`]: crate::sync::Arc
+//! [`Rc::ptr_eq`]: crate::Rc::ptr_eq
+//! [`HashMap`]: std::collections::HashMap
+//! [`BTreeSet`]: std::collections::BTreeSet
+
+use std::ops::Deref;
+
+/// A pointer that is compared, hashed and ordered by the address of its
+/// value.
+///
+/// See the [module-level documentation](./index.html) for more details.
+#[derive(Clone, Copy, Default)]
+pub struct ByAddress<P>(pub P);
+
+impl<P: Deref> ByAddress<P> {
+  /// Returns the address that the pointer is compared by.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{ByAddress, Rc};
+  ///
+  /// let five = Rc::new(5);
+  /// let key = ByAddress(Rc::clone(&five));
+  /// assert_eq!(key.addr(), Rc::as_ptr(&five).addr());
+  /// ```
+  pub fn addr(&self) -> usize {
+    std::ptr::from_ref::<P::Target>(&self.0).cast::<()>().addr()
+  }
+
+  /// Unwraps the pointer.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{ByAddress, Rc};
+  ///
+  /// let key = ByAddress(Rc::new(5));
+  /// assert_eq!(*key.into_inner(), 5);
+  /// ```
+  pub fn into_inner(self) -> P {
+    self.0
+  }
+}
+
+impl<P: Deref> Deref for ByAddress<P> {
+  type Target = P::Target;
+
+  fn deref(&self) -> &P::Target {
+    &self.0
+  }
+}
+
+impl<P> From<P> for ByAddress<P> {
+  fn from(pointer: P) -> ByAddress<P> {
+    ByAddress(pointer)
+  }
+}
+
+impl<P: Deref> PartialEq for ByAddress<P> {
+  fn eq(&self, other: &ByAddress<P>) -> bool {
+    self.addr() == other.addr()
+  }
+}
+
+impl<P: Deref> Eq for ByAddress<P> {}
+
+impl<P: Deref> std::hash::Hash for ByAddress<P> {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    self.addr().hash(state);
+  }
+}
+
+impl<P: Deref> PartialOrd for ByAddress<P> {
+  fn partial_cmp(&self, other: &ByAddress<P>) -> Option<std::cmp::Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl<P: Deref> Ord for ByAddress<P> {
+  fn cmp(&self, other: &ByAddress<P>) -> std::cmp::Ordering {
+    self.addr().cmp(&other.addr())
+  }
+}
+
+impl<P: std::fmt::Debug> std::fmt::Debug for ByAddress<P> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("ByAddress").field(&self.0).finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::sync::Arc;
+  use crate::Rc;
+
+  #[test]
+  fn identity_not_value() {
+    let a = Rc::new(String::from("same"));
+    let b = Rc::new(String::from("same"));
+
+    assert_eq!(ByAddress(Rc::clone(&a)), ByAddress(Rc::clone(&a)));
+    assert_ne!(ByAddress(Rc::clone(&a)), ByAddress(Rc::clone(&b)));
+
+    let arc = Arc::new([1, 2, 3]);
+    assert_eq!(ByAddress(Arc::clone(&arc)), ByAddress(Arc::clone(&arc)));
+    // Slices of the same array from the same start are the same key.
+    assert_eq!(ByAddress(&arc[..]), ByAddress(&arc[..2]));
+  }
+
+  #[test]
+  fn orders_keys_in_a_btree_set() {
+    let nodes: Vec<_> = (0..4).map(Rc::new).collect();
+    let set: std::collections::BTreeSet<_> = nodes
+      .iter()
+      .chain(&nodes)
+      .map(|node| ByAddress(Rc::clone(node)))
+      .collect();
+
+    assert_eq!(set.len(), 4);
+    assert!(set.contains(&ByAddress(Rc::clone(&nodes[2]))));
+  }
+}
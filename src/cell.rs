@@ -39,11 +39,45 @@
 /// assert_eq!(my_struct.special_field.get(), new_value);
 /// ```
 ///
+/// # Memory layout
+///
+/// `Cell<T>` is `#[repr(transparent)]` over [`UnsafeCell<T>`], which has the
+/// same size, alignment and bit validity as `T`. So `Cell<T>` has the layout
+/// of `T`, and `[Cell<T>]` has the layout of `[T]`. [`from_mut`],
+/// [`as_slice_of_cells`] and [`cell_project!`] rely on this, and so may
+/// unsafe code outside this crate. Only the mutability differs: a `&Cell<T>` may be written through,
+/// so it must not be made from a `&T`.
+///
+/// The casts between cells of slices or arrays and slices or arrays of cells
+/// follow from this:
+///
+/// | From                  | To                    | With                     |
+/// |-----------------------|-----------------------|--------------------------|
+/// | `&mut [T]`            | `&[Cell<T>]`          | [`from_slice_mut`]       |
+/// | `&mut [Cell<T>]`      | `&mut [T]`            | [`get_mut_slice`]        |
+/// | `&Cell<[T]>`          | `&[Cell<T>]`          | [`as_slice_of_cells`]    |
+/// | `&[Cell<T>]`          | `&Cell<[T]>`          | [`from_slice_of_cells`]  |
+/// | `&Cell<[T; N]>`       | `&[Cell<T>; N]`       | [`as_cell_of_array`]     |
+/// | `&[Cell<T>; N]`       | `&Cell<[T; N]>`       | [`from_array_of_cells`]  |
+///
+/// [`UnsafeCell<T>`]: std::cell::UnsafeCell
+/// [`from_mut`]: Cell::from_mut
+/// [`as_slice_of_cells`]: Cell::as_slice_of_cells
+/// [`from_slice_mut`]: Cell::from_slice_mut
+/// [`get_mut_slice`]: Cell::get_mut_slice
+/// [`from_slice_of_cells`]: Cell::from_slice_of_cells
+/// [`as_cell_of_array`]: Cell::as_cell_of_array
+/// [`from_array_of_cells`]: Cell::from_array_of_cells
+/// [`cell_project!`]: crate::cell_project
+#[repr(transparent)]
 pub struct Cell<T: ?Sized> {
   value: std::cell::UnsafeCell<T>,
 }
 
 unsafe impl<T> Send for Cell<T> where T: Send {}
+// As with std's `Cell`, the `UnsafeCell` inside makes `Cell<T>` `UnwindSafe`
+// when `T` is, and never `RefUnwindSafe`: a panic may leave the value
+// half-updated for anyone else holding a `&Cell`.
 // impl<T: ?Sized> !Sync for Cell<T> {}
 
 impl<T: Default> Default for Cell<T> {
@@ -54,21 +88,94 @@ impl<T: Default> Default for Cell<T> {
 }
 
 impl<T: Default> Cell<T> {
-  /// Takes the value of a `Cell` leaving `Default::default()` in it's place.
+  nightly_const! {
+    /// Takes the value of a `Cell` leaving `Default::default()` in it's place.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pointer::Cell;
+    ///
+    /// let c = Cell::new(5);
+    /// let five = c.take();
+    ///
+    /// assert_eq!(five, 5);
+    /// assert_eq!(c.into_inner(), 0);
+    /// ```
+    pub fn take(&self) -> T [where T: [const] Default] {
+      self.replace(Default::default())
+    }
+  }
+}
+
+impl<T: Clone + Default> Cell<T> {
+  /// Returns a clone of the contained value.
   ///
-  /// # Example
+  /// The value is taken out of the cell, cloned, and put back, leaving
+  /// `Default::default()` in the cell while `clone` runs. If that `clone`
+  /// calls `get_clone` on this same cell again, which would clone the
+  /// placeholder instead, it panics. The value is put back even if `clone`
+  /// panics. Other writes to the cell while `clone` runs are overwritten.
+  ///
+  /// # Panics
+  ///
+  /// Panics if called from the `clone` of a `get_clone` on the same cell.
+  ///
+  /// # Examples
   ///
   /// ```
   /// use pointer::Cell;
   ///
-  /// let c = Cell::new(5);
-  /// let five = c.take();
+  /// let c = Cell::new(String::from("hello"));
+  /// let hello = c.get_clone();
   ///
-  /// assert_eq!(five, 5);
-  /// assert_eq!(c.into_inner(), 0);
+  /// assert_eq!(hello, "hello");
+  /// assert_eq!(c.into_inner(), "hello");
   /// ```
-  pub fn take(&self) -> T {
-    self.replace(Default::default())
+  pub fn get_clone(&self) -> T {
+    let guard = CloneGuard::enter(self);
+    guard.value.clone()
+  }
+}
+
+std::thread_local! {
+  /// The cells this thread is cloning in `get_clone`, innermost last, by
+  /// address and type, as a cell may start with a cell of another type.
+  static CLONING: std::cell::RefCell<Vec<(usize, &'static str)>> =
+    const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// Holds the value taken out of a cell by [`Cell::get_clone`], and puts it
+/// back when dropped.
+struct CloneGuard<'a, T: Default> {
+  cell: &'a Cell<T>,
+  value: T,
+}
+
+impl<'a, T: Default> CloneGuard<'a, T> {
+  fn enter(cell: &'a Cell<T>) -> CloneGuard<'a, T> {
+    let key = (cell.as_ptr().addr(), std::any::type_name::<T>());
+    // Past the thread's teardown, there is nothing to check against.
+    let _ = CLONING.try_with(|cloning| {
+      let mut cloning = cloning.borrow_mut();
+      if cloning.contains(&key) {
+        drop(cloning);
+        panic!("`Cell::get_clone` called on a cell while cloning its value");
+      }
+      cloning.push(key);
+    });
+    CloneGuard {
+      cell,
+      value: cell.take(),
+    }
+  }
+}
+
+impl<T: Default> Drop for CloneGuard<'_, T> {
+  fn drop(&mut self) {
+    let placeholder = self.cell.replace(std::mem::take(&mut self.value));
+    let _ = CLONING.try_with(|cloning| cloning.borrow_mut().pop());
+    drop(placeholder);
   }
 }
 
@@ -133,21 +240,22 @@ impl<T> Cell<T> {
     }
   }
 
-  /// Sets the contained value.
-  ///
-  /// # Examples
-  ///
-  /// ```
-  /// use pointer::Cell;
-  ///
-  /// let c = Cell::new(5);
-  ///
-  /// c.set(10);
-  /// ```
-  #[inline]
-  pub fn set(&self, val: T) {
-    let old = self.replace(val);
-    drop(old);
+  nightly_const! {
+    /// Sets the contained value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pointer::Cell;
+    ///
+    /// let c = Cell::new(5);
+    ///
+    /// c.set(10);
+    /// ```
+    #[inline]
+    pub fn set(&self, val: T) [where T: [const] std::marker::Destruct] {
+      let _old = self.replace(val);
+    }
   }
 
   /// Swaps the value of two `Cell`s.
@@ -191,26 +299,28 @@ impl<T> Cell<T> {
   /// assert_eq!(cell.replace(10), 5); // returns the replaced value.
   /// assert_eq!(cell.get(), 10);
   /// ```
-  pub fn replace(&self, val: T) -> T {
+  pub const fn replace(&self, val: T) -> T {
     // SAFETY: This can cause data reaces if called from a separate threads,
     // but Cell is `!Sync`, so this won't happen.
     std::mem::replace(unsafe { &mut *self.value.get() }, val)
   }
 
-  /// Unwraps the value.
-  ///
-  /// # Examples
-  ///
-  /// ```
-  /// use pointer::Cell;
-  ///
-  /// let c = Cell::new(5);
-  /// let five = c.into_inner();
-  ///
-  /// assert_eq!(five, 5);
-  /// ```
-  pub fn into_inner(self) -> T {
-    self.value.into_inner()
+  nightly_const! {
+    /// Unwraps the value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pointer::Cell;
+    ///
+    /// let c = Cell::new(5);
+    /// let five = c.into_inner();
+    ///
+    /// assert_eq!(five, 5);
+    /// ```
+    pub fn into_inner(self) -> T {
+      self.value.into_inner()
+    }
   }
 }
 
@@ -236,6 +346,168 @@ impl<T: Copy> Cell<T> {
     new
   }
 
+  /// Updates the contained value using a function and returns the old value.
+  ///
+  /// This is [`update`](#method.update) for callers that need the previous
+  /// value, like the `fetch_*` methods of the atomic types.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cell;
+  ///
+  /// let total = Cell::new(5);
+  /// let old = total.get_and_update(|x| x * 3);
+  ///
+  /// assert_eq!(old, 5);
+  /// assert_eq!(total.get() - old, 10);
+  /// ```
+  #[inline]
+  pub fn get_and_update(&self, f: impl FnOnce(T) -> T) -> T {
+    let old = self.get();
+    self.set(f(old));
+    old
+  }
+
+  /// Updates the contained value using a function and returns the new value.
+  ///
+  /// This is the same as [`update`](#method.update), named to pair with
+  /// [`get_and_update`](#method.get_and_update).
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cell;
+  ///
+  /// let c = Cell::new(5);
+  /// assert_eq!(c.update_and_get(|x| x + 1), 6);
+  /// assert_eq!(c.get(), 6);
+  /// ```
+  #[inline]
+  pub fn update_and_get(&self, f: impl FnOnce(T) -> T) -> T {
+    self.update(f)
+  }
+
+  /// Swaps the values of two `Cell`s, like [`swap`](#method.swap).
+  ///
+  /// Since the values are `Copy`, this reads both and writes them back: two
+  /// loads and two stores of `T`, without the check for the same cell or the
+  /// byte-wise copying of [`std::ptr::swap`] that [`swap`](#method.swap)
+  /// goes through. Swapping a cell with itself leaves it as it is.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cell;
+  ///
+  /// let front = Cell::new([1u8; 16]);
+  /// let back = Cell::new([2u8; 16]);
+  ///
+  /// front.swap_copy(&back);
+  /// assert_eq!((front.get()[0], back.get()[0]), (2, 1));
+  /// ```
+  #[inline]
+  pub fn swap_copy(&self, other: &Self) {
+    let (a, b) = (self.get(), other.get());
+    // SAFETY: `Cell` is `!Sync` and hands out no references into its value,
+    // so nothing else reads or writes either value meanwhile.
+    unsafe {
+      *self.value.get() = b;
+      *other.value.get() = a;
+    }
+  }
+
+  /// Returns the values of a slice of cells in a `Vec`.
+  ///
+  /// Like [`copy_into`] and [`set_all`], this copies the whole slice at once
+  /// rather than element by element.
+  ///
+  /// [`copy_into`]: Cell::copy_into
+  /// [`set_all`]: Cell::set_all
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cell;
+  ///
+  /// let cells = [Cell::new(1), Cell::new(2), Cell::new(3)];
+  /// cells[1].set(20);
+  ///
+  /// assert_eq!(Cell::get_all(&cells), [1, 20, 3]);
+  /// ```
+  pub fn get_all(cells: &[Cell<T>]) -> Vec<T> {
+    Cell::values(cells).to_vec()
+  }
+
+  /// Copies the values of a slice of cells into `dst`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the two slices have different lengths.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cell;
+  ///
+  /// let cells = [Cell::new(1), Cell::new(2)];
+  /// let mut out = [0; 2];
+  /// Cell::copy_into(&cells, &mut out);
+  ///
+  /// assert_eq!(out, [1, 2]);
+  /// ```
+  #[track_caller]
+  pub fn copy_into(cells: &[Cell<T>], dst: &mut [T]) {
+    dst.copy_from_slice(Cell::values(cells));
+  }
+
+  /// Sets each cell of a slice to the value at the same index of `src`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the two slices have different lengths.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cell;
+  ///
+  /// let mut pixels = [0u8; 4];
+  /// let cells = Cell::from_slice_mut(&mut pixels);
+  /// Cell::set_all(&cells[1..3], &[7, 8]);
+  ///
+  /// assert_eq!(pixels, [0, 7, 8, 0]);
+  /// ```
+  #[track_caller]
+  pub fn set_all(cells: &[Cell<T>], src: &[T]) {
+    assert_eq!(
+      cells.len(),
+      src.len(),
+      "source slice length does not match the number of cells"
+    );
+    // SAFETY: `Cell<T>` has the layout of `T`, and `Cell` is `!Sync` and
+    // hands out no references into its values, so nothing else reads or
+    // writes the cells meanwhile. `src` can't point into them, since a `&T`
+    // to a cell's value can't exist alongside the `&Cell<T>`.
+    unsafe {
+      std::ptr::copy_nonoverlapping(
+        src.as_ptr(),
+        cells.as_ptr().cast::<T>().cast_mut(),
+        src.len(),
+      );
+    }
+  }
+
+  /// Views the values of a slice of cells as a slice, for copying them out.
+  fn values(cells: &[Cell<T>]) -> &[T] {
+    // SAFETY: `Cell<T>` has the layout of `T`. The values aren't written
+    // while the result is used to copy them, since `Cell` is `!Sync` and
+    // copying `T: Copy` runs no code of the caller's.
+    unsafe {
+      std::slice::from_raw_parts(cells.as_ptr().cast::<T>(), cells.len())
+    }
+  }
+
   /// Returns a copy of the contained value.
   ///
   /// # Examples
@@ -248,7 +520,7 @@ impl<T: Copy> Cell<T> {
   /// let five = c.get();
   /// ```
   #[inline]
-  pub fn get(&self) -> T {
+  pub const fn get(&self) -> T {
     // SAFETY: This could cause data races but `Cell` is `!Sync`.
     // We know no one else is modifying this value, since only this thread can mutate. (because `!Sync`).
     // and executing only this function. i.e. not mutating the value.
@@ -262,7 +534,7 @@ impl<T: ?Sized> Cell<T> {
   /// # Example
   ///
   /// ```
-  /// use ptr::Cell;
+  /// use pointer::Cell;
   ///
   /// let c = Cell::new(5);
   /// let p = c.as_ptr();
@@ -288,7 +560,7 @@ impl<T: ?Sized> Cell<T> {
   /// assert_eq!(c.get(), 6);
   /// ```
   #[inline]
-  pub fn get_mut(&mut self) -> &mut T {
+  pub const fn get_mut(&mut self) -> &mut T {
     // SAFETY: This can cause data race when called from separate threads, but `Cell` is `!Sync`,
     // so it won't happen and `&mut` guarantees unique access.
     unsafe { &mut *self.value.get() }
@@ -299,7 +571,7 @@ impl<T: ?Sized> Cell<T> {
   /// # Example
   ///
   /// ```
-  /// use ptr::Cell;
+  /// use pointer::Cell;
   ///
   /// let slice: &mut [i32] = &mut [1, 2, 3];
   ///let cell_slice: &Cell<[i32]> = Cell::from_mut(slice);
@@ -310,9 +582,58 @@ impl<T: ?Sized> Cell<T> {
   ///
   /// See also [`as_slice_of_cells`](#method.as_slice_of_cells)
   #[inline]
-  pub fn from_mut(t: &mut T) -> &Cell<T> {
-    // SAFETY: `&mut` ensures unique access.
-    unsafe { &*(t as *mut T as *const Cell<T>) }
+  pub const fn from_mut(t: &mut T) -> &Cell<T> {
+    // SAFETY: `&mut` ensures unique access, and `Cell<T>` has the layout of
+    // `T`.
+    unsafe { &*(std::ptr::from_mut(t) as *const Cell<T>) }
+  }
+}
+
+impl<T> Cell<T> {
+  /// Returns a `&[Cell<T>]` from a `&mut [T]`, so each element can be
+  /// mutated through a shared reference.
+  ///
+  /// This is [`from_mut`] followed by [`as_slice_of_cells`].
+  ///
+  /// [`from_mut`]: Cell::from_mut
+  /// [`as_slice_of_cells`]: Cell::as_slice_of_cells
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cell;
+  ///
+  /// let mut values = [1, 2, 3];
+  /// let cells = Cell::from_slice_mut(&mut values);
+  /// cells[0].set(cells[2].get());
+  ///
+  /// assert_eq!(values, [3, 2, 3]);
+  /// ```
+  #[inline]
+  pub const fn from_slice_mut(slice: &mut [T]) -> &[Cell<T>] {
+    Cell::from_mut(slice).as_slice_of_cells()
+  }
+
+  /// Returns a `&mut [T]` from a `&mut [Cell<T>]`, the reverse of
+  /// [`from_slice_mut`].
+  ///
+  /// [`from_slice_mut`]: Cell::from_slice_mut
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cell;
+  ///
+  /// let mut cells = [Cell::new(3), Cell::new(1), Cell::new(2)];
+  /// Cell::get_mut_slice(&mut cells).sort();
+  ///
+  /// assert_eq!(cells.map(Cell::into_inner), [1, 2, 3]);
+  /// ```
+  #[inline]
+  pub const fn get_mut_slice(cells: &mut [Cell<T>]) -> &mut [T] {
+    // SAFETY: `Cell<T>` has the layout of `T`, and `&mut` ensures that no
+    // other reference can reach the cells meanwhile.
+    unsafe { &mut *(std::ptr::from_mut(cells) as *mut [T]) }
   }
 }
 
@@ -332,9 +653,70 @@ impl<T> Cell<[T]> {
   /// ```
   ///
   /// See also [`from_mut`](#method.from_mut)
-  pub fn as_slice_of_cells(&self) -> &[Cell<T>] {
-    // SAFETY: `Cell<T>` has memory layout as `T`.
-    unsafe { &*(self as *const Cell<[T]> as *const [Cell<T>]) }
+  pub const fn as_slice_of_cells(&self) -> &[Cell<T>] {
+    // SAFETY: `Cell<T>` has the layout of `T`, so `Cell<[T]>` and
+    // `[Cell<T>]` have the same layout and length.
+    unsafe { &*(std::ptr::from_ref(self) as *const [Cell<T>]) }
+  }
+
+  /// Returns a `&Cell<[T]>` from a `&[Cell<T>]`, the reverse of
+  /// [`as_slice_of_cells`](#method.as_slice_of_cells).
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cell;
+  ///
+  /// let cells = [Cell::new(1), Cell::new(2)];
+  /// let whole: &Cell<[i32]> = Cell::from_slice_of_cells(&cells);
+  ///
+  /// assert_eq!(whole.as_slice_of_cells()[1].get(), 2);
+  /// ```
+  pub const fn from_slice_of_cells(cells: &[Cell<T>]) -> &Cell<[T]> {
+    // SAFETY: `Cell<T>` has the layout of `T`, and every element is already
+    // writable through the shared reference.
+    unsafe { &*(std::ptr::from_ref(cells) as *const Cell<[T]>) }
+  }
+}
+
+impl<T, const N: usize> Cell<[T; N]> {
+  /// Returns `&[Cell<T>; N]` from `&Cell<[T; N]>`, keeping the length in
+  /// the type.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cell;
+  ///
+  /// let rgb = Cell::new([255u8, 128, 0]);
+  /// let [r, _, b] = rgb.as_cell_of_array();
+  /// r.swap(b);
+  ///
+  /// assert_eq!(rgb.get(), [0, 128, 255]);
+  /// ```
+  pub const fn as_cell_of_array(&self) -> &[Cell<T>; N] {
+    // SAFETY: `Cell<T>` has the layout of `T`, so `Cell<[T; N]>` and
+    // `[Cell<T>; N]` have the same layout.
+    unsafe { &*(std::ptr::from_ref(self) as *const [Cell<T>; N]) }
+  }
+
+  /// Returns a `&Cell<[T; N]>` from a `&[Cell<T>; N]`, the reverse of
+  /// [`as_cell_of_array`](#method.as_cell_of_array).
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cell;
+  ///
+  /// let point = [Cell::new(1), Cell::new(2)];
+  /// Cell::from_array_of_cells(&point).set([3, 4]);
+  ///
+  /// assert_eq!(point[0].get() + point[1].get(), 7);
+  /// ```
+  pub const fn from_array_of_cells(cells: &[Cell<T>; N]) -> &Cell<[T; N]> {
+    // SAFETY: `Cell<T>` has the layout of `T`, and every element is already
+    // writable through the shared reference.
+    unsafe { &*(std::ptr::from_ref(cells) as *const Cell<[T; N]>) }
   }
 }
 
@@ -364,6 +746,16 @@ mod tests {
     assert_eq!(5, c2.get());
   }
 
+  #[test]
+  fn swap_copy() {
+    let mut values = [1u64, 2, 3];
+    let cells = Cell::from_mut(&mut values[..]).as_slice_of_cells();
+
+    cells[0].swap_copy(&cells[2]);
+    cells[1].swap_copy(&cells[1]);
+    assert_eq!(values, [3, 2, 1]);
+  }
+
   #[test]
   fn replace() {
     let cell = Cell::new(5);
@@ -398,6 +790,15 @@ mod tests {
     assert_eq!(c.get(), 6);
   }
 
+  #[test]
+  fn get_and_update_pair() {
+    let c = Cell::new(5);
+
+    assert_eq!(c.get_and_update(|x| x * 2), 5);
+    assert_eq!(c.update_and_get(|x| x * 2), 20);
+    assert_eq!(c.get(), 20);
+  }
+
   #[test]
   fn as_ptr() {
     let c = Cell::new(5);
@@ -426,6 +827,36 @@ mod tests {
     assert_eq!(slice_cell.len(), 3);
   }
 
+  #[test]
+  fn get_clone_detects_reentrancy() {
+    #[derive(Default)]
+    struct Reads<'a>(Option<&'a Cell<Reads<'a>>>);
+
+    impl Clone for Reads<'_> {
+      fn clone(&self) -> Self {
+        if let Some(cell) = self.0 {
+          cell.get_clone();
+        }
+        Reads(self.0)
+      }
+    }
+
+    let name = Cell::new(String::from("name"));
+    assert_eq!(name.get_clone(), "name");
+    assert_eq!(name.take(), "name");
+
+    let cell = Cell::new(Reads(None));
+    cell.set(Reads(Some(&cell)));
+    let reentered =
+      std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        cell.get_clone();
+      }));
+    assert!(reentered.is_err());
+    // The value is back, and the cell can be cloned from again.
+    assert!(cell.take().0.is_some());
+    assert!(cell.get_clone().0.is_none());
+  }
+
   #[test]
   fn take() {
     let c = Cell::new(5);
@@ -435,6 +866,74 @@ mod tests {
     assert_eq!(c.into_inner(), 0);
   }
 
+  #[test]
+  fn layout_matches_the_value() {
+    use std::mem::{align_of, size_of};
+
+    assert_eq!(size_of::<Cell<[u16; 3]>>(), size_of::<[u16; 3]>());
+    assert_eq!(align_of::<Cell<u64>>(), align_of::<u64>());
+    assert_eq!(size_of::<&Cell<[u8]>>(), size_of::<&[u8]>());
+  }
+
+  #[test]
+  fn raw_views_alias_the_original() {
+    let mut values = [1, 2, 3];
+    let cells = Cell::from_mut(&mut values[..]).as_slice_of_cells();
+    cells[0].set(10);
+    // SAFETY: Nothing else reads the cell while the pointer is written.
+    unsafe { *cells[2].as_ptr() = 30 };
+    cells[1].swap(&cells[2]);
+
+    assert_eq!(values, [10, 30, 2]);
+  }
+
+  #[test]
+  fn batch_casts_keep_layout() {
+    use std::mem::{align_of, size_of};
+    assert_eq!(size_of::<Cell<[u16; 3]>>(), size_of::<[Cell<u16>; 3]>());
+    assert_eq!(align_of::<Cell<[u16; 3]>>(), align_of::<[Cell<u16>; 3]>());
+
+    let mut values = [1u16, 2, 3];
+    let base = values.as_ptr().addr();
+    let cells = Cell::from_slice_mut(&mut values);
+    let whole = Cell::from_slice_of_cells(cells);
+    assert_eq!(whole.as_ptr().cast::<u16>().addr(), base);
+    assert_eq!(whole.as_slice_of_cells().len(), 3);
+    cells[1].set(20);
+
+    let array = Cell::new([1u16, 2, 3]);
+    let elements = array.as_cell_of_array();
+    assert_eq!(elements[2].as_ptr().addr(), array.as_ptr().addr() + 4);
+    assert!(std::ptr::eq(Cell::from_array_of_cells(elements), &array));
+    elements[0].set(10);
+    assert_eq!(array.get(), [10, 2, 3]);
+
+    let mut owned = [Cell::new(3u16), Cell::new(1)];
+    Cell::get_mut_slice(&mut owned).reverse();
+    assert_eq!(owned.map(Cell::into_inner), [1, 3]);
+    assert_eq!(values, [1, 20, 3]);
+  }
+
+  #[test]
+  fn bulk_copies() {
+    let mut values = [1u32, 2, 3, 4];
+    let cells = Cell::from_slice_mut(&mut values);
+    Cell::set_all(&cells[..2], &[10, 20]);
+    assert_eq!(Cell::get_all(cells), [10, 20, 3, 4]);
+
+    let mut out = [0; 3];
+    Cell::copy_into(&cells[1..], &mut out);
+    assert_eq!(out, [20, 3, 4]);
+    assert_eq!(Cell::<u32>::get_all(&[]), []);
+  }
+
+  #[test]
+  #[should_panic(expected = "does not match")]
+  fn set_all_checks_lengths() {
+    let cells = [Cell::new(0), Cell::new(0)];
+    Cell::set_all(&cells, &[1, 2, 3]);
+  }
+
   #[test]
   fn as_slice_of_cells() {
     let slice: &mut [i32] = &mut [1, 2, 3];
@@ -444,6 +943,68 @@ mod tests {
     assert_eq!(slice_cell.len(), 3);
   }
 
+  #[test]
+  fn const_contexts() {
+    const fn bump(mut cell: Cell<u32>) -> Cell<u32> {
+      *cell.get_mut() += 1;
+      let old = cell.replace(10);
+      Cell::new(old + cell.get())
+    }
+    const BUMPED: u32 = bump(Cell::new(1)).get();
+    const EMPTY: crate::Weak<u32> = crate::Weak::new();
+    static SHARED: crate::sync::Weak<u32> = crate::sync::Weak::new();
+
+    assert_eq!(BUMPED, 12);
+    assert!(EMPTY.upgrade().is_none());
+    assert!(SHARED.upgrade().is_none());
+  }
+
+  #[cfg(feature = "nightly-const")]
+  #[test]
+  fn nightly_const_contexts() {
+    const fn swap_out(cell: Cell<u32>) -> u32 {
+      cell.set(5);
+      cell.take() + cell.into_inner()
+    }
+    const FIVE: u32 = swap_out(Cell::new(1));
+
+    assert_eq!(FIVE, 5);
+  }
+
+  #[cfg(feature = "derive")]
+  #[test]
+  fn derived_accessors() {
+    #[derive(crate::InteriorMutable)]
+    struct Cache<K: Copy> {
+      #[interior]
+      pub(crate) key: Cell<K>,
+      #[interior]
+      hits: std::cell::Cell<usize>,
+      #[interior]
+      entries: crate::RefCell<Vec<(K, String)>>,
+      _unmarked: Cell<u8>,
+    }
+
+    let cache = Cache {
+      key: Cell::new('a'),
+      hits: std::cell::Cell::new(0),
+      entries: crate::RefCell::new(Vec::new()),
+      _unmarked: Cell::new(0),
+    };
+    cache.set_key('b');
+    cache.update_hits(|n| n + 2);
+    let len = cache.update_entries(|entries| {
+      entries.push(('b', String::from("bee")));
+      entries.len()
+    });
+    cache.entries_mut().push(('c', String::from("sea")));
+
+    assert_eq!(cache.key(), 'b');
+    assert_eq!(cache.hits(), 2);
+    assert_eq!(len, 1);
+    assert_eq!(cache.entries()[1].1, "sea");
+  }
+
   #[test]
   fn cell_str() {
     let cell = Cell::new("John Doe");
@@ -0,0 +1,331 @@
+//! A vector whose elements can be mutated through a shared reference: [`CellVec<T>`][CellVec].
+//!
+//! Simulation code often keeps its state in a `Vec` and mutates it by index from code that only
+//! holds a shared reference, through `&[Cell<T>]` views made with [`Cell::from_slice_mut`]. A
+//! `CellVec` packages that: it owns a `Vec<Cell<T>>`, and [`get`], [`set`], [`swap`] and
+//! [`update`] take `&self`. Like a `Cell`, it never hands out a reference into an element, so
+//! there is nothing to borrow and nothing to check at runtime. Growing or shrinking the vector
+//! still takes `&mut self`, since that moves the elements.
+//!
+//! ```
+//! use pointer::CellVec;
+//!
+//! // A one-dimensional cellular automaton, stepped in place.
+//! let cells: CellVec<u8> = vec![0, 1, 0, 0, 1].into();
+//! let step = |cells: &CellVec<u8>| {
+//!   let before = cells.to_vec();
+//!   for i in 0..cells.len() {
+//!     let left = before[(i + before.len() - 1) % before.len()];
+//!     cells.set(i, left);
+//!   }
+//! };
+//!
+//! step(&cells);
+//! assert_eq!(cells.to_vec(), [1, 0, 1, 0, 0]);
+//! ```
+//!
+//! [`Cell::from_slice_mut`]: crate::Cell::from_slice_mut
+//! [`get`]: CellVec::get
+//! [`set`]: CellVec::set
+//! [`swap`]: CellVec::swap
+//! [`update`]: CellVec::update
+
+use crate::Cell;
+
+/// A vector of cells, indexable through a shared reference.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct CellVec<T> {
+  cells: Vec<Cell<T>>,
+}
+
+impl<T> CellVec<T> {
+  /// Creates an empty `CellVec`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CellVec;
+  ///
+  /// let v: CellVec<i32> = CellVec::new();
+  /// assert!(v.is_empty());
+  /// ```
+  pub fn new() -> CellVec<T> {
+    CellVec { cells: Vec::new() }
+  }
+
+  /// Returns the number of elements.
+  pub fn len(&self) -> usize {
+    self.cells.len()
+  }
+
+  /// Returns `true` if the vector has no elements.
+  pub fn is_empty(&self) -> bool {
+    self.cells.is_empty()
+  }
+
+  /// Sets the element at `index` to `value`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `index` is out of bounds.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CellVec;
+  ///
+  /// let v: CellVec<_> = vec![1, 2].into();
+  /// v.set(0, 10);
+  /// assert_eq!(v.get(0), Some(10));
+  /// ```
+  #[track_caller]
+  pub fn set(&self, index: usize, value: T) {
+    self.cells[index].set(value);
+  }
+
+  /// Replaces the element at `index` with `value`, and returns the old one.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `index` is out of bounds.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CellVec;
+  ///
+  /// let v: CellVec<_> = vec![String::from("a")].into();
+  /// assert_eq!(v.replace(0, String::from("b")), "a");
+  /// ```
+  #[track_caller]
+  pub fn replace(&self, index: usize, value: T) -> T {
+    self.cells[index].replace(value)
+  }
+
+  /// Swaps the elements at `a` and `b`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if either index is out of bounds.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CellVec;
+  ///
+  /// let v: CellVec<_> = vec!['a', 'b', 'c'].into();
+  /// v.swap(0, 2);
+  /// assert_eq!(v.to_vec(), ['c', 'b', 'a']);
+  /// ```
+  #[track_caller]
+  pub fn swap(&self, a: usize, b: usize) {
+    self.cells[a].swap(&self.cells[b]);
+  }
+
+  /// Returns the elements as a slice of cells.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Cell, CellVec};
+  ///
+  /// let v: CellVec<_> = vec![1, 2, 3].into();
+  /// let cells: &[Cell<i32>] = v.as_slice_of_cells();
+  /// Cell::set_all(&cells[1..], &[20, 30]);
+  ///
+  /// assert_eq!(v.to_vec(), [1, 20, 30]);
+  /// ```
+  pub fn as_slice_of_cells(&self) -> &[Cell<T>] {
+    &self.cells
+  }
+
+  /// Returns the elements as a mutable slice, for operations that need all
+  /// of them at once.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CellVec;
+  ///
+  /// let mut v: CellVec<_> = vec![3, 1, 2].into();
+  /// v.as_mut_slice().sort();
+  /// assert_eq!(v.to_vec(), [1, 2, 3]);
+  /// ```
+  pub fn as_mut_slice(&mut self) -> &mut [T] {
+    Cell::get_mut_slice(&mut self.cells)
+  }
+
+  /// Appends `value` to the back of the vector.
+  pub fn push(&mut self, value: T) {
+    self.cells.push(Cell::new(value));
+  }
+
+  /// Removes the last element and returns it, or returns `None` if the
+  /// vector is empty.
+  pub fn pop(&mut self) -> Option<T> {
+    self.cells.pop().map(Cell::into_inner)
+  }
+
+  /// Consumes the `CellVec`, returning its elements.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CellVec;
+  ///
+  /// let v: CellVec<_> = vec![1, 2].into();
+  /// v.swap(0, 1);
+  /// assert_eq!(v.into_vec(), [2, 1]);
+  /// ```
+  pub fn into_vec(self) -> Vec<T> {
+    self.cells.into_iter().map(Cell::into_inner).collect()
+  }
+}
+
+impl<T: Copy> CellVec<T> {
+  /// Returns a copy of the element at `index`, or `None` if it is out of
+  /// bounds.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CellVec;
+  ///
+  /// let v: CellVec<_> = vec![1, 2].into();
+  /// assert_eq!(v.get(1), Some(2));
+  /// assert_eq!(v.get(2), None);
+  /// ```
+  pub fn get(&self, index: usize) -> Option<T> {
+    self.cells.get(index).map(Cell::get)
+  }
+
+  /// Updates the element at `index` with `f`, and returns the new value.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `index` is out of bounds.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CellVec;
+  ///
+  /// let counts: CellVec<u32> = vec![0; 3].into();
+  /// for i in [0, 2, 2] {
+  ///   counts.update(i, |n| n + 1);
+  /// }
+  /// assert_eq!(counts.to_vec(), [1, 0, 2]);
+  /// ```
+  #[track_caller]
+  pub fn update(&self, index: usize, f: impl FnOnce(T) -> T) -> T {
+    self.cells[index].update(f)
+  }
+
+  /// Returns a copy of the elements.
+  pub fn to_vec(&self) -> Vec<T> {
+    Cell::get_all(&self.cells)
+  }
+}
+
+impl<T> Default for CellVec<T> {
+  fn default() -> CellVec<T> {
+    CellVec::new()
+  }
+}
+
+impl<T> From<Vec<T>> for CellVec<T> {
+  fn from(values: Vec<T>) -> CellVec<T> {
+    values.into_iter().collect()
+  }
+}
+
+impl<T> std::iter::FromIterator<T> for CellVec<T> {
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> CellVec<T> {
+    CellVec {
+      cells: iter.into_iter().map(Cell::new).collect(),
+    }
+  }
+}
+
+impl<T> Extend<T> for CellVec<T> {
+  fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+    self.cells.extend(iter.into_iter().map(Cell::new));
+  }
+}
+
+impl<T> std::ops::Index<usize> for CellVec<T> {
+  type Output = Cell<T>;
+
+  #[track_caller]
+  fn index(&self, index: usize) -> &Cell<T> {
+    &self.cells[index]
+  }
+}
+
+impl<T: Copy> Clone for CellVec<T> {
+  fn clone(&self) -> CellVec<T> {
+    self.to_vec().into()
+  }
+}
+
+impl<T: Copy + std::fmt::Debug> std::fmt::Debug for CellVec<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_list()
+      .entries(self.cells.iter().map(Cell::get))
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn elements_mutate_through_shared_references() {
+    struct Particles<'a> {
+      positions: &'a CellVec<i32>,
+      velocities: &'a CellVec<i32>,
+    }
+
+    let positions: CellVec<_> = vec![0, 10, 20].into();
+    let velocities: CellVec<_> = vec![1, -1, 0].into();
+    let particles = Particles {
+      positions: &positions,
+      velocities: &velocities,
+    };
+
+    for _ in 0..3 {
+      for i in 0..particles.positions.len() {
+        let v = particles.velocities.get(i).unwrap();
+        particles.positions.update(i, |p| p + v);
+      }
+    }
+    particles.velocities.swap(0, 1);
+
+    assert_eq!(positions.to_vec(), [3, 7, 20]);
+    assert_eq!(velocities.into_vec(), [-1, 1, 0]);
+    assert_eq!(format!("{:?}", positions), "[3, 7, 20]");
+  }
+
+  #[test]
+  fn non_copy_elements_are_replaced_whole() {
+    let mut names: CellVec<String> = CellVec::new();
+    names.extend(vec![String::from("a"), String::from("b")]);
+    names.swap(0, 1);
+    names.swap(1, 1);
+    assert_eq!(names.replace(1, String::from("c")), "a");
+    names[0].set(String::from("d"));
+
+    names.push(String::from("e"));
+    assert_eq!(names.pop().as_deref(), Some("e"));
+    assert_eq!(names.into_vec(), ["d", "c"]);
+  }
+
+  #[test]
+  #[should_panic(expected = "out of bounds")]
+  fn setting_out_of_bounds_panics() {
+    let v: CellVec<u8> = vec![0; 2].into();
+    v.set(2, 1);
+  }
+}
@@ -0,0 +1,629 @@
+//! A directed graph that can't leak through cycles: [`Graph<N, E>`][Graph].
+//!
+//! A graph built out of `Rc`s, with each node holding strong pointers to its neighbors, leaks as
+//! soon as it has a cycle. In a `Graph`, only the graph owns the nodes, each through one [`Rc`].
+//! An edge points at the node it leads to through a [`Weak`], and so does a [`NodeRef`], the
+//! handle that [`add_node`] returns. No node owns another, so cycles in the graph are harmless,
+//! and dropping the graph frees every node. [`validate`] checks this, for tests and debug builds.
+//!
+//! Removing a node also removes the edges that lead to it. A `NodeRef` to a removed node is
+//! stale: the graph treats it as a node it doesn't have.
+//!
+//! ```
+//! use pointer::collections::Graph;
+//!
+//! let mut roads = Graph::new();
+//! let home = roads.add_node("home");
+//! let shop = roads.add_node("shop");
+//! let work = roads.add_node("work");
+//! roads.add_edge(&home, &shop, 2);
+//! roads.add_edge(&shop, &work, 3);
+//! roads.add_edge(&work, &home, 4);
+//!
+//! let from_home: Vec<_> = roads.neighbors(&home).map(|n| roads[n]).collect();
+//! assert_eq!(from_home, ["shop"]);
+//!
+//! roads.remove_node(&shop);
+//! assert_eq!(roads.edge_count(), 1);
+//! assert!(roads.get(&shop).is_none());
+//! roads.validate();
+//! ```
+//!
+//! [`Rc`]: crate::Rc
+//! [`Weak`]: crate::Weak
+//! [`add_node`]: Graph::add_node
+//! [`validate`]: Graph::validate
+
+use crate::{Rc, RefCell, Weak};
+
+struct Node<N, E> {
+  id: usize,
+  value: RefCell<N>,
+  /// The outgoing edges.
+  edges: RefCell<Vec<Edge<N, E>>>,
+  /// The token of the graph the node is in.
+  graph: Weak<()>,
+}
+
+struct Edge<N, E> {
+  to: NodeRef<N, E>,
+  value: E,
+}
+
+/// A directed graph, with values of type `N` on its nodes and `E` on its
+/// edges.
+///
+/// Between two nodes there may be several edges, in either direction.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct Graph<N, E> {
+  nodes: Vec<Rc<Node<N, E>>>,
+  /// Marks the nodes that are in this graph.
+  token: Rc<()>,
+  next_id: usize,
+  edge_count: usize,
+}
+
+impl<N, E> Graph<N, E> {
+  /// Creates an empty graph.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::Graph;
+  ///
+  /// let graph: Graph<&str, u32> = Graph::new();
+  /// assert!(graph.is_empty());
+  /// ```
+  pub fn new() -> Graph<N, E> {
+    Graph {
+      nodes: Vec::new(),
+      token: Rc::new(()),
+      next_id: 0,
+      edge_count: 0,
+    }
+  }
+
+  /// Returns the number of nodes.
+  pub fn node_count(&self) -> usize {
+    self.nodes.len()
+  }
+
+  /// Returns the number of edges.
+  pub fn edge_count(&self) -> usize {
+    self.edge_count
+  }
+
+  /// Returns `true` if the graph has no nodes.
+  pub fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
+  }
+
+  /// Adds a node holding `value`, and returns a handle to it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::Graph;
+  ///
+  /// let mut graph: Graph<_, ()> = Graph::new();
+  /// let node = graph.add_node('a');
+  /// assert_eq!(graph.get(&node), Some(&'a'));
+  /// ```
+  pub fn add_node(&mut self, value: N) -> NodeRef<N, E> {
+    let id = self.next_id;
+    self.next_id += 1;
+    let node = Rc::new(Node {
+      id,
+      value: RefCell::new(value),
+      edges: RefCell::new(Vec::new()),
+      graph: Rc::downgrade(&self.token),
+    });
+    let node_ref = NodeRef {
+      node: Rc::downgrade(&node),
+      id,
+    };
+    self.nodes.push(node);
+    node_ref
+  }
+
+  /// Removes `node` and the edges to and from it, and returns its value, or
+  /// returns `None` if the graph doesn't have the node.
+  ///
+  /// This looks at every edge, so it takes time proportional to the size of
+  /// the graph.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::Graph;
+  ///
+  /// let mut graph = Graph::new();
+  /// let a = graph.add_node('a');
+  /// let b = graph.add_node('b');
+  /// graph.add_edge(&a, &b, ());
+  ///
+  /// assert_eq!(graph.remove_node(&b), Some('b'));
+  /// assert_eq!(graph.remove_node(&b), None);
+  /// assert_eq!(graph.neighbors(&a).count(), 0);
+  /// ```
+  pub fn remove_node(&mut self, node: &NodeRef<N, E>) -> Option<N> {
+    let node = self.find(node)?;
+    let index = self.nodes.iter().position(|n| Rc::ptr_eq(n, &node))?;
+    drop(self.nodes.swap_remove(index));
+    let node = match Rc::try_unwrap(node) {
+      Ok(node) => node,
+      Err(_) => unreachable!("only the graph holds its nodes"),
+    };
+    self.edge_count -= node.edges.borrow().len();
+    // The edges to the node lead nowhere now.
+    for other in &self.nodes {
+      let mut edges = other.edges.borrow_mut();
+      let before = edges.len();
+      edges.retain(|edge| edge.to.node.strong_count() > 0);
+      self.edge_count -= before - edges.len();
+    }
+    Some(node.value.into_inner())
+  }
+
+  /// Returns `true` if the graph has `node`.
+  pub fn contains(&self, node: &NodeRef<N, E>) -> bool {
+    self.find(node).is_some()
+  }
+
+  /// Adds an edge from `from` to `to`, holding `value`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the graph doesn't have either node.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::Graph;
+  ///
+  /// let mut graph = Graph::new();
+  /// let a = graph.add_node('a');
+  /// graph.add_edge(&a, &a, "loop");
+  ///
+  /// assert_eq!(graph.edge(&a, &a), Some(&"loop"));
+  /// ```
+  pub fn add_edge(
+    &mut self,
+    from: &NodeRef<N, E>,
+    to: &NodeRef<N, E>,
+    value: E,
+  ) {
+    let (from, _) = match (self.find(from), self.find(to)) {
+      (Some(from), Some(to)) => (from, to),
+      _ => {
+        panic!("`Graph::add_edge` called with a node the graph doesn't have")
+      }
+    };
+    from.edges.borrow_mut().push(Edge {
+      to: to.clone(),
+      value,
+    });
+    self.edge_count += 1;
+  }
+
+  /// Removes the first edge added from `from` to `to`, and returns its
+  /// value, or returns `None` if there is no such edge.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::Graph;
+  ///
+  /// let mut graph = Graph::new();
+  /// let a = graph.add_node('a');
+  /// let b = graph.add_node('b');
+  /// graph.add_edge(&a, &b, 1);
+  /// graph.add_edge(&a, &b, 2);
+  ///
+  /// assert_eq!(graph.remove_edge(&a, &b), Some(1));
+  /// assert_eq!(graph.remove_edge(&b, &a), None);
+  /// assert_eq!(graph.edge_count(), 1);
+  /// ```
+  pub fn remove_edge(
+    &mut self,
+    from: &NodeRef<N, E>,
+    to: &NodeRef<N, E>,
+  ) -> Option<E> {
+    let from = self.find(from)?;
+    let mut edges = from.edges.borrow_mut();
+    let index = edges.iter().position(|edge| edge.to == *to)?;
+    self.edge_count -= 1;
+    Some(edges.remove(index).value)
+  }
+
+  /// Returns a reference to the value of `node`, or `None` if the graph
+  /// doesn't have the node.
+  pub fn get(&self, node: &NodeRef<N, E>) -> Option<&N> {
+    let node = self.find(node)?;
+    // SAFETY: The graph owns the node, and values are only mutated through
+    // `&mut self`, so the value is neither freed nor mutated while `self`
+    // is borrowed.
+    Some(unsafe { &*node.value.as_ptr() })
+  }
+
+  /// Returns a mutable reference to the value of `node`, or `None` if the
+  /// graph doesn't have the node.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::Graph;
+  ///
+  /// let mut graph: Graph<_, ()> = Graph::new();
+  /// let node = graph.add_node(1);
+  /// *graph.get_mut(&node).unwrap() += 1;
+  /// assert_eq!(graph[&node], 2);
+  /// ```
+  pub fn get_mut(&mut self, node: &NodeRef<N, E>) -> Option<&mut N> {
+    let node = self.find(node)?;
+    // SAFETY: As in `get`, and `self` is borrowed mutably, so no other
+    // reference to the value is alive.
+    Some(unsafe { &mut *node.value.as_ptr() })
+  }
+
+  /// Returns a reference to the value of the first edge added from `from`
+  /// to `to`, or `None` if there is no such edge.
+  pub fn edge(&self, from: &NodeRef<N, E>, to: &NodeRef<N, E>) -> Option<&E> {
+    self
+      .edges(from)
+      .find(|(target, _)| *target == to)
+      .map(|(_, value)| value)
+  }
+
+  /// Returns an iterator over the nodes of the graph.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::Graph;
+  ///
+  /// let mut graph: Graph<_, ()> = Graph::new();
+  /// graph.add_node(1);
+  /// graph.add_node(2);
+  ///
+  /// let sum: i32 = graph.nodes().map(|n| graph[&n]).sum();
+  /// assert_eq!(sum, 3);
+  /// ```
+  pub fn nodes(&self) -> Nodes<'_, N, E> {
+    Nodes {
+      nodes: self.nodes.iter(),
+    }
+  }
+
+  /// Returns an iterator over the nodes that the edges from `node` lead to,
+  /// once per edge.
+  ///
+  /// The iterator is empty if the graph doesn't have `node`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::Graph;
+  ///
+  /// let mut graph = Graph::new();
+  /// let a = graph.add_node('a');
+  /// let b = graph.add_node('b');
+  /// graph.add_edge(&a, &b, ());
+  ///
+  /// assert_eq!(graph.neighbors(&a).collect::<Vec<_>>(), [&b]);
+  /// assert_eq!(graph.neighbors(&b).count(), 0);
+  /// ```
+  pub fn neighbors(&self, node: &NodeRef<N, E>) -> Neighbors<'_, N, E> {
+    Neighbors {
+      edges: self.edges(node),
+    }
+  }
+
+  /// Returns an iterator over the edges from `node`, as the node each leads
+  /// to and its value.
+  ///
+  /// The iterator is empty if the graph doesn't have `node`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::Graph;
+  ///
+  /// let mut graph = Graph::new();
+  /// let a = graph.add_node('a');
+  /// let b = graph.add_node('b');
+  /// graph.add_edge(&a, &b, 5);
+  ///
+  /// let weights: Vec<_> = graph.edges(&a).map(|(_, w)| *w).collect();
+  /// assert_eq!(weights, [5]);
+  /// ```
+  pub fn edges(&self, node: &NodeRef<N, E>) -> Edges<'_, N, E> {
+    let edges: &[Edge<N, E>] = match self.find(node) {
+      // SAFETY: The graph owns the node, and edges are only changed through
+      // `&mut self`, so they are neither freed nor changed while `self` is
+      // borrowed.
+      Some(node) => unsafe { &*node.edges.as_ptr() },
+      None => &[],
+    };
+    Edges {
+      edges: edges.iter(),
+    }
+  }
+
+  /// Checks that the graph can't leak: that it is the only owner of each of
+  /// its nodes, so that no node owns another, and that every edge leads to
+  /// a node in the graph.
+  ///
+  /// # Panics
+  ///
+  /// Panics if a check fails.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::Graph;
+  ///
+  /// let mut graph = Graph::new();
+  /// let a = graph.add_node(());
+  /// let b = graph.add_node(());
+  /// graph.add_edge(&a, &b, ());
+  /// graph.add_edge(&b, &a, ());
+  /// graph.validate();
+  /// ```
+  pub fn validate(&self) {
+    let mut edges = 0;
+    for node in &self.nodes {
+      assert_eq!(
+        Rc::strong_count(node),
+        1,
+        "a graph node has an owner besides the graph"
+      );
+      for edge in node.edges.borrow().iter() {
+        assert!(
+          self.contains(&edge.to),
+          "a graph edge leads to a node outside the graph"
+        );
+        edges += 1;
+      }
+    }
+    assert_eq!(edges, self.edge_count, "the graph miscounts its edges");
+  }
+
+  /// Returns the node that `node` refers to, if it is in this graph.
+  fn find(&self, node: &NodeRef<N, E>) -> Option<Rc<Node<N, E>>> {
+    let rc = node.node.upgrade()?;
+    let token = rc.graph.upgrade()?;
+    if Rc::ptr_eq(&token, &self.token) {
+      Some(rc)
+    } else {
+      None
+    }
+  }
+}
+
+impl<N, E> Default for Graph<N, E> {
+  fn default() -> Graph<N, E> {
+    Graph::new()
+  }
+}
+
+impl<N, E> std::ops::Index<&NodeRef<N, E>> for Graph<N, E> {
+  type Output = N;
+
+  /// Returns a reference to the value of `node`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the graph doesn't have the node.
+  fn index(&self, node: &NodeRef<N, E>) -> &N {
+    self
+      .get(node)
+      .expect("indexed a graph with a node it doesn't have")
+  }
+}
+
+impl<N, E> std::ops::IndexMut<&NodeRef<N, E>> for Graph<N, E> {
+  fn index_mut(&mut self, node: &NodeRef<N, E>) -> &mut N {
+    self
+      .get_mut(node)
+      .expect("indexed a graph with a node it doesn't have")
+  }
+}
+
+impl<N: std::fmt::Debug, E: std::fmt::Debug> std::fmt::Debug for Graph<N, E> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_map()
+      .entries(self.nodes().map(|node| {
+        let edges: Vec<_> = self
+          .edges(&node)
+          .map(|(to, value)| (to.id, value))
+          .collect();
+        ((node.id, &self[&node]), edges)
+      }))
+      .finish()
+  }
+}
+
+/// A handle to a node of a [`Graph`].
+///
+/// A handle is weak: it doesn't keep the node in the graph, and goes stale
+/// once the node is removed. Handles compare equal, and hash the same, when
+/// they are to the same node.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct NodeRef<N, E> {
+  node: Weak<Node<N, E>>,
+  /// The id of the node, for hashing without upgrading.
+  id: usize,
+}
+
+impl<N, E> Clone for NodeRef<N, E> {
+  fn clone(&self) -> NodeRef<N, E> {
+    NodeRef {
+      node: self.node.clone(),
+      id: self.id,
+    }
+  }
+}
+
+impl<N, E> PartialEq for NodeRef<N, E> {
+  fn eq(&self, other: &Self) -> bool {
+    self.node.ptr_eq(&other.node)
+  }
+}
+
+impl<N, E> Eq for NodeRef<N, E> {}
+
+impl<N, E> std::hash::Hash for NodeRef<N, E> {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    self.id.hash(state);
+  }
+}
+
+impl<N, E> std::fmt::Debug for NodeRef<N, E> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("NodeRef").field(&self.id).finish()
+  }
+}
+
+/// An iterator over the nodes of a [`Graph`].
+///
+/// This struct is created by [`Graph::nodes`].
+pub struct Nodes<'a, N, E> {
+  nodes: std::slice::Iter<'a, Rc<Node<N, E>>>,
+}
+
+impl<N, E> Iterator for Nodes<'_, N, E> {
+  type Item = NodeRef<N, E>;
+
+  fn next(&mut self) -> Option<NodeRef<N, E>> {
+    let node = self.nodes.next()?;
+    Some(NodeRef {
+      node: Rc::downgrade(node),
+      id: node.id,
+    })
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.nodes.size_hint()
+  }
+}
+
+/// An iterator over the neighbors of a node in a [`Graph`].
+///
+/// This struct is created by [`Graph::neighbors`].
+pub struct Neighbors<'a, N, E> {
+  edges: Edges<'a, N, E>,
+}
+
+impl<'a, N, E> Iterator for Neighbors<'a, N, E> {
+  type Item = &'a NodeRef<N, E>;
+
+  fn next(&mut self) -> Option<&'a NodeRef<N, E>> {
+    self.edges.next().map(|(to, _)| to)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.edges.size_hint()
+  }
+}
+
+/// An iterator over the edges from a node in a [`Graph`].
+///
+/// This struct is created by [`Graph::edges`].
+pub struct Edges<'a, N, E> {
+  edges: std::slice::Iter<'a, Edge<N, E>>,
+}
+
+impl<'a, N, E> Iterator for Edges<'a, N, E> {
+  type Item = (&'a NodeRef<N, E>, &'a E);
+
+  fn next(&mut self) -> Option<(&'a NodeRef<N, E>, &'a E)> {
+    self.edges.next().map(|edge| (&edge.to, &edge.value))
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.edges.size_hint()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn cycles_are_freed() {
+    struct Counted(Rc<crate::Cell<usize>>);
+
+    impl Drop for Counted {
+      fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+      }
+    }
+
+    let dropped = Rc::new(crate::Cell::new(0));
+    let mut graph = Graph::new();
+    let nodes: Vec<_> = (0..4)
+      .map(|_| graph.add_node(Counted(Rc::clone(&dropped))))
+      .collect();
+    for (i, node) in nodes.iter().enumerate() {
+      graph.add_edge(node, &nodes[(i + 1) % nodes.len()], ());
+      graph.add_edge(node, node, ());
+    }
+    graph.validate();
+
+    drop(graph.remove_node(&nodes[0]));
+    assert_eq!((dropped.get(), graph.edge_count()), (1, 5));
+    graph.validate();
+
+    drop(graph);
+    assert_eq!(dropped.get(), 4);
+    assert!(nodes.iter().all(|node| node.node.upgrade().is_none()));
+  }
+
+  #[test]
+  fn nodes_of_other_graphs_are_not_found() {
+    let mut a = Graph::new();
+    let mut b = Graph::new();
+    let in_a = a.add_node(1);
+    let in_b = b.add_node(2);
+
+    assert!(!b.contains(&in_a));
+    assert_eq!(b.get(&in_a), None);
+    assert_eq!(b.remove_node(&in_a), None);
+    assert_eq!(b.remove_edge(&in_a, &in_b), None);
+    assert_eq!(b.neighbors(&in_a).count(), 0);
+    assert_ne!(in_a, in_b);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      b.add_edge(&in_b, &in_a, ())
+    }));
+    assert!(result.is_err());
+    assert_eq!(b.edge_count(), 0);
+  }
+
+  #[test]
+  fn edges_keep_their_order() {
+    let mut graph = Graph::new();
+    let a = graph.add_node('a');
+    let b = graph.add_node('b');
+    let c = graph.add_node('c');
+    graph.add_edge(&a, &c, 1);
+    graph.add_edge(&a, &b, 2);
+    graph.add_edge(&a, &c, 3);
+
+    let edges: Vec<_> = graph.edges(&a).map(|(n, w)| (graph[n], *w)).collect();
+    assert_eq!(edges, [('c', 1), ('b', 2), ('c', 3)]);
+    assert_eq!(graph.edge(&a, &c), Some(&1));
+
+    assert_eq!(graph.remove_edge(&a, &c), Some(1));
+    assert_eq!(graph.edge(&a, &c), Some(&3));
+    let visited: std::collections::HashSet<_> = graph.neighbors(&a).collect();
+    assert_eq!(visited.len(), 2);
+    assert_eq!(
+      format!("{:?}", graph),
+      "{(0, 'a'): [(1, 2), (2, 3)], (1, 'b'): [], (2, 'c'): []}"
+    );
+  }
+}
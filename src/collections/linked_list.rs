@@ -0,0 +1,793 @@
+//! A doubly linked list: [`LinkedList<T>`][LinkedList].
+//!
+//! Each node is an [`Rc<RefCell<_>>`][Rc]. The list owns its first node, and each node owns the
+//! next one, through strong pointers; each node points back at the one before it, and the list at
+//! its last node, through [`Weak`] ones. A node is owned once, so no two nodes own each other,
+//! and dropping the list frees every node.
+//!
+//! The nodes never leave the list: it hands out references to the values, borrowed from the list
+//! the way a `Vec` lends its elements. A [`CursorMut`] walks the list and edits it in the middle.
+//!
+//! ```
+//! use pointer::collections::LinkedList;
+//!
+//! let mut list: LinkedList<_> = (1..=3).collect();
+//! list.push_front(0);
+//! assert_eq!(list.pop_back(), Some(3));
+//!
+//! let mut cursor = list.cursor_front_mut();
+//! cursor.move_next();
+//! cursor.insert_after(10);
+//! assert_eq!(cursor.remove_current(), Some(1));
+//!
+//! assert_eq!(list.iter().copied().collect::<Vec<_>>(), [0, 10, 2]);
+//! ```
+//!
+//! [`Rc`]: crate::Rc
+//! [`Weak`]: crate::Weak
+
+use crate::{Rc, RefCell, Weak};
+
+type Link<T> = Rc<RefCell<Node<T>>>;
+
+struct Node<T> {
+  value: T,
+  next: Option<Link<T>>,
+  prev: Weak<RefCell<Node<T>>>,
+}
+
+/// Returns a weak pointer to `link`, or a dangling one for `None`.
+fn downgrade<T>(link: Option<&Link<T>>) -> Weak<RefCell<Node<T>>> {
+  link.map_or_else(Weak::new, Rc::downgrade)
+}
+
+/// Returns a reference to the node behind `link`, for as long as `'a`.
+///
+/// # Safety
+///
+/// The node must stay in a list that is borrowed for `'a`, and must not be
+/// mutated in that time.
+unsafe fn node<'a, T>(link: *const RefCell<Node<T>>) -> &'a Node<T> {
+  &*(*link).as_ptr()
+}
+
+/// A doubly linked list of shared nodes.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct LinkedList<T> {
+  head: Option<Link<T>>,
+  tail: Weak<RefCell<Node<T>>>,
+  len: usize,
+}
+
+/// A weak handle to a value in a [`LinkedList`], for moving or removing it
+/// without walking the list.
+pub(crate) struct Handle<T>(Weak<RefCell<Node<T>>>);
+
+impl<T> LinkedList<T> {
+  /// Creates an empty list.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LinkedList;
+  ///
+  /// let list: LinkedList<u32> = LinkedList::new();
+  /// assert!(list.is_empty());
+  /// ```
+  pub fn new() -> LinkedList<T> {
+    LinkedList {
+      head: None,
+      tail: Weak::new(),
+      len: 0,
+    }
+  }
+
+  /// Returns the number of values in the list.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Returns `true` if the list holds no values.
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Adds `value` to the front of the list.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LinkedList;
+  ///
+  /// let mut list = LinkedList::new();
+  /// list.push_front(2);
+  /// list.push_front(1);
+  /// assert_eq!(list.front(), Some(&1));
+  /// ```
+  pub fn push_front(&mut self, value: T) {
+    self.link_after(None, value);
+  }
+
+  /// Adds `value` to the back of the list.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LinkedList;
+  ///
+  /// let mut list = LinkedList::new();
+  /// list.push_back(1);
+  /// list.push_back(2);
+  /// assert_eq!(list.back(), Some(&2));
+  /// ```
+  pub fn push_back(&mut self, value: T) {
+    let tail = self.tail.upgrade();
+    self.link_after(tail, value);
+  }
+
+  /// Removes the first value of the list and returns it, or returns `None`
+  /// if the list is empty.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LinkedList;
+  ///
+  /// let mut list: LinkedList<_> = (1..=2).collect();
+  /// assert_eq!(list.pop_front(), Some(1));
+  /// assert_eq!(list.pop_front(), Some(2));
+  /// assert_eq!(list.pop_front(), None);
+  /// ```
+  pub fn pop_front(&mut self) -> Option<T> {
+    let head = self.head.clone()?;
+    Some(self.unlink(head))
+  }
+
+  /// Removes the last value of the list and returns it, or returns `None`
+  /// if the list is empty.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LinkedList;
+  ///
+  /// let mut list: LinkedList<_> = (1..=2).collect();
+  /// assert_eq!(list.pop_back(), Some(2));
+  /// assert_eq!(list.pop_back(), Some(1));
+  /// assert_eq!(list.pop_back(), None);
+  /// ```
+  pub fn pop_back(&mut self) -> Option<T> {
+    let tail = self.tail.upgrade()?;
+    Some(self.unlink(tail))
+  }
+
+  /// Returns a reference to the first value, or `None` if the list is
+  /// empty.
+  pub fn front(&self) -> Option<&T> {
+    self.iter().next()
+  }
+
+  /// Returns a mutable reference to the first value, or `None` if the list
+  /// is empty.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LinkedList;
+  ///
+  /// let mut list: LinkedList<_> = (1..=2).collect();
+  /// *list.front_mut().unwrap() = 10;
+  /// assert_eq!(list.front(), Some(&10));
+  /// ```
+  pub fn front_mut(&mut self) -> Option<&mut T> {
+    self.iter_mut().next()
+  }
+
+  /// Returns a reference to the last value, or `None` if the list is empty.
+  pub fn back(&self) -> Option<&T> {
+    self.iter().next_back()
+  }
+
+  /// Returns a mutable reference to the last value, or `None` if the list
+  /// is empty.
+  pub fn back_mut(&mut self) -> Option<&mut T> {
+    self.iter_mut().next_back()
+  }
+
+  /// Removes every value from the list.
+  pub fn clear(&mut self) {
+    while self.pop_front().is_some() {}
+  }
+
+  /// Returns an iterator over the values, front to back.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LinkedList;
+  ///
+  /// let list: LinkedList<_> = (1..=3).collect();
+  /// assert_eq!(list.iter().rev().collect::<Vec<_>>(), [&3, &2, &1]);
+  /// ```
+  pub fn iter(&self) -> Iter<'_, T> {
+    Iter {
+      front: self.head.as_ref().map(Rc::as_ptr),
+      back: self.tail.upgrade().as_ref().map(Rc::as_ptr),
+      len: self.len,
+      phantom: std::marker::PhantomData,
+    }
+  }
+
+  /// Returns an iterator over mutable references to the values, front to
+  /// back.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LinkedList;
+  ///
+  /// let mut list: LinkedList<_> = (1..=3).collect();
+  /// list.iter_mut().for_each(|n| *n *= 10);
+  /// assert_eq!(list.iter().collect::<Vec<_>>(), [&10, &20, &30]);
+  /// ```
+  pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+    IterMut {
+      front: self.head.as_ref().map(Rc::as_ptr),
+      back: self.tail.upgrade().as_ref().map(Rc::as_ptr),
+      len: self.len,
+      phantom: std::marker::PhantomData,
+    }
+  }
+
+  /// Returns a cursor on the first value, or on the "ghost" position if the
+  /// list is empty.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LinkedList;
+  ///
+  /// let mut list: LinkedList<_> = (1..=2).collect();
+  /// let mut cursor = list.cursor_front_mut();
+  /// assert_eq!(cursor.current(), Some(&mut 1));
+  /// ```
+  pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+    CursorMut {
+      current: downgrade(self.head.as_ref()),
+      list: self,
+    }
+  }
+
+  /// Returns a cursor on the last value, or on the "ghost" position if the
+  /// list is empty.
+  pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+    CursorMut {
+      current: self.tail.clone(),
+      list: self,
+    }
+  }
+
+  /// Adds `value` to the front of the list, and returns a handle to it.
+  pub(crate) fn push_front_handle(&mut self, value: T) -> Handle<T> {
+    Handle(Rc::downgrade(&self.link_after(None, value)))
+  }
+
+  /// Moves the value behind `handle` to the front of the list. Does nothing
+  /// if the value was removed.
+  ///
+  /// The handle must come from this list.
+  pub(crate) fn move_to_front(&mut self, handle: &Handle<T>) {
+    if let Some(node) = handle.0.upgrade() {
+      self.detach(&node);
+      self.attach_after(None, node);
+    }
+  }
+
+  /// Removes the value behind `handle` and returns it, or returns `None` if
+  /// it was removed already.
+  ///
+  /// The handle must come from this list.
+  pub(crate) fn remove(&mut self, handle: &Handle<T>) -> Option<T> {
+    let node = handle.0.upgrade()?;
+    Some(self.unlink(node))
+  }
+
+  /// Links a node holding `value` in after `prev`, or at the front for
+  /// `None`, and returns it.
+  fn link_after(&mut self, prev: Option<Link<T>>, value: T) -> Link<T> {
+    let node = Rc::new(RefCell::new(Node {
+      value,
+      next: None,
+      prev: Weak::new(),
+    }));
+    self.attach_after(prev, Rc::clone(&node));
+    node
+  }
+
+  /// Links the detached `node` in after `prev`, or at the front for `None`.
+  fn attach_after(&mut self, prev: Option<Link<T>>, node: Link<T>) {
+    let next = match &prev {
+      Some(prev) => prev.borrow_mut().next.take(),
+      None => self.head.take(),
+    };
+    {
+      let mut node_mut = node.borrow_mut();
+      node_mut.prev = downgrade(prev.as_ref());
+      match &next {
+        Some(next) => next.borrow_mut().prev = Rc::downgrade(&node),
+        None => self.tail = Rc::downgrade(&node),
+      }
+      node_mut.next = next;
+    }
+    match prev {
+      Some(prev) => prev.borrow_mut().next = Some(node),
+      None => self.head = Some(node),
+    }
+    self.len += 1;
+  }
+
+  /// Takes `node` out of the list, and returns its value.
+  fn unlink(&mut self, node: Link<T>) -> T {
+    self.detach(&node);
+    match Rc::try_unwrap(node) {
+      Ok(node) => node.into_inner().value,
+      Err(_) => unreachable!("an unlinked node has no other owner"),
+    }
+  }
+
+  /// Takes `node` out of the chain of links, leaving it detached.
+  fn detach(&mut self, node: &Link<T>) {
+    let (prev, next) = {
+      let mut node = node.borrow_mut();
+      (node.prev.upgrade(), node.next.take())
+    };
+    match &next {
+      Some(next) => next.borrow_mut().prev = downgrade(prev.as_ref()),
+      None => self.tail = downgrade(prev.as_ref()),
+    }
+    match prev {
+      Some(prev) => prev.borrow_mut().next = next,
+      None => self.head = next,
+    }
+    self.len -= 1;
+  }
+}
+
+impl<T> Drop for LinkedList<T> {
+  fn drop(&mut self) {
+    // Unlink the nodes one at a time; dropping the head would drop the
+    // whole chain recursively, and overflow the stack on a long list.
+    let mut next = self.head.take();
+    while let Some(node) = next {
+      next = node.borrow_mut().next.take();
+    }
+  }
+}
+
+impl<T> Default for LinkedList<T> {
+  fn default() -> LinkedList<T> {
+    LinkedList::new()
+  }
+}
+
+impl<T: Clone> Clone for LinkedList<T> {
+  fn clone(&self) -> LinkedList<T> {
+    self.iter().cloned().collect()
+  }
+}
+
+impl<T: PartialEq> PartialEq for LinkedList<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.len == other.len && self.iter().eq(other.iter())
+  }
+}
+
+impl<T: Eq> Eq for LinkedList<T> {}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for LinkedList<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_list().entries(self.iter()).finish()
+  }
+}
+
+impl<T> std::iter::FromIterator<T> for LinkedList<T> {
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> LinkedList<T> {
+    let mut list = LinkedList::new();
+    list.extend(iter);
+    list
+  }
+}
+
+impl<T> Extend<T> for LinkedList<T> {
+  fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+    for value in iter {
+      self.push_back(value);
+    }
+  }
+}
+
+impl<T> IntoIterator for LinkedList<T> {
+  type Item = T;
+  type IntoIter = IntoIter<T>;
+
+  fn into_iter(self) -> IntoIter<T> {
+    IntoIter { list: self }
+  }
+}
+
+impl<'a, T> IntoIterator for &'a LinkedList<T> {
+  type Item = &'a T;
+  type IntoIter = Iter<'a, T>;
+
+  fn into_iter(self) -> Iter<'a, T> {
+    self.iter()
+  }
+}
+
+impl<'a, T> IntoIterator for &'a mut LinkedList<T> {
+  type Item = &'a mut T;
+  type IntoIter = IterMut<'a, T>;
+
+  fn into_iter(self) -> IterMut<'a, T> {
+    self.iter_mut()
+  }
+}
+
+/// An iterator over the values of a [`LinkedList`].
+///
+/// Returned by [`LinkedList::iter`].
+pub struct Iter<'a, T> {
+  front: Option<*const RefCell<Node<T>>>,
+  back: Option<*const RefCell<Node<T>>>,
+  /// How many values are left, between `front` and `back`.
+  len: usize,
+  phantom: std::marker::PhantomData<&'a LinkedList<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+  type Item = &'a T;
+
+  fn next(&mut self) -> Option<&'a T> {
+    if self.len == 0 {
+      return None;
+    }
+    // SAFETY: The list is borrowed for `'a`, so its nodes are neither
+    // mutated nor freed.
+    let node = unsafe { node(self.front?) };
+    self.front = node.next.as_ref().map(Rc::as_ptr);
+    self.len -= 1;
+    Some(&node.value)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.len, Some(self.len))
+  }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+  fn next_back(&mut self) -> Option<&'a T> {
+    if self.len == 0 {
+      return None;
+    }
+    // SAFETY: As in `next`.
+    let node = unsafe { node(self.back?) };
+    self.back = node.prev.upgrade().as_ref().map(Rc::as_ptr);
+    self.len -= 1;
+    Some(&node.value)
+  }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> Clone for Iter<'_, T> {
+  fn clone(&self) -> Self {
+    Iter { ..*self }
+  }
+}
+
+/// An iterator over mutable references to the values of a [`LinkedList`].
+///
+/// Returned by [`LinkedList::iter_mut`].
+pub struct IterMut<'a, T> {
+  front: Option<*const RefCell<Node<T>>>,
+  back: Option<*const RefCell<Node<T>>>,
+  /// How many values are left, between `front` and `back`.
+  len: usize,
+  phantom: std::marker::PhantomData<&'a mut LinkedList<T>>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+  type Item = &'a mut T;
+
+  fn next(&mut self) -> Option<&'a mut T> {
+    if self.len == 0 {
+      return None;
+    }
+    let link = self.front?;
+    // SAFETY: The list is mutably borrowed for `'a`, and each node is
+    // visited once, so the references to the values don't overlap. The
+    // links aren't changed while the values are borrowed.
+    let node = unsafe { &mut *(*link).as_ptr() };
+    self.front = node.next.as_ref().map(Rc::as_ptr);
+    self.len -= 1;
+    Some(&mut node.value)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.len, Some(self.len))
+  }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+  fn next_back(&mut self) -> Option<&'a mut T> {
+    if self.len == 0 {
+      return None;
+    }
+    let link = self.back?;
+    // SAFETY: As in `next`.
+    let node = unsafe { &mut *(*link).as_ptr() };
+    self.back = node.prev.upgrade().as_ref().map(Rc::as_ptr);
+    self.len -= 1;
+    Some(&mut node.value)
+  }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+
+/// An owning iterator over the values of a [`LinkedList`].
+///
+/// Returned by [`LinkedList::into_iter`](IntoIterator::into_iter).
+pub struct IntoIter<T> {
+  list: LinkedList<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+  type Item = T;
+
+  fn next(&mut self) -> Option<T> {
+    self.list.pop_front()
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.list.len, Some(self.list.len))
+  }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+  fn next_back(&mut self) -> Option<T> {
+    self.list.pop_back()
+  }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+/// A cursor over a [`LinkedList`] that can edit it.
+///
+/// The cursor is on a value, or on the "ghost" position between the back and
+/// the front of the list: moving past either end gets there, and moving on
+/// from it wraps around to the other end.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct CursorMut<'a, T> {
+  list: &'a mut LinkedList<T>,
+  /// The node the cursor is on, or a dangling pointer on the ghost
+  /// position. It is weak, so that only the list owns its nodes.
+  current: Weak<RefCell<Node<T>>>,
+}
+
+impl<T> CursorMut<'_, T> {
+  /// Returns a mutable reference to the value the cursor is on, or `None`
+  /// on the ghost position.
+  pub fn current(&mut self) -> Option<&mut T> {
+    let node = self.current.upgrade()?.as_ptr();
+    // SAFETY: The list owns the node. The cursor borrows the list mutably,
+    // and the returned reference borrows the cursor, so nothing else can
+    // reach the node while it is alive.
+    Some(unsafe { &mut (*node).value })
+  }
+
+  /// Moves to the next value, from the back to the ghost position, or from
+  /// the ghost position to the front.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LinkedList;
+  ///
+  /// let mut list: LinkedList<_> = (1..=2).collect();
+  /// let mut cursor = list.cursor_front_mut();
+  ///
+  /// cursor.move_next();
+  /// assert_eq!(cursor.current(), Some(&mut 2));
+  /// cursor.move_next();
+  /// assert_eq!(cursor.current(), None);
+  /// cursor.move_next();
+  /// assert_eq!(cursor.current(), Some(&mut 1));
+  /// ```
+  pub fn move_next(&mut self) {
+    self.current = match self.current.upgrade() {
+      Some(node) => downgrade(node.borrow().next.as_ref()),
+      None => downgrade(self.list.head.as_ref()),
+    };
+  }
+
+  /// Moves to the previous value, from the front to the ghost position, or
+  /// from the ghost position to the back.
+  pub fn move_prev(&mut self) {
+    self.current = match self.current.upgrade() {
+      Some(node) => node.borrow().prev.clone(),
+      None => self.list.tail.clone(),
+    };
+  }
+
+  /// Inserts `value` after the cursor, or at the front on the ghost
+  /// position. The cursor stays where it is.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LinkedList;
+  ///
+  /// let mut list: LinkedList<_> = vec![1, 3].into_iter().collect();
+  /// list.cursor_front_mut().insert_after(2);
+  /// assert_eq!(list.iter().collect::<Vec<_>>(), [&1, &2, &3]);
+  /// ```
+  pub fn insert_after(&mut self, value: T) {
+    let prev = self.current.upgrade();
+    self.list.link_after(prev, value);
+  }
+
+  /// Inserts `value` before the cursor, or at the back on the ghost
+  /// position. The cursor stays where it is.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LinkedList;
+  ///
+  /// let mut list: LinkedList<_> = vec![1, 3].into_iter().collect();
+  /// list.cursor_back_mut().insert_before(2);
+  /// assert_eq!(list.iter().collect::<Vec<_>>(), [&1, &2, &3]);
+  /// ```
+  pub fn insert_before(&mut self, value: T) {
+    let prev = match self.current.upgrade() {
+      Some(node) => node.borrow().prev.upgrade(),
+      None => self.list.tail.upgrade(),
+    };
+    self.list.link_after(prev, value);
+  }
+
+  /// Removes the value the cursor is on and returns it, moving the cursor
+  /// to the next value. Returns `None` on the ghost position.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LinkedList;
+  ///
+  /// let mut list: LinkedList<_> = (1..=3).collect();
+  /// let mut cursor = list.cursor_front_mut();
+  ///
+  /// assert_eq!(cursor.remove_current(), Some(1));
+  /// assert_eq!(cursor.current(), Some(&mut 2));
+  /// ```
+  pub fn remove_current(&mut self) -> Option<T> {
+    let node = self.current.upgrade()?;
+    self.current = downgrade(node.borrow().next.as_ref());
+    Some(self.list.unlink(node))
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for CursorMut<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // SAFETY: The list owns the node, and the cursor borrows the list, so
+    // no reference to a value is alive while `self` is borrowed.
+    let current = self
+      .current
+      .upgrade()
+      .map(|link| unsafe { &node(Rc::as_ptr(&link)).value });
+    f.debug_struct("CursorMut")
+      .field("current", &current)
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Counts its drops in a shared counter.
+  struct Counted(std::rc::Rc<std::cell::Cell<usize>>);
+
+  impl Drop for Counted {
+    fn drop(&mut self) {
+      self.0.set(self.0.get() + 1);
+    }
+  }
+
+  #[test]
+  fn both_ends_push_and_pop() {
+    let mut list = LinkedList::new();
+    list.push_back(2);
+    list.push_front(1);
+    list.push_back(3);
+    assert_eq!(list.len(), 3);
+    assert_eq!((list.front(), list.back()), (Some(&1), Some(&3)));
+
+    assert_eq!(list.pop_back(), Some(3));
+    assert_eq!(list.pop_front(), Some(1));
+    assert_eq!(list.pop_back(), Some(2));
+    assert!(list.pop_front().is_none() && list.is_empty());
+
+    list.push_front(4);
+    assert_eq!((list.front(), list.back()), (Some(&4), Some(&4)));
+  }
+
+  #[test]
+  fn cursors_edit_in_the_middle() {
+    let mut list: LinkedList<_> = (1..=5).collect();
+    let mut cursor = list.cursor_front_mut();
+    while let Some(n) = cursor.current() {
+      if *n % 2 == 0 {
+        cursor.remove_current();
+      } else {
+        *n *= 10;
+        cursor.move_next();
+      }
+    }
+    // On the ghost position, inserting after goes to the front.
+    cursor.insert_after(0);
+    cursor.move_prev();
+    cursor.insert_before(49);
+
+    let values: Vec<_> = list.iter().copied().collect();
+    assert_eq!(values, [0, 10, 30, 49, 50]);
+    let back: Vec<_> = list.into_iter().rev().collect();
+    assert_eq!(back, [50, 49, 30, 10, 0]);
+  }
+
+  #[test]
+  fn every_value_is_dropped() {
+    let drops = std::rc::Rc::new(std::cell::Cell::new(0));
+    let mut list: LinkedList<_> =
+      (0..10).map(|_| Counted(drops.clone())).collect();
+
+    list.pop_front();
+    list.pop_back();
+    let mut cursor = list.cursor_front_mut();
+    cursor.move_next();
+    cursor.remove_current();
+    assert_eq!(drops.get(), 3);
+
+    drop(list);
+    assert_eq!(drops.get(), 10);
+  }
+
+  #[test]
+  fn long_lists_drop_without_recursing() {
+    let list: LinkedList<_> = (0..200_000).collect();
+    drop(list);
+  }
+
+  #[test]
+  #[cfg(feature = "leak-debug")]
+  fn no_node_outlives_the_list() {
+    let is_node = |a: &crate::rc::LiveAllocation| {
+      a.type_name().contains("linked_list::Node")
+    };
+    let mut list: LinkedList<_> = (0..10).collect();
+    list.cursor_back_mut().insert_before(10);
+    list.pop_front();
+    assert_eq!(
+      crate::rc::live_allocations()
+        .iter()
+        .filter(|a| is_node(a))
+        .count(),
+      10
+    );
+
+    drop(list);
+    assert!(!crate::rc::live_allocations().iter().any(is_node));
+  }
+}
@@ -0,0 +1,340 @@
+//! A persistent singly linked list with shared tails: [`List<T>`][List].
+//!
+//! A `List` never changes. [`cons`] makes a new list out of a value and an existing list, which
+//! becomes the tail of the new one without being copied: both lists point at the same nodes,
+//! through [`Rc`]s. So does [`tail`], and cloning a list copies one pointer. Many lists can share
+//! one tail, as the environments of an interpreter share their enclosing scopes, and keeping an old
+//! version of a list around costs nothing.
+//!
+//! A node is freed when the last list through it is dropped. A long list is dropped a node at a
+//! time, without recursing, and the dropping stops at the first node another list still shares.
+//!
+//! ```
+//! use pointer::collections::List;
+//!
+//! let tail: List<_> = vec![2, 3].into_iter().collect();
+//! let a = tail.cons(1);
+//! let b = tail.cons(10);
+//!
+//! assert_eq!(a.iter().collect::<Vec<_>>(), [&1, &2, &3]);
+//! assert_eq!(b.iter().collect::<Vec<_>>(), [&10, &2, &3]);
+//! assert!(a.tail().unwrap().ptr_eq(&b.tail().unwrap()));
+//! ```
+//!
+//! [`cons`]: List::cons
+//! [`tail`]: List::tail
+//! [`Rc`]: crate::Rc
+
+use crate::Rc;
+
+struct Node<T> {
+  value: T,
+  next: Option<Rc<Node<T>>>,
+}
+
+/// An immutable list whose tails are shared.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct List<T> {
+  head: Option<Rc<Node<T>>>,
+  len: usize,
+}
+
+impl<T> List<T> {
+  /// Creates an empty list.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::List;
+  ///
+  /// let list: List<i32> = List::new();
+  /// assert!(list.is_empty());
+  /// ```
+  pub fn new() -> List<T> {
+    List { head: None, len: 0 }
+  }
+
+  /// Returns a new list with `value` in front of this one, which it shares.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::List;
+  ///
+  /// let list = List::new().cons(2).cons(1);
+  /// assert_eq!(list.head(), Some(&1));
+  /// assert_eq!(list.len(), 2);
+  /// ```
+  pub fn cons(&self, value: T) -> List<T> {
+    List {
+      head: Some(Rc::new(Node {
+        value,
+        next: self.head.clone(),
+      })),
+      len: self.len + 1,
+    }
+  }
+
+  /// Returns the first value of the list, or `None` if it is empty.
+  pub fn head(&self) -> Option<&T> {
+    self.head.as_ref().map(|node| &node.value)
+  }
+
+  /// Returns the list after the first value, shared with this one, or
+  /// `None` if this list is empty.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::List;
+  ///
+  /// let list = List::new().cons('b').cons('a');
+  /// let tail = list.tail().unwrap();
+  ///
+  /// assert_eq!(tail.head(), Some(&'b'));
+  /// assert!(tail.tail().unwrap().is_empty());
+  /// assert!(tail.tail().unwrap().tail().is_none());
+  /// ```
+  pub fn tail(&self) -> Option<List<T>> {
+    let node = self.head.as_ref()?;
+    Some(List {
+      head: node.next.clone(),
+      len: self.len - 1,
+    })
+  }
+
+  /// Returns the first value of the list and the rest of it, or `None` if
+  /// the list is empty.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::List;
+  ///
+  /// fn sum(list: &List<i32>) -> i32 {
+  ///   match list.uncons() {
+  ///     Some((head, tail)) => head + sum(&tail),
+  ///     None => 0,
+  ///   }
+  /// }
+  ///
+  /// let list: List<_> = (1..=4).collect();
+  /// assert_eq!(sum(&list), 10);
+  /// ```
+  pub fn uncons(&self) -> Option<(&T, List<T>)> {
+    Some((self.head()?, self.tail()?))
+  }
+
+  /// Returns the number of values in the list.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Returns `true` if the list holds no values.
+  pub fn is_empty(&self) -> bool {
+    self.head.is_none()
+  }
+
+  /// Returns an iterator over the values of the list, from the front.
+  pub fn iter(&self) -> Iter<'_, T> {
+    Iter {
+      next: self.head.as_deref(),
+      len: self.len,
+    }
+  }
+
+  /// Returns `true` if the two lists are the same nodes, rather than equal
+  /// values.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::List;
+  ///
+  /// let a = List::new().cons(1);
+  /// let b = List::new().cons(1);
+  ///
+  /// assert!(a == b);
+  /// assert!(!a.ptr_eq(&b));
+  /// assert!(a.ptr_eq(&a.clone()));
+  /// ```
+  pub fn ptr_eq(&self, other: &List<T>) -> bool {
+    match (&self.head, &other.head) {
+      (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+      (None, None) => true,
+      _ => false,
+    }
+  }
+}
+
+impl<T: Clone> List<T> {
+  /// Returns the list in reverse order.
+  ///
+  /// This copies every value, since no tail of the result is a tail of this
+  /// list.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::List;
+  ///
+  /// let list: List<_> = (1..=3).collect();
+  /// assert_eq!(list.reverse().iter().collect::<Vec<_>>(), [&3, &2, &1]);
+  /// ```
+  pub fn reverse(&self) -> List<T> {
+    self
+      .iter()
+      .fold(List::new(), |reversed, value| reversed.cons(value.clone()))
+  }
+}
+
+impl<T> Drop for List<T> {
+  fn drop(&mut self) {
+    // Unlink the nodes this list owns alone one at a time; dropping the
+    // head would drop the whole chain recursively, and overflow the stack
+    // on a long list.
+    let mut next = self.head.take();
+    while let Some(node) = next {
+      next = match Rc::try_unwrap(node) {
+        Ok(mut node) => node.next.take(),
+        // Another list shares the rest.
+        Err(_) => None,
+      };
+    }
+  }
+}
+
+impl<T> Clone for List<T> {
+  fn clone(&self) -> List<T> {
+    List {
+      head: self.head.clone(),
+      len: self.len,
+    }
+  }
+}
+
+impl<T> Default for List<T> {
+  fn default() -> List<T> {
+    List::new()
+  }
+}
+
+impl<T: PartialEq> PartialEq for List<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.len == other.len && self.iter().eq(other.iter())
+  }
+}
+
+impl<T: Eq> Eq for List<T> {}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for List<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_list().entries(self).finish()
+  }
+}
+
+impl<T> std::iter::FromIterator<T> for List<T> {
+  /// Collects the values into a list, in the order they come.
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> List<T> {
+    let values: Vec<T> = iter.into_iter().collect();
+    values
+      .into_iter()
+      .rev()
+      .fold(List::new(), |list, value| list.cons(value))
+  }
+}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+  type Item = &'a T;
+  type IntoIter = Iter<'a, T>;
+
+  fn into_iter(self) -> Iter<'a, T> {
+    self.iter()
+  }
+}
+
+/// An iterator over the values of a [`List`].
+///
+/// This struct is created by [`List::iter`].
+pub struct Iter<'a, T> {
+  next: Option<&'a Node<T>>,
+  len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+  type Item = &'a T;
+
+  fn next(&mut self) -> Option<&'a T> {
+    let node = self.next?;
+    self.next = node.next.as_deref();
+    self.len -= 1;
+    Some(&node.value)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.len, Some(self.len))
+  }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> Clone for Iter<'_, T> {
+  fn clone(&self) -> Self {
+    Iter {
+      next: self.next,
+      len: self.len,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tails_are_shared() {
+    let shared: List<_> = (2..=3).collect();
+    let a = shared.cons(1);
+    let b = shared.cons(1).cons(0);
+
+    assert_eq!(Rc::strong_count(shared.head.as_ref().unwrap()), 3);
+    assert_eq!(a, b.tail().unwrap());
+    assert!(!a.ptr_eq(&b.tail().unwrap()));
+    assert!(a.tail().unwrap().ptr_eq(&shared));
+
+    drop((a, b));
+    assert_eq!(Rc::strong_count(shared.head.as_ref().unwrap()), 1);
+    assert_eq!(format!("{:?}", shared), "[2, 3]");
+  }
+
+  #[test]
+  fn dropping_stops_at_shared_nodes() {
+    let dropped = Rc::new(crate::Cell::new(0));
+
+    struct Counted(Rc<crate::Cell<usize>>);
+
+    impl Drop for Counted {
+      fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+      }
+    }
+
+    let tail: List<_> = (0..3).map(|_| Counted(Rc::clone(&dropped))).collect();
+    let list = tail.cons(Counted(Rc::clone(&dropped)));
+    drop(list);
+    assert_eq!(dropped.get(), 1);
+    assert_eq!(tail.len(), 3);
+    drop(tail);
+    assert_eq!(dropped.get(), 4);
+  }
+
+  #[test]
+  fn long_lists_drop_without_recursing() {
+    let list: List<_> = (0..200_000).collect();
+    assert_eq!(list.iter().len(), 200_000);
+    let tail = list.tail().unwrap();
+    drop(list);
+    assert_eq!(tail.head(), Some(&1));
+  }
+}
@@ -0,0 +1,356 @@
+//! A cache that forgets the least recently used values first: [`LruCache<K, V>`][LruCache].
+//!
+//! The cache holds at most its capacity of values. Inserting past it evicts the value that was
+//! used longest ago. Values are handed out as [`Rc<V>`][Rc], so evicting a value only drops the
+//! cache's share of it: a caller still holding the value keeps it alive, and the cache doesn't have
+//! to know.
+//!
+//! The keys are kept in a [`LinkedList`], from the most recently used to the least. Each entry of
+//! the cache holds a weak handle to its key's node, so a hit moves the key to the front without
+//! walking the list, and an eviction pops the key from the back. The cache takes `&self`, through
+//! [`RefCell`]s, so it can be shared as a plain `Rc<LruCache<_, _>>`.
+//!
+//! ```
+//! use pointer::collections::LruCache;
+//!
+//! let cache = LruCache::new(2);
+//! cache.insert("a", 1);
+//! cache.insert("b", 2);
+//! let a = cache.get(&"a").unwrap();
+//!
+//! // "b" is now the least recently used.
+//! cache.insert("c", 3);
+//! assert!(cache.get(&"b").is_none());
+//!
+//! cache.insert("d", 4);
+//! assert!(cache.get(&"a").is_none());
+//! // The evicted value lives on while it is held.
+//! assert_eq!(*a, 1);
+//! ```
+//!
+//! [`LinkedList`]: crate::collections::LinkedList
+//! [`RefCell`]: crate::RefCell
+//! [Rc]: crate::Rc
+
+use super::linked_list::{Handle, LinkedList};
+use crate::{Rc, RefCell};
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+struct Entry<K, V> {
+  value: Rc<V>,
+  /// The key's node in the recency list.
+  node: Handle<K>,
+}
+
+/// A cache of shared values that evicts the least recently used first.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct LruCache<K, V> {
+  entries: RefCell<HashMap<K, Entry<K, V>>>,
+  /// The cached keys, the most recently used first.
+  order: RefCell<LinkedList<K>>,
+  capacity: usize,
+}
+
+impl<K: Clone + Eq + Hash, V> LruCache<K, V> {
+  /// Creates an empty cache that holds at most `capacity` values.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LruCache;
+  ///
+  /// let cache: LruCache<u32, String> = LruCache::new(16);
+  /// assert_eq!(cache.capacity(), 16);
+  /// assert!(cache.is_empty());
+  /// ```
+  pub fn new(capacity: usize) -> LruCache<K, V> {
+    LruCache {
+      entries: RefCell::new(HashMap::new()),
+      order: RefCell::new(LinkedList::new()),
+      capacity,
+    }
+  }
+
+  /// Returns the value cached for `key`, and marks it as the most recently
+  /// used.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LruCache;
+  ///
+  /// let cache = LruCache::new(1);
+  /// cache.insert(1, "one");
+  ///
+  /// assert_eq!(cache.get(&1).as_deref(), Some(&"one"));
+  /// assert!(cache.get(&2).is_none());
+  /// ```
+  pub fn get(&self, key: &K) -> Option<Rc<V>> {
+    let entries = self.entries.borrow();
+    let entry = entries.get(key)?;
+    self.order.borrow_mut().move_to_front(&entry.node);
+    Some(Rc::clone(&entry.value))
+  }
+
+  /// Returns the value cached for `key`, without marking it as used.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LruCache;
+  ///
+  /// let cache = LruCache::new(2);
+  /// cache.insert('a', 1);
+  /// cache.insert('b', 2);
+  /// cache.peek(&'a');
+  /// cache.insert('c', 3);
+  ///
+  /// // Peeking didn't save 'a' from eviction.
+  /// assert!(cache.peek(&'a').is_none());
+  /// ```
+  pub fn peek(&self, key: &K) -> Option<Rc<V>> {
+    let entries = self.entries.borrow();
+    entries.get(key).map(|entry| Rc::clone(&entry.value))
+  }
+
+  /// Returns `true` if a value is cached for `key`, without marking it as
+  /// used.
+  pub fn contains(&self, key: &K) -> bool {
+    self.entries.borrow().contains_key(key)
+  }
+
+  /// Caches `value` for `key` as the most recently used value, evicting the
+  /// least recently used one if the cache is full, and returns the value
+  /// that was cached for `key` before.
+  ///
+  /// A cache with a capacity of zero caches nothing.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LruCache;
+  ///
+  /// let cache = LruCache::new(2);
+  /// assert!(cache.insert("k", 1).is_none());
+  /// assert_eq!(cache.insert("k", 2).as_deref(), Some(&1));
+  /// assert_eq!(cache.len(), 1);
+  /// ```
+  pub fn insert(&self, key: K, value: V) -> Option<Rc<V>> {
+    self.insert_rc(key, Rc::new(value))
+  }
+
+  /// Caches the shared `value` for `key`, as [`insert`](LruCache::insert)
+  /// does.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LruCache;
+  /// use pointer::Rc;
+  ///
+  /// let cache = LruCache::new(2);
+  /// let value = Rc::new(vec![1, 2, 3]);
+  /// cache.insert_rc("v", Rc::clone(&value));
+  ///
+  /// assert!(Rc::ptr_eq(&cache.get(&"v").unwrap(), &value));
+  /// ```
+  pub fn insert_rc(&self, key: K, value: Rc<V>) -> Option<Rc<V>> {
+    if self.capacity == 0 {
+      return None;
+    }
+    let old = self.remove(&key);
+    let evicted = if self.len() == self.capacity {
+      self.evict()
+    } else {
+      None
+    };
+    let node = self.order.borrow_mut().push_front_handle(key.clone());
+    self.entries.borrow_mut().insert(key, Entry { value, node });
+    // The evicted value is only dropped once the cache isn't borrowed, in
+    // case its destructor uses the cache.
+    drop(evicted);
+    old
+  }
+
+  /// Returns the value cached for `key`, marking it as the most recently
+  /// used, or computes it with `f` and caches it.
+  ///
+  /// The cache isn't borrowed while `f` runs, so `f` may use it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LruCache;
+  ///
+  /// let cache = LruCache::new(4);
+  /// let len = cache.get_or_insert_with("hello", || "hello".len());
+  /// assert_eq!(*len, 5);
+  /// assert_eq!(*cache.get_or_insert_with("hello", || 0), 5);
+  /// ```
+  pub fn get_or_insert_with(&self, key: K, f: impl FnOnce() -> V) -> Rc<V> {
+    if let Some(value) = self.get(&key) {
+      return value;
+    }
+    let value = Rc::new(f());
+    self.insert_rc(key, Rc::clone(&value));
+    value
+  }
+
+  /// Removes the value cached for `key`, and returns it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LruCache;
+  ///
+  /// let cache = LruCache::new(2);
+  /// cache.insert(1, 'a');
+  ///
+  /// assert_eq!(cache.remove(&1).as_deref(), Some(&'a'));
+  /// assert!(cache.remove(&1).is_none());
+  /// ```
+  pub fn remove(&self, key: &K) -> Option<Rc<V>> {
+    let entry = self.entries.borrow_mut().remove(key)?;
+    self.order.borrow_mut().remove(&entry.node);
+    Some(entry.value)
+  }
+
+  /// Removes every cached value.
+  pub fn clear(&self) {
+    let entries = std::mem::take(&mut *self.entries.borrow_mut());
+    self.order.borrow_mut().clear();
+    drop(entries);
+  }
+
+  /// Returns the number of cached values.
+  pub fn len(&self) -> usize {
+    self.entries.borrow().len()
+  }
+
+  /// Returns `true` if no value is cached.
+  pub fn is_empty(&self) -> bool {
+    self.entries.borrow().is_empty()
+  }
+
+  /// Returns the most values the cache holds.
+  pub fn capacity(&self) -> usize {
+    self.capacity
+  }
+
+  /// Returns the cached keys, the most recently used first, without marking
+  /// them as used.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::LruCache;
+  ///
+  /// let cache = LruCache::new(3);
+  /// for n in 1..=3 {
+  ///   cache.insert(n, ());
+  /// }
+  /// cache.get(&1);
+  ///
+  /// assert_eq!(cache.keys(), [1, 3, 2]);
+  /// ```
+  pub fn keys(&self) -> Vec<K> {
+    self.order.borrow().iter().cloned().collect()
+  }
+
+  /// Removes the least recently used value, and returns it.
+  fn evict(&self) -> Option<Rc<V>> {
+    let key = self.order.borrow_mut().pop_back()?;
+    let entry = self.entries.borrow_mut().remove(&key)?;
+    Some(entry.value)
+  }
+}
+
+impl<K: std::fmt::Debug, V: std::fmt::Debug> std::fmt::Debug
+  for LruCache<K, V>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    /// The entries, shown as a map from keys to values.
+    struct Entries<'a, K, V>(&'a HashMap<K, Entry<K, V>>);
+
+    impl<K: std::fmt::Debug, V: std::fmt::Debug> std::fmt::Debug
+      for Entries<'_, K, V>
+    {
+      fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+          .entries(self.0.iter().map(|(key, entry)| (key, &*entry.value)))
+          .finish()
+      }
+    }
+
+    match self.entries.try_borrow() {
+      Ok(entries) => f
+        .debug_struct("LruCache")
+        .field("entries", &Entries(&entries))
+        .field("capacity", &self.capacity)
+        .finish(),
+      Err(_) => f.write_str("LruCache { <borrowed> }"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn the_least_recently_used_value_is_evicted() {
+    let cache = LruCache::new(3);
+    for n in 0..3 {
+      cache.insert(n, n * 10);
+    }
+    cache.get(&0);
+    cache.insert(1, 11);
+    cache.insert(3, 30);
+
+    assert_eq!(cache.keys(), [3, 1, 0]);
+    assert!(cache.peek(&2).is_none());
+    assert_eq!(cache.get(&1).as_deref(), Some(&11));
+
+    cache.remove(&3);
+    cache.insert(4, 40);
+    cache.insert(5, 50);
+    assert_eq!(cache.keys(), [5, 4, 1]);
+    assert_eq!(cache.len(), 3);
+  }
+
+  #[test]
+  fn evicted_values_outlive_the_cache_entry() {
+    let cache = LruCache::new(1);
+    let first = cache.get_or_insert_with("first", || String::from("kept"));
+    cache.insert("second", String::from("newer"));
+
+    assert!(!cache.contains(&"first"));
+    assert_eq!(Rc::strong_count(&first), 1);
+    assert_eq!(*first, "kept");
+
+    let none = LruCache::new(0);
+    assert!(none.insert((), 1).is_none());
+    assert!(none.is_empty());
+  }
+
+  #[test]
+  fn destructors_may_use_the_cache() {
+    struct Reentrant(Rc<LruCache<u32, Reentrant>>);
+
+    impl Drop for Reentrant {
+      fn drop(&mut self) {
+        assert!(self.0.peek(&99).is_none());
+      }
+    }
+
+    let cache = Rc::new(LruCache::new(1));
+    cache.insert(1, Reentrant(Rc::clone(&cache)));
+    cache.insert(2, Reentrant(Rc::clone(&cache)));
+    assert_eq!(cache.keys(), [2]);
+    cache.clear();
+    assert!(cache.is_empty());
+  }
+}
@@ -0,0 +1,22 @@
+//! Collections built out of the crate's shared pointers.
+//!
+//! Each of these is a structure that people build by hand out of [`Rc`] and [`Weak`], and then
+//! leak: a strong pointer in each direction of a link makes a cycle that is never freed. Here every
+//! link that points back, towards what owns it, is weak, so dropping a collection frees it whole.
+//!
+//! [`Rc`]: crate::Rc
+//! [`Weak`]: crate::Weak
+
+pub mod graph;
+pub mod linked_list;
+pub mod list;
+pub mod lru_cache;
+pub mod persistent_map;
+pub mod tree;
+
+pub use graph::{Graph, NodeRef};
+pub use linked_list::{CursorMut, LinkedList};
+pub use list::List;
+pub use lru_cache::LruCache;
+pub use persistent_map::PersistentMap;
+pub use tree::{Tree, TreeNode};
@@ -0,0 +1,584 @@
+//! A persistent hash map with shared structure: [`PersistentMap<K, V>`][PersistentMap].
+//!
+//! The map is a hash array mapped trie: a tree of nodes of up to 32 entries each, indexed by five
+//! bits of the key's hash per level, so a lookup visits a handful of nodes. The nodes are held by
+//! [`Rc`]s, and cloning a map copies one of them. The clones go on sharing every node until one is
+//! changed. [`insert`] and [`remove`] then copy only the nodes on the path from the root to the
+//! key, through [`Rc::make_mut`]: a node the map holds alone is changed in place, and a node it
+//! shares is cloned first, leaving the other maps as they were. Keeping an old version of a map,
+//! to undo to or to read from while building the next, costs only the nodes that differ.
+//!
+//! Keys whose hashes are equal in all 64 bits share a bucket in the trie. The hashes are computed
+//! with [`DefaultHasher::new`], which is the same for every map, so versions of a map agree on
+//! them.
+//!
+//! ```
+//! use pointer::collections::PersistentMap;
+//!
+//! let mut v1 = PersistentMap::new();
+//! v1.insert("a", 1);
+//! v1.insert("b", 2);
+//!
+//! let mut v2 = v1.clone();
+//! v2.insert("a", 10);
+//! v2.remove(&"b");
+//!
+//! assert_eq!((v1.get(&"a"), v1.get(&"b")), (Some(&1), Some(&2)));
+//! assert_eq!((v2.get(&"a"), v2.get(&"b")), (Some(&10), None));
+//! ```
+//!
+//! [`Rc`]: crate::Rc
+//! [`Rc::make_mut`]: crate::Rc::make_mut
+//! [`insert`]: PersistentMap::insert
+//! [`remove`]: PersistentMap::remove
+//! [`DefaultHasher::new`]: std::collections::hash_map::DefaultHasher::new
+
+use crate::Rc;
+
+use std::hash::{Hash, Hasher};
+
+/// The number of hash bits that index a node.
+const BITS: u32 = 5;
+
+#[derive(Clone)]
+enum Entry<K, V> {
+  Leaf {
+    hash: u64,
+    key: K,
+    value: V,
+  },
+  Branch(Rc<Node<K, V>>),
+  /// The keys whose hashes are all `hash`, at least two of them.
+  Collision {
+    hash: u64,
+    pairs: Rc<Vec<(K, V)>>,
+  },
+}
+
+#[derive(Clone)]
+struct Node<K, V> {
+  /// Which of the 32 slots of the node are in `entries`.
+  bitmap: u32,
+  entries: Vec<Entry<K, V>>,
+}
+
+impl<K, V> Node<K, V> {
+  fn new() -> Node<K, V> {
+    Node {
+      bitmap: 0,
+      entries: Vec::new(),
+    }
+  }
+
+  /// Returns the bit of the slot for `hash` at `shift`, and the index its
+  /// entry has, or would have, in `entries`.
+  fn slot(&self, hash: u64, shift: u32) -> (u32, usize) {
+    let bit = 1 << ((hash >> shift) & 31);
+    (bit, (self.bitmap & (bit - 1)).count_ones() as usize)
+  }
+}
+
+/// A hash map whose clones share their structure.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct PersistentMap<K, V> {
+  root: Rc<Node<K, V>>,
+  len: usize,
+}
+
+impl<K, V> PersistentMap<K, V> {
+  /// Creates an empty map.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::PersistentMap;
+  ///
+  /// let map: PersistentMap<String, u32> = PersistentMap::new();
+  /// assert!(map.is_empty());
+  /// ```
+  pub fn new() -> PersistentMap<K, V> {
+    PersistentMap {
+      root: Rc::new(Node::new()),
+      len: 0,
+    }
+  }
+
+  /// Returns the number of entries in the map.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Returns `true` if the map has no entries.
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Returns an iterator over the entries of the map, in no particular
+  /// order.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::PersistentMap;
+  ///
+  /// let map: PersistentMap<_, _> = (1..=3).map(|n| (n, n * n)).collect();
+  /// let mut squares: Vec<_> = map.iter().map(|(_, v)| *v).collect();
+  /// squares.sort();
+  /// assert_eq!(squares, [1, 4, 9]);
+  /// ```
+  pub fn iter(&self) -> Iter<'_, K, V> {
+    Iter {
+      nodes: vec![self.root.entries.iter()],
+      pairs: [].iter(),
+      len: self.len,
+    }
+  }
+
+  /// Returns `true` if the two maps are the same nodes, as a map and an
+  /// unchanged clone of it are.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::PersistentMap;
+  ///
+  /// let mut a = PersistentMap::new();
+  /// a.insert(1, ());
+  /// let mut b = a.clone();
+  /// assert!(a.ptr_eq(&b));
+  ///
+  /// b.insert(2, ());
+  /// assert!(!a.ptr_eq(&b));
+  /// ```
+  pub fn ptr_eq(&self, other: &PersistentMap<K, V>) -> bool {
+    Rc::ptr_eq(&self.root, &other.root)
+  }
+}
+
+impl<K: Eq + Hash, V> PersistentMap<K, V> {
+  /// Returns a reference to the value for `key`, if there is one.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::PersistentMap;
+  ///
+  /// let mut map = PersistentMap::new();
+  /// map.insert('x', 1);
+  ///
+  /// assert_eq!(map.get(&'x'), Some(&1));
+  /// assert_eq!(map.get(&'y'), None);
+  /// ```
+  pub fn get(&self, key: &K) -> Option<&V> {
+    let hash = hash(key);
+    let mut node = &*self.root;
+    let mut shift = 0;
+    loop {
+      let (bit, index) = node.slot(hash, shift);
+      if node.bitmap & bit == 0 {
+        return None;
+      }
+      match &node.entries[index] {
+        Entry::Leaf { key: k, value, .. } => {
+          return if k == key { Some(value) } else { None };
+        }
+        Entry::Branch(child) => node = child,
+        Entry::Collision { hash: h, pairs } => {
+          if *h != hash {
+            return None;
+          }
+          return pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+        }
+      }
+      shift += BITS;
+    }
+  }
+
+  /// Returns `true` if the map has an entry for `key`.
+  pub fn contains_key(&self, key: &K) -> bool {
+    self.get(key).is_some()
+  }
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> PersistentMap<K, V> {
+  /// Maps `key` to `value`, and returns the value it was mapped to before.
+  ///
+  /// The nodes on the path to the key that the map shares with its clones
+  /// are copied; all others stay shared.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::PersistentMap;
+  ///
+  /// let mut map = PersistentMap::new();
+  /// assert_eq!(map.insert("k", 1), None);
+  /// assert_eq!(map.insert("k", 2), Some(1));
+  /// assert_eq!(map.len(), 1);
+  /// ```
+  pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+    let hash = hash(&key);
+    let old = insert(Rc::make_mut(&mut self.root), 0, hash, key, value);
+    if old.is_none() {
+      self.len += 1;
+    }
+    old
+  }
+
+  /// Removes the entry for `key`, and returns its value.
+  ///
+  /// Nothing is copied if the map has no entry for `key`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::PersistentMap;
+  ///
+  /// let mut map = PersistentMap::new();
+  /// map.insert(1, "one");
+  /// let before = map.clone();
+  ///
+  /// assert_eq!(map.remove(&1), Some("one"));
+  /// assert_eq!(map.remove(&1), None);
+  /// assert_eq!(before.get(&1), Some(&"one"));
+  /// ```
+  pub fn remove(&mut self, key: &K) -> Option<V> {
+    if !self.contains_key(key) {
+      return None;
+    }
+    let hash = hash(key);
+    let value = remove(Rc::make_mut(&mut self.root), 0, hash, key)?;
+    self.len -= 1;
+    Some(value)
+  }
+}
+
+/// Hashes `key` the same way for every map.
+fn hash<K: Hash>(key: &K) -> u64 {
+  let mut hasher = std::collections::hash_map::DefaultHasher::new();
+  key.hash(&mut hasher);
+  hasher.finish()
+}
+
+/// Inserts into `node`, which is at `shift`, and returns the value replaced.
+fn insert<K: Clone + Eq, V: Clone>(
+  node: &mut Node<K, V>,
+  shift: u32,
+  hash: u64,
+  key: K,
+  value: V,
+) -> Option<V> {
+  let (bit, index) = node.slot(hash, shift);
+  if node.bitmap & bit == 0 {
+    node.bitmap |= bit;
+    node.entries.insert(index, Entry::Leaf { hash, key, value });
+    return None;
+  }
+  let entry = &mut node.entries[index];
+  match entry {
+    Entry::Leaf {
+      hash: h,
+      key: k,
+      value: v,
+    } if *h == hash => {
+      if *k == key {
+        return Some(std::mem::replace(v, value));
+      }
+      let pairs = vec![(k.clone(), v.clone()), (key, value)];
+      *entry = Entry::Collision {
+        hash,
+        pairs: Rc::new(pairs),
+      };
+      None
+    }
+    Entry::Branch(child) => {
+      insert(Rc::make_mut(child), shift + BITS, hash, key, value)
+    }
+    Entry::Collision { hash: h, pairs } if *h == hash => {
+      let pairs = Rc::make_mut(pairs);
+      match pairs.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, v)) => Some(std::mem::replace(v, value)),
+        None => {
+          pairs.push((key, value));
+          None
+        }
+      }
+    }
+    // A leaf or a bucket for another hash: push it down a level, next to
+    // the new leaf.
+    Entry::Leaf { hash: other, .. } | Entry::Collision { hash: other, .. } => {
+      let other = *other;
+      let old = node.entries.remove(index);
+      let new = Entry::Leaf { hash, key, value };
+      let branch = split(shift + BITS, (other, old), (hash, new));
+      node.entries.insert(index, Entry::Branch(Rc::new(branch)));
+      None
+    }
+  }
+}
+
+/// Returns a node at `shift` that holds the two entries, whose hashes
+/// differ, nesting it as deep as their hashes agree.
+fn split<K, V>(
+  shift: u32,
+  a: (u64, Entry<K, V>),
+  b: (u64, Entry<K, V>),
+) -> Node<K, V> {
+  let mut node = Node::new();
+  let (bit_a, _) = node.slot(a.0, shift);
+  let (bit_b, _) = node.slot(b.0, shift);
+  if bit_a == bit_b {
+    node.bitmap = bit_a;
+    node
+      .entries
+      .push(Entry::Branch(Rc::new(split(shift + BITS, a, b))));
+  } else {
+    node.bitmap = bit_a | bit_b;
+    node.entries = if bit_a < bit_b {
+      vec![a.1, b.1]
+    } else {
+      vec![b.1, a.1]
+    };
+  }
+  node
+}
+
+/// Removes `key` from `node`, which is at `shift`, and returns its value.
+fn remove<K: Clone + Eq, V: Clone>(
+  node: &mut Node<K, V>,
+  shift: u32,
+  hash: u64,
+  key: &K,
+) -> Option<V> {
+  let (bit, index) = node.slot(hash, shift);
+  if node.bitmap & bit == 0 {
+    return None;
+  }
+  match &mut node.entries[index] {
+    Entry::Leaf { key: k, .. } => {
+      if k != key {
+        return None;
+      }
+      node.bitmap &= !bit;
+      match node.entries.remove(index) {
+        Entry::Leaf { value, .. } => Some(value),
+        _ => unreachable!("the entry is a leaf"),
+      }
+    }
+    Entry::Branch(child) => {
+      let child = Rc::make_mut(child);
+      let value = remove(child, shift + BITS, hash, key)?;
+      // A branch left with one leaf or bucket gives it back to this node.
+      let lone = child.entries.len() == 1
+        && !matches!(child.entries[0], Entry::Branch(_));
+      if lone {
+        node.entries[index] = child.entries.pop()?;
+      }
+      Some(value)
+    }
+    Entry::Collision { hash: h, pairs } => {
+      if *h != hash {
+        return None;
+      }
+      let position = pairs.iter().position(|(k, _)| k == key)?;
+      let pairs = Rc::make_mut(pairs);
+      let (_, value) = pairs.remove(position);
+      if pairs.len() == 1 {
+        let (key, last) = pairs.pop()?;
+        node.entries[index] = Entry::Leaf {
+          hash,
+          key,
+          value: last,
+        };
+      }
+      Some(value)
+    }
+  }
+}
+
+impl<K, V> Clone for PersistentMap<K, V> {
+  /// Makes a clone of the map, which shares all its nodes.
+  fn clone(&self) -> PersistentMap<K, V> {
+    PersistentMap {
+      root: Rc::clone(&self.root),
+      len: self.len,
+    }
+  }
+}
+
+impl<K, V> Default for PersistentMap<K, V> {
+  fn default() -> PersistentMap<K, V> {
+    PersistentMap::new()
+  }
+}
+
+impl<K: Eq + Hash, V: PartialEq> PartialEq for PersistentMap<K, V> {
+  fn eq(&self, other: &Self) -> bool {
+    self.len == other.len
+      && (self.ptr_eq(other)
+        || self.iter().all(|(k, v)| other.get(k) == Some(v)))
+  }
+}
+
+impl<K: Eq + Hash, V: Eq> Eq for PersistentMap<K, V> {}
+
+impl<K: std::fmt::Debug, V: std::fmt::Debug> std::fmt::Debug
+  for PersistentMap<K, V>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_map().entries(self.iter()).finish()
+  }
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> std::iter::FromIterator<(K, V)>
+  for PersistentMap<K, V>
+{
+  fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+    let mut map = PersistentMap::new();
+    map.extend(iter);
+    map
+  }
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> Extend<(K, V)> for PersistentMap<K, V> {
+  fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+    for (key, value) in iter {
+      self.insert(key, value);
+    }
+  }
+}
+
+impl<'a, K, V> IntoIterator for &'a PersistentMap<K, V> {
+  type Item = (&'a K, &'a V);
+  type IntoIter = Iter<'a, K, V>;
+
+  fn into_iter(self) -> Iter<'a, K, V> {
+    self.iter()
+  }
+}
+
+/// An iterator over the entries of a [`PersistentMap`].
+///
+/// This struct is created by [`PersistentMap::iter`].
+pub struct Iter<'a, K, V> {
+  /// The entries left in each node on the path to the current one.
+  nodes: Vec<std::slice::Iter<'a, Entry<K, V>>>,
+  /// The pairs left in the current bucket.
+  pairs: std::slice::Iter<'a, (K, V)>,
+  len: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+  type Item = (&'a K, &'a V);
+
+  fn next(&mut self) -> Option<(&'a K, &'a V)> {
+    loop {
+      if let Some((key, value)) = self.pairs.next() {
+        self.len -= 1;
+        return Some((key, value));
+      }
+      match self.nodes.last_mut()?.next() {
+        Some(Entry::Leaf { key, value, .. }) => {
+          self.len -= 1;
+          return Some((key, value));
+        }
+        Some(Entry::Branch(child)) => self.nodes.push(child.entries.iter()),
+        Some(Entry::Collision { pairs, .. }) => self.pairs = pairs.iter(),
+        None => {
+          self.nodes.pop();
+        }
+      }
+    }
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.len, Some(self.len))
+  }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+
+  /// A key whose hash is only its low bits, so that keys collide.
+  #[derive(Clone, Debug, PartialEq, Eq)]
+  struct Colliding(u32);
+
+  impl Hash for Colliding {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+      (self.0 % 4).hash(state);
+    }
+  }
+
+  #[test]
+  fn matches_a_hash_map() {
+    let mut map = PersistentMap::new();
+    let mut model = HashMap::new();
+    let mut seed = 12345u64;
+    for _ in 0..5000 {
+      seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+      let key = (seed >> 33) % 700;
+      if seed.is_multiple_of(3) {
+        assert_eq!(map.remove(&key), model.remove(&key));
+      } else {
+        assert_eq!(map.insert(key, seed), model.insert(key, seed));
+      }
+      assert_eq!(map.len(), model.len());
+    }
+
+    for (key, value) in &model {
+      assert_eq!(map.get(key), Some(value));
+    }
+    assert_eq!(map.iter().count(), model.len());
+    assert_eq!(map, model.into_iter().collect());
+  }
+
+  #[test]
+  fn colliding_keys_share_a_bucket() {
+    let mut map = PersistentMap::new();
+    for n in 0..12 {
+      map.insert(Colliding(n), n);
+    }
+    assert_eq!(map.insert(Colliding(5), 50), Some(5));
+    assert_eq!(map.get(&Colliding(13)), None);
+
+    let before = map.clone();
+    for n in 0..12 {
+      assert!(map.remove(&Colliding(n)).is_some());
+    }
+    assert!(map.is_empty());
+    assert_eq!(before.len(), 12);
+    assert_eq!(before.get(&Colliding(5)), Some(&50));
+  }
+
+  #[test]
+  fn clones_share_what_they_dont_change() {
+    let mut a: PersistentMap<_, _> = (0..1000).map(|n| (n, n)).collect();
+    let mut b = a.clone();
+    b.insert(0, 1);
+
+    // Only the path to the changed key was copied: the other branches of
+    // the root are still shared.
+    let shared = a
+      .root
+      .entries
+      .iter()
+      .zip(&b.root.entries)
+      .filter(|pair| match pair {
+        (Entry::Branch(x), Entry::Branch(y)) => Rc::ptr_eq(x, y),
+        _ => false,
+      })
+      .count();
+    assert_eq!(shared, a.root.entries.len() - 1);
+
+    assert_eq!((a.get(&0), b.get(&0)), (Some(&0), Some(&1)));
+    a.remove(&0);
+    assert_eq!((a.len(), b.len()), (999, 1000));
+    assert_eq!(b.remove(&5000), None);
+  }
+}
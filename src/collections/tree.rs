@@ -0,0 +1,779 @@
+//! A tree of shared nodes, with [`Rc`] links to the children and [`Weak`] links to the parent.
+//!
+//! The [crate documentation](crate) describes how to build a tree out of reference-counted
+//! pointers without leaking it: parents own their children through strong pointers, and children
+//! only point back at their parent through weak ones, so there is never a strong cycle.
+//! [`TreeNode<T>`][TreeNode] is that pattern, written once. Every operation keeps the invariant:
+//! the ones that move a node, such as [`append_child`], [`insert_before`] and [`reparent`],
+//! detach it from its old parent first, and refuse to make it a child of its own descendant.
+//!
+//! A `TreeNode` is a handle: cloning it makes another handle to the same node, and a node lives
+//! as long as its parent or any handle to it does. A [`Tree<T>`][Tree] owns the root of a tree,
+//! and walks the whole tree [depth-first] or [breadth-first].
+//!
+//! ```
+//! use pointer::TreeNode;
+//!
+//! let root = TreeNode::new("root");
+//! let branch = TreeNode::new("branch");
+//! let leaf = TreeNode::new("leaf");
+//! root.append_child(&branch);
+//! branch.append_child(&leaf);
+//!
+//! let path: Vec<_> = leaf.ancestors().map(|node| *node.borrow()).collect();
+//! assert_eq!(path, ["branch", "root"]);
+//!
+//! branch.detach();
+//! assert!(branch.parent().is_none());
+//! assert_eq!(root.descendants().count(), 0);
+//! assert_eq!(branch.descendants().count(), 1);
+//! ```
+//!
+//! [`append_child`]: TreeNode::append_child
+//! [`insert_before`]: TreeNode::insert_before
+//! [`reparent`]: TreeNode::reparent
+//! [depth-first]: Tree::depth_first
+//! [breadth-first]: Tree::breadth_first
+
+use crate::refcell::{Ref, RefCell, RefMut};
+use crate::{Rc, Weak};
+
+use std::collections::VecDeque;
+
+/// A tree, owned through its root node.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct Tree<T> {
+  root: TreeNode<T>,
+}
+
+impl<T> Tree<T> {
+  /// Creates a tree of one node, holding `value`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::Tree;
+  ///
+  /// let tree = Tree::new("root");
+  /// assert_eq!(tree.node_count(), 1);
+  /// ```
+  pub fn new(value: T) -> Tree<T> {
+    Tree {
+      root: TreeNode::new(value),
+    }
+  }
+
+  /// Returns the root node.
+  pub fn root(&self) -> &TreeNode<T> {
+    &self.root
+  }
+
+  /// Consumes the tree, returning its root node.
+  pub fn into_root(self) -> TreeNode<T> {
+    self.root
+  }
+
+  /// Returns the number of nodes in the tree.
+  ///
+  /// This walks the tree, so it takes time proportional to its size.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::{Tree, TreeNode};
+  ///
+  /// let tree = Tree::new(0);
+  /// tree.root().append_child(&TreeNode::new(1));
+  /// assert_eq!(tree.node_count(), 2);
+  /// ```
+  pub fn node_count(&self) -> usize {
+    self.root.depth_first().count()
+  }
+
+  /// Returns an iterator over the nodes of the tree, depth-first from the
+  /// root, with each node before its children.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::{Tree, TreeNode};
+  ///
+  /// let tree = Tree::new(0);
+  /// let a = TreeNode::new(1);
+  /// tree.root().append_child(&a);
+  /// a.append_child(&TreeNode::new(2));
+  /// tree.root().append_child(&TreeNode::new(3));
+  ///
+  /// let values: Vec<_> = tree.depth_first().map(|n| *n.borrow()).collect();
+  /// assert_eq!(values, [0, 1, 2, 3]);
+  /// ```
+  pub fn depth_first(&self) -> Descendants<T> {
+    self.root.depth_first()
+  }
+
+  /// Returns an iterator over the nodes of the tree, breadth-first from the
+  /// root, level by level.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::collections::{Tree, TreeNode};
+  ///
+  /// let tree = Tree::new(0);
+  /// let a = TreeNode::new(1);
+  /// tree.root().append_child(&a);
+  /// a.append_child(&TreeNode::new(2));
+  /// tree.root().append_child(&TreeNode::new(3));
+  ///
+  /// let values: Vec<_> = tree.breadth_first().map(|n| *n.borrow()).collect();
+  /// assert_eq!(values, [0, 1, 3, 2]);
+  /// ```
+  pub fn breadth_first(&self) -> BreadthFirst<T> {
+    self.root.breadth_first()
+  }
+}
+
+impl<T> From<TreeNode<T>> for Tree<T> {
+  /// Makes a tree of `root` and its descendants, detaching `root` from its
+  /// parent.
+  fn from(root: TreeNode<T>) -> Tree<T> {
+    root.detach();
+    Tree { root }
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Tree<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Tree")
+      .field("root", &self.root)
+      .field("nodes", &self.node_count())
+      .finish()
+  }
+}
+
+/// A node in a tree, holding a value of type `T`.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct TreeNode<T> {
+  rc: Rc<Node<T>>,
+}
+
+struct Node<T> {
+  value: RefCell<T>,
+  parent: RefCell<Weak<Node<T>>>,
+  children: RefCell<Vec<Rc<Node<T>>>>,
+}
+
+impl<T> TreeNode<T> {
+  /// Creates a new node with no parent and no children.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let node = TreeNode::new(5);
+  /// assert!(node.parent().is_none());
+  /// ```
+  pub fn new(value: T) -> TreeNode<T> {
+    TreeNode {
+      rc: Rc::new(Node {
+        value: RefCell::new(value),
+        parent: RefCell::new(Weak::new()),
+        children: RefCell::new(Vec::new()),
+      }),
+    }
+  }
+
+  /// Immutably borrows the value of the node.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently mutably borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let node = TreeNode::new(5);
+  /// assert_eq!(*node.borrow(), 5);
+  /// ```
+  pub fn borrow(&self) -> Ref<'_, T> {
+    self.rc.value.borrow()
+  }
+
+  /// Mutably borrows the value of the node.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let node = TreeNode::new(5);
+  /// *node.borrow_mut() += 1;
+  /// assert_eq!(*node.borrow(), 6);
+  /// ```
+  pub fn borrow_mut(&self) -> RefMut<'_, T> {
+    self.rc.value.borrow_mut()
+  }
+
+  /// Returns the parent of the node, if it has one.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let parent = TreeNode::new(1);
+  /// let child = TreeNode::new(2);
+  /// parent.append_child(&child);
+  /// assert!(child.parent().unwrap().ptr_eq(&parent));
+  /// ```
+  pub fn parent(&self) -> Option<TreeNode<T>> {
+    self.rc.parent.borrow().upgrade().map(|rc| TreeNode { rc })
+  }
+
+  /// Returns the children of the node, in order.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let parent = TreeNode::new(0);
+  /// parent.append_child(&TreeNode::new(1));
+  /// parent.append_child(&TreeNode::new(2));
+  ///
+  /// let values: Vec<_> = parent.children().map(|c| *c.borrow()).collect();
+  /// assert_eq!(values, [1, 2]);
+  /// ```
+  pub fn children(&self) -> Children<T> {
+    Children {
+      parent: Rc::clone(&self.rc),
+      next: 0,
+    }
+  }
+
+  /// Makes `child` the last child of this node, detaching it from its old
+  /// parent first.
+  ///
+  /// To rule out cycles, this walks up all the ancestors of this node on
+  /// every call, so it takes time proportional to the depth of the node.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `child` is this node or one of its ancestors, which would
+  /// make a cycle.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let a = TreeNode::new('a');
+  /// let b = TreeNode::new('b');
+  /// let child = TreeNode::new('c');
+  /// a.append_child(&child);
+  /// b.append_child(&child);
+  ///
+  /// assert_eq!(a.children().count(), 0);
+  /// assert!(child.parent().unwrap().ptr_eq(&b));
+  /// ```
+  pub fn append_child(&self, child: &TreeNode<T>) {
+    self.adopt(child);
+    self.rc.children.borrow_mut().push(Rc::clone(&child.rc));
+  }
+
+  /// Makes `child` the child at `index` of this node, detaching it from its
+  /// old parent first, and shifting the children after it.
+  ///
+  /// `index` counts the children without `child`, if it is one already.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `index` is greater than the number of children, or if `child`
+  /// is this node or one of its ancestors.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let parent = TreeNode::new(0);
+  /// parent.append_child(&TreeNode::new(1));
+  /// parent.insert_child(0, &TreeNode::new(2));
+  ///
+  /// let values: Vec<_> = parent.children().map(|c| *c.borrow()).collect();
+  /// assert_eq!(values, [2, 1]);
+  /// ```
+  pub fn insert_child(&self, index: usize, child: &TreeNode<T>) {
+    let others = self
+      .rc
+      .children
+      .borrow()
+      .iter()
+      .filter(|c| !Rc::ptr_eq(c, &child.rc))
+      .count();
+    assert!(
+      index <= others,
+      "insertion index (is {}) should be <= the number of children (is {})",
+      index,
+      others
+    );
+    self.adopt(child);
+    self
+      .rc
+      .children
+      .borrow_mut()
+      .insert(index, Rc::clone(&child.rc));
+  }
+
+  /// Puts `sibling` just before this node, under its parent, detaching it
+  /// from its old parent first.
+  ///
+  /// # Panics
+  ///
+  /// Panics if this node has no parent, or if `sibling` is this node or one
+  /// of its ancestors.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let parent = TreeNode::new(0);
+  /// let last = TreeNode::new(2);
+  /// parent.append_child(&last);
+  /// last.insert_before(&TreeNode::new(1));
+  ///
+  /// let values: Vec<_> = parent.children().map(|c| *c.borrow()).collect();
+  /// assert_eq!(values, [1, 2]);
+  /// ```
+  pub fn insert_before(&self, sibling: &TreeNode<T>) {
+    self.insert_sibling(sibling, 0);
+  }
+
+  /// Puts `sibling` just after this node, under its parent, detaching it
+  /// from its old parent first.
+  ///
+  /// # Panics
+  ///
+  /// Panics if this node has no parent, or if `sibling` is this node or one
+  /// of its ancestors.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let parent = TreeNode::new(0);
+  /// let first = TreeNode::new(1);
+  /// parent.append_child(&first);
+  /// parent.append_child(&TreeNode::new(3));
+  /// first.insert_after(&TreeNode::new(2));
+  ///
+  /// let values: Vec<_> = parent.children().map(|c| *c.borrow()).collect();
+  /// assert_eq!(values, [1, 2, 3]);
+  /// ```
+  pub fn insert_after(&self, sibling: &TreeNode<T>) {
+    self.insert_sibling(sibling, 1);
+  }
+
+  /// Moves the node, with its descendants, to be the last child of
+  /// `parent`.
+  ///
+  /// This is `parent.append_child(self)`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `parent` is this node or one of its descendants.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let (a, b) = (TreeNode::new('a'), TreeNode::new('b'));
+  /// let node = TreeNode::new('n');
+  /// a.append_child(&node);
+  /// node.append_child(&TreeNode::new('x'));
+  ///
+  /// node.reparent(&b);
+  /// assert_eq!(a.descendants().count(), 0);
+  /// assert_eq!(b.descendants().count(), 2);
+  /// ```
+  pub fn reparent(&self, parent: &TreeNode<T>) {
+    parent.append_child(self);
+  }
+
+  /// Removes the node from its parent, making it the root of its own tree.
+  ///
+  /// The node keeps its children. Nothing happens if it has no parent.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let parent = TreeNode::new(1);
+  /// let child = TreeNode::new(2);
+  /// parent.append_child(&child);
+  ///
+  /// child.detach();
+  /// assert!(child.parent().is_none());
+  /// assert_eq!(parent.children().count(), 0);
+  /// ```
+  pub fn detach(&self) {
+    let parent = self.rc.parent.replace(Weak::new()).upgrade();
+    if let Some(parent) = parent {
+      parent
+        .children
+        .borrow_mut()
+        .retain(|child| !Rc::ptr_eq(child, &self.rc));
+    }
+  }
+
+  /// Returns an iterator over the ancestors of the node, from its parent up
+  /// to the root.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let root = TreeNode::new(0);
+  /// let node = TreeNode::new(1);
+  /// root.append_child(&node);
+  ///
+  /// assert_eq!(node.ancestors().count(), 1);
+  /// assert_eq!(root.ancestors().count(), 0);
+  /// ```
+  pub fn ancestors(&self) -> Ancestors<T> {
+    Ancestors {
+      next: self.parent(),
+    }
+  }
+
+  /// Returns an iterator over the descendants of the node, depth-first with
+  /// each node before its children.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let root = TreeNode::new(0);
+  /// let a = TreeNode::new(1);
+  /// root.append_child(&a);
+  /// a.append_child(&TreeNode::new(2));
+  /// root.append_child(&TreeNode::new(3));
+  ///
+  /// let values: Vec<_> = root.descendants().map(|n| *n.borrow()).collect();
+  /// assert_eq!(values, [1, 2, 3]);
+  /// ```
+  pub fn descendants(&self) -> Descendants<T> {
+    let mut stack = self.rc.children.borrow().clone();
+    stack.reverse();
+    Descendants { stack }
+  }
+
+  /// Returns an iterator over the node and its descendants, depth-first with
+  /// each node before its children.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let root = TreeNode::new(0);
+  /// root.append_child(&TreeNode::new(1));
+  ///
+  /// let values: Vec<_> = root.depth_first().map(|n| *n.borrow()).collect();
+  /// assert_eq!(values, [0, 1]);
+  /// ```
+  pub fn depth_first(&self) -> Descendants<T> {
+    Descendants {
+      stack: vec![Rc::clone(&self.rc)],
+    }
+  }
+
+  /// Returns an iterator over the node and its descendants, breadth-first,
+  /// level by level.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let root = TreeNode::new(0);
+  /// let a = TreeNode::new(1);
+  /// root.append_child(&a);
+  /// a.append_child(&TreeNode::new(2));
+  /// root.append_child(&TreeNode::new(3));
+  ///
+  /// let values: Vec<_> = root.breadth_first().map(|n| *n.borrow()).collect();
+  /// assert_eq!(values, [0, 1, 3, 2]);
+  /// ```
+  pub fn breadth_first(&self) -> BreadthFirst<T> {
+    BreadthFirst {
+      queue: VecDeque::from(vec![Rc::clone(&self.rc)]),
+    }
+  }
+
+  /// Returns `true` if the two handles are to the same node.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TreeNode;
+  ///
+  /// let node = TreeNode::new(5);
+  /// assert!(node.ptr_eq(&node.clone()));
+  /// assert!(!node.ptr_eq(&TreeNode::new(5)));
+  /// ```
+  pub fn ptr_eq(&self, other: &TreeNode<T>) -> bool {
+    Rc::ptr_eq(&self.rc, &other.rc)
+  }
+
+  /// Detaches `child` and makes this node its parent, leaving it to the
+  /// caller to put it in the children.
+  ///
+  /// To rule out cycles, this walks up all the ancestors of this node.
+  fn adopt(&self, child: &TreeNode<T>) {
+    assert!(
+      !self.ptr_eq(child) && !self.ancestors().any(|a| a.ptr_eq(child)),
+      "a tree node can't be a child of itself or its descendants"
+    );
+    child.detach();
+    *child.rc.parent.borrow_mut() = Rc::downgrade(&self.rc);
+  }
+
+  /// Puts `sibling` under the parent of this node, `offset` places after
+  /// it.
+  fn insert_sibling(&self, sibling: &TreeNode<T>, offset: usize) {
+    assert!(
+      !self.ptr_eq(sibling),
+      "a tree node can't be its own sibling"
+    );
+    let parent = self.parent().expect("a root tree node has no siblings");
+    parent.adopt(sibling);
+    let mut children = parent.rc.children.borrow_mut();
+    let index = children
+      .iter()
+      .position(|c| Rc::ptr_eq(c, &self.rc))
+      .expect("a tree node is among the children of its parent");
+    children.insert(index + offset, Rc::clone(&sibling.rc));
+  }
+}
+
+impl<T> Clone for TreeNode<T> {
+  /// Makes another handle to the same node.
+  fn clone(&self) -> TreeNode<T> {
+    TreeNode {
+      rc: Rc::clone(&self.rc),
+    }
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for TreeNode<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut d = f.debug_struct("TreeNode");
+    match self.rc.value.try_borrow() {
+      Ok(value) => d.field("value", &*value),
+      Err(_) => d.field("value", &format_args!("<borrowed>")),
+    };
+    d.field("children", &self.rc.children.borrow().len())
+      .finish()
+  }
+}
+
+impl<T> Drop for Node<T> {
+  /// Drops the subtree one node at a time, so that deep trees don't overflow
+  /// the stack.
+  fn drop(&mut self) {
+    let mut stack = std::mem::take(self.children.get_mut());
+    while let Some(child) = stack.pop() {
+      if let Ok(mut node) = Rc::try_unwrap(child) {
+        stack.append(node.children.get_mut());
+      }
+    }
+  }
+}
+
+/// An iterator over the children of a [`TreeNode`].
+///
+/// This struct is created by [`TreeNode::children`].
+pub struct Children<T> {
+  parent: Rc<Node<T>>,
+  next: usize,
+}
+
+impl<T> Iterator for Children<T> {
+  type Item = TreeNode<T>;
+
+  fn next(&mut self) -> Option<TreeNode<T>> {
+    let rc = Rc::clone(self.parent.children.borrow().get(self.next)?);
+    self.next += 1;
+    Some(TreeNode { rc })
+  }
+}
+
+/// An iterator over the ancestors of a [`TreeNode`].
+///
+/// This struct is created by [`TreeNode::ancestors`].
+pub struct Ancestors<T> {
+  next: Option<TreeNode<T>>,
+}
+
+impl<T> Iterator for Ancestors<T> {
+  type Item = TreeNode<T>;
+
+  fn next(&mut self) -> Option<TreeNode<T>> {
+    let node = self.next.take()?;
+    self.next = node.parent();
+    Some(node)
+  }
+}
+
+impl<T> std::iter::FusedIterator for Ancestors<T> {}
+
+/// A depth-first iterator over the descendants of a [`TreeNode`].
+///
+/// This struct is created by [`TreeNode::descendants`].
+pub struct Descendants<T> {
+  /// The nodes still to visit, the next one last.
+  stack: Vec<Rc<Node<T>>>,
+}
+
+impl<T> Iterator for Descendants<T> {
+  type Item = TreeNode<T>;
+
+  fn next(&mut self) -> Option<TreeNode<T>> {
+    let rc = self.stack.pop()?;
+    self
+      .stack
+      .extend(rc.children.borrow().iter().rev().map(Rc::clone));
+    Some(TreeNode { rc })
+  }
+}
+
+impl<T> std::iter::FusedIterator for Descendants<T> {}
+
+/// A breadth-first iterator over a [`TreeNode`] and its descendants.
+///
+/// This struct is created by [`TreeNode::breadth_first`].
+pub struct BreadthFirst<T> {
+  /// The nodes still to visit, the next one first.
+  queue: VecDeque<Rc<Node<T>>>,
+}
+
+impl<T> Iterator for BreadthFirst<T> {
+  type Item = TreeNode<T>;
+
+  fn next(&mut self) -> Option<TreeNode<T>> {
+    let rc = self.queue.pop_front()?;
+    self
+      .queue
+      .extend(rc.children.borrow().iter().map(Rc::clone));
+    Some(TreeNode { rc })
+  }
+}
+
+impl<T> std::iter::FusedIterator for BreadthFirst<T> {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  #[should_panic(expected = "child of itself or its descendants")]
+  fn rejects_cycles() {
+    let root = TreeNode::new(0);
+    let child = TreeNode::new(1);
+    root.append_child(&child);
+    child.append_child(&root);
+  }
+
+  fn values(nodes: impl Iterator<Item = TreeNode<char>>) -> String {
+    nodes.map(|n| *n.borrow()).collect()
+  }
+
+  #[test]
+  fn siblings_keep_their_order() {
+    let tree = Tree::new('r');
+    let root = tree.root();
+    let (a, b, c, d) = (
+      TreeNode::new('a'),
+      TreeNode::new('b'),
+      TreeNode::new('c'),
+      TreeNode::new('d'),
+    );
+    root.append_child(&c);
+    c.insert_before(&a);
+    a.insert_after(&b);
+    root.insert_child(3, &d);
+    assert_eq!(values(root.children()), "abcd");
+
+    // Moving a sibling counts the others only.
+    root.insert_child(3, &a);
+    assert_eq!(values(root.children()), "bcda");
+    b.append_child(&d);
+    d.reparent(&c);
+    c.insert_after(&b);
+    assert_eq!(values(tree.depth_first()), "rcdba");
+    assert_eq!(values(tree.breadth_first()), "rcbad");
+    assert_eq!(tree.node_count(), 5);
+  }
+
+  #[test]
+  #[should_panic(expected = "child of itself or its descendants")]
+  fn siblings_cant_be_ancestors() {
+    let root = TreeNode::new('r');
+    let child = TreeNode::new('c');
+    let grandchild = TreeNode::new('g');
+    root.append_child(&child);
+    child.append_child(&grandchild);
+    grandchild.insert_after(&root);
+  }
+
+  #[test]
+  fn moved_subtrees_are_freed_with_their_new_root() {
+    let old = Tree::new(0);
+    let new = Tree::new(1);
+    let branch = TreeNode::new(2);
+    old.root().append_child(&branch);
+    branch.append_child(&TreeNode::new(3));
+    let leaf = Rc::downgrade(&branch.rc.children.borrow()[0]);
+
+    branch.reparent(new.root());
+    drop(branch);
+    drop(old);
+    assert!(leaf.upgrade().is_some());
+    drop(new);
+    assert!(leaf.upgrade().is_none());
+  }
+
+  #[test]
+  fn dropping_the_root_frees_the_tree() {
+    // Build the chain from the leaf up, so that each new parent has no
+    // ancestors for `append_child` to check.
+    let leaf = TreeNode::new(0);
+    let weak_leaf = Rc::downgrade(&leaf.rc);
+    let mut root = leaf;
+    for n in 1..100_000 {
+      let parent = TreeNode::new(n);
+      parent.append_child(&root);
+      root = parent;
+    }
+
+    assert_eq!(root.descendants().count(), 99_999);
+    drop(root);
+    assert!(weak_leaf.upgrade().is_none());
+  }
+}
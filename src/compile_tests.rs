@@ -0,0 +1,133 @@
+//! Variance and auto-trait checks, which are part of the soundness of the pointers and cells.
+//!
+//! A wrong variance or a stray `Send` or `Sync` compiles without complaint, so each property
+//! has a check here that stops compiling if it breaks: the unit tests below for what must
+//! compile, and the `compile_fail` examples in this module's docs for what must not. Only
+//! `cargo test` sees this module.
+//!
+//! `RefCell<T>` is invariant in `T`, since it can be written through a shared reference:
+//!
+//! ```compile_fail
+//! use pointer::RefCell;
+//!
+//! fn shorten<'a>(cell: RefCell<&'static str>) -> RefCell<&'a str> {
+//!   cell
+//! }
+//! ```
+//!
+//! And so is `Cell<T>`:
+//!
+//! ```compile_fail
+//! use pointer::Cell;
+//!
+//! fn shorten<'a>(cell: &Cell<&'static str>) -> &Cell<&'a str> {
+//!   cell
+//! }
+//! ```
+//!
+//! A `RefMut` writes, so it is invariant too:
+//!
+//! ```compile_fail
+//! use pointer::RefMut;
+//!
+//! fn shorten<'r, 'a>(r: RefMut<'r, &'static str>) -> RefMut<'r, &'a str> {
+//!   r
+//! }
+//! ```
+//!
+//! `Rc` and `Weak` are neither `Send` nor `Sync`, whatever `T` is, as their counts aren't
+//! atomic:
+//!
+//! ```compile_fail,E0277
+//! fn is_send<T: Send>() {}
+//! is_send::<pointer::Rc<u8>>();
+//! ```
+//!
+//! ```compile_fail,E0277
+//! fn is_sync<T: Sync>() {}
+//! is_sync::<pointer::Rc<u8>>();
+//! ```
+//!
+//! ```compile_fail,E0277
+//! fn is_send<T: Send>() {}
+//! is_send::<pointer::Weak<u8>>();
+//! ```
+//!
+//! ```compile_fail,E0277
+//! fn is_sync<T: Sync>() {}
+//! is_sync::<pointer::Weak<u8>>();
+//! ```
+//!
+//! `Cell` and `RefCell` may be sent, but never shared between threads:
+//!
+//! ```compile_fail,E0277
+//! fn is_sync<T: Sync>() {}
+//! is_sync::<pointer::Cell<u8>>();
+//! ```
+//!
+//! ```compile_fail,E0277
+//! fn is_sync<T: Sync>() {}
+//! is_sync::<pointer::RefCell<u8>>();
+//! ```
+//!
+//! Nor can their contents be sent when `T` can't:
+//!
+//! ```compile_fail,E0277
+//! fn is_send<T: Send>() {}
+//! is_send::<pointer::RefCell<std::rc::Rc<u8>>>();
+//! ```
+//!
+//! The guards of a `RefCell` borrow stay on the thread of the `RefCell`:
+//!
+//! ```compile_fail,E0277
+//! fn is_send<T: Send>() {}
+//! is_send::<pointer::Ref<'static, u8>>();
+//! ```
+//!
+//! ```compile_fail,E0277
+//! fn is_send<T: Send>() {}
+//! is_send::<pointer::RefMut<'static, u8>>();
+//! ```
+
+#[cfg(test)]
+mod tests {
+  use crate::{Cell, Rc, Ref, RefCell, Weak};
+
+  fn is_send<T: Send>() {}
+  fn is_sync<T: Sync>() {}
+
+  #[test]
+  fn pointers_and_borrows_are_covariant() {
+    // Each of these only compiles if the type is covariant in `T`.
+    fn rc<'a>(rc: Rc<&'static str>) -> Rc<&'a str> {
+      rc
+    }
+    fn weak<'a>(weak: Weak<&'static str>) -> Weak<&'a str> {
+      weak
+    }
+    fn borrow<'r, 'a>(r: Ref<'r, &'static str>) -> Ref<'r, &'a str> {
+      r
+    }
+    fn arc<'a>(
+      arc: crate::sync::Arc<&'static str>,
+    ) -> crate::sync::Arc<&'a str> {
+      arc
+    }
+
+    let shared = Rc::new("shared");
+    assert_eq!(*rc(Rc::clone(&shared)), "shared");
+    assert!(weak(Rc::downgrade(&shared)).upgrade().is_some());
+    let cell = RefCell::new("cell");
+    assert_eq!(*borrow(cell.borrow()), "cell");
+    assert_eq!(*arc(crate::sync::Arc::new("arc")), "arc");
+  }
+
+  #[test]
+  fn cells_are_send_when_their_values_are() {
+    is_send::<Cell<u8>>();
+    is_send::<RefCell<Vec<u8>>>();
+    is_send::<crate::sync::Arc<u8>>();
+    is_sync::<crate::sync::Arc<u8>>();
+    is_sync::<crate::Frozen<u8>>();
+  }
+}
@@ -0,0 +1,347 @@
+//! A clone-on-write pointer that can also share ownership through an [`Rc`].
+//!
+//! [`Cow<'a, B>`][Cow] works like [`std::borrow::Cow`]: it hands out `&B` through [`Deref`] and
+//! only makes an owned copy of the data when it is mutated or consumed. Next to the `Borrowed` and
+//! `Owned` variants it has a third, `Shared`, that holds an [`Rc<B>`][Rc]. Turning an `Rc` into a
+//! `Cow` is free; the clone is put off until [`to_mut`] or [`into_owned`] needs it.
+//!
+//! That suits APIs which sometimes borrow their input and sometimes keep it alive for longer: a
+//! shared value can be passed along without copying, and going back to an `Rc` with
+//! [`Cow::into_rc`] reuses the allocation when it is already shared.
+//!
+//! ```
+//! use pointer::{Cow, Rc};
+//!
+//! let name: Rc<str> = Rc::from(pointer::Boxed::from(String::from("ferris")));
+//! let mut cow = Cow::from(Rc::clone(&name));
+//! assert!(cow.is_shared());
+//!
+//! // Mutating makes a private copy, leaving the shared value alone.
+//! cow.to_mut().push_str("!");
+//! assert_eq!(&*cow, "ferris!");
+//! assert_eq!(&*name, "ferris");
+//! ```
+//!
+//! [`Deref`]: std::ops::Deref
+//! [`to_mut`]: Cow::to_mut
+//! [`into_owned`]: Cow::into_owned
+
+use crate::{Boxed, Rc};
+
+use std::borrow::{Borrow, ToOwned};
+
+/// A clone-on-write smart pointer that borrows, owns or shares its data.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub enum Cow<'a, B: ?Sized + ToOwned + 'a> {
+  /// Borrowed data.
+  Borrowed(&'a B),
+  /// Owned data.
+  Owned(<B as ToOwned>::Owned),
+  /// Data shared with other [`Rc`]s.
+  Shared(Rc<B>),
+}
+
+impl<B: ?Sized + ToOwned> Cow<'_, B> {
+  /// Returns `true` if the data is borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cow;
+  ///
+  /// let cow = Cow::Borrowed("moo");
+  /// assert!(cow.is_borrowed());
+  /// assert!(!cow.is_owned());
+  /// ```
+  pub fn is_borrowed(&self) -> bool {
+    matches!(self, Cow::Borrowed(_))
+  }
+
+  /// Returns `true` if the data is owned.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cow;
+  ///
+  /// let cow: Cow<'_, str> = Cow::Owned(String::from("moo"));
+  /// assert!(cow.is_owned());
+  /// ```
+  pub fn is_owned(&self) -> bool {
+    matches!(self, Cow::Owned(_))
+  }
+
+  /// Returns `true` if the data is shared through an [`Rc`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Cow, Rc};
+  ///
+  /// let cow = Cow::from(Rc::new(5));
+  /// assert!(cow.is_shared());
+  /// ```
+  pub fn is_shared(&self) -> bool {
+    matches!(self, Cow::Shared(_))
+  }
+
+  /// Acquires a mutable reference to the owned form of the data.
+  ///
+  /// Clones the data if it is not already owned.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Cow, Rc};
+  ///
+  /// let mut cow = Cow::from(Rc::new(vec![1, 2]));
+  /// cow.to_mut().push(3);
+  ///
+  /// assert!(cow.is_owned());
+  /// assert_eq!(*cow, [1, 2, 3]);
+  /// ```
+  pub fn to_mut(&mut self) -> &mut <B as ToOwned>::Owned {
+    if let Cow::Borrowed(_) | Cow::Shared(_) = *self {
+      *self = Cow::Owned((**self).to_owned());
+    }
+    match *self {
+      Cow::Owned(ref mut owned) => owned,
+      Cow::Borrowed(_) | Cow::Shared(_) => unreachable!(),
+    }
+  }
+
+  /// Extracts the owned data.
+  ///
+  /// Clones the data if it is not already owned.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Cow;
+  ///
+  /// let cow = Cow::Borrowed("moo");
+  /// assert_eq!(cow.into_owned(), String::from("moo"));
+  /// ```
+  pub fn into_owned(self) -> <B as ToOwned>::Owned {
+    match self {
+      Cow::Borrowed(borrowed) => borrowed.to_owned(),
+      Cow::Owned(owned) => owned,
+      Cow::Shared(rc) => (*rc).to_owned(),
+    }
+  }
+
+  /// Converts the data into an [`Rc`].
+  ///
+  /// Shared data is returned as it is, without allocating; anything else is
+  /// moved into a new allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Cow, Rc};
+  ///
+  /// let five = Rc::new(5);
+  /// let cow = Cow::from(Rc::clone(&five));
+  ///
+  /// assert!(Rc::ptr_eq(&cow.into_rc(), &five));
+  /// ```
+  pub fn into_rc(self) -> Rc<B>
+  where
+    <B as ToOwned>::Owned: Into<Boxed<B>>,
+  {
+    Rc::from(self)
+  }
+}
+
+impl<B: ?Sized + ToOwned> std::ops::Deref for Cow<'_, B> {
+  type Target = B;
+
+  fn deref(&self) -> &B {
+    match *self {
+      Cow::Borrowed(borrowed) => borrowed,
+      Cow::Owned(ref owned) => owned.borrow(),
+      Cow::Shared(ref rc) => rc,
+    }
+  }
+}
+
+impl<B: ?Sized + ToOwned> Clone for Cow<'_, B> {
+  fn clone(&self) -> Self {
+    match *self {
+      Cow::Borrowed(borrowed) => Cow::Borrowed(borrowed),
+      Cow::Owned(ref owned) => Cow::Owned(owned.borrow().to_owned()),
+      Cow::Shared(ref rc) => Cow::Shared(Rc::clone(rc)),
+    }
+  }
+}
+
+impl<B: ?Sized + ToOwned> Default for Cow<'_, B>
+where
+  <B as ToOwned>::Owned: Default,
+{
+  /// Creates an owned `Cow` with the default value of the owned type.
+  fn default() -> Self {
+    Cow::Owned(Default::default())
+  }
+}
+
+impl<B: ?Sized + ToOwned> Borrow<B> for Cow<'_, B> {
+  fn borrow(&self) -> &B {
+    self
+  }
+}
+
+impl<B: ?Sized + ToOwned> AsRef<B> for Cow<'_, B> {
+  fn as_ref(&self) -> &B {
+    self
+  }
+}
+
+impl<'b, B, C> PartialEq<Cow<'b, C>> for Cow<'_, B>
+where
+  B: ?Sized + ToOwned + PartialEq<C>,
+  C: ?Sized + ToOwned,
+{
+  fn eq(&self, other: &Cow<'b, C>) -> bool {
+    **self == **other
+  }
+}
+
+impl<B: ?Sized + ToOwned + Eq> Eq for Cow<'_, B> {}
+
+impl<B: ?Sized + ToOwned + std::hash::Hash> std::hash::Hash for Cow<'_, B> {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    (**self).hash(state)
+  }
+}
+
+impl<'a, B: ?Sized + ToOwned> From<&'a B> for Cow<'a, B> {
+  fn from(borrowed: &'a B) -> Cow<'a, B> {
+    Cow::Borrowed(borrowed)
+  }
+}
+
+impl<B: ?Sized + ToOwned> From<Rc<B>> for Cow<'_, B> {
+  /// Wraps an `Rc` without cloning its data.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Cow, Rc};
+  ///
+  /// let five = Rc::new(5);
+  /// let cow = Cow::from(Rc::clone(&five));
+  ///
+  /// assert_eq!(*cow, 5);
+  /// assert_eq!(Rc::strong_count(&five), 2);
+  /// ```
+  fn from(rc: Rc<B>) -> Self {
+    Cow::Shared(rc)
+  }
+}
+
+impl From<String> for Cow<'_, str> {
+  fn from(s: String) -> Self {
+    Cow::Owned(s)
+  }
+}
+
+impl<T: Clone> From<Vec<T>> for Cow<'_, [T]> {
+  fn from(v: Vec<T>) -> Self {
+    Cow::Owned(v)
+  }
+}
+
+impl<'a, B: ?Sized + ToOwned> From<std::borrow::Cow<'a, B>> for Cow<'a, B> {
+  fn from(cow: std::borrow::Cow<'a, B>) -> Self {
+    match cow {
+      std::borrow::Cow::Borrowed(borrowed) => Cow::Borrowed(borrowed),
+      std::borrow::Cow::Owned(owned) => Cow::Owned(owned),
+    }
+  }
+}
+
+impl<B: ?Sized + ToOwned> From<Cow<'_, B>> for Rc<B>
+where
+  <B as ToOwned>::Owned: Into<Boxed<B>>,
+{
+  /// Moves the data into an `Rc`, cloning it first if it is borrowed.
+  ///
+  /// A `Shared` cow gives back its `Rc` without allocating.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Cow, Rc};
+  ///
+  /// let rc: Rc<str> = Rc::from(Cow::Borrowed("moo"));
+  /// assert_eq!(&*rc, "moo");
+  /// ```
+  fn from(cow: Cow<'_, B>) -> Rc<B> {
+    match cow {
+      Cow::Shared(rc) => rc,
+      Cow::Borrowed(borrowed) => Rc::from(borrowed.to_owned().into()),
+      Cow::Owned(owned) => Rc::from(owned.into()),
+    }
+  }
+}
+
+impl<B: ?Sized + ToOwned + std::fmt::Debug> std::fmt::Debug for Cow<'_, B>
+where
+  <B as ToOwned>::Owned: std::fmt::Debug,
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match *self {
+      Cow::Borrowed(ref borrowed) => std::fmt::Debug::fmt(borrowed, f),
+      Cow::Owned(ref owned) => std::fmt::Debug::fmt(owned, f),
+      Cow::Shared(ref rc) => std::fmt::Debug::fmt(rc, f),
+    }
+  }
+}
+
+impl<B: ?Sized + ToOwned + std::fmt::Display> std::fmt::Display for Cow<'_, B>
+where
+  <B as ToOwned>::Owned: std::fmt::Display,
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match *self {
+      Cow::Borrowed(ref borrowed) => std::fmt::Display::fmt(borrowed, f),
+      Cow::Owned(ref owned) => std::fmt::Display::fmt(owned, f),
+      Cow::Shared(ref rc) => std::fmt::Display::fmt(rc, f),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn clones_only_on_write() {
+    let shared = Rc::new(vec![1, 2]);
+    let mut cow = Cow::from(Rc::clone(&shared));
+
+    let copy = cow.clone();
+    assert!(copy.is_shared());
+    assert_eq!(Rc::strong_count(&shared), 3);
+
+    cow.to_mut().push(3);
+    assert!(cow.is_owned());
+    assert_eq!(*cow, [1, 2, 3]);
+    assert_eq!(*shared, [1, 2]);
+    assert_eq!(cow, Cow::Borrowed(&[1, 2, 3][..]));
+  }
+
+  #[test]
+  fn into_rc_reuses_shared_allocation() {
+    let shared = Rc::new(String::from("moo"));
+    let back = Cow::from(Rc::clone(&shared)).into_rc();
+    assert!(Rc::ptr_eq(&back, &shared));
+
+    let owned: Rc<[u8]> = Cow::from(vec![1, 2]).into_rc();
+    assert_eq!(*owned, [1, 2]);
+    let borrowed: Rc<str> = Cow::Borrowed("moo").into_rc();
+    assert_eq!(&*borrowed, "moo");
+  }
+}
@@ -0,0 +1,313 @@
+//! A vector whose clones share their storage until one is changed: [`CowVec<T>`][CowVec].
+//!
+//! A configuration snapshot handed to many consumers is read far more often than it is edited. A
+//! `CowVec` holds its elements in an [`Rc<Vec<T>>`][Rc], so cloning it copies one pointer and every
+//! clone reads the same storage, through [`Deref`] to `[T]`. The first change to a clone goes
+//! through [`Rc::make_mut`], which copies the storage if another clone still shares it and changes
+//! it in place if not. Later changes to the same clone copy nothing, and the other clones never see
+//! them.
+//!
+//! ```
+//! use pointer::CowVec;
+//!
+//! let defaults: CowVec<_> = vec!["--color", "--quiet"].into();
+//! let for_tests = defaults.clone();
+//! assert!(for_tests.ptr_eq(&defaults));
+//!
+//! let mut for_ci = defaults.clone();
+//! for_ci.push("--locked");
+//! assert!(!for_ci.ptr_eq(&defaults));
+//!
+//! assert_eq!(*defaults, ["--color", "--quiet"]);
+//! assert_eq!(*for_ci, ["--color", "--quiet", "--locked"]);
+//! ```
+//!
+//! [`Deref`]: std::ops::Deref
+//! [`Rc::make_mut`]: crate::Rc::make_mut
+//! [Rc]: crate::Rc
+
+use crate::Rc;
+
+/// A clone-on-write vector.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct CowVec<T> {
+  inner: Rc<Vec<T>>,
+}
+
+impl<T> CowVec<T> {
+  /// Creates an empty `CowVec`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CowVec;
+  ///
+  /// let v: CowVec<u8> = CowVec::new();
+  /// assert!(v.is_empty());
+  /// ```
+  pub fn new() -> CowVec<T> {
+    CowVec::from(Vec::new())
+  }
+
+  /// Returns the elements as a slice.
+  pub fn as_slice(&self) -> &[T] {
+    &self.inner
+  }
+
+  /// Returns `true` if another `CowVec` shares the storage, so that the
+  /// next change copies it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CowVec;
+  ///
+  /// let v: CowVec<_> = vec![1].into();
+  /// assert!(!v.is_shared());
+  ///
+  /// let clone = v.clone();
+  /// assert!(v.is_shared());
+  /// drop(clone);
+  /// assert!(!v.is_shared());
+  /// ```
+  pub fn is_shared(&self) -> bool {
+    Rc::strong_count(&self.inner) > 1
+  }
+
+  /// Returns `true` if the two vectors share their storage.
+  pub fn ptr_eq(&self, other: &CowVec<T>) -> bool {
+    Rc::ptr_eq(&self.inner, &other.inner)
+  }
+
+  /// Returns the storage, shared with this vector.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{CowVec, Rc};
+  ///
+  /// let v: CowVec<_> = vec![1, 2].into();
+  /// let rc = v.to_rc();
+  /// assert_eq!(*rc, [1, 2]);
+  /// assert!(v.is_shared());
+  /// ```
+  pub fn to_rc(&self) -> Rc<Vec<T>> {
+    Rc::clone(&self.inner)
+  }
+}
+
+impl<T: Clone> CowVec<T> {
+  /// Returns a mutable reference to the vector, copying the storage first
+  /// if it is shared.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CowVec;
+  ///
+  /// let original: CowVec<_> = vec![3, 1, 2].into();
+  /// let mut sorted = original.clone();
+  /// sorted.make_mut().sort();
+  ///
+  /// assert_eq!(*sorted, [1, 2, 3]);
+  /// assert_eq!(*original, [3, 1, 2]);
+  /// ```
+  pub fn make_mut(&mut self) -> &mut Vec<T> {
+    Rc::make_mut(&mut self.inner)
+  }
+
+  /// Sets the element at `index` to `value`, copying the storage first if
+  /// it is shared.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `index` is out of bounds.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CowVec;
+  ///
+  /// let mut v: CowVec<_> = vec!['a', 'b'].into();
+  /// v.set(1, 'c');
+  /// assert_eq!(*v, ['a', 'c']);
+  /// ```
+  #[track_caller]
+  pub fn set(&mut self, index: usize, value: T) {
+    self.make_mut()[index] = value;
+  }
+
+  /// Appends `value`, copying the storage first if it is shared.
+  pub fn push(&mut self, value: T) {
+    self.make_mut().push(value);
+  }
+
+  /// Removes the last element and returns it, or returns `None` if the
+  /// vector is empty. The storage is copied first if it is shared.
+  pub fn pop(&mut self) -> Option<T> {
+    if self.is_empty() {
+      return None;
+    }
+    self.make_mut().pop()
+  }
+
+  /// Inserts `value` at `index`, copying the storage first if it is shared.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `index` is greater than the length.
+  #[track_caller]
+  pub fn insert(&mut self, index: usize, value: T) {
+    self.make_mut().insert(index, value);
+  }
+
+  /// Removes the element at `index` and returns it, copying the storage
+  /// first if it is shared.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `index` is out of bounds.
+  #[track_caller]
+  pub fn remove(&mut self, index: usize) -> T {
+    self.make_mut().remove(index)
+  }
+
+  /// Consumes the `CowVec`, returning its elements, which are cloned if
+  /// another `CowVec` shares them.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CowVec;
+  ///
+  /// let v: CowVec<_> = vec![1, 2].into();
+  /// let clone = v.clone();
+  ///
+  /// assert_eq!(v.into_vec(), [1, 2]);
+  /// assert_eq!(clone.into_vec(), [1, 2]);
+  /// ```
+  pub fn into_vec(self) -> Vec<T> {
+    Rc::try_unwrap(self.inner).unwrap_or_else(|rc| (*rc).clone())
+  }
+}
+
+impl<T> std::ops::Deref for CowVec<T> {
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    &self.inner
+  }
+}
+
+impl<T> AsRef<[T]> for CowVec<T> {
+  fn as_ref(&self) -> &[T] {
+    &self.inner
+  }
+}
+
+impl<T> Clone for CowVec<T> {
+  /// Makes a clone of the vector, which shares its storage.
+  fn clone(&self) -> CowVec<T> {
+    CowVec {
+      inner: Rc::clone(&self.inner),
+    }
+  }
+}
+
+impl<T> Default for CowVec<T> {
+  fn default() -> CowVec<T> {
+    CowVec::new()
+  }
+}
+
+impl<T> From<Vec<T>> for CowVec<T> {
+  fn from(values: Vec<T>) -> CowVec<T> {
+    CowVec {
+      inner: Rc::new(values),
+    }
+  }
+}
+
+impl<T> From<Rc<Vec<T>>> for CowVec<T> {
+  /// Wraps the shared storage, without copying it.
+  fn from(inner: Rc<Vec<T>>) -> CowVec<T> {
+    CowVec { inner }
+  }
+}
+
+impl<T> std::iter::FromIterator<T> for CowVec<T> {
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> CowVec<T> {
+    CowVec::from(iter.into_iter().collect::<Vec<T>>())
+  }
+}
+
+impl<T: Clone> Extend<T> for CowVec<T> {
+  fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+    self.make_mut().extend(iter);
+  }
+}
+
+impl<'a, T> IntoIterator for &'a CowVec<T> {
+  type Item = &'a T;
+  type IntoIter = std::slice::Iter<'a, T>;
+
+  fn into_iter(self) -> std::slice::Iter<'a, T> {
+    self.inner.iter()
+  }
+}
+
+impl<T: PartialEq> PartialEq for CowVec<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.as_slice() == other.as_slice()
+  }
+}
+
+impl<T: Eq> Eq for CowVec<T> {}
+
+impl<T: std::hash::Hash> std::hash::Hash for CowVec<T> {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    self.as_slice().hash(state);
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for CowVec<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_list().entries(self.iter()).finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn the_first_change_copies_once() {
+    let snapshot: CowVec<_> = (0..4).collect();
+    let mut edited = snapshot.clone();
+    let readers: Vec<_> = (0..3).map(|_| snapshot.clone()).collect();
+    assert_eq!(Rc::strong_count(&snapshot.inner), 5);
+
+    edited.set(0, 10);
+    assert_eq!(Rc::strong_count(&snapshot.inner), 4);
+    edited.remove(1);
+    edited.insert(0, -1);
+    edited.extend(vec![4, 5]);
+    assert!(!edited.is_shared());
+
+    assert!(readers.iter().all(|r| r.ptr_eq(&snapshot)));
+    assert_eq!(*snapshot, [0, 1, 2, 3]);
+    assert_eq!(*edited, [-1, 10, 2, 3, 4, 5]);
+  }
+
+  #[test]
+  fn unshared_storage_is_reused() {
+    let mut v: CowVec<String> = vec![String::from("a")].into();
+    let before = v.as_ptr();
+    v.set(0, String::from("b"));
+    assert_eq!(v.as_ptr(), before);
+    assert_eq!(v.pop().as_deref(), Some("b"));
+    assert_eq!(v.pop(), None);
+    assert_eq!(format!("{:?}", v), "[]");
+  }
+}
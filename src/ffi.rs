@@ -0,0 +1,365 @@
+//! A C interface to reference-counted byte buffers, for the `ffi` feature.
+//!
+//! A buffer made in Rust as an [`Rc<[u8]>`][Rc] or an [`Arc<[u8]>`][Arc] is handed to C as an
+//! [`RcBytes`] or an [`ArcBytes`]: a pointer to the bytes and their length, which owns one strong
+//! reference. C code retains a buffer with `pointer_rc_clone_raw` and gives its reference back with
+//! `pointer_rc_release_raw` (`pointer_arc_*` for `ArcBytes`), and reads it through the data and
+//! length accessors. The declarations are in `include/pointer.h`, which [cbindgen] regenerates from
+//! this module with the `cbindgen.toml` at the root of the crate.
+//!
+//! An `RcBytes` must stay on the thread that made it, like the `Rc` it came from. An `ArcBytes` may
+//! be used from any thread.
+//!
+//! ```
+//! use pointer::ffi::{self, RcBytes};
+//! use pointer::{Boxed, Rc};
+//!
+//! let buffer: Rc<[u8]> = Rc::from(Boxed::<[u8]>::from(vec![1, 2, 3]));
+//! let handle = RcBytes::from_rc(buffer);
+//!
+//! // What C code would do with the handle.
+//! unsafe {
+//!   let retained = ffi::pointer_rc_clone_raw(handle);
+//!   assert_eq!(ffi::pointer_rc_len(retained), 3);
+//!   ffi::pointer_rc_release_raw(retained);
+//! }
+//!
+//! let buffer = unsafe { handle.into_rc() };
+//! assert_eq!(Rc::strong_count(&buffer), 1);
+//! ```
+//!
+//! [Rc]: crate::Rc
+//! [Arc]: crate::sync::Arc
+//! [cbindgen]: https://github.com/mozilla/cbindgen
+
+use crate::sync::Arc;
+use crate::Rc;
+
+/// A strong reference to an `Rc<[u8]>`, in a form C can hold.
+///
+/// See the [module-level documentation](./index.html) for more details.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct RcBytes {
+  /// The first byte of the buffer.
+  data: *const u8,
+  /// The length of the buffer in bytes.
+  len: usize,
+}
+
+/// A strong reference to an `Arc<[u8]>`, in a form C can hold.
+///
+/// Unlike [`RcBytes`], it may be sent to and shared with other threads.
+///
+/// See the [module-level documentation](./index.html) for more details.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ArcBytes {
+  /// The first byte of the buffer.
+  data: *const u8,
+  /// The length of the buffer in bytes.
+  len: usize,
+}
+
+// SAFETY: An `ArcBytes` is an `Arc<[u8]>`, which is `Send` and `Sync`.
+unsafe impl Send for ArcBytes {}
+// SAFETY: As above.
+unsafe impl Sync for ArcBytes {}
+
+impl RcBytes {
+  /// Turns `rc` into a handle for C, keeping its strong reference.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ffi::RcBytes;
+  /// use pointer::Boxed;
+  ///
+  /// let handle = RcBytes::from_rc(Boxed::<[u8]>::from(b"hi".to_vec()).into());
+  /// # drop(unsafe { handle.into_rc() });
+  /// ```
+  pub fn from_rc(rc: Rc<[u8]>) -> RcBytes {
+    let len = rc.len();
+    RcBytes {
+      data: Rc::into_raw(rc).cast::<u8>(),
+      len,
+    }
+  }
+
+  /// Turns the handle back into the `Rc` it holds a reference of.
+  ///
+  /// # Safety
+  ///
+  /// The handle must have come from [`RcBytes::from_rc`] or
+  /// [`pointer_rc_clone_raw`], on this thread, and its reference must not
+  /// have been given back already. Copies of the handle share its reference.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ffi::RcBytes;
+  /// use pointer::Boxed;
+  ///
+  /// let handle = RcBytes::from_rc(Boxed::<[u8]>::from(b"hi".to_vec()).into());
+  /// let rc = unsafe { handle.into_rc() };
+  /// assert_eq!(&*rc, b"hi");
+  /// ```
+  pub unsafe fn into_rc(self) -> Rc<[u8]> {
+    Rc::from_raw_slice(self.data, self.len)
+  }
+}
+
+impl ArcBytes {
+  /// Turns `arc` into a handle for C, keeping its strong reference.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ffi::ArcBytes;
+  /// use pointer::sync::Arc;
+  ///
+  /// let handle = ArcBytes::from_arc(Arc::from(&b"hi"[..]));
+  /// # drop(unsafe { handle.into_arc() });
+  /// ```
+  pub fn from_arc(arc: Arc<[u8]>) -> ArcBytes {
+    let len = arc.len();
+    ArcBytes {
+      data: Arc::into_raw(arc).cast::<u8>(),
+      len,
+    }
+  }
+
+  /// Turns the handle back into the `Arc` it holds a reference of.
+  ///
+  /// # Safety
+  ///
+  /// The handle must have come from [`ArcBytes::from_arc`] or
+  /// [`pointer_arc_clone_raw`], and its reference must not have been given
+  /// back already. Copies of the handle share its reference.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ffi::ArcBytes;
+  /// use pointer::sync::Arc;
+  ///
+  /// let handle = ArcBytes::from_arc(Arc::from(&b"hi"[..]));
+  /// let arc = unsafe { handle.into_arc() };
+  /// assert_eq!(&*arc, b"hi");
+  /// ```
+  pub unsafe fn into_arc(self) -> Arc<[u8]> {
+    Arc::from_raw_slice(self.data, self.len)
+  }
+}
+
+/// Takes another strong reference to the buffer of `bytes`, returning a
+/// handle that owns it.
+///
+/// # Safety
+///
+/// `bytes` must hold a reference that hasn't been given back, as for
+/// [`RcBytes::into_rc`].
+///
+/// # Examples
+///
+/// ```
+/// use pointer::ffi::{self, RcBytes};
+/// use pointer::{Boxed, Rc};
+///
+/// let rc: Rc<[u8]> = Boxed::<[u8]>::from(b"hi".to_vec()).into();
+/// let handle = RcBytes::from_rc(Rc::clone(&rc));
+/// let retained = unsafe { ffi::pointer_rc_clone_raw(handle) };
+/// assert_eq!(Rc::strong_count(&rc), 3);
+/// # unsafe { ffi::pointer_rc_release_raw(handle) };
+/// # unsafe { ffi::pointer_rc_release_raw(retained) };
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn pointer_rc_clone_raw(bytes: RcBytes) -> RcBytes {
+  let rc = std::mem::ManuallyDrop::new(bytes.into_rc());
+  RcBytes::from_rc(Rc::clone(&rc))
+}
+
+/// Gives back the strong reference held by `bytes`, freeing the buffer if it
+/// was the last one.
+///
+/// # Safety
+///
+/// As for [`RcBytes::into_rc`]; the handle and its copies can't be used
+/// afterwards.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::ffi::{self, RcBytes};
+/// use pointer::{Boxed, Rc};
+///
+/// let rc: Rc<[u8]> = Boxed::<[u8]>::from(b"hi".to_vec()).into();
+/// let handle = RcBytes::from_rc(Rc::clone(&rc));
+/// unsafe { ffi::pointer_rc_release_raw(handle) };
+/// assert_eq!(Rc::strong_count(&rc), 1);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn pointer_rc_release_raw(bytes: RcBytes) {
+  drop(bytes.into_rc());
+}
+
+/// Returns a pointer to the first byte of the buffer of `bytes`.
+///
+/// The bytes are valid for as long as the handle holds its reference.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::ffi::{self, RcBytes};
+/// use pointer::Boxed;
+///
+/// let handle = RcBytes::from_rc(Boxed::<[u8]>::from(b"hi".to_vec()).into());
+/// assert_eq!(unsafe { *ffi::pointer_rc_data(handle) }, b'h');
+/// # drop(unsafe { handle.into_rc() });
+/// ```
+#[no_mangle]
+pub extern "C" fn pointer_rc_data(bytes: RcBytes) -> *const u8 {
+  bytes.data
+}
+
+/// Returns the length of the buffer of `bytes`, in bytes.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::ffi::{self, RcBytes};
+/// use pointer::Boxed;
+///
+/// let handle = RcBytes::from_rc(Boxed::<[u8]>::from(b"hi".to_vec()).into());
+/// assert_eq!(ffi::pointer_rc_len(handle), 2);
+/// # drop(unsafe { handle.into_rc() });
+/// ```
+#[no_mangle]
+pub extern "C" fn pointer_rc_len(bytes: RcBytes) -> usize {
+  bytes.len
+}
+
+/// Takes another strong reference to the buffer of `bytes`, returning a
+/// handle that owns it.
+///
+/// # Safety
+///
+/// `bytes` must hold a reference that hasn't been given back, as for
+/// [`ArcBytes::into_arc`].
+///
+/// # Examples
+///
+/// ```
+/// use pointer::ffi::{self, ArcBytes};
+/// use pointer::sync::Arc;
+///
+/// let arc: Arc<[u8]> = Arc::from(&b"hi"[..]);
+/// let handle = ArcBytes::from_arc(Arc::clone(&arc));
+/// let retained = unsafe { ffi::pointer_arc_clone_raw(handle) };
+/// assert_eq!(Arc::strong_count(&arc), 3);
+/// # unsafe { ffi::pointer_arc_release_raw(handle) };
+/// # unsafe { ffi::pointer_arc_release_raw(retained) };
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn pointer_arc_clone_raw(bytes: ArcBytes) -> ArcBytes {
+  let arc = std::mem::ManuallyDrop::new(bytes.into_arc());
+  ArcBytes::from_arc(Arc::clone(&arc))
+}
+
+/// Gives back the strong reference held by `bytes`, freeing the buffer if it
+/// was the last one.
+///
+/// # Safety
+///
+/// As for [`ArcBytes::into_arc`]; the handle and its copies can't be used
+/// afterwards.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::ffi::{self, ArcBytes};
+/// use pointer::sync::Arc;
+///
+/// let arc: Arc<[u8]> = Arc::from(&b"hi"[..]);
+/// let handle = ArcBytes::from_arc(Arc::clone(&arc));
+/// unsafe { ffi::pointer_arc_release_raw(handle) };
+/// assert_eq!(Arc::strong_count(&arc), 1);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn pointer_arc_release_raw(bytes: ArcBytes) {
+  drop(bytes.into_arc());
+}
+
+/// Returns a pointer to the first byte of the buffer of `bytes`.
+///
+/// The bytes are valid for as long as the handle holds its reference.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::ffi::{self, ArcBytes};
+/// use pointer::sync::Arc;
+///
+/// let handle = ArcBytes::from_arc(Arc::from(&b"hi"[..]));
+/// assert_eq!(unsafe { *ffi::pointer_arc_data(handle) }, b'h');
+/// # drop(unsafe { handle.into_arc() });
+/// ```
+#[no_mangle]
+pub extern "C" fn pointer_arc_data(bytes: ArcBytes) -> *const u8 {
+  bytes.data
+}
+
+/// Returns the length of the buffer of `bytes`, in bytes.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::ffi::{self, ArcBytes};
+/// use pointer::sync::Arc;
+///
+/// let handle = ArcBytes::from_arc(Arc::from(&b"hi"[..]));
+/// assert_eq!(ffi::pointer_arc_len(handle), 2);
+/// # drop(unsafe { handle.into_arc() });
+/// ```
+#[no_mangle]
+pub extern "C" fn pointer_arc_len(bytes: ArcBytes) -> usize {
+  bytes.len
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn raw_rc_handles_keep_the_count() {
+    let rc: Rc<[u8]> = crate::Boxed::<[u8]>::from(vec![7u8; 5]).into();
+    let handle = RcBytes::from_rc(Rc::clone(&rc));
+
+    // SAFETY: Each reference is given back once.
+    unsafe {
+      let retained = pointer_rc_clone_raw(handle);
+      assert_eq!(Rc::strong_count(&rc), 3);
+      assert_eq!(pointer_rc_data(retained), rc.as_ptr());
+      pointer_rc_release_raw(retained);
+      pointer_rc_release_raw(handle);
+    }
+    assert_eq!(Rc::strong_count(&rc), 1);
+  }
+
+  #[test]
+  fn raw_arc_handles_cross_threads() {
+    let arc: Arc<[u8]> = Arc::from(vec![1u8, 2, 3]);
+    let handle = ArcBytes::from_arc(Arc::clone(&arc));
+
+    let sum = std::thread::spawn(move || {
+      // SAFETY: The handle holds a reference, which is given back here.
+      let arc = unsafe { handle.into_arc() };
+      arc.iter().map(|&b| b as u32).sum::<u32>()
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(sum, 6);
+    assert_eq!(Arc::strong_count(&arc), 1);
+  }
+}
@@ -0,0 +1,763 @@
+//! A single-threaded reference-counting pointer that also collects cycles.
+//!
+//! [`Gc<T>`][Gc] counts references like [`Rc<T>`][Rc] and frees a value as soon as its last
+//! pointer is dropped. What it adds is a way out for cycles: an object graph where nodes point
+//! back to each other never reaches a count of zero, so `Rc` leaks it unless the back edges are
+//! made [`Weak`]. With `Gc`, [`collect_cycles`] finds such garbage and frees it, so graphs with
+//! genuine cycles can be built from strong pointers alone.
+//!
+//! The collector is the synchronous trial deletion of Bacon and Rajan ("Concurrent Cycle
+//! Collection in Reference Counted Systems", 2001). When a count is decremented without reaching
+//! zero, the object is remembered as a possible root of a garbage cycle. [`collect_cycles`] then
+//! subtracts the references the candidates hold to each other; whatever is left with no count is
+//! only kept alive by the cycle, and is freed. Nothing is scanned that isn't reachable from a
+//! candidate, and nothing runs in the background: cycles are only collected when
+//! [`collect_cycles`] is called.
+//!
+//! To find the references between objects, the collector asks each value for the `Gc`s it owns
+//! through the [`Trace`] trait. With the `derive` feature, `#[derive(Trace)]` implements it.
+//!
+//! ```
+//! use pointer::gc::{self, Gc, Trace, Tracer};
+//! use pointer::RefCell;
+//!
+//! struct Node {
+//!   edges: RefCell<Vec<Gc<Node>>>,
+//! }
+//!
+//! unsafe impl Trace for Node {
+//!   fn trace(&self, tracer: &mut Tracer<'_>) {
+//!     self.edges.trace(tracer);
+//!   }
+//! }
+//!
+//! let a = Gc::new(Node { edges: RefCell::new(vec![]) });
+//! let b = Gc::new(Node { edges: RefCell::new(vec![Gc::clone(&a)]) });
+//! a.edges.borrow_mut().push(Gc::clone(&b));
+//!
+//! // The two nodes now keep each other alive.
+//! drop(a);
+//! drop(b);
+//! assert_eq!(gc::collect_cycles(), 2);
+//! ```
+//!
+//! A value's [`Drop`] may run while the rest of its cycle is being freed, so it must not
+//! dereference or clone the `Gc`s it holds into the cycle; doing so panics.
+//!
+//! [Rc]: crate::Rc
+//! [`Weak`]: crate::Weak
+
+use crate::cell::Cell;
+
+use std::ptr::NonNull;
+
+#[cfg(feature = "derive")]
+pub use pointer_derive::Trace;
+
+/// A type whose values can report the [`Gc`] pointers they own.
+///
+/// # Safety
+///
+/// [`trace`] must call [`Tracer::visit`] at most once for each `Gc` the
+/// value owns, i.e. each `Gc` whose drop would decrement a count, and must
+/// report the same pointers each time it is called while the value isn't
+/// mutated. Reporting a `Gc` the value doesn't own, like one behind a shared
+/// reference or an [`Rc`](crate::Rc), leads to freeing values that are still
+/// in use. Leaving a `Gc` out is safe, but any cycle through it is never
+/// collected.
+///
+/// [`trace`]: Trace::trace
+pub unsafe trait Trace {
+  /// Calls [`Tracer::visit`] with each `Gc` owned by `self`.
+  fn trace(&self, tracer: &mut Tracer<'_>);
+}
+
+/// The visitor passed to [`Trace::trace`].
+pub struct Tracer<'a> {
+  visit: &'a mut dyn FnMut(GcPtr),
+}
+
+impl Tracer<'_> {
+  /// Reports a `Gc` owned by the value being traced.
+  pub fn visit<T: Trace + 'static>(&mut self, gc: &Gc<T>) {
+    (self.visit)(gc.ptr)
+  }
+}
+
+/// The state of an allocation in the collector, as in the paper.
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Color {
+  /// In use, or freed.
+  Black,
+  /// Possibly part of a garbage cycle.
+  Gray,
+  /// Part of a garbage cycle.
+  White,
+  /// A possible root of a garbage cycle.
+  Purple,
+  /// The value is being dropped.
+  Dropped,
+}
+
+struct Header {
+  strong: Cell<usize>,
+  color: Cell<Color>,
+  /// Whether the allocation is in the roots buffer.
+  buffered: Cell<bool>,
+}
+
+#[repr(C)]
+struct GcBox<T: ?Sized> {
+  header: Header,
+  value: T,
+}
+
+/// A type-erased pointer to a `GcBox`.
+type GcPtr = NonNull<GcBox<dyn Trace>>;
+
+std::thread_local! {
+  /// The possible roots of garbage cycles on this thread.
+  static ROOTS: std::cell::RefCell<Vec<GcPtr>> =
+    const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// A single-threaded reference-counting pointer with cycle collection.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct Gc<T: Trace + 'static> {
+  ptr: NonNull<GcBox<T>>,
+  phantom: std::marker::PhantomData<GcBox<T>>,
+}
+
+impl<T: Trace + 'static> Gc<T> {
+  /// Constructs a new `Gc<T>`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::gc::Gc;
+  ///
+  /// let five = Gc::new(5);
+  /// ```
+  pub fn new(value: T) -> Gc<T> {
+    let boxed = Box::new(GcBox {
+      header: Header {
+        strong: Cell::new(1),
+        color: Cell::new(Color::Black),
+        buffered: Cell::new(false),
+      },
+      value,
+    });
+    Gc {
+      // SAFETY: `Box::into_raw` never returns a null pointer.
+      ptr: unsafe { NonNull::new_unchecked(Box::into_raw(boxed)) },
+      phantom: std::marker::PhantomData,
+    }
+  }
+
+  /// Gets the number of `Gc` pointers to this allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::gc::Gc;
+  ///
+  /// let five = Gc::new(5);
+  /// let _also_five = Gc::clone(&five);
+  ///
+  /// assert_eq!(2, Gc::strong_count(&five));
+  /// ```
+  pub fn strong_count(this: &Self) -> usize {
+    header(this.ptr).strong.get()
+  }
+
+  /// Returns `true` if the two `Gc`s point to the same allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::gc::Gc;
+  ///
+  /// let five = Gc::new(5);
+  /// let same_five = Gc::clone(&five);
+  /// let other_five = Gc::new(5);
+  ///
+  /// assert!(Gc::ptr_eq(&five, &same_five));
+  /// assert!(!Gc::ptr_eq(&five, &other_five));
+  /// ```
+  pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+    this.ptr == other.ptr
+  }
+
+  #[track_caller]
+  fn check_alive(&self) {
+    assert!(
+      header(self.ptr).color.get() != Color::Dropped,
+      "`Gc` used while its cycle is being collected"
+    );
+  }
+}
+
+impl<T: Trace + 'static> Clone for Gc<T> {
+  /// Makes a clone of the `Gc` pointer, increasing the count.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is being dropped by [`collect_cycles`].
+  fn clone(&self) -> Gc<T> {
+    self.check_alive();
+    let header = header(self.ptr);
+    let strong = header.strong.get();
+    // Abort on overflow instead of freeing a value that is still in use.
+    if strong == usize::MAX {
+      crate::alloc::abort_on_overflow();
+    }
+    header.strong.set(strong + 1);
+    header.color.set(Color::Black);
+    Gc {
+      ptr: self.ptr,
+      phantom: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<T: Trace + 'static> std::ops::Deref for Gc<T> {
+  type Target = T;
+
+  /// # Panics
+  ///
+  /// Panics if the value is being dropped by [`collect_cycles`].
+  #[inline]
+  fn deref(&self) -> &T {
+    self.check_alive();
+    // SAFETY: While this `Gc` is alive and the value isn't being dropped,
+    // the allocation is valid.
+    unsafe { &(*self.ptr.as_ptr()).value }
+  }
+}
+
+impl<T: Trace + 'static> Drop for Gc<T> {
+  /// Drops the `Gc`, freeing the value with the last pointer, or remembering
+  /// it as a possible root of a garbage cycle otherwise.
+  fn drop(&mut self) {
+    let ptr: GcPtr = self.ptr;
+    let header = header(ptr);
+    // The collector frees this value along with the rest of its cycle, and
+    // has already accounted for this pointer.
+    if header.color.get() == Color::Dropped {
+      return;
+    }
+
+    let strong = header.strong.get() - 1;
+    header.strong.set(strong);
+    if strong == 0 {
+      header.color.set(Color::Dropped);
+      // SAFETY: This was the last pointer.
+      unsafe { std::ptr::drop_in_place(&mut (*self.ptr.as_ptr()).value) };
+      if header.buffered.get() {
+        // The roots buffer still points here; the collector frees it.
+        header.color.set(Color::Black);
+      } else {
+        // SAFETY: Nothing points to the allocation anymore.
+        unsafe { dealloc_box(ptr) };
+      }
+    } else if header.color.get() != Color::Purple {
+      header.color.set(Color::Purple);
+      if !header.buffered.get() {
+        // During thread teardown the buffer may be gone, and the value simply
+        // isn't considered for collection.
+        let pushed = ROOTS.try_with(|roots| roots.borrow_mut().push(ptr));
+        header.buffered.set(pushed.is_ok());
+      }
+    }
+  }
+}
+
+impl<T: Trace + Default + 'static> Default for Gc<T> {
+  fn default() -> Gc<T> {
+    Gc::new(Default::default())
+  }
+}
+
+impl<T: Trace + 'static> From<T> for Gc<T> {
+  fn from(value: T) -> Gc<T> {
+    Gc::new(value)
+  }
+}
+
+impl<T: Trace + std::fmt::Display + 'static> std::fmt::Display for Gc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: Trace + std::fmt::Debug + 'static> std::fmt::Debug for Gc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: Trace + 'static> std::fmt::Pointer for Gc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Pointer::fmt(&self.ptr, f)
+  }
+}
+
+/// Frees the garbage cycles among the possible roots on this thread, and
+/// returns how many values were freed.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::gc::{self, Gc, Trace, Tracer};
+/// use pointer::RefCell;
+///
+/// struct Link(RefCell<Option<Gc<Link>>>);
+///
+/// unsafe impl Trace for Link {
+///   fn trace(&self, tracer: &mut Tracer<'_>) {
+///     self.0.trace(tracer);
+///   }
+/// }
+///
+/// let node = Gc::new(Link(RefCell::new(None)));
+/// *node.0.borrow_mut() = Some(Gc::clone(&node));
+///
+/// // Still reachable.
+/// assert_eq!(gc::collect_cycles(), 0);
+///
+/// drop(node);
+/// assert_eq!(gc::collect_cycles(), 1);
+/// ```
+pub fn collect_cycles() -> usize {
+  let roots = ROOTS.with(|roots| std::mem::take(&mut *roots.borrow_mut()));
+
+  // Trial deletion: take away the references within the subgraphs of the
+  // candidates.
+  let mut candidates = Vec::new();
+  for root in roots {
+    let header = header(root);
+    match header.color.get() {
+      Color::Purple if header.strong.get() > 0 => {
+        mark_gray(root);
+        candidates.push(root);
+      }
+      color => {
+        header.buffered.set(false);
+        if color == Color::Black && header.strong.get() == 0 {
+          // SAFETY: The value was dropped with the last pointer, and the
+          // allocation was only left for the buffer.
+          unsafe { dealloc_box(root) };
+        }
+      }
+    }
+  }
+
+  // Whatever still has a count is referenced from outside, along with
+  // everything it reaches.
+  for &root in &candidates {
+    scan(root);
+  }
+
+  for &root in &candidates {
+    header(root).buffered.set(false);
+  }
+  let mut garbage = Vec::new();
+  for &root in &candidates {
+    collect_white(root, &mut garbage);
+  }
+
+  // Give back the references from the garbage to live values, which are
+  // released as the garbage is dropped.
+  for &ptr in &garbage {
+    for_each_child(ptr, &mut |child| {
+      let header = header(child);
+      if header.color.get() != Color::Dropped {
+        header.strong.set(header.strong.get() + 1);
+      }
+    });
+  }
+  for &ptr in &garbage {
+    // SAFETY: Only the cycle refers to the value, and the `Gc`s in it are
+    // inert now that it is marked as dropped.
+    unsafe { std::ptr::drop_in_place(&mut (*ptr.as_ptr()).value) };
+  }
+  for &ptr in &garbage {
+    // SAFETY: All the values are dropped, and nothing points to them.
+    unsafe { dealloc_box(ptr) };
+  }
+  garbage.len()
+}
+
+fn mark_gray(root: GcPtr) {
+  if header(root).color.get() == Color::Gray {
+    return;
+  }
+  header(root).color.set(Color::Gray);
+  let mut stack = vec![root];
+  while let Some(ptr) = stack.pop() {
+    for_each_child(ptr, &mut |child| {
+      let header = header(child);
+      header.strong.set(header.strong.get() - 1);
+      if header.color.get() != Color::Gray {
+        header.color.set(Color::Gray);
+        stack.push(child);
+      }
+    });
+  }
+}
+
+fn scan(root: GcPtr) {
+  let mut stack = vec![root];
+  while let Some(ptr) = stack.pop() {
+    let header = header(ptr);
+    if header.color.get() != Color::Gray {
+      continue;
+    }
+    if header.strong.get() > 0 {
+      scan_black(ptr);
+    } else {
+      header.color.set(Color::White);
+      for_each_child(ptr, &mut |child| stack.push(child));
+    }
+  }
+}
+
+fn scan_black(root: GcPtr) {
+  header(root).color.set(Color::Black);
+  let mut stack = vec![root];
+  while let Some(ptr) = stack.pop() {
+    for_each_child(ptr, &mut |child| {
+      let header = header(child);
+      header.strong.set(header.strong.get() + 1);
+      if header.color.get() != Color::Black {
+        header.color.set(Color::Black);
+        stack.push(child);
+      }
+    });
+  }
+}
+
+fn collect_white(root: GcPtr, garbage: &mut Vec<GcPtr>) {
+  let mut stack = vec![root];
+  while let Some(ptr) = stack.pop() {
+    let header = header(ptr);
+    if header.color.get() == Color::White && !header.buffered.get() {
+      header.color.set(Color::Dropped);
+      garbage.push(ptr);
+      for_each_child(ptr, &mut |child| stack.push(child));
+    }
+  }
+}
+
+/// Calls `f` with each `Gc` the value at `ptr` reports.
+fn for_each_child(ptr: GcPtr, f: &mut dyn FnMut(GcPtr)) {
+  // SAFETY: The collector only traces values that haven't been dropped.
+  let value = unsafe { &(*ptr.as_ptr()).value };
+  value.trace(&mut Tracer { visit: f });
+}
+
+fn header<'a, T: ?Sized>(ptr: NonNull<GcBox<T>>) -> &'a Header {
+  // SAFETY: The header stays valid until the allocation is freed, and is
+  // only accessed through `Cell`s.
+  unsafe { &*std::ptr::addr_of!((*ptr.as_ptr()).header) }
+}
+
+/// Frees the allocation of a `GcBox` whose value has been dropped.
+unsafe fn dealloc_box(ptr: GcPtr) {
+  let layout = std::alloc::Layout::for_value(ptr.as_ref());
+  std::alloc::dealloc(ptr.as_ptr().cast::<u8>(), layout);
+}
+
+unsafe impl<T: Trace + 'static> Trace for Gc<T> {
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    tracer.visit(self);
+  }
+}
+
+macro_rules! untraced {
+  ($($t:ty),* $(,)?) => {
+    $(
+      unsafe impl Trace for $t {
+        #[inline]
+        fn trace(&self, _: &mut Tracer<'_>) {}
+      }
+    )*
+  };
+}
+
+untraced!(
+  (),
+  bool,
+  char,
+  u8,
+  u16,
+  u32,
+  u64,
+  u128,
+  usize,
+  i8,
+  i16,
+  i32,
+  i64,
+  i128,
+  isize,
+  f32,
+  f64,
+  str,
+  String,
+  std::path::Path,
+  std::path::PathBuf,
+);
+
+// A `Copy` value can't own a `Gc`.
+unsafe impl<T: Copy> Trace for Cell<T> {
+  #[inline]
+  fn trace(&self, _: &mut Tracer<'_>) {}
+}
+
+unsafe impl<T: ?Sized> Trace for std::marker::PhantomData<T> {
+  #[inline]
+  fn trace(&self, _: &mut Tracer<'_>) {}
+}
+
+unsafe impl<T: Trace> Trace for crate::RefCell<T> {
+  /// Traces the value, unless it is mutably borrowed.
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    // A mutably borrowed value is in use, so it can't be garbage; leaving out
+    // its `Gc`s keeps everything it reaches alive for this collection.
+    if let Ok(value) = self.try_borrow() {
+      value.trace(tracer);
+    }
+  }
+}
+
+unsafe impl<T: Trace> Trace for std::cell::RefCell<T> {
+  /// Traces the value, unless it is mutably borrowed.
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    if let Ok(value) = self.try_borrow() {
+      value.trace(tracer);
+    }
+  }
+}
+
+unsafe impl<T: Trace + ?Sized> Trace for Box<T> {
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    (**self).trace(tracer);
+  }
+}
+
+unsafe impl<T: Trace> Trace for Option<T> {
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    if let Some(value) = self {
+      value.trace(tracer);
+    }
+  }
+}
+
+unsafe impl<T: Trace, E: Trace> Trace for Result<T, E> {
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    match self {
+      Ok(value) => value.trace(tracer),
+      Err(error) => error.trace(tracer),
+    }
+  }
+}
+
+unsafe impl<T: Trace> Trace for [T] {
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    for value in self {
+      value.trace(tracer);
+    }
+  }
+}
+
+unsafe impl<T: Trace, const N: usize> Trace for [T; N] {
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    self[..].trace(tracer);
+  }
+}
+
+unsafe impl<T: Trace> Trace for Vec<T> {
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    self[..].trace(tracer);
+  }
+}
+
+unsafe impl<T: Trace> Trace for std::collections::VecDeque<T> {
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    for value in self {
+      value.trace(tracer);
+    }
+  }
+}
+
+unsafe impl<K: Trace, V: Trace, S> Trace
+  for std::collections::HashMap<K, V, S>
+{
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    for (key, value) in self {
+      key.trace(tracer);
+      value.trace(tracer);
+    }
+  }
+}
+
+unsafe impl<K: Trace, V: Trace> Trace for std::collections::BTreeMap<K, V> {
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    for (key, value) in self {
+      key.trace(tracer);
+      value.trace(tracer);
+    }
+  }
+}
+
+unsafe impl<T: Trace, S> Trace for std::collections::HashSet<T, S> {
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    for value in self {
+      value.trace(tracer);
+    }
+  }
+}
+
+unsafe impl<T: Trace> Trace for std::collections::BTreeSet<T> {
+  fn trace(&self, tracer: &mut Tracer<'_>) {
+    for value in self {
+      value.trace(tracer);
+    }
+  }
+}
+
+macro_rules! traced_tuple {
+  ($($name:ident)+) => {
+    unsafe impl<$($name: Trace),+> Trace for ($($name,)+) {
+      #[allow(non_snake_case)]
+      fn trace(&self, tracer: &mut Tracer<'_>) {
+        let ($($name,)+) = self;
+        $($name.trace(tracer);)+
+      }
+    }
+  };
+}
+
+traced_tuple!(A);
+traced_tuple!(A B);
+traced_tuple!(A B C);
+traced_tuple!(A B C D);
+traced_tuple!(A B C D E);
+traced_tuple!(A B C D E F);
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::RefCell;
+
+  struct Node {
+    edges: RefCell<Vec<Gc<Node>>>,
+    dropped: crate::Rc<Cell<usize>>,
+  }
+
+  unsafe impl Trace for Node {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+      self.edges.trace(tracer);
+    }
+  }
+
+  impl Drop for Node {
+    fn drop(&mut self) {
+      self.dropped.set(self.dropped.get() + 1);
+    }
+  }
+
+  fn node(dropped: &crate::Rc<Cell<usize>>) -> Gc<Node> {
+    Gc::new(Node {
+      edges: RefCell::new(Vec::new()),
+      dropped: crate::Rc::clone(dropped),
+    })
+  }
+
+  #[test]
+  fn collects_cycle_but_keeps_reachable_values() {
+    let dropped = crate::Rc::new(Cell::new(0));
+    let (a, b, c) = (node(&dropped), node(&dropped), node(&dropped));
+    // a <-> b -> c, with c kept alive from outside too.
+    a.edges.borrow_mut().push(Gc::clone(&b));
+    b.edges.borrow_mut().push(Gc::clone(&a));
+    b.edges.borrow_mut().push(Gc::clone(&c));
+
+    drop(b);
+    assert_eq!(collect_cycles(), 0);
+    assert_eq!(Gc::strong_count(&a), 2);
+
+    drop(a);
+    assert_eq!(collect_cycles(), 2);
+    assert_eq!(dropped.get(), 2);
+    assert_eq!(Gc::strong_count(&c), 1);
+
+    // `c` became a possible root when `b` let go of it, so its memory is
+    // left to the next collection.
+    drop(c);
+    assert_eq!(dropped.get(), 3);
+    assert_eq!(collect_cycles(), 0);
+  }
+
+  #[test]
+  fn buffered_value_freed_by_collector() {
+    let dropped = crate::Rc::new(Cell::new(0));
+    let a = node(&dropped);
+    let b = Gc::clone(&a);
+    drop(b);
+    // `a` is a possible root now, and its value is dropped right away but the
+    // memory is left to the collector.
+    drop(a);
+    assert_eq!(dropped.get(), 1);
+    assert_eq!(collect_cycles(), 0);
+  }
+
+  #[test]
+  #[should_panic(expected = "being collected")]
+  fn deref_in_drop_panics() {
+    struct Peek(RefCell<Option<Gc<Peek>>>);
+    unsafe impl Trace for Peek {
+      fn trace(&self, tracer: &mut Tracer<'_>) {
+        self.0.trace(tracer);
+      }
+    }
+    impl Drop for Peek {
+      fn drop(&mut self) {
+        if let Some(other) = &*self.0.borrow() {
+          let _ = &**other;
+        }
+      }
+    }
+
+    let a = Gc::new(Peek(RefCell::new(None)));
+    *a.0.borrow_mut() = Some(Gc::clone(&a));
+    drop(a);
+    collect_cycles();
+  }
+
+  #[cfg(feature = "derive")]
+  #[test]
+  fn derived_trace() {
+    #[derive(Trace)]
+    enum Tree<T: Trace + 'static> {
+      Leaf(T),
+      Branch {
+        children: RefCell<Vec<Gc<Tree<T>>>>,
+        #[trace(skip)]
+        _label: crate::Rc<str>,
+      },
+    }
+
+    let root: Gc<Tree<u8>> = Gc::new(Tree::Branch {
+      children: RefCell::new(vec![Gc::new(Tree::Leaf(1))]),
+      _label: crate::Rc::from("root"),
+    });
+    if let Tree::Branch { children, .. } = &*root {
+      children.borrow_mut().push(Gc::clone(&root));
+    }
+    drop(root);
+    assert_eq!(collect_cycles(), 2);
+  }
+}
@@ -0,0 +1,396 @@
+//! A cell that remembers its earlier values: [`HistoryCell<T>`][HistoryCell], with undo and redo.
+//!
+//! A `HistoryCell` keeps an undo list of earlier values, holding at most the limit it was made
+//! with and forgetting the oldest values first. [`set`] and [`update`] record the value they
+//! overwrite, so each of them is one step to undo. Edits through [`borrow_mut`] aren't recorded:
+//! call [`checkpoint`] before a group of them to make the whole group one step.
+//!
+//! [`undo`] goes back to the last recorded value, and [`redo`] forward again. A new step clears
+//! what could have been redone, as in an editor.
+//!
+//! ```
+//! use pointer::HistoryCell;
+//!
+//! let text = HistoryCell::new(String::new(), 100);
+//!
+//! text.set(String::from("Hello"));
+//! text.checkpoint();
+//! text.borrow_mut().push(',');
+//! text.borrow_mut().push_str(" world");
+//! assert_eq!(text.get(), "Hello, world");
+//!
+//! assert!(text.undo());
+//! assert_eq!(text.get(), "Hello");
+//! assert!(text.undo());
+//! assert_eq!(text.get(), "");
+//!
+//! assert!(text.redo());
+//! assert_eq!(text.get(), "Hello");
+//! ```
+//!
+//! [`set`]: HistoryCell::set
+//! [`update`]: HistoryCell::update
+//! [`borrow_mut`]: HistoryCell::borrow_mut
+//! [`checkpoint`]: HistoryCell::checkpoint
+//! [`undo`]: HistoryCell::undo
+//! [`redo`]: HistoryCell::redo
+
+use crate::{Ref, RefCell, RefMut};
+use std::collections::VecDeque;
+
+/// A mutable value with a bounded history of earlier values.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct HistoryCell<T> {
+  value: RefCell<T>,
+  /// Earlier values, the most recent last.
+  undo: RefCell<VecDeque<T>>,
+  /// Undone values, the most recently undone last.
+  redo: RefCell<Vec<T>>,
+  limit: usize,
+}
+
+impl<T> HistoryCell<T> {
+  /// Creates a new `HistoryCell` containing `value`, which remembers at most
+  /// `limit` earlier values.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::HistoryCell;
+  ///
+  /// let cell = HistoryCell::new(5, 10);
+  /// assert!(!cell.can_undo());
+  /// ```
+  pub fn new(value: T, limit: usize) -> HistoryCell<T> {
+    HistoryCell {
+      value: RefCell::new(value),
+      undo: RefCell::new(VecDeque::new()),
+      redo: RefCell::new(Vec::new()),
+      limit,
+    }
+  }
+
+  /// Immutably borrows the value.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently mutably borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::HistoryCell;
+  ///
+  /// let cell = HistoryCell::new(vec![1], 10);
+  /// assert_eq!(cell.borrow().len(), 1);
+  /// ```
+  pub fn borrow(&self) -> Ref<'_, T> {
+    self.value.borrow()
+  }
+
+  /// Mutably borrows the value, without recording it in the history.
+  ///
+  /// Call [`checkpoint`](HistoryCell::checkpoint) first to make the edits
+  /// undoable.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::HistoryCell;
+  ///
+  /// let cell = HistoryCell::new(vec![1], 10);
+  /// cell.borrow_mut().push(2);
+  ///
+  /// assert_eq!(*cell.borrow(), [1, 2]);
+  /// assert!(!cell.can_undo());
+  /// ```
+  pub fn borrow_mut(&self) -> RefMut<'_, T> {
+    self.value.borrow_mut()
+  }
+
+  /// Goes back to the most recent value in the history, and returns `true`,
+  /// or returns `false` if there is none.
+  ///
+  /// The current value can then be brought back with
+  /// [`redo`](HistoryCell::redo).
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::HistoryCell;
+  ///
+  /// let cell = HistoryCell::new(1, 10);
+  /// cell.set(2);
+  ///
+  /// assert!(cell.undo());
+  /// assert_eq!(cell.get(), 1);
+  /// assert!(!cell.undo());
+  /// ```
+  pub fn undo(&self) -> bool {
+    match self.undo.borrow_mut().pop_back() {
+      Some(earlier) => {
+        let current = self.value.replace(earlier);
+        self.redo.borrow_mut().push(current);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Goes forward to the most recently undone value, and returns `true`, or
+  /// returns `false` if there is none.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::HistoryCell;
+  ///
+  /// let cell = HistoryCell::new(1, 10);
+  /// cell.set(2);
+  /// cell.undo();
+  ///
+  /// assert!(cell.redo());
+  /// assert_eq!(cell.get(), 2);
+  /// assert!(!cell.redo());
+  /// ```
+  pub fn redo(&self) -> bool {
+    match self.redo.borrow_mut().pop() {
+      Some(later) => {
+        let current = self.value.replace(later);
+        self.push_undo(current);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Returns `true` if there is a value to [`undo`](HistoryCell::undo) to.
+  pub fn can_undo(&self) -> bool {
+    !self.undo.borrow().is_empty()
+  }
+
+  /// Returns `true` if there is a value to [`redo`](HistoryCell::redo) to.
+  pub fn can_redo(&self) -> bool {
+    !self.redo.borrow().is_empty()
+  }
+
+  /// Forgets every earlier and undone value, keeping the current one.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::HistoryCell;
+  ///
+  /// let cell = HistoryCell::new(1, 10);
+  /// cell.set(2);
+  /// cell.clear_history();
+  ///
+  /// assert!(!cell.undo());
+  /// assert_eq!(cell.get(), 2);
+  /// ```
+  pub fn clear_history(&self) {
+    self.undo.borrow_mut().clear();
+    self.redo.borrow_mut().clear();
+  }
+
+  /// Consumes the cell, returning the current value.
+  pub fn into_inner(self) -> T {
+    self.value.into_inner()
+  }
+
+  /// Adds `earlier` to the history, forgetting the oldest value if the
+  /// history is full.
+  fn push_undo(&self, earlier: T) {
+    if self.limit == 0 {
+      return;
+    }
+    let mut undo = self.undo.borrow_mut();
+    if undo.len() == self.limit {
+      undo.pop_front();
+    }
+    undo.push_back(earlier);
+  }
+
+  /// Records `earlier` as a new step, which can't be followed by a redo.
+  fn record(&self, earlier: T) {
+    self.push_undo(earlier);
+    self.redo.borrow_mut().clear();
+  }
+}
+
+impl<T: Clone> HistoryCell<T> {
+  /// Returns a clone of the value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::HistoryCell;
+  ///
+  /// let cell = HistoryCell::new(5, 10);
+  /// assert_eq!(cell.get(), 5);
+  /// ```
+  pub fn get(&self) -> T {
+    self.value.borrow().clone()
+  }
+
+  /// Records the current value in the history, and clears what could be
+  /// redone.
+  ///
+  /// The edits that follow, up to the next step, are undone together.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently mutably borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::HistoryCell;
+  ///
+  /// let cell = HistoryCell::new(vec![1], 10);
+  /// cell.checkpoint();
+  /// cell.borrow_mut().push(2);
+  /// cell.borrow_mut().push(3);
+  ///
+  /// cell.undo();
+  /// assert_eq!(cell.get(), [1]);
+  /// ```
+  pub fn checkpoint(&self) {
+    let current = self.get();
+    self.record(current);
+  }
+
+  /// Sets the value, recording the old one in the history.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::HistoryCell;
+  ///
+  /// let cell = HistoryCell::new(1, 10);
+  /// cell.set(2);
+  /// assert_eq!(cell.get(), 2);
+  /// ```
+  pub fn set(&self, value: T) {
+    let earlier = self.value.replace(value);
+    self.record(earlier);
+  }
+
+  /// Changes the value in place with `f`, recording the old value in the
+  /// history.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently borrowed, or if `f` borrows it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::HistoryCell;
+  ///
+  /// let cell = HistoryCell::new(vec![1], 10);
+  /// cell.update(|v| v.push(2));
+  ///
+  /// cell.undo();
+  /// assert_eq!(cell.get(), [1]);
+  /// ```
+  pub fn update(&self, f: impl FnOnce(&mut T)) {
+    self.checkpoint();
+    f(&mut self.value.borrow_mut());
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for HistoryCell<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.value.try_borrow() {
+      Ok(value) => f
+        .debug_struct("HistoryCell")
+        .field("value", &*value)
+        .field("undo", &self.undo.borrow().len())
+        .field("redo", &self.redo.borrow().len())
+        .finish(),
+      Err(_) => f.write_str("HistoryCell { <borrowed> }"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn undo_and_redo_walk_the_history() {
+    let cell = HistoryCell::new(0, 10);
+    for n in 1..=3 {
+      cell.set(n);
+    }
+
+    assert!(cell.undo());
+    assert!(cell.undo());
+    assert_eq!(cell.get(), 1);
+    assert!(cell.redo());
+    assert_eq!(cell.get(), 2);
+
+    // A new step drops what could have been redone.
+    cell.set(10);
+    assert!(!cell.can_redo());
+    assert!(cell.undo());
+    assert_eq!(cell.get(), 2);
+    assert!(cell.undo());
+    assert!(cell.undo());
+    assert_eq!(cell.get(), 0);
+    assert!(!cell.undo());
+  }
+
+  #[test]
+  fn history_is_bounded() {
+    let cell = HistoryCell::new(0, 2);
+    for n in 1..=5 {
+      cell.set(n);
+    }
+
+    assert!(cell.undo());
+    assert!(cell.undo());
+    assert!(!cell.undo());
+    assert_eq!(cell.get(), 3);
+
+    let forgetful = HistoryCell::new(0, 0);
+    forgetful.set(1);
+    assert!(!forgetful.undo());
+  }
+
+  #[test]
+  fn checkpoints_group_edits() {
+    let cell = HistoryCell::new(String::from("a"), 10);
+    cell.checkpoint();
+    cell.borrow_mut().push('b');
+    cell.borrow_mut().push('c');
+    cell.update(|s| s.push('d'));
+
+    assert!(cell.undo());
+    assert_eq!(cell.get(), "abc");
+    assert!(cell.undo());
+    assert_eq!(cell.get(), "a");
+
+    cell.clear_history();
+    assert!(!cell.can_undo() && !cell.can_redo());
+    assert_eq!(cell.into_inner(), "a");
+  }
+}
@@ -0,0 +1,237 @@
+//! A per-thread registry of live `Rc` allocations, for the `leak-debug` feature.
+//!
+//! Every `RcBox` is registered when it is allocated and removed when it is freed, together with
+//! the name of its value type and the backtrace of where it was made. Backtraces are captured
+//! with [`Backtrace::capture`], so they are only recorded when `RUST_BACKTRACE` or
+//! `RUST_LIB_BACKTRACE` is set.
+//!
+//! When a thread exits with allocations still registered, they are reported on standard error.
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::collections::HashMap;
+
+/// Reads the strong and weak counts at the start of an `RcBox`.
+pub(crate) type ReadCounts = unsafe fn(*const ()) -> (usize, usize);
+
+struct Entry {
+  /// The allocation, kept as a pointer so that its counts can be read.
+  address: *const (),
+  seq: u64,
+  type_name: &'static str,
+  counts: ReadCounts,
+  backtrace: std::rc::Rc<Backtrace>,
+}
+
+struct Registry {
+  entries: std::cell::RefCell<HashMap<usize, Entry>>,
+  next_seq: std::cell::Cell<u64>,
+}
+
+impl Drop for Registry {
+  /// Reports the allocations that outlive their thread.
+  fn drop(&mut self) {
+    let live = snapshot(&self.entries.borrow());
+    if live.is_empty() {
+      return;
+    }
+    eprintln!(
+      "{} `Rc` allocation(s) still live at thread exit:",
+      live.len()
+    );
+    for allocation in live {
+      eprintln!("{}", allocation);
+    }
+  }
+}
+
+std::thread_local! {
+  static REGISTRY: Registry = Registry {
+    entries: std::cell::RefCell::new(HashMap::new()),
+    next_seq: std::cell::Cell::new(0),
+  };
+}
+
+/// Records a new allocation at `address`.
+pub(crate) fn register(
+  address: *const (),
+  type_name: &'static str,
+  counts: ReadCounts,
+) {
+  let backtrace = std::rc::Rc::new(Backtrace::capture());
+  // Allocations made while the thread is being torn down aren't tracked.
+  let _ = REGISTRY.try_with(|registry| {
+    let seq = registry.next_seq.get();
+    registry.next_seq.set(seq + 1);
+    registry.entries.borrow_mut().insert(
+      address.addr(),
+      Entry {
+        address,
+        seq,
+        type_name,
+        counts,
+        backtrace,
+      },
+    );
+  });
+}
+
+/// Forgets the allocation at `address`, which is being freed.
+pub(crate) fn unregister(address: *const ()) {
+  let _ = REGISTRY
+    .try_with(|registry| registry.entries.borrow_mut().remove(&address.addr()));
+}
+
+fn snapshot(entries: &HashMap<usize, Entry>) -> Vec<LiveAllocation> {
+  let mut live: Vec<_> = entries
+    .values()
+    .map(|entry| {
+      // SAFETY: Registered allocations are valid until they are unregistered.
+      let (strong, weak) = unsafe { (entry.counts)(entry.address) };
+      (
+        entry.seq,
+        LiveAllocation {
+          type_name: entry.type_name,
+          address: entry.address,
+          strong_count: strong,
+          // Leave out the implicit weak pointer held by the strong ones.
+          weak_count: if strong > 0 { weak - 1 } else { weak },
+          backtrace: std::rc::Rc::clone(&entry.backtrace),
+        },
+      )
+    })
+    .collect();
+  live.sort_by_key(|&(seq, _)| seq);
+  live.into_iter().map(|(_, allocation)| allocation).collect()
+}
+
+/// Returns the `Rc` allocations on this thread that haven't been freed, oldest
+/// first.
+///
+/// An allocation is live until both its strong and weak pointers are gone;
+/// one with a strong count of zero only has its memory kept by [`Weak`]s.
+/// Allocations that are still live when their thread exits are also reported
+/// on standard error.
+///
+/// This is only available with the `leak-debug` feature.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::{rc, Rc, RefCell};
+///
+/// struct Node {
+///   next: RefCell<Option<Rc<Node>>>,
+/// }
+///
+/// let before = rc::live_allocations().len();
+///
+/// let node = Rc::new(Node { next: RefCell::new(None) });
+/// *node.next.borrow_mut() = Some(Rc::clone(&node));
+/// let weak = Rc::downgrade(&node);
+/// drop(node);
+///
+/// // The node keeps itself alive.
+/// let live = rc::live_allocations();
+/// assert_eq!(live.len(), before + 1);
+/// assert!(live[before].type_name().ends_with("Node"));
+/// assert_eq!(live[before].strong_count(), 1);
+///
+/// weak.upgrade().unwrap().next.borrow_mut().take();
+/// assert_eq!(rc::live_allocations().len(), before + 1);
+/// drop(weak);
+/// assert_eq!(rc::live_allocations().len(), before);
+/// ```
+///
+/// [`Weak`]: crate::Weak
+pub fn live_allocations() -> Vec<LiveAllocation> {
+  REGISTRY
+    .try_with(|registry| snapshot(&registry.entries.borrow()))
+    .unwrap_or_default()
+}
+
+/// A live `Rc` allocation, as returned by [`live_allocations`].
+#[derive(Clone)]
+pub struct LiveAllocation {
+  type_name: &'static str,
+  address: *const (),
+  strong_count: usize,
+  weak_count: usize,
+  backtrace: std::rc::Rc<Backtrace>,
+}
+
+impl LiveAllocation {
+  /// Returns the name of the value type, as given by
+  /// [`std::any::type_name`].
+  pub fn type_name(&self) -> &'static str {
+    self.type_name
+  }
+
+  /// Returns the address of the allocation.
+  pub fn address(&self) -> *const () {
+    self.address
+  }
+
+  /// Returns the number of strong pointers at the time of the snapshot.
+  pub fn strong_count(&self) -> usize {
+    self.strong_count
+  }
+
+  /// Returns the number of weak pointers at the time of the snapshot.
+  pub fn weak_count(&self) -> usize {
+    self.weak_count
+  }
+
+  /// Returns where the allocation was made.
+  ///
+  /// The backtrace is only captured if `RUST_BACKTRACE` or
+  /// `RUST_LIB_BACKTRACE` was set.
+  pub fn backtrace(&self) -> &Backtrace {
+    &self.backtrace
+  }
+}
+
+impl std::fmt::Display for LiveAllocation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} at {:p} (strong: {}, weak: {})",
+      self.type_name, self.address, self.strong_count, self.weak_count
+    )?;
+    if self.backtrace.status() == BacktraceStatus::Captured {
+      write!(f, ", allocated at:\n{}", self.backtrace)?;
+    }
+    Ok(())
+  }
+}
+
+impl std::fmt::Debug for LiveAllocation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("LiveAllocation")
+      .field("type_name", &self.type_name)
+      .field("address", &self.address)
+      .field("strong_count", &self.strong_count)
+      .field("weak_count", &self.weak_count)
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{rc, Rc, UniqueRc};
+
+  #[test]
+  fn tracks_every_kind_of_allocation() {
+    let before = rc::live_allocations().len();
+    let a = Rc::new(1u8);
+    let b: Rc<str> = Rc::from("b");
+    let c = UniqueRc::new(String::from("c"));
+    let d = crate::SmallRc::from(7u16);
+
+    let live = rc::live_allocations();
+    let names: Vec<_> = live[before..].iter().map(|a| a.type_name()).collect();
+    assert_eq!(names, ["u8", "str", "alloc::string::String", "u16"]);
+
+    drop((a, b, c, d));
+    assert_eq!(rc::live_allocations().len(), before);
+  }
+}
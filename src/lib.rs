@@ -25,6 +25,7 @@
 //! - For types that implement [`Copy`], the [`get`] method retrieves the current interior value.
 //! - For types that implement [`Default`], the [`take`] method replaces the current interior value with [`Default::default()`][`default`] and returns the replaced value.
 //! - For all types, the [`replace`] method replaces the current interior value and returns the replaced value and the [`into_inner`] method consumes the [`Cell<T>`][`Cell`] and returns the interior value.
+//!
 //! Additionally, the [`set`] method replaces the interior value, dropping the replaced value.
 //!
 //! [`RefCell<T>`][`RefCell`] uses Rust's lifetimes to implement 'dynamic borrowing', a process whereby one can claim temporary, exclusive, mutable access to the inner value.
@@ -181,15 +182,166 @@
 //! [`clone`]: Clone::clone
 //! [`Copy`]: std::marker::Copy
 //! [`Sync`]: std::marker::Sync
-//! [`Mutex`]: std::sync::Mutex
-//! [`RwLock`]: std::sync::RwLock
-//! [`Arc`]: std::sync::Arc
+//! [`Mutex`]: crate::sync::Mutex
+//! [`RwLock`]: crate::sync::RwLock
+//! [`Arc`]: crate::sync::Arc
 //! [atomic]: std::sync::atomic
 
+#![cfg_attr(
+  feature = "nightly-const",
+  feature(
+    const_default,
+    const_destruct,
+    const_precise_live_drops,
+    const_trait_impl
+  )
+)]
+#![cfg_attr(feature = "may-dangle", feature(dropck_eyepatch))]
+
+// Lets derived code name `::pointer` in this crate's own tests.
+#[cfg(all(test, feature = "derive"))]
+extern crate self as pointer;
+
+// Defines a function that is a `const fn` with the `nightly-const` feature,
+// and a plain one without it. The bracketed `where` clause holds the `[const]`
+// bounds the `const fn` needs, which only nightly can parse.
+#[cfg(feature = "nightly-const")]
+macro_rules! nightly_const {
+  (
+    $(#[$attr:meta])*
+    $vis:vis fn $name:ident($($args:tt)*) $(-> $ret:ty)?
+    $([where $($bounds:tt)*])?
+    $body:block
+  ) => {
+    $(#[$attr])*
+    $vis const fn $name($($args)*) $(-> $ret)? $(where $($bounds)*)? $body
+  };
+}
+#[cfg(not(feature = "nightly-const"))]
+macro_rules! nightly_const {
+  (
+    $(#[$attr:meta])*
+    $vis:vis fn $name:ident($($args:tt)*) $(-> $ret:ty)?
+    $([where $($bounds:tt)*])?
+    $body:block
+  ) => {
+    $(#[$attr])*
+    $vis fn $name($($args)*) $(-> $ret)? $body
+  };
+}
+
+// Implements `Drop` for a type generic over `T`. With the `may-dangle`
+// feature, `T` is marked `#[may_dangle]`: the impl promises the drop checker
+// that `drop` does nothing with the `T` but drop it, so the `T` may borrow
+// values that are already gone. Without the feature it is a plain impl.
+#[cfg(feature = "may-dangle")]
+macro_rules! drop_may_dangle {
+  (
+    impl<$t:ident: ?Sized $(, $param:ident: $bound:path)*> Drop for $ty:ty {
+      $($body:tt)*
+    }
+  ) => {
+    // SAFETY: The callers' `drop` only drops the `T` and frees its memory.
+    unsafe impl<#[may_dangle] $t: ?Sized $(, $param: $bound)*> Drop for $ty {
+      $($body)*
+    }
+  };
+}
+#[cfg(not(feature = "may-dangle"))]
+macro_rules! drop_may_dangle {
+  (
+    impl<$t:ident: ?Sized $(, $param:ident: $bound:path)*> Drop for $ty:ty {
+      $($body:tt)*
+    }
+  ) => {
+    impl<$t: ?Sized $(, $param: $bound)*> Drop for $ty {
+      $($body)*
+    }
+  };
+}
+
+pub mod alloc;
+pub mod arena;
+pub mod async_local;
+pub mod boxed;
+pub mod by_address;
 pub mod cell;
+pub mod cell_vec;
+pub mod collections;
+#[cfg(any(test, doctest))]
+mod compile_tests;
+pub mod cow;
+pub mod cow_vec;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod gc;
+pub mod history_cell;
+#[cfg(feature = "leak-debug")]
+mod leak_debug;
+pub mod listener;
+#[cfg(feature = "lite-rc")]
+pub mod lite_rc;
+pub mod local_singleton;
+mod loom;
+mod macros;
+pub mod memo_cell;
+pub mod observable_cell;
+#[cfg(feature = "alloc-observer")]
+pub mod observer;
+pub mod owned_projection;
 pub mod rc;
+pub mod rc_cell;
+pub mod rc_pool;
+pub mod rc_slice;
 pub mod refcell;
+pub mod registry;
+pub mod rope;
+pub mod shared_string;
+pub mod signals;
+pub mod state_cell;
+#[cfg(feature = "stats")]
+pub mod stats;
+pub mod sync;
+pub mod thin_rc;
+pub mod tx_cell;
+pub mod versioned_refcell;
+pub mod watch;
+pub mod weak_cache;
 
+pub use arena::Arena;
+pub use boxed::Boxed;
+pub use by_address::ByAddress;
 pub use cell::Cell;
-pub use rc::{Rc, Weak};
-pub use refcell::{BorrowError, BorrowMutError, Ref, RefCell, RefMut};
+pub use cell_vec::CellVec;
+pub use collections::tree::{self, TreeNode};
+pub use cow::Cow;
+pub use cow_vec::CowVec;
+pub use history_cell::HistoryCell;
+pub use listener::{Listeners, Subscription};
+#[cfg(feature = "lite-rc")]
+pub use lite_rc::LiteRc;
+pub use local_singleton::LocalSingleton;
+pub use memo_cell::MemoCell;
+pub use observable_cell::{Change, ObservableCell};
+#[cfg(feature = "alloc-observer")]
+pub use observer::set_alloc_observer;
+pub use owned_projection::{OwnedProjection, OwnedRef, RcRef};
+#[cfg(feature = "derive")]
+pub use pointer_derive::InteriorMutable;
+pub use rc::{
+  CycleBuilder, Rc, RcBorrow, SecretRc, SmallRc, SmallWeak, UniqueRc, Weak,
+};
+pub use rc_cell::{RcCell, WeakCell};
+pub use rc_pool::{PooledRc, RcPool};
+pub use rc_slice::RcSlice;
+pub use refcell::{BorrowError, BorrowMutError, Frozen, Ref, RefCell, RefMut};
+pub use registry::Registry;
+pub use rope::Rope;
+pub use shared_string::{ArcString, SharedString};
+pub use state_cell::StateCell;
+#[cfg(feature = "stats")]
+pub use stats::stats;
+pub use thin_rc::ThinRc;
+pub use tx_cell::{Transaction, TxCell};
+pub use versioned_refcell::{Snapshot, VersionedRefCell};
+pub use weak_cache::WeakCache;
@@ -0,0 +1,273 @@
+//! Observer lists whose callbacks are held through [`Weak`] pointers.
+//!
+//! A [`Listeners<Args>`][Listeners] keeps only [`Weak`] references to its callbacks.
+//! The strong reference lives in the [`Subscription`] handed back by [`subscribe`],
+//! so a listener stays registered exactly as long as its `Subscription` is alive.
+//! Dead listeners are skipped and pruned the next time the list is [`emit`]ted.
+//!
+//! ```
+//! use pointer::{Cell, Listeners, Rc};
+//!
+//! let clicks = Listeners::new();
+//! let total = Rc::new(Cell::new(0));
+//!
+//! let counter = Rc::clone(&total);
+//! let subscription = clicks.subscribe(move |n: i32| counter.set(counter.get() + n));
+//!
+//! clicks.emit(2);
+//! clicks.emit(3);
+//! assert_eq!(total.get(), 5);
+//!
+//! // Dropping the subscription unregisters the listener.
+//! drop(subscription);
+//! clicks.emit(10);
+//! assert_eq!(total.get(), 5);
+//! ```
+//!
+//! [`subscribe`]: Listeners::subscribe
+//! [`emit`]: Listeners::emit
+
+use crate::{Rc, RefCell, Weak};
+
+/// Callback stored by [`Listeners`].
+type Callback<Args> = RefCell<Box<dyn FnMut(Args)>>;
+
+/// A list of callbacks that are notified with a copy of `Args` on every [`emit`].
+///
+/// See the [module-level documentation](index.html) for more.
+///
+/// [`emit`]: Listeners::emit
+pub struct Listeners<Args> {
+  /// Registered callbacks. Only the [`Subscription`] keeps them alive.
+  slots: RefCell<Vec<Weak<Callback<Args>>>>,
+}
+
+/// An RAII handle returned by [`Listeners::subscribe`].
+///
+/// The listener is unregistered when this handle is dropped.
+#[must_use = "the listener is unregistered as soon as the subscription is dropped"]
+pub struct Subscription<Args> {
+  callback: Rc<Callback<Args>>,
+}
+
+impl<Args> Listeners<Args> {
+  /// Creates an empty list of listeners.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Listeners;
+  ///
+  /// let listeners: Listeners<u8> = Listeners::new();
+  /// assert!(listeners.is_empty());
+  /// ```
+  pub fn new() -> Listeners<Args> {
+    Listeners {
+      slots: RefCell::new(Vec::new()),
+    }
+  }
+
+  /// Registers `f` and returns the [`Subscription`] that keeps it alive.
+  ///
+  /// Listeners subscribed while an [`emit`](#method.emit) is in progress
+  /// are first notified by the next `emit`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Listeners;
+  ///
+  /// let listeners = Listeners::new();
+  /// let _subscription = listeners.subscribe(|s: &str| println!("{}", s));
+  ///
+  /// assert_eq!(listeners.len(), 1);
+  /// ```
+  pub fn subscribe(&self, f: impl FnMut(Args) + 'static) -> Subscription<Args> {
+    let callback: Rc<Callback<Args>> = Rc::new(RefCell::new(Box::new(f)));
+    self.slots.borrow_mut().push(Rc::downgrade(&callback));
+    Subscription { callback }
+  }
+
+  /// Returns the number of listeners whose [`Subscription`] is still alive.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Listeners;
+  ///
+  /// let listeners = Listeners::new();
+  /// let first = listeners.subscribe(|_: ()| {});
+  /// let _second = listeners.subscribe(|_: ()| {});
+  ///
+  /// drop(first);
+  /// assert_eq!(listeners.len(), 1);
+  /// ```
+  pub fn len(&self) -> usize {
+    self
+      .slots
+      .borrow()
+      .iter()
+      .filter(|slot| slot.strong_count() > 0)
+      .count()
+  }
+
+  /// Returns `true` if no listener is alive.
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Removes the slots of listeners whose [`Subscription`] was dropped.
+  fn prune(&self) {
+    self
+      .slots
+      .borrow_mut()
+      .retain(|slot| slot.strong_count() > 0);
+  }
+}
+
+impl<Args: Clone> Listeners<Args> {
+  /// Calls every live listener with a clone of `args`, pruning dead ones.
+  ///
+  /// A listener that emits on the list it is subscribed to does not get
+  /// called again for that nested `emit`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Listeners, Rc, RefCell};
+  ///
+  /// let listeners = Listeners::new();
+  /// let seen = Rc::new(RefCell::new(Vec::new()));
+  ///
+  /// let log = Rc::clone(&seen);
+  /// let _subscription = listeners.subscribe(move |n| log.borrow_mut().push(n));
+  ///
+  /// listeners.emit(1);
+  /// listeners.emit(2);
+  ///
+  /// assert_eq!(*seen.borrow(), vec![1, 2]);
+  /// ```
+  pub fn emit(&self, args: Args) {
+    self.prune();
+
+    // Upgrade first so listeners may (un)subscribe while being notified.
+    let live: Vec<Rc<Callback<Args>>> = self
+      .slots
+      .borrow()
+      .iter()
+      .filter_map(Weak::upgrade)
+      .collect();
+
+    for callback in live {
+      // A callback that is already running is being re-entered; skip it.
+      if let Ok(mut f) = callback.try_borrow_mut() {
+        f(args.clone());
+      }
+    }
+  }
+}
+
+impl<Args> Default for Listeners<Args> {
+  fn default() -> Listeners<Args> {
+    Listeners::new()
+  }
+}
+
+impl<Args> std::fmt::Debug for Listeners<Args> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Listeners")
+      .field("len", &self.len())
+      .finish()
+  }
+}
+
+impl<Args> Subscription<Args> {
+  /// Returns `true` if both subscriptions refer to the same listener.
+  pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+    Rc::ptr_eq(&this.callback, &other.callback)
+  }
+}
+
+impl<Args> std::fmt::Debug for Subscription<Args> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Subscription").finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Cell;
+
+  #[test]
+  fn emit() {
+    let listeners = Listeners::new();
+    let total = Rc::new(Cell::new(0));
+
+    let counter = Rc::clone(&total);
+    let _subscription = listeners.subscribe(move |n: i32| {
+      counter.set(counter.get() + n);
+    });
+
+    listeners.emit(2);
+    listeners.emit(3);
+
+    assert_eq!(total.get(), 5);
+  }
+
+  #[test]
+  fn drop_subscription_prunes() {
+    let listeners = Listeners::new();
+    let calls = Rc::new(Cell::new(0));
+
+    let counter = Rc::clone(&calls);
+    let first =
+      listeners.subscribe(move |_: ()| counter.set(counter.get() + 1));
+    let counter = Rc::clone(&calls);
+    let _second =
+      listeners.subscribe(move |_: ()| counter.set(counter.get() + 1));
+
+    listeners.emit(());
+    assert_eq!(calls.get(), 2);
+
+    drop(first);
+    assert_eq!(listeners.len(), 1);
+
+    listeners.emit(());
+    assert_eq!(calls.get(), 3);
+    assert_eq!(listeners.slots.borrow().len(), 1);
+  }
+
+  #[test]
+  fn subscribe_during_emit() {
+    let listeners: Rc<Listeners<()>> = Rc::new(Listeners::new());
+    let added = Rc::new(RefCell::new(Vec::new()));
+
+    let list = Rc::clone(&listeners);
+    let store = Rc::clone(&added);
+    let _subscription = listeners.subscribe(move |_| {
+      store.borrow_mut().push(list.subscribe(|_| {}));
+    });
+
+    listeners.emit(());
+    assert_eq!(listeners.len(), 2);
+  }
+
+  #[test]
+  fn reentrant_emit_skips_running_listener() {
+    let listeners: Rc<Listeners<u32>> = Rc::new(Listeners::new());
+    let calls = Rc::new(Cell::new(0));
+
+    let list = Rc::clone(&listeners);
+    let counter = Rc::clone(&calls);
+    let _subscription = listeners.subscribe(move |depth| {
+      counter.set(counter.get() + 1);
+      if depth == 0 {
+        list.emit(depth + 1);
+      }
+    });
+
+    listeners.emit(0);
+    assert_eq!(calls.get(), 1);
+  }
+}
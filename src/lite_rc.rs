@@ -0,0 +1,358 @@
+//! A leaner single-threaded reference-counting pointer, without weak references.
+//!
+//! [`LiteRc<T>`][LiteRc] is an [`Rc<T>`][Rc] stripped down to a single counter. There is no
+//! [`Weak`], so the allocation holds one count instead of two and dropping the last pointer frees
+//! it right away, without checking for weak pointers first. That makes the box a word smaller and
+//! `clone`/`drop` a little cheaper, for workloads that share many values and never need to break
+//! cycles.
+//!
+//! This module is only available with the `lite-rc` feature. The `rc` benchmark compares it with
+//! the full `Rc` (`cargo bench --features lite-rc`).
+//!
+//! Converting between the two pointers moves the value, so it only works while the value is
+//! uniquely owned:
+//!
+//! ```
+//! use pointer::{LiteRc, Rc};
+//! use std::convert::TryFrom;
+//!
+//! let lite = LiteRc::new(5);
+//! let rc: Rc<i32> = Rc::try_from(lite).unwrap();
+//!
+//! let shared = Rc::clone(&rc);
+//! // `shared` still points to the value, so it can't be moved out.
+//! let rc = LiteRc::<i32>::try_from(rc).unwrap_err();
+//! drop(shared);
+//! let lite: LiteRc<i32> = LiteRc::try_from(rc).unwrap();
+//! assert_eq!(*lite, 5);
+//! ```
+//!
+//! [`Weak`]: crate::Weak
+
+use crate::cell::Cell;
+use crate::Rc;
+
+struct LiteRcBox<T: ?Sized> {
+  count: Cell<usize>,
+  value: T,
+}
+
+/// A single-threaded reference-counting pointer without weak references.
+///
+/// See the [module-level documentation](./index.html) for more details.
+///
+/// Like with [`Rc`], the inherent methods are associated functions: call
+/// them as e.g. [`LiteRc::get_mut(&mut value)`][get_mut].
+///
+/// [get_mut]: LiteRc::get_mut
+pub struct LiteRc<T: ?Sized> {
+  ptr: std::ptr::NonNull<LiteRcBox<T>>,
+  phantom: std::marker::PhantomData<LiteRcBox<T>>,
+}
+
+impl<T> LiteRc<T> {
+  /// Constructs a new `LiteRc<T>`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::LiteRc;
+  ///
+  /// let five = LiteRc::new(5);
+  /// ```
+  pub fn new(value: T) -> LiteRc<T> {
+    let boxed = Box::new(LiteRcBox {
+      count: Cell::new(1),
+      value,
+    });
+    LiteRc {
+      // SAFETY: `Box::into_raw` never returns a null pointer.
+      ptr: unsafe { std::ptr::NonNull::new_unchecked(Box::into_raw(boxed)) },
+      phantom: std::marker::PhantomData,
+    }
+  }
+
+  /// Returns the inner value, if the `LiteRc` has exactly one strong
+  /// reference.
+  ///
+  /// Otherwise, an [`Err`] is returned with the same `LiteRc` that was
+  /// passed in.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::LiteRc;
+  ///
+  /// let x = LiteRc::new(3);
+  /// assert_eq!(LiteRc::try_unwrap(x).ok(), Some(3));
+  ///
+  /// let x = LiteRc::new(4);
+  /// let _y = LiteRc::clone(&x);
+  /// assert_eq!(*LiteRc::try_unwrap(x).unwrap_err(), 4);
+  /// ```
+  pub fn try_unwrap(this: Self) -> Result<T, Self> {
+    if LiteRc::strong_count(&this) != 1 {
+      return Err(this);
+    }
+    let ptr = this.ptr;
+    std::mem::forget(this);
+    // SAFETY: This was the only pointer, and the `Box` it came from is
+    // rebuilt to move the value out and free the memory.
+    let boxed = unsafe { Box::from_raw(ptr.as_ptr()) };
+    Ok(boxed.value)
+  }
+
+  /// Returns the inner value, if this is the only pointer to it.
+  ///
+  /// Otherwise the pointer is dropped and [`None`] is returned.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::LiteRc;
+  ///
+  /// let x = LiteRc::new(3);
+  /// let y = LiteRc::clone(&x);
+  ///
+  /// assert_eq!(LiteRc::into_inner(x), None);
+  /// assert_eq!(LiteRc::into_inner(y), Some(3));
+  /// ```
+  pub fn into_inner(this: Self) -> Option<T> {
+    LiteRc::try_unwrap(this).ok()
+  }
+}
+
+impl<T: Clone> LiteRc<T> {
+  /// Makes a mutable reference into the given `LiteRc`, cloning the inner
+  /// value first if other pointers to it exist.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::LiteRc;
+  ///
+  /// let mut data = LiteRc::new(5);
+  /// let other_data = LiteRc::clone(&data);
+  ///
+  /// *LiteRc::make_mut(&mut data) += 1;
+  /// assert_eq!(*data, 6);
+  /// assert_eq!(*other_data, 5);
+  /// ```
+  pub fn make_mut(this: &mut Self) -> &mut T {
+    if LiteRc::strong_count(this) != 1 {
+      *this = LiteRc::new((**this).clone());
+    }
+    // SAFETY: `this` is now the only pointer to the allocation, and it is
+    // borrowed mutably.
+    unsafe { &mut (*this.ptr.as_ptr()).value }
+  }
+}
+
+impl<T: ?Sized> LiteRc<T> {
+  /// Gets the number of pointers to this allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::LiteRc;
+  ///
+  /// let five = LiteRc::new(5);
+  /// let _also_five = LiteRc::clone(&five);
+  ///
+  /// assert_eq!(2, LiteRc::strong_count(&five));
+  /// ```
+  #[inline]
+  pub fn strong_count(this: &Self) -> usize {
+    this.inner().count.get()
+  }
+
+  /// Returns a mutable reference into the given `LiteRc`, if there are no
+  /// other pointers to the same allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::LiteRc;
+  ///
+  /// let mut x = LiteRc::new(3);
+  /// *LiteRc::get_mut(&mut x).unwrap() = 4;
+  /// assert_eq!(*x, 4);
+  ///
+  /// let _y = LiteRc::clone(&x);
+  /// assert!(LiteRc::get_mut(&mut x).is_none());
+  /// ```
+  pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+    if LiteRc::strong_count(this) == 1 {
+      // SAFETY: No other pointer can reach the value.
+      Some(unsafe { &mut (*this.ptr.as_ptr()).value })
+    } else {
+      None
+    }
+  }
+
+  /// Provides a raw pointer to the data.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::LiteRc;
+  ///
+  /// let x = LiteRc::new("hello".to_owned());
+  /// let x_ptr = LiteRc::as_ptr(&x);
+  /// assert_eq!(unsafe { &*x_ptr }, "hello");
+  /// ```
+  pub fn as_ptr(this: &Self) -> *const T {
+    &this.inner().value
+  }
+
+  /// Returns `true` if the two `LiteRc`s point to the same allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::LiteRc;
+  ///
+  /// let five = LiteRc::new(5);
+  /// let same_five = LiteRc::clone(&five);
+  /// let other_five = LiteRc::new(5);
+  ///
+  /// assert!(LiteRc::ptr_eq(&five, &same_five));
+  /// assert!(!LiteRc::ptr_eq(&five, &other_five));
+  /// ```
+  #[inline]
+  pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+    this.ptr.as_ptr().cast::<()>() == other.ptr.as_ptr().cast::<()>()
+  }
+
+  #[inline]
+  fn inner(&self) -> &LiteRcBox<T> {
+    // SAFETY: While this `LiteRc` is alive the allocation is valid.
+    unsafe { self.ptr.as_ref() }
+  }
+}
+
+impl<T: ?Sized> Clone for LiteRc<T> {
+  /// Makes a clone of the `LiteRc` pointer, increasing the count.
+  #[inline]
+  fn clone(&self) -> LiteRc<T> {
+    let count = &self.inner().count;
+    // Abort on overflow instead of freeing a value that is still in use.
+    if count.get() == usize::MAX {
+      crate::alloc::abort_on_overflow();
+    }
+    count.set(count.get() + 1);
+    LiteRc {
+      ptr: self.ptr,
+      phantom: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for LiteRc<T> {
+  type Target = T;
+
+  #[inline]
+  fn deref(&self) -> &T {
+    &self.inner().value
+  }
+}
+
+impl<T: ?Sized> Drop for LiteRc<T> {
+  /// Drops the `LiteRc`, freeing the value with the last pointer.
+  #[inline]
+  fn drop(&mut self) {
+    let count = &self.inner().count;
+    count.set(count.get() - 1);
+    if count.get() == 0 {
+      // SAFETY: This was the last pointer, and the allocation came from a
+      // `Box`.
+      unsafe { drop(Box::from_raw(self.ptr.as_ptr())) };
+    }
+  }
+}
+
+impl<T: Default> Default for LiteRc<T> {
+  fn default() -> LiteRc<T> {
+    LiteRc::new(Default::default())
+  }
+}
+
+impl<T> From<T> for LiteRc<T> {
+  fn from(t: T) -> LiteRc<T> {
+    LiteRc::new(t)
+  }
+}
+
+impl<T> std::convert::TryFrom<Rc<T>> for LiteRc<T> {
+  type Error = Rc<T>;
+
+  /// Moves the value of a uniquely owned [`Rc`] into a `LiteRc`.
+  ///
+  /// Fails, handing the `Rc` back, if it has other strong pointers.
+  fn try_from(rc: Rc<T>) -> Result<LiteRc<T>, Rc<T>> {
+    Rc::try_unwrap(rc).map(LiteRc::new)
+  }
+}
+
+impl<T> std::convert::TryFrom<LiteRc<T>> for Rc<T> {
+  type Error = LiteRc<T>;
+
+  /// Moves the value of a uniquely owned `LiteRc` into an [`Rc`].
+  ///
+  /// Fails, handing the `LiteRc` back, if it has been cloned.
+  fn try_from(lite: LiteRc<T>) -> Result<Rc<T>, LiteRc<T>> {
+    LiteRc::try_unwrap(lite).map(Rc::new)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display for LiteRc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for LiteRc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized> std::fmt::Pointer for LiteRc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Pointer::fmt(&(&**self as *const T), f)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn smaller_than_rc_box() {
+    // One count instead of two.
+    assert_eq!(
+      std::mem::size_of::<LiteRcBox<u64>>() + std::mem::size_of::<usize>(),
+      std::mem::size_of::<(Cell<usize>, Cell<usize>, u64)>()
+    );
+  }
+
+  #[test]
+  fn clone_and_drop() {
+    let dropped = Cell::new(false);
+    struct Flag<'a>(&'a Cell<bool>);
+    impl Drop for Flag<'_> {
+      fn drop(&mut self) {
+        self.0.set(true);
+      }
+    }
+
+    let first = LiteRc::new(Flag(&dropped));
+    let second = LiteRc::clone(&first);
+    assert_eq!(LiteRc::strong_count(&second), 2);
+
+    drop(first);
+    assert!(!dropped.get());
+    drop(second);
+    assert!(dropped.get());
+  }
+}
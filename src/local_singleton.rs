@@ -0,0 +1,293 @@
+//! Lazily made, per-thread values behind a `static`, for the `thread_local!` + `RefCell` pattern.
+//!
+//! Loggers, random number generators and interners are often kept one per thread, so that they
+//! can be reached from anywhere without being passed around or locked. [`local_singleton!`]
+//! declares such a value as a `static` [`LocalSingleton<T>`][LocalSingleton]: each thread gets its
+//! own `T`, made by the initializer the first time the thread uses it.
+//!
+//! ```
+//! use pointer::local_singleton;
+//!
+//! #[derive(Default)]
+//! struct Interner {
+//!   names: Vec<String>,
+//! }
+//!
+//! impl Interner {
+//!   fn intern(&mut self, name: &str) -> usize {
+//!     match self.names.iter().position(|n| n == name) {
+//!       Some(id) => id,
+//!       None => {
+//!         self.names.push(name.to_owned());
+//!         self.names.len() - 1
+//!       }
+//!     }
+//!   }
+//! }
+//!
+//! local_singleton! {
+//!   static INTERNER: Interner = Interner::default();
+//! }
+//!
+//! assert_eq!(INTERNER.with_mut(|i| i.intern("a")), 0);
+//! assert_eq!(INTERNER.with_mut(|i| i.intern("b")), 1);
+//! assert_eq!(INTERNER.with_mut(|i| i.intern("a")), 0);
+//!
+//! // Another thread has an interner of its own.
+//! std::thread::spawn(|| assert_eq!(INTERNER.with(|i| i.names.len()), 0))
+//!   .join()
+//!   .unwrap();
+//! assert_eq!(INTERNER.with(|i| i.names.len()), 2);
+//! ```
+//!
+//! [`try_init`] replaces the initializer for one thread, such as to open a log file that may fail
+//! to open, and [`reset_for_test`] drops a thread's value so that each test starts afresh.
+//!
+//! [`try_init`]: LocalSingleton::try_init
+//! [`reset_for_test`]: LocalSingleton::reset_for_test
+
+use crate::refcell::RefCell;
+
+/// A value of type `T` for each thread, made on first use.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct LocalSingleton<T: 'static> {
+  slot: &'static std::thread::LocalKey<RefCell<Option<T>>>,
+  init: fn() -> T,
+}
+
+/// Declares `static` [`LocalSingleton`]s, each with its initializer.
+///
+/// The initializer is an expression, evaluated on each thread the first time
+/// the thread uses the singleton.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::local_singleton;
+///
+/// local_singleton! {
+///   /// The lines logged on this thread.
+///   static LOG: Vec<String> = Vec::new();
+///   pub(crate) static SEED: u64 = 42;
+/// }
+///
+/// LOG.with_mut(|log| log.push(format!("seed {}", SEED.with(|s| *s))));
+/// assert_eq!(LOG.with(|log| log.clone()), ["seed 42"]);
+/// ```
+#[macro_export]
+macro_rules! local_singleton {
+  ($($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $init:expr;)+) => {
+    $(
+      $(#[$attr])*
+      $vis static $name: $crate::LocalSingleton<$ty> = {
+        ::std::thread_local! {
+          static SLOT: $crate::RefCell<::std::option::Option<$ty>> =
+            const { $crate::RefCell::new(::std::option::Option::None) };
+        }
+        fn init() -> $ty {
+          $init
+        }
+        $crate::LocalSingleton::new(&SLOT, init)
+      };
+    )+
+  };
+}
+
+impl<T: 'static> LocalSingleton<T> {
+  /// Creates a singleton that keeps each thread's value in `slot` and makes
+  /// it with `init`.
+  ///
+  /// [`local_singleton!`] declares the slot and calls this; the slot should
+  /// not be used for anything else.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{LocalSingleton, RefCell};
+  ///
+  /// std::thread_local! {
+  ///   static SLOT: RefCell<Option<u32>> = const { RefCell::new(None) };
+  /// }
+  /// static COUNTER: LocalSingleton<u32> = LocalSingleton::new(&SLOT, || 0);
+  ///
+  /// COUNTER.with_mut(|n| *n += 1);
+  /// assert_eq!(COUNTER.with(|n| *n), 1);
+  /// ```
+  pub const fn new(
+    slot: &'static std::thread::LocalKey<RefCell<Option<T>>>,
+    init: fn() -> T,
+  ) -> LocalSingleton<T> {
+    LocalSingleton { slot, init }
+  }
+
+  /// Calls `f` with a shared reference to this thread's value, making the
+  /// value first if the thread hasn't used it yet.
+  ///
+  /// Calls of `with` may nest, including for the same singleton.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is being used by [`with_mut`], if the initializer
+  /// uses the singleton, or if the thread is being torn down.
+  ///
+  /// [`with_mut`]: LocalSingleton::with_mut
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::local_singleton;
+  ///
+  /// local_singleton! {
+  ///   static GREETING: String = String::from("hello");
+  /// }
+  ///
+  /// assert_eq!(GREETING.with(|g| g.len()), 5);
+  /// ```
+  pub fn with<R>(&'static self, f: impl FnOnce(&T) -> R) -> R {
+    self.slot.with(|slot| {
+      self.make(slot);
+      f(slot.borrow().as_ref().expect("the value was just made"))
+    })
+  }
+
+  /// Calls `f` with a mutable reference to this thread's value, making the
+  /// value first if the thread hasn't used it yet.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is already in use by [`with`] or `with_mut`, if the
+  /// initializer uses the singleton, or if the thread is being torn down.
+  ///
+  /// [`with`]: LocalSingleton::with
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::local_singleton;
+  ///
+  /// local_singleton! {
+  ///   static NEXT_ID: u64 = 1;
+  /// }
+  ///
+  /// let id = || NEXT_ID.with_mut(|n| std::mem::replace(n, *n + 1));
+  /// assert_eq!((id(), id()), (1, 2));
+  /// ```
+  pub fn with_mut<R>(&'static self, f: impl FnOnce(&mut T) -> R) -> R {
+    self.slot.with(|slot| {
+      self.make(slot);
+      f(slot.borrow_mut().as_mut().expect("the value was just made"))
+    })
+  }
+
+  /// Makes this thread's value with `f` instead of the initializer, unless
+  /// the thread already has a value.
+  ///
+  /// Returns the error of `f` if it fails, leaving the thread without a
+  /// value, so a later use tries again. `f` isn't called if there is a value
+  /// already.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `f` uses the singleton, or if the thread is being torn down.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::local_singleton;
+  ///
+  /// local_singleton! {
+  ///   static PORT: u16 = 80;
+  /// }
+  ///
+  /// assert!(PORT.try_init(|| "not a port".parse::<u16>()).is_err());
+  /// assert_eq!(PORT.try_init(|| "8080".parse::<u16>()), Ok(()));
+  /// assert_eq!(PORT.with(|p| *p), 8080);
+  /// ```
+  pub fn try_init<E>(
+    &'static self,
+    f: impl FnOnce() -> Result<T, E>,
+  ) -> Result<(), E> {
+    self.slot.with(|slot| {
+      if slot.borrow().is_none() {
+        let mut empty = slot.borrow_mut();
+        *empty = Some(f()?);
+      }
+      Ok(())
+    })
+  }
+
+  /// Drops this thread's value, so that the next use makes it again.
+  ///
+  /// This is meant for tests, which the test harness may run one after
+  /// another on the same thread. Nothing happens if the thread has no value.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is in use, or if the thread is being torn down.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::local_singleton;
+  ///
+  /// local_singleton! {
+  ///   static CALLS: u32 = 0;
+  /// }
+  ///
+  /// CALLS.with_mut(|n| *n += 1);
+  /// CALLS.reset_for_test();
+  /// assert_eq!(CALLS.with(|n| *n), 0);
+  /// ```
+  pub fn reset_for_test(&'static self) {
+    let old = self.slot.with(|slot| slot.borrow_mut().take());
+    // The value may use the singleton when it is dropped.
+    drop(old);
+  }
+
+  /// Makes the value in `slot` if there is none.
+  fn make(&self, slot: &RefCell<Option<T>>) {
+    if slot.borrow().is_none() {
+      // The slot stays borrowed while the value is made, so a use of the
+      // singleton by the initializer panics instead of recursing.
+      let mut empty = slot.borrow_mut();
+      *empty = Some((self.init)());
+    }
+  }
+}
+
+impl<T: 'static> std::fmt::Debug for LocalSingleton<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("LocalSingleton").finish_non_exhaustive()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::Cell;
+
+  #[test]
+  fn nested_uses_share_the_value() {
+    crate::local_singleton! {
+      static DEPTH: Cell<u32> = Cell::new(0);
+    }
+
+    DEPTH.with(|outer| {
+      outer.set(1);
+      DEPTH.with(|inner| inner.set(inner.get() + 1));
+    });
+    assert_eq!(DEPTH.with(Cell::get), 2);
+    DEPTH.reset_for_test();
+    assert_eq!(DEPTH.with(Cell::get), 0);
+  }
+
+  #[test]
+  #[should_panic(expected = "already mutably borrowed")]
+  fn rejects_reentrant_init() {
+    crate::local_singleton! {
+      static SELF_REF: u32 = SELF_REF.with(|n| *n + 1);
+    }
+
+    SELF_REF.with(|_| ());
+  }
+}
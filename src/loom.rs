@@ -0,0 +1,78 @@
+#![allow(dead_code, unused_imports)]
+
+//! Concurrency primitives used by the thread-safe types.
+//!
+//! Everything in [`sync`](crate::sync) goes through this module rather than `std` directly,
+//! so that building with `RUSTFLAGS="--cfg loom"` swaps the atomics and `UnsafeCell` for
+//! [loom]'s model-checked versions:
+//!
+//! ```sh
+//! RUSTFLAGS="--cfg loom" cargo test --lib --release loom
+//! ```
+//!
+//! [loom]: https://docs.rs/loom
+
+#[cfg(loom)]
+pub(crate) use ::loom::{hint, sync::atomic};
+
+#[cfg(not(loom))]
+pub(crate) use std::{hint, sync::atomic};
+
+#[cfg(loom)]
+pub(crate) use ::loom::thread_local;
+
+#[cfg(not(loom))]
+pub(crate) use std::thread_local;
+
+pub(crate) mod thread {
+  #[cfg(loom)]
+  pub(crate) use ::loom::thread::{current, park, yield_now, Thread};
+
+  #[cfg(not(loom))]
+  pub(crate) use std::thread::{
+    current, park, park_timeout, yield_now, Thread,
+  };
+
+  /// Loom cannot model the passage of time, so a timed park is modelled as a
+  /// spurious wakeup; callers re-check their deadline and condition anyway.
+  #[cfg(loom)]
+  pub(crate) fn park_timeout(_dur: std::time::Duration) {
+    yield_now();
+  }
+}
+
+pub(crate) mod cell {
+  #[cfg(loom)]
+  pub(crate) use ::loom::cell::UnsafeCell;
+
+  /// `std::cell::UnsafeCell` behind loom's closure-based API.
+  #[cfg(not(loom))]
+  #[derive(Debug)]
+  pub(crate) struct UnsafeCell<T: ?Sized>(std::cell::UnsafeCell<T>);
+
+  #[cfg(not(loom))]
+  impl<T> UnsafeCell<T> {
+    #[inline]
+    pub(crate) const fn new(data: T) -> UnsafeCell<T> {
+      UnsafeCell(std::cell::UnsafeCell::new(data))
+    }
+
+    #[inline]
+    pub(crate) fn into_inner(self) -> T {
+      self.0.into_inner()
+    }
+  }
+
+  #[cfg(not(loom))]
+  impl<T: ?Sized> UnsafeCell<T> {
+    #[inline]
+    pub(crate) fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+      f(self.0.get())
+    }
+
+    #[inline]
+    pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+      f(self.0.get())
+    }
+  }
+}
@@ -0,0 +1,250 @@
+//! Shorthands for the nestings of pointers and cells that `Rc`-heavy code keeps spelling out.
+//!
+//! [`rc_refcell!`] builds the usual `Rc<RefCell<T>>`. [`clone_all!`] and [`weak!`] rebind handles
+//! under the same names before they are moved into a closure, which is otherwise a `let` per
+//! captured handle:
+//!
+//! ```
+//! use pointer::{clone_all, rc_refcell, weak};
+//!
+//! let log = rc_refcell!(Vec::new());
+//! let count = rc_refcell!(0);
+//!
+//! let record = {
+//!   clone_all!(log, count);
+//!   move |line: &str| {
+//!     log.borrow_mut().push(line.to_owned());
+//!     *count.borrow_mut() += 1;
+//!   }
+//! };
+//! record("started");
+//!
+//! let peek = {
+//!   weak!(log);
+//!   move || log.upgrade().map(|log| log.borrow().len())
+//! };
+//! assert_eq!(peek(), Some(1));
+//! assert_eq!(*count.borrow(), 1);
+//! ```
+//!
+//! [`cell_project!`] goes the other way, from a [`Cell`] of a struct to a `Cell` of one of its
+//! fields, so that a field can be read and written on its own without a [`RefCell`] around it.
+//!
+//! [`Cell`]: crate::Cell
+//! [`RefCell`]: crate::RefCell
+
+/// Creates an [`Rc`](crate::Rc)`<`[`RefCell`](crate::RefCell)`<T>>` holding
+/// the value.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::{rc_refcell, Rc, RefCell};
+///
+/// let shared: Rc<RefCell<Vec<i32>>> = rc_refcell!(vec![1, 2]);
+/// shared.borrow_mut().push(3);
+/// assert_eq!(*shared.borrow(), [1, 2, 3]);
+/// ```
+#[macro_export]
+macro_rules! rc_refcell {
+  ($value:expr $(,)?) => {
+    $crate::Rc::new($crate::RefCell::new($value))
+  };
+}
+
+/// Rebinds each named handle to a clone of itself, so that a `move` closure
+/// after it takes the clones and leaves the originals usable.
+///
+/// It works with anything that is [`Clone`], such as [`Rc`](crate::Rc) and
+/// [`Arc`](crate::sync::Arc).
+///
+/// # Examples
+///
+/// ```
+/// use pointer::{clone_all, Cell, Rc};
+///
+/// let hits = Rc::new(Cell::new(0));
+/// let hit = {
+///   clone_all!(hits);
+///   move || hits.set(hits.get() + 1)
+/// };
+///
+/// hit();
+/// hit();
+/// assert_eq!(hits.get(), 2);
+/// ```
+#[macro_export]
+macro_rules! clone_all {
+  ($($name:ident),+ $(,)?) => {
+    $(
+      let $name = ::std::clone::Clone::clone(&$name);
+    )+
+  };
+}
+
+/// Rebinds each named [`Rc`](crate::Rc) to a [`Weak`](crate::Weak) pointer
+/// to its value, so that a `move` closure after it doesn't keep the value
+/// alive.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::{weak, Rc};
+///
+/// let name = Rc::new(String::from("five"));
+/// let len = {
+///   weak!(name);
+///   move || name.upgrade().map(|name| name.len())
+/// };
+///
+/// assert_eq!(len(), Some(4));
+/// drop(name);
+/// assert_eq!(len(), None);
+/// ```
+#[macro_export]
+macro_rules! weak {
+  ($($name:ident),+ $(,)?) => {
+    $(
+      let $name = $crate::Rc::downgrade(&$name);
+    )+
+  };
+}
+
+/// Projects a `&`[`Cell`](crate::Cell)`<Struct>` to a `&Cell<Field>` for one
+/// of the fields of the struct.
+///
+/// The first argument is the type of the struct, and the second is a
+/// variable holding the `&Cell<Struct>`, followed by `.` and the field name.
+/// Through the projected cell, the field can be got, set and replaced while
+/// the rest of the struct stays put. Together with [`Cell::from_mut`], this
+/// gives field-by-field mutation through shared references without the
+/// bookkeeping of a [`RefCell`](crate::RefCell).
+///
+/// This is sound because a `Cell<T>` has the layout of `T` (see the
+/// [memory layout](crate::Cell#memory-layout) of `Cell`), so the field of
+/// the struct inside the cell is a `Cell` of the field's type. The macro
+/// refuses fields that are reached through [`Deref`](std::ops::Deref) and
+/// fields of `#[repr(packed)]` structs, which may be misaligned.
+///
+/// [`Cell::from_mut`]: crate::Cell::from_mut
+///
+/// # Examples
+///
+/// ```
+/// use pointer::{cell_project, Cell};
+///
+/// struct Point {
+///   x: i32,
+///   y: i32,
+/// }
+///
+/// let mut point = Point { x: 1, y: 2 };
+/// let cell = Cell::from_mut(&mut point);
+/// let x = cell_project!(Point, cell.x);
+/// let y = cell_project!(Point, cell.y);
+///
+/// x.set(y.get() * 10);
+/// y.set(y.get() + 1);
+/// assert_eq!((point.x, point.y), (20, 3));
+/// ```
+///
+/// A field of a packed struct doesn't compile:
+///
+/// ```compile_fail,E0793
+/// use pointer::{cell_project, Cell};
+///
+/// #[repr(packed)]
+/// struct Packed {
+///   tag: u8,
+///   value: u32,
+/// }
+///
+/// let cell = &Cell::new(Packed { tag: 0, value: 1 });
+/// let value = cell_project!(Packed, cell.value);
+/// ```
+#[macro_export]
+macro_rules! cell_project {
+  ($type:path, $cell:ident . $field:ident) => {{
+    let cell: &$crate::Cell<$type> = $cell;
+    // Never called. The pattern only matches a field of the struct itself,
+    // not one behind `Deref`, and binding a reference to the field fails to
+    // compile if the struct is packed.
+    #[allow(unused_variables)]
+    let _ = |value: &$type| {
+      let $type { $field: field, .. } = value;
+    };
+    // Ties the type and lifetime of the projection to the field and `cell`.
+    unsafe fn project<T, F>(
+      _cell: &$crate::Cell<T>,
+      field: *mut F,
+    ) -> &$crate::Cell<F> {
+      // SAFETY: `Cell<T>` has the layout of `T`, so the field is a valid and
+      // aligned `Cell<F>` for as long as `cell` is borrowed.
+      #[allow(unused_unsafe)]
+      unsafe {
+        &*field.cast_const().cast::<$crate::Cell<F>>()
+      }
+    }
+    // SAFETY: the place is a field of the struct in the cell, and only its
+    // address is taken.
+    #[allow(unused_unsafe)]
+    unsafe {
+      project(cell, ::std::ptr::addr_of_mut!((*cell.as_ptr()).$field))
+    }
+  }};
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::Rc;
+
+  #[test]
+  fn clones_leave_the_originals_usable() {
+    let a = rc_refcell!(1);
+    let b = crate::sync::Arc::new(2);
+    let sum = {
+      clone_all!(a, b);
+      move || *a.borrow() + *b
+    };
+
+    *a.borrow_mut() = 10;
+    assert_eq!(sum(), 12);
+    assert_eq!(Rc::strong_count(&a), 2);
+    drop(sum);
+    assert_eq!(Rc::strong_count(&a), 1);
+  }
+
+  #[test]
+  fn weak_handles_dont_keep_values_alive() {
+    let a = Rc::new(1);
+    let b = Rc::new(2);
+    let alive = {
+      weak!(a, b);
+      move || (a.upgrade().is_some(), b.upgrade().is_some())
+    };
+
+    drop(b);
+    assert_eq!(alive(), (true, false));
+    assert_eq!(Rc::weak_count(&a), 1);
+  }
+
+  #[test]
+  fn projected_cells_alias_their_fields() {
+    struct Pair<T> {
+      left: T,
+      right: (T, T),
+    }
+
+    let shared = crate::Cell::new(Pair {
+      left: 1_u8,
+      right: (2, 3),
+    });
+    let whole = &shared;
+    let left = cell_project!(Pair<u8>, whole.left);
+    let right = cell_project!(Pair<u8>, whole.right);
+
+    left.set(right.replace((4, 5)).0);
+    let Pair { left, right } = shared.into_inner();
+    assert_eq!((left, right), (2, (4, 5)));
+  }
+}
@@ -0,0 +1,305 @@
+//! A memoization cache that can be filled through a shared reference: [`MemoCell<K, V>`][MemoCell].
+//!
+//! The `span_tree_cache` example in the [crate documentation](crate) caches one value in a
+//! `RefCell<Option<_>>`; a `MemoCell` caches one value per key in a `RefCell<HashMap<_, _>>`.
+//! [`get_or_insert_with`] returns the value cached for a key, or computes and caches it. The map
+//! isn't borrowed while the value is computed, so the computation may use the cache for other
+//! keys, as a recursive function memoizing its subproblems does. A computation that asks for its
+//! own key would otherwise recurse until the stack overflows; the cell detects it and panics
+//! instead.
+//!
+//! A cell made [`with_limit`] holds at most that many values, and forgets the oldest first.
+//! [`invalidate`] and [`clear`] forget values whose inputs have changed.
+//!
+//! ```
+//! use pointer::MemoCell;
+//!
+//! fn fib(n: u64, memo: &MemoCell<u64, u64>) -> u64 {
+//!   if n < 2 {
+//!     return n;
+//!   }
+//!   memo.get_or_insert_with(n, || fib(n - 1, memo) + fib(n - 2, memo))
+//! }
+//!
+//! let memo = MemoCell::new();
+//! assert_eq!(fib(80, &memo), 23_416_728_348_467_685);
+//! assert_eq!(memo.len(), 79);
+//! ```
+//!
+//! [`get_or_insert_with`]: MemoCell::get_or_insert_with
+//! [`with_limit`]: MemoCell::with_limit
+//! [`invalidate`]: MemoCell::invalidate
+//! [`clear`]: MemoCell::clear
+
+use crate::RefCell;
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// A cache of computed values, one per key.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct MemoCell<K, V> {
+  values: RefCell<HashMap<K, V>>,
+  /// The cached keys, the oldest first.
+  order: RefCell<VecDeque<K>>,
+  /// The keys whose values are being computed, the innermost last.
+  computing: RefCell<Vec<K>>,
+  limit: usize,
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> MemoCell<K, V> {
+  /// Creates an empty `MemoCell`, without a limit on how many values it
+  /// holds.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::MemoCell;
+  ///
+  /// let memo: MemoCell<&str, usize> = MemoCell::new();
+  /// assert!(memo.is_empty());
+  /// ```
+  pub fn new() -> MemoCell<K, V> {
+    MemoCell::with_limit(usize::MAX)
+  }
+
+  /// Creates an empty `MemoCell` that holds at most `limit` values.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::MemoCell;
+  ///
+  /// let memo = MemoCell::with_limit(2);
+  /// for n in 0..3 {
+  ///   memo.get_or_insert_with(n, || n * 10);
+  /// }
+  ///
+  /// // The oldest value was forgotten.
+  /// assert_eq!(memo.get(&0), None);
+  /// assert_eq!(memo.get(&2), Some(20));
+  /// ```
+  pub fn with_limit(limit: usize) -> MemoCell<K, V> {
+    MemoCell {
+      values: RefCell::new(HashMap::new()),
+      order: RefCell::new(VecDeque::new()),
+      computing: RefCell::new(Vec::new()),
+      limit,
+    }
+  }
+
+  /// Returns a clone of the value cached for `key`, or computes it with `f`,
+  /// caches it, and returns a clone of it.
+  ///
+  /// `f` may call into the cell for other keys.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `f` asks for `key` itself, directly or through other keys.
+  /// If `f` panics, nothing is cached for `key`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::MemoCell;
+  ///
+  /// let memo = MemoCell::new();
+  /// assert_eq!(memo.get_or_insert_with("a", || 1), 1);
+  /// assert_eq!(memo.get_or_insert_with("a", || 2), 1);
+  /// ```
+  pub fn get_or_insert_with(&self, key: K, f: impl FnOnce() -> V) -> V {
+    if let Some(value) = self.get(&key) {
+      return value;
+    }
+
+    /// Marks the key as computed, even if `f` panics.
+    struct Computing<'a, K>(&'a RefCell<Vec<K>>);
+
+    impl<K> Drop for Computing<'_, K> {
+      fn drop(&mut self) {
+        self.0.borrow_mut().pop();
+      }
+    }
+
+    if self.computing.borrow().contains(&key) {
+      panic!("`MemoCell::get_or_insert_with` called for a key from its own computation");
+    }
+    self.computing.borrow_mut().push(key.clone());
+    let computing = Computing(&self.computing);
+    let value = f();
+    drop(computing);
+    self.insert(key, value.clone());
+    value
+  }
+
+  /// Returns a clone of the value cached for `key`, if there is one.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::MemoCell;
+  ///
+  /// let memo = MemoCell::new();
+  /// memo.get_or_insert_with(1, || "one");
+  ///
+  /// assert_eq!(memo.get(&1), Some("one"));
+  /// assert_eq!(memo.get(&2), None);
+  /// ```
+  pub fn get(&self, key: &K) -> Option<V> {
+    self.values.borrow().get(key).cloned()
+  }
+
+  /// Forgets the value cached for `key`, and returns it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::MemoCell;
+  ///
+  /// let memo = MemoCell::new();
+  /// memo.get_or_insert_with("size", || 10);
+  ///
+  /// assert_eq!(memo.invalidate(&"size"), Some(10));
+  /// assert_eq!(memo.get_or_insert_with("size", || 20), 20);
+  /// ```
+  pub fn invalidate(&self, key: &K) -> Option<V> {
+    let value = self.values.borrow_mut().remove(key)?;
+    self.order.borrow_mut().retain(|k| k != key);
+    Some(value)
+  }
+
+  /// Forgets every cached value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::MemoCell;
+  ///
+  /// let memo = MemoCell::new();
+  /// memo.get_or_insert_with(1, || 1);
+  /// memo.clear();
+  /// assert!(memo.is_empty());
+  /// ```
+  pub fn clear(&self) {
+    self.values.borrow_mut().clear();
+    self.order.borrow_mut().clear();
+  }
+
+  /// Returns the number of cached values.
+  pub fn len(&self) -> usize {
+    self.values.borrow().len()
+  }
+
+  /// Returns `true` if no value is cached.
+  pub fn is_empty(&self) -> bool {
+    self.values.borrow().is_empty()
+  }
+
+  /// Caches `value` for `key`, forgetting the oldest value if the cell is
+  /// full.
+  fn insert(&self, key: K, value: V) {
+    if self.limit == 0 {
+      return;
+    }
+    let mut values = self.values.borrow_mut();
+    let mut order = self.order.borrow_mut();
+    values.insert(key.clone(), value);
+    order.push_back(key);
+    if order.len() > self.limit {
+      if let Some(oldest) = order.pop_front() {
+        values.remove(&oldest);
+      }
+    }
+  }
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> Default for MemoCell<K, V> {
+  fn default() -> MemoCell<K, V> {
+    MemoCell::new()
+  }
+}
+
+impl<K: std::fmt::Debug, V: std::fmt::Debug> std::fmt::Debug
+  for MemoCell<K, V>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.values.try_borrow() {
+      Ok(values) => f
+        .debug_struct("MemoCell")
+        .field("values", &*values)
+        .field("limit", &self.limit)
+        .finish(),
+      Err(_) => f.write_str("MemoCell { <borrowed> }"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn values_are_computed_once() {
+    let memo = MemoCell::new();
+    let calls = crate::Cell::new(0);
+    let square = |n: u32| {
+      memo.get_or_insert_with(n, || {
+        calls.set(calls.get() + 1);
+        n * n
+      })
+    };
+
+    assert_eq!((square(3), square(3), square(4)), (9, 9, 16));
+    assert_eq!(calls.get(), 2);
+
+    memo.invalidate(&3);
+    assert_eq!(square(3), 9);
+    assert_eq!(calls.get(), 3);
+  }
+
+  #[test]
+  fn the_oldest_values_are_evicted() {
+    let memo = MemoCell::with_limit(2);
+    memo.get_or_insert_with('a', || 1);
+    memo.get_or_insert_with('b', || 2);
+    memo.invalidate(&'a');
+    memo.get_or_insert_with('c', || 3);
+    memo.get_or_insert_with('d', || 4);
+
+    assert_eq!(memo.len(), 2);
+    assert_eq!((memo.get(&'b'), memo.get(&'c')), (None, Some(3)));
+
+    let none = MemoCell::with_limit(0);
+    assert_eq!(none.get_or_insert_with((), || 1), 1);
+    assert!(none.is_empty());
+  }
+
+  #[test]
+  #[should_panic(expected = "its own computation")]
+  fn recursion_on_the_same_key_panics() {
+    fn collatz(n: u64, memo: &MemoCell<u64, u64>) -> u64 {
+      // A bug: the sequence from 4 comes back to 4.
+      let next = if n.is_multiple_of(2) {
+        n / 2
+      } else {
+        3 * n + 1
+      };
+      memo.get_or_insert_with(n, || 1 + collatz(next, memo))
+    }
+
+    collatz(4, &MemoCell::new());
+  }
+
+  #[test]
+  fn a_panicking_computation_caches_nothing() {
+    let memo = MemoCell::new();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      memo.get_or_insert_with(1, || panic!("out of budget"))
+    }));
+    assert!(result.is_err());
+
+    assert!(memo.is_empty());
+    assert_eq!(memo.get_or_insert_with(1, || 2), 2);
+  }
+}
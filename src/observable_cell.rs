@@ -0,0 +1,410 @@
+//! A cell that tells its subscribers what changed: [`ObservableCell<T>`][ObservableCell].
+//!
+//! Each write to an `ObservableCell` is delivered to its subscribers as a [`Change<T>`][Change]
+//! that says what the write was and carries the values involved, so a data-binding layer can
+//! update only what the change touched. Subscribers are held as in [`Listeners`]: a subscriber
+//! stays registered for as long as the [`Subscription`] returned by [`subscribe`] is alive.
+//!
+//! Inside a [`batch`], writes are collected instead, and delivered together as one
+//! [`Change::Batch`] when the batch ends.
+//!
+//! ```
+//! use pointer::{Change, ObservableCell, Rc, RefCell};
+//!
+//! let name = ObservableCell::new(String::from("Ada"));
+//! let log = Rc::new(RefCell::new(Vec::new()));
+//!
+//! let seen = Rc::clone(&log);
+//! let _subscription = name.subscribe(move |change: &Change<String>| {
+//!   seen.borrow_mut().push(change.clone());
+//! });
+//!
+//! name.set(String::from("Grace"));
+//! assert_eq!(
+//!   *log.borrow(),
+//!   [Change::Set {
+//!     old: String::from("Ada"),
+//!     new: String::from("Grace"),
+//!   }],
+//! );
+//! ```
+//!
+//! [`Listeners`]: crate::Listeners
+//! [`Subscription`]: crate::Subscription
+//! [`subscribe`]: ObservableCell::subscribe
+//! [`batch`]: ObservableCell::batch
+
+use crate::{Listeners, Rc, RefCell, Subscription};
+
+/// A write to an [`ObservableCell`], as delivered to its subscribers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Change<T> {
+  /// The value was [set](ObservableCell::set). The event owns `old`, which
+  /// is dropped with it.
+  Set {
+    /// The value before the write.
+    old: T,
+    /// The value after the write.
+    new: T,
+  },
+  /// The value was [replaced](ObservableCell::replace), and `old` was
+  /// returned to the writer.
+  Replace {
+    /// The value before the write.
+    old: T,
+    /// The value after the write.
+    new: T,
+  },
+  /// The value was [taken](ObservableCell::take), leaving
+  /// `Default::default()` in its place.
+  Take {
+    /// The value before the write.
+    old: T,
+  },
+  /// The writes made in a [`batch`](ObservableCell::batch), in order.
+  Batch(Vec<Change<T>>),
+}
+
+/// A mutable value that notifies its subscribers of each [`Change`].
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct ObservableCell<T> {
+  value: RefCell<T>,
+  listeners: Listeners<Rc<Change<T>>>,
+  /// The changes made in the current batch, if there is one.
+  batch: RefCell<Option<Vec<Change<T>>>>,
+}
+
+impl<T> ObservableCell<T> {
+  /// Creates a new `ObservableCell` containing `value`, with no subscribers.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ObservableCell;
+  ///
+  /// let cell = ObservableCell::new(5);
+  /// ```
+  pub fn new(value: T) -> ObservableCell<T> {
+    ObservableCell {
+      value: RefCell::new(value),
+      listeners: Listeners::new(),
+      batch: RefCell::new(None),
+    }
+  }
+
+  /// Calls `f` with a reference to the value, and returns what it returns.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `f` writes to the cell.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ObservableCell;
+  ///
+  /// let cell = ObservableCell::new(vec![1, 2]);
+  /// assert_eq!(cell.with(|v| v.len()), 2);
+  /// ```
+  pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+    f(&self.value.borrow())
+  }
+
+  /// Registers `f` to be called with each change, and returns the
+  /// [`Subscription`] that keeps it registered.
+  ///
+  /// A subscriber that writes to the cell isn't told of its own write,
+  /// as with [`Listeners::emit`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Cell, ObservableCell, Rc};
+  ///
+  /// let cell = ObservableCell::new(0);
+  /// let changes = Rc::new(Cell::new(0));
+  ///
+  /// let count = Rc::clone(&changes);
+  /// let subscription = cell.subscribe(move |_| count.set(count.get() + 1));
+  /// cell.set(1);
+  ///
+  /// drop(subscription);
+  /// cell.set(2);
+  /// assert_eq!(changes.get(), 1);
+  /// ```
+  pub fn subscribe(
+    &self,
+    mut f: impl FnMut(&Change<T>) + 'static,
+  ) -> Subscription<Rc<Change<T>>> {
+    self
+      .listeners
+      .subscribe(move |change: Rc<Change<T>>| f(&change))
+  }
+
+  /// Collects the changes made while `f` runs, and delivers them to the
+  /// subscribers as one [`Change::Batch`] once it returns.
+  ///
+  /// Nothing is delivered for a batch without writes. A batch inside a batch
+  /// joins the outer one. If `f` panics, its writes stay and aren't
+  /// delivered.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Change, ObservableCell, Rc, RefCell};
+  ///
+  /// let cell = ObservableCell::new(1);
+  /// let log = Rc::new(RefCell::new(Vec::new()));
+  ///
+  /// let seen = Rc::clone(&log);
+  /// let _subscription =
+  ///   cell.subscribe(move |change| seen.borrow_mut().push(change.clone()));
+  ///
+  /// cell.batch(|| {
+  ///   cell.set(2);
+  ///   cell.set(3);
+  /// });
+  ///
+  /// let changes = vec![
+  ///   Change::Set { old: 1, new: 2 },
+  ///   Change::Set { old: 2, new: 3 },
+  /// ];
+  /// assert_eq!(*log.borrow(), [Change::Batch(changes)]);
+  /// ```
+  pub fn batch<R>(&self, f: impl FnOnce() -> R) -> R {
+    /// Ends the batch, even if `f` panics.
+    struct EndBatch<'a, T>(&'a RefCell<Option<Vec<Change<T>>>>);
+
+    impl<T> Drop for EndBatch<'_, T> {
+      fn drop(&mut self) {
+        *self.0.borrow_mut() = None;
+      }
+    }
+
+    if self.batch.borrow().is_some() {
+      return f();
+    }
+    *self.batch.borrow_mut() = Some(Vec::new());
+    let end = EndBatch(&self.batch);
+    let result = f();
+    let changes = self.batch.borrow_mut().take().unwrap_or_default();
+    drop(end);
+    if !changes.is_empty() {
+      self.listeners.emit(Rc::new(Change::Batch(changes)));
+    }
+    result
+  }
+
+  /// Delivers `change`, or adds it to the batch.
+  fn notify(&self, change: Change<T>) {
+    if let Some(batch) = &mut *self.batch.borrow_mut() {
+      batch.push(change);
+      return;
+    }
+    self.listeners.emit(Rc::new(change));
+  }
+}
+
+impl<T: Clone> ObservableCell<T> {
+  /// Returns a clone of the value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ObservableCell;
+  ///
+  /// let cell = ObservableCell::new(5);
+  /// assert_eq!(cell.get(), 5);
+  /// ```
+  pub fn get(&self) -> T {
+    self.value.borrow().clone()
+  }
+
+  /// Sets the value, and delivers a [`Change::Set`].
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is being read, from within [`with`].
+  ///
+  /// [`with`]: ObservableCell::with
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ObservableCell;
+  ///
+  /// let cell = ObservableCell::new(5);
+  /// cell.set(10);
+  /// assert_eq!(cell.get(), 10);
+  /// ```
+  pub fn set(&self, value: T) {
+    let new = value.clone();
+    let old = self.value.replace(value);
+    self.notify(Change::Set { old, new });
+  }
+
+  /// Replaces the value, returning the old one, and delivers a
+  /// [`Change::Replace`].
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is being read, from within [`with`].
+  ///
+  /// [`with`]: ObservableCell::with
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ObservableCell;
+  ///
+  /// let cell = ObservableCell::new(5);
+  /// assert_eq!(cell.replace(10), 5);
+  /// ```
+  pub fn replace(&self, value: T) -> T {
+    let new = value.clone();
+    let old = self.value.replace(value);
+    self.notify(Change::Replace {
+      old: old.clone(),
+      new,
+    });
+    old
+  }
+}
+
+impl<T: Clone + Default> ObservableCell<T> {
+  /// Takes the value, leaving `Default::default()` in its place, and
+  /// delivers a [`Change::Take`].
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is being read, from within [`with`].
+  ///
+  /// [`with`]: ObservableCell::with
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ObservableCell;
+  ///
+  /// let cell = ObservableCell::new(vec![1]);
+  /// assert_eq!(cell.take(), [1]);
+  /// assert!(cell.get().is_empty());
+  /// ```
+  pub fn take(&self) -> T {
+    let old = self.value.take();
+    self.notify(Change::Take { old: old.clone() });
+    old
+  }
+}
+
+impl<T: Default> Default for ObservableCell<T> {
+  fn default() -> ObservableCell<T> {
+    ObservableCell::new(T::default())
+  }
+}
+
+impl<T> From<T> for ObservableCell<T> {
+  fn from(value: T) -> ObservableCell<T> {
+    ObservableCell::new(value)
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for ObservableCell<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.value.try_borrow() {
+      Ok(value) => f
+        .debug_struct("ObservableCell")
+        .field("value", &*value)
+        .field("subscribers", &self.listeners.len())
+        .finish(),
+      Err(_) => f.write_str("ObservableCell { <borrowed> }"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  type Log<T> = Rc<RefCell<Vec<Change<T>>>>;
+
+  fn recorded<T: Clone + 'static>(
+    cell: &ObservableCell<T>,
+  ) -> (Log<T>, Subscription<Rc<Change<T>>>) {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let seen = Rc::clone(&log);
+    let subscription =
+      cell.subscribe(move |change| seen.borrow_mut().push(change.clone()));
+    (log, subscription)
+  }
+
+  #[test]
+  fn each_write_is_delivered_with_its_values() {
+    let cell = ObservableCell::new(String::from("a"));
+    let (log, _subscription) = recorded(&cell);
+
+    cell.set(String::from("b"));
+    assert_eq!(cell.replace(String::from("c")), "b");
+    assert_eq!(cell.take(), "c");
+
+    let s = String::from;
+    assert_eq!(
+      *log.borrow(),
+      [
+        Change::Set {
+          old: s("a"),
+          new: s("b"),
+        },
+        Change::Replace {
+          old: s("b"),
+          new: s("c"),
+        },
+        Change::Take { old: s("c") },
+      ],
+    );
+  }
+
+  #[test]
+  fn batches_deliver_once() {
+    let cell = ObservableCell::new(0);
+    let (log, _subscription) = recorded(&cell);
+
+    cell.batch(|| {});
+    assert!(log.borrow().is_empty());
+
+    let taken = cell.batch(|| {
+      cell.set(1);
+      cell.batch(|| cell.set(2));
+      cell.take()
+    });
+    assert_eq!(taken, 2);
+    assert_eq!(
+      *log.borrow(),
+      [Change::Batch(vec![
+        Change::Set { old: 0, new: 1 },
+        Change::Set { old: 1, new: 2 },
+        Change::Take { old: 2 },
+      ])],
+    );
+  }
+
+  #[test]
+  fn a_panicking_batch_ends() {
+    let cell = ObservableCell::new(0);
+    let (log, _subscription) = recorded(&cell);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      cell.batch(|| {
+        cell.set(1);
+        panic!("in a batch");
+      })
+    }));
+    assert!(result.is_err());
+    assert!(log.borrow().is_empty());
+
+    cell.set(2);
+    assert_eq!(*log.borrow(), [Change::Set { old: 1, new: 2 }]);
+  }
+}
@@ -0,0 +1,391 @@
+//! Owning handles that deref to a part of an [`Rc`]'d value.
+//!
+//! An accessor that wants to hand out a field of a shared value is usually stuck: a plain `&U`
+//! borrows from whoever holds the `Rc`, and returning the `Rc` itself exposes the whole container.
+//! [`OwnedProjection<T, U>`][OwnedProjection] keeps the strong reference to the `T` together with a
+//! reference into it, and derefs to that `&U`. It can be returned, stored and cloned freely, and the
+//! value stays alive for as long as any projection of it does. It also goes by [`RcRef<T, U>`][RcRef].
+//!
+//! For an `Rc<RefCell<T>>`, [`OwnedRef<T, U>`][OwnedRef] does the same while holding a shared
+//! borrow of the cell, so the value can't be mutated behind the projected reference.
+//!
+//! ```
+//! use pointer::{OwnedProjection, Rc};
+//!
+//! struct User {
+//!   name: String,
+//!   age: u32,
+//! }
+//!
+//! impl User {
+//!   fn name(this: &Rc<User>) -> OwnedProjection<User, str> {
+//!     OwnedProjection::new(Rc::clone(this), |user| user.name.as_str())
+//!   }
+//! }
+//!
+//! let user = Rc::new(User { name: String::from("ferris"), age: 7 });
+//! let name = User::name(&user);
+//! drop(user);
+//!
+//! // `name` still owns the user.
+//! assert_eq!(&*name, "ferris");
+//! assert_eq!(OwnedProjection::owner(&name).age, 7);
+//! ```
+
+use crate::refcell::{Ref, RefCell};
+use crate::Rc;
+
+use std::ptr::NonNull;
+
+/// An [`Rc<T>`][Rc] that derefs to a `U` inside its value.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct OwnedProjection<T: ?Sized, U: ?Sized> {
+  owner: Rc<T>,
+  // Points into the value of `owner`, or elsewhere for at least as long.
+  ptr: NonNull<U>,
+}
+
+impl<T: ?Sized, U: ?Sized> OwnedProjection<T, U> {
+  /// Creates a projection of `owner` through `f`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{OwnedProjection, Rc};
+  ///
+  /// let pair = Rc::new((1, String::from("one")));
+  /// let second = OwnedProjection::new(pair, |pair| &pair.1);
+  /// assert_eq!(*second, "one");
+  /// ```
+  pub fn new(owner: Rc<T>, f: impl FnOnce(&T) -> &U) -> OwnedProjection<T, U> {
+    let ptr = NonNull::from(f(&owner));
+    OwnedProjection { owner, ptr }
+  }
+
+  /// Creates a projection of `owner` through `f`, or gives `owner` back if
+  /// `f` returns [`None`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{OwnedProjection, Rc};
+  ///
+  /// let nums = Rc::new(vec![1, 2, 3]);
+  /// let first = OwnedProjection::try_new(nums, |v| v.first()).unwrap();
+  /// assert_eq!(*first, 1);
+  ///
+  /// let empty = Rc::new(Vec::<i32>::new());
+  /// assert!(OwnedProjection::try_new(empty, |v| v.first()).is_err());
+  /// ```
+  pub fn try_new(
+    owner: Rc<T>,
+    f: impl FnOnce(&T) -> Option<&U>,
+  ) -> Result<OwnedProjection<T, U>, Rc<T>> {
+    match f(&owner).map(NonNull::from) {
+      Some(ptr) => Ok(OwnedProjection { owner, ptr }),
+      None => Err(owner),
+    }
+  }
+
+  /// Projects further, from the current `U` to a `V` inside it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{OwnedProjection, Rc};
+  ///
+  /// let config = Rc::new((String::from("name"), vec![80, 443]));
+  /// let ports = OwnedProjection::new(config, |c| &c.1);
+  /// let https = OwnedProjection::map(ports, |ports| &ports[1]);
+  /// assert_eq!(*https, 443);
+  /// ```
+  pub fn map<V: ?Sized>(
+    this: Self,
+    f: impl FnOnce(&U) -> &V,
+  ) -> OwnedProjection<T, V> {
+    let ptr = NonNull::from(f(&this));
+    OwnedProjection {
+      owner: this.owner,
+      ptr,
+    }
+  }
+
+  /// Returns the `Rc` this projection keeps alive.
+  pub fn owner(this: &Self) -> &Rc<T> {
+    &this.owner
+  }
+
+  /// Drops the projection and returns the `Rc` it kept alive.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{OwnedProjection, Rc};
+  ///
+  /// let pair = Rc::new((1, 2));
+  /// let first = OwnedProjection::new(Rc::clone(&pair), |pair| &pair.0);
+  /// assert!(Rc::ptr_eq(&OwnedProjection::into_owner(first), &pair));
+  /// ```
+  pub fn into_owner(this: Self) -> Rc<T> {
+    this.owner
+  }
+}
+
+impl<T: ?Sized, U: ?Sized> Clone for OwnedProjection<T, U> {
+  fn clone(&self) -> OwnedProjection<T, U> {
+    OwnedProjection {
+      owner: Rc::clone(&self.owner),
+      ptr: self.ptr,
+    }
+  }
+}
+
+impl<T: ?Sized, U: ?Sized> std::ops::Deref for OwnedProjection<T, U> {
+  type Target = U;
+
+  #[inline]
+  fn deref(&self) -> &U {
+    // SAFETY: `ptr` came from a reference that lives at least as long as the
+    // value of `owner`, which is kept alive and never mutated through the
+    // `Rc`.
+    unsafe { self.ptr.as_ref() }
+  }
+}
+
+impl<T: ?Sized, U: ?Sized> AsRef<U> for OwnedProjection<T, U> {
+  fn as_ref(&self) -> &U {
+    self
+  }
+}
+
+impl<T: ?Sized, U: ?Sized> std::borrow::Borrow<U> for OwnedProjection<T, U> {
+  fn borrow(&self) -> &U {
+    self
+  }
+}
+
+impl<T: ?Sized> From<Rc<T>> for OwnedProjection<T, T> {
+  /// Projects the whole value.
+  fn from(owner: Rc<T>) -> OwnedProjection<T, T> {
+    OwnedProjection::new(owner, |value| value)
+  }
+}
+
+impl<T: ?Sized, U: ?Sized + std::fmt::Display> std::fmt::Display
+  for OwnedProjection<T, U>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized, U: ?Sized + std::fmt::Debug> std::fmt::Debug
+  for OwnedProjection<T, U>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+/// Another name for [`OwnedProjection`], for code that thinks of it as a
+/// reference into an [`Rc`].
+///
+/// `RcRef::from` turns an `Rc<T>` into a handle to the whole value, and
+/// [`RcRef::map`] narrows it to a part. Like the methods of `Rc`, `map` is an
+/// associated function, so it can't clash with a method of `U`.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::{Rc, RcRef};
+///
+/// struct Server {
+///   host: String,
+///   port: u16,
+/// }
+///
+/// fn host(server: &Rc<Server>) -> RcRef<Server, str> {
+///   RcRef::map(RcRef::from(Rc::clone(server)), |s| s.host.as_str())
+/// }
+///
+/// let server = Rc::new(Server { host: String::from("localhost"), port: 80 });
+/// let name = host(&server);
+/// drop(server);
+/// assert_eq!(&*name, "localhost");
+/// assert_eq!(RcRef::owner(&name).port, 80);
+/// ```
+pub type RcRef<T, U = T> = OwnedProjection<T, U>;
+
+/// An [`Rc<RefCell<T>>`][Rc] that holds a shared borrow of the cell and derefs
+/// to a `U` inside its value.
+///
+/// While an `OwnedRef` (or a clone of it) is alive, the cell can't be borrowed
+/// mutably. See the [module-level documentation](./index.html) for more
+/// details.
+pub struct OwnedRef<T, U: ?Sized> {
+  // Holds a shared borrow of the cell, leaked from a `Ref` and released on
+  // drop.
+  owner: Rc<RefCell<T>>,
+  ptr: NonNull<U>,
+}
+
+impl<T, U: ?Sized> OwnedRef<T, U> {
+  /// Borrows the cell in `owner` and projects its value through `f`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently mutably borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{OwnedRef, Rc, RefCell};
+  ///
+  /// let cell = Rc::new(RefCell::new((1, String::from("one"))));
+  /// let second = OwnedRef::new(Rc::clone(&cell), |pair| pair.1.as_str());
+  ///
+  /// assert_eq!(&*second, "one");
+  /// assert!(cell.try_borrow_mut().is_err());
+  /// drop(second);
+  /// assert!(cell.try_borrow_mut().is_ok());
+  /// ```
+  #[track_caller]
+  pub fn new(
+    owner: Rc<RefCell<T>>,
+    f: impl FnOnce(&T) -> &U,
+  ) -> OwnedRef<T, U> {
+    let borrow = owner.borrow();
+    let ptr = NonNull::from(f(&borrow));
+    std::mem::forget(borrow);
+    OwnedRef { owner, ptr }
+  }
+
+  /// Projects further, from the current `U` to a `V` inside it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{OwnedRef, Rc, RefCell};
+  ///
+  /// let cell = Rc::new(RefCell::new(vec![vec![1, 2], vec![3]]));
+  /// let first = OwnedRef::new(cell, |rows| &rows[0]);
+  /// let last = OwnedRef::map(first, |row| &row[1]);
+  /// assert_eq!(*last, 2);
+  /// ```
+  pub fn map<V: ?Sized>(
+    this: Self,
+    f: impl FnOnce(&U) -> &V,
+  ) -> OwnedRef<T, V> {
+    let ptr = NonNull::from(f(&this));
+    let this = std::mem::ManuallyDrop::new(this);
+    OwnedRef {
+      // SAFETY: `this` is never dropped, so the `Rc` and its borrow move to
+      // the new projection.
+      owner: unsafe { std::ptr::read(&this.owner) },
+      ptr,
+    }
+  }
+
+  /// Returns the `Rc` this projection keeps alive.
+  pub fn owner(this: &Self) -> &Rc<RefCell<T>> {
+    &this.owner
+  }
+
+  /// Releases the borrow and returns the `Rc` it kept alive.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{OwnedRef, Rc, RefCell};
+  ///
+  /// let first = OwnedRef::new(Rc::new(RefCell::new((1, 2))), |p| &p.0);
+  /// let cell = OwnedRef::into_owner(first);
+  /// cell.borrow_mut().0 = 10;
+  /// ```
+  pub fn into_owner(this: Self) -> Rc<RefCell<T>> {
+    let owner = Rc::clone(&this.owner);
+    drop(this);
+    owner
+  }
+}
+
+impl<T, U: ?Sized> Clone for OwnedRef<T, U> {
+  /// Clones the projection, taking out another shared borrow of the cell.
+  fn clone(&self) -> OwnedRef<T, U> {
+    // Can't fail: `self` already holds a shared borrow.
+    std::mem::forget(self.owner.borrow());
+    OwnedRef {
+      owner: Rc::clone(&self.owner),
+      ptr: self.ptr,
+    }
+  }
+}
+
+impl<T, U: ?Sized> Drop for OwnedRef<T, U> {
+  fn drop(&mut self) {
+    // SAFETY: The borrow leaked when this projection was made is still held.
+    drop(unsafe { Ref::from_leaked(&self.owner) });
+  }
+}
+
+impl<T, U: ?Sized> std::ops::Deref for OwnedRef<T, U> {
+  type Target = U;
+
+  #[inline]
+  fn deref(&self) -> &U {
+    // SAFETY: `ptr` came from a reference into the borrowed value, and the
+    // borrow is held for as long as `self` is.
+    unsafe { self.ptr.as_ref() }
+  }
+}
+
+impl<T, U: ?Sized> AsRef<U> for OwnedRef<T, U> {
+  fn as_ref(&self) -> &U {
+    self
+  }
+}
+
+impl<T, U: ?Sized + std::fmt::Display> std::fmt::Display for OwnedRef<T, U> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T, U: ?Sized + std::fmt::Debug> std::fmt::Debug for OwnedRef<T, U> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn projection_outlives_caller_rc() {
+    let value = Rc::new(vec![String::from("a"), String::from("b")]);
+    let second = OwnedProjection::new(Rc::clone(&value), |v| v[1].as_str());
+    let copy = second.clone();
+    drop(value);
+
+    assert_eq!(Rc::strong_count(OwnedProjection::owner(&copy)), 2);
+    drop(second);
+    assert_eq!(&*copy, "b");
+    assert_eq!(format!("{:?}", copy), "\"b\"");
+  }
+
+  #[test]
+  fn owned_ref_holds_borrow_until_last_clone() {
+    let cell = Rc::new(RefCell::new(vec![1, 2, 3]));
+    let tail = OwnedRef::new(Rc::clone(&cell), |v| &v[1..]);
+    let copy = OwnedRef::map(tail.clone(), |tail| &tail[1]);
+
+    drop(tail);
+    assert!(cell.try_borrow_mut().is_err());
+    assert_eq!(*copy, 3);
+    drop(copy);
+    cell.borrow_mut().push(4);
+    assert_eq!(Rc::strong_count(&cell), 1);
+  }
+}
@@ -1,5 +1,3 @@
-#![allow(dead_code)]
-
 //! Single-threaded reference-counting pointers. 'Rc' stands for 'Reference Counted'.
 //!
 //! The type [`Rc<T>`][Rc] provides shared ownership of a value of type `T`, allocated in the heap.
@@ -13,7 +11,7 @@
 //! [`Rc`] uses non-atomic reference counting. This means that overhead is very low, but an [`Rc`] cannot
 //! be sent between threads, and consequently [`Rc`] does not implement [`Send`][send]. As a result, the
 //! Rust compiler will check *at compile time* that you are not sending [`Rc`]s between threads.
-//! If you need multi-threaded, atomic reference counting, use [`std::sync::Arc`][arc].
+//! If you need multi-threaded, atomic reference counting, use [`sync::Arc`][arc].
 //!
 //! The [`downgrade`][downgrade] method can be used to create a non-owning [`Weak`] pointer.
 //! A [`Weak`] pointer can be [`upgrade`][upgrade]d to an [`Rc`], but this will return [`None`]
@@ -197,27 +195,95 @@
 //!   // Gadget Man, so he gets destroyed as well.
 //! }
 //! ```
+//!
+//! # Finding leaks
+//!
+//! A cycle of `Rc` pointers is never deallocated. With the `leak-debug` feature, every `Rc`
+//! allocation is recorded in a per-thread table until it is freed, with its value type and where it
+//! was made. `live_allocations()` lists the allocations on the current thread, and any still live
+//! when the thread exits are reported on standard error.
+//!
 //! [clone]: Clone::clone
 //! [`Cell`]: crate::Cell
 //! [`RefCell`]: crate::RefCell
 //! [send]: std::marker::Send
-//! [arc]: std::sync::Arc
+//! [arc]: crate::sync::Arc
 //! [`Deref`]: std::ops::Deref
 //! [downgrade]: Rc::downgrade
 //! [upgrade]: Weak::upgrade
 //! [mutability]: crate#introducing-mutability-inside-of-something-immutable
 
+use crate::alloc::{Allocator, Global};
 use crate::cell::Cell;
 
+#[cfg(feature = "leak-debug")]
+pub use crate::leak_debug::{live_allocations, LiveAllocation};
+
 // This is repr(C) to future-proof against possible field-reodering, which would
 // interface with otherwise safe [into|from]_raw() of transmutable inner types.
 #[repr(C)]
-struct RcBox<T: ?Sized> {
-  strong: Cell<usize>,
-  weak: Cell<usize>,
+struct RcBox<T: ?Sized, C = usize> {
+  strong: Cell<C>,
+  weak: Cell<C>,
   value: T,
 }
 
+/// An integer type for the reference counts of an [`Rc`].
+///
+/// The counts default to `usize`. With `u32` counts, as in [`SmallRc`], each
+/// allocation is 8 bytes smaller on 64-bit targets, which adds up for large
+/// numbers of small shared values. The process aborts if a count would go past
+/// the maximum of its type, so a `u32` allows a little over four billion
+/// strong pointers to one allocation. The weak count keeps its top bit for
+/// internal use, which leaves room for half as many weak pointers.
+///
+/// This trait is sealed: it is implemented for `usize` and `u32` only.
+pub trait Counter: private::Sealed + Copy {
+  #[doc(hidden)]
+  const MAX: usize;
+
+  #[doc(hidden)]
+  fn to_usize(self) -> usize;
+
+  #[doc(hidden)]
+  fn from_usize(n: usize) -> Self;
+}
+
+impl Counter for usize {
+  const MAX: usize = usize::MAX;
+
+  #[inline]
+  fn to_usize(self) -> usize {
+    self
+  }
+
+  #[inline]
+  fn from_usize(n: usize) -> usize {
+    n
+  }
+}
+
+impl Counter for u32 {
+  const MAX: usize = u32::MAX as usize;
+
+  #[inline]
+  fn to_usize(self) -> usize {
+    self as usize
+  }
+
+  #[inline]
+  fn from_usize(n: usize) -> u32 {
+    n as u32
+  }
+}
+
+mod private {
+  pub trait Sealed {}
+
+  impl Sealed for usize {}
+  impl Sealed for u32 {}
+}
+
 /// A single-threaded reference-counting pointer. 'Rc' stands for 'Reference Counted.'
 ///
 /// See the [module-level documentation](./index.html) for more details.
@@ -226,15 +292,142 @@ struct RcBox<T: ?Sized> {
 /// e.g., [`Rc::get_mut(&mut value)`][get_mut] instead of `value.get_mut()`. This avoids conflicts with
 /// methods of the inner type `T`.
 ///
+/// The counts are `usize`s unless `C` says otherwise; see [`Counter`]. The
+/// allocation comes from the [`Global`] allocator unless `A` says otherwise;
+/// see [`Rc::new_in`].
+///
+/// # Memory layout
+///
+/// An `Rc<T>` is a single non-null pointer to the allocation, which is thin
+/// for a sized `T` and fat for an unsized one, next to the allocator (a
+/// zero-sized [`Global`] by default). Because the pointer is never null,
+/// `Option<Rc<T>>` is the same size as `Rc<T>`. This is a guarantee, and it
+/// holds for [`Weak`] and [`SmallRc`] too.
+///
+/// ```
+/// use pointer::{Rc, Weak};
+/// use std::mem::size_of;
+///
+/// assert_eq!(size_of::<Option<Rc<u64>>>(), size_of::<usize>());
+/// assert_eq!(size_of::<Option<Weak<u64>>>(), size_of::<usize>());
+/// assert_eq!(size_of::<Option<Rc<str>>>(), size_of::<Rc<str>>());
+/// ```
+///
+/// # Drop order
+///
+/// When the last `Rc` is dropped, the strong count reaches zero first, then
+/// the value is dropped in place, and only then is the memory freed, unless
+/// [`Weak`] pointers remain, in which case it is freed with the last of them.
+/// So the value's own `Drop` can still use `Weak`s to it and to its
+/// neighbours: they stay valid, and the ones to it no longer upgrade.
+///
+/// ```
+/// use pointer::{Rc, RefCell, Weak};
+///
+/// struct Node {
+///   parent: Weak<RefCell<Node>>,
+///   name: &'static str,
+///   log: Rc<RefCell<Vec<String>>>,
+/// }
+///
+/// impl Drop for Node {
+///   fn drop(&mut self) {
+///     let parent = self.parent.upgrade().map(|p| p.borrow().name);
+///     self.log.borrow_mut().push(format!("{} under {:?}", self.name, parent));
+///   }
+/// }
+///
+/// let log = Rc::new(RefCell::new(Vec::new()));
+/// let root = Rc::new(RefCell::new(Node {
+///   parent: Weak::new(),
+///   name: "root",
+///   log: Rc::clone(&log),
+/// }));
+/// let child = Rc::new(RefCell::new(Node {
+///   parent: Rc::downgrade(&root),
+///   name: "child",
+///   log: Rc::clone(&log),
+/// }));
+///
+/// drop(child);
+/// drop(root);
+/// let log = log.borrow();
+/// assert_eq!(*log, ["child under Some(\"root\")", "root under None"]);
+/// ```
+///
 /// [get_mut]: #method.get_mut
-pub struct Rc<T: ?Sized> {
-  ptr: std::ptr::NonNull<RcBox<T>>,
-  phantom: std::marker::PhantomData<RcBox<T>>,
+pub struct Rc<T: ?Sized, C: Counter = usize, A: Allocator = Global> {
+  ptr: std::ptr::NonNull<RcBox<T, C>>,
+  // Tells the drop checker that dropping an `Rc` may drop a `T`, so that
+  // values borrowed by the `T` must outlive the `Rc`.
+  phantom: std::marker::PhantomData<RcBox<T, C>>,
+  alloc: A,
 }
 
+/// An [`Rc`] with `u32` reference counts, 8 bytes smaller per allocation on
+/// 64-bit targets.
+///
+/// Construct one with `SmallRc::from(value)`.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::SmallRc;
+///
+/// let five = SmallRc::from(5u64);
+/// let also_five = SmallRc::clone(&five);
+///
+/// assert_eq!(SmallRc::strong_count(&five), 2);
+/// assert_eq!(*also_five, 5);
+/// ```
+pub type SmallRc<T> = Rc<T, u32>;
+
+/// The [`Weak`] counterpart of a [`SmallRc`].
+pub type SmallWeak<T> = Weak<T, u32>;
+
+/// An [`Rc`] whose allocation is overwritten with zeros before it is freed,
+/// for secrets such as key material.
+///
+/// Construct one with [`Rc::new_zeroizing`]. See [`Wiping`] for what is
+/// wiped, and when.
+///
+/// [`Wiping`]: crate::alloc::Wiping
+pub type SecretRc<T> = Rc<T, usize, crate::alloc::Wiping>;
+
+// The memory layout that the docs of `Rc` guarantee.
+const _: () = {
+  use std::mem::size_of;
+  assert!(size_of::<Rc<u8>>() == size_of::<usize>());
+  assert!(size_of::<Option<Rc<u8>>>() == size_of::<usize>());
+  assert!(size_of::<Option<SmallRc<u8>>>() == size_of::<usize>());
+  assert!(size_of::<Option<Rc<[u8]>>>() == size_of::<Rc<[u8]>>());
+  assert!(size_of::<Option<Rc<dyn Fn()>>>() == size_of::<Rc<dyn Fn()>>());
+  assert!(size_of::<Weak<u8>>() == size_of::<usize>());
+  assert!(size_of::<Option<Weak<u8>>>() == size_of::<usize>());
+  assert!(size_of::<Option<Weak<[u8]>>>() == size_of::<Weak<[u8]>>());
+};
+
 // impl<T: ?Sized> !std::marker::Send for Rc<T> {}
 // impl<T: ?Sized> !std::marker::Sync for Rc<T> {}
 
+// As in std, an `Rc<T>` only gives out `&T`, so it is unwind safe when `&T`
+// is. The counts are cells, which would otherwise rule it out, but a panic
+// never leaves them half updated.
+impl<
+    T: ?Sized + std::panic::RefUnwindSafe,
+    C: Counter,
+    A: Allocator + std::panic::UnwindSafe,
+  > std::panic::UnwindSafe for Rc<T, C, A>
+{
+}
+impl<
+    T: ?Sized + std::panic::RefUnwindSafe,
+    C: Counter,
+    A: Allocator + std::panic::UnwindSafe,
+  > std::panic::RefUnwindSafe for Rc<T, C, A>
+{
+}
+
 // impl<T: ?Sized + std::marker::Unsize<U>, U: ?Sized> std::ops::CoerceUnsized<Rc<U>> for Rc<T> {}
 // impl<T: ?Sized + std::marker::Unsize<U>, U: ?Sized> std::ops::DispatchFromDyn<Rc<U>> for Rc<T> {}
 
@@ -253,18 +446,2888 @@ pub struct Rc<T: ?Sized> {
 ///
 /// The typical way to obtain a `Weak` pointer is to call [`Rc::downgrade`].
 ///
+/// A `Weak` has the [memory layout](Rc#memory-layout) of an `Rc`. One made by
+/// [`Weak::new`] doesn't allocate; it holds a non-null address that no
+/// allocation can have, so `Option<Weak<T>>` is still the size of `Weak<T>`.
+///
 /// [`upgrade`]: Weak::upgrade
-pub struct Weak<T> {
+pub struct Weak<T: ?Sized, C: Counter = usize, A: Allocator = Global> {
   // This is a `NonNull` to allow optimizing the size of this type in enums,
   // but it is not necessarily a valid pointer.
   // `Weak::new` sets this to `usize::MAX` so that it doesn't need
   // to allocate space on the heap. That's not a value a real pointer
   // will ever have because RcBox has alignment at least 2.
   // This is only possible when `T: Sized`; unsized `T` never dangle.
-  ptr: std::ptr::NonNull<RcBox<T>>,
+  ptr: std::ptr::NonNull<RcBox<T, C>>,
+  alloc: A,
 }
 
 // impl<T: ?Sized> !std::marker::Send for Weak<T> {}
 // impl<T: ?Sized> !std::marker::Sync for Weak<T> {}
+// Like std's, `Weak` is neither `UnwindSafe` nor `RefUnwindSafe`: its counts
+// are cells and nothing overrides that.
 // impl<T: ?Sized + std::marker::Unsize<U>, U: ?Sized> std::ops::CoerceUnsized<Weak<U>> for Weak<T> {}
 // impl<T: std::marker::Unsize<U>, U: ?Sized> std::ops::DispatchFromDyn<Weak<U>> for Weak<T> {}
+
+impl<T> Rc<T> {
+  /// Constructs a new `Rc<T>`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new(5);
+  /// ```
+  pub fn new(value: T) -> Rc<T> {
+    Rc::allocate_in(value, Global)
+  }
+
+  /// Constructs a new `Rc<T>` using a weak reference to itself. Attempting
+  /// to upgrade the weak reference before this function returns will result
+  /// in a `None` value. However, the weak reference may be cloned freely and
+  /// stored for use at a later time.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Rc, Weak};
+  ///
+  /// struct Gadget {
+  ///   me: Weak<Gadget>,
+  /// }
+  ///
+  /// let gadget = Rc::new_cyclic(|me| {
+  ///   // Upgrading is not possible until `new_cyclic` returns.
+  ///   assert!(me.upgrade().is_none());
+  ///   Gadget { me: me.clone() }
+  /// });
+  ///
+  /// assert!(Rc::ptr_eq(&gadget, &gadget.me.upgrade().unwrap()));
+  /// ```
+  pub fn new_cyclic(data_fn: impl FnOnce(&Weak<T>) -> T) -> Rc<T> {
+    // Construct the inner in the "uninitialized" state with a single
+    // weak reference.
+    let layout = std::alloc::Layout::new::<RcBox<std::mem::MaybeUninit<T>>>();
+    let uninit_ptr = crate::alloc::allocate_or_abort(&Global, layout)
+      .cast::<RcBox<std::mem::MaybeUninit<T>>>();
+    // SAFETY: The block fits an `RcBox<MaybeUninit<T>>`.
+    unsafe {
+      uninit_ptr.as_ptr().write(RcBox {
+        strong: Cell::new(0),
+        weak: Cell::new(1),
+        value: std::mem::MaybeUninit::<T>::uninit(),
+      });
+    }
+    let init_ptr: std::ptr::NonNull<RcBox<T>> = uninit_ptr.cast();
+    track(init_ptr);
+
+    let weak = Weak {
+      ptr: init_ptr,
+      alloc: Global,
+    };
+
+    // It's important we don't give up ownership of the weak pointer, or
+    // else the memory might be freed by the time `data_fn` returns. If
+    // we really wanted to pass ownership, we could create an additional
+    // weak pointer for ourselves, but this would result in additional
+    // updates to the weak reference count which might not be necessary
+    // otherwise.
+    let data = data_fn(&weak);
+
+    // SAFETY: `MaybeUninit<T>` has the same layout as `T`, and no one can
+    // upgrade while the strong count is zero.
+    unsafe {
+      std::ptr::write(std::ptr::addr_of_mut!((*init_ptr.as_ptr()).value), data);
+
+      let inner = init_ptr.as_ref();
+      debug_assert_eq!(
+        inner.strong(),
+        0,
+        "No prior strong references should exist"
+      );
+      inner.strong.set(1);
+    }
+
+    // Strong references should collectively own a shared weak reference,
+    // so don't run the destructor for our old weak reference.
+    std::mem::forget(weak);
+    Self::from_inner(init_ptr)
+  }
+
+  /// Constructs a new `Rc<T>` that calls `f` on its value once the last
+  /// strong pointer is dropped, just before the value itself is dropped.
+  ///
+  /// The finalizer gets a mutable reference, so it can move resources out of
+  /// the value, such as handing a buffer back to a pool. [`Weak`] pointers
+  /// can't be upgraded while it runs, and it never runs if the value is moved
+  /// out with [`Rc::try_unwrap`] or [`Rc::into_inner`]. If the value is moved
+  /// to a new allocation by [`Rc::make_mut`], the finalizer goes with it.
+  ///
+  /// Finalizers are kept in a per-thread table until they run, so they are
+  /// skipped for `Rc`s dropped while their thread is being torn down.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Rc, RefCell};
+  ///
+  /// let pool = Rc::new(RefCell::new(Vec::new()));
+  ///
+  /// let buffer = Rc::new_with_finalizer(vec![0u8; 64], {
+  ///   let pool = Rc::clone(&pool);
+  ///   move |buffer: &mut Vec<u8>| pool.borrow_mut().push(std::mem::take(buffer))
+  /// });
+  /// let also_buffer = Rc::clone(&buffer);
+  ///
+  /// drop(buffer);
+  /// assert!(pool.borrow().is_empty());
+  /// drop(also_buffer);
+  /// assert_eq!(pool.borrow()[0].len(), 64);
+  /// ```
+  pub fn new_with_finalizer(value: T, f: impl FnOnce(&mut T) + 'static) -> Rc<T>
+  where
+    T: 'static,
+  {
+    let this = Rc::new(value);
+    let finalizer: Finalizer = Box::new(move |value| {
+      // SAFETY: The finalizer is only called with a pointer to the value of
+      // the allocation it was registered for.
+      f(unsafe { &mut *value.cast::<T>() })
+    });
+    set_finalizer(this.inner(), finalizer);
+    this
+  }
+
+  /// Constructs a new `Pin<Rc<T>>`. If `T` does not implement `Unpin`, then
+  /// `value` will be pinned in memory and unable to be moved.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let pinned = Rc::pin(5);
+  /// assert_eq!(*pinned, 5);
+  /// ```
+  pub fn pin(value: T) -> std::pin::Pin<Rc<T>> {
+    // SAFETY: The value lives on the heap and `Rc` never moves it out
+    // through a shared pointer.
+    unsafe { std::pin::Pin::new_unchecked(Rc::new(value)) }
+  }
+
+  /// Constructs an `Rc<T>` from a raw pointer.
+  ///
+  /// The raw pointer must have been previously returned by a call to
+  /// [`Rc::into_raw`].
+  ///
+  /// The user of `from_raw` has to make sure a specific value of `T` is only
+  /// dropped once.
+  ///
+  /// # Safety
+  ///
+  /// `ptr` must come from [`Rc::into_raw`] on an `Rc<T>` whose strong
+  /// reference has not been reclaimed yet.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let x = Rc::new("hello".to_owned());
+  /// let x_ptr = Rc::into_raw(x);
+  ///
+  /// unsafe {
+  ///   // Convert back to an `Rc` to prevent leak.
+  ///   let x = Rc::from_raw(x_ptr);
+  ///   assert_eq!(&*x, "hello");
+  ///
+  ///   // Further calls to `Rc::from_raw(x_ptr)` would be memory-unsafe.
+  /// }
+  ///
+  /// // The memory was freed when `x` went out of scope above, so `x_ptr` is now dangling!
+  /// ```
+  pub unsafe fn from_raw(ptr: *const T) -> Rc<T> {
+    let offset = data_offset::<T>();
+
+    // Reverse the offset to find the original RcBox.
+    let rc_ptr = ptr.byte_sub(offset).cast::<RcBox<T>>().cast_mut();
+
+    Self::from_inner(std::ptr::NonNull::new_unchecked(rc_ptr))
+  }
+
+  /// Increments the strong reference count on the `Rc<T>` associated with the
+  /// provided pointer by one.
+  ///
+  /// # Safety
+  ///
+  /// The pointer must have been obtained through `Rc::into_raw`, and the
+  /// associated `Rc` instance must be valid (i.e. the strong count must be at
+  /// least 1) for the duration of this method.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new(5);
+  ///
+  /// unsafe {
+  ///   let ptr = Rc::into_raw(five);
+  ///   Rc::increment_strong_count(ptr);
+  ///
+  ///   let five = Rc::from_raw(ptr);
+  ///   assert_eq!(2, Rc::strong_count(&five));
+  ///   Rc::decrement_strong_count(ptr);
+  /// }
+  /// ```
+  #[inline]
+  pub unsafe fn increment_strong_count(ptr: *const T) {
+    // Retain Rc, but don't touch refcount by wrapping in ManuallyDrop
+    let rc = std::mem::ManuallyDrop::new(Rc::<T>::from_raw(ptr));
+    // Now increase refcount, but don't drop new refcount either
+    let _rc_clone: std::mem::ManuallyDrop<_> = rc.clone();
+  }
+
+  /// Decrements the strong reference count on the `Rc<T>` associated with the
+  /// provided pointer by one.
+  ///
+  /// # Safety
+  ///
+  /// The pointer must have been obtained through `Rc::into_raw`, and the
+  /// associated `Rc` instance must be valid (i.e. the strong count must be at
+  /// least 1) when invoking this method. This method can be used to release
+  /// the final `Rc` and backing storage, but **should not** be called after
+  /// the final `Rc` has been released.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new(5);
+  ///
+  /// unsafe {
+  ///   let ptr = Rc::into_raw(five);
+  ///   Rc::increment_strong_count(ptr);
+  ///
+  ///   let five = Rc::from_raw(ptr);
+  ///   assert_eq!(2, Rc::strong_count(&five));
+  ///   Rc::decrement_strong_count(ptr);
+  ///   assert_eq!(1, Rc::strong_count(&five));
+  /// }
+  /// ```
+  #[inline]
+  pub unsafe fn decrement_strong_count(ptr: *const T) {
+    drop(Rc::from_raw(ptr));
+  }
+}
+
+impl<T, A: Allocator> Rc<T, usize, A> {
+  /// Constructs a new `Rc<T, usize, A>` in the given allocator.
+  ///
+  /// The allocator is kept in every `Rc` and [`Weak`] to the value, and the
+  /// memory goes back to it once the last of them is dropped. Cloning a
+  /// pointer clones the allocator, so it's usually a reference or a handle.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::alloc::Global;
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new_in(5, Global);
+  /// assert_eq!(*five, 5);
+  /// ```
+  pub fn new_in(value: T, alloc: A) -> Rc<T, usize, A> {
+    Rc::allocate_in(value, alloc)
+  }
+}
+
+impl<T> Rc<T, usize, crate::alloc::Wiping> {
+  /// Constructs a new [`SecretRc<T>`], whose allocation is overwritten with
+  /// zeros once the last `Rc` and [`Weak`] to it are dropped.
+  ///
+  /// The value is dropped first, as usual. A value that owns more memory
+  /// must wipe it in its own `Drop`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Rc, SecretRc};
+  ///
+  /// let key: SecretRc<[u8; 32]> = Rc::new_zeroizing([0x5a; 32]);
+  /// let session = Rc::clone(&key);
+  ///
+  /// assert_eq!(session[31], 0x5a);
+  /// ```
+  pub fn new_zeroizing(value: T) -> SecretRc<T> {
+    Rc::new_in(value, crate::alloc::Wiping::new(crate::alloc::Global))
+  }
+}
+
+impl<T, C: Counter, A: Allocator> Rc<T, C, A> {
+  fn allocate_in(value: T, alloc: A) -> Rc<T, C, A> {
+    let layout = std::alloc::Layout::new::<RcBox<T, C>>();
+    let ptr =
+      crate::alloc::allocate_or_abort(&alloc, layout).cast::<RcBox<T, C>>();
+    // There is an implicit weak pointer owned by all the strong
+    // pointers, which ensures that the weak destructor never frees
+    // the allocation while the strong destructor is running, even
+    // if the weak pointer is stored inside the strong one.
+    //
+    // SAFETY: The block fits an `RcBox<T, C>`.
+    unsafe {
+      ptr.as_ptr().write(RcBox {
+        strong: Cell::new(C::from_usize(1)),
+        weak: Cell::new(C::from_usize(1)),
+        value,
+      });
+    }
+    track(ptr);
+    Self::from_inner_in(ptr, alloc)
+  }
+
+  /// Returns the inner value, if the `Rc` has exactly one strong reference.
+  ///
+  /// Otherwise, an [`Err`] is returned with the same `Rc` that was
+  /// passed in.
+  ///
+  /// This will succeed even if there are outstanding weak references.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let x = Rc::new(3);
+  /// assert_eq!(Rc::try_unwrap(x).ok(), Some(3));
+  ///
+  /// let x = Rc::new(4);
+  /// let _y = Rc::clone(&x);
+  /// assert_eq!(*Rc::try_unwrap(x).unwrap_err(), 4);
+  /// ```
+  #[inline]
+  pub fn try_unwrap(this: Self) -> Result<T, Self> {
+    if Rc::strong_count(&this) == 1 {
+      // SAFETY: We are the only strong pointer, so the value is ours to move.
+      unsafe {
+        let val = std::ptr::read(&*this); // copy the contained object
+
+        // Indicate to Weaks that they can't be promoted by decrementing
+        // the strong count, and then remove the implicit "strong weak"
+        // pointer while also handling drop logic by just crafting a
+        // fake Weak.
+        this.inner().dec_strong();
+        drop(take_finalizer(this.inner()));
+        release(this.ptr);
+        let this = std::mem::ManuallyDrop::new(this);
+        let _weak = Weak {
+          ptr: this.ptr,
+          alloc: std::ptr::read(&this.alloc),
+        };
+        Ok(val)
+      }
+    } else {
+      Err(this)
+    }
+  }
+
+  /// Returns the inner value, if the `Rc` has exactly one strong reference.
+  ///
+  /// Otherwise, [`None`] is returned and the `Rc` is dropped.
+  ///
+  /// This will succeed even if there are outstanding weak references.
+  ///
+  /// If `Rc::into_inner` is called on every clone of this `Rc`,
+  /// it is guaranteed that exactly one of the calls returns the inner value.
+  /// This means in particular that the inner value is not dropped.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let x = Rc::new(3);
+  /// assert_eq!(Rc::into_inner(x), Some(3));
+  ///
+  /// let x = Rc::new(4);
+  /// let y = Rc::clone(&x);
+  ///
+  /// assert_eq!(Rc::into_inner(y), None);
+  /// assert_eq!(Rc::into_inner(x), Some(4));
+  /// ```
+  #[inline]
+  pub fn into_inner(this: Self) -> Option<T> {
+    Rc::try_unwrap(this).ok()
+  }
+}
+
+impl<T: Clone, C: Counter, A: Allocator + Clone> Rc<T, C, A> {
+  /// Makes a mutable reference into the given `Rc`.
+  ///
+  /// If there are other `Rc` pointers to the same allocation, then `make_mut`
+  /// will [`clone`] the inner value to a new allocation to ensure unique
+  /// ownership. This is also referred to as clone-on-write.
+  ///
+  /// If there are no other `Rc` pointers to this allocation, then [`Weak`]
+  /// pointers to this allocation will be disassociated.
+  ///
+  /// See also [`get_mut`], which will fail rather than cloning.
+  ///
+  /// [`clone`]: Clone::clone
+  /// [`get_mut`]: Rc::get_mut
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let mut data = Rc::new(5);
+  ///
+  /// *Rc::make_mut(&mut data) += 1;        // Won't clone anything
+  /// let mut other_data = Rc::clone(&data); // Won't clone inner data
+  /// *Rc::make_mut(&mut data) += 1;        // Clones inner data
+  /// *Rc::make_mut(&mut data) += 1;        // Won't clone anything
+  /// *Rc::make_mut(&mut other_data) *= 2;  // Won't clone anything
+  ///
+  /// // Now `data` and `other_data` point to different allocations.
+  /// assert_eq!(*data, 8);
+  /// assert_eq!(*other_data, 12);
+  /// ```
+  #[inline]
+  pub fn make_mut(this: &mut Self) -> &mut T {
+    if Rc::strong_count(this) != 1 {
+      // Gotta clone the data, there are other Rcs.
+      *this = Rc::allocate_in((**this).clone(), this.alloc.clone());
+    } else if Rc::weak_count(this) != 0 {
+      // Can just steal the data, all that's left is Weaks.
+      //
+      // SAFETY: We are the only strong pointer; after decrementing the
+      // counts the old allocation belongs to the remaining `Weak`s.
+      unsafe {
+        let data = std::ptr::read(&**this);
+
+        // Remove the strong reference and the implicit "strong weak"
+        // reference. The remaining Weaks clean up the allocation.
+        this.inner().dec_strong();
+        this.inner().dec_weak();
+        release(this.ptr);
+
+        let finalizer = take_finalizer(this.inner());
+        let alloc = std::ptr::read(&this.alloc);
+        std::ptr::write(this, Rc::allocate_in(data, alloc));
+        if let Some(finalizer) = finalizer {
+          set_finalizer(this.inner(), finalizer);
+        }
+      }
+    }
+    // This unsafety is ok because we're guaranteed that the pointer
+    // returned is the *only* pointer that will ever be returned to T. Our
+    // reference count is guaranteed to be 1 at this point, and we required
+    // the `Rc<T>` itself to be `mut`, so we're returning the only possible
+    // reference to the allocation.
+    unsafe { &mut (*this.ptr.as_ptr()).value }
+  }
+}
+
+impl<T: ?Sized, C: Counter> Rc<T, C> {
+  fn from_inner(ptr: std::ptr::NonNull<RcBox<T, C>>) -> Self {
+    Self::from_inner_in(ptr, Global)
+  }
+}
+
+impl<T: ?Sized, C: Counter, A: Allocator> Rc<T, C, A> {
+  fn from_inner_in(ptr: std::ptr::NonNull<RcBox<T, C>>, alloc: A) -> Self {
+    Self {
+      ptr,
+      phantom: std::marker::PhantomData,
+      alloc,
+    }
+  }
+
+  /// Returns a reference to the allocator of the `Rc`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::alloc::Global;
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new_in(5, Global);
+  /// let _: &Global = Rc::allocator(&five);
+  /// ```
+  pub fn allocator(this: &Self) -> &A {
+    &this.alloc
+  }
+
+  /// Provides a raw pointer to the data.
+  ///
+  /// The counts are not affected in any way and the `Rc` is not consumed. The pointer is valid
+  /// for as long as there are strong counts in the `Rc`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let x = Rc::new("hello".to_owned());
+  /// let y = Rc::clone(&x);
+  /// let x_ptr = Rc::as_ptr(&x);
+  /// assert_eq!(x_ptr, Rc::as_ptr(&y));
+  /// assert_eq!(unsafe { &*x_ptr }, "hello");
+  /// ```
+  pub fn as_ptr(this: &Self) -> *const T {
+    let ptr: *mut RcBox<T, C> = this.ptr.as_ptr();
+
+    // SAFETY: This cannot go through Deref::deref or Rc::inner because
+    // this is required to retain raw/mut provenance such that e.g. `get_mut` can
+    // write through the pointer after the Rc is recovered through `from_raw`.
+    unsafe { std::ptr::addr_of_mut!((*ptr).value) as *const T }
+  }
+
+  /// Creates a new [`Weak`] pointer to this allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new(5);
+  ///
+  /// let weak_five = Rc::downgrade(&five);
+  /// ```
+  pub fn downgrade(this: &Self) -> Weak<T, C, A>
+  where
+    A: Clone,
+  {
+    this.inc_weak();
+    // Make sure we do not create a dangling Weak.
+    debug_assert!(!is_dangling(this.ptr));
+    Weak {
+      ptr: this.ptr,
+      alloc: this.alloc.clone(),
+    }
+  }
+
+  /// Gets the number of [`Weak`] pointers to this allocation.
+  ///
+  /// As in std, this counts only the `Weak`s made by [`downgrade`] and their
+  /// clones. The strong pointers share one more weak reference, which keeps
+  /// the allocation alive while they exist, and that one isn't counted: an
+  /// `Rc` that was never downgraded has a weak count of 0.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new(5);
+  /// assert_eq!(0, Rc::weak_count(&five));
+  ///
+  /// let _weak_five = Rc::downgrade(&five);
+  /// assert_eq!(1, Rc::weak_count(&five));
+  /// ```
+  ///
+  /// [`downgrade`]: Rc::downgrade
+  #[inline]
+  pub fn weak_count(this: &Self) -> usize {
+    // Don't count the implicit weak pointer owned by the strong pointers.
+    this.weak() - 1
+  }
+
+  /// Gets the number of strong (`Rc`) pointers to this allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new(5);
+  /// let _also_five = Rc::clone(&five);
+  ///
+  /// assert_eq!(2, Rc::strong_count(&five));
+  /// ```
+  #[inline]
+  pub fn strong_count(this: &Self) -> usize {
+    this.strong()
+  }
+
+  /// Returns `true` if `this` is the only pointer to its allocation: the
+  /// strong count is 1 and there are no [`Weak`]s.
+  ///
+  /// This is when [`get_mut`] succeeds, and when [`make_mut`] hands out the
+  /// value in place without cloning or moving it. Any [`Weak`] makes the
+  /// `Rc` shared, since it could upgrade to a second `Rc`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new(5);
+  /// assert!(Rc::is_unique(&five));
+  ///
+  /// let weak_five = Rc::downgrade(&five);
+  /// assert!(!Rc::is_unique(&five));
+  /// drop(weak_five);
+  ///
+  /// let also_five = Rc::clone(&five);
+  /// assert!(!Rc::is_unique(&five));
+  /// drop(also_five);
+  ///
+  /// assert!(Rc::is_unique(&five));
+  /// ```
+  ///
+  /// [`get_mut`]: Rc::get_mut
+  /// [`make_mut`]: Rc::make_mut
+  #[inline]
+  pub fn is_unique(this: &Self) -> bool {
+    Rc::strong_count(this) == 1 && Rc::weak_count(this) == 0
+  }
+
+  /// Gets the number of bytes in the allocation, including the counts and
+  /// any padding.
+  ///
+  /// For unsized values such as `Rc<str>` or `Rc<[T]>` the size depends on
+  /// the length of the value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let short: Rc<str> = Rc::from("ab");
+  /// let long: Rc<str> = Rc::from("abcdefghijklmnopqrstuvwxyz");
+  /// assert_eq!(
+  ///   Rc::allocation_size(&long) - Rc::allocation_size(&short),
+  ///   24,
+  /// );
+  /// ```
+  #[inline]
+  pub fn allocation_size(this: &Self) -> usize {
+    Rc::allocation_layout(this).size()
+  }
+
+  /// Gets the layout of the allocation, as it was requested from the
+  /// allocator.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Boxed, Rc};
+  ///
+  /// let values: Rc<[u64]> = Rc::from(Boxed::from(vec![1, 2, 3]));
+  /// let layout = Rc::allocation_layout(&values);
+  /// assert_eq!(layout.size(), 2 * std::mem::size_of::<usize>() + 24);
+  /// assert_eq!(layout.align(), std::mem::align_of::<u64>());
+  /// ```
+  #[inline]
+  pub fn allocation_layout(this: &Self) -> std::alloc::Layout {
+    std::alloc::Layout::for_value(this.inner())
+  }
+
+  /// Returns a mutable reference into the given `Rc`, if there are
+  /// no other `Rc` or [`Weak`] pointers to the same allocation.
+  ///
+  /// Returns [`None`] otherwise, because it is not safe to
+  /// mutate a shared value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let mut x = Rc::new(3);
+  /// *Rc::get_mut(&mut x).unwrap() = 4;
+  /// assert_eq!(*x, 4);
+  ///
+  /// let _y = Rc::clone(&x);
+  /// assert!(Rc::get_mut(&mut x).is_none());
+  /// ```
+  #[inline]
+  pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+    if Rc::is_unique(this) {
+      // SAFETY: We are the only pointer to the allocation, and `&mut self`
+      // guarantees no one else is dereferencing it.
+      unsafe { Some(&mut (*this.ptr.as_ptr()).value) }
+    } else {
+      None
+    }
+  }
+
+  /// Returns `true` if the two `Rc`s point to the same allocation
+  /// (in a vein similar to [`std::ptr::eq`]).
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new(5);
+  /// let same_five = Rc::clone(&five);
+  /// let other_five = Rc::new(5);
+  ///
+  /// assert!(Rc::ptr_eq(&five, &same_five));
+  /// assert!(!Rc::ptr_eq(&five, &other_five));
+  /// ```
+  #[inline]
+  pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+    this.ptr.as_ptr().cast::<()>() == other.ptr.as_ptr().cast::<()>()
+  }
+}
+
+impl<T: ?Sized> Rc<T> {
+  /// Frees the allocation of a uniquely owned `Rc` without dropping its value.
+  ///
+  /// # Safety
+  ///
+  /// `this` must be the only strong or weak pointer, and its value must have
+  /// been moved out already.
+  pub(crate) unsafe fn dealloc_unique(this: Self) {
+    let ptr = this.ptr;
+    std::mem::forget(this);
+    dealloc_box(ptr, &Global);
+  }
+
+  /// Consumes the `Rc`, returning the wrapped pointer.
+  ///
+  /// To avoid a memory leak the pointer must be converted back to an `Rc` using
+  /// [`Rc::from_raw`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let x = Rc::new("hello".to_owned());
+  /// let x_ptr = Rc::into_raw(x);
+  /// assert_eq!(unsafe { &*x_ptr }, "hello");
+  /// # drop(unsafe { Rc::from_raw(x_ptr) });
+  /// ```
+  pub fn into_raw(this: Self) -> *const T {
+    let ptr = Self::as_ptr(&this);
+    std::mem::forget(this);
+    ptr
+  }
+
+  /// Borrows the `Rc` as a [`RcBorrow`], a one-word handle that derefs to the
+  /// value and can be turned back into an owned `Rc` with
+  /// [`RcBorrow::to_owned`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new(5);
+  /// let borrowed = Rc::borrow_rc(&five);
+  ///
+  /// assert_eq!(*borrowed, 5);
+  /// assert_eq!(Rc::strong_count(&five), 1);
+  /// ```
+  #[inline]
+  pub fn borrow_rc(this: &Self) -> RcBorrow<'_, T> {
+    RcBorrow {
+      ptr: this.ptr,
+      phantom: std::marker::PhantomData,
+    }
+  }
+}
+
+impl Rc<dyn std::any::Any> {
+  /// Attempts to downcast the `Rc<dyn Any>` to a concrete type, returning it
+  /// unchanged if the value isn't a `T`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Boxed, Rc};
+  /// use std::any::Any;
+  ///
+  /// let any: Rc<dyn Any> = Rc::from(Boxed::from(Box::new(5) as Box<dyn Any>));
+  ///
+  /// let any = Rc::downcast::<String>(any).unwrap_err();
+  /// assert_eq!(*Rc::downcast::<i32>(any).unwrap(), 5);
+  /// ```
+  pub fn downcast<T: std::any::Any>(self) -> Result<Rc<T>, Self> {
+    if !(*self).is::<T>() {
+      return Err(self);
+    }
+    let ptr = self.ptr.cast::<RcBox<T>>();
+    std::mem::forget(self);
+    // The value is a `T`, so the allocation is an `RcBox<T>`; the counts move
+    // over with the pointer.
+    Ok(Rc::from_inner(ptr))
+  }
+}
+
+impl<T> Rc<[T]> {
+  /// Rebuilds an `Rc<[T]>` from the parts of a pointer returned by
+  /// [`Rc::into_raw`].
+  ///
+  /// # Safety
+  ///
+  /// `data` and `len` must be the address and length of such a pointer, as
+  /// for [`Rc::from_raw`].
+  #[cfg(feature = "ffi")]
+  pub(crate) unsafe fn from_raw_slice(data: *const T, len: usize) -> Rc<[T]> {
+    // Reverse the offset to find the original RcBox; it is the same for a
+    // slice as for one element.
+    let start = data.byte_sub(data_offset::<T>()).cast_mut();
+    let inner =
+      std::ptr::slice_from_raw_parts_mut(start, len) as *mut RcBox<[T]>;
+    Self::from_inner(std::ptr::NonNull::new_unchecked(inner))
+  }
+
+  /// Allocates an `RcBox<[T]>` for `len` elements, with both counts at one
+  /// and the elements uninitialized. Returns the layout of the allocation
+  /// too, for freeing it if the elements can't be written.
+  fn allocate_for_slice(
+    len: usize,
+  ) -> (std::ptr::NonNull<RcBox<[T]>>, std::alloc::Layout) {
+    let layout = std::alloc::Layout::new::<RcBox<()>>()
+      .extend(std::alloc::Layout::array::<T>(len).expect("slice too large"))
+      .expect("RcBox layout overflow")
+      .0
+      .pad_to_align();
+    let mem = crate::alloc::allocate_or_abort(&Global, layout);
+    // SAFETY: `RcBox` is `repr(C)`, so `layout` is the layout of an
+    // `RcBox<[T]>` of `len` elements, and the counts are written in place.
+    unsafe {
+      let inner =
+        std::ptr::slice_from_raw_parts_mut(mem.as_ptr().cast::<T>(), len)
+          as *mut RcBox<[T]>;
+      std::ptr::addr_of_mut!((*inner).strong).write(Cell::new(1));
+      std::ptr::addr_of_mut!((*inner).weak).write(Cell::new(1));
+      (std::ptr::NonNull::new_unchecked(inner), layout)
+    }
+  }
+
+  /// Collects `iter`, which claims to have exactly `len` items, straight
+  /// into one allocation.
+  ///
+  /// The claim is checked: an iterator that ends early or runs long still
+  /// gives the right slice, through a `Vec`.
+  fn from_exact_iter(mut iter: impl Iterator<Item = T>, len: usize) -> Rc<[T]> {
+    /// Drops the elements written so far and frees the allocation, if the
+    /// iterator panics or the slice has to be rebuilt.
+    struct Partial<T> {
+      mem: std::ptr::NonNull<u8>,
+      layout: std::alloc::Layout,
+      data: *mut T,
+      written: usize,
+    }
+
+    impl<T> Drop for Partial<T> {
+      fn drop(&mut self) {
+        // SAFETY: The first `written` elements are initialized, and the
+        // allocation was made with `layout` and never shared.
+        unsafe {
+          std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(
+            self.data,
+            self.written,
+          ));
+          Global.deallocate(self.mem, self.layout);
+        }
+      }
+    }
+
+    let (ptr, layout) = Rc::allocate_for_slice(len);
+    let mut partial = Partial {
+      mem: ptr.cast::<u8>(),
+      layout,
+      // SAFETY: The pointer is to the new allocation.
+      data: unsafe {
+        std::ptr::addr_of_mut!((*ptr.as_ptr()).value).cast::<T>()
+      },
+      written: 0,
+    };
+    while partial.written < len {
+      match iter.next() {
+        // SAFETY: There is room for `len` elements.
+        Some(item) => unsafe {
+          partial.data.add(partial.written).write(item);
+          partial.written += 1;
+        },
+        None => break,
+      }
+    }
+
+    let extra = iter.next();
+    if partial.written < len || extra.is_some() {
+      // The size hint was wrong. Move what was written into a `Vec`, and
+      // collect the rest the slow way.
+      let mut items = Vec::with_capacity(partial.written + 1);
+      // SAFETY: Exactly `written` elements are moved out and forgotten.
+      unsafe {
+        std::ptr::copy_nonoverlapping(
+          partial.data,
+          items.as_mut_ptr(),
+          partial.written,
+        );
+        items.set_len(partial.written);
+      }
+      partial.written = 0;
+      drop(partial);
+      items.extend(extra);
+      items.extend(iter);
+      return Rc::from(items);
+    }
+
+    std::mem::forget(partial);
+    track(ptr);
+    Rc::from_inner(ptr)
+  }
+}
+
+impl<T: Clone> Rc<[T]> {
+  /// Returns an iterator over clones of the elements, which keeps the slice
+  /// alive by itself.
+  ///
+  /// Unlike `iter().cloned()`, the iterator doesn't borrow `this`, so it can
+  /// be returned or stored.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Boxed, Rc};
+  ///
+  /// fn names() -> impl Iterator<Item = String> {
+  ///   let names: Rc<[String]> =
+  ///     Rc::from(Boxed::from(vec![String::from("a"), String::from("b")]));
+  ///   Rc::iter_cloned(&names)
+  /// }
+  ///
+  /// assert_eq!(names().collect::<Vec<_>>(), ["a", "b"]);
+  /// ```
+  pub fn iter_cloned(this: &Self) -> IterCloned<T> {
+    IterCloned {
+      slice: Rc::clone(this),
+      front: 0,
+      back: this.len(),
+    }
+  }
+}
+
+impl<T: ?Sized, C: Counter, A: Allocator + Clone> Clone for Rc<T, C, A> {
+  /// Makes a clone of the `Rc` pointer.
+  ///
+  /// This creates another pointer to the same allocation, increasing the
+  /// strong reference count.
+  #[inline]
+  fn clone(&self) -> Rc<T, C, A> {
+    self.inc_strong();
+    Self::from_inner_in(self.ptr, self.alloc.clone())
+  }
+}
+
+impl<T: ?Sized, C: Counter, A: Allocator> std::ops::Deref for Rc<T, C, A> {
+  type Target = T;
+
+  #[inline]
+  fn deref(&self) -> &T {
+    &self.inner().value
+  }
+}
+
+impl<'a, T, C: Counter, A: Allocator> IntoIterator for &'a Rc<[T], C, A> {
+  type Item = &'a T;
+  type IntoIter = std::slice::Iter<'a, T>;
+
+  fn into_iter(self) -> std::slice::Iter<'a, T> {
+    self.iter()
+  }
+}
+
+impl<'a, T, C: Counter, A: Allocator> IntoIterator for &'a Rc<Vec<T>, C, A> {
+  type Item = &'a T;
+  type IntoIter = std::slice::Iter<'a, T>;
+
+  fn into_iter(self) -> std::slice::Iter<'a, T> {
+    self.iter()
+  }
+}
+
+drop_may_dangle! {
+  impl<T: ?Sized, C: Counter, A: Allocator> Drop for Rc<T, C, A> {
+    /// Drops the `Rc`.
+    ///
+    /// This will decrement the strong reference count. If the strong
+    /// reference count reaches zero then the only other references (if any)
+    /// are [`Weak`], so we `drop` the inner value.
+    ///
+    /// Apart from dropping it, this only passes the value to a finalizer from
+    /// [`Rc::new_with_finalizer`], which needs `T: 'static`, so the value
+    /// can't borrow anything that is gone.
+    fn drop(&mut self) {
+      self.dec_strong();
+      if self.strong() == 0 {
+        // SAFETY: The strong count just reached zero, so no one else can
+        // reach the inner value any more.
+        unsafe {
+          let value = std::ptr::addr_of_mut!((*self.ptr.as_ptr()).value);
+          if let Some(finalize) = take_finalizer(self.inner()) {
+            finalize(value.cast::<()>());
+          }
+          // destroy the contained object
+          std::ptr::drop_in_place(value);
+        }
+        release(self.ptr);
+
+        // remove the implicit "strong weak" pointer now that we've
+        // destroyed the contents.
+        self.dec_weak();
+
+        if self.weak() == 0 {
+          // SAFETY: Both counts are zero, so nothing else points into the box.
+          // The value has already been dropped, so only free the memory.
+          unsafe {
+            dealloc_box(self.ptr, &self.alloc);
+          }
+        }
+      }
+    }
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display, C: Counter, A: Allocator> std::fmt::Display
+  for Rc<T, C, A>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::error::Error, C: Counter, A: Allocator> std::error::Error
+  for Rc<T, C, A>
+{
+  #[allow(deprecated)]
+  fn description(&self) -> &str {
+    std::error::Error::description(&**self)
+  }
+
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    std::error::Error::source(&**self)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug, C: Counter, A: Allocator> std::fmt::Debug
+  for Rc<T, C, A>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized, C: Counter, A: Allocator> std::fmt::Pointer for Rc<T, C, A> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Pointer::fmt(&(&**self as *const T), f)
+  }
+}
+
+impl<T, C: Counter> From<T> for Rc<T, C> {
+  /// Moves a value into a new `Rc`. This also constructs an `Rc` with
+  /// non-default counts, such as a [`SmallRc`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Rc, SmallRc};
+  ///
+  /// let five: Rc<i32> = Rc::from(5);
+  /// let small = SmallRc::from(5);
+  /// assert_eq!(*five, *small);
+  /// ```
+  fn from(value: T) -> Rc<T, C> {
+    Rc::allocate_in(value, Global)
+  }
+}
+
+impl From<&str> for Rc<str> {
+  /// Copies a string slice into a new reference-counted `str`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let shared: Rc<str> = Rc::from("eggplant");
+  /// assert_eq!("eggplant", &shared[..]);
+  /// ```
+  #[inline]
+  fn from(v: &str) -> Rc<str> {
+    Rc::from(String::from(v))
+  }
+}
+
+impl From<String> for Rc<str> {
+  /// Moves a [`String`] into a new reference-counted `str`.
+  #[inline]
+  fn from(v: String) -> Rc<str> {
+    Rc::from(crate::Boxed::from(v))
+  }
+}
+
+impl<T: ?Sized> From<crate::Boxed<T>> for Rc<T> {
+  /// Moves a boxed value, which may be unsized, into a new `Rc<T>`.
+  ///
+  /// This is the way to get an `Rc<dyn Trait>` or an `Rc<[T]>` without
+  /// unsized coercions.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Boxed, Rc};
+  ///
+  /// let boxed: Boxed<[i32]> = Boxed::from(vec![1, 2, 3]);
+  /// let shared: Rc<[i32]> = Rc::from(boxed);
+  ///
+  /// assert_eq!(*shared, [1, 2, 3]);
+  /// ```
+  fn from(b: crate::Boxed<T>) -> Rc<T> {
+    let value_layout = std::alloc::Layout::for_value(&*b);
+    let value_size = value_layout.size();
+    let layout = std::alloc::Layout::new::<RcBox<()>>()
+      .extend(value_layout)
+      .expect("RcBox layout overflow")
+      .0
+      .pad_to_align();
+    let bptr = crate::Boxed::into_raw(b);
+
+    let mem = crate::alloc::allocate_or_abort(&Global, layout).as_ptr();
+
+    // SAFETY: `RcBox` is `repr(C)`, so `layout` is the layout of an
+    // `RcBox<T>` holding this value. The value is moved bit for bit into the
+    // new allocation, so the box must give up its memory without dropping it.
+    unsafe {
+      let inner = crate::boxed::set_data_ptr(bptr as *mut RcBox<T>, mem);
+      std::ptr::addr_of_mut!((*inner).strong).write(Cell::new(1));
+      std::ptr::addr_of_mut!((*inner).weak).write(Cell::new(1));
+      std::ptr::copy_nonoverlapping(
+        bptr.cast::<u8>(),
+        std::ptr::addr_of_mut!((*inner).value).cast::<u8>(),
+        value_size,
+      );
+      if value_size != 0 {
+        std::alloc::dealloc(bptr.cast::<u8>(), value_layout);
+      }
+      let inner = std::ptr::NonNull::new_unchecked(inner);
+      track(inner);
+      Rc::from_inner(inner)
+    }
+  }
+}
+
+impl<T> From<Vec<T>> for Rc<[T]> {
+  /// Moves the elements of a [`Vec`] into a new reference-counted slice.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let shared: Rc<[i32]> = Rc::from(vec![1, 2, 3]);
+  /// assert_eq!(*shared, [1, 2, 3]);
+  /// ```
+  fn from(mut v: Vec<T>) -> Rc<[T]> {
+    let (ptr, _) = Rc::allocate_for_slice(v.len());
+    // SAFETY: The new slice has room for exactly `v.len()` elements. They
+    // are moved out bit for bit, so the `Vec` must forget them before it
+    // frees its buffer.
+    unsafe {
+      let data = std::ptr::addr_of_mut!((*ptr.as_ptr()).value).cast::<T>();
+      std::ptr::copy_nonoverlapping(v.as_ptr(), data, v.len());
+      v.set_len(0);
+    }
+    track(ptr);
+    Rc::from_inner(ptr)
+  }
+}
+
+impl<T> std::iter::FromIterator<T> for Rc<[T]> {
+  /// Collects the items of an iterator into a new reference-counted slice.
+  ///
+  /// If the [size hint](Iterator::size_hint) of the iterator gives an exact
+  /// length, as for an [`ExactSizeIterator`], the items are written straight
+  /// into the new allocation. Otherwise they are collected into a [`Vec`]
+  /// first.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let squares: Rc<[u32]> = (1..=4).map(|n| n * n).collect();
+  /// assert_eq!(*squares, [1, 4, 9, 16]);
+  ///
+  /// let evens: Rc<[u32]> = (0..10).filter(|n| n % 2 == 0).collect();
+  /// assert_eq!(*evens, [0, 2, 4, 6, 8]);
+  /// ```
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Rc<[T]> {
+    let iter = iter.into_iter();
+    match iter.size_hint() {
+      (lower, Some(upper)) if lower == upper => {
+        Rc::from_exact_iter(iter, lower)
+      }
+      _ => Rc::from(iter.collect::<Vec<T>>()),
+    }
+  }
+}
+
+/// An iterator over clones of the elements of an `Rc<[T]>`.
+///
+/// This struct is created by [`Rc::iter_cloned`].
+pub struct IterCloned<T> {
+  slice: Rc<[T]>,
+  front: usize,
+  back: usize,
+}
+
+impl<T: Clone> Iterator for IterCloned<T> {
+  type Item = T;
+
+  fn next(&mut self) -> Option<T> {
+    if self.front == self.back {
+      return None;
+    }
+    self.front += 1;
+    Some(self.slice[self.front - 1].clone())
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let len = self.back - self.front;
+    (len, Some(len))
+  }
+}
+
+impl<T: Clone> DoubleEndedIterator for IterCloned<T> {
+  fn next_back(&mut self) -> Option<T> {
+    if self.front == self.back {
+      return None;
+    }
+    self.back -= 1;
+    Some(self.slice[self.back].clone())
+  }
+}
+
+impl<T: Clone> ExactSizeIterator for IterCloned<T> {}
+
+impl<T: Clone> std::iter::FusedIterator for IterCloned<T> {}
+
+impl<T> Clone for IterCloned<T> {
+  fn clone(&self) -> IterCloned<T> {
+    IterCloned {
+      slice: Rc::clone(&self.slice),
+      front: self.front,
+      back: self.back,
+    }
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for IterCloned<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("IterCloned")
+      .field(&&self.slice[self.front..self.back])
+      .finish()
+  }
+}
+
+impl<T> Weak<T> {
+  /// Constructs a new `Weak<T>`, without allocating any memory.
+  /// Calling [`upgrade`] on the return value always gives [`None`].
+  ///
+  /// [`upgrade`]: Weak::upgrade
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Weak;
+  ///
+  /// let empty: Weak<i64> = Weak::new();
+  /// assert!(empty.upgrade().is_none());
+  /// ```
+  pub const fn new() -> Weak<T> {
+    Weak::dangling()
+  }
+}
+
+impl<T, C: Counter> Weak<T, C> {
+  const fn dangling() -> Weak<T, C> {
+    Weak {
+      // SAFETY: `usize::MAX` is not null.
+      ptr: unsafe {
+        std::ptr::NonNull::new_unchecked(std::ptr::without_provenance_mut(
+          usize::MAX,
+        ))
+      },
+      alloc: Global,
+    }
+  }
+}
+
+impl<T: ?Sized, C: Counter, A: Allocator> Weak<T, C, A> {
+  /// Attempts to upgrade the `Weak` pointer to an [`Rc`], delaying
+  /// dropping of the inner value if successful.
+  ///
+  /// Returns [`None`] if the inner value has since been dropped.
+  ///
+  /// This includes while it is being dropped: the strong count reaches zero
+  /// before the value's `Drop` runs, so from then on `upgrade` returns `None`
+  /// even though the `Weak` keeps the allocation alive. In particular, a
+  /// `Drop` impl that upgrades a `Weak` to its own value gets `None`, rather
+  /// than a new `Rc` to a value that is going away.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new(5);
+  ///
+  /// let weak_five = Rc::downgrade(&five);
+  ///
+  /// let strong_five = weak_five.upgrade();
+  /// assert!(strong_five.is_some());
+  ///
+  /// // Destroy all strong pointers.
+  /// drop(strong_five);
+  /// drop(five);
+  ///
+  /// assert!(weak_five.upgrade().is_none());
+  /// ```
+  pub fn upgrade(&self) -> Option<Rc<T, C, A>>
+  where
+    A: Clone,
+  {
+    let inner = self.inner()?;
+    if inner.strong() == 0 {
+      None
+    } else {
+      inner.inc_strong();
+      Some(Rc::from_inner_in(self.ptr, self.alloc.clone()))
+    }
+  }
+
+  /// Gets the number of strong (`Rc`) pointers pointing to this allocation.
+  ///
+  /// If `self` was created using [`Weak::new`], this will return 0.
+  pub fn strong_count(&self) -> usize {
+    self.inner().map_or(0, |inner| inner.strong())
+  }
+
+  /// Gets the number of `Weak` pointers pointing to this allocation.
+  ///
+  /// As with [`Rc::weak_count`], the weak reference that the strong pointers
+  /// share isn't counted. If no strong pointers remain, this will return
+  /// zero, as in std.
+  pub fn weak_count(&self) -> usize {
+    self.inner().map_or(0, |inner| {
+      if inner.strong() > 0 {
+        inner.weak() - 1 // subtract the implicit weak ptr
+      } else {
+        0
+      }
+    })
+  }
+
+  /// Returns `true` if the two `Weak`s point to the same allocation
+  /// (similar to [`std::ptr::eq`]), or if both don't point to any allocation
+  /// (because they were created with `Weak::new()`).
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Rc, Weak};
+  ///
+  /// let first_rc = Rc::new(5);
+  /// let first = Rc::downgrade(&first_rc);
+  /// let second = Rc::downgrade(&first_rc);
+  ///
+  /// assert!(first.ptr_eq(&second));
+  ///
+  /// let first: Weak<i32> = Weak::new();
+  /// let second: Weak<i32> = Weak::new();
+  /// assert!(first.ptr_eq(&second));
+  /// ```
+  #[inline]
+  pub fn ptr_eq(&self, other: &Self) -> bool {
+    self.ptr.as_ptr().cast::<()>() == other.ptr.as_ptr().cast::<()>()
+  }
+
+  /// Returns `None` when the pointer is dangling and there is no allocated
+  /// `RcBox` (i.e. when this `Weak` was created by `Weak::new`).
+  #[inline]
+  fn inner(&self) -> Option<WeakInner<'_, C>> {
+    if is_dangling(self.ptr) {
+      None
+    } else {
+      // We are careful to *not* create a reference covering the "value" field, as
+      // the field may be mutated concurrently (for example, if the last `Rc`
+      // is dropped, the value field will be dropped in-place).
+      //
+      // SAFETY: A non-dangling `Weak` keeps the allocation alive.
+      Some(unsafe {
+        let ptr = self.ptr.as_ptr();
+        WeakInner {
+          strong: &*std::ptr::addr_of!((*ptr).strong),
+          weak: &*std::ptr::addr_of!((*ptr).weak),
+        }
+      })
+    }
+  }
+}
+
+impl<T: ?Sized, C: Counter, A: Allocator + Clone> Clone for Weak<T, C, A> {
+  /// Makes a clone of the `Weak` pointer that points to the same allocation.
+  #[inline]
+  fn clone(&self) -> Weak<T, C, A> {
+    if let Some(inner) = self.inner() {
+      inner.inc_weak()
+    }
+    Weak {
+      ptr: self.ptr,
+      alloc: self.alloc.clone(),
+    }
+  }
+}
+
+impl<T, C: Counter> Default for Weak<T, C> {
+  /// Constructs a new `Weak<T>`, without allocating any memory.
+  /// Calling [`upgrade`] on the return value always gives [`None`].
+  ///
+  /// [`upgrade`]: Weak::upgrade
+  fn default() -> Weak<T, C> {
+    Weak::dangling()
+  }
+}
+
+drop_may_dangle! {
+  impl<T: ?Sized, C: Counter, A: Allocator> Drop for Weak<T, C, A> {
+    /// Drops the `Weak` pointer.
+    ///
+    /// The value is already gone, so this only frees its memory.
+    fn drop(&mut self) {
+      let inner = if let Some(inner) = self.inner() {
+        inner
+      } else {
+        return;
+      };
+
+      inner.dec_weak();
+      // the weak count starts at 1, and will only go to zero if all
+      // the strong pointers have disappeared.
+      if inner.weak() == 0 {
+        // SAFETY: The value was dropped with the last strong pointer, and
+        // this was the last weak pointer, so only the memory is left.
+        unsafe {
+          dealloc_box(self.ptr, &self.alloc);
+        }
+      }
+    }
+  }
+}
+
+impl<T: ?Sized, C: Counter, A: Allocator> std::fmt::Debug for Weak<T, C, A> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "(Weak)")
+  }
+}
+
+/// A uniquely owned [`Rc`] under construction.
+///
+/// A `UniqueRc<T>` owns its allocation outright, so it derefs mutably. It can still hand out
+/// [`Weak`] pointers with [`UniqueRc::downgrade`]; those can't be upgraded until the value is
+/// shared with [`UniqueRc::into_rc`]. That makes it a way to build cyclic structures step by
+/// step, without cramming the construction into the closure of [`Rc::new_cyclic`].
+///
+/// If the `UniqueRc` is dropped instead of shared, the value is dropped and the weak pointers
+/// never upgrade.
+///
+/// ```
+/// use pointer::{Rc, UniqueRc, Weak};
+///
+/// struct Node {
+///   parent: Weak<Node>,
+///   children: Vec<Rc<Node>>,
+/// }
+///
+/// let mut root = UniqueRc::new(Node { parent: Weak::new(), children: Vec::new() });
+/// for _ in 0..2 {
+///   let parent = UniqueRc::downgrade(&root);
+///   root.children.push(Rc::new(Node { parent, children: Vec::new() }));
+/// }
+///
+/// let root = UniqueRc::into_rc(root);
+/// let parent = root.children[0].parent.upgrade().unwrap();
+/// assert!(Rc::ptr_eq(&parent, &root));
+/// ```
+pub struct UniqueRc<T: ?Sized> {
+  ptr: std::ptr::NonNull<RcBox<T>>,
+  phantom: std::marker::PhantomData<RcBox<T>>,
+}
+
+impl<T> UniqueRc<T> {
+  /// Constructs a new `UniqueRc<T>`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::UniqueRc;
+  ///
+  /// let mut five = UniqueRc::new(5);
+  /// *five += 1;
+  /// assert_eq!(*five, 6);
+  /// ```
+  pub fn new(value: T) -> UniqueRc<T> {
+    // The strong count stays at zero until the value is shared, which keeps
+    // weak pointers from upgrading. The weak count starts with the implicit
+    // weak pointer, owned by the `UniqueRc` and later by the strong ones.
+    let ptr = crate::alloc::allocate_or_abort(&Global, box_layout::<T>())
+      .cast::<RcBox<T>>();
+    // SAFETY: The block fits an `RcBox<T>`.
+    unsafe {
+      ptr.as_ptr().write(RcBox {
+        strong: Cell::new(0),
+        weak: Cell::new(1),
+        value,
+      });
+    }
+    track(ptr);
+    UniqueRc {
+      ptr,
+      phantom: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<T: ?Sized> UniqueRc<T> {
+  /// Creates a new [`Weak`] pointer to this allocation.
+  ///
+  /// The pointer can't be upgraded until the value is shared with
+  /// [`UniqueRc::into_rc`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::UniqueRc;
+  ///
+  /// let five = UniqueRc::new(5);
+  /// let weak_five = UniqueRc::downgrade(&five);
+  /// assert!(weak_five.upgrade().is_none());
+  ///
+  /// let five = UniqueRc::into_rc(five);
+  /// assert_eq!(*weak_five.upgrade().unwrap(), 5);
+  /// ```
+  pub fn downgrade(this: &Self) -> Weak<T> {
+    this.counts().inc_weak();
+    Weak {
+      ptr: this.ptr,
+      alloc: Global,
+    }
+  }
+
+  /// Converts the `UniqueRc` into a regular [`Rc`], making its weak pointers
+  /// upgradable.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Rc, UniqueRc};
+  ///
+  /// let unique = UniqueRc::new(5);
+  /// let five = UniqueRc::into_rc(unique);
+  /// assert_eq!(Rc::strong_count(&five), 1);
+  /// ```
+  pub fn into_rc(this: Self) -> Rc<T> {
+    let ptr = this.ptr;
+    std::mem::forget(this);
+    let rc = Rc::from_inner(ptr);
+    rc.inner().strong.set(1);
+    rc
+  }
+
+  /// Accesses the reference counts without a reference to the value, which
+  /// may be borrowed mutably.
+  #[inline]
+  fn counts(&self) -> WeakInner<'_> {
+    // SAFETY: The allocation lives at least as long as `self`.
+    unsafe {
+      let ptr = self.ptr.as_ptr();
+      WeakInner {
+        strong: &*std::ptr::addr_of!((*ptr).strong),
+        weak: &*std::ptr::addr_of!((*ptr).weak),
+      }
+    }
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for UniqueRc<T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    // SAFETY: The value is alive while `self` is, and only mutated through
+    // `&mut self`.
+    unsafe { &*std::ptr::addr_of!((*self.ptr.as_ptr()).value) }
+  }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for UniqueRc<T> {
+  fn deref_mut(&mut self) -> &mut T {
+    // SAFETY: No other pointer can reach the value while the strong count is
+    // zero; weak pointers only ever touch the counts.
+    unsafe { &mut *std::ptr::addr_of_mut!((*self.ptr.as_ptr()).value) }
+  }
+}
+
+impl<T: ?Sized> Drop for UniqueRc<T> {
+  fn drop(&mut self) {
+    // SAFETY: The value was never shared, so this is the only owner.
+    unsafe {
+      std::ptr::drop_in_place(std::ptr::addr_of_mut!(
+        (*self.ptr.as_ptr()).value
+      ));
+    }
+    release(self.ptr);
+
+    let counts = self.counts();
+    counts.dec_weak();
+    if counts.weak() == 0 {
+      // SAFETY: No weak pointers are left, and the value is dropped.
+      unsafe {
+        dealloc_box(self.ptr, &Global);
+      }
+    }
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display for UniqueRc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for UniqueRc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized> std::fmt::Pointer for UniqueRc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Pointer::fmt(&(&**self as *const T), f)
+  }
+}
+
+/// Builds a group of [`Rc`] allocations that point at each other.
+///
+/// [`Rc::new_cyclic`] lets one value hold a weak pointer to itself. A `CycleBuilder` does the same
+/// for any number of values: [`reserve`] sets an allocation aside and returns a [`Weak`] pointer to
+/// it right away, and [`finish`] then makes all of the values in one go, with every weak pointer
+/// already at hand. This builds doubly-linked lists and graphs without `Option<Weak<T>>` fields
+/// that are filled in afterwards.
+///
+/// The weak pointers can't be upgraded until `finish` returns. If the builder is dropped instead,
+/// they never upgrade.
+///
+/// ```
+/// use pointer::{CycleBuilder, Weak};
+///
+/// struct Node {
+///   name: char,
+///   prev: Weak<Node>,
+///   next: Weak<Node>,
+/// }
+///
+/// let mut builder = CycleBuilder::new();
+/// let ring: Vec<Weak<Node>> = (0..3).map(|_| builder.reserve()).collect();
+/// let nodes = builder.finish(|i| Node {
+///   name: ['a', 'b', 'c'][i],
+///   prev: ring[(i + 2) % 3].clone(),
+///   next: ring[(i + 1) % 3].clone(),
+/// });
+///
+/// let b = nodes[0].next.upgrade().unwrap();
+/// assert_eq!(b.name, 'b');
+/// assert_eq!(b.prev.upgrade().unwrap().name, 'a');
+/// assert_eq!(b.next.upgrade().unwrap().next.upgrade().unwrap().name, 'a');
+/// ```
+///
+/// [`reserve`]: CycleBuilder::reserve
+/// [`finish`]: CycleBuilder::finish
+pub struct CycleBuilder<T> {
+  /// The reserved allocations, in order. Their strong counts are zero and
+  /// their values uninitialized; the builder owns the implicit weak pointers.
+  slots: Vec<Weak<T>>,
+}
+
+impl<T> CycleBuilder<T> {
+  /// Creates a builder with no allocations reserved.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CycleBuilder;
+  ///
+  /// let builder = CycleBuilder::<i32>::new();
+  /// assert!(builder.finish(|_| unreachable!()).is_empty());
+  /// ```
+  pub fn new() -> CycleBuilder<T> {
+    CycleBuilder { slots: Vec::new() }
+  }
+
+  /// Reserves an allocation for the next value, and returns a [`Weak`]
+  /// pointer to it.
+  ///
+  /// The value is the one [`finish`] makes for the index of this call,
+  /// counting from zero.
+  ///
+  /// [`finish`]: CycleBuilder::finish
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::CycleBuilder;
+  ///
+  /// let mut builder = CycleBuilder::new();
+  /// let first = builder.reserve();
+  /// assert!(first.upgrade().is_none());
+  ///
+  /// let values = builder.finish(|i| i * 10);
+  /// assert_eq!(*first.upgrade().unwrap(), 0);
+  /// assert_eq!(values.len(), 1);
+  /// ```
+  pub fn reserve(&mut self) -> Weak<T> {
+    let ptr = crate::alloc::allocate_or_abort(&Global, box_layout::<T>())
+      .cast::<RcBox<T>>();
+    // SAFETY: The block fits an `RcBox<T>`, and `MaybeUninit<T>` has the
+    // layout of `T`. One weak pointer is the builder's, the other is
+    // returned.
+    unsafe {
+      ptr
+        .as_ptr()
+        .cast::<RcBox<std::mem::MaybeUninit<T>>>()
+        .write(RcBox {
+          strong: Cell::new(0),
+          weak: Cell::new(2),
+          value: std::mem::MaybeUninit::uninit(),
+        });
+    }
+    track(ptr);
+    self.slots.push(Weak { ptr, alloc: Global });
+    Weak { ptr, alloc: Global }
+  }
+
+  /// Makes the values of the reserved allocations, calling `init` with the
+  /// index of each, and returns the [`Rc`]s in the order they were reserved.
+  ///
+  /// No weak pointer can be upgraded while `init` runs; they all become
+  /// upgradable together once every value is made. If `init` panics, the
+  /// values made so far are dropped and none of the weak pointers ever
+  /// upgrade.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{CycleBuilder, Rc, Weak};
+  ///
+  /// struct Pair {
+  ///   other: Weak<Pair>,
+  /// }
+  ///
+  /// let mut builder = CycleBuilder::new();
+  /// let weaks = [builder.reserve(), builder.reserve()];
+  /// let pair = builder.finish(|i| {
+  ///   assert!(weaks[0].upgrade().is_none());
+  ///   Pair { other: weaks[1 - i].clone() }
+  /// });
+  ///
+  /// assert!(Rc::ptr_eq(&pair[0].other.upgrade().unwrap(), &pair[1]));
+  /// assert!(Rc::ptr_eq(&pair[1].other.upgrade().unwrap(), &pair[0]));
+  /// ```
+  pub fn finish(self, mut init: impl FnMut(usize) -> T) -> Vec<Rc<T>> {
+    /// Drops the values written so far if `init` panics.
+    struct Written<'a, T> {
+      slots: &'a [Weak<T>],
+      len: usize,
+    }
+
+    impl<T> Drop for Written<'_, T> {
+      fn drop(&mut self) {
+        for slot in &self.slots[..self.len] {
+          // SAFETY: The value was written, and no strong pointer to it was
+          // ever made. The builder frees the memory afterwards.
+          unsafe {
+            std::ptr::drop_in_place(std::ptr::addr_of_mut!(
+              (*slot.ptr.as_ptr()).value
+            ));
+          }
+          release(slot.ptr);
+        }
+      }
+    }
+
+    let CycleBuilder { slots } = self;
+    let mut written = Written {
+      slots: &slots,
+      len: 0,
+    };
+    for (index, slot) in slots.iter().enumerate() {
+      let value = init(index);
+      // SAFETY: The value of the slot is uninitialized, and nothing can read
+      // it while the strong count is zero.
+      unsafe {
+        std::ptr::addr_of_mut!((*slot.ptr.as_ptr()).value).write(value);
+      }
+      written.len += 1;
+    }
+    std::mem::forget(written);
+
+    slots
+      .into_iter()
+      .map(|slot| {
+        // The builder's weak pointer becomes the one the strong pointers
+        // own together.
+        let slot = std::mem::ManuallyDrop::new(slot);
+        let rc = Rc::from_inner(slot.ptr);
+        rc.inner().strong.set(1);
+        rc
+      })
+      .collect()
+  }
+}
+
+impl<T> Default for CycleBuilder<T> {
+  fn default() -> CycleBuilder<T> {
+    CycleBuilder::new()
+  }
+}
+
+impl<T> std::fmt::Debug for CycleBuilder<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("CycleBuilder")
+      .field("reserved", &self.slots.len())
+      .finish()
+  }
+}
+
+/// A borrowed [`Rc<T>`][Rc] that derefs like the owned pointer.
+///
+/// `RcBorrow<'a, T>` is a single pointer into the allocation, cheaper to pass around than
+/// `&'a Rc<T>` and free of the reference-count traffic of cloning. When a callee does need
+/// ownership, [`to_owned`] turns the handle back into a [`Rc`].
+///
+/// Handles are made with [`Rc::borrow_rc`] or `From<&Rc<T>>`.
+///
+/// ```
+/// use pointer::{Rc, RcBorrow};
+///
+/// fn sum(values: RcBorrow<'_, Vec<u32>>) -> u32 {
+///   values.iter().sum()
+/// }
+///
+/// let values = Rc::new(vec![1, 2, 3]);
+/// assert_eq!(sum(Rc::borrow_rc(&values)), 6);
+/// assert_eq!(Rc::strong_count(&values), 1);
+/// ```
+///
+/// Since [`get`] keeps the lifetime of the original borrow, a recursive walk over a graph of
+/// `Rc`s can hand each call frame a handle to a child without a clone and drop per frame:
+///
+/// ```
+/// use pointer::{Rc, RcBorrow};
+///
+/// struct Node {
+///   value: u32,
+///   children: Vec<Rc<Node>>,
+/// }
+///
+/// fn total(node: RcBorrow<'_, Node>) -> u32 {
+///   let children = &node.get().children;
+///   node.value + children.iter().map(|c| total(Rc::borrow_rc(c))).sum::<u32>()
+/// }
+///
+/// let leaf = Rc::new(Node { value: 2, children: vec![] });
+/// let root = Rc::new(Node { value: 1, children: vec![Rc::clone(&leaf), leaf] });
+/// assert_eq!(total(Rc::borrow_rc(&root)), 5);
+/// ```
+///
+/// [`to_owned`]: RcBorrow::to_owned
+/// [`get`]: RcBorrow::get
+pub struct RcBorrow<'a, T: ?Sized> {
+  ptr: std::ptr::NonNull<RcBox<T>>,
+  phantom: std::marker::PhantomData<&'a Rc<T>>,
+}
+
+impl<'a, T: ?Sized> RcBorrow<'a, T> {
+  /// Creates a new [`Rc`] pointing to the same allocation, increasing the
+  /// strong reference count.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new(5);
+  /// let owned = Rc::borrow_rc(&five).to_owned();
+  ///
+  /// assert!(Rc::ptr_eq(&five, &owned));
+  /// assert_eq!(Rc::strong_count(&five), 2);
+  /// ```
+  #[inline]
+  pub fn to_owned(self) -> Rc<T> {
+    // The borrowed pointer owns one strong reference already; clone it
+    // without giving that reference back.
+    let this = std::mem::ManuallyDrop::new(Rc::from_inner(self.ptr));
+    Rc::clone(&this)
+  }
+
+  /// Returns a reference to the value that lives as long as the original
+  /// borrow of the [`Rc`], rather than as long as this handle.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rc;
+  ///
+  /// let five = Rc::new(5);
+  /// let value: &i32 = Rc::borrow_rc(&five).get();
+  ///
+  /// assert_eq!(*value, 5);
+  /// ```
+  #[inline]
+  pub fn get(self) -> &'a T {
+    // SAFETY: The allocation is kept alive by the `Rc` borrowed for `'a`.
+    unsafe { &(*self.ptr.as_ptr()).value }
+  }
+
+  /// Gets the number of strong (`Rc`) pointers to this allocation.
+  #[inline]
+  pub fn strong_count(this: &Self) -> usize {
+    Rc::strong_count(&std::mem::ManuallyDrop::new(Rc::from_inner(this.ptr)))
+  }
+
+  /// Creates a new [`Weak`] pointer to this allocation, without going
+  /// through an owned [`Rc`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Rc, RcBorrow};
+  ///
+  /// let five = Rc::new(5);
+  /// let weak = RcBorrow::downgrade(&Rc::borrow_rc(&five));
+  ///
+  /// assert_eq!(Rc::strong_count(&five), 1);
+  /// assert_eq!(weak.upgrade().as_deref(), Some(&5));
+  /// ```
+  #[inline]
+  pub fn downgrade(this: &Self) -> Weak<T> {
+    Rc::downgrade(&std::mem::ManuallyDrop::new(Rc::from_inner(this.ptr)))
+  }
+
+  /// Returns `true` if the two handles point to the same allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Rc, RcBorrow};
+  ///
+  /// let five = Rc::new(5);
+  /// let other_five = Rc::new(5);
+  ///
+  /// assert!(RcBorrow::ptr_eq(&Rc::borrow_rc(&five), &Rc::borrow_rc(&five)));
+  /// assert!(!RcBorrow::ptr_eq(&Rc::borrow_rc(&five), &Rc::borrow_rc(&other_five)));
+  /// ```
+  #[inline]
+  pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+    this.ptr.as_ptr().cast::<()>() == other.ptr.as_ptr().cast::<()>()
+  }
+}
+
+impl<T: ?Sized> Clone for RcBorrow<'_, T> {
+  #[inline]
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<T: ?Sized> Copy for RcBorrow<'_, T> {}
+
+impl<T: ?Sized> std::ops::Deref for RcBorrow<'_, T> {
+  type Target = T;
+
+  #[inline]
+  fn deref(&self) -> &T {
+    self.get()
+  }
+}
+
+impl<'a, T: ?Sized> From<&'a Rc<T>> for RcBorrow<'a, T> {
+  #[inline]
+  fn from(rc: &'a Rc<T>) -> Self {
+    Rc::borrow_rc(rc)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display for RcBorrow<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for RcBorrow<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized> std::fmt::Pointer for RcBorrow<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Pointer::fmt(&(&**self as *const T), f)
+  }
+}
+
+/// Frees the memory backing an `RcBox` without dropping its value.
+///
+/// # Safety
+///
+/// `ptr` must have been allocated by `alloc`, and the value must already be
+/// dropped.
+unsafe fn dealloc_box<T: ?Sized, C, A: Allocator>(
+  ptr: std::ptr::NonNull<RcBox<T, C>>,
+  alloc: &A,
+) {
+  #[cfg(feature = "leak-debug")]
+  crate::leak_debug::unregister(ptr.as_ptr().cast::<()>());
+  let layout = std::alloc::Layout::for_value(ptr.as_ref());
+  #[cfg(feature = "stats")]
+  crate::stats::record_dealloc(
+    crate::stats::Kind::Rc,
+    std::any::type_name::<T>(),
+    layout.size(),
+  );
+  #[cfg(feature = "alloc-observer")]
+  crate::observer::notify(
+    crate::observer::EventKind::Deallocate,
+    std::any::type_name::<T>(),
+    layout.size(),
+    ptr.as_ptr().cast::<()>(),
+  );
+  alloc.deallocate(ptr.cast(), layout);
+}
+
+/// A finalizer from [`Rc::new_with_finalizer`], called with a pointer to the
+/// value.
+type Finalizer = Box<dyn FnOnce(*mut ())>;
+
+std::thread_local! {
+  /// The finalizers of the `Rc` allocations on this thread, by address.
+  static FINALIZERS: std::cell::RefCell<
+    std::collections::BTreeMap<usize, Finalizer>,
+  > = const { std::cell::RefCell::new(std::collections::BTreeMap::new()) };
+}
+
+fn set_finalizer<T: ?Sized, C: Counter>(
+  inner: &RcBox<T, C>,
+  finalizer: Finalizer,
+) {
+  inner.set_has_finalizer(true);
+  let address = std::ptr::from_ref(inner).addr();
+  let _ = FINALIZERS
+    .try_with(|finalizers| finalizers.borrow_mut().insert(address, finalizer));
+}
+
+/// Removes the finalizer of the allocation, if it has one.
+///
+/// Only allocations flagged by [`set_finalizer`] look up the table.
+#[inline]
+fn take_finalizer<T: ?Sized, C: Counter>(
+  inner: &RcBox<T, C>,
+) -> Option<Finalizer> {
+  if !inner.has_finalizer() {
+    return None;
+  }
+  inner.set_has_finalizer(false);
+  let address = std::ptr::from_ref(inner).addr();
+  FINALIZERS
+    .try_with(|finalizers| finalizers.borrow_mut().remove(&address))
+    .ok()
+    .flatten()
+}
+
+/// Registers a new `RcBox` with the `leak-debug` registry, the `stats`
+/// counters and the allocation observer, if they're enabled.
+#[inline]
+fn track<T: ?Sized, C: Counter>(ptr: std::ptr::NonNull<RcBox<T, C>>) {
+  #[cfg(feature = "leak-debug")]
+  crate::leak_debug::register(
+    ptr.as_ptr().cast::<()>(),
+    std::any::type_name::<T>(),
+    read_counts::<C>,
+  );
+  #[cfg(feature = "stats")]
+  crate::stats::record_alloc(
+    crate::stats::Kind::Rc,
+    std::any::type_name::<T>(),
+    // SAFETY: `ptr` points to a fresh allocation with its metadata set.
+    unsafe { std::mem::size_of_val(ptr.as_ref()) },
+  );
+  #[cfg(feature = "alloc-observer")]
+  crate::observer::notify(
+    crate::observer::EventKind::Allocate,
+    std::any::type_name::<T>(),
+    // SAFETY: As above.
+    unsafe { std::mem::size_of_val(ptr.as_ref()) },
+    ptr.as_ptr().cast::<()>(),
+  );
+  #[cfg(not(any(
+    feature = "leak-debug",
+    feature = "stats",
+    feature = "alloc-observer"
+  )))]
+  let _ = ptr;
+}
+
+/// Tells the allocation observer, if it's enabled, that the last strong
+/// pointer to the `RcBox` at `ptr` is gone.
+#[inline]
+fn release<T: ?Sized, C>(ptr: std::ptr::NonNull<RcBox<T, C>>) {
+  #[cfg(feature = "alloc-observer")]
+  crate::observer::notify(
+    crate::observer::EventKind::Release,
+    std::any::type_name::<T>(),
+    // SAFETY: The memory of the box is still allocated.
+    unsafe { std::mem::size_of_val(ptr.as_ref()) },
+    ptr.as_ptr().cast::<()>(),
+  );
+  #[cfg(not(feature = "alloc-observer"))]
+  let _ = ptr;
+}
+
+/// Reads the counts of the `RcBox` at `ptr`, for the `leak-debug` registry.
+///
+/// # Safety
+///
+/// `ptr` must point to a live `RcBox` with `C` counts.
+#[cfg(feature = "leak-debug")]
+unsafe fn read_counts<C: Counter>(ptr: *const ()) -> (usize, usize) {
+  // The counts come first whatever the value type, as `RcBox` is `repr(C)`.
+  let header = &*(ptr as *const RcBox<(), C>);
+  (header.strong(), header.weak())
+}
+
+/// Returns the layout of the allocation behind an `Rc<T>`.
+pub(crate) fn box_layout<T>() -> std::alloc::Layout {
+  std::alloc::Layout::new::<RcBox<T>>()
+}
+
+/// Gets the offset within an `RcBox` for the payload behind a pointer.
+fn data_offset<T>() -> usize {
+  // The header is `repr(C)`, so the payload sits right after the two counts,
+  // padded to the alignment of `T`.
+  std::alloc::Layout::new::<RcBox<()>>()
+    .extend(std::alloc::Layout::new::<T>())
+    .expect("RcBox layout overflow")
+    .1
+}
+
+/// Whether `ptr` is the sentinel used by `Weak::new`.
+fn is_dangling<T: ?Sized>(ptr: std::ptr::NonNull<T>) -> bool {
+  ptr.as_ptr().cast::<()>().addr() == usize::MAX
+}
+
+/// Helper type to allow accessing the reference counts without
+/// making any assertions about the data field.
+struct WeakInner<'a, C = usize> {
+  weak: &'a Cell<C>,
+  strong: &'a Cell<C>,
+}
+
+/// Shared access to the reference counts of an `RcBox`.
+trait RcBoxPtr<C: Counter> {
+  fn strong_ref(&self) -> &Cell<C>;
+  fn weak_ref(&self) -> &Cell<C>;
+
+  #[inline]
+  fn strong(&self) -> usize {
+    self.strong_ref().get().to_usize()
+  }
+
+  #[inline]
+  fn inc_strong(&self) {
+    let strong = self.strong();
+
+    // We want to abort on overflow instead of dropping the value.
+    // The reference count will never be zero when this is called;
+    // nevertheless, we insert an abort here to hint LLVM at
+    // an otherwise missed optimization.
+    if strong == 0 || strong == C::MAX {
+      crate::alloc::abort_on_overflow();
+    }
+    self.strong_ref().set(C::from_usize(strong + 1));
+  }
+
+  #[inline]
+  fn dec_strong(&self) {
+    self.strong_ref().set(C::from_usize(self.strong() - 1));
+  }
+
+  #[inline]
+  fn weak(&self) -> usize {
+    self.weak_ref().get().to_usize() & !finalizer_flag::<C>()
+  }
+
+  /// Sets the weak count, keeping the finalizer flag.
+  #[inline]
+  fn set_weak(&self, weak: usize) {
+    let flag = self.weak_ref().get().to_usize() & finalizer_flag::<C>();
+    self.weak_ref().set(C::from_usize(weak | flag));
+  }
+
+  #[inline]
+  fn inc_weak(&self) {
+    let weak = self.weak();
+
+    // See `inc_strong` for why we abort. The top bit holds the finalizer
+    // flag, so the count has one bit less to grow into.
+    if weak == 0 || weak == C::MAX >> 1 {
+      crate::alloc::abort_on_overflow();
+    }
+    self.set_weak(weak + 1);
+  }
+
+  #[inline]
+  fn dec_weak(&self) {
+    self.set_weak(self.weak() - 1);
+  }
+
+  /// Whether the allocation has a finalizer from
+  /// [`Rc::new_with_finalizer`].
+  #[inline]
+  fn has_finalizer(&self) -> bool {
+    self.weak_ref().get().to_usize() & finalizer_flag::<C>() != 0
+  }
+
+  #[inline]
+  fn set_has_finalizer(&self, has_finalizer: bool) {
+    let flag = if has_finalizer {
+      finalizer_flag::<C>()
+    } else {
+      0
+    };
+    self.weak_ref().set(C::from_usize(self.weak() | flag));
+  }
+}
+
+/// The top bit of the weak count, which is set while the allocation has an
+/// entry in the finalizer table. It keeps `Rc`s without a finalizer, which
+/// is nearly all of them, from looking the table up when they're dropped.
+#[inline]
+fn finalizer_flag<C: Counter>() -> usize {
+  (C::MAX >> 1) + 1
+}
+
+impl<T: ?Sized, C: Counter> RcBoxPtr<C> for RcBox<T, C> {
+  #[inline]
+  fn strong_ref(&self) -> &Cell<C> {
+    &self.strong
+  }
+
+  #[inline]
+  fn weak_ref(&self) -> &Cell<C> {
+    &self.weak
+  }
+}
+
+impl<C: Counter> RcBoxPtr<C> for WeakInner<'_, C> {
+  #[inline]
+  fn strong_ref(&self) -> &Cell<C> {
+    self.strong
+  }
+
+  #[inline]
+  fn weak_ref(&self) -> &Cell<C> {
+    self.weak
+  }
+}
+
+impl<T: ?Sized, C: Counter, A: Allocator> RcBoxPtr<C> for Rc<T, C, A> {
+  #[inline]
+  fn strong_ref(&self) -> &Cell<C> {
+    &self.inner().strong
+  }
+
+  #[inline]
+  fn weak_ref(&self) -> &Cell<C> {
+    &self.inner().weak
+  }
+}
+
+impl<T: ?Sized, C: Counter, A: Allocator> Rc<T, C, A> {
+  #[inline]
+  fn inner(&self) -> &RcBox<T, C> {
+    // SAFETY: While this `Rc` is alive we're guaranteed that the inner
+    // pointer is valid.
+    unsafe { self.ptr.as_ref() }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn new() {
+    let five = Rc::new(5);
+    assert_eq!(*five, 5);
+  }
+
+  #[test]
+  fn clone() {
+    let five = Rc::new(5);
+    let also_five = Rc::clone(&five);
+
+    assert_eq!(Rc::strong_count(&five), 2);
+    assert!(Rc::ptr_eq(&five, &also_five));
+
+    drop(also_five);
+    assert_eq!(Rc::strong_count(&five), 1);
+  }
+
+  #[test]
+  fn downgrade_upgrade() {
+    let five = Rc::new(5);
+    let weak_five = Rc::downgrade(&five);
+
+    assert_eq!(Rc::weak_count(&five), 1);
+    assert_eq!(weak_five.strong_count(), 1);
+    assert_eq!(*weak_five.upgrade().unwrap(), 5);
+
+    drop(five);
+    assert!(weak_five.upgrade().is_none());
+    assert_eq!(weak_five.strong_count(), 0);
+    assert_eq!(weak_five.weak_count(), 0);
+  }
+
+  #[test]
+  fn is_unique_counts_strong_and_weak() {
+    let mut five = Rc::new(5);
+    assert!(Rc::is_unique(&five));
+    assert_eq!(Rc::weak_count(&five), 0);
+
+    let weak_five = Rc::downgrade(&five);
+    assert!(!Rc::is_unique(&five));
+    assert!(Rc::get_mut(&mut five).is_none());
+    drop(weak_five);
+
+    let also_five = Rc::clone(&five);
+    assert!(!Rc::is_unique(&also_five));
+    drop(also_five);
+    assert!(Rc::is_unique(&five));
+    assert!(Rc::get_mut(&mut five).is_some());
+  }
+
+  #[test]
+  fn weak_new() {
+    let empty: Weak<i64> = Weak::new();
+
+    assert!(empty.upgrade().is_none());
+    assert_eq!(empty.strong_count(), 0);
+    assert!(empty.clone().ptr_eq(&empty));
+  }
+
+  #[test]
+  fn get_mut() {
+    let mut x = Rc::new(3);
+    *Rc::get_mut(&mut x).unwrap() = 4;
+    assert_eq!(*x, 4);
+
+    let weak = Rc::downgrade(&x);
+    assert!(Rc::get_mut(&mut x).is_none());
+    drop(weak);
+    assert!(Rc::get_mut(&mut x).is_some());
+  }
+
+  #[test]
+  fn try_unwrap() {
+    let x = Rc::new(3);
+    assert_eq!(Rc::try_unwrap(x).ok(), Some(3));
+
+    let x = Rc::new(4);
+    let y = Rc::clone(&x);
+    let x = Rc::try_unwrap(x).unwrap_err();
+    drop(y);
+
+    let weak = Rc::downgrade(&x);
+    assert_eq!(Rc::into_inner(x), Some(4));
+    assert!(weak.upgrade().is_none());
+  }
+
+  #[test]
+  fn make_mut() {
+    let mut data = Rc::new(5);
+    let other = Rc::clone(&data);
+
+    *Rc::make_mut(&mut data) += 1;
+    assert_eq!((*data, *other), (6, 5));
+
+    let weak = Rc::downgrade(&data);
+    *Rc::make_mut(&mut data) += 1;
+    assert_eq!(*data, 7);
+    assert!(weak.upgrade().is_none());
+    assert_eq!(Rc::weak_count(&data), 0);
+  }
+
+  #[test]
+  fn new_cyclic() {
+    struct Node {
+      me: Weak<Node>,
+    }
+
+    let node = Rc::new_cyclic(|me| {
+      assert!(me.upgrade().is_none());
+      Node { me: me.clone() }
+    });
+
+    assert_eq!(Rc::strong_count(&node), 1);
+    assert_eq!(Rc::weak_count(&node), 1);
+    assert!(Rc::ptr_eq(&node, &node.me.upgrade().unwrap()));
+  }
+
+  #[test]
+  fn upgrade_fails_while_the_value_drops() {
+    struct Node {
+      me: Weak<Node>,
+      seen: Rc<crate::Cell<Option<(bool, usize)>>>,
+    }
+
+    impl Drop for Node {
+      fn drop(&mut self) {
+        let upgraded = self.me.upgrade();
+        self
+          .seen
+          .set(Some((upgraded.is_some(), self.me.strong_count())));
+      }
+    }
+
+    let seen = Rc::new(crate::Cell::new(None));
+    let node = Rc::new_cyclic(|me| Node {
+      me: me.clone(),
+      seen: Rc::clone(&seen),
+    });
+    let weak = Rc::downgrade(&node);
+
+    drop(node);
+    assert_eq!(seen.get(), Some((false, 0)));
+    assert!(weak.upgrade().is_none());
+
+    // Taking the value out counts as dropping it too.
+    let five = Rc::new(5);
+    let weak = Rc::downgrade(&five);
+    assert_eq!(Rc::try_unwrap(five).ok(), Some(5));
+    assert!(weak.upgrade().is_none());
+  }
+
+  #[test]
+  fn into_raw_from_raw() {
+    #[repr(align(32))]
+    struct Aligned(u8);
+
+    let ptr = Rc::into_raw(Rc::new(Aligned(3)));
+    assert_eq!(ptr.addr() % 32, 0);
+
+    // SAFETY: `ptr` came from `into_raw` and is reclaimed once.
+    unsafe {
+      Rc::increment_strong_count(ptr);
+      let x = Rc::from_raw(ptr);
+      assert_eq!(Rc::strong_count(&x), 2);
+      Rc::decrement_strong_count(ptr);
+      assert_eq!(Rc::strong_count(&x), 1);
+      assert_eq!(x.0, 3);
+    }
+  }
+
+  #[test]
+  fn raw_sentinels_and_unsized_moves() {
+    let empty: Weak<[u8; 4]> = Weak::new();
+    assert!(empty.upgrade().is_none());
+    drop(empty.clone());
+
+    // Zero-sized values take the dangling path of the allocator.
+    let unit = Rc::new(());
+    assert_eq!(Rc::strong_count(&unit), 1);
+
+    let boxed: crate::Boxed<[u32]> = vec![1, 2, 3].into();
+    let slice: Rc<[u32]> = Rc::from(boxed);
+    assert_eq!(*slice, [1, 2, 3]);
+    assert_eq!(Rc::as_ptr(&slice).len(), 3);
+  }
+
+  #[test]
+  fn drops_value_once() {
+    let dropped = Cell::new(0);
+
+    struct Droppable<'a>(&'a Cell<i32>);
+    impl Drop for Droppable<'_> {
+      fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+      }
+    }
+
+    let rc = Rc::new(Droppable(&dropped));
+    let weak = Rc::downgrade(&rc);
+    let other = Rc::clone(&rc);
+
+    drop(rc);
+    assert_eq!(dropped.get(), 0);
+    drop(other);
+    assert_eq!(dropped.get(), 1);
+    drop(weak);
+    assert_eq!(dropped.get(), 1);
+  }
+
+  #[test]
+  fn drops_see_weak_parents_and_children() {
+    use crate::RefCell;
+
+    struct Node {
+      parent: Weak<RefCell<Node>>,
+      children: Vec<Rc<RefCell<Node>>>,
+      seen: Rc<RefCell<Vec<(usize, bool, usize)>>>,
+    }
+
+    impl Drop for Node {
+      fn drop(&mut self) {
+        // The children are dropped after this, so they are still alive.
+        let weak_child = self.children.first().map(Rc::downgrade);
+        self.seen.borrow_mut().push((
+          self.parent.strong_count(),
+          self.parent.upgrade().is_some(),
+          weak_child.map_or(0, |child| child.strong_count()),
+        ));
+      }
+    }
+
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let root = Rc::new_cyclic(|me| {
+      let child = Rc::new(RefCell::new(Node {
+        parent: me.clone(),
+        children: Vec::new(),
+        seen: Rc::clone(&seen),
+      }));
+      RefCell::new(Node {
+        parent: Weak::new(),
+        children: vec![child],
+        seen: Rc::clone(&seen),
+      })
+    });
+    let child = Rc::clone(&root.borrow().children[0]);
+    let parent = Rc::downgrade(&root);
+
+    drop(root);
+    // The root saw its child; the child outlives it and no longer upgrades
+    // its parent, whose memory the `Weak`s keep.
+    assert_eq!(*seen.borrow(), [(0, false, 2)]);
+    assert_eq!(parent.strong_count(), 0);
+    drop(child);
+    assert_eq!(*seen.borrow(), [(0, false, 2), (0, false, 0)]);
+  }
+
+  #[test]
+  fn unwind_safety_matches_std() {
+    use std::marker::PhantomData;
+    use std::panic::{RefUnwindSafe, UnwindSafe};
+
+    // `Probe::<T>::UNWIND_SAFE` resolves to the inherent constant when `T`
+    // has the trait, and to the default of `No` otherwise.
+    struct Probe<T: ?Sized>(PhantomData<T>);
+    trait No {
+      const UNWIND_SAFE: bool = false;
+      const REF_UNWIND_SAFE: bool = false;
+    }
+    impl<T: ?Sized> No for Probe<T> {}
+    impl<T: ?Sized + UnwindSafe> Probe<T> {
+      const UNWIND_SAFE: bool = true;
+    }
+    impl<T: ?Sized + RefUnwindSafe> Probe<T> {
+      const REF_UNWIND_SAFE: bool = true;
+    }
+    macro_rules! same_as_std {
+      ($($ours:ty => $std:ty),+ $(,)?) => {$(
+        assert_eq!(
+          (Probe::<$ours>::UNWIND_SAFE, Probe::<$ours>::REF_UNWIND_SAFE),
+          (Probe::<$std>::UNWIND_SAFE, Probe::<$std>::REF_UNWIND_SAFE),
+          "{}",
+          stringify!($ours),
+        );
+      )+};
+    }
+
+    same_as_std! {
+      crate::Cell<i32> => std::cell::Cell<i32>,
+      crate::Cell<&mut i32> => std::cell::Cell<&mut i32>,
+      crate::RefCell<i32> => std::cell::RefCell<i32>,
+      crate::Ref<'static, i32> => std::cell::Ref<'static, i32>,
+      crate::RefMut<'static, i32> => std::cell::RefMut<'static, i32>,
+      Rc<i32> => std::rc::Rc<i32>,
+      Rc<str> => std::rc::Rc<str>,
+      Rc<&mut i32> => std::rc::Rc<&mut i32>,
+      Rc<crate::Cell<i32>> => std::rc::Rc<std::cell::Cell<i32>>,
+      Weak<i32> => std::rc::Weak<i32>,
+      crate::sync::Arc<i32> => std::sync::Arc<i32>,
+      crate::sync::Arc<crate::Cell<i32>> => std::sync::Arc<std::cell::Cell<i32>>,
+      crate::sync::Weak<i32> => std::sync::Weak<i32>,
+    }
+    // Guard against both sides of the matrix being wrong the same way.
+    const { assert!(Probe::<Rc<i32>>::UNWIND_SAFE) };
+    const { assert!(!Probe::<crate::RefCell<i32>>::REF_UNWIND_SAFE) };
+  }
+
+  #[cfg(feature = "may-dangle")]
+  #[test]
+  fn drops_after_what_the_value_borrows() {
+    // Without `#[may_dangle]`, the drop checker rejects this: `rc` and
+    // `weak` are dropped after `value`, which they borrow.
+    let (rc, weak);
+    let value = String::from("gone first");
+    rc = Rc::new(&value);
+    weak = Rc::downgrade(&rc);
+    assert_eq!(**rc, "gone first");
+    assert!(weak.upgrade().is_some());
+  }
+
+  #[test]
+  fn borrow_rc() {
+    let five = Rc::new(5);
+    let borrowed = Rc::borrow_rc(&five);
+    let copy = borrowed;
+
+    assert_eq!(*borrowed + *copy, 10);
+    assert_eq!(RcBorrow::strong_count(&borrowed), 1);
+
+    let owned = copy.to_owned();
+    assert!(Rc::ptr_eq(&five, &owned));
+    assert_eq!(RcBorrow::strong_count(&borrowed), 2);
+
+    drop(owned);
+    assert_eq!(Rc::strong_count(&five), 1);
+
+    let weak = RcBorrow::downgrade(&borrowed);
+    assert_eq!(Rc::weak_count(&five), 1);
+    assert!(Rc::ptr_eq(&weak.upgrade().unwrap(), &five));
+  }
+
+  #[test]
+  fn unique_rc_weak_before_sharing() {
+    let mut unique = UniqueRc::new(vec![1]);
+    let weak = UniqueRc::downgrade(&unique);
+    unique.push(2);
+    assert!(weak.upgrade().is_none());
+
+    let rc = UniqueRc::into_rc(unique);
+    assert_eq!(Rc::strong_count(&rc), 1);
+    assert_eq!(Rc::weak_count(&rc), 1);
+    assert_eq!(*weak.upgrade().unwrap(), [1, 2]);
+  }
+
+  #[test]
+  fn unique_rc_dropped_unshared() {
+    let unique = UniqueRc::new(String::from("gone"));
+    let weak = UniqueRc::downgrade(&unique);
+    drop(unique);
+    assert!(weak.upgrade().is_none());
+    assert_eq!(weak.strong_count(), 0);
+  }
+
+  #[test]
+  fn iterates_shared_slices() {
+    let slice: Rc<[String]> = Rc::from(crate::Boxed::from(vec![
+      String::from("a"),
+      String::from("b"),
+      String::from("c"),
+    ]));
+    let mut joined = String::new();
+    for s in &slice {
+      joined.push_str(s);
+    }
+    assert_eq!(joined, "abc");
+
+    let mut cloned = Rc::iter_cloned(&slice);
+    assert_eq!(cloned.len(), 3);
+    assert_eq!(cloned.next_back().as_deref(), Some("c"));
+    drop(slice);
+    assert_eq!(cloned.collect::<Vec<_>>(), ["a", "b"]);
+
+    let vec = Rc::new(vec![1, 2, 3]);
+    assert_eq!((&vec).into_iter().sum::<i32>(), 6);
+  }
+
+  #[test]
+  fn collects_slices_with_wrong_size_hints() {
+    struct Lying<I>(I, usize);
+
+    impl<I: Iterator> Iterator for Lying<I> {
+      type Item = I::Item;
+
+      fn next(&mut self) -> Option<I::Item> {
+        self.0.next()
+      }
+
+      fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.1, Some(self.1))
+      }
+    }
+
+    let exact: Rc<[String]> = (0..3).map(|n| n.to_string()).collect();
+    assert_eq!(*exact, ["0", "1", "2"]);
+    let short: Rc<[i32]> = Lying(0..2, 5).collect();
+    assert_eq!(*short, [0, 1]);
+    let long: Rc<[i32]> = Lying(0..5, 2).collect();
+    assert_eq!(*long, [0, 1, 2, 3, 4]);
+    let empty: Rc<[()]> = std::iter::empty().collect();
+    assert!(empty.is_empty());
+  }
+
+  #[test]
+  fn collecting_drops_written_items_on_panic() {
+    let live = std::rc::Rc::new(());
+    let result = std::panic::catch_unwind(|| {
+      (0..4)
+        .map(|n| {
+          assert!(n < 2, "third item");
+          std::rc::Rc::clone(&live)
+        })
+        .collect::<Rc<[_]>>()
+    });
+
+    assert!(result.is_err());
+    assert_eq!(std::rc::Rc::strong_count(&live), 1);
+  }
+
+  #[test]
+  fn cycle_builder_shares_the_implicit_weak() {
+    let mut builder = CycleBuilder::new();
+    let weak = builder.reserve();
+    let _unused = builder.reserve();
+    assert_eq!(weak.weak_count(), 0);
+
+    let values = builder.finish(|i| i);
+    assert_eq!(Rc::strong_count(&values[0]), 1);
+    assert_eq!(Rc::weak_count(&values[0]), 1);
+    assert_eq!(Rc::weak_count(&values[1]), 1);
+
+    let unfinished = CycleBuilder::<String>::default();
+    drop((unfinished, values));
+    assert!(weak.upgrade().is_none());
+  }
+
+  #[test]
+  fn cycle_builder_drops_made_values_on_panic() {
+    struct Droppable<'a>(&'a Cell<i32>);
+    impl Drop for Droppable<'_> {
+      fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+      }
+    }
+
+    let dropped = Cell::new(0);
+    let mut builder = CycleBuilder::new();
+    let weaks: Vec<_> = (0..3).map(|_| builder.reserve()).collect();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      builder.finish(|i| {
+        assert!(i < 2, "no third value");
+        Droppable(&dropped)
+      })
+    }));
+
+    assert!(result.is_err());
+    assert_eq!(dropped.get(), 2);
+    assert!(weaks.iter().all(|weak| weak.upgrade().is_none()));
+  }
+
+  #[test]
+  fn small_rc_counts() {
+    assert_eq!(
+      std::mem::size_of::<RcBox<u64, u32>>() + std::mem::size_of::<u32>() * 2,
+      std::mem::size_of::<RcBox<u64>>()
+    );
+
+    let five = SmallRc::from(5);
+    let weak: SmallWeak<i32> = Rc::downgrade(&five);
+    let also_five = Rc::clone(&five);
+    assert_eq!(Rc::strong_count(&five), 2);
+    assert_eq!(Rc::weak_count(&five), 1);
+
+    drop(also_five);
+    assert_eq!(Rc::try_unwrap(five).ok(), Some(5));
+    assert!(weak.upgrade().is_none());
+  }
+
+  #[test]
+  fn allocation_size_counts_the_header() {
+    let rc = Rc::new(7u32);
+    let small = SmallRc::from(7u32);
+    assert_eq!(Rc::allocation_size(&rc), box_layout::<u32>().size());
+    // Both `u32` counts and the value, with no padding.
+    assert_eq!(SmallRc::allocation_size(&small), 12);
+  }
+
+  #[test]
+  fn errors_flow_through_question_mark() {
+    #[derive(Debug)]
+    struct Wrapped(std::fmt::Error);
+    impl std::fmt::Display for Wrapped {
+      fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "wrapped")
+      }
+    }
+    impl std::error::Error for Wrapped {
+      fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+      }
+    }
+
+    let shared = Rc::new(Wrapped(std::fmt::Error));
+    let fails =
+      || -> Result<(), Box<dyn std::error::Error>> { Err(Rc::clone(&shared))? };
+
+    let err = fails().unwrap_err();
+    assert_eq!(err.to_string(), "wrapped");
+    assert!(err.source().unwrap().is::<std::fmt::Error>());
+    drop(err);
+    assert_eq!(Rc::strong_count(&shared), 1);
+  }
+
+  #[test]
+  fn finalizer_runs_once_before_drop() {
+    struct Noisy(Rc<crate::RefCell<Vec<&'static str>>>);
+    impl Drop for Noisy {
+      fn drop(&mut self) {
+        self.0.borrow_mut().push("drop");
+      }
+    }
+
+    let log = Rc::new(crate::RefCell::new(Vec::new()));
+
+    let value = Rc::new_with_finalizer(Noisy(Rc::clone(&log)), |noisy| {
+      noisy.0.borrow_mut().push("finalize")
+    });
+    let weak = Rc::downgrade(&value);
+    let clone = Rc::clone(&value);
+    // The finalizer flag shares a word with the weak count.
+    assert!(value.inner().has_finalizer());
+    assert_eq!(Rc::weak_count(&value), 1);
+    drop(value);
+    assert!(log.borrow().is_empty());
+    drop(clone);
+    assert_eq!(*log.borrow(), ["finalize", "drop"]);
+    assert!(weak.upgrade().is_none());
+
+    let unwrapped = Rc::new_with_finalizer(Noisy(Rc::clone(&log)), |_| {
+      panic!("finalizer of an unwrapped value")
+    });
+    drop(Rc::try_unwrap(unwrapped).ok().unwrap());
+    assert_eq!(*log.borrow(), ["finalize", "drop", "drop"]);
+  }
+}
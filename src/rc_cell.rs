@@ -0,0 +1,332 @@
+//! A shared, mutable value: [`Rc<RefCell<T>>`][Rc] in one type.
+//!
+//! Sharing a value that several owners can mutate is what the [crate documentation](crate)
+//! recommends `Rc<RefCell<T>>` for, and that nesting shows up at every construction, clone and
+//! borrow. [`RcCell<T>`][RcCell] is a newtype over it with the same behaviour and a flatter API:
+//! [`borrow`] and [`borrow_mut`] reach the value directly, [`with`] and [`with_mut`] run a closure
+//! on it without holding a guard, and [`downgrade`] gives a [`WeakCell<T>`][WeakCell] to break
+//! cycles.
+//!
+//! ```
+//! use pointer::RcCell;
+//!
+//! let counter = RcCell::new(0);
+//! let handle = RcCell::clone(&counter);
+//!
+//! handle.with_mut(|n| *n += 1);
+//! *counter.borrow_mut() += 1;
+//! assert_eq!(handle.with(|n| *n), 2);
+//! ```
+//!
+//! [`borrow`]: RcCell::borrow
+//! [`borrow_mut`]: RcCell::borrow_mut
+//! [`with`]: RcCell::with
+//! [`with_mut`]: RcCell::with_mut
+//! [`downgrade`]: RcCell::downgrade
+
+use crate::refcell::{BorrowError, Ref, RefCell, RefMut};
+use crate::{Rc, Weak};
+
+/// A reference-counted, dynamically borrowed value.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct RcCell<T> {
+  rc: Rc<RefCell<T>>,
+}
+
+impl<T> RcCell<T> {
+  /// Creates a new `RcCell` containing `value`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcCell;
+  ///
+  /// let cell = RcCell::new(5);
+  /// ```
+  pub fn new(value: T) -> RcCell<T> {
+    RcCell {
+      rc: Rc::new(RefCell::new(value)),
+    }
+  }
+
+  /// Immutably borrows the value.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently mutably borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcCell;
+  ///
+  /// let cell = RcCell::new(vec![1, 2]);
+  /// assert_eq!(cell.borrow().len(), 2);
+  /// ```
+  #[track_caller]
+  pub fn borrow(&self) -> Ref<'_, T> {
+    self.rc.borrow()
+  }
+
+  /// Immutably borrows the value, returning an error if it is currently
+  /// mutably borrowed.
+  pub fn try_borrow(&self) -> Result<Ref<'_, T>, BorrowError> {
+    self.rc.try_borrow()
+  }
+
+  /// Mutably borrows the value.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcCell;
+  ///
+  /// let cell = RcCell::new(vec![1, 2]);
+  /// cell.borrow_mut().push(3);
+  /// assert_eq!(*cell.borrow(), [1, 2, 3]);
+  /// ```
+  #[track_caller]
+  pub fn borrow_mut(&self) -> RefMut<'_, T> {
+    self.rc.borrow_mut()
+  }
+
+  /// Mutably borrows the value, returning an error if it is currently
+  /// borrowed.
+  pub fn try_borrow_mut(&self) -> Result<RefMut<'_, T>, BorrowError> {
+    self.rc.try_borrow_mut()
+  }
+
+  /// Calls `f` with a shared borrow of the value.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently mutably borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcCell;
+  ///
+  /// let cell = RcCell::new(String::from("hello"));
+  /// assert_eq!(cell.with(|s| s.len()), 5);
+  /// ```
+  #[track_caller]
+  pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+    f(&self.borrow())
+  }
+
+  /// Calls `f` with a mutable borrow of the value.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcCell;
+  ///
+  /// let cell = RcCell::new(String::from("hello"));
+  /// cell.with_mut(|s| s.push('!'));
+  /// assert_eq!(*cell.borrow(), "hello!");
+  /// ```
+  #[track_caller]
+  pub fn with_mut<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+    f(&mut self.borrow_mut())
+  }
+
+  /// Creates a new [`WeakCell`] pointer to this value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcCell;
+  ///
+  /// let cell = RcCell::new(5);
+  /// let weak = RcCell::downgrade(&cell);
+  /// assert!(weak.upgrade().is_some());
+  /// ```
+  pub fn downgrade(this: &Self) -> WeakCell<T> {
+    WeakCell {
+      weak: Rc::downgrade(&this.rc),
+    }
+  }
+
+  /// Returns `true` if the two `RcCell`s point to the same allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcCell;
+  ///
+  /// let five = RcCell::new(5);
+  /// let same_five = RcCell::clone(&five);
+  /// let other_five = RcCell::new(5);
+  ///
+  /// assert!(RcCell::ptr_eq(&five, &same_five));
+  /// assert!(!RcCell::ptr_eq(&five, &other_five));
+  /// ```
+  pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+    Rc::ptr_eq(&this.rc, &other.rc)
+  }
+
+  /// Gets the number of `RcCell`s pointing to this value.
+  pub fn strong_count(this: &Self) -> usize {
+    Rc::strong_count(&this.rc)
+  }
+
+  /// Returns the underlying `Rc<RefCell<T>>`.
+  pub fn into_rc(this: Self) -> Rc<RefCell<T>> {
+    this.rc
+  }
+}
+
+impl<T> Clone for RcCell<T> {
+  /// Makes another pointer to the same value.
+  fn clone(&self) -> RcCell<T> {
+    RcCell {
+      rc: Rc::clone(&self.rc),
+    }
+  }
+}
+
+impl<T: Default> Default for RcCell<T> {
+  fn default() -> RcCell<T> {
+    RcCell::new(Default::default())
+  }
+}
+
+impl<T> From<T> for RcCell<T> {
+  fn from(value: T) -> RcCell<T> {
+    RcCell::new(value)
+  }
+}
+
+impl<T> From<Rc<RefCell<T>>> for RcCell<T> {
+  fn from(rc: Rc<RefCell<T>>) -> RcCell<T> {
+    RcCell { rc }
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for RcCell<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.try_borrow() {
+      Ok(value) => f.debug_struct("RcCell").field("value", &*value).finish(),
+      Err(_) => f.write_str("RcCell { <borrowed> }"),
+    }
+  }
+}
+
+/// A weak pointer to the value of an [`RcCell`].
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct WeakCell<T> {
+  weak: Weak<RefCell<T>>,
+}
+
+impl<T> WeakCell<T> {
+  /// Constructs a `WeakCell` that doesn't point to any value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::WeakCell;
+  ///
+  /// let empty: WeakCell<i32> = WeakCell::new();
+  /// assert!(empty.upgrade().is_none());
+  /// ```
+  pub fn new() -> WeakCell<T> {
+    WeakCell { weak: Weak::new() }
+  }
+
+  /// Attempts to upgrade to an [`RcCell`], returning [`None`] if the value
+  /// has since been dropped.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcCell;
+  ///
+  /// let cell = RcCell::new(5);
+  /// let weak = RcCell::downgrade(&cell);
+  /// *weak.upgrade().unwrap().borrow_mut() += 1;
+  /// assert_eq!(*cell.borrow(), 6);
+  ///
+  /// drop(cell);
+  /// assert!(weak.upgrade().is_none());
+  /// ```
+  pub fn upgrade(&self) -> Option<RcCell<T>> {
+    self.weak.upgrade().map(|rc| RcCell { rc })
+  }
+
+  /// Returns `true` if the two `WeakCell`s point to the same value, or if
+  /// neither points to any.
+  pub fn ptr_eq(&self, other: &Self) -> bool {
+    self.weak.ptr_eq(&other.weak)
+  }
+}
+
+impl<T> Clone for WeakCell<T> {
+  fn clone(&self) -> WeakCell<T> {
+    WeakCell {
+      weak: self.weak.clone(),
+    }
+  }
+}
+
+impl<T> Default for WeakCell<T> {
+  fn default() -> WeakCell<T> {
+    WeakCell::new()
+  }
+}
+
+impl<T> std::fmt::Debug for WeakCell<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "(WeakCell)")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parent_child_cycle() {
+    struct Node {
+      parent: WeakCell<Node>,
+      children: Vec<RcCell<Node>>,
+    }
+
+    let root = RcCell::new(Node {
+      parent: WeakCell::new(),
+      children: Vec::new(),
+    });
+    let child = RcCell::new(Node {
+      parent: RcCell::downgrade(&root),
+      children: Vec::new(),
+    });
+    root.with_mut(|node| node.children.push(RcCell::clone(&child)));
+
+    let parent = child.with(|node| node.parent.upgrade()).unwrap();
+    assert!(RcCell::ptr_eq(&parent, &root));
+    assert_eq!(RcCell::strong_count(&child), 2);
+
+    drop(parent);
+    drop(root);
+    assert!(child.borrow().parent.upgrade().is_none());
+    assert_eq!(RcCell::strong_count(&child), 1);
+  }
+
+  #[test]
+  fn debug_while_borrowed() {
+    let cell = RcCell::new(1);
+    assert_eq!(format!("{:?}", cell), "RcCell { value: 1 }");
+    let _guard = cell.borrow_mut();
+    assert_eq!(format!("{:?}", cell), "RcCell { <borrowed> }");
+  }
+}
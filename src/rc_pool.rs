@@ -0,0 +1,253 @@
+//! A pool that recycles the allocations of [`Rc`]s.
+//!
+//! Code that creates and drops shared values at a high rate spends much of its time in the global
+//! allocator. An [`RcPool<T>`][RcPool] keeps the memory of dropped [`PooledRc<T>`][PooledRc]s on a
+//! free list instead, and [`alloc`] takes from that list before it asks the global allocator for
+//! more. Once warmed up, creating and dropping a pooled `Rc` only moves a pointer on and off the
+//! free list.
+//!
+//! A `PooledRc<T>` is an [`Rc`] whose [allocator] is the pool, so it has the whole `Rc` API.
+//! Every `PooledRc` keeps the pool alive, and the idle memory is freed once the pool and all its
+//! pointers are gone, or earlier with [`shrink_to`].
+//!
+//! ```
+//! use pointer::{PooledRc, RcPool};
+//!
+//! let pool = RcPool::new();
+//! let particle = pool.alloc([0.5f32, 1.5]);
+//! let address = PooledRc::as_ptr(&particle);
+//! drop(particle);
+//! assert_eq!(pool.idle(), 1);
+//!
+//! // The next value goes into the same memory.
+//! let particle = pool.alloc([2.0, 3.0]);
+//! assert_eq!(PooledRc::as_ptr(&particle), address);
+//! assert_eq!(pool.idle(), 0);
+//! ```
+//!
+//! [`alloc`]: RcPool::alloc
+//! [allocator]: crate::alloc
+//! [`shrink_to`]: RcPool::shrink_to
+
+use crate::alloc::{AllocError, Allocator, Global};
+use crate::cell::Cell;
+use crate::Rc;
+use std::alloc::Layout;
+use std::ptr::NonNull;
+
+/// An [`Rc`] whose allocation comes from, and goes back to, an [`RcPool`].
+pub type PooledRc<T> = Rc<T, usize, RcPool<T>>;
+
+/// A free list of allocations for [`PooledRc<T>`][PooledRc].
+///
+/// Cloning an `RcPool` makes another handle to the same pool.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct RcPool<T> {
+  free: Rc<FreeList>,
+  phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+struct FreeList {
+  /// The layout of every block on the list.
+  layout: Layout,
+  blocks: Cell<Vec<NonNull<u8>>>,
+}
+
+impl FreeList {
+  fn with_blocks<R>(&self, f: impl FnOnce(&mut Vec<NonNull<u8>>) -> R) -> R {
+    let mut blocks = self.blocks.take();
+    let result = f(&mut blocks);
+    self.blocks.set(blocks);
+    result
+  }
+}
+
+impl Drop for FreeList {
+  fn drop(&mut self) {
+    for block in self.blocks.get_mut().drain(..) {
+      // SAFETY: Every block on the list came from the global allocator with
+      // this layout.
+      unsafe { Global.deallocate(block, self.layout) }
+    }
+  }
+}
+
+impl<T> RcPool<T> {
+  /// Constructs a new, empty pool.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcPool;
+  ///
+  /// let pool: RcPool<u32> = RcPool::new();
+  /// assert_eq!(pool.idle(), 0);
+  /// ```
+  pub fn new() -> RcPool<T> {
+    RcPool {
+      free: Rc::new(FreeList {
+        layout: crate::rc::box_layout::<T>(),
+        blocks: Cell::new(Vec::new()),
+      }),
+      phantom: std::marker::PhantomData,
+    }
+  }
+
+  /// Constructs a new pool with `capacity` allocations ready to use.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcPool;
+  ///
+  /// let pool: RcPool<u32> = RcPool::with_capacity(16);
+  /// assert_eq!(pool.idle(), 16);
+  /// ```
+  pub fn with_capacity(capacity: usize) -> RcPool<T> {
+    let pool = RcPool::new();
+    pool.reserve(capacity);
+    pool
+  }
+
+  /// Moves `value` into a pooled allocation, reusing an idle one if there is
+  /// any.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{PooledRc, RcPool};
+  ///
+  /// let pool = RcPool::new();
+  /// let five = pool.alloc(5);
+  /// let also_five = PooledRc::clone(&five);
+  /// assert_eq!(*also_five, 5);
+  /// ```
+  pub fn alloc(&self, value: T) -> PooledRc<T> {
+    Rc::new_in(value, self.clone())
+  }
+
+  /// Returns the number of idle allocations on the free list.
+  pub fn idle(&self) -> usize {
+    self.free.with_blocks(|blocks| blocks.len())
+  }
+
+  /// Allocates until there are at least `idle` idle allocations.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcPool;
+  ///
+  /// let pool: RcPool<u32> = RcPool::new();
+  /// pool.reserve(4);
+  /// assert_eq!(pool.idle(), 4);
+  /// ```
+  pub fn reserve(&self, idle: usize) {
+    let layout = self.free.layout;
+    self.free.with_blocks(|blocks| {
+      blocks.reserve(idle.saturating_sub(blocks.len()));
+      while blocks.len() < idle {
+        blocks.push(crate::alloc::allocate_or_abort(&Global, layout));
+      }
+    })
+  }
+
+  /// Frees idle allocations until at most `idle` are left.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcPool;
+  ///
+  /// let pool: RcPool<u32> = RcPool::with_capacity(8);
+  /// pool.shrink_to(2);
+  /// assert_eq!(pool.idle(), 2);
+  /// ```
+  pub fn shrink_to(&self, idle: usize) {
+    let layout = self.free.layout;
+    self.free.with_blocks(|blocks| {
+      for block in blocks.drain(idle.min(blocks.len())..) {
+        // SAFETY: Every block on the list came from the global allocator
+        // with this layout.
+        unsafe { Global.deallocate(block, layout) }
+      }
+    })
+  }
+}
+
+unsafe impl<T> Allocator for RcPool<T> {
+  fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+    if layout == self.free.layout {
+      if let Some(block) = self.free.with_blocks(|blocks| blocks.pop()) {
+        return Ok(block);
+      }
+    }
+    Global.allocate(layout)
+  }
+
+  unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+    if layout == self.free.layout {
+      self.free.with_blocks(|blocks| blocks.push(ptr));
+    } else {
+      Global.deallocate(ptr, layout)
+    }
+  }
+}
+
+impl<T> Clone for RcPool<T> {
+  /// Makes another handle to the same pool.
+  fn clone(&self) -> RcPool<T> {
+    RcPool {
+      free: Rc::clone(&self.free),
+      phantom: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<T> Default for RcPool<T> {
+  fn default() -> RcPool<T> {
+    RcPool::new()
+  }
+}
+
+impl<T> std::fmt::Debug for RcPool<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("RcPool")
+      .field("idle", &self.idle())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn weak_pointers_hold_the_allocation() {
+    let pool = RcPool::new();
+    let value = pool.alloc(String::from("a"));
+    let weak = PooledRc::downgrade(&value);
+
+    drop(value);
+    assert_eq!(pool.idle(), 0);
+    assert!(weak.upgrade().is_none());
+    drop(weak);
+    assert_eq!(pool.idle(), 1);
+  }
+
+  #[test]
+  fn pointers_outlive_their_pool_handle() {
+    let values: Vec<_> = {
+      let pool = RcPool::with_capacity(2);
+      (0..4).map(|n| pool.alloc(n)).collect()
+    };
+    assert_eq!(values.iter().map(|n| **n).sum::<i32>(), 6);
+
+    let pool = PooledRc::allocator(&values[0]).clone();
+    drop(values);
+    assert_eq!(pool.idle(), 4);
+    pool.shrink_to(1);
+    assert_eq!(pool.idle(), 1);
+  }
+}
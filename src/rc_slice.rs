@@ -0,0 +1,304 @@
+//! Owned subslices of a shared [`Rc<[T]>`][Rc].
+//!
+//! An [`RcSlice<T>`][RcSlice] is an `Rc<[T]>` together with a range into it. It derefs to just
+//! that range, and cutting it further with [`slice`] or [`split_at`] only bumps the reference count:
+//! every piece keeps the whole backing buffer alive and none of them copies it. That is the shape
+//! parsers and rope-like structures want, where tokens or chunks outlive the borrow of the input
+//! they came from.
+//!
+//! [`make_mut`] gives mutable access copy-on-write: in place while the piece is the only owner of
+//! the buffer, otherwise after copying out just its own range.
+//!
+//! ```
+//! use pointer::RcSlice;
+//!
+//! let line = RcSlice::from(b"key=value".to_vec());
+//! let (key, value) = line.split_at(3);
+//! let value = value.slice(1..);
+//!
+//! assert_eq!(&*key, b"key");
+//! assert_eq!(&*value, b"value");
+//! assert!(RcSlice::ptr_eq_buffer(&key, &value));
+//! ```
+//!
+//! [`slice`]: RcSlice::slice
+//! [`split_at`]: RcSlice::split_at
+//! [`make_mut`]: RcSlice::make_mut
+
+use crate::{Boxed, Rc};
+
+use std::ops::{Bound, RangeBounds};
+
+/// A shared, owned subslice of an `Rc<[T]>`.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct RcSlice<T> {
+  buf: Rc<[T]>,
+  start: usize,
+  end: usize,
+}
+
+impl<T> RcSlice<T> {
+  /// Creates an `RcSlice` covering all of `buf`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Boxed, Rc, RcSlice};
+  ///
+  /// let buf: Rc<[u8]> = Rc::from(Boxed::from(vec![1, 2, 3]));
+  /// let all = RcSlice::new(buf);
+  /// assert_eq!(*all, [1, 2, 3]);
+  /// ```
+  pub fn new(buf: Rc<[T]>) -> RcSlice<T> {
+    let end = buf.len();
+    RcSlice { buf, start: 0, end }
+  }
+
+  /// Returns a new `RcSlice` for `range`, relative to this one, sharing the
+  /// same buffer.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the range is out of bounds or its start is past its end.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcSlice;
+  ///
+  /// let nums = RcSlice::from(vec![1, 2, 3, 4]);
+  /// assert_eq!(*nums.slice(1..3), [2, 3]);
+  /// assert_eq!(*nums.slice(..=1), [1, 2]);
+  /// ```
+  #[track_caller]
+  pub fn slice(&self, range: impl RangeBounds<usize>) -> RcSlice<T> {
+    let start = match range.start_bound() {
+      Bound::Included(&n) => n,
+      Bound::Excluded(&n) => n.checked_add(1).expect("range start overflow"),
+      Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+      Bound::Included(&n) => n.checked_add(1).expect("range end overflow"),
+      Bound::Excluded(&n) => n,
+      Bound::Unbounded => self.len(),
+    };
+    assert!(
+      start <= end && end <= self.len(),
+      "range {}..{} out of bounds for RcSlice of length {}",
+      start,
+      end,
+      self.len()
+    );
+    RcSlice {
+      buf: Rc::clone(&self.buf),
+      start: self.start + start,
+      end: self.start + end,
+    }
+  }
+
+  /// Divides the slice into two at `mid`, both sharing the same buffer.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `mid > len`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcSlice;
+  ///
+  /// let nums = RcSlice::from(vec![1, 2, 3]);
+  /// let (left, right) = nums.split_at(1);
+  /// assert_eq!(*left, [1]);
+  /// assert_eq!(*right, [2, 3]);
+  /// ```
+  #[track_caller]
+  pub fn split_at(&self, mid: usize) -> (RcSlice<T>, RcSlice<T>) {
+    (self.slice(..mid), self.slice(mid..))
+  }
+
+  /// Returns an iterator over the slice.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcSlice;
+  ///
+  /// let nums = RcSlice::from(vec![1, 2, 3]).slice(1..);
+  /// assert_eq!(nums.iter().sum::<i32>(), 5);
+  /// ```
+  pub fn iter(&self) -> std::slice::Iter<'_, T> {
+    self.as_slice().iter()
+  }
+
+  /// Extracts a slice of the whole `RcSlice`.
+  pub fn as_slice(&self) -> &[T] {
+    &self.buf[self.start..self.end]
+  }
+
+  /// Returns the shared buffer this slice points into.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcSlice;
+  ///
+  /// let tail = RcSlice::from(vec![1, 2, 3]).slice(2..);
+  /// assert_eq!(**RcSlice::buffer(&tail), [1, 2, 3]);
+  /// ```
+  pub fn buffer(this: &Self) -> &Rc<[T]> {
+    &this.buf
+  }
+
+  /// Returns `true` if both slices point into the same buffer, whichever
+  /// parts of it they cover.
+  pub fn ptr_eq_buffer(this: &Self, other: &Self) -> bool {
+    // Compare the addresses only, not the slice metadata.
+    Rc::as_ptr(&this.buf) as *const () == Rc::as_ptr(&other.buf) as *const ()
+  }
+}
+
+impl<T: Clone> RcSlice<T> {
+  /// Makes a mutable reference into the given `RcSlice`.
+  ///
+  /// If the buffer is shared with other slices or `Rc`s, this slice's range
+  /// is first copied into a buffer of its own. Otherwise it is mutated in
+  /// place.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RcSlice;
+  ///
+  /// let nums = RcSlice::from(vec![1, 2, 3]);
+  /// let mut tail = nums.slice(1..);
+  ///
+  /// RcSlice::make_mut(&mut tail)[0] = 20;
+  /// assert_eq!(*tail, [20, 3]);
+  /// assert_eq!(*nums, [1, 2, 3]);
+  /// ```
+  pub fn make_mut(this: &mut Self) -> &mut [T] {
+    if Rc::get_mut(&mut this.buf).is_none() {
+      *this = RcSlice::from(this.as_slice().to_vec());
+    }
+    let (start, end) = (this.start, this.end);
+    &mut Rc::get_mut(&mut this.buf).unwrap()[start..end]
+  }
+}
+
+impl<T> Clone for RcSlice<T> {
+  fn clone(&self) -> RcSlice<T> {
+    RcSlice {
+      buf: Rc::clone(&self.buf),
+      start: self.start,
+      end: self.end,
+    }
+  }
+}
+
+impl<T> std::ops::Deref for RcSlice<T> {
+  type Target = [T];
+
+  fn deref(&self) -> &[T] {
+    self.as_slice()
+  }
+}
+
+impl<T> AsRef<[T]> for RcSlice<T> {
+  fn as_ref(&self) -> &[T] {
+    self
+  }
+}
+
+impl<T> std::borrow::Borrow<[T]> for RcSlice<T> {
+  fn borrow(&self) -> &[T] {
+    self
+  }
+}
+
+impl<T> Default for RcSlice<T> {
+  fn default() -> RcSlice<T> {
+    RcSlice::from(Vec::new())
+  }
+}
+
+impl<T> From<Rc<[T]>> for RcSlice<T> {
+  fn from(buf: Rc<[T]>) -> RcSlice<T> {
+    RcSlice::new(buf)
+  }
+}
+
+impl<T> From<Vec<T>> for RcSlice<T> {
+  fn from(v: Vec<T>) -> RcSlice<T> {
+    RcSlice::new(Rc::from(Boxed::from(v)))
+  }
+}
+
+impl<'a, T> IntoIterator for &'a RcSlice<T> {
+  type Item = &'a T;
+  type IntoIter = std::slice::Iter<'a, T>;
+
+  fn into_iter(self) -> std::slice::Iter<'a, T> {
+    self.iter()
+  }
+}
+
+impl<T: PartialEq> PartialEq for RcSlice<T> {
+  fn eq(&self, other: &RcSlice<T>) -> bool {
+    **self == **other
+  }
+}
+
+impl<T: Eq> Eq for RcSlice<T> {}
+
+impl<T: std::hash::Hash> std::hash::Hash for RcSlice<T> {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    (**self).hash(state)
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for RcSlice<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn nested_slices_share_buffer() {
+    let all = RcSlice::from((0..10).collect::<Vec<_>>());
+    let middle = all.slice(2..8);
+    let (left, right) = middle.split_at(3);
+
+    assert_eq!(*left, [2, 3, 4]);
+    assert_eq!(*right.slice(1..=1), [6]);
+    assert!(RcSlice::ptr_eq_buffer(&all, &right));
+    assert_eq!(Rc::strong_count(RcSlice::buffer(&all)), 4);
+    assert!(middle.slice(6..).is_empty());
+  }
+
+  #[test]
+  #[should_panic(expected = "out of bounds")]
+  fn slice_past_end() {
+    let nums = RcSlice::from(vec![1, 2, 3]).slice(1..);
+    let _ = nums.slice(..3);
+  }
+
+  #[test]
+  fn make_mut_copies_only_when_shared() {
+    let mut nums = RcSlice::from(vec![1, 2, 3]);
+    let ptr = Rc::as_ptr(RcSlice::buffer(&nums));
+    RcSlice::make_mut(&mut nums)[0] = 10;
+    assert_eq!(Rc::as_ptr(RcSlice::buffer(&nums)), ptr);
+
+    let mut tail = nums.slice(1..);
+    RcSlice::make_mut(&mut tail)[1] = 30;
+    assert_eq!(*tail, [2, 30]);
+    assert_eq!(RcSlice::buffer(&tail).len(), 2);
+    assert_eq!(*nums, [10, 2, 3]);
+  }
+}
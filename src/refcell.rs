@@ -7,20 +7,81 @@ pub struct RefCell<T> {
   /// Protected value that can be borrowed with dynamically checked rules.
   value: std::cell::UnsafeCell<T>,
   /// Borrow rulues for `value`.
-  state: Cell<Borrow>,
+  state: BorrowTracker,
 }
 
-/// `Borrow` represents the different states/rules which we can borrow [`RefCell`](struct.RefCell)
-#[derive(Copy, Clone, PartialEq)]
-enum Borrow {
-  /// Shared state with ref count - We have `n` borrows but *NO* exclusive borrow.
-  Shared(usize),
+/// The borrow flag of a [`BorrowTracker`]: the number of shared borrows if
+/// positive, [`WRITING`] for an exclusive borrow, or [`UNUSED`].
+///
+/// A single integer keeps each borrow and release to one load, one compare
+/// and one store.
+type BorrowFlag = isize;
+
+/// No borrows are given out.
+const UNUSED: BorrowFlag = 0;
+
+/// A *(SINGLE)* mutable borrow is given out.
+const WRITING: BorrowFlag = -1;
+
+/// The borrow state of a [`RefCell`](struct.RefCell).
+///
+/// It is kept apart from the value and isn't generic, so the borrow rules are
+/// compiled once rather than once for every `T` a `RefCell` holds.
+struct BorrowTracker {
+  flag: Cell<BorrowFlag>,
+}
+
+impl BorrowTracker {
+  const fn new() -> BorrowTracker {
+    BorrowTracker {
+      flag: Cell::new(UNUSED),
+    }
+  }
+
+  #[inline]
+  const fn is_unused(&self) -> bool {
+    self.flag.get() == UNUSED
+  }
+
+  /// Takes a shared borrow, unless there is an exclusive one.
+  #[inline]
+  fn borrow(&self) -> bool {
+    // This also fails, rather than wrapping around, once there are
+    // `isize::MAX` shared borrows.
+    let flag = self.flag.get().wrapping_add(1);
+    if flag > UNUSED {
+      self.flag.set(flag);
+      true
+    } else {
+      false
+    }
+  }
 
-  /// Unshared state - We don't have any borrows to `T` or haven't given out any yet.
-  UnShared,
+  /// Takes an exclusive borrow, unless there is any borrow.
+  #[inline]
+  fn borrow_mut(&self) -> bool {
+    if self.is_unused() {
+      self.flag.set(WRITING);
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Gives back a shared borrow.
+  #[inline]
+  fn release(&self) {
+    let flag = self.flag.get();
+    debug_assert!(flag > UNUSED);
+    self.flag.set(flag - 1);
+  }
 
-  /// Exclusive state - Giving out a *(SINGLE)* mutable borrow to `T`.
-  Exclusive,
+  /// Gives back the exclusive borrow.
+  #[inline]
+  fn release_mut(&self) {
+    debug_assert!(self.flag.get() == WRITING);
+    self.flag.set(UNUSED);
+  }
 }
 
 impl<T> RefCell<T> {
@@ -37,28 +98,30 @@ impl<T> RefCell<T> {
   pub const fn new(value: T) -> RefCell<T> {
     RefCell {
       value: std::cell::UnsafeCell::new(value),
-      state: Cell::new(Borrow::UnShared),
+      state: BorrowTracker::new(),
     }
   }
 
-  /// Consules the `RefCell`, returning the wrapped value.
-  ///
-  /// # Examples
-  ///
-  /// ```
-  /// use pointer::RefCell;
-  ///
-  /// let c  = RefCell::new(5);
-  ///
-  /// let five = c.into_inner();
-  /// ```
-  #[inline]
-  pub fn into_inner(self) -> T {
-    // Since this function takes `self` (the `RefCell`) by value, the
-    // compiler statically verifies that it is not currently borrowed.
-    // Therefore the following assertion is just a `debug_assert!`.
-    debug_assert!(self.state.get() == Borrow::UnShared);
-    self.value.into_inner()
+  nightly_const! {
+    /// Consules the `RefCell`, returning the wrapped value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pointer::RefCell;
+    ///
+    /// let c  = RefCell::new(5);
+    ///
+    /// let five = c.into_inner();
+    /// ```
+    #[inline]
+    pub fn into_inner(self) -> T {
+      // Since this function takes `self` (the `RefCell`) by value, the
+      // compiler statically verifies that it is not currently borrowed.
+      // Therefore the following assertion is just a `debug_assert!`.
+      debug_assert!(self.state.is_unused());
+      self.value.into_inner()
+    }
   }
 
   /// Replace the wrapped value with a new one, returning the old value, without deinitializing either one.
@@ -177,10 +240,13 @@ impl<T> RefCell<T> {
   /// let m = c.borrow_mut();
   /// let b = c.borrow(); // this causes a panic
   /// ```
+  #[inline]
+  #[track_caller]
   pub fn borrow(&self) -> Ref<'_, T> {
-    self
-      .try_borrow()
-      .unwrap_or_else(|_| panic!("{}", BorrowError))
+    match self.try_borrow() {
+      Ok(borrow) => borrow,
+      Err(_) => panic_already_mutably_borrowed(),
+    }
   }
 
   /// Immutably borrows the wrapped value, returning an error if the value is currently mutably borrowed.
@@ -206,22 +272,15 @@ impl<T> RefCell<T> {
   ///    assert!(c.try_borrow().is_ok());
   /// }
   /// ```
+  #[inline]
   pub fn try_borrow(&self) -> Result<Ref<'_, T>, BorrowError> {
     // Shared borrow.
-    match self.state.get() {
-      Borrow::UnShared => {
-        self.state.set(Borrow::Shared(1));
-        // SAFETY: No data reace when called from separate threads because `!Sync`.
-        // Also, `RefCell` guarantees no `&mut T`, so we can have as many `T` as we want.
-        Ok(Ref { cell: self })
-      }
-      Borrow::Shared(n) => {
-        self.state.set(Borrow::Shared(n + 1));
-        // SAFETY: No data reace when called from separate threads because `!Sync`.
-        // Also, `RefCell` guarantees no `&mut T`, so we can have as many `T` as we want.
-        Ok(Ref { cell: self })
-      }
-      Borrow::Exclusive => Err(BorrowError),
+    if self.state.borrow() {
+      // SAFETY: No data reace when called from separate threads because `!Sync`.
+      // Also, `RefCell` guarantees no `&mut T`, so we can have as many `T` as we want.
+      Ok(Ref::new(self))
+    } else {
+      Err(BorrowError)
     }
   }
 
@@ -258,22 +317,24 @@ impl<T> RefCell<T> {
   ///
   /// let b = c.borrow_mut();  //this causes a panic.
   /// ````
+  #[inline]
+  #[track_caller]
   pub fn borrow_mut(&self) -> RefMut<'_, T> {
-    self
-      .try_borrow_mut()
-      .unwrap_or_else(|_| panic!("{}", BorrowMutError))
+    match self.try_borrow_mut() {
+      Ok(borrow) => borrow,
+      Err(_) => panic_already_borrowed(),
+    }
   }
 
+  #[inline]
   pub fn try_borrow_mut(&self) -> Result<RefMut<'_, T>, BorrowError> {
     // We want exclusive access to modify T.
-    match self.state.get() {
-      Borrow::Exclusive | Borrow::Shared(_) => Err(BorrowError),
-      Borrow::UnShared => {
-        self.state.set(Borrow::Exclusive);
-        // SAFETY: No data race when called from spearate threads because `!Sync`,
-        // in addition, `RefCell` gurantees no other borrow to T.
-        Ok(RefMut { cell: self })
-      }
+    if self.state.borrow_mut() {
+      // SAFETY: No data race when called from spearate threads because `!Sync`,
+      // in addition, `RefCell` gurantees no other borrow to T.
+      Ok(RefMut { cell: self })
+    } else {
+      Err(BorrowError)
     }
   }
 
@@ -289,7 +350,7 @@ impl<T> RefCell<T> {
   /// let ptr = c.as_ptr();
   /// ```
   #[inline]
-  pub fn as_ptr(&self) -> *mut T {
+  pub const fn as_ptr(&self) -> *mut T {
     self.value.get()
   }
 
@@ -315,11 +376,36 @@ impl<T> RefCell<T> {
   /// assert_eq!(*c.borrow(), 6);
   /// ```
   #[inline]
-  pub fn get_mut(&mut self) -> &mut T {
+  pub const fn get_mut(&mut self) -> &mut T {
     // SAFETY: This can cause data race when called from separate threads,
     // but `Cell` is `!Sync`,  so it won't happen and `&mut` guarantees unique access.
     unsafe { &mut *self.value.get() }
   }
+
+  /// Consumes the `RefCell`, returning its value in a [`Frozen`] that only
+  /// gives out shared references.
+  ///
+  /// Reads through a `Frozen` are plain dereferences, with none of the
+  /// borrow bookkeeping of [`borrow`](#method.borrow). This suits values
+  /// that are built up through a `RefCell` and only read afterwards.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::RefCell;
+  ///
+  /// let config = RefCell::new(vec![("port", 80)]);
+  /// config.borrow_mut().push(("threads", 4));
+  ///
+  /// let config = config.freeze();
+  /// assert_eq!(config.len(), 2);
+  /// assert_eq!(config[1], ("threads", 4));
+  /// ```
+  pub fn freeze(self) -> Frozen<T> {
+    Frozen {
+      value: self.into_inner(),
+    }
+  }
 }
 
 impl<T: Default> RefCell<T> {
@@ -346,6 +432,9 @@ impl<T: Default> RefCell<T> {
 }
 
 unsafe impl<T> Send for RefCell<T> where T: Send {}
+// As with std's `RefCell`, the `UnsafeCell` inside makes `RefCell<T>` `UnwindSafe`
+// when `T` is, and never `RefUnwindSafe`: a panic may leave the value
+// half-updated for anyone else holding a `&RefCell`.
 
 impl<T: Clone> Clone for RefCell<T> {
   /// # Panics
@@ -437,29 +526,71 @@ impl<T> From<T> for RefCell<T> {
 
 // impl<T: std::ops::CoerceUnsized<U>, U> std::ops::CoerceUnsized<RefCell<U>> for RefCell<T> {}
 
+// The panics of `borrow` and `borrow_mut` are outlined, so that the success
+// paths stay small enough to inline into the callers.
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn panic_already_mutably_borrowed() -> ! {
+  panic!("{}", BorrowError)
+}
+
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn panic_already_borrowed() -> ! {
+  panic!("{}", BorrowMutError)
+}
+
 /// Wraps a borrowed reference to a value in a `RefCell` box.
 /// A wrapper type for an immutably borrowed value from a [`RefCell<T>`](struct.RefCell.html).
 pub struct Ref<'r, T> {
-  cell: &'r RefCell<T>,
+  // A pointer rather than a `&RefCell<T>`, which would make `Ref` invariant
+  // in `T` like the `RefCell`. The borrow only reads `T`, so it can be
+  // covariant, as a `&T` is.
+  value: std::ptr::NonNull<T>,
+  state: &'r BorrowTracker,
+}
+
+impl<'r, T> Ref<'r, T> {
+  /// Wraps a shared borrow of `cell` that was just taken.
+  #[inline]
+  fn new(cell: &'r RefCell<T>) -> Ref<'r, T> {
+    Ref {
+      // SAFETY: `UnsafeCell::get` never returns null.
+      value: unsafe { std::ptr::NonNull::new_unchecked(cell.value.get()) },
+      state: &cell.state,
+    }
+  }
+
+  /// Rebuilds the `Ref` of a shared borrow of `cell` that was leaked with
+  /// [`std::mem::forget`], so dropping it releases the borrow.
+  ///
+  /// # Safety
+  ///
+  /// A leaked shared borrow of `cell` must be outstanding, and it must not be
+  /// rebuilt more than once.
+  pub(crate) unsafe fn from_leaked(cell: &'r RefCell<T>) -> Ref<'r, T> {
+    Ref::new(cell)
+  }
 }
 
 impl<T> Drop for Ref<'_, T> {
+  #[inline]
   fn drop(&mut self) {
-    match self.cell.state.get() {
-      Borrow::Exclusive | Borrow::UnShared => unreachable!(),
-      Borrow::Shared(1) => self.cell.state.set(Borrow::UnShared),
-      Borrow::Shared(n) => self.cell.state.set(Borrow::Shared(n - 1)),
-    }
+    self.state.release();
   }
 }
 
 impl<T> std::ops::Deref for Ref<'_, T> {
   type Target = T;
+
+  #[inline]
   fn deref(&self) -> &Self::Target {
     // SAEFTY: A `Ref` is only created if no exlusive reference have been given out.
     // once it's given out state is set to Shared, so no exclusive refs are given out.
     // so dereferencing into a shred ref is fine.
-    unsafe { &*self.cell.value.get() }
+    unsafe { self.value.as_ref() }
   }
 }
 
@@ -469,19 +600,16 @@ pub struct RefMut<'r, T> {
 }
 
 impl<T> Drop for RefMut<'_, T> {
+  #[inline]
   fn drop(&mut self) {
-    match self.cell.state.get() {
-      Borrow::UnShared | Borrow::Shared(_) => unreachable!(),
-      Borrow::Exclusive => {
-        self.cell.state.set(Borrow::UnShared);
-      }
-    }
+    self.cell.state.release_mut();
   }
 }
 
 impl<T> std::ops::Deref for RefMut<'_, T> {
   type Target = T;
 
+  #[inline]
   fn deref(&self) -> &Self::Target {
     // SAFETY: See `deref_mut`.
     unsafe { &*self.cell.value.get() }
@@ -489,6 +617,7 @@ impl<T> std::ops::Deref for RefMut<'_, T> {
 }
 
 impl<T> std::ops::DerefMut for RefMut<'_, T> {
+  #[inline]
   fn deref_mut(&mut self) -> &mut Self::Target {
     // SAFETY: A `RefMut` is only created if no other references have been given out.
     // once it's given out state is set to Exlusive, so no future refs are given out.
@@ -497,6 +626,65 @@ impl<T> std::ops::DerefMut for RefMut<'_, T> {
   }
 }
 
+/// A value taken out of a [`RefCell<T>`](struct.RefCell.html) by
+/// [`RefCell::freeze`](struct.RefCell.html#method.freeze), which can only be
+/// read.
+///
+/// Unlike a `RefCell`, a `Frozen<T>` is [`Sync`] when `T` is, so the frozen
+/// value can be shared between threads.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Frozen<T> {
+  value: T,
+}
+
+impl<T> Frozen<T> {
+  /// Puts the value back into a `RefCell`, so that it can be mutated again.
+  ///
+  /// This is an associated function, so that it doesn't hide a method of
+  /// `T`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Frozen, RefCell};
+  ///
+  /// let frozen = RefCell::new(1).freeze();
+  /// let cell = Frozen::thaw(frozen);
+  /// *cell.borrow_mut() += 1;
+  /// assert_eq!(cell.into_inner(), 2);
+  /// ```
+  pub fn thaw(this: Self) -> RefCell<T> {
+    RefCell::new(this.value)
+  }
+}
+
+impl<T> std::ops::Deref for Frozen<T> {
+  type Target = T;
+
+  #[inline]
+  fn deref(&self) -> &T {
+    &self.value
+  }
+}
+
+impl<T> AsRef<T> for Frozen<T> {
+  fn as_ref(&self) -> &T {
+    &self.value
+  }
+}
+
+impl<T> std::borrow::Borrow<T> for Frozen<T> {
+  fn borrow(&self) -> &T {
+    &self.value
+  }
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for Frozen<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&self.value, f)
+  }
+}
+
 /// An error returned by [`RefCell::try_borrow`](struct.RefCell.html#method.try_borrow)
 pub struct BorrowError;
 
@@ -645,4 +833,38 @@ mod tests {
   fn partial_cmp() {
     assert!(RefCell::new(5) == RefCell::new(5));
   }
+
+  #[test]
+  fn frozen_values_are_shared_between_threads() {
+    let cell = RefCell::new(String::from("start"));
+    cell.borrow_mut().push_str("ed");
+
+    let frozen = std::sync::Arc::new(cell.freeze());
+    let len = {
+      let frozen = std::sync::Arc::clone(&frozen);
+      std::thread::spawn(move || frozen.len()).join().unwrap()
+    };
+    assert_eq!(len, 7);
+
+    let frozen = std::sync::Arc::try_unwrap(frozen).unwrap();
+    assert_eq!(frozen.to_string(), "started");
+    assert_eq!(Frozen::thaw(frozen).take(), "started");
+  }
+
+  #[test]
+  fn borrow_flag_is_one_word() {
+    assert_eq!(
+      std::mem::size_of::<RefCell<usize>>(),
+      2 * std::mem::size_of::<usize>()
+    );
+
+    let c = RefCell::new(1);
+    let (a, b) = (c.borrow(), c.borrow());
+    assert_eq!(c.state.flag.get(), 2);
+    drop((a, b));
+    let m = c.borrow_mut();
+    assert_eq!(c.state.flag.get(), WRITING);
+    drop(m);
+    assert!(c.state.is_unused());
+  }
 }
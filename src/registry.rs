@@ -0,0 +1,439 @@
+//! A container of shared services, one per type: [`Registry`].
+//!
+//! Small GUI and game apps tend to wire their singletons, such as the config, the asset cache
+//! and the audio mixer, through one place that hands each of them out by type. A `Registry` is
+//! that place. It holds each service as an [`Rc<dyn Any>`][Rc] keyed by its [`TypeId`], and
+//! [`get::<T>`] returns an `Rc<T>` to it, or `None` if there is no `T`.
+//!
+//! A service can be [`insert`]ed ready-made, or [`provide`]d as a function that makes it on the
+//! first `get`. The function is given the registry, so that it can get the services it depends
+//! on, in whatever order they were registered. A provider that (indirectly) gets its own service
+//! panics, rather than recursing forever.
+//!
+//! A [`child`] registry is a scope: what it holds shadows its parent, and what it doesn't hold it
+//! gets from the parent. A lazily provided service is made, and kept, in the registry it was
+//! provided to, so each scope sees the same one.
+//!
+//! ```
+//! use pointer::{Rc, Registry};
+//!
+//! struct Config {
+//!   volume: u8,
+//! }
+//!
+//! struct Mixer {
+//!   config: Rc<Config>,
+//! }
+//!
+//! let app = Rc::new(Registry::new());
+//! app.provide(|services| Mixer {
+//!   config: services.get::<Config>().unwrap(),
+//! });
+//! app.insert(Config { volume: 7 });
+//!
+//! let level = Registry::child(&app);
+//! level.insert(Config { volume: 3 });
+//!
+//! assert_eq!(level.get::<Config>().unwrap().volume, 3);
+//! // The mixer was provided to the app, so it gets the app's config.
+//! assert_eq!(level.get::<Mixer>().unwrap().config.volume, 7);
+//! ```
+//!
+//! [`TypeId`]: std::any::TypeId
+//! [`get::<T>`]: Registry::get
+//! [`insert`]: Registry::insert
+//! [`provide`]: Registry::provide
+//! [`child`]: Registry::child
+
+use crate::{Boxed, Rc, RefCell};
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// A function that makes a service.
+type Provider = Box<dyn FnOnce(&Registry) -> Rc<dyn Any>>;
+
+/// A service, or the way to make it.
+enum Entry {
+  Ready(Rc<dyn Any>),
+  Lazy(Provider),
+  /// The provider is running.
+  Making,
+}
+
+/// A container of shared services, keyed by their type.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct Registry {
+  entries: RefCell<HashMap<TypeId, Entry>>,
+  parent: Option<Rc<Registry>>,
+}
+
+impl Registry {
+  /// Creates an empty registry.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Registry;
+  ///
+  /// let registry = Registry::new();
+  /// assert!(registry.get::<String>().is_none());
+  /// ```
+  pub fn new() -> Registry {
+    Registry {
+      entries: RefCell::new(HashMap::new()),
+      parent: None,
+    }
+  }
+
+  /// Creates an empty registry that gets what it doesn't hold from `parent`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Rc, Registry};
+  ///
+  /// let parent = Rc::new(Registry::new());
+  /// parent.insert(1u8);
+  ///
+  /// let child = Registry::child(&parent);
+  /// child.insert(2u16);
+  ///
+  /// assert_eq!(child.get::<u8>().as_deref(), Some(&1));
+  /// assert!(parent.get::<u16>().is_none());
+  /// ```
+  pub fn child(parent: &Rc<Registry>) -> Registry {
+    Registry {
+      entries: RefCell::new(HashMap::new()),
+      parent: Some(Rc::clone(parent)),
+    }
+  }
+
+  /// Returns the registry this one was made a child of, if any.
+  pub fn parent(&self) -> Option<&Rc<Registry>> {
+    self.parent.as_ref()
+  }
+
+  /// Puts `value` in the registry, as the service of type `T`.
+  ///
+  /// Returns the service it replaces in this registry, if it was made.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Registry;
+  ///
+  /// let registry = Registry::new();
+  /// assert!(registry.insert(String::from("a")).is_none());
+  ///
+  /// let old = registry.insert(String::from("b")).unwrap();
+  /// assert_eq!(*old, "a");
+  /// ```
+  pub fn insert<T: Any>(&self, value: T) -> Option<Rc<T>> {
+    self.put::<T>(erase(value))
+  }
+
+  /// Puts an `Rc` in the registry, as the service of type `T`, so that the
+  /// caller can keep sharing it.
+  ///
+  /// Returns the service it replaces in this registry, if it was made.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Rc, Registry};
+  ///
+  /// let registry = Registry::new();
+  /// let name = Rc::new(String::from("a"));
+  /// registry.insert_rc(Rc::clone(&name));
+  ///
+  /// assert!(Rc::ptr_eq(&registry.get::<String>().unwrap(), &name));
+  /// ```
+  pub fn insert_rc<T: Any>(&self, value: Rc<T>) -> Option<Rc<T>> {
+    self.put::<T>(erase(Shared(value)))
+  }
+
+  /// Registers `provider` to make the service of type `T` the first time it
+  /// is asked for, replacing any `T` this registry holds.
+  ///
+  /// The provider is called with the registry it was given to.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Cell, Rc, Registry};
+  ///
+  /// let registry = Registry::new();
+  /// let made = Rc::new(Cell::new(0));
+  ///
+  /// let count = Rc::clone(&made);
+  /// registry.provide(move |_| {
+  ///   count.set(count.get() + 1);
+  ///   vec![1, 2, 3]
+  /// });
+  /// assert_eq!(made.get(), 0);
+  ///
+  /// assert_eq!(registry.get::<Vec<i32>>().unwrap().len(), 3);
+  /// assert_eq!(registry.get::<Vec<i32>>().unwrap().len(), 3);
+  /// assert_eq!(made.get(), 1);
+  /// ```
+  pub fn provide<T: Any>(
+    &self,
+    provider: impl FnOnce(&Registry) -> T + 'static,
+  ) {
+    let lazy = Box::new(move |registry: &Registry| erase(provider(registry)));
+    self
+      .entries
+      .borrow_mut()
+      .insert(TypeId::of::<T>(), Entry::Lazy(lazy));
+  }
+
+  /// Returns the service of type `T`, from this registry or the nearest
+  /// parent that holds one, making it first if it was provided lazily.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the provider of the service gets it, directly or through
+  /// other providers.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Registry;
+  ///
+  /// let registry = Registry::new();
+  /// registry.insert(5u32);
+  ///
+  /// assert_eq!(registry.get::<u32>().as_deref(), Some(&5));
+  /// assert!(registry.get::<u64>().is_none());
+  /// ```
+  pub fn get<T: Any>(&self) -> Option<Rc<T>> {
+    if !self.contains_here::<T>() {
+      return self.parent.as_ref()?.get();
+    }
+    let key = TypeId::of::<T>();
+    let provider = {
+      let mut entries = self.entries.borrow_mut();
+      let entry = entries.get_mut(&key)?;
+      if let Entry::Ready(value) = entry {
+        return Some(downcast(Rc::clone(value)));
+      }
+      match std::mem::replace(entry, Entry::Making) {
+        Entry::Lazy(provider) => provider,
+        _ => panic!(
+          "the provider of `{}` depends on itself",
+          std::any::type_name::<T>()
+        ),
+      }
+    };
+
+    /// Forgets the service if its provider panics, so that it isn't taken
+    /// for a cycle on the next `get`.
+    struct Forget<'a> {
+      registry: &'a Registry,
+      key: TypeId,
+    }
+
+    impl Drop for Forget<'_> {
+      fn drop(&mut self) {
+        self.registry.entries.borrow_mut().remove(&self.key);
+      }
+    }
+
+    let forget = Forget {
+      registry: self,
+      key,
+    };
+    let value = provider(self);
+    std::mem::forget(forget);
+    self
+      .entries
+      .borrow_mut()
+      .insert(key, Entry::Ready(Rc::clone(&value)));
+    Some(downcast(value))
+  }
+
+  /// Returns `true` if the registry, or one of its parents, has a service of
+  /// type `T`, made or not.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Registry;
+  ///
+  /// let registry = Registry::new();
+  /// registry.provide(|_| 5u32);
+  /// assert!(registry.contains::<u32>());
+  /// ```
+  pub fn contains<T: Any>(&self) -> bool {
+    self.contains_here::<T>()
+      || self.parent.as_ref().is_some_and(|p| p.contains::<T>())
+  }
+
+  /// Takes the service of type `T` out of this registry, leaving its
+  /// parents alone.
+  ///
+  /// Returns the service if it was made; a lazy provider is dropped
+  /// uncalled.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Registry;
+  ///
+  /// let registry = Registry::new();
+  /// registry.insert(5u32);
+  ///
+  /// assert_eq!(registry.remove::<u32>().as_deref(), Some(&5));
+  /// assert!(!registry.contains::<u32>());
+  /// ```
+  pub fn remove<T: Any>(&self) -> Option<Rc<T>> {
+    match self.entries.borrow_mut().remove(&TypeId::of::<T>()) {
+      Some(Entry::Ready(value)) => Some(downcast(value)),
+      _ => None,
+    }
+  }
+
+  /// Stores `value` as the service of type `T`, and returns the service it
+  /// replaces, if it was made.
+  fn put<T: Any>(&self, value: Rc<dyn Any>) -> Option<Rc<T>> {
+    let old = self
+      .entries
+      .borrow_mut()
+      .insert(TypeId::of::<T>(), Entry::Ready(value));
+    match old {
+      Some(Entry::Ready(old)) => Some(downcast(old)),
+      _ => None,
+    }
+  }
+
+  fn contains_here<T: Any>(&self) -> bool {
+    self.entries.borrow().contains_key(&TypeId::of::<T>())
+  }
+}
+
+impl Default for Registry {
+  fn default() -> Registry {
+    Registry::new()
+  }
+}
+
+impl std::fmt::Debug for Registry {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Registry")
+      .field("services", &self.entries.borrow().len())
+      .field("parent", &self.parent)
+      .finish()
+  }
+}
+
+/// Moves `value` into an `Rc<dyn Any>`.
+///
+/// The crate's `Rc` has no unsized coercions, so the value goes through a
+/// box of `dyn Any`.
+fn erase<T: Any>(value: T) -> Rc<dyn Any> {
+  Rc::from(Boxed::from(Box::new(value) as Box<dyn Any>))
+}
+
+/// An `Rc<T>` put in with [`Registry::insert_rc`], stored as the service of
+/// type `T` so that the caller keeps sharing it.
+struct Shared<T>(Rc<T>);
+
+/// Gets back the `Rc<T>` of a service of type `T`.
+fn downcast<T: Any>(value: Rc<dyn Any>) -> Rc<T> {
+  match Rc::downcast::<Shared<T>>(value) {
+    Ok(shared) => Rc::clone(&shared.0),
+    Err(value) => match Rc::downcast::<T>(value) {
+      Ok(value) => value,
+      Err(_) => {
+        unreachable!("services are keyed by the `TypeId` of their type")
+      }
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn children_shadow_their_parent() {
+    let root = Rc::new(Registry::new());
+    root.insert(1u8);
+    root.insert(String::from("root"));
+
+    let child = Rc::new(Registry::child(&root));
+    child.insert(String::from("child"));
+    let grandchild = Registry::child(&child);
+
+    assert_eq!(*grandchild.get::<String>().unwrap(), "child");
+    assert_eq!(*grandchild.get::<u8>().unwrap(), 1);
+    assert!(!grandchild.contains::<u16>());
+
+    assert!(grandchild.remove::<String>().is_none());
+    assert_eq!(*child.remove::<String>().unwrap(), "child");
+    assert_eq!(*grandchild.get::<String>().unwrap(), "root");
+  }
+
+  #[test]
+  fn shared_services_stay_shared() {
+    let registry = Registry::new();
+    let list = Rc::new(RefCell::new(vec![1]));
+    registry.insert_rc(Rc::clone(&list));
+
+    registry
+      .get::<RefCell<Vec<i32>>>()
+      .unwrap()
+      .borrow_mut()
+      .push(2);
+    assert_eq!(*list.borrow(), [1, 2]);
+    assert!(Rc::ptr_eq(
+      &registry.get::<RefCell<Vec<i32>>>().unwrap(),
+      &list
+    ));
+  }
+
+  #[test]
+  fn providers_run_once_in_their_own_registry() {
+    let root = Rc::new(Registry::new());
+    let made = Rc::new(crate::Cell::new(0));
+    let count = Rc::clone(&made);
+    root.provide(move |services| {
+      count.set(count.get() + 1);
+      u32::from(*services.get::<u8>().unwrap()) + 1
+    });
+    root.insert(1u8);
+
+    let child = Registry::child(&root);
+    child.insert(10u8);
+    // The provider resolved `u8` in the root, where it was registered.
+    assert_eq!(*child.get::<u32>().unwrap(), 2);
+    assert!(Rc::ptr_eq(
+      &root.get::<u32>().unwrap(),
+      &child.get::<u32>().unwrap()
+    ));
+    assert_eq!(made.get(), 1);
+  }
+
+  #[test]
+  #[should_panic(expected = "depends on itself")]
+  fn cycles_panic() {
+    let registry = Registry::new();
+    registry.provide(|services| *services.get::<u16>().unwrap() as u8);
+    registry.provide(|services| *services.get::<u8>().unwrap() as u16);
+    registry.get::<u8>();
+  }
+
+  #[test]
+  fn panicking_providers_are_forgotten() {
+    let registry = Registry::new();
+    registry.provide(|_| -> u8 { panic!("no device") });
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      registry.get::<u8>()
+    }));
+    assert!(result.is_err());
+    assert!(registry.get::<u8>().is_none());
+  }
+}
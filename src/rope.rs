@@ -0,0 +1,453 @@
+//! A text buffer whose copies share their unchanged text: [`Rope`].
+//!
+//! A `Rope` holds its text as a list of chunks of at most [`CHUNK_LEN`] bytes, each an
+//! [`Rc<str>`][Rc]. The list itself is a [`CowVec`], so cloning a rope, to keep an undo state or
+//! hand a snapshot to a background task, copies one pointer. An edit rebuilds only the chunks
+//! around the edited range; every other chunk stays shared with the clones, however big the
+//! document is. [`slice`] shares the chunks that lie whole inside its range, too.
+//!
+//! Offsets are in bytes, as for `str`, and must lie on char boundaries. Finding an offset walks
+//! the chunk list, so the rope suits documents of up to some thousands of chunks; a balanced tree
+//! would do better on bigger ones.
+//!
+//! ```
+//! use pointer::Rope;
+//!
+//! let mut doc = Rope::from("Hello, world!");
+//! let saved = doc.clone();
+//!
+//! doc.insert(7, "big ");
+//! doc.remove(0..5);
+//! doc.insert(0, "Goodbye");
+//!
+//! assert_eq!(doc.to_string(), "Goodbye, big world!");
+//! assert_eq!(saved.to_string(), "Hello, world!");
+//! assert_eq!(doc.slice(9..12), "big");
+//! ```
+//!
+//! [`CowVec`]: crate::CowVec
+//! [`slice`]: Rope::slice
+//! [Rc]: crate::Rc
+
+use crate::{CowVec, Rc};
+
+use std::ops::{Bound, RangeBounds};
+
+/// The most bytes that a chunk of a [`Rope`] holds.
+pub const CHUNK_LEN: usize = 512;
+
+/// A text buffer of shared chunks.
+///
+/// See the [module-level documentation](./index.html) for more details.
+#[derive(Clone, Default)]
+pub struct Rope {
+  chunks: CowVec<Rc<str>>,
+  len: usize,
+}
+
+impl Rope {
+  /// Creates an empty rope.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rope;
+  ///
+  /// let rope = Rope::new();
+  /// assert!(rope.is_empty());
+  /// ```
+  pub fn new() -> Rope {
+    Rope::default()
+  }
+
+  /// Returns the length of the text, in bytes.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Returns `true` if the text is empty.
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Returns `true` if the byte offset `at` is on a char boundary, or is the
+  /// end of the text.
+  pub fn is_char_boundary(&self, at: usize) -> bool {
+    if at > self.len {
+      return false;
+    }
+    let (index, offset) = self.locate(at);
+    index == self.chunks.len() || self.chunks[index].is_char_boundary(offset)
+  }
+
+  /// Inserts `text` at the byte offset `at`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `at` is past the end or not on a char boundary.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rope;
+  ///
+  /// let mut rope = Rope::from("ac");
+  /// rope.insert(1, "b");
+  /// rope.insert(3, "d");
+  /// assert_eq!(rope, "abcd");
+  /// ```
+  #[track_caller]
+  pub fn insert(&mut self, at: usize, text: &str) {
+    assert!(
+      self.is_char_boundary(at),
+      "offset {} is not on a char boundary of a rope of length {}",
+      at,
+      self.len
+    );
+    if text.is_empty() {
+      return;
+    }
+    let (mut index, mut offset) = self.locate(at);
+    if self.chunks.is_empty() {
+      self.chunks = chunked(text).into_iter().collect();
+      self.len = text.len();
+      return;
+    }
+    if offset == 0 && index > 0 {
+      // Append to the chunk before rather than start a small one.
+      index -= 1;
+      offset = self.chunks[index].len();
+    }
+    let chunk = &self.chunks[index];
+    let joined = [&chunk[..offset], text, &chunk[offset..]].concat();
+    self
+      .chunks
+      .make_mut()
+      .splice(index..=index, chunked(&joined));
+    self.len += text.len();
+  }
+
+  /// Removes the text in the byte `range`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the range is out of bounds, its start is after its end, or
+  /// either end is not on a char boundary.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rope;
+  ///
+  /// let mut rope = Rope::from("one two three");
+  /// rope.remove(3..7);
+  /// assert_eq!(rope, "one three");
+  /// ```
+  #[track_caller]
+  pub fn remove(&mut self, range: impl RangeBounds<usize>) {
+    let (start, end) = self.range(range);
+    if start == end {
+      return;
+    }
+    let (first, a) = self.locate(start);
+    let (last, b) = self.locate(end);
+    let mut kept = String::from(&self.chunks[first][..a]);
+    let stop = if b > 0 {
+      kept.push_str(&self.chunks[last][b..]);
+      last + 1
+    } else {
+      last
+    };
+    self.chunks.make_mut().splice(first..stop, chunked(&kept));
+    self.len -= end - start;
+  }
+
+  /// Returns the text in the byte `range`, sharing the chunks that lie whole
+  /// inside it.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the range is out of bounds, its start is after its end, or
+  /// either end is not on a char boundary.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rope;
+  ///
+  /// let rope = Rope::from("fn main() {}");
+  /// assert_eq!(rope.slice(3..7), "main");
+  /// assert_eq!(rope.slice(..2), "fn");
+  /// ```
+  #[track_caller]
+  pub fn slice(&self, range: impl RangeBounds<usize>) -> Rope {
+    let (start, end) = self.range(range);
+    if start == end {
+      return Rope::new();
+    }
+    let (first, a) = self.locate(start);
+    let (last, b) = self.locate(end);
+    let mut chunks = Vec::new();
+    if first == last {
+      chunks.push(Rc::from(&self.chunks[first][a..b]));
+    } else {
+      chunks.push(match a {
+        0 => Rc::clone(&self.chunks[first]),
+        _ => Rc::from(&self.chunks[first][a..]),
+      });
+      chunks.extend(self.chunks[first + 1..last].iter().cloned());
+      if b > 0 {
+        chunks.push(Rc::from(&self.chunks[last][..b]));
+      }
+    }
+    Rope {
+      chunks: chunks.into(),
+      len: end - start,
+    }
+  }
+
+  /// Returns an iterator over the chunks of the text, in order.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::Rope;
+  ///
+  /// let rope = Rope::from("x".repeat(1000).as_str());
+  /// assert!(rope.chunks().count() > 1);
+  /// assert_eq!(rope.chunks().map(str::len).sum::<usize>(), 1000);
+  /// ```
+  pub fn chunks(&self) -> Chunks<'_> {
+    Chunks {
+      chunks: self.chunks.iter(),
+    }
+  }
+
+  /// Returns an iterator over the chars of the text.
+  pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+    self.chunks().flat_map(str::chars)
+  }
+
+  /// Returns the index of the chunk that holds the byte at `at`, and the
+  /// offset of the byte in it, or the number of chunks and zero for the end
+  /// of the text.
+  fn locate(&self, at: usize) -> (usize, usize) {
+    let mut start = 0;
+    for (index, chunk) in self.chunks.iter().enumerate() {
+      if at < start + chunk.len() {
+        return (index, at - start);
+      }
+      start += chunk.len();
+    }
+    (self.chunks.len(), 0)
+  }
+
+  /// Resolves `range` to byte offsets, checking them.
+  #[track_caller]
+  fn range(&self, range: impl RangeBounds<usize>) -> (usize, usize) {
+    let start = match range.start_bound() {
+      Bound::Included(&n) => n,
+      Bound::Excluded(&n) => n.checked_add(1).expect("out of range"),
+      Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+      Bound::Included(&n) => n.checked_add(1).expect("out of range"),
+      Bound::Excluded(&n) => n,
+      Bound::Unbounded => self.len,
+    };
+
+    assert!(
+      start <= end && end <= self.len,
+      "range {}..{} out of bounds for a rope of length {}",
+      start,
+      end,
+      self.len
+    );
+    assert!(
+      self.is_char_boundary(start) && self.is_char_boundary(end),
+      "range {}..{} is not on char boundaries",
+      start,
+      end
+    );
+    (start, end)
+  }
+}
+
+/// Splits `text` into chunks of at most [`CHUNK_LEN`] bytes.
+fn chunked(text: &str) -> Vec<Rc<str>> {
+  let mut chunks = Vec::new();
+  let mut rest = text;
+  while rest.len() > CHUNK_LEN {
+    let mut cut = CHUNK_LEN;
+    while !rest.is_char_boundary(cut) {
+      cut -= 1;
+    }
+    chunks.push(Rc::from(&rest[..cut]));
+    rest = &rest[cut..];
+  }
+  if !rest.is_empty() {
+    chunks.push(Rc::from(rest));
+  }
+  chunks
+}
+
+impl From<&str> for Rope {
+  fn from(text: &str) -> Rope {
+    Rope {
+      chunks: chunked(text).into(),
+      len: text.len(),
+    }
+  }
+}
+
+impl From<String> for Rope {
+  fn from(text: String) -> Rope {
+    Rope::from(text.as_str())
+  }
+}
+
+impl PartialEq for Rope {
+  fn eq(&self, other: &Self) -> bool {
+    self.len == other.len
+      && self
+        .chunks()
+        .flat_map(str::bytes)
+        .eq(other.chunks().flat_map(str::bytes))
+  }
+}
+
+impl Eq for Rope {}
+
+impl PartialEq<str> for Rope {
+  fn eq(&self, other: &str) -> bool {
+    self.len == other.len()
+      && self.chunks().flat_map(str::bytes).eq(other.bytes())
+  }
+}
+
+impl PartialEq<&str> for Rope {
+  fn eq(&self, other: &&str) -> bool {
+    *self == **other
+  }
+}
+
+impl std::fmt::Display for Rope {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    self.chunks().try_for_each(|chunk| f.write_str(chunk))
+  }
+}
+
+impl std::fmt::Debug for Rope {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&self.to_string(), f)
+  }
+}
+
+/// An iterator over the chunks of a [`Rope`].
+///
+/// This struct is created by [`Rope::chunks`].
+pub struct Chunks<'a> {
+  chunks: std::slice::Iter<'a, Rc<str>>,
+}
+
+impl<'a> Iterator for Chunks<'a> {
+  type Item = &'a str;
+
+  fn next(&mut self) -> Option<&'a str> {
+    self.chunks.next().map(|chunk| &**chunk)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.chunks.size_hint()
+  }
+}
+
+impl DoubleEndedIterator for Chunks<'_> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    self.chunks.next_back().map(|chunk| &**chunk)
+  }
+}
+
+impl ExactSizeIterator for Chunks<'_> {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Returns how many chunks of `a` are shared with `b`.
+  fn shared(a: &Rope, b: &Rope) -> usize {
+    a.chunks
+      .iter()
+      .filter(|x| b.chunks.iter().any(|y| Rc::ptr_eq(x, y)))
+      .count()
+  }
+
+  #[test]
+  fn edits_match_a_string() {
+    let mut rope = Rope::new();
+    let mut model = String::new();
+    let words = ["héllo ", "wörld ", "🦀", "\n"];
+    let mut seed = 7u64;
+    for n in 0..3000 {
+      seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+      let mut at = (seed >> 33) as usize % (model.len() + 1);
+      while !model.is_char_boundary(at) {
+        at -= 1;
+      }
+      if n % 4 == 3 {
+        let mut end = (at + (seed >> 40) as usize % 12).min(model.len());
+        while !model.is_char_boundary(end) {
+          end -= 1;
+        }
+        rope.remove(at..end);
+        model.replace_range(at..end, "");
+      } else {
+        let word = words[n % words.len()];
+        rope.insert(at, word);
+        model.insert_str(at, word);
+      }
+    }
+
+    assert_eq!(rope, model.as_str());
+    assert_eq!(rope.len(), model.len());
+    assert!(rope.chunks().len() > 10);
+    assert!(rope.chunks().all(|c| !c.is_empty() && c.len() <= CHUNK_LEN));
+    let (mut start, mut end) = (10, 200);
+    while !model.is_char_boundary(start) || !model.is_char_boundary(end) {
+      start += 1;
+      end += 1;
+    }
+    assert_eq!(rope.slice(start..end), model[start..end]);
+    assert!(rope.chars().eq(model.chars()));
+  }
+
+  #[test]
+  fn copies_share_untouched_chunks() {
+    let original = Rope::from("abcdefgh".repeat(1000));
+    let chunks = original.chunks().len();
+    let mut copy = original.clone();
+    copy.insert(CHUNK_LEN * 3 + 5, "inserted");
+    copy.remove(CHUNK_LEN * 10..CHUNK_LEN * 10 + 3);
+
+    assert_eq!(shared(&copy, &original), chunks - 2);
+    let slice = original.slice(CHUNK_LEN..CHUNK_LEN * 4 + 1);
+    assert_eq!(shared(&slice, &original), 3);
+    assert_eq!(original.len(), 8000);
+  }
+
+  #[test]
+  fn typing_grows_chunks_instead_of_adding_them() {
+    let mut rope = Rope::new();
+    for _ in 0..1000 {
+      rope.insert(rope.len(), "a");
+    }
+    assert_eq!(rope.chunks().len(), 1000_usize.div_ceil(CHUNK_LEN));
+    assert_eq!(format!("{:?}", rope.slice(..3)), "\"aaa\"");
+  }
+
+  #[test]
+  #[should_panic(expected = "not on a char boundary")]
+  fn inserting_inside_a_char_panics() {
+    Rope::from("é").insert(1, "x");
+  }
+}
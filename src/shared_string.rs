@@ -0,0 +1,548 @@
+//! Cheaply cloneable, immutable strings.
+//!
+//! [`SharedString`] is a view into a reference-counted `str` buffer: the buffer plus a byte range.
+//! It derefs to [`str`], clones in O(1) by bumping the reference count, and cuts out substrings
+//! with [`slice`] and [`split_at`] that keep sharing the same buffer instead of copying it.
+//!
+//! [`SharedString`] keeps its buffer in an [`Rc<str>`][Rc]. [`ArcString`] is the same type over an
+//! [`Arc<str>`][Arc], which can be sent to and shared between threads; it is also exported from
+//! [`sync`](crate::sync). Both are [`SharedStr<B>`][SharedStr] with a different buffer `B`.
+//!
+//! ```
+//! use pointer::SharedString;
+//!
+//! let line = SharedString::from("language: rust, mascot: ferris");
+//! let (key, value) = line.split_at(8);
+//! let value = value.slice(2..);
+//!
+//! assert_eq!(key, "language");
+//! assert_eq!(value, "rust, mascot: ferris");
+//! assert!(SharedString::ptr_eq_buffer(&line, &value));
+//! ```
+//!
+//! Strings of up to [`INLINE_CAPACITY`] bytes are made without a buffer at all: they are stored
+//! inline in the handle, which is as big as a buffer and range, and are copied instead of being
+//! reference counted. Identifiers and keys are mostly this short, so making and dropping them
+//! doesn't allocate. Substrings of an inline string are inline too, while substrings of a shared
+//! buffer keep sharing it whatever their length. Apart from [`ptr_eq_buffer`] and [`is_inline`],
+//! which tell the two apart, both behave the same.
+//!
+//! ```
+//! use pointer::SharedString;
+//!
+//! let short = SharedString::from("ident");
+//! assert!(SharedString::is_inline(&short));
+//! assert!(!SharedString::is_inline(&SharedString::from("a".repeat(40))));
+//! ```
+//!
+//! [`INLINE_CAPACITY`]: SharedStr::INLINE_CAPACITY
+//! [`ptr_eq_buffer`]: SharedStr::ptr_eq_buffer
+//! [`is_inline`]: SharedStr::is_inline
+//! [`slice`]: SharedStr::slice
+//! [`split_at`]: SharedStr::split_at
+//! [Arc]: crate::sync::Arc
+
+use crate::sync::Arc;
+use crate::Rc;
+
+use std::ops::{Bound, RangeBounds};
+
+/// A reference-counted `str` that can back a [`SharedStr`].
+///
+/// This trait is sealed: it is implemented for [`Rc<str>`][Rc] and
+/// [`Arc<str>`][Arc] only.
+///
+/// [Arc]: crate::sync::Arc
+pub trait StrBuffer:
+  private::Sealed + Clone + std::ops::Deref<Target = str>
+{
+  #[doc(hidden)]
+  fn from_string(s: String) -> Self;
+
+  #[doc(hidden)]
+  fn from_str(s: &str) -> Self;
+
+  #[doc(hidden)]
+  fn ptr_eq(this: &Self, other: &Self) -> bool;
+}
+
+impl StrBuffer for Rc<str> {
+  fn from_string(s: String) -> Rc<str> {
+    Rc::from(s)
+  }
+
+  fn from_str(s: &str) -> Rc<str> {
+    Rc::from(s)
+  }
+
+  fn ptr_eq(this: &Rc<str>, other: &Rc<str>) -> bool {
+    Rc::ptr_eq(this, other)
+  }
+}
+
+impl StrBuffer for Arc<str> {
+  fn from_string(s: String) -> Arc<str> {
+    Arc::from(s)
+  }
+
+  fn from_str(s: &str) -> Arc<str> {
+    Arc::from(s)
+  }
+
+  fn ptr_eq(this: &Arc<str>, other: &Arc<str>) -> bool {
+    Arc::ptr_eq(this, other)
+  }
+}
+
+mod private {
+  pub trait Sealed {}
+
+  impl Sealed for crate::Rc<str> {}
+  impl Sealed for crate::sync::Arc<str> {}
+}
+
+/// An immutable view into a shared `str` buffer `B`.
+///
+/// Use it through the [`SharedString`] and [`ArcString`] aliases. See the
+/// [module-level documentation](./index.html) for more details.
+#[derive(Clone)]
+pub struct SharedStr<B: StrBuffer> {
+  repr: Repr<B>,
+}
+
+/// The longest string kept inline. Together with its length and the enum
+/// tag, it takes as many bytes as a `Shared` string on 64-bit targets.
+const INLINE: usize = 22;
+
+#[derive(Clone)]
+enum Repr<B> {
+  // Invariant: `bytes[..len]` is a `str`.
+  Inline { len: u8, bytes: [u8; INLINE] },
+  // Invariant: both ends are on char boundaries of `buf`.
+  Shared { buf: B, start: usize, end: usize },
+}
+
+/// A cheaply cloneable string backed by an [`Rc<str>`][Rc].
+pub type SharedString = SharedStr<Rc<str>>;
+
+/// A cheaply cloneable string backed by an [`Arc<str>`][Arc], which can be
+/// shared between threads.
+///
+/// [Arc]: crate::sync::Arc
+pub type ArcString = SharedStr<Arc<str>>;
+
+impl<B: StrBuffer> SharedStr<B> {
+  /// The length in bytes of the longest string that is stored inline instead
+  /// of in a buffer.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::SharedString;
+  ///
+  /// let s = SharedString::from("x".repeat(SharedString::INLINE_CAPACITY));
+  /// assert!(SharedString::is_inline(&s));
+  /// ```
+  pub const INLINE_CAPACITY: usize = INLINE;
+
+  /// Creates an empty string.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::SharedString;
+  ///
+  /// let s = SharedString::new();
+  /// assert!(s.is_empty());
+  /// ```
+  pub fn new() -> SharedStr<B> {
+    SharedStr::from("")
+  }
+
+  /// Stores `s` inline, or returns `None` if it is too long.
+  fn inline(s: &str) -> Option<SharedStr<B>> {
+    let mut bytes = [0; INLINE];
+    bytes.get_mut(..s.len())?.copy_from_slice(s.as_bytes());
+    Some(SharedStr {
+      repr: Repr::Inline {
+        len: s.len() as u8,
+        bytes,
+      },
+    })
+  }
+
+  /// Returns the string as a `&str`.
+  #[inline]
+  pub fn as_str(&self) -> &str {
+    match &self.repr {
+      Repr::Inline { len, bytes } => {
+        // SAFETY: `bytes[..len]` is a `str` by the invariant of `Inline`.
+        unsafe { std::str::from_utf8_unchecked(&bytes[..usize::from(*len)]) }
+      }
+      Repr::Shared { buf, start, end } => &buf[*start..*end],
+    }
+  }
+
+  /// Returns a substring for the byte `range`, relative to this string,
+  /// sharing the same buffer if the string has one.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the range is out of bounds, its start is after its end, or
+  /// either end is not on a char boundary.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::SharedString;
+  ///
+  /// let s = SharedString::from("hello world");
+  /// assert_eq!(s.slice(6..), "world");
+  /// assert_eq!(s.slice(..=4), "hello");
+  /// ```
+  #[track_caller]
+  pub fn slice(&self, range: impl RangeBounds<usize>) -> SharedStr<B> {
+    let start = match range.start_bound() {
+      Bound::Included(&n) => n,
+      Bound::Excluded(&n) => n.checked_add(1).expect("out of range"),
+      Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+      Bound::Included(&n) => n.checked_add(1).expect("out of range"),
+      Bound::Excluded(&n) => n,
+      Bound::Unbounded => self.len(),
+    };
+
+    assert!(
+      start <= end && end <= self.len(),
+      "range {}..{} out of bounds for a string of length {}",
+      start,
+      end,
+      self.len()
+    );
+    let s = self.as_str();
+    assert!(
+      s.is_char_boundary(start) && s.is_char_boundary(end),
+      "range {}..{} is not on char boundaries",
+      start,
+      end
+    );
+
+    self.sub(start, end)
+  }
+
+  /// Divides the string into two at the byte index `mid`, both sharing the
+  /// same buffer if the string has one.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `mid` is past the end or not on a char boundary.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::SharedString;
+  ///
+  /// let (left, right) = SharedString::from("left|right").split_at(4);
+  /// assert_eq!(left, "left");
+  /// assert_eq!(right, "|right");
+  /// ```
+  #[track_caller]
+  pub fn split_at(&self, mid: usize) -> (SharedStr<B>, SharedStr<B>) {
+    (self.slice(..mid), self.slice(mid..))
+  }
+
+  /// Returns the substring that `sub` borrows, sharing the buffer, or `None`
+  /// if `sub` doesn't point into this string.
+  ///
+  /// This turns what the `str` methods return back into shared strings.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::SharedString;
+  ///
+  /// let s = SharedString::from("  padded  ");
+  /// let trimmed = s.slice_ref(s.trim()).unwrap();
+  /// assert_eq!(trimmed, "padded");
+  ///
+  /// assert!(s.slice_ref("elsewhere").is_none());
+  /// ```
+  pub fn slice_ref(&self, sub: &str) -> Option<SharedStr<B>> {
+    let base = self.as_str().as_ptr().addr();
+    let start = sub.as_ptr().addr().checked_sub(base)?;
+    let end = start.checked_add(sub.len())?;
+    if end > self.len() {
+      return None;
+    }
+    // `sub` is a valid `str` inside ours, so both ends are char boundaries.
+    Some(self.sub(start, end))
+  }
+
+  /// Returns the substring between the char boundaries `start` and `end`.
+  fn sub(&self, start: usize, end: usize) -> SharedStr<B> {
+    match &self.repr {
+      Repr::Inline { .. } => SharedStr::inline(&self.as_str()[start..end])
+        .expect("a substring of an inline string fits inline"),
+      Repr::Shared {
+        buf, start: base, ..
+      } => SharedStr {
+        repr: Repr::Shared {
+          buf: buf.clone(),
+          start: base + start,
+          end: base + end,
+        },
+      },
+    }
+  }
+
+  /// Returns `true` if both strings point into the same buffer.
+  ///
+  /// Inline strings have no buffer, so this is `false` if either is inline.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::SharedString;
+  ///
+  /// let long = SharedString::from("a string too long to be kept inline");
+  /// assert!(SharedString::ptr_eq_buffer(&long, &long.slice(2..4)));
+  ///
+  /// let short = SharedString::from("short");
+  /// assert!(!SharedString::ptr_eq_buffer(&short, &short.clone()));
+  /// ```
+  pub fn ptr_eq_buffer(this: &Self, other: &Self) -> bool {
+    match (&this.repr, &other.repr) {
+      (Repr::Shared { buf: a, .. }, Repr::Shared { buf: b, .. }) => {
+        B::ptr_eq(a, b)
+      }
+      _ => false,
+    }
+  }
+
+  /// Returns `true` if the string is stored inline in the handle rather
+  /// than in a shared buffer.
+  ///
+  /// Strings of up to [`INLINE_CAPACITY`] bytes are made inline, and so are
+  /// their substrings. Substrings of a shared buffer always share it.
+  ///
+  /// [`INLINE_CAPACITY`]: SharedStr::INLINE_CAPACITY
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::SharedString;
+  ///
+  /// assert!(SharedString::is_inline(&SharedString::from("id")));
+  ///
+  /// let long = SharedString::from("a string too long to be kept inline");
+  /// assert!(!SharedString::is_inline(&long));
+  /// assert!(!SharedString::is_inline(&long.slice(..1)));
+  /// ```
+  pub fn is_inline(this: &Self) -> bool {
+    matches!(this.repr, Repr::Inline { .. })
+  }
+}
+
+impl<B: StrBuffer> std::ops::Deref for SharedStr<B> {
+  type Target = str;
+
+  #[inline]
+  fn deref(&self) -> &str {
+    self.as_str()
+  }
+}
+
+impl<B: StrBuffer> AsRef<str> for SharedStr<B> {
+  fn as_ref(&self) -> &str {
+    self.as_str()
+  }
+}
+
+impl<B: StrBuffer> std::borrow::Borrow<str> for SharedStr<B> {
+  fn borrow(&self) -> &str {
+    self.as_str()
+  }
+}
+
+impl<B: StrBuffer> Default for SharedStr<B> {
+  fn default() -> SharedStr<B> {
+    SharedStr::new()
+  }
+}
+
+impl<B: StrBuffer> From<&str> for SharedStr<B> {
+  fn from(s: &str) -> SharedStr<B> {
+    SharedStr::inline(s).unwrap_or_else(|| SharedStr {
+      repr: Repr::Shared {
+        buf: B::from_str(s),
+        start: 0,
+        end: s.len(),
+      },
+    })
+  }
+}
+
+impl<B: StrBuffer> From<String> for SharedStr<B> {
+  fn from(s: String) -> SharedStr<B> {
+    if let Some(inline) = SharedStr::inline(&s) {
+      return inline;
+    }
+    let end = s.len();
+    SharedStr {
+      repr: Repr::Shared {
+        buf: B::from_string(s),
+        start: 0,
+        end,
+      },
+    }
+  }
+}
+
+impl From<Rc<str>> for SharedString {
+  fn from(buf: Rc<str>) -> SharedString {
+    let end = buf.len();
+    SharedStr {
+      repr: Repr::Shared { buf, start: 0, end },
+    }
+  }
+}
+
+impl From<Arc<str>> for ArcString {
+  fn from(buf: Arc<str>) -> ArcString {
+    let end = buf.len();
+    SharedStr {
+      repr: Repr::Shared { buf, start: 0, end },
+    }
+  }
+}
+
+impl<B: StrBuffer> From<SharedStr<B>> for String {
+  fn from(s: SharedStr<B>) -> String {
+    String::from(s.as_str())
+  }
+}
+
+impl<B: StrBuffer> PartialEq for SharedStr<B> {
+  fn eq(&self, other: &SharedStr<B>) -> bool {
+    self.as_str() == other.as_str()
+  }
+}
+
+impl<B: StrBuffer> Eq for SharedStr<B> {}
+
+impl<B: StrBuffer> PartialEq<str> for SharedStr<B> {
+  fn eq(&self, other: &str) -> bool {
+    self.as_str() == other
+  }
+}
+
+impl<B: StrBuffer> PartialEq<&str> for SharedStr<B> {
+  fn eq(&self, other: &&str) -> bool {
+    self.as_str() == *other
+  }
+}
+
+impl<B: StrBuffer> PartialEq<String> for SharedStr<B> {
+  fn eq(&self, other: &String) -> bool {
+    self.as_str() == other
+  }
+}
+
+impl<B: StrBuffer> PartialEq<SharedStr<B>> for str {
+  fn eq(&self, other: &SharedStr<B>) -> bool {
+    self == other.as_str()
+  }
+}
+
+impl<B: StrBuffer> PartialEq<SharedStr<B>> for &str {
+  fn eq(&self, other: &SharedStr<B>) -> bool {
+    *self == other.as_str()
+  }
+}
+
+impl<B: StrBuffer> PartialOrd for SharedStr<B> {
+  fn partial_cmp(&self, other: &SharedStr<B>) -> Option<std::cmp::Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl<B: StrBuffer> Ord for SharedStr<B> {
+  fn cmp(&self, other: &SharedStr<B>) -> std::cmp::Ordering {
+    self.as_str().cmp(other.as_str())
+  }
+}
+
+impl<B: StrBuffer> std::hash::Hash for SharedStr<B> {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    self.as_str().hash(state);
+  }
+}
+
+impl<B: StrBuffer> std::fmt::Display for SharedStr<B> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(self.as_str(), f)
+  }
+}
+
+impl<B: StrBuffer> std::fmt::Debug for SharedStr<B> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(self.as_str(), f)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn substrings_share_buffer() {
+    let s = SharedString::from(String::from("héllo wörld, from a buffer"));
+    let clone = s.clone();
+    let (hello, world) = s.split_at(6);
+
+    assert_eq!(hello, "héllo");
+    assert_eq!(world.slice(1..7), "wörld");
+    assert!(SharedString::ptr_eq_buffer(&clone, &world));
+    assert_eq!(format!("{:?}", hello), "\"héllo\"");
+
+    let set: std::collections::HashSet<_> =
+      vec![hello.clone()].into_iter().collect();
+    assert!(set.contains("héllo"));
+  }
+
+  #[test]
+  fn short_strings_are_inline() {
+    let longest = "ü".repeat(SharedString::INLINE_CAPACITY / 2);
+    let s = SharedString::from(longest.as_str());
+    assert!(SharedString::is_inline(&s));
+    assert_eq!(s, longest);
+    assert_eq!(s.slice(2..6), "üü");
+    assert!(SharedString::is_inline(&s.slice(2..6)));
+    assert!(!SharedString::is_inline(&SharedString::from(longest + "!")));
+
+    // An existing buffer is shared, however short.
+    let buf = Rc::<str>::from("rc");
+    assert!(!SharedString::is_inline(&SharedString::from(Rc::clone(
+      &buf
+    ))));
+    assert_eq!(Rc::strong_count(&buf), 1);
+
+    #[cfg(target_pointer_width = "64")]
+    assert_eq!(std::mem::size_of::<SharedString>(), 32);
+  }
+
+  #[test]
+  #[should_panic(expected = "char boundaries")]
+  fn slice_inside_char() {
+    let _ = SharedString::from("é").slice(1..);
+  }
+
+  #[test]
+  fn arc_string_across_threads() {
+    let s = ArcString::from("shared across threads");
+    let word = s.slice(7..13);
+    let handle = std::thread::spawn(move || word.to_uppercase());
+    assert_eq!(handle.join().unwrap(), "ACROSS");
+  }
+}
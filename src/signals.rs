@@ -0,0 +1,959 @@
+//! Fine-grained reactive values: [`Signal`]s, the [`Computed`] values derived from them, and the
+//! [`Effect`]s that run when they change.
+//!
+//! A [`Signal<T>`][Signal] holds a value that can be read and written through shared handles. A
+//! [`Computed<T>`][Computed] derives a value from signals and other computed values with a
+//! closure, and caches it until one of them changes. An [`Effect`] runs a closure for its side
+//! effects, once when it is made and again after each change to what it read.
+//!
+//! Dependencies aren't declared: whatever a `Computed` or an `Effect` reads while it runs is what
+//! it depends on, and only that, so a branch that stops reading a signal also stops depending on
+//! it. A write marks everything that depends on the signal, directly or through other computed
+//! values, as stale, and only then runs the effects. So an effect never sees a computed value that
+//! is out of date with the signals it was computed from, and it runs once per write however many
+//! paths lead to it from the signal.
+//!
+//! ```
+//! use pointer::signals::{Computed, Effect, Signal};
+//! use pointer::{Rc, RefCell};
+//!
+//! let first = Signal::new("Ada");
+//! let last = Signal::new("Lovelace");
+//!
+//! let full = {
+//!   let (first, last) = (first.clone(), last.clone());
+//!   Computed::new(move || format!("{} {}", first.get(), last.get()))
+//! };
+//!
+//! let greetings = Rc::new(RefCell::new(Vec::new()));
+//! let _effect = {
+//!   let (full, greetings) = (full.clone(), Rc::clone(&greetings));
+//!   Effect::new(move || greetings.borrow_mut().push(full.get()))
+//! };
+//!
+//! last.set("Byron");
+//! assert_eq!(*greetings.borrow(), ["Ada Lovelace", "Ada Byron"]);
+//! ```
+//!
+//! Signals hold strong references to nothing but their values. What depends on a value holds that
+//! value's handle, and the value only holds [`Weak`] references back, so dropping an `Effect` or
+//! the last handle to a `Computed` unsubscribes it. Everything here lives on one thread: the
+//! handles are neither `Send` nor `Sync`.
+//!
+//! An effect that writes a signal it reads runs again after that write, and again after the next,
+//! so it must stop writing at some point.
+//!
+//! # Scheduling
+//!
+//! By default, the effects that a write makes stale run before the write returns, so a loop of
+//! writes runs them once per write. Writes inside [`batch`] run them once, when the batch ends.
+//! With [`Schedule::Deferred`], writes only queue the effects, each of them once however many
+//! writes made it stale, and the host event loop runs them: [`tick`] runs the effects queued so
+//! far, and [`flush`] runs effects until none are left, including those queued by the effects it
+//! runs. Computed values are always computed lazily, once per read after a change.
+//!
+//! ```
+//! use pointer::signals::{self, Effect, Schedule, Signal};
+//! use pointer::{Cell, Rc};
+//!
+//! let count = Signal::new(0);
+//! let runs = Rc::new(Cell::new(0));
+//! let _effect = {
+//!   let (count, runs) = (count.clone(), Rc::clone(&runs));
+//!   Effect::new(move || {
+//!     count.get();
+//!     runs.set(runs.get() + 1);
+//!   })
+//! };
+//!
+//! signals::set_schedule(Schedule::Deferred);
+//! for n in 1..=100 {
+//!   count.set(n);
+//! }
+//! assert_eq!(runs.get(), 1);
+//!
+//! assert!(!signals::tick());
+//! assert_eq!(runs.get(), 2);
+//! # signals::set_schedule(Schedule::Immediate);
+//! ```
+
+use crate::{Cell, Rc, RefCell, Weak};
+
+std::thread_local! {
+  /// The computed value or effect that is running, which subscribes to what
+  /// it reads.
+  static CURRENT: RefCell<Option<Rc<Observer>>> = const { RefCell::new(None) };
+
+  /// The effects to run once a write has marked everything stale.
+  static PENDING: RefCell<Vec<Weak<Observer>>> = const { RefCell::new(Vec::new()) };
+
+  /// Whether this thread is running the pending effects.
+  static FLUSHING: Cell<bool> = const { Cell::new(false) };
+
+  /// When writes run the effects they make stale.
+  static SCHEDULE: Cell<Schedule> = const { Cell::new(Schedule::Immediate) };
+
+  /// How many calls to `batch` are running.
+  static BATCHES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// When the effects that a write makes stale run, on the current thread.
+///
+/// See the [module-level documentation](./index.html#scheduling) for more
+/// details.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Schedule {
+  /// Before the write returns, or when the [`batch`] it is in ends.
+  #[default]
+  Immediate,
+  /// When the host calls [`tick`] or [`flush`].
+  Deferred,
+}
+
+/// Sets when the effects that writes on the current thread make stale run,
+/// and returns the schedule it replaces.
+///
+/// Effects still queued from the [`Deferred`](Schedule::Deferred) schedule
+/// run at the next write, batch, tick or flush.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::signals::{self, Schedule};
+///
+/// let previous = signals::set_schedule(Schedule::Deferred);
+/// assert_eq!(previous, Schedule::Immediate);
+/// # signals::set_schedule(previous);
+/// ```
+pub fn set_schedule(schedule: Schedule) -> Schedule {
+  SCHEDULE.with(|current| current.replace(schedule))
+}
+
+/// Runs `f`, and runs the effects that its writes make stale once it
+/// returns, each of them once.
+///
+/// A batch inside a batch joins the outer one. With the
+/// [`Deferred`](Schedule::Deferred) schedule, the effects stay queued for the
+/// host.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::signals::{self, Effect, Signal};
+/// use pointer::{Rc, RefCell};
+///
+/// let (x, y) = (Signal::new(0), Signal::new(0));
+/// let seen = Rc::new(RefCell::new(Vec::new()));
+/// let _effect = {
+///   let (x, y, seen) = (x.clone(), y.clone(), Rc::clone(&seen));
+///   Effect::new(move || seen.borrow_mut().push((x.get(), y.get())))
+/// };
+///
+/// signals::batch(|| {
+///   x.set(1);
+///   y.set(2);
+/// });
+/// assert_eq!(*seen.borrow(), [(0, 0), (1, 2)]);
+/// ```
+pub fn batch<R>(f: impl FnOnce() -> R) -> R {
+  /// Ends the batch, even if `f` panics.
+  struct EndBatch;
+
+  impl Drop for EndBatch {
+    fn drop(&mut self) {
+      let _ = BATCHES.try_with(|batches| batches.set(batches.get() - 1));
+    }
+  }
+
+  BATCHES.with(|batches| batches.set(batches.get() + 1));
+  let end = EndBatch;
+  let result = f();
+  drop(end);
+  written();
+  result
+}
+
+/// Runs the queued effects, and then those that they queue, until there are
+/// none left.
+///
+/// Called from an effect, this does nothing: the effects are already
+/// running.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::signals::{self, Effect, Schedule, Signal};
+/// use pointer::{Cell, Rc};
+///
+/// signals::set_schedule(Schedule::Deferred);
+/// let count = Signal::new(0);
+/// let shown = Rc::new(Cell::new(0));
+/// let _effect = {
+///   let (count, shown) = (count.clone(), Rc::clone(&shown));
+///   Effect::new(move || shown.set(count.get()))
+/// };
+///
+/// count.set(1);
+/// count.set(2);
+/// assert_eq!(shown.get(), 0);
+///
+/// signals::flush();
+/// assert_eq!(shown.get(), 2);
+/// # signals::set_schedule(Schedule::Immediate);
+/// ```
+pub fn flush() {
+  running_effects(|| {
+    while let Some(next) = PENDING.with(|pending| {
+      let mut pending = pending.borrow_mut();
+      (!pending.is_empty()).then(|| pending.remove(0))
+    }) {
+      if let Some(observer) = next.upgrade() {
+        Observer::run_effect(&observer);
+      }
+    }
+  });
+}
+
+/// Runs the effects queued so far, but not those that they queue, and
+/// returns `true` if effects are left for the next tick.
+///
+/// Called from an effect, this does nothing and returns `true`.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::signals::{self, Effect, Schedule, Signal};
+/// use pointer::{Cell, Rc};
+///
+/// signals::set_schedule(Schedule::Deferred);
+/// let (celsius, fahrenheit) = (Signal::new(0), Signal::new(32));
+/// let _convert = {
+///   let (celsius, fahrenheit) = (celsius.clone(), fahrenheit.clone());
+///   Effect::new(move || fahrenheit.set(celsius.get() * 9 / 5 + 32))
+/// };
+/// let shown = Rc::new(Cell::new(0));
+/// let _show = {
+///   let (fahrenheit, shown) = (fahrenheit.clone(), Rc::clone(&shown));
+///   Effect::new(move || shown.set(fahrenheit.get()))
+/// };
+/// signals::flush();
+///
+/// celsius.set(100);
+/// assert!(signals::tick());
+/// assert_eq!(fahrenheit.get(), 212);
+/// assert_eq!(shown.get(), 32);
+///
+/// assert!(!signals::tick());
+/// assert_eq!(shown.get(), 212);
+/// # signals::set_schedule(Schedule::Immediate);
+/// ```
+pub fn tick() -> bool {
+  running_effects(|| {
+    let queued =
+      PENDING.with(|pending| std::mem::take(&mut *pending.borrow_mut()));
+    for next in queued {
+      if let Some(observer) = next.upgrade() {
+        Observer::run_effect(&observer);
+      }
+    }
+  });
+  PENDING.with(|pending| !pending.borrow().is_empty())
+}
+
+/// A computed value or effect, as seen by what it reads.
+struct Observer {
+  /// How many times it has run. Subscriptions from earlier runs are stale.
+  run: Cell<u64>,
+  kind: Kind,
+}
+
+enum Kind {
+  Computed {
+    dirty: Cell<bool>,
+    subscribers: Subscribers,
+  },
+  Effect(RefCell<Box<dyn FnMut()>>),
+}
+
+impl Observer {
+  /// Returns the state of a computed value's observer.
+  fn computed(&self) -> (&Cell<bool>, &Subscribers) {
+    match &self.kind {
+      Kind::Computed { dirty, subscribers } => (dirty, subscribers),
+      Kind::Effect(_) => unreachable!("a `Computed` has a computed observer"),
+    }
+  }
+
+  /// Marks the observer as out of date, after one of its sources changed.
+  fn mark_stale(this: &Rc<Observer>) {
+    match &this.kind {
+      Kind::Computed { dirty, subscribers } => {
+        if !dirty.replace(true) {
+          subscribers.notify();
+        }
+      }
+      Kind::Effect(_) => PENDING.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        let weak = Rc::downgrade(this);
+        if !pending.iter().any(|p| p.ptr_eq(&weak)) {
+          pending.push(weak);
+        }
+      }),
+    }
+  }
+
+  /// Runs an effect.
+  fn run_effect(this: &Rc<Observer>) {
+    if let Kind::Effect(f) = &this.kind {
+      Observer::track(this, || (f.borrow_mut())());
+    }
+  }
+
+  /// Runs `f` with this observer subscribing to everything it reads, in
+  /// place of its subscriptions from the last run.
+  fn track<R>(this: &Rc<Observer>, f: impl FnOnce() -> R) -> R {
+    this.run.set(this.run.get() + 1);
+    with_current(Some(Rc::clone(this)), f)
+  }
+}
+
+/// Runs `f` with `observer` as the one subscribing to what is read.
+fn with_current<R>(observer: Option<Rc<Observer>>, f: impl FnOnce() -> R) -> R {
+  /// Puts the previous observer back, even if `f` panics.
+  struct Restore(Option<Rc<Observer>>);
+
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      let previous = self.0.take();
+      let _ = CURRENT.try_with(|current| *current.borrow_mut() = previous);
+    }
+  }
+
+  let previous = CURRENT.with(|current| current.replace(observer));
+  let _restore = Restore(previous);
+  f()
+}
+
+/// Runs `run`, which runs effects, unless this thread is already running
+/// them.
+fn running_effects(run: impl FnOnce()) {
+  /// Clears the flag, even if an effect panics.
+  struct Flushing;
+
+  impl Drop for Flushing {
+    fn drop(&mut self) {
+      let _ = FLUSHING.try_with(|flushing| flushing.set(false));
+    }
+  }
+
+  // Effects that write signals queue more effects, which the running loop
+  // takes care of.
+  if FLUSHING.with(|flushing| flushing.replace(true)) {
+    return;
+  }
+  let _flushing = Flushing;
+  run();
+}
+
+/// Runs the effects after a write, if the schedule says so.
+fn written() {
+  let immediate = SCHEDULE.with(Cell::get) == Schedule::Immediate;
+  if immediate && BATCHES.with(Cell::get) == 0 {
+    flush();
+  }
+}
+
+/// The observers of a signal or computed value, with the run that each
+/// subscribed in.
+struct Subscribers {
+  observers: RefCell<Vec<(Weak<Observer>, u64)>>,
+}
+
+impl Subscribers {
+  const fn new() -> Subscribers {
+    Subscribers {
+      observers: RefCell::new(Vec::new()),
+    }
+  }
+
+  /// Subscribes the running observer, if there is one.
+  fn track(&self) {
+    CURRENT.with(|current| {
+      if let Some(observer) = &*current.borrow() {
+        let subscription = (Rc::downgrade(observer), observer.run.get());
+        let mut observers = self.observers.borrow_mut();
+        if !observers.iter().any(|(weak, run)| {
+          weak.ptr_eq(&subscription.0) && *run == subscription.1
+        }) {
+          observers.push(subscription);
+        }
+      }
+    });
+  }
+
+  /// Marks every observer that is still subscribed as stale.
+  ///
+  /// Notifying ends the subscriptions: an observer subscribes again when it
+  /// next runs.
+  fn notify(&self) {
+    let observers = std::mem::take(&mut *self.observers.borrow_mut());
+    for (weak, run) in observers {
+      if let Some(observer) = weak.upgrade() {
+        if observer.run.get() == run {
+          Observer::mark_stale(&observer);
+        }
+      }
+    }
+  }
+}
+
+/// Runs `f` without subscribing the running [`Computed`] or [`Effect`] to
+/// what `f` reads.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::signals::{untracked, Computed, Signal};
+///
+/// let count = Signal::new(1);
+/// let step = Signal::new(10);
+///
+/// let next = {
+///   let (count, step) = (count.clone(), step.clone());
+///   Computed::new(move || count.get() + untracked(|| step.get()))
+/// };
+/// assert_eq!(next.get(), 11);
+///
+/// // `next` doesn't depend on `step`, so it keeps its cached value.
+/// step.set(20);
+/// assert_eq!(next.get(), 11);
+/// ```
+pub fn untracked<R>(f: impl FnOnce() -> R) -> R {
+  with_current(None, f)
+}
+
+/// A value that [`Computed`] values and [`Effect`]s can depend on.
+///
+/// Cloning a `Signal` makes another handle to the same value.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct Signal<T> {
+  inner: Rc<SignalInner<T>>,
+}
+
+struct SignalInner<T> {
+  value: RefCell<T>,
+  subscribers: Subscribers,
+}
+
+impl<T> Signal<T> {
+  /// Creates a new signal holding `value`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::signals::Signal;
+  ///
+  /// let count = Signal::new(0);
+  /// assert_eq!(count.get(), 0);
+  /// ```
+  pub fn new(value: T) -> Signal<T> {
+    Signal {
+      inner: Rc::new(SignalInner {
+        value: RefCell::new(value),
+        subscribers: Subscribers::new(),
+      }),
+    }
+  }
+
+  /// Calls `f` with a reference to the value, and returns what it returns.
+  ///
+  /// A running [`Computed`] or [`Effect`] comes to depend on the signal.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `f` writes to the signal.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::signals::Signal;
+  ///
+  /// let name = Signal::new(String::from("signal"));
+  /// assert_eq!(name.with(|name| name.len()), 6);
+  /// ```
+  pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+    self.inner.subscribers.track();
+    f(&self.inner.value.borrow())
+  }
+
+  /// Replaces the value with `value`, and runs the effects that depend on
+  /// the signal, as the [schedule](Schedule) says.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is being read, from within [`with`].
+  ///
+  /// [`with`]: Signal::with
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::signals::Signal;
+  ///
+  /// let count = Signal::new(0);
+  /// count.set(1);
+  /// assert_eq!(count.get(), 1);
+  /// ```
+  pub fn set(&self, value: T) {
+    self.update(|old| *old = value);
+  }
+
+  /// Changes the value in place with `f`, and runs the effects that depend on
+  /// the signal, as the [schedule](Schedule) says.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is being read, from within [`with`], or if `f` reads
+  /// the signal.
+  ///
+  /// [`with`]: Signal::with
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::signals::Signal;
+  ///
+  /// let items = Signal::new(vec![1]);
+  /// items.update(|items| items.push(2));
+  /// assert_eq!(items.get(), [1, 2]);
+  /// ```
+  pub fn update(&self, f: impl FnOnce(&mut T)) {
+    f(&mut self.inner.value.borrow_mut());
+    self.inner.subscribers.notify();
+    written();
+  }
+}
+
+impl<T: Clone> Signal<T> {
+  /// Returns a clone of the value.
+  ///
+  /// A running [`Computed`] or [`Effect`] comes to depend on the signal.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::signals::Signal;
+  ///
+  /// let count = Signal::new(5);
+  /// assert_eq!(count.get(), 5);
+  /// ```
+  pub fn get(&self) -> T {
+    self.with(T::clone)
+  }
+}
+
+impl<T> Clone for Signal<T> {
+  fn clone(&self) -> Signal<T> {
+    Signal {
+      inner: Rc::clone(&self.inner),
+    }
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Signal<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut d = f.debug_struct("Signal");
+    match self.inner.value.try_borrow() {
+      Ok(value) => d.field("value", &&*value),
+      Err(_) => d.field("value", &format_args!("<borrowed>")),
+    };
+    d.finish()
+  }
+}
+
+/// A value derived from [`Signal`]s and other `Computed` values, and cached
+/// until they change.
+///
+/// The closure runs when the value is first read, and again on the next read
+/// after a change to something it read. Cloning a `Computed` makes another
+/// handle to the same value.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct Computed<T> {
+  inner: Rc<ComputedInner<T>>,
+}
+
+struct ComputedInner<T> {
+  observer: Rc<Observer>,
+  f: RefCell<Box<dyn FnMut() -> T>>,
+  value: RefCell<Option<T>>,
+}
+
+impl<T> Computed<T> {
+  /// Creates a value computed by `f`, which isn't called until the value is
+  /// read.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::signals::{Computed, Signal};
+  ///
+  /// let count = Signal::new(2);
+  /// let doubled = {
+  ///   let count = count.clone();
+  ///   Computed::new(move || count.get() * 2)
+  /// };
+  ///
+  /// assert_eq!(doubled.get(), 4);
+  /// count.set(3);
+  /// assert_eq!(doubled.get(), 6);
+  /// ```
+  pub fn new(f: impl FnMut() -> T + 'static) -> Computed<T> {
+    Computed {
+      inner: Rc::new(ComputedInner {
+        observer: Rc::new(Observer {
+          run: Cell::new(0),
+          kind: Kind::Computed {
+            dirty: Cell::new(true),
+            subscribers: Subscribers::new(),
+          },
+        }),
+        f: RefCell::new(Box::new(f)),
+        value: RefCell::new(None),
+      }),
+    }
+  }
+
+  /// Calls `f` with a reference to the value, computing it first if it is
+  /// out of date, and returns what `f` returns.
+  ///
+  /// A running [`Computed`] or [`Effect`] comes to depend on this value.
+  ///
+  /// # Panics
+  ///
+  /// Panics if computing the value reads it, directly or through other
+  /// computed values.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::signals::{Computed, Signal};
+  ///
+  /// let words = Signal::new(vec!["a", "b"]);
+  /// let joined = {
+  ///   let words = words.clone();
+  ///   Computed::new(move || words.with(|words| words.join(" ")))
+  /// };
+  ///
+  /// assert_eq!(joined.with(|s| s.len()), 3);
+  /// ```
+  pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+    let inner = &*self.inner;
+    let (dirty, subscribers) = inner.observer.computed();
+    subscribers.track();
+    if dirty.replace(false) {
+      // A change while it runs marks the value dirty again.
+      let mut compute = inner
+        .f
+        .try_borrow_mut()
+        .expect("a `Computed` value depends on itself");
+      let value = Observer::track(&inner.observer, &mut *compute);
+      drop(compute);
+      *inner.value.borrow_mut() = Some(value);
+    }
+    let value = inner.value.borrow();
+    f(value
+      .as_ref()
+      .expect("a `Computed` value is set once computed"))
+  }
+}
+
+impl<T: Clone> Computed<T> {
+  /// Returns a clone of the value, computing it first if it is out of date.
+  ///
+  /// A running [`Computed`] or [`Effect`] comes to depend on this value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::signals::{Computed, Signal};
+  ///
+  /// let count = Signal::new(1);
+  /// let next = {
+  ///   let count = count.clone();
+  ///   Computed::new(move || count.get() + 1)
+  /// };
+  ///
+  /// assert_eq!(next.get(), 2);
+  /// ```
+  pub fn get(&self) -> T {
+    self.with(T::clone)
+  }
+}
+
+impl<T> Clone for Computed<T> {
+  fn clone(&self) -> Computed<T> {
+    Computed {
+      inner: Rc::clone(&self.inner),
+    }
+  }
+}
+
+impl<T> std::fmt::Debug for Computed<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let (dirty, _) = self.inner.observer.computed();
+    f.debug_struct("Computed")
+      .field("dirty", &dirty.get())
+      .finish()
+  }
+}
+
+/// A closure that runs once when it is made, and again after each change to
+/// the [`Signal`]s and [`Computed`] values it read.
+///
+/// The effect stops running when the `Effect` is dropped.
+///
+/// See the [module-level documentation](./index.html) for more details.
+#[must_use = "the effect stops running as soon as it is dropped"]
+pub struct Effect {
+  observer: Rc<Observer>,
+}
+
+impl Effect {
+  /// Runs `f`, and runs it again whenever what it read changes, until the
+  /// returned `Effect` is dropped.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::signals::{Effect, Signal};
+  /// use pointer::{Cell, Rc};
+  ///
+  /// let count = Signal::new(1);
+  /// let runs = Rc::new(Cell::new(0));
+  ///
+  /// let effect = {
+  ///   let (count, runs) = (count.clone(), Rc::clone(&runs));
+  ///   Effect::new(move || runs.set(runs.get() + count.get()))
+  /// };
+  /// assert_eq!(runs.get(), 1);
+  ///
+  /// count.set(2);
+  /// assert_eq!(runs.get(), 3);
+  ///
+  /// drop(effect);
+  /// count.set(3);
+  /// assert_eq!(runs.get(), 3);
+  /// ```
+  pub fn new(f: impl FnMut() + 'static) -> Effect {
+    let observer = Rc::new(Observer {
+      run: Cell::new(0),
+      kind: Kind::Effect(RefCell::new(Box::new(f))),
+    });
+    Observer::run_effect(&observer);
+    Effect { observer }
+  }
+}
+
+impl std::fmt::Debug for Effect {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Effect")
+      .field("runs", &self.observer.run.get())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn counter() -> (Rc<Cell<u32>>, impl Fn() -> u32) {
+    let count = Rc::new(Cell::new(0));
+    let read = Rc::clone(&count);
+    (count, move || read.get())
+  }
+
+  #[test]
+  fn computed_values_are_cached_until_a_source_changes() {
+    let a = Signal::new(1);
+    let (runs, run_count) = counter();
+    let doubled = {
+      let (a, runs) = (a.clone(), Rc::clone(&runs));
+      Computed::new(move || {
+        runs.set(runs.get() + 1);
+        a.get() * 2
+      })
+    };
+
+    assert_eq!(run_count(), 0);
+    assert_eq!(doubled.get(), 2);
+    assert_eq!(doubled.get(), 2);
+    assert_eq!(run_count(), 1);
+
+    a.set(5);
+    assert_eq!(run_count(), 1);
+    assert_eq!(doubled.get(), 10);
+    assert_eq!(run_count(), 2);
+  }
+
+  #[test]
+  fn effects_run_once_per_write_without_glitches() {
+    // a -> b, a -> c, (b, c) -> effect: a diamond.
+    let a = Signal::new(1);
+    let b = {
+      let a = a.clone();
+      Computed::new(move || a.get() + 1)
+    };
+    let c = {
+      let a = a.clone();
+      Computed::new(move || a.get() * 10)
+    };
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let _effect = {
+      let seen = Rc::clone(&seen);
+      Effect::new(move || seen.borrow_mut().push((b.get(), c.get())))
+    };
+
+    a.set(2);
+    a.set(3);
+    assert_eq!(*seen.borrow(), [(2, 10), (3, 20), (4, 30)]);
+  }
+
+  #[test]
+  fn dependencies_follow_the_last_run() {
+    let use_left = Signal::new(true);
+    let left = Signal::new("left");
+    let right = Signal::new("right");
+    let (runs, run_count) = counter();
+    let _effect = {
+      let (use_left, left, right) =
+        (use_left.clone(), left.clone(), right.clone());
+      Effect::new(move || {
+        runs.set(runs.get() + 1);
+        if use_left.get() {
+          left.get();
+        } else {
+          right.get();
+        }
+      })
+    };
+
+    right.set("ignored");
+    assert_eq!(run_count(), 1);
+
+    use_left.set(false);
+    assert_eq!(run_count(), 2);
+    left.set("ignored now");
+    assert_eq!(run_count(), 2);
+    right.set("read");
+    assert_eq!(run_count(), 3);
+  }
+
+  #[test]
+  fn effects_that_write_signals_run_the_effects_downstream() {
+    let celsius = Signal::new(0);
+    let fahrenheit = Signal::new(0);
+    let _convert = {
+      let (celsius, fahrenheit) = (celsius.clone(), fahrenheit.clone());
+      Effect::new(move || fahrenheit.set(celsius.get() * 9 / 5 + 32))
+    };
+    let shown = Rc::new(Cell::new(0));
+    let _show = {
+      let (fahrenheit, shown) = (fahrenheit.clone(), Rc::clone(&shown));
+      Effect::new(move || shown.set(fahrenheit.get()))
+    };
+
+    assert_eq!(shown.get(), 32);
+    celsius.set(100);
+    assert_eq!(shown.get(), 212);
+  }
+
+  #[test]
+  fn deferred_writes_run_each_effect_once_per_tick() {
+    let previous = set_schedule(Schedule::Deferred);
+    let a = Signal::new(0);
+    let total = {
+      let a = a.clone();
+      Computed::new(move || a.get() * 2)
+    };
+    let (runs, run_count) = counter();
+    let _effect = {
+      let total = total.clone();
+      Effect::new(move || {
+        total.get();
+        runs.set(runs.get() + 1);
+      })
+    };
+
+    for n in 0..10 {
+      a.set(n);
+    }
+    assert_eq!(run_count(), 1);
+    assert!(!tick());
+    assert_eq!(run_count(), 2);
+    assert!(!tick());
+    assert_eq!(run_count(), 2);
+
+    // A batch with the deferred schedule leaves the effects to the host.
+    batch(|| a.set(20));
+    assert_eq!(run_count(), 2);
+    set_schedule(previous);
+    flush();
+    assert_eq!((run_count(), total.get()), (3, 40));
+  }
+
+  #[test]
+  fn batches_run_effects_when_they_end() {
+    let a = Signal::new(0);
+    let (runs, run_count) = counter();
+    let _effect = {
+      let a = a.clone();
+      Effect::new(move || {
+        a.get();
+        runs.set(runs.get() + 1);
+      })
+    };
+
+    batch(|| {
+      a.set(1);
+      batch(|| a.set(2));
+      a.set(3);
+      assert_eq!(run_count(), 1);
+    });
+    assert_eq!(run_count(), 2);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      batch(|| {
+        a.set(4);
+        panic!("in a batch");
+      })
+    }));
+    assert!(result.is_err());
+    // The batch ended, so writes run effects again.
+    a.set(5);
+    assert_eq!(run_count(), 3);
+  }
+
+  #[test]
+  fn dropped_observers_are_released() {
+    let a = Signal::new(1);
+    let doubled = {
+      let a = a.clone();
+      Computed::new(move || a.get() * 2)
+    };
+    let effect = {
+      let doubled = doubled.clone();
+      Effect::new(move || {
+        doubled.get();
+      })
+    };
+    let weak = Rc::downgrade(&doubled.inner);
+
+    drop((doubled, effect));
+    assert!(weak.upgrade().is_none());
+    // Only the dead subscription is left, and the write clears it.
+    a.set(2);
+    assert!(a.inner.subscribers.observers.borrow().is_empty());
+  }
+}
@@ -0,0 +1,294 @@
+//! A cell for a state machine, whose state only changes by valid transitions: [`StateCell<S>`][StateCell].
+//!
+//! A `RefCell` holding an enum is the usual way to keep a state machine in shared code, but any
+//! `borrow_mut` can write any state into it. A `StateCell` has no `borrow_mut`. Its state changes only
+//! through [`transition`], which hands the current state to a function that returns either the
+//! next state or an error. An error leaves the state as it was, and is returned to the caller, so
+//! rejected transitions are reported where they're attempted. The rules of the machine live in
+//! those functions, and usually in one method on `S` that they all call.
+//!
+//! Listeners registered with [`on_transition`] are told of each transition, with the state before
+//! it and the state after it. They are held as in [`Listeners`]: a listener stays registered for
+//! as long as its [`Subscription`] is alive.
+//!
+//! ```
+//! use pointer::StateCell;
+//!
+//! #[derive(Debug, PartialEq)]
+//! enum Door {
+//!   Open,
+//!   Closed,
+//!   Locked,
+//! }
+//!
+//! impl Door {
+//!   fn lock(&self) -> Result<Door, &'static str> {
+//!     match self {
+//!       Door::Closed => Ok(Door::Locked),
+//!       Door::Open => Err("close the door first"),
+//!       Door::Locked => Err("already locked"),
+//!     }
+//!   }
+//! }
+//!
+//! let door = StateCell::new(Door::Open);
+//! assert_eq!(door.transition(Door::lock), Err("close the door first"));
+//! assert_eq!(*door.state(), Door::Open);
+//!
+//! door.transition(|_| Ok::<_, ()>(Door::Closed)).unwrap();
+//! door.transition(Door::lock).unwrap();
+//! assert_eq!(*door.state(), Door::Locked);
+//! ```
+//!
+//! [`transition`]: StateCell::transition
+//! [`on_transition`]: StateCell::on_transition
+//! [`Listeners`]: crate::Listeners
+//! [`Subscription`]: crate::Subscription
+
+use crate::{Listeners, Rc, RefCell, Subscription};
+
+/// A state that changes only by validated transitions.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct StateCell<S> {
+  state: RefCell<Rc<S>>,
+  /// Called with the states before and after each transition.
+  listeners: Listeners<(Rc<S>, Rc<S>)>,
+}
+
+impl<S> StateCell<S> {
+  /// Creates a new `StateCell` in `state`, with no listeners.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::StateCell;
+  ///
+  /// let cell = StateCell::new("idle");
+  /// assert_eq!(*cell.state(), "idle");
+  /// ```
+  pub fn new(state: S) -> StateCell<S> {
+    StateCell {
+      state: RefCell::new(Rc::new(state)),
+      listeners: Listeners::new(),
+    }
+  }
+
+  /// Returns the current state.
+  ///
+  /// The returned `Rc` keeps showing this state after later transitions.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::StateCell;
+  ///
+  /// let cell = StateCell::new(1);
+  /// let before = cell.state();
+  /// cell.transition(|n| Ok::<_, ()>(n + 1)).unwrap();
+  ///
+  /// assert_eq!((*before, *cell.state()), (1, 2));
+  /// ```
+  pub fn state(&self) -> Rc<S> {
+    Rc::clone(&self.state.borrow())
+  }
+
+  /// Calls `f` with a reference to the current state, and returns what it
+  /// returns.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::StateCell;
+  ///
+  /// let cell = StateCell::new(vec!["draft"]);
+  /// assert_eq!(cell.with(|s| s.len()), 1);
+  /// ```
+  pub fn with<R>(&self, f: impl FnOnce(&S) -> R) -> R {
+    f(&self.state.borrow())
+  }
+
+  /// Moves to the state that `f` returns for the current one, and tells the
+  /// listeners.
+  ///
+  /// # Errors
+  ///
+  /// If `f` returns an error, the state is left as it was, no listener is
+  /// called, and the error is returned.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `f` makes a transition on the same cell.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::StateCell;
+  ///
+  /// let count = StateCell::new(0u8);
+  /// let increment = |n: &u8| n.checked_add(1).ok_or("overflow");
+  ///
+  /// assert_eq!(count.transition(increment), Ok(()));
+  /// assert_eq!(*count.state(), 1);
+  ///
+  /// let full = StateCell::new(u8::MAX);
+  /// assert_eq!(full.transition(increment), Err("overflow"));
+  /// assert_eq!(*full.state(), u8::MAX);
+  /// ```
+  pub fn transition<E>(
+    &self,
+    f: impl FnOnce(&S) -> Result<S, E>,
+  ) -> Result<(), E> {
+    let from = self.state();
+    let to = Rc::new(f(&from)?);
+    let mut state = self.state.borrow_mut();
+    if !Rc::ptr_eq(&state, &from) {
+      panic!("`StateCell::transition` called from within a transition");
+    }
+    *state = Rc::clone(&to);
+    drop(state);
+    self.listeners.emit((from, to));
+    Ok(())
+  }
+
+  /// Registers `f` to be called with the states before and after each
+  /// transition, and returns the [`Subscription`] that keeps it registered.
+  ///
+  /// A listener that makes a transition on the cell isn't told of its own
+  /// transition, as with [`Listeners::emit`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Rc, RefCell, StateCell};
+  ///
+  /// let cell = StateCell::new('a');
+  /// let log = Rc::new(RefCell::new(Vec::new()));
+  ///
+  /// let seen = Rc::clone(&log);
+  /// let _subscription =
+  ///   cell.on_transition(move |from, to| seen.borrow_mut().push((*from, *to)));
+  ///
+  /// cell.transition(|_| Ok::<_, ()>('b')).unwrap();
+  /// assert!(cell.transition(|_| Err(())).is_err());
+  /// assert_eq!(*log.borrow(), [('a', 'b')]);
+  /// ```
+  pub fn on_transition(
+    &self,
+    mut f: impl FnMut(&S, &S) + 'static,
+  ) -> Subscription<(Rc<S>, Rc<S>)> {
+    self
+      .listeners
+      .subscribe(move |(from, to): (Rc<S>, Rc<S>)| f(&from, &to))
+  }
+}
+
+impl<S: Clone> StateCell<S> {
+  /// Consumes the cell, returning the current state, which is cloned if an
+  /// `Rc` returned by [`state`](StateCell::state) still shares it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::StateCell;
+  ///
+  /// let cell = StateCell::new(5);
+  /// assert_eq!(cell.into_inner(), 5);
+  /// ```
+  pub fn into_inner(self) -> S {
+    Rc::try_unwrap(self.state.into_inner()).unwrap_or_else(|rc| (*rc).clone())
+  }
+}
+
+impl<S: Default> Default for StateCell<S> {
+  fn default() -> StateCell<S> {
+    StateCell::new(S::default())
+  }
+}
+
+impl<S: std::fmt::Debug> std::fmt::Debug for StateCell<S> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.state.try_borrow() {
+      Ok(state) => f
+        .debug_struct("StateCell")
+        .field("state", &**state)
+        .field("listeners", &self.listeners.len())
+        .finish(),
+      Err(_) => f.write_str("StateCell { <borrowed> }"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Clone, Debug, PartialEq)]
+  enum Job {
+    Queued,
+    Running(u32),
+    Done,
+  }
+
+  impl Job {
+    fn start(&self) -> Result<Job, String> {
+      match self {
+        Job::Queued => Ok(Job::Running(0)),
+        other => Err(format!("can't start a job that is {:?}", other)),
+      }
+    }
+  }
+
+  #[test]
+  fn rejected_transitions_change_nothing() {
+    let job = StateCell::new(Job::Queued);
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let seen = Rc::clone(&log);
+    let _subscription = job.on_transition(move |from, to| {
+      seen.borrow_mut().push((from.clone(), to.clone()))
+    });
+
+    assert_eq!(job.transition(Job::start), Ok(()));
+    assert_eq!(
+      job.transition(Job::start),
+      Err(String::from("can't start a job that is Running(0)"))
+    );
+    job.transition(|_| Ok::<_, String>(Job::Done)).unwrap();
+
+    assert_eq!(
+      *log.borrow(),
+      [(Job::Queued, Job::Running(0)), (Job::Running(0), Job::Done)]
+    );
+    assert_eq!(job.into_inner(), Job::Done);
+  }
+
+  #[test]
+  #[should_panic(expected = "called from within a transition")]
+  fn nested_transitions_panic() {
+    let job = StateCell::new(Job::Queued);
+    let _ = job.transition(|state| {
+      job.transition(Job::start)?;
+      state.start()
+    });
+  }
+
+  #[test]
+  fn listeners_can_move_on() {
+    let job = Rc::new(StateCell::new(Job::Queued));
+    let this = Rc::downgrade(&job);
+    let _subscription = job.on_transition(move |_, to| {
+      if let Job::Running(n) = to {
+        if let Some(job) = this.upgrade() {
+          let n = *n;
+          job
+            .transition(move |_| Ok::<_, ()>(Job::Running(n + 1)))
+            .unwrap();
+        }
+      }
+    });
+
+    job.transition(Job::start).unwrap();
+    // The listener isn't told of its own transition, so it moved on once.
+    assert_eq!(*job.state(), Job::Running(1));
+  }
+}
@@ -0,0 +1,218 @@
+//! Counts of the live [`Rc`] and [`Arc`] allocations, for the `stats` feature.
+//!
+//! Every allocation made for an `Rc` or an `Arc` is counted, with its size in bytes, until it is
+//! freed, which is when its last strong or [`Weak`] pointer is dropped. [`stats()`] reads the
+//! totals for the whole process from a few atomic counters, cheaply enough to poll from a metrics
+//! endpoint. [`of::<T>()`][of] reads them for the allocations that hold a `T`; types are told apart
+//! by [`std::any::type_name`].
+//!
+//! ```
+//! use pointer::{stats, Rc};
+//!
+//! let before = stats::of::<[u64; 4]>();
+//! let values = Rc::new([1u64, 2, 3, 4]);
+//!
+//! let during = stats::of::<[u64; 4]>();
+//! assert_eq!(during.rc.allocations, before.rc.allocations + 1);
+//! assert!(during.rc.bytes >= before.rc.bytes + 32);
+//!
+//! drop(values);
+//! assert_eq!(stats::of::<[u64; 4]>(), before);
+//! ```
+//!
+//! [`Rc`]: crate::Rc
+//! [`Arc`]: crate::sync::Arc
+//! [`Weak`]: crate::Weak
+
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+/// A snapshot of the live allocations of [`Rc`] and [`Arc`].
+///
+/// [`Rc`]: crate::Rc
+/// [`Arc`]: crate::sync::Arc
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Stats {
+  /// The allocations of [`Rc`](crate::Rc).
+  pub rc: Usage,
+  /// The allocations of [`Arc`](crate::sync::Arc).
+  pub arc: Usage,
+}
+
+/// The number and total size of some live allocations.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Usage {
+  /// The number of allocations.
+  pub allocations: usize,
+  /// Their total size in bytes, counts included.
+  pub bytes: usize,
+}
+
+/// The kind of pointer an allocation is for.
+#[derive(Clone, Copy)]
+pub(crate) enum Kind {
+  Rc,
+  Arc,
+}
+
+struct Counters {
+  allocations: AtomicUsize,
+  bytes: AtomicUsize,
+}
+
+impl Counters {
+  const fn new() -> Counters {
+    Counters {
+      allocations: AtomicUsize::new(0),
+      bytes: AtomicUsize::new(0),
+    }
+  }
+
+  fn usage(&self) -> Usage {
+    Usage {
+      allocations: self.allocations.load(Relaxed),
+      bytes: self.bytes.load(Relaxed),
+    }
+  }
+}
+
+static RC: Counters = Counters::new();
+static ARC: Counters = Counters::new();
+
+/// The counts per value type, by type name.
+static TYPES: std::sync::Mutex<
+  std::collections::BTreeMap<&'static str, Stats>,
+> = std::sync::Mutex::new(std::collections::BTreeMap::new());
+
+fn types() -> std::sync::MutexGuard<
+  'static,
+  std::collections::BTreeMap<&'static str, Stats>,
+> {
+  // The counts are always left consistent, so a panic elsewhere can't have
+  // broken them.
+  TYPES
+    .lock()
+    .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Counts a new allocation of `bytes` bytes holding a `type_name`.
+pub(crate) fn record_alloc(kind: Kind, type_name: &'static str, bytes: usize) {
+  let counters = match kind {
+    Kind::Rc => &RC,
+    Kind::Arc => &ARC,
+  };
+  counters.allocations.fetch_add(1, Relaxed);
+  counters.bytes.fetch_add(bytes, Relaxed);
+
+  let mut types = types();
+  let usage = usage_mut(types.entry(type_name).or_default(), kind);
+  usage.allocations += 1;
+  usage.bytes += bytes;
+}
+
+/// Stops counting an allocation that is being freed.
+pub(crate) fn record_dealloc(
+  kind: Kind,
+  type_name: &'static str,
+  bytes: usize,
+) {
+  let counters = match kind {
+    Kind::Rc => &RC,
+    Kind::Arc => &ARC,
+  };
+  counters.allocations.fetch_sub(1, Relaxed);
+  counters.bytes.fetch_sub(bytes, Relaxed);
+
+  let mut types = types();
+  if let Some(stats) = types.get_mut(type_name) {
+    let usage = usage_mut(stats, kind);
+    usage.allocations -= 1;
+    usage.bytes -= bytes;
+    if *stats == Stats::default() {
+      types.remove(type_name);
+    }
+  }
+}
+
+fn usage_mut(stats: &mut Stats, kind: Kind) -> &mut Usage {
+  match kind {
+    Kind::Rc => &mut stats.rc,
+    Kind::Arc => &mut stats.arc,
+  }
+}
+
+/// Returns the live allocations of every `Rc` and `Arc` in the process.
+///
+/// This is only available with the `stats` feature.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::sync::Arc;
+///
+/// let value = Arc::new(5u32);
+/// let stats = pointer::stats();
+/// assert!(stats.arc.allocations >= 1);
+/// # drop(value);
+/// ```
+pub fn stats() -> Stats {
+  Stats {
+    rc: RC.usage(),
+    arc: ARC.usage(),
+  }
+}
+
+/// Returns the live allocations of the `Rc`s and `Arc`s that hold a `T`.
+///
+/// Unlike [`stats()`], this takes a lock shared with every allocation.
+///
+/// This is only available with the `stats` feature.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::{stats, Rc};
+///
+/// let name: Rc<str> = Rc::from("five");
+/// assert!(stats::of::<str>().rc.allocations >= 1);
+/// ```
+pub fn of<T: ?Sized>() -> Stats {
+  types()
+    .get(std::any::type_name::<T>())
+    .copied()
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn counts_until_the_last_weak_pointer() {
+    struct Probe(#[allow(dead_code)] u64);
+
+    let rc = crate::Rc::new(Probe(1));
+    let weak = crate::Rc::downgrade(&rc);
+    let arc = crate::sync::Arc::new(Probe(2));
+    let stats = of::<Probe>();
+    assert_eq!(stats.rc.allocations, 1);
+    assert_eq!(stats.arc.allocations, 1);
+    assert_eq!(stats.rc.bytes, crate::rc::box_layout::<Probe>().size());
+
+    drop((rc, arc));
+    assert_eq!(of::<Probe>().rc.allocations, 1);
+    assert_eq!(of::<Probe>().arc, Usage::default());
+    drop(weak);
+    assert_eq!(of::<Probe>(), Stats::default());
+  }
+
+  #[test]
+  fn counts_unsized_values() {
+    let slice: crate::sync::Arc<[u32]> = vec![1, 2, 3].into();
+    let usage = of::<[u32]>().arc;
+    assert!(usage.allocations >= 1);
+    assert!(usage.bytes >= 2 * std::mem::size_of::<usize>() + 12);
+    drop(slice);
+  }
+}
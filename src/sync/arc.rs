@@ -0,0 +1,2452 @@
+//! Thread-safe reference-counting pointers. 'Arc' stands for 'Atomically Reference Counted'.
+//!
+//! The type [`Arc<T>`][Arc] provides shared ownership of a value of type `T`, allocated in the heap.
+//! Invoking [`clone`][clone] on [`Arc`] produces a new [`Arc`] instance, which points to the same
+//! allocation on the heap as the source [`Arc`], while increasing a reference count.
+//! When the last [`Arc`] pointer to a given allocation is destroyed, the value stored in that allocation
+//! (often referred to as "inner value") is also dropped.
+//!
+//! Shared references in Rust disallow mutation by default, and [`Arc`] is no exception:
+//! you cannot generally obtain a mutable reference to something inside an [`Arc`].
+//! If you need to mutate through an [`Arc`], use [`Mutex`][mutex], [`RwLock`][rwlock], or one of the [`Atomic`][atomic] types.
+//!
+//! Unlike [`Rc<T>`][rc], `Arc<T>` uses atomic operations for its reference counting.
+//! This means that it is thread-safe. The disadvantage is that atomic operations are more expensive
+//! than ordinary memory accesses. If you are not sharing reference-counted allocations between threads,
+//! consider using [`Rc<T>`][rc] for lower overhead.
+//!
+//! `Arc<T>` will implement [`Send`] and [`Sync`] as long as the `T` implements [`Send`] and [`Sync`].
+//! Why can't you put a non-thread-safe type `T` in an `Arc<T>` to make it thread-safe?
+//! This may be a bit counter-intuitive at first: after all, isn't the point of `Arc<T>` thread safety?
+//! The key is this: `Arc<T>` makes it thread safe to have multiple ownership of the same data,
+//! but it doesn't add thread safety to its data. Consider `Arc<`[`RefCell<T>`][refcell]`>`.
+//! [`RefCell<T>`][refcell] isn't [`Sync`], and if `Arc<T>` was always [`Send`],
+//! `Arc<`[`RefCell<T>`][refcell]`>` would be as well. But then we'd have a problem:
+//! [`RefCell<T>`][refcell] is not thread safe; it keeps track of the borrowing count using non-atomic operations.
+//!
+//! `Arc<T>` automatically dereferences to `T` (via the [`Deref`] trait), so you can call `T`'s
+//! methods on a value of type `Arc<T>`. To avoid name clashes with `T`'s methods,
+//! the methods of `Arc<T>` itself are associated functions, called using function-like syntax:
+//!
+//! ```
+//! use pointer::sync::Arc;
+//!
+//! let my_arc = Arc::new(());
+//!
+//! assert_eq!(Arc::strong_count(&my_arc), 1);
+//! ```
+//!
+//! # Examples
+//!
+//! Sharing some immutable data between threads:
+//!
+//! ```
+//! use pointer::sync::Arc;
+//! use std::thread;
+//!
+//! let five = Arc::new(5);
+//!
+//! let handles: Vec<_> = (0..10)
+//!   .map(|_| {
+//!     let five = Arc::clone(&five);
+//!
+//!     thread::spawn(move || {
+//!       println!("{:?}", five);
+//!     })
+//!   })
+//!   .collect();
+//!
+//! for handle in handles {
+//!   handle.join().unwrap();
+//! }
+//! ```
+//!
+//! [clone]: Clone::clone
+//! [rc]: crate::Rc
+//! [refcell]: crate::RefCell
+//! [mutex]: crate::sync::Mutex
+//! [rwlock]: crate::sync::RwLock
+//! [atomic]: std::sync::atomic
+//! [`Deref`]: std::ops::Deref
+
+use crate::alloc::{Allocator, Global};
+use crate::loom::atomic;
+use crate::loom::atomic::Ordering::{Acquire, Relaxed, Release};
+
+/// A soft limit on the amount of references that may be made to an `Arc`.
+///
+/// Going above this limit will abort your program (although not
+/// necessarily) at _exactly_ `MAX_REFCOUNT + 1` references.
+const MAX_REFCOUNT: usize = (isize::MAX) as usize;
+
+// This is repr(C) to future-proof against possible field-reordering, which
+// would interfere with otherwise safe [into|from]_raw() of transmutable
+// inner types.
+#[repr(C)]
+struct ArcInner<T: ?Sized> {
+  strong: atomic::AtomicUsize,
+
+  // the value usize::MAX acts as a sentinel for temporarily "locking" the
+  // ability to upgrade weak pointers or downgrade strong ones; this is used
+  // to avoid races in `make_mut` and `get_mut`.
+  weak: atomic::AtomicUsize,
+
+  data: T,
+}
+
+unsafe impl<T: ?Sized + Sync + Send> Send for ArcInner<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Sync for ArcInner<T> {}
+
+/// A thread-safe reference-counting pointer. 'Arc' stands for 'Atomically Reference Counted'.
+///
+/// See the [module-level documentation](./index.html) for more details.
+///
+/// The inherent methods of `Arc` are all associated functions, which means that you have to call them as
+/// e.g., [`Arc::strong_count(&value)`][strong_count] instead of `value.strong_count()`. This avoids conflicts
+/// with methods of the inner type `T`.
+///
+/// The allocation comes from the [`Global`] allocator unless `A` says
+/// otherwise; see [`Arc::new_in`].
+///
+/// # Memory layout
+///
+/// An `Arc<T>` is a single non-null pointer to the allocation, next to the
+/// allocator (a zero-sized [`Global`] by default), so `Option<Arc<T>>` is the
+/// same size as `Arc<T>`. This is a guarantee, and it holds for [`Weak`] too.
+///
+/// ```
+/// use pointer::sync::{Arc, Weak};
+/// use std::mem::size_of;
+///
+/// assert_eq!(size_of::<Option<Arc<u64>>>(), size_of::<usize>());
+/// assert_eq!(size_of::<Option<Weak<u64>>>(), size_of::<usize>());
+/// ```
+///
+/// [strong_count]: #method.strong_count
+pub struct Arc<T: ?Sized, A: Allocator = Global> {
+  ptr: std::ptr::NonNull<ArcInner<T>>,
+  phantom: std::marker::PhantomData<ArcInner<T>>,
+  alloc: A,
+}
+
+unsafe impl<T: ?Sized + Sync + Send, A: Allocator + Send> Send for Arc<T, A> {}
+unsafe impl<T: ?Sized + Sync + Send, A: Allocator + Sync> Sync for Arc<T, A> {}
+
+// The memory layout that the docs of `Arc` guarantee.
+const _: () = {
+  use std::mem::size_of;
+  assert!(size_of::<Arc<u8>>() == size_of::<usize>());
+  assert!(size_of::<Option<Arc<u8>>>() == size_of::<usize>());
+  assert!(size_of::<Option<Arc<[u8]>>>() == size_of::<Arc<[u8]>>());
+  assert!(size_of::<Weak<u8>>() == size_of::<usize>());
+  assert!(size_of::<Option<Weak<u8>>>() == size_of::<usize>());
+};
+
+// impl<T: ?Sized + std::marker::Unsize<U>, U: ?Sized> std::ops::CoerceUnsized<Arc<U>> for Arc<T> {}
+// impl<T: ?Sized + std::marker::Unsize<U>, U: ?Sized> std::ops::DispatchFromDyn<Arc<U>> for Arc<T> {}
+
+/// `Weak` is a version of [`Arc`] that holds a non-owning reference to the managed allocation.
+/// The allocation is accessed by calling [`upgrade`] on the `Weak` pointer, which returns an [`Option`]`<`[`Arc`]`<T>>`.
+///
+/// Since a `Weak` reference does not count towards ownership, it will not prevent the value stored in
+/// the allocation from being dropped, and `Weak` itself makes no guarantees about the value still being present.
+/// Thus it may return [`None`] when [`upgrade`]d. Note however that a `Weak` reference *does* prevent
+/// the allocation itself (the backing store) from being deallocated.
+///
+/// A `Weak` pointer is useful for keeping a temporary reference to the allocation managed by [`Arc`]
+/// without preventing its inner value from being dropped. It is also used to prevent circular references
+/// between [`Arc`] pointers, since mutual owning references would never allow either [`Arc`] to be dropped.
+///
+/// The typical way to obtain a `Weak` pointer is to call [`Arc::downgrade`].
+///
+/// A `Weak` has the [memory layout](Arc#memory-layout) of an `Arc`. One made
+/// by [`Weak::new`] doesn't allocate; it holds a non-null address that no
+/// allocation can have, so `Option<Weak<T>>` is still the size of `Weak<T>`.
+///
+/// [`upgrade`]: Weak::upgrade
+pub struct Weak<T: ?Sized, A: Allocator = Global> {
+  // This is a `NonNull` to allow optimizing the size of this type in enums,
+  // but it is not necessarily a valid pointer.
+  // `Weak::new` sets this to `usize::MAX` so that it doesn't need
+  // to allocate space on the heap. That's not a value a real pointer
+  // will ever have because ArcInner has alignment at least 2.
+  // This is only possible when `T: Sized`; unsized `T` never dangle.
+  ptr: std::ptr::NonNull<ArcInner<T>>,
+  alloc: A,
+}
+
+unsafe impl<T: ?Sized + Sync + Send, A: Allocator + Send> Send for Weak<T, A> {}
+unsafe impl<T: ?Sized + Sync + Send, A: Allocator + Sync> Sync for Weak<T, A> {}
+
+/// Helper type to allow accessing the reference counts without
+/// making any assertions about the data field.
+struct WeakInner<'a> {
+  weak: &'a atomic::AtomicUsize,
+  strong: &'a atomic::AtomicUsize,
+}
+
+impl<T> Arc<T> {
+  /// Constructs a new `Arc<T>`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new(5);
+  /// ```
+  #[inline]
+  pub fn new(data: T) -> Arc<T> {
+    Arc::new_in(data, Global)
+  }
+
+  /// Constructs a new `Arc<T>` using a weak reference to itself. Attempting
+  /// to upgrade the weak reference before this function returns will result
+  /// in a `None` value. However, the weak reference may be cloned freely and
+  /// stored for use at a later time.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, Weak};
+  ///
+  /// struct Foo {
+  ///   me: Weak<Foo>,
+  /// }
+  ///
+  /// let foo = Arc::new_cyclic(|me| {
+  ///   // Upgrading is not possible until `new_cyclic` returns.
+  ///   assert!(me.upgrade().is_none());
+  ///   Foo { me: me.clone() }
+  /// });
+  ///
+  /// assert!(Arc::ptr_eq(&foo, &foo.me.upgrade().unwrap()));
+  /// ```
+  pub fn new_cyclic(data_fn: impl FnOnce(&Weak<T>) -> T) -> Arc<T> {
+    // Construct the inner in the "uninitialized" state with a single
+    // weak reference.
+    let layout =
+      std::alloc::Layout::new::<ArcInner<std::mem::MaybeUninit<T>>>();
+    let uninit_ptr = crate::alloc::allocate_or_abort(&Global, layout)
+      .cast::<ArcInner<std::mem::MaybeUninit<T>>>();
+    // SAFETY: The block fits an `ArcInner<MaybeUninit<T>>`.
+    unsafe {
+      uninit_ptr.as_ptr().write(ArcInner {
+        strong: atomic::AtomicUsize::new(0),
+        weak: atomic::AtomicUsize::new(1),
+        data: std::mem::MaybeUninit::<T>::uninit(),
+      });
+    }
+    let init_ptr: std::ptr::NonNull<ArcInner<T>> = uninit_ptr.cast();
+    track(init_ptr);
+
+    let weak = Weak {
+      ptr: init_ptr,
+      alloc: Global,
+    };
+
+    // It's important we don't give up ownership of the weak pointer, or
+    // else the memory might be freed by the time `data_fn` returns. If
+    // we really wanted to pass ownership, we could create an additional
+    // weak pointer for ourselves, but this would result in additional
+    // updates to the weak reference count which might not be necessary
+    // otherwise.
+    let data = data_fn(&weak);
+
+    // Now we can properly initialize the inner value and turn our weak
+    // reference into a strong reference.
+    //
+    // SAFETY: `MaybeUninit<T>` has the same layout as `T`, and no one can
+    // upgrade while the strong count is zero.
+    unsafe {
+      std::ptr::write(std::ptr::addr_of_mut!((*init_ptr.as_ptr()).data), data);
+
+      // The above write to the data field must be visible to any threads which
+      // observe a non-zero strong count. Therefore we need at least "Release" ordering
+      // in order to synchronize with the `compare_exchange_weak` in `Weak::upgrade`.
+      //
+      // "Acquire" ordering is not required. When considering the possible behaviours
+      // of `data_fn` we only need to look at what it could do with a reference to a
+      // non-upgradeable `Weak`:
+      // - It can *clone* the `Weak`, increasing the weak reference count.
+      // - It can drop those clones, decreasing the weak reference count (but never to zero).
+      //
+      // These side effects do not impact us in any way, and no other side effects are
+      // possible with safe code alone.
+      init_ptr.as_ref().strong.store(1, Release);
+    }
+
+    // Strong references should collectively own a shared weak reference,
+    // so don't run the destructor for our old weak reference.
+    std::mem::forget(weak);
+    Self::from_inner(init_ptr)
+  }
+
+  /// Constructs a new `Pin<Arc<T>>`. If `T` does not implement `Unpin`, then
+  /// `data` will be pinned in memory and unable to be moved.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let pinned = Arc::pin(5);
+  /// assert_eq!(*pinned, 5);
+  /// ```
+  pub fn pin(data: T) -> std::pin::Pin<Arc<T>> {
+    // SAFETY: The value lives on the heap and `Arc` never moves it out
+    // through a shared pointer.
+    unsafe { std::pin::Pin::new_unchecked(Arc::new(data)) }
+  }
+
+  /// Constructs an `Arc<T>` from a raw pointer.
+  ///
+  /// The raw pointer must have been previously returned by a call to
+  /// [`Arc::into_raw`].
+  ///
+  /// The user of `from_raw` has to make sure a specific value of `T` is only
+  /// dropped once.
+  ///
+  /// # Safety
+  ///
+  /// `ptr` must come from [`Arc::into_raw`] on an `Arc<T>` whose strong
+  /// reference has not been reclaimed yet.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let x = Arc::new("hello".to_owned());
+  /// let x_ptr = Arc::into_raw(x);
+  ///
+  /// unsafe {
+  ///   // Convert back to an `Arc` to prevent leak.
+  ///   let x = Arc::from_raw(x_ptr);
+  ///   assert_eq!(&*x, "hello");
+  ///
+  ///   // Further calls to `Arc::from_raw(x_ptr)` would be memory-unsafe.
+  /// }
+  ///
+  /// // The memory was freed when `x` went out of scope above, so `x_ptr` is now dangling!
+  /// ```
+  pub unsafe fn from_raw(ptr: *const T) -> Arc<T> {
+    let offset = data_offset::<T>();
+
+    // Reverse the offset to find the original ArcInner.
+    let arc_ptr = ptr.byte_sub(offset).cast::<ArcInner<T>>().cast_mut();
+
+    Self::from_inner(std::ptr::NonNull::new_unchecked(arc_ptr))
+  }
+
+  /// Increments the strong reference count on the `Arc<T>` associated with the
+  /// provided pointer by one.
+  ///
+  /// # Safety
+  ///
+  /// The pointer must have been obtained through `Arc::into_raw`, and the
+  /// associated `Arc` instance must be valid (i.e. the strong count must be at
+  /// least 1) for the duration of this method.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new(5);
+  ///
+  /// unsafe {
+  ///   let ptr = Arc::into_raw(five);
+  ///   Arc::increment_strong_count(ptr);
+  ///
+  ///   // This assertion is deterministic because we haven't shared
+  ///   // the `Arc` between threads.
+  ///   let five = Arc::from_raw(ptr);
+  ///   assert_eq!(2, Arc::strong_count(&five));
+  ///   Arc::decrement_strong_count(ptr);
+  /// }
+  /// ```
+  #[inline]
+  pub unsafe fn increment_strong_count(ptr: *const T) {
+    // Retain Arc, but don't touch refcount by wrapping in ManuallyDrop
+    let arc = std::mem::ManuallyDrop::new(Arc::<T>::from_raw(ptr));
+    // Now increase refcount, but don't drop new refcount either
+    let _arc_clone: std::mem::ManuallyDrop<_> = arc.clone();
+  }
+
+  /// Decrements the strong reference count on the `Arc<T>` associated with the
+  /// provided pointer by one.
+  ///
+  /// # Safety
+  ///
+  /// The pointer must have been obtained through `Arc::into_raw`, and the
+  /// associated `Arc` instance must be valid (i.e. the strong count must be at
+  /// least 1) when invoking this method. This method can be used to release the final
+  /// `Arc` and backing storage, but **should not** be called after the final `Arc` has been
+  /// released.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new(5);
+  ///
+  /// unsafe {
+  ///   let ptr = Arc::into_raw(five);
+  ///   Arc::increment_strong_count(ptr);
+  ///
+  ///   let five = Arc::from_raw(ptr);
+  ///   assert_eq!(2, Arc::strong_count(&five));
+  ///   Arc::decrement_strong_count(ptr);
+  ///   assert_eq!(1, Arc::strong_count(&five));
+  /// }
+  /// ```
+  #[inline]
+  pub unsafe fn decrement_strong_count(ptr: *const T) {
+    drop(Arc::from_raw(ptr));
+  }
+}
+
+impl<T, A: Allocator> Arc<T, A> {
+  /// Constructs a new `Arc<T, A>` in the given allocator.
+  ///
+  /// The allocator is kept in every `Arc` and [`Weak`] to the value, and the
+  /// memory goes back to it once the last of them is dropped. Cloning a
+  /// pointer clones the allocator, so it's usually a reference or a handle.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::alloc::Global;
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new_in(5, Global);
+  /// assert_eq!(*five, 5);
+  /// ```
+  #[inline]
+  pub fn new_in(data: T, alloc: A) -> Arc<T, A> {
+    let layout = std::alloc::Layout::new::<ArcInner<T>>();
+    let ptr =
+      crate::alloc::allocate_or_abort(&alloc, layout).cast::<ArcInner<T>>();
+    // Start the weak pointer count as 1 which is the weak pointer that's
+    // held by all the strong pointers (kinda), see std/rc.rs for more info
+    //
+    // SAFETY: The block fits an `ArcInner<T>`.
+    unsafe {
+      ptr.as_ptr().write(ArcInner {
+        strong: atomic::AtomicUsize::new(1),
+        weak: atomic::AtomicUsize::new(1),
+        data,
+      });
+    }
+    track(ptr);
+    Self::from_inner_in(ptr, alloc)
+  }
+
+  /// Returns the inner value, if the `Arc` has exactly one strong reference.
+  ///
+  /// Otherwise, an [`Err`] is returned with the same `Arc` that was
+  /// passed in.
+  ///
+  /// This will succeed even if there are outstanding weak references.
+  ///
+  /// It is strongly recommended to use [`Arc::into_inner`] instead if you don't
+  /// want to keep the `Arc` in the [`Err`] case.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let x = Arc::new(3);
+  /// assert_eq!(Arc::try_unwrap(x).ok(), Some(3));
+  ///
+  /// let x = Arc::new(4);
+  /// let _y = Arc::clone(&x);
+  /// assert_eq!(*Arc::try_unwrap(x).unwrap_err(), 4);
+  /// ```
+  #[inline]
+  pub fn try_unwrap(this: Self) -> Result<T, Self> {
+    if this
+      .inner()
+      .strong
+      .compare_exchange(1, 0, Relaxed, Relaxed)
+      .is_err()
+    {
+      return Err(this);
+    }
+
+    atomic::fence(Acquire);
+
+    // SAFETY: The strong count is now zero, so we own the value.
+    unsafe {
+      let elem = std::ptr::read(&this.ptr.as_ref().data);
+
+      // Make a weak pointer to clean up the implicit strong-weak reference
+      let this = std::mem::ManuallyDrop::new(this);
+      let _weak = Weak {
+        ptr: this.ptr,
+        alloc: std::ptr::read(&this.alloc),
+      };
+
+      Ok(elem)
+    }
+  }
+
+  /// Returns the inner value, if the `Arc` has exactly one strong reference.
+  ///
+  /// Otherwise, [`None`] is returned and the `Arc` is dropped.
+  ///
+  /// This will succeed even if there are outstanding weak references.
+  ///
+  /// If `Arc::into_inner` is called on every clone of this `Arc`,
+  /// it is guaranteed that exactly one of the calls returns the inner value.
+  /// This means in particular that the inner value is not dropped.
+  ///
+  /// The similar expression `Arc::try_unwrap(this).ok()` does not
+  /// offer such a guarantee: two threads may both see a strong count of two
+  /// and both drop their `Arc` in the `Err` case.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let x = Arc::new(3);
+  /// let y = Arc::clone(&x);
+  ///
+  /// // Two threads calling `Arc::into_inner` on both clones of an `Arc`:
+  /// let x_thread = std::thread::spawn(|| Arc::into_inner(x));
+  /// let y_thread = std::thread::spawn(|| Arc::into_inner(y));
+  ///
+  /// let x_inner_value = x_thread.join().unwrap();
+  /// let y_inner_value = y_thread.join().unwrap();
+  ///
+  /// // One of the threads is guaranteed to receive the inner value:
+  /// assert!(matches!(
+  ///   (x_inner_value, y_inner_value),
+  ///   (None, Some(3)) | (Some(3), None)
+  /// ));
+  /// ```
+  #[inline]
+  pub fn into_inner(this: Self) -> Option<T> {
+    // Make sure that the ordinary `Drop` implementation isn't called as well
+    let this = std::mem::ManuallyDrop::new(this);
+
+    // Following the implementation of `drop` and `drop_slow`
+    if this.inner().strong.fetch_sub(1, Release) != 1 {
+      return None;
+    }
+
+    atomic::fence(Acquire);
+
+    // SAFETY: This mirrors the `drop_slow` path: we were the last strong
+    // reference, so the value is ours to move out.
+    let elem = unsafe { std::ptr::read(&this.ptr.as_ref().data) };
+
+    // Make a weak pointer to clean up the implicit strong-weak reference
+    //
+    // SAFETY: `this` is never dropped, so the allocator moves to the `Weak`.
+    let _weak = Weak {
+      ptr: this.ptr,
+      alloc: unsafe { std::ptr::read(&this.alloc) },
+    };
+
+    Some(elem)
+  }
+}
+
+impl<T: ?Sized> Arc<T> {
+  fn from_inner(ptr: std::ptr::NonNull<ArcInner<T>>) -> Self {
+    Self::from_inner_in(ptr, Global)
+  }
+
+  /// Consumes the `Arc`, returning the wrapped pointer.
+  ///
+  /// To avoid a memory leak the pointer must be converted back to an `Arc` using
+  /// [`Arc::from_raw`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let x = Arc::new("hello".to_owned());
+  /// let x_ptr = Arc::into_raw(x);
+  /// assert_eq!(unsafe { &*x_ptr }, "hello");
+  /// # drop(unsafe { Arc::from_raw(x_ptr) });
+  /// ```
+  pub fn into_raw(this: Self) -> *const T {
+    let ptr = Self::as_ptr(&this);
+    std::mem::forget(this);
+    ptr
+  }
+
+  /// Borrows the `Arc` as a [`ArcBorrow`], a one-word handle that derefs to the
+  /// value and can be turned back into an owned `Arc` with
+  /// [`ArcBorrow::to_owned`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new(5);
+  /// let borrowed = Arc::borrow_arc(&five);
+  ///
+  /// assert_eq!(*borrowed, 5);
+  /// assert_eq!(Arc::strong_count(&five), 1);
+  /// ```
+  #[inline]
+  pub fn borrow_arc(this: &Self) -> ArcBorrow<'_, T> {
+    ArcBorrow {
+      ptr: this.ptr,
+      phantom: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<T: ?Sized, A: Allocator> Arc<T, A> {
+  fn from_inner_in(ptr: std::ptr::NonNull<ArcInner<T>>, alloc: A) -> Self {
+    Self {
+      ptr,
+      phantom: std::marker::PhantomData,
+      alloc,
+    }
+  }
+
+  /// Returns a reference to the allocator of the `Arc`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::alloc::Global;
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new_in(5, Global);
+  /// let _: &Global = Arc::allocator(&five);
+  /// ```
+  pub fn allocator(this: &Self) -> &A {
+    &this.alloc
+  }
+
+  #[inline]
+  fn inner(&self) -> &ArcInner<T> {
+    // This unsafety is ok because while this arc is alive we're guaranteed
+    // that the inner pointer is valid. Furthermore, we know that the
+    // `ArcInner` structure itself is `Sync` because the inner data is
+    // `Sync` as well, so we're ok loaning out an immutable pointer to these
+    // contents.
+    unsafe { self.ptr.as_ref() }
+  }
+
+  /// Provides a raw pointer to the data.
+  ///
+  /// The counts are not affected in any way and the `Arc` is not consumed. The pointer is valid for
+  /// as long as there are strong counts in the `Arc`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let x = Arc::new("hello".to_owned());
+  /// let y = Arc::clone(&x);
+  /// let x_ptr = Arc::as_ptr(&x);
+  /// assert_eq!(x_ptr, Arc::as_ptr(&y));
+  /// assert_eq!(unsafe { &*x_ptr }, "hello");
+  /// ```
+  pub fn as_ptr(this: &Self) -> *const T {
+    let ptr: *mut ArcInner<T> = this.ptr.as_ptr();
+
+    // SAFETY: This cannot go through Deref::deref or Arc::inner because
+    // this is required to retain raw/mut provenance such that e.g. `get_mut` can
+    // write through the pointer after the Arc is recovered through `from_raw`.
+    unsafe { std::ptr::addr_of_mut!((*ptr).data) as *const T }
+  }
+
+  /// Creates a new [`Weak`] pointer to this allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new(5);
+  ///
+  /// let weak_five = Arc::downgrade(&five);
+  /// ```
+  pub fn downgrade(this: &Self) -> Weak<T, A>
+  where
+    A: Clone,
+  {
+    // This Relaxed is OK because we're checking the value in the CAS
+    // below.
+    let mut cur = this.inner().weak.load(Relaxed);
+
+    loop {
+      // check if the weak counter is currently "locked"; if so, spin.
+      if cur == usize::MAX {
+        crate::loom::hint::spin_loop();
+        cur = this.inner().weak.load(Relaxed);
+        continue;
+      }
+
+      // NOTE: this code currently ignores the possibility of overflow
+      // into usize::MAX; in general both Rc and Arc need to be adjusted
+      // to deal with overflow.
+
+      // Unlike with Clone(), we need this to be an Acquire read to
+      // synchronize with the write coming from `is_unique`, so that the
+      // events prior to that write happen before this read.
+      match this.inner().weak.compare_exchange_weak(
+        cur,
+        cur + 1,
+        Acquire,
+        Relaxed,
+      ) {
+        Ok(_) => {
+          // Make sure we do not create a dangling Weak.
+          debug_assert!(!is_dangling(this.ptr));
+          return Weak {
+            ptr: this.ptr,
+            alloc: this.alloc.clone(),
+          };
+        }
+        Err(old) => cur = old,
+      }
+    }
+  }
+
+  /// Gets the number of weak pointers to this allocation.
+  ///
+  /// # Safety
+  ///
+  /// This method by itself is safe, but using it correctly requires extra care.
+  /// Another thread can change the weak count at any time,
+  /// including potentially between calling this method and acting on the result.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new(5);
+  ///
+  /// // This assertion is deterministic because we haven't shared
+  /// // the `Arc` between threads.
+  /// assert_eq!(0, Arc::weak_count(&five));
+  /// ```
+  #[inline]
+  pub fn weak_count(this: &Self) -> usize {
+    let cnt = this.inner().weak.load(Acquire);
+    // If the weak count is currently locked, the value of the
+    // count was 0 just before taking the lock.
+    if cnt == usize::MAX {
+      0
+    } else {
+      cnt - 1
+    }
+  }
+
+  /// Gets the number of strong (`Arc`) pointers to this allocation.
+  ///
+  /// # Safety
+  ///
+  /// This method by itself is safe, but using it correctly requires extra care.
+  /// Another thread can change the strong count at any time,
+  /// including potentially between calling this method and acting on the result.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new(5);
+  /// let _also_five = Arc::clone(&five);
+  ///
+  /// // This assertion is deterministic because we haven't shared
+  /// // the `Arc` between threads.
+  /// assert_eq!(2, Arc::strong_count(&five));
+  /// ```
+  #[inline]
+  pub fn strong_count(this: &Self) -> usize {
+    this.inner().strong.load(Acquire)
+  }
+
+  /// Returns `true` if the two `Arc`s point to the same allocation
+  /// (in a vein similar to [`std::ptr::eq`]).
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new(5);
+  /// let same_five = Arc::clone(&five);
+  /// let other_five = Arc::new(5);
+  ///
+  /// assert!(Arc::ptr_eq(&five, &same_five));
+  /// assert!(!Arc::ptr_eq(&five, &other_five));
+  /// ```
+  #[inline]
+  pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+    this.ptr.as_ptr().cast::<()>() == other.ptr.as_ptr().cast::<()>()
+  }
+
+  /// Returns a mutable reference into the given `Arc`, if there are
+  /// no other `Arc` or [`Weak`] pointers to the same allocation.
+  ///
+  /// Returns [`None`] otherwise, because it is not safe to
+  /// mutate a shared value.
+  ///
+  /// See also [`make_mut`][make_mut], which will [`clone`][clone]
+  /// the inner value when there are other pointers.
+  ///
+  /// [make_mut]: Arc::make_mut
+  /// [clone]: Clone::clone
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let mut x = Arc::new(3);
+  /// *Arc::get_mut(&mut x).unwrap() = 4;
+  /// assert_eq!(*x, 4);
+  ///
+  /// let _y = Arc::clone(&x);
+  /// assert!(Arc::get_mut(&mut x).is_none());
+  /// ```
+  #[inline]
+  pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+    if this.is_unique() {
+      // This unsafety is ok because we're guaranteed that the pointer
+      // returned is the *only* pointer that will ever be returned to T. Our
+      // reference count is guaranteed to be 1 at this point, and we required
+      // the Arc itself to be `mut`, so we're returning the only possible
+      // reference to the inner data.
+      unsafe { Some(Arc::get_mut_unchecked(this)) }
+    } else {
+      None
+    }
+  }
+
+  /// Returns a mutable reference into the given `Arc`, without any check.
+  ///
+  /// # Safety
+  ///
+  /// Any other `Arc` or [`Weak`] pointers to the same allocation must not be
+  /// dereferenced for the duration of the returned borrow.
+  #[inline]
+  pub(crate) unsafe fn get_mut_unchecked(this: &mut Self) -> &mut T {
+    // We are careful to *not* create a reference covering the "count" fields,
+    // as this would alias with concurrent access to the reference counts
+    // (e.g. by `Weak`).
+    &mut (*this.ptr.as_ptr()).data
+  }
+
+  /// Determine whether this is the unique reference (including weak refs) to
+  /// the underlying data.
+  ///
+  /// Note that this requires locking the weak ref count.
+  fn is_unique(&mut self) -> bool {
+    // lock the weak pointer count if we appear to be the sole weak pointer
+    // holder.
+    //
+    // The acquire label here ensures a happens-before relationship with any
+    // writes to `strong` (in particular in `Weak::upgrade`) prior to
+    // decrements of the `weak` count (via `Weak::drop`, which uses release).
+    // If the upgraded weak ref was never dropped, the CAS here will fail so
+    // we do not care to synchronize.
+    if self
+      .inner()
+      .weak
+      .compare_exchange(1, usize::MAX, Acquire, Relaxed)
+      .is_ok()
+    {
+      // This needs to be an `Acquire` to synchronize with the decrement of
+      // the `strong` counter in `drop` -- the only access that happens when
+      // any but the last reference is being dropped.
+      let unique = self.inner().strong.load(Acquire) == 1;
+
+      // The release write here synchronizes with a read in `downgrade`,
+      // effectively preventing the above read of `strong` from happening
+      // after the write.
+      self.inner().weak.store(1, Release); // release the lock
+      unique
+    } else {
+      false
+    }
+  }
+
+  // Non-inlined part of `drop`.
+  #[inline(never)]
+  unsafe fn drop_slow(&mut self) {
+    // Destroy the data at this time, even though we may not free the box
+    // allocation itself (there may still be weak pointers lying around).
+    std::ptr::drop_in_place(&mut (*self.ptr.as_ptr()).data);
+
+    // Drop the weak ref collectively held by all strong references.
+    if self.inner().weak.fetch_sub(1, Release) == 1 {
+      atomic::fence(Acquire);
+      dealloc_inner(self.ptr, &self.alloc);
+    }
+  }
+}
+
+impl<T: Clone, A: Allocator + Clone> Arc<T, A> {
+  /// Makes a mutable reference into the given `Arc`.
+  ///
+  /// If there are other `Arc` pointers to the same allocation, then `make_mut`
+  /// will [`clone`] the inner value to a new allocation to ensure unique
+  /// ownership. This is also referred to as clone-on-write.
+  ///
+  /// However, if there are no other `Arc` pointers to this allocation, but
+  /// some [`Weak`] pointers, then the [`Weak`] pointers will be disassociated
+  /// and the inner value will not be cloned.
+  ///
+  /// See also [`get_mut`], which will fail rather than cloning the inner value
+  /// or diassociating [`Weak`] pointers.
+  ///
+  /// [`clone`]: Clone::clone
+  /// [`get_mut`]: Arc::get_mut
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let mut data = Arc::new(5);
+  ///
+  /// *Arc::make_mut(&mut data) += 1;         // Won't clone anything
+  /// let mut other_data = Arc::clone(&data); // Won't clone inner data
+  /// *Arc::make_mut(&mut data) += 1;         // Clones inner data
+  /// *Arc::make_mut(&mut data) += 1;         // Won't clone anything
+  /// *Arc::make_mut(&mut other_data) *= 2;   // Won't clone anything
+  ///
+  /// // Now `data` and `other_data` point to different allocations.
+  /// assert_eq!(*data, 8);
+  /// assert_eq!(*other_data, 12);
+  /// ```
+  ///
+  /// [`Weak`] pointers will be disassociated:
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let mut data = Arc::new(75);
+  /// let weak = Arc::downgrade(&data);
+  ///
+  /// assert!(75 == *data);
+  /// assert!(75 == *weak.upgrade().unwrap());
+  ///
+  /// *Arc::make_mut(&mut data) += 1;
+  ///
+  /// assert!(76 == *data);
+  /// assert!(weak.upgrade().is_none());
+  /// ```
+  #[inline]
+  pub fn make_mut(this: &mut Self) -> &mut T {
+    // Note that we hold both a strong reference and a weak reference.
+    // Thus, releasing our strong reference only will not, by itself, cause
+    // the memory to be deallocated.
+    //
+    // Use Acquire to ensure that we see any writes to `weak` that happen
+    // before release writes (i.e., decrements) to `strong`. Since we hold a
+    // weak count, there's no chance the ArcInner itself could be
+    // deallocated.
+    if this
+      .inner()
+      .strong
+      .compare_exchange(1, 0, Acquire, Relaxed)
+      .is_err()
+    {
+      // Another strong pointer exists, so we must clone.
+      *this = Arc::new_in((**this).clone(), this.alloc.clone());
+    } else if this.inner().weak.load(Relaxed) != 1 {
+      // Relaxed suffices in the above because this is fundamentally an
+      // optimization: we are always racing with weak pointers being
+      // dropped. Worst case, we end up allocated a new Arc unnecessarily.
+
+      // We removed the last strong ref, but there are additional weak
+      // refs remaining. We'll move the contents to a new Arc, and
+      // invalidate the other weak refs.
+
+      // Note that it is not possible for the read of `weak` to yield
+      // usize::MAX (i.e., locked), since the weak count can only be
+      // locked by a thread with a strong reference.
+
+      // Materialize our own implicit weak pointer, so that it can clean
+      // up the ArcInner as needed.
+      let _weak = Weak {
+        ptr: this.ptr,
+        alloc: this.alloc.clone(),
+      };
+
+      // SAFETY: The strong count is zero, so no one else can reach the
+      // data; move it out and overwrite `this` without running its `Drop`.
+      unsafe {
+        let data = std::ptr::read(&**this);
+        let alloc = std::ptr::read(&this.alloc);
+        std::ptr::write(this, Arc::new_in(data, alloc));
+      }
+    } else {
+      // We were the sole reference of either kind; bump back up the
+      // strong ref count.
+      this.inner().strong.store(1, Release);
+    }
+
+    // As with `get_mut()`, the unsafety is ok because our reference was
+    // either unique to begin with, or became one upon cloning the contents.
+    unsafe { Arc::get_mut_unchecked(this) }
+  }
+}
+
+impl<T: ?Sized, A: Allocator + Clone> Clone for Arc<T, A> {
+  /// Makes a clone of the `Arc` pointer.
+  ///
+  /// This creates another pointer to the same allocation, increasing the
+  /// strong reference count.
+  #[inline]
+  fn clone(&self) -> Arc<T, A> {
+    // Using a relaxed ordering is alright here, as knowledge of the
+    // original reference prevents other threads from erroneously deleting
+    // the object.
+    //
+    // As explained in the [Boost documentation][1], Increasing the
+    // reference counter can always be done with memory_order_relaxed: New
+    // references to an object can only be formed from an existing
+    // reference, and passing an existing reference from one thread to
+    // another must already provide any required synchronization.
+    //
+    // [1]: (www.boost.org/doc/libs/1_55_0/doc/html/atomic/usage_examples.html)
+    let old_size = self.inner().strong.fetch_add(1, Relaxed);
+
+    // However we need to guard against massive refcounts in case someone
+    // is `mem::forget`ing Arcs. If we don't do this the count can overflow
+    // and users will use-after free. We racily saturate to `isize::MAX` on
+    // the assumption that there aren't ~2 billion threads incrementing
+    // the reference count at once. This branch will never be taken in
+    // any realistic program.
+    //
+    // We abort because such a program is incredibly degenerate, and we
+    // don't care to support it.
+    if old_size > MAX_REFCOUNT {
+      crate::alloc::abort_on_overflow();
+    }
+
+    Self::from_inner_in(self.ptr, self.alloc.clone())
+  }
+}
+
+impl<T: ?Sized, A: Allocator> std::ops::Deref for Arc<T, A> {
+  type Target = T;
+
+  #[inline]
+  fn deref(&self) -> &T {
+    &self.inner().data
+  }
+}
+
+impl<T: ?Sized, A: Allocator> Drop for Arc<T, A> {
+  /// Drops the `Arc`.
+  ///
+  /// This will decrement the strong reference count. If the strong reference
+  /// count reaches zero then the only other references (if any) are weak,
+  /// so we `drop` the inner value.
+  #[inline]
+  fn drop(&mut self) {
+    // Because `fetch_sub` is already atomic, we do not need to synchronize
+    // with other threads unless we are going to delete the object. This
+    // same logic applies to the below `fetch_sub` to the `weak` count.
+    if self.inner().strong.fetch_sub(1, Release) != 1 {
+      return;
+    }
+
+    // This fence is needed to prevent reordering of use of the data and
+    // deletion of the data. Because it is marked `Release`, the decreasing
+    // of the reference count synchronizes with this `Acquire` fence. This
+    // means that use of the data happens before decreasing the reference
+    // count, which happens before this fence, which happens before the
+    // deletion of the data.
+    //
+    // As explained in the [Boost documentation][1],
+    //
+    // > It is important to enforce any possible access to the object in one
+    // > thread (through an existing reference) to *happen before* deleting
+    // > the object in a different thread. This is achieved by a "release"
+    // > operation after dropping a reference (any access to the object
+    // > through this reference must obviously happened before), and an
+    // > "acquire" operation before deleting the object.
+    //
+    // [1]: (www.boost.org/doc/libs/1_55_0/doc/html/atomic/usage_examples.html)
+    atomic::fence(Acquire);
+
+    // SAFETY: We were the last strong reference.
+    unsafe {
+      self.drop_slow();
+    }
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display, A: Allocator> std::fmt::Display
+  for Arc<T, A>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug, A: Allocator> std::fmt::Debug for Arc<T, A> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized, A: Allocator> std::fmt::Pointer for Arc<T, A> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Pointer::fmt(&(&**self as *const T), f)
+  }
+}
+
+impl<T: ?Sized> Arc<T> {
+  /// Allocates an `ArcInner<T>` with room for a value of `value_layout`,
+  /// with both counts set to one and the value left uninitialized.
+  ///
+  /// `mem_to_inner` turns the fresh allocation into a (possibly fat) pointer
+  /// to the inner box.
+  fn allocate_for_layout(
+    value_layout: std::alloc::Layout,
+    mem_to_inner: impl FnOnce(*mut u8) -> *mut ArcInner<T>,
+  ) -> std::ptr::NonNull<ArcInner<T>> {
+    let layout = std::alloc::Layout::new::<ArcInner<()>>()
+      .extend(value_layout)
+      .expect("ArcInner layout overflow")
+      .0
+      .pad_to_align();
+
+    let mem = crate::alloc::allocate_or_abort(&Global, layout).as_ptr();
+
+    let inner = mem_to_inner(mem);
+    // SAFETY: `inner` points to fresh memory laid out for `ArcInner<T>`.
+    unsafe {
+      std::ptr::write(
+        std::ptr::addr_of_mut!((*inner).strong),
+        atomic::AtomicUsize::new(1),
+      );
+      std::ptr::write(
+        std::ptr::addr_of_mut!((*inner).weak),
+        atomic::AtomicUsize::new(1),
+      );
+      let inner = std::ptr::NonNull::new_unchecked(inner);
+      track(inner);
+      inner
+    }
+  }
+}
+
+impl<T> Arc<[T]> {
+  /// Rebuilds an `Arc<[T]>` from the parts of a pointer returned by
+  /// [`Arc::into_raw`].
+  ///
+  /// # Safety
+  ///
+  /// `data` and `len` must be the address and length of such a pointer, as
+  /// for [`Arc::from_raw`].
+  #[cfg(feature = "ffi")]
+  pub(crate) unsafe fn from_raw_slice(data: *const T, len: usize) -> Arc<[T]> {
+    // Reverse the offset to find the original ArcInner; it is the same for a
+    // slice as for one element.
+    let start = data.byte_sub(data_offset::<T>()).cast_mut();
+    let inner =
+      std::ptr::slice_from_raw_parts_mut(start, len) as *mut ArcInner<[T]>;
+    Self::from_inner(std::ptr::NonNull::new_unchecked(inner))
+  }
+
+  /// Allocates an `ArcInner<[T]>` for `len` elements, with both counts set to
+  /// one and the elements left uninitialized.
+  fn allocate_for_slice(len: usize) -> std::ptr::NonNull<ArcInner<[T]>> {
+    Arc::allocate_for_layout(
+      std::alloc::Layout::array::<T>(len).expect("slice too large"),
+      |mem| {
+        std::ptr::slice_from_raw_parts_mut(mem as *mut T, len)
+          as *mut ArcInner<[T]>
+      },
+    )
+  }
+}
+
+impl<T> From<Vec<T>> for Arc<[T]> {
+  /// Moves the elements of a [`Vec`] into a new reference-counted slice.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let shared: Arc<[i32]> = Arc::from(vec![1, 2, 3]);
+  /// assert_eq!(&shared[..], [1, 2, 3]);
+  /// ```
+  fn from(mut v: Vec<T>) -> Arc<[T]> {
+    let ptr = Arc::allocate_for_slice(v.len());
+    // SAFETY: The new slice has room for exactly `v.len()` elements. They
+    // are moved out bit for bit, so the `Vec` must forget them before it
+    // frees its buffer.
+    unsafe {
+      let data = std::ptr::addr_of_mut!((*ptr.as_ptr()).data) as *mut T;
+      std::ptr::copy_nonoverlapping(v.as_ptr(), data, v.len());
+      v.set_len(0);
+    }
+    Arc::from_inner(ptr)
+  }
+}
+
+impl<T: Clone> From<&[T]> for Arc<[T]> {
+  /// Clones the elements of a slice into a new reference-counted slice.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let shared: Arc<[u8]> = Arc::from(&b"abc"[..]);
+  /// assert_eq!(&shared[..], b"abc");
+  /// ```
+  fn from(v: &[T]) -> Arc<[T]> {
+    Arc::from(v.to_vec())
+  }
+}
+
+impl<T> std::iter::FromIterator<T> for Arc<[T]> {
+  /// Collects the items of an iterator into a new reference-counted slice.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let evens: Arc<[u32]> = (0..10).filter(|n| n % 2 == 0).collect();
+  /// assert_eq!(&evens[..], [0, 2, 4, 6, 8]);
+  /// ```
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Arc<[T]> {
+    Arc::from(iter.into_iter().collect::<Vec<T>>())
+  }
+}
+
+impl From<&str> for Arc<str> {
+  /// Copies a string slice into a new reference-counted `str`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let shared: Arc<str> = Arc::from("eggplant");
+  /// assert_eq!("eggplant", &shared[..]);
+  /// ```
+  #[inline]
+  fn from(v: &str) -> Arc<str> {
+    let arc = std::mem::ManuallyDrop::new(Arc::<[u8]>::from(v.as_bytes()));
+    // SAFETY: `str` has the same layout as `[u8]`, and the bytes came from a
+    // `str`, so they are valid UTF-8.
+    Arc::from_inner(unsafe {
+      std::ptr::NonNull::new_unchecked(arc.ptr.as_ptr() as *mut ArcInner<str>)
+    })
+  }
+}
+
+impl From<String> for Arc<str> {
+  /// Moves a [`String`] into a new reference-counted `str`.
+  #[inline]
+  fn from(v: String) -> Arc<str> {
+    Arc::from(&v[..])
+  }
+}
+
+impl From<Arc<str>> for Arc<[u8]> {
+  /// Converts a reference-counted `str` into a byte slice without copying.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let string: Arc<str> = Arc::from("eggplant");
+  /// let bytes: Arc<[u8]> = Arc::from(string);
+  /// assert_eq!(b"eggplant", &bytes[..]);
+  /// ```
+  #[inline]
+  fn from(arc: Arc<str>) -> Arc<[u8]> {
+    let arc = std::mem::ManuallyDrop::new(arc);
+    // SAFETY: `str` has the same layout as `[u8]`.
+    Arc::from_inner(unsafe {
+      std::ptr::NonNull::new_unchecked(arc.ptr.as_ptr() as *mut ArcInner<[u8]>)
+    })
+  }
+}
+
+impl<T> From<T> for Arc<T> {
+  /// Moves a `T` into a new `Arc<T>`; the same as [`Arc::new`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let five: Arc<i32> = Arc::from(5);
+  /// assert_eq!(*five, 5);
+  /// ```
+  #[inline]
+  fn from(t: T) -> Arc<T> {
+    Arc::new(t)
+  }
+}
+
+impl<T: ?Sized> From<crate::Boxed<T>> for Arc<T> {
+  /// Moves a [`Boxed`](crate::Boxed) value, which may be unsized, into a new
+  /// `Arc<T>`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  /// use pointer::Boxed;
+  ///
+  /// let boxed: Boxed<str> = Boxed::from(String::from("hi"));
+  /// let shared: Arc<str> = Arc::from(boxed);
+  /// assert_eq!(&*shared, "hi");
+  /// ```
+  fn from(b: crate::Boxed<T>) -> Arc<T> {
+    Arc::from(crate::Boxed::into_box(b))
+  }
+}
+
+impl<T: ?Sized> From<Box<T>> for Arc<T> {
+  /// Moves a boxed value, which may be unsized, into a new `Arc<T>`.
+  ///
+  /// This is the way to get an `Arc<dyn Trait>` without unsized coercions.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let boxed: Box<dyn Fn(u32) -> u32 + Send + Sync> = Box::new(|n| n + 1);
+  /// let shared: Arc<dyn Fn(u32) -> u32 + Send + Sync> = Arc::from(boxed);
+  ///
+  /// assert_eq!(shared(1), 2);
+  /// ```
+  fn from(b: Box<T>) -> Arc<T> {
+    let value_layout = std::alloc::Layout::for_value(&*b);
+    let value_size = value_layout.size();
+    let bptr = Box::into_raw(b);
+
+    let ptr = Arc::allocate_for_layout(value_layout, |mem| {
+      crate::boxed::set_data_ptr(bptr as *mut ArcInner<T>, mem)
+    });
+
+    // SAFETY: The value is moved bit for bit into the new allocation, so the
+    // box must give up its memory without dropping it.
+    unsafe {
+      std::ptr::copy_nonoverlapping(
+        bptr.cast::<u8>(),
+        std::ptr::addr_of_mut!((*ptr.as_ptr()).data).cast::<u8>(),
+        value_size,
+      );
+      if value_size != 0 {
+        std::alloc::dealloc(bptr.cast::<u8>(), value_layout);
+      }
+    }
+
+    Arc::from_inner(ptr)
+  }
+}
+
+impl<T: Default> Default for Arc<T> {
+  /// Creates a new `Arc<T>`, with the `Default` value for `T`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let x: Arc<i32> = Default::default();
+  /// assert_eq!(*x, 0);
+  /// ```
+  fn default() -> Arc<T> {
+    Arc::new(Default::default())
+  }
+}
+
+impl<T: ?Sized + PartialEq, A: Allocator> PartialEq for Arc<T, A> {
+  /// Equality for two `Arc`s.
+  ///
+  /// Two `Arc`s are equal if their inner values are equal, even if they are
+  /// stored in different allocations.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new(5);
+  ///
+  /// assert!(five == Arc::new(5));
+  /// ```
+  #[inline]
+  fn eq(&self, other: &Arc<T, A>) -> bool {
+    **self == **other
+  }
+}
+
+impl<T: ?Sized + Eq, A: Allocator> Eq for Arc<T, A> {}
+
+impl<T: ?Sized + PartialOrd, A: Allocator> PartialOrd for Arc<T, A> {
+  /// Partial comparison for two `Arc`s, by their inner values.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  /// use std::cmp::Ordering;
+  ///
+  /// let five = Arc::new(5);
+  ///
+  /// assert_eq!(Some(Ordering::Less), five.partial_cmp(&Arc::new(6)));
+  /// ```
+  fn partial_cmp(&self, other: &Arc<T, A>) -> Option<std::cmp::Ordering> {
+    (**self).partial_cmp(&**other)
+  }
+
+  fn lt(&self, other: &Arc<T, A>) -> bool {
+    **self < **other
+  }
+
+  fn le(&self, other: &Arc<T, A>) -> bool {
+    **self <= **other
+  }
+
+  fn gt(&self, other: &Arc<T, A>) -> bool {
+    **self > **other
+  }
+
+  fn ge(&self, other: &Arc<T, A>) -> bool {
+    **self >= **other
+  }
+}
+
+impl<T: ?Sized + Ord, A: Allocator> Ord for Arc<T, A> {
+  /// Comparison for two `Arc`s, by their inner values.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  /// use std::cmp::Ordering;
+  ///
+  /// let five = Arc::new(5);
+  ///
+  /// assert_eq!(Ordering::Less, five.cmp(&Arc::new(6)));
+  /// ```
+  fn cmp(&self, other: &Arc<T, A>) -> std::cmp::Ordering {
+    (**self).cmp(&**other)
+  }
+}
+
+impl<T: ?Sized + std::hash::Hash, A: Allocator> std::hash::Hash for Arc<T, A> {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    (**self).hash(state)
+  }
+}
+
+impl<T: ?Sized, A: Allocator> std::borrow::Borrow<T> for Arc<T, A> {
+  fn borrow(&self) -> &T {
+    self
+  }
+}
+
+impl<T: ?Sized, A: Allocator> AsRef<T> for Arc<T, A> {
+  fn as_ref(&self) -> &T {
+    self
+  }
+}
+
+impl<T: ?Sized + std::error::Error, A: Allocator> std::error::Error
+  for Arc<T, A>
+{
+  #[allow(deprecated)]
+  fn description(&self) -> &str {
+    std::error::Error::description(&**self)
+  }
+
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    std::error::Error::source(&**self)
+  }
+}
+
+impl<T: ?Sized, A: Allocator> Unpin for Arc<T, A> {}
+
+impl<T> Weak<T> {
+  /// Constructs a new `Weak<T>`, without allocating any memory.
+  /// Calling [`upgrade`] on the return value always gives [`None`].
+  ///
+  /// [`upgrade`]: Weak::upgrade
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Weak;
+  ///
+  /// let empty: Weak<i64> = Weak::new();
+  /// assert!(empty.upgrade().is_none());
+  /// ```
+  pub const fn new() -> Weak<T> {
+    Weak {
+      // SAFETY: `usize::MAX` is not null.
+      ptr: unsafe {
+        std::ptr::NonNull::new_unchecked(std::ptr::without_provenance_mut(
+          usize::MAX,
+        ))
+      },
+      alloc: Global,
+    }
+  }
+}
+
+impl<T: ?Sized, A: Allocator> Weak<T, A> {
+  /// Attempts to upgrade the `Weak` pointer to an [`Arc`], delaying
+  /// dropping of the inner value if successful.
+  ///
+  /// Returns [`None`] if the inner value has since been dropped.
+  ///
+  /// This includes while it is being dropped: the strong count reaches zero
+  /// before the value's `Drop` runs, so from then on `upgrade` returns `None`
+  /// even though the `Weak` keeps the allocation alive. In particular, a
+  /// `Drop` impl that upgrades a `Weak` to its own value gets `None`, rather
+  /// than a new `Arc` to a value that is going away.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new(5);
+  ///
+  /// let weak_five = Arc::downgrade(&five);
+  ///
+  /// let strong_five = weak_five.upgrade();
+  /// assert!(strong_five.is_some());
+  ///
+  /// // Destroy all strong pointers.
+  /// drop(strong_five);
+  /// drop(five);
+  ///
+  /// assert!(weak_five.upgrade().is_none());
+  /// ```
+  pub fn upgrade(&self) -> Option<Arc<T, A>>
+  where
+    A: Clone,
+  {
+    // We use a CAS loop to increment the strong count instead of a
+    // fetch_add as this function should never take the reference count
+    // from zero to one.
+    let inner = self.inner()?;
+
+    // Relaxed load because any write of 0 that we can observe
+    // leaves the field in a permanently zero state (so a
+    // "stale" read of 0 is fine), and any other value is
+    // confirmed via the CAS below.
+    let mut n = inner.strong.load(Relaxed);
+
+    loop {
+      if n == 0 {
+        return None;
+      }
+
+      // See comments in `Arc::clone` for why we do this (for `mem::forget`).
+      if n > MAX_REFCOUNT {
+        crate::alloc::abort_on_overflow();
+      }
+
+      // Relaxed is fine for the failure case because we don't have any
+      // expectations about the new state. Acquire is necessary for the
+      // success case to synchronise with `Arc::new_cyclic`, when the inner
+      // value can be initialized after `Weak` references have already been
+      // created. In that case, we expect to observe the fully initialized
+      // value.
+      match inner
+        .strong
+        .compare_exchange_weak(n, n + 1, Acquire, Relaxed)
+      {
+        // null checked above
+        Ok(_) => return Some(Arc::from_inner_in(self.ptr, self.alloc.clone())),
+        Err(old) => n = old,
+      }
+    }
+  }
+
+  /// Gets the number of strong (`Arc`) pointers pointing to this allocation.
+  ///
+  /// If `self` was created using [`Weak::new`], this will return 0.
+  pub fn strong_count(&self) -> usize {
+    self.inner().map_or(0, |inner| inner.strong.load(Acquire))
+  }
+
+  /// Gets an approximation of the number of `Weak` pointers pointing to this
+  /// allocation.
+  ///
+  /// If `self` was created using [`Weak::new`], or if there are no remaining
+  /// strong pointers, this will return 0.
+  ///
+  /// # Accuracy
+  ///
+  /// Due to implementation details, the returned value can be off by 1 in
+  /// either direction when other threads are manipulating any `Arc`s or
+  /// `Weak`s pointing to the same allocation.
+  pub fn weak_count(&self) -> usize {
+    self.inner().map_or(0, |inner| {
+      let weak = inner.weak.load(Acquire);
+      let strong = inner.strong.load(Acquire);
+      if strong == 0 {
+        0
+      } else {
+        // Since we observed that there was at least one strong pointer
+        // after reading the weak count, we know that the implicit weak
+        // reference (present whenever any strong references are alive)
+        // was still around when we observed the weak count, and can
+        // therefore safely subtract it.
+        weak - 1
+      }
+    })
+  }
+
+  /// Returns `true` if the two `Weak`s point to the same allocation
+  /// (similar to [`std::ptr::eq`]), or if both don't point to any allocation
+  /// (because they were created with `Weak::new()`).
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, Weak};
+  ///
+  /// let first_arc = Arc::new(5);
+  /// let first = Arc::downgrade(&first_arc);
+  /// let second = Arc::downgrade(&first_arc);
+  ///
+  /// assert!(first.ptr_eq(&second));
+  ///
+  /// let first: Weak<i32> = Weak::new();
+  /// let second: Weak<i32> = Weak::new();
+  /// assert!(first.ptr_eq(&second));
+  /// ```
+  #[inline]
+  pub fn ptr_eq(&self, other: &Self) -> bool {
+    self.ptr.as_ptr().cast::<()>() == other.ptr.as_ptr().cast::<()>()
+  }
+
+  /// Returns `None` when the pointer is dangling and there is no allocated
+  /// `ArcInner` (i.e. when this `Weak` was created by `Weak::new`).
+  #[inline]
+  fn inner(&self) -> Option<WeakInner<'_>> {
+    if is_dangling(self.ptr) {
+      None
+    } else {
+      // We are careful to *not* create a reference covering the "data" field, as
+      // the field may be mutated concurrently (for example, if the last `Arc`
+      // is dropped, the data field will be dropped in-place).
+      //
+      // SAFETY: A non-dangling `Weak` keeps the allocation alive.
+      Some(unsafe {
+        let ptr = self.ptr.as_ptr();
+        WeakInner {
+          strong: &*std::ptr::addr_of!((*ptr).strong),
+          weak: &*std::ptr::addr_of!((*ptr).weak),
+        }
+      })
+    }
+  }
+}
+
+impl<T: ?Sized, A: Allocator + Clone> Clone for Weak<T, A> {
+  /// Makes a clone of the `Weak` pointer that points to the same allocation.
+  #[inline]
+  fn clone(&self) -> Weak<T, A> {
+    let inner = if let Some(inner) = self.inner() {
+      inner
+    } else {
+      return Weak {
+        ptr: self.ptr,
+        alloc: self.alloc.clone(),
+      };
+    };
+    // See comments in Arc::clone() for why this is relaxed. This can use a
+    // fetch_add (ignoring the lock) because the weak count is only locked
+    // where are *no other* weak pointers in existence. (So we can't be
+    // running this code in that case).
+    let old_size = inner.weak.fetch_add(1, Relaxed);
+
+    // See comments in Arc::clone() for why we do this (for mem::forget).
+    if old_size > MAX_REFCOUNT {
+      crate::alloc::abort_on_overflow();
+    }
+
+    Weak {
+      ptr: self.ptr,
+      alloc: self.alloc.clone(),
+    }
+  }
+}
+
+impl<T> Default for Weak<T> {
+  /// Constructs a new `Weak<T>`, without allocating memory.
+  /// Calling [`upgrade`] on the return value always gives [`None`].
+  ///
+  /// [`upgrade`]: Weak::upgrade
+  fn default() -> Weak<T> {
+    Weak::new()
+  }
+}
+
+impl<T: ?Sized, A: Allocator> Drop for Weak<T, A> {
+  /// Drops the `Weak` pointer.
+  fn drop(&mut self) {
+    // If we find out that we were the last weak pointer, then its time to
+    // deallocate the data entirely. See the discussion in Arc::drop() about
+    // the memory orderings
+    //
+    // It's not necessary to check for the locked state here, because the
+    // weak count can only be locked if there was precisely one weak ref,
+    // meaning that drop could only subsequently run ON that remaining weak
+    // ref, which can only happen after the lock is released.
+    let inner = if let Some(inner) = self.inner() {
+      inner
+    } else {
+      return;
+    };
+
+    if inner.weak.fetch_sub(1, Release) == 1 {
+      atomic::fence(Acquire);
+      // SAFETY: The value was dropped with the last strong pointer, and
+      // this was the last weak pointer, so only the memory is left.
+      unsafe { dealloc_inner(self.ptr, &self.alloc) }
+    }
+  }
+}
+
+impl<T: ?Sized, A: Allocator> std::fmt::Debug for Weak<T, A> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "(Weak)")
+  }
+}
+
+/// A borrowed [`Arc<T>`][Arc] that derefs like the owned pointer.
+///
+/// `ArcBorrow<'a, T>` is a single pointer into the allocation, cheaper to pass around than
+/// `&'a Arc<T>` and free of the reference-count traffic of cloning. When a callee does need
+/// ownership, [`to_owned`] turns the handle back into a [`Arc`].
+///
+/// Handles are made with [`Arc::borrow_arc`] or `From<&Arc<T>>`.
+///
+/// ```
+/// use pointer::sync::{Arc, ArcBorrow};
+///
+/// fn sum(values: ArcBorrow<'_, Vec<u32>>) -> u32 {
+///   values.iter().sum()
+/// }
+///
+/// let values = Arc::new(vec![1, 2, 3]);
+/// assert_eq!(sum(Arc::borrow_arc(&values)), 6);
+/// assert_eq!(Arc::strong_count(&values), 1);
+/// ```
+///
+/// [`to_owned`]: ArcBorrow::to_owned
+pub struct ArcBorrow<'a, T: ?Sized> {
+  ptr: std::ptr::NonNull<ArcInner<T>>,
+  phantom: std::marker::PhantomData<&'a Arc<T>>,
+}
+
+unsafe impl<T: ?Sized + Sync + Send> Send for ArcBorrow<'_, T> {}
+unsafe impl<T: ?Sized + Sync + Send> Sync for ArcBorrow<'_, T> {}
+
+impl<'a, T: ?Sized> ArcBorrow<'a, T> {
+  /// Creates a new [`Arc`] pointing to the same allocation, increasing the
+  /// strong reference count.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new(5);
+  /// let owned = Arc::borrow_arc(&five).to_owned();
+  ///
+  /// assert!(Arc::ptr_eq(&five, &owned));
+  /// assert_eq!(Arc::strong_count(&five), 2);
+  /// ```
+  #[inline]
+  pub fn to_owned(self) -> Arc<T> {
+    // The borrowed pointer owns one strong reference already; clone it
+    // without giving that reference back.
+    let this = std::mem::ManuallyDrop::new(Arc::from_inner(self.ptr));
+    Arc::clone(&this)
+  }
+
+  /// Returns a reference to the value that lives as long as the original
+  /// borrow of the [`Arc`], rather than as long as this handle.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Arc;
+  ///
+  /// let five = Arc::new(5);
+  /// let value: &i32 = Arc::borrow_arc(&five).get();
+  ///
+  /// assert_eq!(*value, 5);
+  /// ```
+  #[inline]
+  pub fn get(self) -> &'a T {
+    // SAFETY: The allocation is kept alive by the `Arc` borrowed for `'a`.
+    unsafe { &(*self.ptr.as_ptr()).data }
+  }
+
+  /// Gets the number of strong (`Arc`) pointers to this allocation.
+  #[inline]
+  pub fn strong_count(this: &Self) -> usize {
+    Arc::strong_count(&std::mem::ManuallyDrop::new(Arc::from_inner(this.ptr)))
+  }
+
+  /// Returns `true` if the two handles point to the same allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, ArcBorrow};
+  ///
+  /// let five = Arc::new(5);
+  /// let other_five = Arc::new(5);
+  ///
+  /// assert!(ArcBorrow::ptr_eq(&Arc::borrow_arc(&five), &Arc::borrow_arc(&five)));
+  /// assert!(!ArcBorrow::ptr_eq(&Arc::borrow_arc(&five), &Arc::borrow_arc(&other_five)));
+  /// ```
+  #[inline]
+  pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+    this.ptr.as_ptr().cast::<()>() == other.ptr.as_ptr().cast::<()>()
+  }
+}
+
+impl<T: ?Sized> Clone for ArcBorrow<'_, T> {
+  #[inline]
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<T: ?Sized> Copy for ArcBorrow<'_, T> {}
+
+impl<T: ?Sized> std::ops::Deref for ArcBorrow<'_, T> {
+  type Target = T;
+
+  #[inline]
+  fn deref(&self) -> &T {
+    self.get()
+  }
+}
+
+impl<'a, T: ?Sized> From<&'a Arc<T>> for ArcBorrow<'a, T> {
+  #[inline]
+  fn from(arc: &'a Arc<T>) -> Self {
+    Arc::borrow_arc(arc)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display for ArcBorrow<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for ArcBorrow<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized> std::fmt::Pointer for ArcBorrow<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Pointer::fmt(&(&**self as *const T), f)
+  }
+}
+
+/// Frees the memory backing an `ArcInner` without dropping its value.
+///
+/// # Safety
+///
+/// `ptr` must have been allocated by `alloc`, and the value must already be
+/// dropped.
+unsafe fn dealloc_inner<T: ?Sized, A: Allocator>(
+  ptr: std::ptr::NonNull<ArcInner<T>>,
+  alloc: &A,
+) {
+  let layout = std::alloc::Layout::for_value(ptr.as_ref());
+  #[cfg(feature = "stats")]
+  crate::stats::record_dealloc(
+    crate::stats::Kind::Arc,
+    std::any::type_name::<T>(),
+    layout.size(),
+  );
+  alloc.deallocate(ptr.cast(), layout);
+}
+
+/// Counts a new `ArcInner` in the `stats` counters, if they're enabled.
+#[inline]
+fn track<T: ?Sized>(ptr: std::ptr::NonNull<ArcInner<T>>) {
+  #[cfg(feature = "stats")]
+  crate::stats::record_alloc(
+    crate::stats::Kind::Arc,
+    std::any::type_name::<T>(),
+    // SAFETY: `ptr` points to a fresh allocation with its metadata set.
+    unsafe { std::mem::size_of_val(ptr.as_ref()) },
+  );
+  #[cfg(not(feature = "stats"))]
+  let _ = ptr;
+}
+
+/// Gets the offset within an `ArcInner` for the payload behind a pointer.
+fn data_offset<T>() -> usize {
+  // The header is `repr(C)`, so the payload sits right after the two counts,
+  // padded to the alignment of `T`.
+  std::alloc::Layout::new::<ArcInner<()>>()
+    .extend(std::alloc::Layout::new::<T>())
+    .expect("ArcInner layout overflow")
+    .1
+}
+
+/// Whether `ptr` is the sentinel used by `Weak::new`.
+fn is_dangling<T: ?Sized>(ptr: std::ptr::NonNull<T>) -> bool {
+  ptr.as_ptr().cast::<()>().addr() == usize::MAX
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+
+  use std::sync::atomic::AtomicUsize;
+  use std::sync::atomic::Ordering::SeqCst;
+  use std::thread;
+
+  /// Counts how many times it was dropped.
+  struct Tracked(std::sync::Arc<AtomicUsize>);
+
+  impl Drop for Tracked {
+    fn drop(&mut self) {
+      self.0.fetch_add(1, SeqCst);
+    }
+  }
+
+  struct Canary(*mut AtomicUsize);
+
+  impl Drop for Canary {
+    fn drop(&mut self) {
+      // SAFETY: The tests keep the counter alive for longer than the canary.
+      unsafe {
+        (*self.0).fetch_add(1, SeqCst);
+      }
+    }
+  }
+
+  #[test]
+  fn new() {
+    let five = Arc::new(5);
+    assert_eq!(*five, 5);
+    assert_eq!(Arc::strong_count(&five), 1);
+    assert_eq!(Arc::weak_count(&five), 0);
+  }
+
+  #[test]
+  fn clone() {
+    let five = Arc::new(5);
+    let also_five = Arc::clone(&five);
+
+    assert_eq!(Arc::strong_count(&five), 2);
+    assert!(Arc::ptr_eq(&five, &also_five));
+
+    drop(also_five);
+    assert_eq!(Arc::strong_count(&five), 1);
+  }
+
+  #[test]
+  fn drop_once() {
+    let mut canary = AtomicUsize::new(0);
+    let x = Arc::new(Canary(&mut canary as *mut AtomicUsize));
+    let y = Arc::clone(&x);
+
+    drop(x);
+    assert_eq!(canary.load(SeqCst), 0);
+    drop(y);
+    assert_eq!(canary.load(SeqCst), 1);
+  }
+
+  #[test]
+  fn downgrade_upgrade() {
+    let five = Arc::new(5);
+    let weak_five = Arc::downgrade(&five);
+
+    assert_eq!(Arc::weak_count(&five), 1);
+    assert_eq!(weak_five.strong_count(), 1);
+    assert_eq!(*weak_five.upgrade().unwrap(), 5);
+
+    drop(five);
+    assert!(weak_five.upgrade().is_none());
+    assert_eq!(weak_five.strong_count(), 0);
+    assert_eq!(weak_five.weak_count(), 0);
+  }
+
+  #[test]
+  fn weak_new() {
+    let empty: Weak<i64> = Weak::new();
+
+    assert!(empty.upgrade().is_none());
+    assert_eq!(empty.strong_count(), 0);
+    assert!(empty.clone().ptr_eq(&empty));
+  }
+
+  #[test]
+  fn weak_keeps_allocation_not_value() {
+    let mut canary = AtomicUsize::new(0);
+    let arc = Arc::new(Canary(&mut canary as *mut AtomicUsize));
+    let weak = Arc::downgrade(&arc);
+
+    drop(arc);
+    assert_eq!(canary.load(SeqCst), 1);
+    assert!(weak.upgrade().is_none());
+    drop(weak);
+    assert_eq!(canary.load(SeqCst), 1);
+  }
+
+  #[test]
+  fn upgrade_races_with_drop() {
+    for _ in 0..100 {
+      let dropped = std::sync::Arc::new(AtomicUsize::new(0));
+      let arc = Arc::new(Tracked(std::sync::Arc::clone(&dropped)));
+      let weak = Arc::downgrade(&arc);
+
+      let upgrader = thread::spawn(move || {
+        // An `Arc` obtained here must never observe a dropped value.
+        if let Some(strong) = weak.upgrade() {
+          assert_eq!(strong.0.load(SeqCst), 0);
+        }
+      });
+      drop(arc);
+
+      upgrader.join().unwrap();
+      assert_eq!(dropped.load(SeqCst), 1);
+    }
+  }
+
+  #[test]
+  fn get_mut() {
+    let mut x = Arc::new(3);
+    *Arc::get_mut(&mut x).unwrap() = 4;
+    assert_eq!(*x, 4);
+
+    let y = Arc::clone(&x);
+    assert!(Arc::get_mut(&mut x).is_none());
+    drop(y);
+
+    let weak = Arc::downgrade(&x);
+    assert!(Arc::get_mut(&mut x).is_none());
+    drop(weak);
+    assert!(Arc::get_mut(&mut x).is_some());
+  }
+
+  #[test]
+  fn make_mut_clones_when_shared() {
+    let mut data = Arc::new(5);
+    let other = Arc::clone(&data);
+
+    *Arc::make_mut(&mut data) += 1;
+
+    assert_eq!(*data, 6);
+    assert_eq!(*other, 5);
+    assert!(!Arc::ptr_eq(&data, &other));
+    assert_eq!(Arc::strong_count(&other), 1);
+  }
+
+  #[test]
+  fn make_mut_unique_in_place() {
+    let mut data = Arc::new(5);
+    let before = &*data as *const i32;
+
+    *Arc::make_mut(&mut data) += 1;
+
+    assert_eq!(*data, 6);
+    assert_eq!(&*data as *const i32, before);
+    assert_eq!(Arc::strong_count(&data), 1);
+  }
+
+  #[test]
+  fn make_mut_disassociates_weak() {
+    let mut canary = AtomicUsize::new(0);
+    let mut data =
+      Arc::new(vec![Arc::new(Canary(&mut canary as *mut AtomicUsize))]);
+    let weak = Arc::downgrade(&data);
+
+    let first = Arc::clone(&data[0]);
+    Arc::make_mut(&mut data).push(first);
+
+    assert!(weak.upgrade().is_none());
+    assert_eq!(Arc::weak_count(&data), 0);
+    assert_eq!(data.len(), 2);
+
+    drop(weak);
+    drop(data);
+    assert_eq!(canary.load(SeqCst), 1);
+  }
+
+  #[test]
+  fn make_mut_across_threads() {
+    let data = Arc::new(0usize);
+
+    let handles: Vec<_> = (0..8)
+      .map(|i| {
+        let mut local = Arc::clone(&data);
+        thread::spawn(move || {
+          *Arc::make_mut(&mut local) += i;
+          *local
+        })
+      })
+      .collect();
+
+    for (i, handle) in handles.into_iter().enumerate() {
+      assert_eq!(handle.join().unwrap(), i);
+    }
+    assert_eq!(*data, 0);
+  }
+
+  #[test]
+  fn try_unwrap() {
+    let x = Arc::new(3);
+    assert_eq!(Arc::try_unwrap(x).ok(), Some(3));
+
+    let x = Arc::new(4);
+    let y = Arc::clone(&x);
+    let x = Arc::try_unwrap(x).unwrap_err();
+    drop(y);
+
+    let weak = Arc::downgrade(&x);
+    assert_eq!(Arc::try_unwrap(x).ok(), Some(4));
+    assert!(weak.upgrade().is_none());
+  }
+
+  #[test]
+  fn into_inner_exactly_once() {
+    for _ in 0..100 {
+      let x = Arc::new(vec![1, 2, 3]);
+      let y = Arc::clone(&x);
+
+      let x_thread = thread::spawn(move || Arc::into_inner(x));
+      let y_thread = thread::spawn(move || Arc::into_inner(y));
+
+      let found = x_thread.join().unwrap().into_iter().count()
+        + y_thread.join().unwrap().into_iter().count();
+      assert_eq!(found, 1);
+    }
+  }
+
+  #[test]
+  fn new_cyclic() {
+    struct Node {
+      me: Weak<Node>,
+      value: u8,
+    }
+
+    let node = Arc::new_cyclic(|me| {
+      assert!(me.upgrade().is_none());
+      Node {
+        me: me.clone(),
+        value: 7,
+      }
+    });
+
+    assert_eq!(Arc::strong_count(&node), 1);
+    assert_eq!(Arc::weak_count(&node), 1);
+    assert_eq!(node.me.upgrade().unwrap().value, 7);
+  }
+
+  #[test]
+  fn upgrade_fails_while_the_value_drops() {
+    struct Node {
+      me: Weak<Node>,
+      seen: Arc<std::sync::Mutex<Option<(bool, usize)>>>,
+    }
+
+    impl Drop for Node {
+      fn drop(&mut self) {
+        let upgraded = self.me.upgrade();
+        *self.seen.lock().unwrap() =
+          Some((upgraded.is_some(), self.me.strong_count()));
+      }
+    }
+
+    let seen = Arc::new(std::sync::Mutex::new(None));
+    let node = Arc::new_cyclic(|me| Node {
+      me: me.clone(),
+      seen: Arc::clone(&seen),
+    });
+    let weak = Arc::downgrade(&node);
+
+    drop(node);
+    assert_eq!(*seen.lock().unwrap(), Some((false, 0)));
+    assert!(weak.upgrade().is_none());
+  }
+
+  #[test]
+  fn into_raw_from_raw() {
+    let mut canary = AtomicUsize::new(0);
+    let x = Arc::new(Canary(&mut canary as *mut AtomicUsize));
+    let ptr = Arc::into_raw(x);
+
+    // SAFETY: `ptr` came from `into_raw` and is reclaimed once.
+    unsafe {
+      Arc::increment_strong_count(ptr);
+      let x = Arc::from_raw(ptr);
+      assert_eq!(Arc::strong_count(&x), 2);
+      Arc::decrement_strong_count(ptr);
+      assert_eq!(Arc::strong_count(&x), 1);
+    }
+    assert_eq!(canary.load(SeqCst), 1);
+  }
+
+  #[test]
+  fn from_raw_over_aligned() {
+    #[repr(align(64))]
+    struct Aligned(u8);
+
+    let ptr = Arc::into_raw(Arc::new(Aligned(9)));
+    assert_eq!(ptr.addr() % 64, 0);
+    // SAFETY: `ptr` came from `into_raw`.
+    let x = unsafe { Arc::from_raw(ptr) };
+    assert_eq!(x.0, 9);
+  }
+
+  #[test]
+  fn share_between_threads() {
+    let data = Arc::new(vec![1, 2, 3]);
+
+    let handles: Vec<_> = (0..8)
+      .map(|_| {
+        let data = Arc::clone(&data);
+        thread::spawn(move || {
+          let local = Arc::clone(&data);
+          local.iter().sum::<i32>()
+        })
+      })
+      .collect();
+
+    for handle in handles {
+      assert_eq!(handle.join().unwrap(), 6);
+    }
+    assert_eq!(Arc::strong_count(&data), 1);
+  }
+
+  #[test]
+  fn borrow_arc_across_scoped_threads() {
+    let data = Arc::new(vec![1, 2, 3]);
+    let borrowed = Arc::borrow_arc(&data);
+
+    let owned: Vec<Arc<Vec<i32>>> = thread::scope(|s| {
+      let handles: Vec<_> = (0..4)
+        .map(|_| s.spawn(move || borrowed.to_owned()))
+        .collect();
+      handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    assert_eq!(ArcBorrow::strong_count(&borrowed), 5);
+    drop(owned);
+    assert_eq!(Arc::strong_count(&data), 1);
+    assert_eq!(borrowed.get().iter().sum::<i32>(), 6);
+  }
+
+  #[test]
+  fn from_box_unsized() {
+    let dropped = std::sync::Arc::new(AtomicUsize::new(0));
+    let tracked = Tracked(std::sync::Arc::clone(&dropped));
+    let boxed: Box<dyn Fn() -> usize + Send + Sync> =
+      Box::new(move || tracked.0.load(SeqCst));
+    let shared: Arc<dyn Fn() -> usize + Send + Sync> = Arc::from(boxed);
+
+    let other = Arc::clone(&shared);
+    let seen = thread::spawn(move || (*other)()).join().unwrap();
+    assert_eq!(seen, 0);
+    assert_eq!(dropped.load(SeqCst), 0);
+
+    drop(shared);
+    assert_eq!(dropped.load(SeqCst), 1);
+
+    let slice: Arc<[String]> =
+      Arc::from(vec!["a".to_owned()].into_boxed_slice());
+    assert_eq!(&slice[..], ["a"]);
+
+    let zst: Arc<[()]> = Arc::from(Box::<[()]>::from(vec![(), ()]));
+    assert_eq!(zst.len(), 2);
+  }
+
+  #[test]
+  fn str_conversions() {
+    let s: Arc<str> = Arc::from(String::from("hello"));
+    let bytes: Arc<[u8]> = Arc::from(Arc::clone(&s));
+
+    assert_eq!(&*s, "hello");
+    assert_eq!(&bytes[..], b"hello");
+    assert_eq!(Arc::strong_count(&s), 2);
+  }
+
+  #[test]
+  fn compare_and_hash_by_value() {
+    use std::collections::HashSet;
+
+    let set: HashSet<Arc<str>> =
+      ["a", "b", "a"].iter().map(|s| Arc::from(*s)).collect();
+    assert_eq!(set.len(), 2);
+    assert!(set.contains("a"));
+
+    let mut sorted = vec![Arc::new(3), Arc::new(1), Arc::new(2)];
+    sorted.sort();
+    assert_eq!(sorted, [Arc::new(1), Arc::new(2), Arc::new(3)]);
+  }
+
+  #[test]
+  fn error_forwards_source() {
+    #[derive(Debug)]
+    struct Inner;
+    impl std::fmt::Display for Inner {
+      fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "inner")
+      }
+    }
+    impl std::error::Error for Inner {}
+
+    #[derive(Debug)]
+    struct Outer(Inner);
+    impl std::fmt::Display for Outer {
+      fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "outer")
+      }
+    }
+    impl std::error::Error for Outer {
+      fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+      }
+    }
+
+    let err: Box<dyn std::error::Error> = Box::new(Arc::new(Outer(Inner)));
+    assert_eq!(err.to_string(), "outer");
+    assert_eq!(err.source().unwrap().to_string(), "inner");
+  }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+  use super::*;
+
+  use ::loom::thread;
+  use std::sync::atomic::AtomicUsize;
+  use std::sync::atomic::Ordering::SeqCst;
+
+  /// Counts how many times it was dropped.
+  struct Tracked(std::sync::Arc<AtomicUsize>);
+
+  impl Drop for Tracked {
+    fn drop(&mut self) {
+      self.0.fetch_add(1, SeqCst);
+    }
+  }
+
+  #[test]
+  fn loom_clone_drop() {
+    ::loom::model(|| {
+      let dropped = std::sync::Arc::new(AtomicUsize::new(0));
+      let a = Arc::new(Tracked(std::sync::Arc::clone(&dropped)));
+      let b = Arc::clone(&a);
+
+      let t = thread::spawn(move || drop(b));
+      drop(a);
+      t.join().unwrap();
+
+      assert_eq!(dropped.load(SeqCst), 1);
+    });
+  }
+
+  #[test]
+  fn loom_upgrade_drop() {
+    ::loom::model(|| {
+      let dropped = std::sync::Arc::new(AtomicUsize::new(0));
+      let strong = Arc::new(Tracked(std::sync::Arc::clone(&dropped)));
+      let weak = Arc::downgrade(&strong);
+
+      let t = thread::spawn(move || {
+        if let Some(upgraded) = weak.upgrade() {
+          assert_eq!(upgraded.0.load(SeqCst), 0);
+        }
+      });
+      drop(strong);
+      t.join().unwrap();
+
+      assert_eq!(dropped.load(SeqCst), 1);
+    });
+  }
+
+  #[test]
+  fn loom_downgrade_get_mut() {
+    ::loom::model(|| {
+      let mut a = Arc::new(0);
+      let b = Arc::clone(&a);
+
+      let t = thread::spawn(move || {
+        let weak = Arc::downgrade(&b);
+        drop(b);
+        weak
+      });
+      // Never hand out `&mut` while another thread may still upgrade.
+      if let Some(value) = Arc::get_mut(&mut a) {
+        *value += 1;
+      }
+      let weak = t.join().unwrap();
+      if let Some(upgraded) = weak.upgrade() {
+        assert!(*upgraded <= 1);
+      }
+    });
+  }
+
+  #[test]
+  fn loom_into_inner() {
+    ::loom::model(|| {
+      let a = Arc::new(5);
+      let b = Arc::clone(&a);
+
+      let t = thread::spawn(move || Arc::into_inner(b));
+      let mine = Arc::into_inner(a);
+      let theirs = t.join().unwrap();
+
+      assert_eq!(mine.into_iter().chain(theirs).count(), 1);
+    });
+  }
+
+  #[test]
+  fn loom_make_mut_weak() {
+    ::loom::model(|| {
+      let mut a = Arc::new(1);
+      let weak = Arc::downgrade(&a);
+
+      let t = thread::spawn(move || weak.upgrade().map(|x| *x));
+      *Arc::make_mut(&mut a) += 1;
+      let seen = t.join().unwrap();
+
+      assert_eq!(*a, 2);
+      assert!(seen.is_none() || seen == Some(1) || seen == Some(2));
+    });
+  }
+}
@@ -0,0 +1,425 @@
+//! An atomically swappable [`Arc`] slot.
+//!
+//! [`ArcCell<T>`][ArcCell] holds an [`Arc<T>`][Arc] that many threads can [`load`], [`store`] and
+//! [`swap`] at the same time. Readers get their own snapshot `Arc`, so they keep seeing a
+//! consistent value for as long as they hold on to it, while writers replace the whole value.
+//! This read-copy-update pattern suits read-mostly data such as configuration.
+//!
+//! ```
+//! use pointer::sync::{Arc, ArcCell};
+//!
+//! let config = ArcCell::new(Arc::new(String::from("v1")));
+//!
+//! let snapshot = config.load();
+//! config.store(Arc::new(String::from("v2")));
+//!
+//! // The old snapshot is unaffected by the update.
+//! assert_eq!(*snapshot, "v1");
+//! assert_eq!(*config.load(), "v2");
+//! ```
+//!
+//! Loads never block each other. A writer only waits for the loads that were
+//! already in flight when it published its value; each of them does no more than
+//! bump a reference count.
+//!
+//! [`load`]: ArcCell::load
+//! [`store`]: ArcCell::store
+//! [`swap`]: ArcCell::swap
+
+use super::Arc;
+use crate::loom::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering::*};
+use crate::loom::hint;
+
+/// A thread-safe, atomically replaceable [`Arc<T>`][Arc].
+///
+/// See the [module-level documentation](index.html) for more.
+pub struct ArcCell<T> {
+  /// Pointer obtained from [`Arc::into_raw`]; the slot owns one strong count.
+  ptr: AtomicPtr<T>,
+  /// In-flight loads, split by the parity of `epoch` they started in.
+  readers: [AtomicUsize; 2],
+  /// Bumped by every writer after it has published a new pointer.
+  epoch: AtomicUsize,
+  /// Serializes writers.
+  writer: AtomicBool,
+  phantom: std::marker::PhantomData<Arc<T>>,
+}
+
+impl<T> ArcCell<T> {
+  /// Creates a new `ArcCell` holding `value`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, ArcCell};
+  ///
+  /// let cell = ArcCell::new(Arc::new(5));
+  /// ```
+  pub fn new(value: Arc<T>) -> ArcCell<T> {
+    ArcCell {
+      ptr: AtomicPtr::new(Arc::into_raw(value) as *mut T),
+      readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+      epoch: AtomicUsize::new(0),
+      writer: AtomicBool::new(false),
+      phantom: std::marker::PhantomData,
+    }
+  }
+
+  /// Returns a snapshot of the current value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, ArcCell};
+  ///
+  /// let cell = ArcCell::new(Arc::new(5));
+  /// let five = cell.load();
+  ///
+  /// assert_eq!(*five, 5);
+  /// assert_eq!(Arc::strong_count(&five), 2);
+  /// ```
+  pub fn load(&self) -> Arc<T> {
+    let bucket = self.enter();
+    let ptr = self.ptr.load(Acquire);
+    // SAFETY: The slot's strong count cannot be released while we are
+    // registered as a reader, see `wait_for_readers`.
+    unsafe { Arc::increment_strong_count(ptr) };
+    self.readers[bucket].fetch_sub(1, Release);
+
+    // SAFETY: We took a strong count for ourselves just above.
+    unsafe { Arc::from_raw(ptr) }
+  }
+
+  /// Replaces the current value with `value`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, ArcCell};
+  ///
+  /// let cell = ArcCell::new(Arc::new(5));
+  /// cell.store(Arc::new(6));
+  ///
+  /// assert_eq!(*cell.load(), 6);
+  /// ```
+  pub fn store(&self, value: Arc<T>) {
+    drop(self.swap(value));
+  }
+
+  /// Replaces the current value with `value`, returning the previous one.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, ArcCell};
+  ///
+  /// let cell = ArcCell::new(Arc::new(5));
+  /// let old = cell.swap(Arc::new(6));
+  ///
+  /// assert_eq!(*old, 5);
+  /// assert_eq!(*cell.load(), 6);
+  /// ```
+  pub fn swap(&self, value: Arc<T>) -> Arc<T> {
+    self.lock_writer();
+    let old = self.ptr.swap(Arc::into_raw(value) as *mut T, AcqRel);
+    self.wait_for_readers();
+    self.unlock_writer();
+
+    // SAFETY: `old` came from `Arc::into_raw`, and no reader can still be
+    // about to bump its count.
+    unsafe { Arc::from_raw(old) }
+  }
+
+  /// Stores `new` if the current value is the same allocation as `current`.
+  ///
+  /// Returns the previous value on success, or gives `new` back as the
+  /// error if another writer got there first.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, ArcCell};
+  ///
+  /// let cell = ArcCell::new(Arc::new(5));
+  /// let current = cell.load();
+  ///
+  /// assert!(cell.compare_and_swap(&current, Arc::new(6)).is_ok());
+  /// assert_eq!(*cell.compare_and_swap(&current, Arc::new(7)).unwrap_err(), 7);
+  /// assert_eq!(*cell.load(), 6);
+  /// ```
+  pub fn compare_and_swap(
+    &self,
+    current: &Arc<T>,
+    new: Arc<T>,
+  ) -> Result<Arc<T>, Arc<T>> {
+    self.lock_writer();
+    if !std::ptr::eq(self.ptr.load(Acquire), Arc::as_ptr(current)) {
+      self.unlock_writer();
+      return Err(new);
+    }
+    let old = self.ptr.swap(Arc::into_raw(new) as *mut T, AcqRel);
+    self.wait_for_readers();
+    self.unlock_writer();
+
+    // SAFETY: See `swap`.
+    Ok(unsafe { Arc::from_raw(old) })
+  }
+
+  /// Updates the value read-copy-update style: `f` computes the new value from
+  /// a snapshot of the current one, retrying if another writer raced us.
+  ///
+  /// Returns the value that was replaced.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, ArcCell};
+  ///
+  /// let counter = ArcCell::new(Arc::new(0));
+  /// counter.rcu(|n| n + 1);
+  ///
+  /// assert_eq!(*counter.load(), 1);
+  /// ```
+  pub fn rcu(&self, mut f: impl FnMut(&T) -> T) -> Arc<T> {
+    let mut current = self.load();
+    loop {
+      let new = Arc::new(f(&current));
+      match self.compare_and_swap(&current, new) {
+        Ok(old) => return old,
+        Err(_) => current = self.load(),
+      }
+    }
+  }
+
+  /// Consumes the `ArcCell`, returning the value it held.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, ArcCell};
+  ///
+  /// let cell = ArcCell::new(Arc::new(5));
+  /// assert_eq!(*cell.into_inner(), 5);
+  /// ```
+  pub fn into_inner(self) -> Arc<T> {
+    let this = std::mem::ManuallyDrop::new(self);
+    // SAFETY: We own the slot's strong count and nobody else can reach it.
+    unsafe { Arc::from_raw(this.ptr.load(Acquire)) }
+  }
+
+  /// Registers a load, returning the `readers` bucket it was counted in.
+  fn enter(&self) -> usize {
+    loop {
+      let epoch = self.epoch.load(Acquire);
+      let bucket = epoch & 1;
+      self.readers[bucket].fetch_add(1, AcqRel);
+
+      // If a writer moved on in the meantime it may already have stopped
+      // looking at this bucket; back out and join the current one instead.
+      if self.epoch.load(Acquire) == epoch {
+        return bucket;
+      }
+      self.readers[bucket].fetch_sub(1, Release);
+    }
+  }
+
+  /// Waits for every load that could still observe the pointer we just
+  /// replaced. Must be called with the writer lock held.
+  fn wait_for_readers(&self) {
+    // Loads starting from now on go to the other bucket and will see the
+    // new pointer, so we only need to wait for this one to drain.
+    let bucket = self.epoch.fetch_add(1, AcqRel) & 1;
+
+    // This must be a read-modify-write rather than a plain load: it is
+    // ordered after any reader's increment that it does not observe, so such
+    // a reader is guaranteed to see both the new epoch and the new pointer.
+    while self.readers[bucket].fetch_add(0, AcqRel) != 0 {
+      hint::spin_loop();
+    }
+  }
+
+  fn lock_writer(&self) {
+    while self
+      .writer
+      .compare_exchange_weak(false, true, Acquire, Relaxed)
+      .is_err()
+    {
+      hint::spin_loop();
+    }
+  }
+
+  fn unlock_writer(&self) {
+    self.writer.store(false, Release);
+  }
+}
+
+impl<T> Drop for ArcCell<T> {
+  fn drop(&mut self) {
+    // SAFETY: `&mut self` guarantees there are no concurrent loads, and the
+    // slot owns one strong count.
+    unsafe { drop(Arc::from_raw(self.ptr.load(Acquire))) }
+  }
+}
+
+impl<T> From<Arc<T>> for ArcCell<T> {
+  fn from(value: Arc<T>) -> ArcCell<T> {
+    ArcCell::new(value)
+  }
+}
+
+impl<T: Default> Default for ArcCell<T> {
+  fn default() -> ArcCell<T> {
+    ArcCell::new(Arc::new(T::default()))
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for ArcCell<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("ArcCell").field(&self.load()).finish()
+  }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+
+  use std::sync::atomic::AtomicUsize;
+  use std::sync::atomic::Ordering::SeqCst;
+  use std::thread;
+
+  struct Tracked(std::sync::Arc<AtomicUsize>);
+
+  impl Drop for Tracked {
+    fn drop(&mut self) {
+      self.0.fetch_add(1, SeqCst);
+    }
+  }
+
+  #[test]
+  fn load_store() {
+    let cell = ArcCell::new(Arc::new(1));
+    let one = cell.load();
+
+    cell.store(Arc::new(2));
+
+    assert_eq!(*one, 1);
+    assert_eq!(*cell.load(), 2);
+    assert_eq!(Arc::strong_count(&one), 1);
+  }
+
+  #[test]
+  fn swap_returns_previous() {
+    let cell = ArcCell::new(Arc::new(1));
+    let old = cell.swap(Arc::new(2));
+
+    assert_eq!(*old, 1);
+    assert_eq!(Arc::strong_count(&old), 1);
+  }
+
+  #[test]
+  fn compare_and_swap() {
+    let cell = ArcCell::new(Arc::new(1));
+    let stale = Arc::new(1);
+
+    assert_eq!(*cell.compare_and_swap(&stale, Arc::new(3)).unwrap_err(), 3);
+
+    let current = cell.load();
+    assert_eq!(*cell.compare_and_swap(&current, Arc::new(2)).unwrap(), 1);
+    assert_eq!(*cell.load(), 2);
+  }
+
+  #[test]
+  fn drops_every_value_once() {
+    let dropped = std::sync::Arc::new(AtomicUsize::new(0));
+    let tracked = || Arc::new(Tracked(std::sync::Arc::clone(&dropped)));
+
+    let cell = ArcCell::new(tracked());
+    cell.store(tracked());
+    let held = cell.load();
+    cell.store(tracked());
+
+    assert_eq!(dropped.load(SeqCst), 1);
+    drop(held);
+    assert_eq!(dropped.load(SeqCst), 2);
+    drop(cell);
+    assert_eq!(dropped.load(SeqCst), 3);
+  }
+
+  #[test]
+  fn concurrent_rcu() {
+    let cell = Arc::new(ArcCell::new(Arc::new(0usize)));
+
+    let handles: Vec<_> = (0..4)
+      .map(|_| {
+        let cell = Arc::clone(&cell);
+        thread::spawn(move || {
+          for _ in 0..250 {
+            cell.rcu(|n| n + 1);
+            assert!(*cell.load() > 0);
+          }
+        })
+      })
+      .collect();
+
+    for handle in handles {
+      handle.join().unwrap();
+    }
+    assert_eq!(*cell.load(), 1000);
+  }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+  use super::*;
+
+  use ::loom::thread;
+  use std::sync::atomic::AtomicUsize;
+  use std::sync::atomic::Ordering::SeqCst;
+
+  struct Tracked(usize, std::sync::Arc<AtomicUsize>);
+
+  impl Drop for Tracked {
+    fn drop(&mut self) {
+      self.1.fetch_add(1, SeqCst);
+    }
+  }
+
+  #[test]
+  fn loom_load_swap() {
+    ::loom::model(|| {
+      let dropped = std::sync::Arc::new(AtomicUsize::new(0));
+      let cell = Arc::new(ArcCell::new(Arc::new(Tracked(
+        1,
+        std::sync::Arc::clone(&dropped),
+      ))));
+
+      let reader = {
+        let cell = Arc::clone(&cell);
+        thread::spawn(move || cell.load().0)
+      };
+      cell.store(Arc::new(Tracked(2, std::sync::Arc::clone(&dropped))));
+
+      let seen = reader.join().unwrap();
+      assert!(seen == 1 || seen == 2);
+      assert_eq!(dropped.load(SeqCst), 1);
+    });
+  }
+
+  #[test]
+  fn loom_two_writers() {
+    ::loom::model(|| {
+      let cell = Arc::new(ArcCell::new(Arc::new(0)));
+
+      let writer = {
+        let cell = Arc::clone(&cell);
+        thread::spawn(move || {
+          cell.rcu(|n| n + 1);
+        })
+      };
+      cell.rcu(|n| n + 1);
+      writer.join().unwrap();
+
+      assert_eq!(*cell.load(), 2);
+    });
+  }
+}
@@ -0,0 +1,248 @@
+//! A fair, FIFO lock for tasks: the waiting half of [`AsyncMutex`] and [`AsyncRwLock`].
+//!
+//! Where the blocking locks park threads on a [`WaitQueue`](super::wait_queue::WaitQueue), an
+//! async lock can't block at all. A task that can't take the lock registers its [`Waker`] at the
+//! back of the queue and returns [`Poll::Pending`]; releasing the lock hands it to the waiters at
+//! the front, in the order they arrived. New arrivals never overtake a queued task, so neither
+//! readers nor writers can be starved.
+//!
+//! Ownership is handed over when a waiter is granted the lock, not when its task runs again. If
+//! the future is dropped in between, [`cancel`](RawAsyncLock::cancel) releases it again.
+//!
+//! [`AsyncMutex`]: super::AsyncMutex
+//! [`AsyncRwLock`]: super::AsyncRwLock
+//! [`Waker`]: std::task::Waker
+//! [`Poll::Pending`]: std::task::Poll::Pending
+
+use super::Mutex;
+
+use std::task::{Context, Poll, Waker};
+
+/// A task waiting for the lock.
+struct Waiter {
+  id: u64,
+  exclusive: bool,
+  waker: Waker,
+}
+
+struct State {
+  /// How many shared holders there are.
+  readers: usize,
+  /// Whether an exclusive holder has the lock.
+  writer: bool,
+  waiters: std::collections::VecDeque<Waiter>,
+  next_id: u64,
+}
+
+impl State {
+  fn is_free_for(&self, exclusive: bool) -> bool {
+    !self.writer && (!exclusive || self.readers == 0)
+  }
+
+  fn take(&mut self, exclusive: bool) {
+    if exclusive {
+      self.writer = true;
+    } else {
+      self.readers += 1;
+    }
+  }
+
+  fn release(&mut self, exclusive: bool) {
+    if exclusive {
+      self.writer = false;
+    } else {
+      self.readers -= 1;
+    }
+  }
+
+  /// Hands the lock to the waiters at the front of the queue and returns
+  /// their wakers, to be woken once the state is unlocked.
+  fn grant(&mut self) -> Vec<Waker> {
+    let mut woken = Vec::new();
+    while let Some(front) = self.waiters.front() {
+      if !self.is_free_for(front.exclusive) {
+        break;
+      }
+      let waiter = self.waiters.pop_front().unwrap();
+      self.take(waiter.exclusive);
+      woken.push(waiter.waker);
+      if waiter.exclusive {
+        break;
+      }
+    }
+    woken
+  }
+}
+
+/// The locking protocol behind the async locks, without the data.
+///
+/// A waiting future remembers the id it was queued under. Once that id is
+/// gone from the queue, the lock has been granted to it.
+pub(crate) struct RawAsyncLock {
+  state: Mutex<State>,
+}
+
+impl RawAsyncLock {
+  pub(crate) fn new() -> RawAsyncLock {
+    RawAsyncLock {
+      state: Mutex::new(State {
+        readers: 0,
+        writer: false,
+        waiters: std::collections::VecDeque::new(),
+        next_id: 0,
+      }),
+    }
+  }
+
+  /// Takes the lock if it is free and nobody is queued for it.
+  pub(crate) fn try_acquire(&self, exclusive: bool) -> bool {
+    let mut state = self.state.lock();
+    if state.waiters.is_empty() && state.is_free_for(exclusive) {
+      state.take(exclusive);
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Polls for the lock on behalf of the future that owns `id`.
+  ///
+  /// `id` starts out as `None` and is set while the future is queued. It is
+  /// `None` again once this returns [`Poll::Ready`].
+  pub(crate) fn poll_acquire(
+    &self,
+    exclusive: bool,
+    id: &mut Option<u64>,
+    cx: &mut Context<'_>,
+  ) -> Poll<()> {
+    let mut state = self.state.lock();
+    match *id {
+      None => {
+        if state.waiters.is_empty() && state.is_free_for(exclusive) {
+          state.take(exclusive);
+          return Poll::Ready(());
+        }
+        let next = state.next_id;
+        state.next_id += 1;
+        state.waiters.push_back(Waiter {
+          id: next,
+          exclusive,
+          waker: cx.waker().clone(),
+        });
+        *id = Some(next);
+        Poll::Pending
+      }
+      Some(queued) => match state.waiters.iter_mut().find(|w| w.id == queued) {
+        Some(waiter) => {
+          if !waiter.waker.will_wake(cx.waker()) {
+            waiter.waker = cx.waker().clone();
+          }
+          Poll::Pending
+        }
+        None => {
+          *id = None;
+          Poll::Ready(())
+        }
+      },
+    }
+  }
+
+  /// Gives up on the lock for a future that was dropped while queued under
+  /// `id`, releasing it if it had already been granted.
+  pub(crate) fn cancel(&self, exclusive: bool, id: u64) {
+    let woken = {
+      let mut state = self.state.lock();
+      match state.waiters.iter().position(|w| w.id == id) {
+        Some(i) => {
+          state.waiters.remove(i);
+        }
+        None => state.release(exclusive),
+      }
+      // A cancelled writer at the front may have kept readers waiting.
+      state.grant()
+    };
+    woken.into_iter().for_each(Waker::wake);
+  }
+
+  /// Releases a lock taken through `try_acquire` or `poll_acquire`.
+  pub(crate) fn release(&self, exclusive: bool) {
+    let woken = {
+      let mut state = self.state.lock();
+      state.release(exclusive);
+      state.grant()
+    };
+    woken.into_iter().for_each(Waker::wake);
+  }
+
+  /// Returns `true` if someone holds the lock exclusively.
+  pub(crate) fn is_locked_exclusive(&self) -> bool {
+    self.state.lock().writer
+  }
+}
+
+/// Drives `future` to completion on the current thread.
+#[cfg(all(test, not(loom)))]
+pub(crate) fn block_on<F: std::future::Future>(future: F) -> F::Output {
+  struct ThreadWaker(std::thread::Thread);
+
+  impl std::task::Wake for ThreadWaker {
+    fn wake(self: std::sync::Arc<Self>) {
+      self.0.unpark();
+    }
+  }
+
+  let mut future = std::pin::pin!(future);
+  let waker =
+    Waker::from(std::sync::Arc::new(ThreadWaker(std::thread::current())));
+  let mut cx = Context::from_waker(&waker);
+  loop {
+    match future.as_mut().poll(&mut cx) {
+      Poll::Ready(output) => return output,
+      Poll::Pending => std::thread::park(),
+    }
+  }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+
+  fn poll(lock: &RawAsyncLock, exclusive: bool, id: &mut Option<u64>) -> bool {
+    let mut cx = Context::from_waker(Waker::noop());
+    lock.poll_acquire(exclusive, id, &mut cx).is_ready()
+  }
+
+  #[test]
+  fn grants_in_arrival_order() {
+    let lock = RawAsyncLock::new();
+    let (mut reader, mut writer, mut late) = (None, None, None);
+
+    assert!(poll(&lock, false, &mut reader));
+    assert!(!poll(&lock, true, &mut writer));
+    // A reader arriving after a queued writer waits behind it.
+    assert!(!poll(&lock, false, &mut late));
+    assert!(!lock.try_acquire(false));
+
+    lock.release(false);
+    assert!(poll(&lock, true, &mut writer));
+    assert!(!poll(&lock, false, &mut late));
+
+    lock.release(true);
+    assert!(poll(&lock, false, &mut late));
+  }
+
+  #[test]
+  fn cancel_after_grant_releases() {
+    let lock = RawAsyncLock::new();
+    let (mut first, mut second) = (None, None);
+
+    assert!(lock.try_acquire(true));
+    assert!(!poll(&lock, true, &mut first));
+    assert!(!poll(&lock, true, &mut second));
+
+    // `first` is granted the lock but dropped before it is polled again.
+    lock.release(true);
+    lock.cancel(true, first.unwrap());
+    assert!(poll(&lock, true, &mut second));
+  }
+}
@@ -0,0 +1,312 @@
+//! A mutex for async code, whose guard may be held across an `.await`.
+//!
+//! Holding a [`MutexGuard`] across an `.await` is the classic async deadlock: the task is suspended
+//! with the lock held, and another task on the same executor thread blocks the thread trying to
+//! take it, so the holder never runs again. [`AsyncMutex::lock`] returns a future instead, which
+//! yields to the executor until the lock is available.
+//!
+//! Waiting tasks are served in the order they asked for the lock. An [`AsyncMutexGuard`] is [`Send`]
+//! whenever `T` is, so a task holding one can still be moved between executor threads.
+//!
+//! The crate doesn't ship an executor; the futures work with any of them.
+//!
+//! ```
+//! use pointer::sync::{Arc, AsyncMutex};
+//!
+//! async fn append(log: Arc<AsyncMutex<Vec<String>>>, line: String) {
+//!   let mut log = log.lock().await;
+//!   // The guard may live across other awaits here.
+//!   log.push(line);
+//! }
+//!
+//! let log = Arc::new(AsyncMutex::new(Vec::new()));
+//! let _task = append(Arc::clone(&log), "hello".to_string());
+//! ```
+//!
+//! [`MutexGuard`]: crate::sync::MutexGuard
+
+use super::async_lock::RawAsyncLock;
+use crate::loom::cell::UnsafeCell;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A mutual exclusion primitive for async tasks.
+///
+/// See the [module-level documentation](index.html) for more.
+pub struct AsyncMutex<T: ?Sized> {
+  raw: RawAsyncLock,
+  data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for AsyncMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for AsyncMutex<T> {}
+
+/// The future returned by [`AsyncMutex::lock`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct AsyncMutexLockFuture<'a, T: ?Sized> {
+  mutex: &'a AsyncMutex<T>,
+  /// Our place in the waiter queue, once we have one.
+  id: Option<u64>,
+}
+
+/// An RAII guard for a locked [`AsyncMutex`], returned by
+/// [`AsyncMutex::lock`] and [`AsyncMutex::try_lock`].
+///
+/// The lock is released when the guard is dropped.
+#[must_use = "if unused the AsyncMutex will immediately unlock"]
+pub struct AsyncMutexGuard<'a, T: ?Sized> {
+  mutex: &'a AsyncMutex<T>,
+  marker: std::marker::PhantomData<&'a mut T>,
+}
+
+impl<T> AsyncMutex<T> {
+  /// Creates a new async mutex in an unlocked state.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::AsyncMutex;
+  ///
+  /// let mutex = AsyncMutex::new(0);
+  /// ```
+  pub fn new(t: T) -> AsyncMutex<T> {
+    AsyncMutex {
+      raw: RawAsyncLock::new(),
+      data: UnsafeCell::new(t),
+    }
+  }
+
+  /// Consumes this mutex, returning the underlying data.
+  pub fn into_inner(self) -> T {
+    self.data.into_inner()
+  }
+}
+
+impl<T: ?Sized> AsyncMutex<T> {
+  /// Returns a future that resolves to a guard once the lock is acquired.
+  ///
+  /// Tasks acquire the lock in the order they first polled this future.
+  /// Dropping the future gives up the place in the queue.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::AsyncMutex;
+  ///
+  /// async fn increment(counter: &AsyncMutex<u32>) {
+  ///   *counter.lock().await += 1;
+  /// }
+  /// ```
+  pub fn lock(&self) -> AsyncMutexLockFuture<'_, T> {
+    AsyncMutexLockFuture {
+      mutex: self,
+      id: None,
+    }
+  }
+
+  /// Attempts to acquire the lock right away.
+  ///
+  /// Fails if the lock is held or other tasks are already waiting for it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::AsyncMutex;
+  ///
+  /// let mutex = AsyncMutex::new(1);
+  ///
+  /// let guard = mutex.try_lock().unwrap();
+  /// assert!(mutex.try_lock().is_none());
+  /// drop(guard);
+  /// assert!(mutex.try_lock().is_some());
+  /// ```
+  pub fn try_lock(&self) -> Option<AsyncMutexGuard<'_, T>> {
+    if self.raw.try_acquire(true) {
+      Some(AsyncMutexGuard::new(self))
+    } else {
+      None
+    }
+  }
+
+  /// Returns `true` if the lock is currently held.
+  ///
+  /// Another task can lock or unlock at any time, so this is only a hint.
+  pub fn is_locked(&self) -> bool {
+    self.raw.is_locked_exclusive()
+  }
+
+  /// Returns a mutable reference to the underlying data.
+  ///
+  /// Since this call borrows the `AsyncMutex` mutably, no actual locking
+  /// needs to take place.
+  pub fn get_mut(&mut self) -> &mut T {
+    // SAFETY: `&mut self` guarantees there is no guard alive.
+    self.data.with_mut(|data| unsafe { &mut *data })
+  }
+}
+
+impl<T: Default> Default for AsyncMutex<T> {
+  fn default() -> AsyncMutex<T> {
+    AsyncMutex::new(Default::default())
+  }
+}
+
+impl<T> From<T> for AsyncMutex<T> {
+  fn from(t: T) -> AsyncMutex<T> {
+    AsyncMutex::new(t)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for AsyncMutex<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut d = f.debug_struct("AsyncMutex");
+    match self.try_lock() {
+      Some(guard) => d.field("data", &&*guard),
+      None => d.field("data", &format_args!("<locked>")),
+    };
+    d.finish()
+  }
+}
+
+impl<'a, T: ?Sized> Future for AsyncMutexLockFuture<'a, T> {
+  type Output = AsyncMutexGuard<'a, T>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let this = &mut *self;
+    match this.mutex.raw.poll_acquire(true, &mut this.id, cx) {
+      Poll::Ready(()) => Poll::Ready(AsyncMutexGuard::new(this.mutex)),
+      Poll::Pending => Poll::Pending,
+    }
+  }
+}
+
+impl<T: ?Sized> Drop for AsyncMutexLockFuture<'_, T> {
+  fn drop(&mut self) {
+    if let Some(id) = self.id {
+      self.mutex.raw.cancel(true, id);
+    }
+  }
+}
+
+impl<T: ?Sized> std::fmt::Debug for AsyncMutexLockFuture<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("AsyncMutexLockFuture")
+      .field("queued", &self.id.is_some())
+      .finish_non_exhaustive()
+  }
+}
+
+impl<'a, T: ?Sized> AsyncMutexGuard<'a, T> {
+  fn new(mutex: &'a AsyncMutex<T>) -> AsyncMutexGuard<'a, T> {
+    AsyncMutexGuard {
+      mutex,
+      marker: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for AsyncMutexGuard<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    // SAFETY: The guard holds the lock.
+    self.mutex.data.with(|data| unsafe { &*data })
+  }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for AsyncMutexGuard<'_, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    // SAFETY: The guard holds the lock, and `&mut self` makes this the only
+    // reference handed out by it.
+    self.mutex.data.with_mut(|data| unsafe { &mut *data })
+  }
+}
+
+impl<T: ?Sized> Drop for AsyncMutexGuard<'_, T> {
+  #[inline]
+  fn drop(&mut self) {
+    self.mutex.raw.release(true);
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for AsyncMutexGuard<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display
+  for AsyncMutexGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+  use crate::sync::async_lock::block_on;
+  use crate::sync::Arc;
+
+  use std::task::Waker;
+  use std::thread;
+
+  #[test]
+  fn lock_and_unlock() {
+    let mutex = AsyncMutex::new(vec![1]);
+
+    block_on(async {
+      mutex.lock().await.push(2);
+      let guard = mutex.lock().await;
+      assert!(mutex.is_locked());
+      assert_eq!(*guard, [1, 2]);
+    });
+    assert!(!mutex.is_locked());
+  }
+
+  #[test]
+  fn dropped_future_leaves_queue() {
+    let mutex = AsyncMutex::new(0);
+    let mut cx = Context::from_waker(Waker::noop());
+
+    let guard = mutex.try_lock().unwrap();
+    let mut waiting = Box::pin(mutex.lock());
+    assert!(waiting.as_mut().poll(&mut cx).is_pending());
+    // The queued future keeps `try_lock` from overtaking it.
+    drop(guard);
+    assert!(mutex.is_locked());
+
+    drop(waiting);
+    assert!(!mutex.is_locked());
+    assert!(mutex.try_lock().is_some());
+  }
+
+  #[test]
+  fn contended_across_threads() {
+    let mutex = Arc::new(AsyncMutex::new(0));
+
+    let handles: Vec<_> = (0..4)
+      .map(|_| {
+        let mutex = Arc::clone(&mutex);
+        thread::spawn(move || {
+          block_on(async {
+            for _ in 0..500 {
+              *mutex.lock().await += 1;
+            }
+          })
+        })
+      })
+      .collect();
+
+    for handle in handles {
+      handle.join().unwrap();
+    }
+    assert_eq!(*mutex.try_lock().unwrap(), 2000);
+  }
+}
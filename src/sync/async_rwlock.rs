@@ -0,0 +1,453 @@
+//! A reader-writer lock for async code.
+//!
+//! [`AsyncRwLock<T>`][AsyncRwLock] is to [`RwLock`] what [`AsyncMutex`] is to [`Mutex`]: any
+//! number of tasks may hold a [`read`] guard, or a single task may hold a [`write`] guard, and
+//! waiting for a conflicting guard yields to the executor instead of blocking the thread.
+//!
+//! The lock is fair. Tasks are served in the order they asked, and consecutive readers at the
+//! front of the queue are let in together. A reader that arrives while a writer is waiting queues
+//! up behind the writer, so neither side can be starved.
+//!
+//! ```
+//! use pointer::sync::AsyncRwLock;
+//!
+//! async fn bump(config: &AsyncRwLock<u32>) -> u32 {
+//!   let before = *config.read().await;
+//!   *config.write().await += 1;
+//!   before
+//! }
+//!
+//! let config = AsyncRwLock::new(1);
+//! let _task = bump(&config);
+//! ```
+//!
+//! [`RwLock`]: crate::sync::RwLock
+//! [`AsyncMutex`]: crate::sync::AsyncMutex
+//! [`Mutex`]: crate::sync::Mutex
+//! [`read`]: AsyncRwLock::read
+//! [`write`]: AsyncRwLock::write
+
+use super::async_lock::RawAsyncLock;
+use crate::loom::cell::UnsafeCell;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A reader-writer lock for async tasks.
+///
+/// See the [module-level documentation](index.html) for more.
+pub struct AsyncRwLock<T: ?Sized> {
+  raw: RawAsyncLock,
+  data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for AsyncRwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for AsyncRwLock<T> {}
+
+/// The future returned by [`AsyncRwLock::read`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct AsyncRwLockReadFuture<'a, T: ?Sized> {
+  lock: &'a AsyncRwLock<T>,
+  id: Option<u64>,
+}
+
+/// The future returned by [`AsyncRwLock::write`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct AsyncRwLockWriteFuture<'a, T: ?Sized> {
+  lock: &'a AsyncRwLock<T>,
+  id: Option<u64>,
+}
+
+/// RAII structure used to release the shared read access of a lock when
+/// dropped, returned by [`AsyncRwLock::read`] and [`AsyncRwLock::try_read`].
+#[must_use = "if unused the AsyncRwLock will immediately unlock"]
+pub struct AsyncRwLockReadGuard<'a, T: ?Sized> {
+  lock: &'a AsyncRwLock<T>,
+  marker: std::marker::PhantomData<&'a T>,
+}
+
+/// RAII structure used to release the exclusive write access of a lock when
+/// dropped, returned by [`AsyncRwLock::write`] and [`AsyncRwLock::try_write`].
+#[must_use = "if unused the AsyncRwLock will immediately unlock"]
+pub struct AsyncRwLockWriteGuard<'a, T: ?Sized> {
+  lock: &'a AsyncRwLock<T>,
+  marker: std::marker::PhantomData<&'a mut T>,
+}
+
+impl<T> AsyncRwLock<T> {
+  /// Creates a new instance of an `AsyncRwLock<T>` which is unlocked.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::AsyncRwLock;
+  ///
+  /// let lock = AsyncRwLock::new(5);
+  /// ```
+  pub fn new(t: T) -> AsyncRwLock<T> {
+    AsyncRwLock {
+      raw: RawAsyncLock::new(),
+      data: UnsafeCell::new(t),
+    }
+  }
+
+  /// Consumes this `AsyncRwLock`, returning the underlying data.
+  pub fn into_inner(self) -> T {
+    self.data.into_inner()
+  }
+}
+
+impl<T: ?Sized> AsyncRwLock<T> {
+  /// Returns a future that resolves to a shared guard once no writer holds
+  /// or is queued ahead of it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::AsyncRwLock;
+  ///
+  /// async fn len(names: &AsyncRwLock<Vec<String>>) -> usize {
+  ///   names.read().await.len()
+  /// }
+  /// ```
+  pub fn read(&self) -> AsyncRwLockReadFuture<'_, T> {
+    AsyncRwLockReadFuture {
+      lock: self,
+      id: None,
+    }
+  }
+
+  /// Attempts to acquire this lock with shared read access right away.
+  ///
+  /// Fails if a writer holds the lock or any task is already waiting for it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::AsyncRwLock;
+  ///
+  /// let lock = AsyncRwLock::new(1);
+  ///
+  /// let r1 = lock.try_read().unwrap();
+  /// let r2 = lock.try_read().unwrap();
+  /// assert!(lock.try_write().is_none());
+  /// assert_eq!(*r1 + *r2, 2);
+  /// ```
+  pub fn try_read(&self) -> Option<AsyncRwLockReadGuard<'_, T>> {
+    if self.raw.try_acquire(false) {
+      Some(AsyncRwLockReadGuard::new(self))
+    } else {
+      None
+    }
+  }
+
+  /// Returns a future that resolves to an exclusive guard once every other
+  /// guard is gone.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::AsyncRwLock;
+  ///
+  /// async fn add(names: &AsyncRwLock<Vec<String>>, name: String) {
+  ///   names.write().await.push(name);
+  /// }
+  /// ```
+  pub fn write(&self) -> AsyncRwLockWriteFuture<'_, T> {
+    AsyncRwLockWriteFuture {
+      lock: self,
+      id: None,
+    }
+  }
+
+  /// Attempts to acquire this lock with exclusive write access right away.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::AsyncRwLock;
+  ///
+  /// let lock = AsyncRwLock::new(1);
+  ///
+  /// let mut w = lock.try_write().unwrap();
+  /// *w += 1;
+  /// assert!(lock.try_read().is_none());
+  /// ```
+  pub fn try_write(&self) -> Option<AsyncRwLockWriteGuard<'_, T>> {
+    if self.raw.try_acquire(true) {
+      Some(AsyncRwLockWriteGuard::new(self))
+    } else {
+      None
+    }
+  }
+
+  /// Returns a mutable reference to the underlying data.
+  ///
+  /// Since this call borrows the `AsyncRwLock` mutably, no actual locking
+  /// needs to take place.
+  pub fn get_mut(&mut self) -> &mut T {
+    // SAFETY: `&mut self` guarantees there is no guard alive.
+    self.data.with_mut(|data| unsafe { &mut *data })
+  }
+}
+
+impl<T: Default> Default for AsyncRwLock<T> {
+  fn default() -> AsyncRwLock<T> {
+    AsyncRwLock::new(Default::default())
+  }
+}
+
+impl<T> From<T> for AsyncRwLock<T> {
+  fn from(t: T) -> AsyncRwLock<T> {
+    AsyncRwLock::new(t)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for AsyncRwLock<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut d = f.debug_struct("AsyncRwLock");
+    match self.try_read() {
+      Some(guard) => d.field("data", &&*guard),
+      None => d.field("data", &format_args!("<locked>")),
+    };
+    d.finish()
+  }
+}
+
+impl<'a, T: ?Sized> Future for AsyncRwLockReadFuture<'a, T> {
+  type Output = AsyncRwLockReadGuard<'a, T>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let this = &mut *self;
+    match this.lock.raw.poll_acquire(false, &mut this.id, cx) {
+      Poll::Ready(()) => Poll::Ready(AsyncRwLockReadGuard::new(this.lock)),
+      Poll::Pending => Poll::Pending,
+    }
+  }
+}
+
+impl<T: ?Sized> Drop for AsyncRwLockReadFuture<'_, T> {
+  fn drop(&mut self) {
+    if let Some(id) = self.id {
+      self.lock.raw.cancel(false, id);
+    }
+  }
+}
+
+impl<T: ?Sized> std::fmt::Debug for AsyncRwLockReadFuture<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("AsyncRwLockReadFuture")
+      .field("queued", &self.id.is_some())
+      .finish_non_exhaustive()
+  }
+}
+
+impl<'a, T: ?Sized> Future for AsyncRwLockWriteFuture<'a, T> {
+  type Output = AsyncRwLockWriteGuard<'a, T>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let this = &mut *self;
+    match this.lock.raw.poll_acquire(true, &mut this.id, cx) {
+      Poll::Ready(()) => Poll::Ready(AsyncRwLockWriteGuard::new(this.lock)),
+      Poll::Pending => Poll::Pending,
+    }
+  }
+}
+
+impl<T: ?Sized> Drop for AsyncRwLockWriteFuture<'_, T> {
+  fn drop(&mut self) {
+    if let Some(id) = self.id {
+      self.lock.raw.cancel(true, id);
+    }
+  }
+}
+
+impl<T: ?Sized> std::fmt::Debug for AsyncRwLockWriteFuture<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("AsyncRwLockWriteFuture")
+      .field("queued", &self.id.is_some())
+      .finish_non_exhaustive()
+  }
+}
+
+impl<'a, T: ?Sized> AsyncRwLockReadGuard<'a, T> {
+  fn new(lock: &'a AsyncRwLock<T>) -> AsyncRwLockReadGuard<'a, T> {
+    AsyncRwLockReadGuard {
+      lock,
+      marker: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<'a, T: ?Sized> AsyncRwLockWriteGuard<'a, T> {
+  fn new(lock: &'a AsyncRwLock<T>) -> AsyncRwLockWriteGuard<'a, T> {
+    AsyncRwLockWriteGuard {
+      lock,
+      marker: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for AsyncRwLockReadGuard<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    // SAFETY: The guard holds shared access, so no writer exists.
+    self.lock.data.with(|data| unsafe { &*data })
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for AsyncRwLockWriteGuard<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    // SAFETY: The guard holds exclusive access.
+    self.lock.data.with(|data| unsafe { &*data })
+  }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for AsyncRwLockWriteGuard<'_, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    // SAFETY: The guard holds exclusive access, and `&mut self` makes this
+    // the only reference handed out by it.
+    self.lock.data.with_mut(|data| unsafe { &mut *data })
+  }
+}
+
+impl<T: ?Sized> Drop for AsyncRwLockReadGuard<'_, T> {
+  #[inline]
+  fn drop(&mut self) {
+    self.lock.raw.release(false);
+  }
+}
+
+impl<T: ?Sized> Drop for AsyncRwLockWriteGuard<'_, T> {
+  #[inline]
+  fn drop(&mut self) {
+    self.lock.raw.release(true);
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug
+  for AsyncRwLockReadGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display
+  for AsyncRwLockReadGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug
+  for AsyncRwLockWriteGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display
+  for AsyncRwLockWriteGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+  use crate::sync::async_lock::block_on;
+  use crate::sync::Arc;
+
+  use std::task::Waker;
+  use std::thread;
+
+  #[test]
+  fn queued_writer_blocks_new_readers() {
+    let lock = AsyncRwLock::new(0);
+    let mut cx = Context::from_waker(Waker::noop());
+
+    let reader = lock.try_read().unwrap();
+    let mut writer = Box::pin(lock.write());
+    assert!(writer.as_mut().poll(&mut cx).is_pending());
+    let mut late = Box::pin(lock.read());
+    assert!(late.as_mut().poll(&mut cx).is_pending());
+
+    drop(reader);
+    let mut w = match writer.as_mut().poll(&mut cx) {
+      Poll::Ready(w) => w,
+      Poll::Pending => panic!("writer should have been granted the lock"),
+    };
+    *w += 1;
+    assert!(late.as_mut().poll(&mut cx).is_pending());
+
+    drop(w);
+    match late.as_mut().poll(&mut cx) {
+      Poll::Ready(r) => assert_eq!(*r, 1),
+      Poll::Pending => panic!("reader should have been granted the lock"),
+    };
+  }
+
+  #[test]
+  fn readers_at_front_share_the_lock() {
+    let lock = AsyncRwLock::new(());
+    let mut cx = Context::from_waker(Waker::noop());
+
+    let writer = lock.try_write().unwrap();
+    let mut readers: Vec<_> = (0..3).map(|_| Box::pin(lock.read())).collect();
+    for reader in &mut readers {
+      assert!(reader.as_mut().poll(&mut cx).is_pending());
+    }
+
+    drop(writer);
+    let guards: Vec<_> = readers
+      .iter_mut()
+      .map(|reader| match reader.as_mut().poll(&mut cx) {
+        Poll::Ready(guard) => guard,
+        Poll::Pending => panic!("every queued reader should be let in"),
+      })
+      .collect();
+    assert_eq!(guards.len(), 3);
+  }
+
+  #[test]
+  fn contended_readers_and_writers() {
+    let lock = Arc::new(AsyncRwLock::new(0));
+
+    let handles: Vec<_> = (0..4)
+      .map(|i| {
+        let lock = Arc::clone(&lock);
+        thread::spawn(move || {
+          block_on(async {
+            for _ in 0..200 {
+              if i % 2 == 0 {
+                *lock.write().await += 1;
+              } else {
+                assert!(*lock.read().await <= 400);
+              }
+            }
+          })
+        })
+      })
+      .collect();
+
+    for handle in handles {
+      handle.join().unwrap();
+    }
+    assert_eq!(*lock.try_read().unwrap(), 400);
+  }
+}
@@ -0,0 +1,560 @@
+//! A thread-safe reference-counting pointer without weak references.
+//!
+//! [`CompactArc<T>`][CompactArc] is an [`Arc<T>`][Arc] that gives up [`Weak`] pointers in exchange
+//! for a smaller header: the allocation carries a single atomic count in front of the value.
+//! Cloning and dropping touch only that count, and dropping the last pointer frees the allocation
+//! straight away instead of first checking for outstanding weak references.
+//!
+//! The layout is `repr(C)` and `CompactArc<T>` is a single non-null pointer, so it can be handed
+//! across an FFI boundary with [`into_raw`] and reclaimed with [`from_raw`].
+//!
+//! ```
+//! use pointer::sync::CompactArc;
+//!
+//! let a = CompactArc::new(5);
+//! let b = CompactArc::clone(&a);
+//!
+//! assert_eq!(*b, 5);
+//! assert_eq!(CompactArc::count(&a), 2);
+//! ```
+//!
+//! [`Weak`]: crate::sync::Weak
+//! [`into_raw`]: CompactArc::into_raw
+//! [`from_raw`]: CompactArc::from_raw
+
+use super::Arc;
+use crate::loom::atomic;
+use crate::loom::atomic::Ordering::{Acquire, Relaxed, Release};
+
+/// A soft limit on the amount of references that may be made to a `CompactArc`.
+const MAX_REFCOUNT: usize = (isize::MAX) as usize;
+
+/// The heap allocation behind a [`CompactArc`]: one count, then the value.
+#[repr(C)]
+struct CompactInner<T: ?Sized> {
+  count: atomic::AtomicUsize,
+  data: T,
+}
+
+unsafe impl<T: ?Sized + Sync + Send> Send for CompactInner<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Sync for CompactInner<T> {}
+
+/// A thread-safe reference-counting pointer with no weak count.
+///
+/// See the [module-level documentation](index.html) for more.
+#[repr(transparent)]
+pub struct CompactArc<T: ?Sized> {
+  ptr: std::ptr::NonNull<CompactInner<T>>,
+  phantom: std::marker::PhantomData<CompactInner<T>>,
+}
+
+unsafe impl<T: ?Sized + Sync + Send> Send for CompactArc<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Sync for CompactArc<T> {}
+
+impl<T> CompactArc<T> {
+  /// Constructs a new `CompactArc<T>`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::CompactArc;
+  ///
+  /// let five = CompactArc::new(5);
+  /// ```
+  #[inline]
+  pub fn new(data: T) -> CompactArc<T> {
+    let x = Box::new(CompactInner {
+      count: atomic::AtomicUsize::new(1),
+      data,
+    });
+    // SAFETY: `Box::into_raw` never returns a null pointer.
+    Self::from_inner(unsafe {
+      std::ptr::NonNull::new_unchecked(Box::into_raw(x))
+    })
+  }
+
+  /// Returns the inner value, if the `CompactArc` has exactly one reference.
+  ///
+  /// Otherwise, an [`Err`] is returned with the same `CompactArc` that was
+  /// passed in.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::CompactArc;
+  ///
+  /// let x = CompactArc::new(3);
+  /// assert_eq!(CompactArc::try_unwrap(x).ok(), Some(3));
+  ///
+  /// let x = CompactArc::new(4);
+  /// let _y = CompactArc::clone(&x);
+  /// assert_eq!(*CompactArc::try_unwrap(x).unwrap_err(), 4);
+  /// ```
+  pub fn try_unwrap(this: Self) -> Result<T, Self> {
+    if this
+      .inner()
+      .count
+      .compare_exchange(1, 0, Relaxed, Relaxed)
+      .is_err()
+    {
+      return Err(this);
+    }
+
+    atomic::fence(Acquire);
+
+    let this = std::mem::ManuallyDrop::new(this);
+    // SAFETY: The count is now zero, so we own both the value and the
+    // allocation. Move the value out, then free the memory.
+    unsafe {
+      let elem = std::ptr::read(&this.ptr.as_ref().data);
+      dealloc_inner(this.ptr);
+      Ok(elem)
+    }
+  }
+
+  /// Moves the value of a unique [`Arc`] into a new `CompactArc`.
+  ///
+  /// Like [`Arc::try_unwrap`], this succeeds when there is exactly one strong
+  /// reference; outstanding [`Weak`](crate::sync::Weak) pointers can no longer
+  /// be upgraded afterwards. Otherwise the `Arc` is given back.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, CompactArc};
+  ///
+  /// let compact = CompactArc::from_arc(Arc::new(5)).unwrap();
+  /// assert_eq!(*compact, 5);
+  ///
+  /// let shared = Arc::new(6);
+  /// let _other = Arc::clone(&shared);
+  /// assert!(CompactArc::from_arc(shared).is_err());
+  /// ```
+  pub fn from_arc(arc: Arc<T>) -> Result<CompactArc<T>, Arc<T>> {
+    Arc::try_unwrap(arc).map(CompactArc::new)
+  }
+
+  /// Moves the value of a unique `CompactArc` into a new [`Arc`].
+  ///
+  /// Otherwise the `CompactArc` is given back.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, CompactArc};
+  ///
+  /// let arc = CompactArc::into_arc(CompactArc::new(5)).unwrap();
+  /// assert_eq!(*arc, 5);
+  /// assert_eq!(Arc::weak_count(&arc), 0);
+  /// ```
+  pub fn into_arc(this: Self) -> Result<Arc<T>, CompactArc<T>> {
+    CompactArc::try_unwrap(this).map(Arc::new)
+  }
+
+  /// Constructs a `CompactArc<T>` from a raw pointer.
+  ///
+  /// # Safety
+  ///
+  /// `ptr` must come from [`CompactArc::into_raw`] on a `CompactArc<T>` whose
+  /// reference has not been reclaimed yet.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::CompactArc;
+  ///
+  /// let x = CompactArc::new("hello".to_owned());
+  /// let x_ptr = CompactArc::into_raw(x);
+  ///
+  /// unsafe {
+  ///   let x = CompactArc::from_raw(x_ptr);
+  ///   assert_eq!(&*x, "hello");
+  /// }
+  /// ```
+  pub unsafe fn from_raw(ptr: *const T) -> CompactArc<T> {
+    let offset = data_offset::<T>();
+
+    // Reverse the offset to find the original CompactInner.
+    let inner = ptr.byte_sub(offset).cast::<CompactInner<T>>().cast_mut();
+
+    Self::from_inner(std::ptr::NonNull::new_unchecked(inner))
+  }
+}
+
+impl<T: ?Sized> CompactArc<T> {
+  fn from_inner(ptr: std::ptr::NonNull<CompactInner<T>>) -> Self {
+    Self {
+      ptr,
+      phantom: std::marker::PhantomData,
+    }
+  }
+
+  #[inline]
+  fn inner(&self) -> &CompactInner<T> {
+    // SAFETY: While this pointer is alive the allocation is valid, and
+    // `CompactInner` is `Sync` whenever the data is.
+    unsafe { self.ptr.as_ref() }
+  }
+
+  /// Gets the number of `CompactArc` pointers to this allocation.
+  ///
+  /// Another thread can change the count at any time, including
+  /// between calling this method and acting on the result.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::CompactArc;
+  ///
+  /// let five = CompactArc::new(5);
+  /// let _also_five = CompactArc::clone(&five);
+  ///
+  /// assert_eq!(2, CompactArc::count(&five));
+  /// ```
+  #[inline]
+  pub fn count(this: &Self) -> usize {
+    this.inner().count.load(Acquire)
+  }
+
+  /// Returns `true` if this is the only pointer to the allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::CompactArc;
+  ///
+  /// let five = CompactArc::new(5);
+  /// assert!(CompactArc::is_unique(&five));
+  ///
+  /// let _also_five = CompactArc::clone(&five);
+  /// assert!(!CompactArc::is_unique(&five));
+  /// ```
+  #[inline]
+  pub fn is_unique(this: &Self) -> bool {
+    // Acquire synchronizes with the `Release` decrement in `drop`, so all
+    // accesses through other pointers happen before we go on to mutate.
+    this.inner().count.load(Acquire) == 1
+  }
+
+  /// Returns a mutable reference into the given `CompactArc`, if there are
+  /// no other pointers to the same allocation.
+  ///
+  /// Unlike [`Arc::get_mut`] there is no weak count to lock, so this is a
+  /// single load.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::CompactArc;
+  ///
+  /// let mut x = CompactArc::new(3);
+  /// *CompactArc::get_mut(&mut x).unwrap() = 4;
+  /// assert_eq!(*x, 4);
+  ///
+  /// let _y = CompactArc::clone(&x);
+  /// assert!(CompactArc::get_mut(&mut x).is_none());
+  /// ```
+  #[inline]
+  pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+    if CompactArc::is_unique(this) {
+      // SAFETY: We hold the only pointer, and `&mut self` keeps it that way.
+      unsafe { Some(&mut (*this.ptr.as_ptr()).data) }
+    } else {
+      None
+    }
+  }
+
+  /// Returns `true` if the two `CompactArc`s point to the same allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::CompactArc;
+  ///
+  /// let five = CompactArc::new(5);
+  /// let same_five = CompactArc::clone(&five);
+  /// let other_five = CompactArc::new(5);
+  ///
+  /// assert!(CompactArc::ptr_eq(&five, &same_five));
+  /// assert!(!CompactArc::ptr_eq(&five, &other_five));
+  /// ```
+  #[inline]
+  pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+    this.ptr.as_ptr().cast::<()>() == other.ptr.as_ptr().cast::<()>()
+  }
+
+  /// Consumes the `CompactArc`, returning the wrapped pointer.
+  ///
+  /// To avoid a memory leak the pointer must be converted back using
+  /// [`CompactArc::from_raw`].
+  pub fn into_raw(this: Self) -> *const T {
+    let ptr = Self::as_ptr(&this);
+    std::mem::forget(this);
+    ptr
+  }
+
+  /// Provides a raw pointer to the data.
+  pub fn as_ptr(this: &Self) -> *const T {
+    let ptr: *mut CompactInner<T> = this.ptr.as_ptr();
+    // SAFETY: Only computes the field address, keeping the provenance of the
+    // whole allocation for `from_raw`.
+    unsafe { std::ptr::addr_of_mut!((*ptr).data) as *const T }
+  }
+}
+
+impl<T: Clone> CompactArc<T> {
+  /// Makes a mutable reference into the given `CompactArc`, cloning the inner
+  /// value into a new allocation first if it is shared.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::CompactArc;
+  ///
+  /// let mut data = CompactArc::new(5);
+  /// let other = CompactArc::clone(&data);
+  ///
+  /// *CompactArc::make_mut(&mut data) += 1;
+  ///
+  /// assert_eq!(*data, 6);
+  /// assert_eq!(*other, 5);
+  /// ```
+  pub fn make_mut(this: &mut Self) -> &mut T {
+    if !CompactArc::is_unique(this) {
+      *this = CompactArc::new((**this).clone());
+    }
+    // SAFETY: We are now the only pointer to the allocation.
+    unsafe { &mut (*this.ptr.as_ptr()).data }
+  }
+}
+
+impl<T: ?Sized> Clone for CompactArc<T> {
+  /// Makes a clone of the `CompactArc` pointer, increasing the count.
+  #[inline]
+  fn clone(&self) -> CompactArc<T> {
+    // See `Arc::clone` for why `Relaxed` is enough here.
+    let old_size = self.inner().count.fetch_add(1, Relaxed);
+
+    // See `Arc::clone` for why we abort on (near) overflow.
+    if old_size > MAX_REFCOUNT {
+      crate::alloc::abort_on_overflow();
+    }
+
+    Self::from_inner(self.ptr)
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for CompactArc<T> {
+  type Target = T;
+
+  #[inline]
+  fn deref(&self) -> &T {
+    &self.inner().data
+  }
+}
+
+impl<T: ?Sized> Drop for CompactArc<T> {
+  /// Drops the `CompactArc`, dropping the value and freeing the allocation
+  /// if this was the last pointer.
+  #[inline]
+  fn drop(&mut self) {
+    if self.inner().count.fetch_sub(1, Release) != 1 {
+      return;
+    }
+
+    // See `Arc::drop` for why this fence is needed.
+    atomic::fence(Acquire);
+
+    // SAFETY: We were the last pointer; nothing else can observe the value.
+    unsafe {
+      std::ptr::drop_in_place(&mut (*self.ptr.as_ptr()).data);
+      dealloc_inner(self.ptr);
+    }
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display for CompactArc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for CompactArc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized> std::fmt::Pointer for CompactArc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Pointer::fmt(&(&**self as *const T), f)
+  }
+}
+
+/// Frees the memory backing a `CompactInner` without dropping its value.
+///
+/// # Safety
+///
+/// `ptr` must come from `Box::into_raw` and the value must already be dropped
+/// or moved out.
+unsafe fn dealloc_inner<T: ?Sized>(ptr: std::ptr::NonNull<CompactInner<T>>) {
+  let layout = std::alloc::Layout::for_value(ptr.as_ref());
+  std::alloc::dealloc(ptr.as_ptr().cast::<u8>(), layout);
+}
+
+/// Gets the offset within a `CompactInner` for the payload behind a pointer.
+fn data_offset<T>() -> usize {
+  std::alloc::Layout::new::<CompactInner<()>>()
+    .extend(std::alloc::Layout::new::<T>())
+    .expect("CompactInner layout overflow")
+    .1
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+
+  use std::sync::atomic::AtomicUsize;
+  use std::sync::atomic::Ordering::SeqCst;
+  use std::thread;
+
+  struct Tracked(std::sync::Arc<AtomicUsize>);
+
+  impl Drop for Tracked {
+    fn drop(&mut self) {
+      self.0.fetch_add(1, SeqCst);
+    }
+  }
+
+  #[test]
+  fn single_word() {
+    assert_eq!(
+      std::mem::size_of::<CompactArc<u8>>(),
+      std::mem::size_of::<usize>()
+    );
+    assert_eq!(
+      std::mem::size_of::<Option<CompactArc<u8>>>(),
+      std::mem::size_of::<usize>()
+    );
+    assert_eq!(
+      std::mem::size_of::<CompactInner<usize>>(),
+      2 * std::mem::size_of::<usize>()
+    );
+  }
+
+  #[test]
+  fn clone_drop() {
+    let dropped = std::sync::Arc::new(AtomicUsize::new(0));
+    let a = CompactArc::new(Tracked(std::sync::Arc::clone(&dropped)));
+    let b = CompactArc::clone(&a);
+
+    assert_eq!(CompactArc::count(&a), 2);
+    drop(a);
+    assert_eq!(dropped.load(SeqCst), 0);
+    drop(b);
+    assert_eq!(dropped.load(SeqCst), 1);
+  }
+
+  #[test]
+  fn try_unwrap_does_not_drop() {
+    let dropped = std::sync::Arc::new(AtomicUsize::new(0));
+    let a = CompactArc::new(Tracked(std::sync::Arc::clone(&dropped)));
+
+    let value = CompactArc::try_unwrap(a).ok().unwrap();
+    assert_eq!(dropped.load(SeqCst), 0);
+    drop(value);
+    assert_eq!(dropped.load(SeqCst), 1);
+  }
+
+  #[test]
+  fn arc_round_trip() {
+    let arc = Arc::new(String::from("hello"));
+    let weak = Arc::downgrade(&arc);
+
+    let compact = CompactArc::from_arc(arc).unwrap();
+    assert!(weak.upgrade().is_none());
+
+    let other = CompactArc::clone(&compact);
+    let compact = CompactArc::into_arc(compact).unwrap_err();
+    drop(other);
+
+    let arc = CompactArc::into_arc(compact).unwrap();
+    assert_eq!(*arc, "hello");
+  }
+
+  #[test]
+  fn raw_round_trip() {
+    let ptr = CompactArc::into_raw(CompactArc::new(7u64));
+    // SAFETY: `ptr` came from `into_raw` and is reclaimed once.
+    let x = unsafe { CompactArc::from_raw(ptr) };
+    assert_eq!(*x, 7);
+    assert!(CompactArc::is_unique(&x));
+  }
+
+  #[test]
+  fn make_mut() {
+    let mut data = CompactArc::new(5);
+    let before = CompactArc::as_ptr(&data);
+
+    *CompactArc::make_mut(&mut data) += 1;
+    assert_eq!(CompactArc::as_ptr(&data), before);
+
+    let other = CompactArc::clone(&data);
+    *CompactArc::make_mut(&mut data) += 1;
+    assert_eq!((*data, *other), (7, 6));
+  }
+
+  #[test]
+  fn share_between_threads() {
+    let data = CompactArc::new(vec![1, 2, 3]);
+
+    let handles: Vec<_> = (0..8)
+      .map(|_| {
+        let data = CompactArc::clone(&data);
+        thread::spawn(move || data.iter().sum::<i32>())
+      })
+      .collect();
+
+    for handle in handles {
+      assert_eq!(handle.join().unwrap(), 6);
+    }
+    assert!(CompactArc::is_unique(&data));
+  }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+  use super::*;
+
+  use ::loom::thread;
+
+  #[test]
+  fn loom_clone_drop_get_mut() {
+    ::loom::model(|| {
+      let mut a = CompactArc::new(0);
+      let b = CompactArc::clone(&a);
+
+      let t = thread::spawn(move || *b);
+      let seen = t.join().unwrap();
+
+      *CompactArc::get_mut(&mut a).unwrap() += 1;
+      assert_eq!((seen, *a), (0, 1));
+    });
+  }
+
+  #[test]
+  fn loom_try_unwrap() {
+    ::loom::model(|| {
+      let a = CompactArc::new(1);
+      let b = CompactArc::clone(&a);
+
+      let t = thread::spawn(move || drop(b));
+      let result = CompactArc::try_unwrap(a);
+      t.join().unwrap();
+
+      if let Err(a) = result {
+        assert!(CompactArc::is_unique(&a));
+      }
+    });
+  }
+}
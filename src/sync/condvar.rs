@@ -0,0 +1,558 @@
+//! Condition variables, for blocking a thread until a [`Mutex`]-protected condition holds.
+//!
+//! A [`Condvar`] is always used together with a [`MutexGuard`]: [`wait`] atomically releases the
+//! mutex and parks the thread, and takes the mutex back before returning. Another thread changes
+//! the protected state and calls [`notify_one`] or [`notify_all`] to wake the waiters up.
+//!
+//! Waits may wake up spuriously, so the condition has to be re-checked in a loop, which is what
+//! [`wait_while`] does.
+//!
+//! If the mutex [poisons](crate::sync::poison) and another thread panics while holding it during
+//! the wait, the plain wait methods panic once they have the lock back, like [`Mutex::lock`]. The
+//! `*_checked` methods return a [`PoisonError`] holding the guard instead.
+//!
+//! ```
+//! use pointer::sync::{Arc, Condvar, Mutex};
+//! use std::thread;
+//!
+//! let pair = Arc::new((Mutex::new(false), Condvar::new()));
+//! let pair2 = Arc::clone(&pair);
+//!
+//! thread::spawn(move || {
+//!   let (lock, cvar) = &*pair2;
+//!   *lock.lock() = true;
+//!   cvar.notify_one();
+//! });
+//!
+//! let (lock, cvar) = &*pair;
+//! let started = cvar.wait_while(lock.lock(), |started| !*started);
+//! assert!(*started);
+//! ```
+//!
+//! [`Mutex`]: crate::sync::Mutex
+//! [`Mutex::lock`]: crate::sync::Mutex::lock
+//! [`PoisonError`]: crate::sync::PoisonError
+//! [`wait`]: Condvar::wait
+//! [`wait_while`]: Condvar::wait_while
+//! [`notify_one`]: Condvar::notify_one
+//! [`notify_all`]: Condvar::notify_all
+
+use super::poison::{self, LockResult, PoisonError};
+use super::wait_queue::{ParkResult, WaitQueue};
+use super::MutexGuard;
+
+/// A type indicating whether a timed wait on a [`Condvar`] returned due to a
+/// time out or not.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct WaitTimeoutResult(bool);
+
+impl WaitTimeoutResult {
+  /// Returns `true` if the wait was known to have timed out.
+  pub fn timed_out(&self) -> bool {
+    self.0
+  }
+}
+
+/// A condition variable.
+///
+/// See the [module-level documentation](index.html) for more.
+pub struct Condvar {
+  queue: WaitQueue,
+}
+
+impl Condvar {
+  /// Creates a new condition variable which is ready to be waited on and
+  /// notified.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Condvar;
+  ///
+  /// let condvar = Condvar::new();
+  /// ```
+  pub fn new() -> Condvar {
+    Condvar {
+      queue: WaitQueue::new(),
+    }
+  }
+
+  /// Blocks the current thread until this condition variable receives a
+  /// notification.
+  ///
+  /// The mutex behind `guard` is unlocked while the thread is blocked and
+  /// locked again before this returns. The wakeup may be spurious, so the
+  /// caller should re-check its condition afterwards.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the mutex is [poisoned](crate::sync::Mutex::is_poisoned) when
+  /// this thread takes it back. Use [`wait_checked`](Condvar::wait_checked)
+  /// to recover the guard instead.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, Condvar, Mutex};
+  /// use std::thread;
+  ///
+  /// let pair = Arc::new((Mutex::new(false), Condvar::new()));
+  /// let pair2 = Arc::clone(&pair);
+  ///
+  /// thread::spawn(move || {
+  ///   let (lock, cvar) = &*pair2;
+  ///   *lock.lock() = true;
+  ///   cvar.notify_one();
+  /// });
+  ///
+  /// let (lock, cvar) = &*pair;
+  /// let mut started = lock.lock();
+  /// while !*started {
+  ///   started = cvar.wait(started);
+  /// }
+  /// ```
+  #[track_caller]
+  pub fn wait<'a, T: ?Sized>(
+    &self,
+    guard: MutexGuard<'a, T>,
+  ) -> MutexGuard<'a, T> {
+    poison::unwrap(self.wait_checked(guard))
+  }
+
+  /// Blocks the current thread like [`wait`](Condvar::wait), returning an
+  /// error that still holds the guard if the mutex is poisoned once this
+  /// thread takes it back.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, Condvar, MutexBuilder};
+  /// use std::thread;
+  ///
+  /// let pair = Arc::new((MutexBuilder::new().poison(true).build(false), Condvar::new()));
+  /// let pair2 = Arc::clone(&pair);
+  ///
+  /// let (lock, cvar) = &*pair;
+  /// let mut started = lock.lock();
+  /// let handle = thread::spawn(move || {
+  ///   let (lock, cvar) = &*pair2;
+  ///   let mut started = lock.lock();
+  ///   *started = true;
+  ///   cvar.notify_one();
+  ///   panic!("half-way through starting");
+  /// });
+  ///
+  /// let poisoned = loop {
+  ///   match cvar.wait_checked(started) {
+  ///     Ok(guard) => started = guard,
+  ///     Err(e) => break e.into_inner(),
+  ///   }
+  /// };
+  /// assert!(*poisoned);
+  /// drop(poisoned);
+  /// assert!(handle.join().is_err());
+  /// ```
+  pub fn wait_checked<'a, T: ?Sized>(
+    &self,
+    guard: MutexGuard<'a, T>,
+  ) -> LockResult<MutexGuard<'a, T>> {
+    self.wait_until_internal(&guard, None);
+    let mutex = guard.mutex;
+    mutex.poison.check(guard)
+  }
+
+  /// Blocks the current thread while `condition` returns `true`.
+  ///
+  /// `condition` is checked with the mutex locked, first before waiting at
+  /// all and then after every wakeup.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the mutex is [poisoned](crate::sync::Mutex::is_poisoned) when
+  /// this thread takes it back after a wait. Use
+  /// [`wait_while_checked`](Condvar::wait_while_checked) to recover the guard
+  /// instead.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, Condvar, Mutex};
+  /// use std::thread;
+  ///
+  /// let pair = Arc::new((Mutex::new(0), Condvar::new()));
+  /// let pair2 = Arc::clone(&pair);
+  ///
+  /// thread::spawn(move || {
+  ///   let (lock, cvar) = &*pair2;
+  ///   for _ in 0..3 {
+  ///     *lock.lock() += 1;
+  ///     cvar.notify_all();
+  ///   }
+  /// });
+  ///
+  /// let (lock, cvar) = &*pair;
+  /// let count = cvar.wait_while(lock.lock(), |count| *count < 3);
+  /// assert_eq!(*count, 3);
+  /// ```
+  #[track_caller]
+  pub fn wait_while<'a, T: ?Sized>(
+    &self,
+    guard: MutexGuard<'a, T>,
+    condition: impl FnMut(&mut T) -> bool,
+  ) -> MutexGuard<'a, T> {
+    poison::unwrap(self.wait_while_checked(guard, condition))
+  }
+
+  /// Blocks the current thread like [`wait_while`](Condvar::wait_while),
+  /// returning an error that still holds the guard as soon as the mutex is
+  /// poisoned after a wait. `condition` isn't checked on the poisoned data.
+  pub fn wait_while_checked<'a, T: ?Sized>(
+    &self,
+    mut guard: MutexGuard<'a, T>,
+    mut condition: impl FnMut(&mut T) -> bool,
+  ) -> LockResult<MutexGuard<'a, T>> {
+    while condition(&mut *guard) {
+      guard = self.wait_checked(guard)?;
+    }
+    Ok(guard)
+  }
+
+  /// Waits on this condition variable for a notification, timing out after
+  /// `dur`.
+  ///
+  /// Like [`wait`](Condvar::wait), the wakeup may be spurious. The returned
+  /// [`WaitTimeoutResult`] tells whether the time ran out.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the mutex is [poisoned](crate::sync::Mutex::is_poisoned) when
+  /// this thread takes it back. Use
+  /// [`wait_timeout_checked`](Condvar::wait_timeout_checked) to recover the
+  /// guard instead.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Condvar, Mutex};
+  /// use std::time::Duration;
+  ///
+  /// let lock = Mutex::new(());
+  /// let cvar = Condvar::new();
+  ///
+  /// let (_guard, result) = cvar.wait_timeout(lock.lock(), Duration::from_millis(1));
+  /// assert!(result.timed_out());
+  /// ```
+  #[track_caller]
+  pub fn wait_timeout<'a, T: ?Sized>(
+    &self,
+    guard: MutexGuard<'a, T>,
+    dur: std::time::Duration,
+  ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+    poison::unwrap(self.wait_timeout_checked(guard, dur))
+  }
+
+  /// Waits like [`wait_timeout`](Condvar::wait_timeout), returning an error
+  /// that still holds the guard and the [`WaitTimeoutResult`] if the mutex
+  /// is poisoned once this thread takes it back.
+  pub fn wait_timeout_checked<'a, T: ?Sized>(
+    &self,
+    guard: MutexGuard<'a, T>,
+    dur: std::time::Duration,
+  ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)> {
+    let deadline = std::time::Instant::now().checked_add(dur);
+    let result = self.wait_until_internal(&guard, deadline);
+    let mutex = guard.mutex;
+    mutex.poison.check((guard, result))
+  }
+
+  /// Waits on this condition variable while `condition` returns `true`,
+  /// timing out after `dur` in total.
+  ///
+  /// Returns with [`WaitTimeoutResult::timed_out`] set if `condition` still
+  /// held when the time ran out.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the mutex is [poisoned](crate::sync::Mutex::is_poisoned) when
+  /// this thread takes it back after a wait. Use
+  /// [`wait_timeout_while_checked`](Condvar::wait_timeout_while_checked) to
+  /// recover the guard instead.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Condvar, Mutex};
+  /// use std::time::Duration;
+  ///
+  /// let lock = Mutex::new(false);
+  /// let cvar = Condvar::new();
+  ///
+  /// let (ready, result) =
+  ///   cvar.wait_timeout_while(lock.lock(), Duration::from_millis(1), |ready| !*ready);
+  /// assert!(result.timed_out());
+  /// assert!(!*ready);
+  /// ```
+  #[track_caller]
+  pub fn wait_timeout_while<'a, T: ?Sized>(
+    &self,
+    guard: MutexGuard<'a, T>,
+    dur: std::time::Duration,
+    condition: impl FnMut(&mut T) -> bool,
+  ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+    poison::unwrap(self.wait_timeout_while_checked(guard, dur, condition))
+  }
+
+  /// Waits like [`wait_timeout_while`](Condvar::wait_timeout_while),
+  /// returning an error that still holds the guard and the
+  /// [`WaitTimeoutResult`] as soon as the mutex is poisoned after a wait.
+  /// `condition` isn't checked on the poisoned data.
+  pub fn wait_timeout_while_checked<'a, T: ?Sized>(
+    &self,
+    mut guard: MutexGuard<'a, T>,
+    dur: std::time::Duration,
+    mut condition: impl FnMut(&mut T) -> bool,
+  ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)> {
+    let deadline = std::time::Instant::now().checked_add(dur);
+    let mutex = guard.mutex;
+    while condition(&mut *guard) {
+      let result = self.wait_until_internal(&guard, deadline);
+      if mutex.is_poisoned() {
+        return Err(PoisonError::new((guard, result)));
+      }
+      if result.timed_out() {
+        let timed_out = condition(&mut *guard);
+        return Ok((guard, WaitTimeoutResult(timed_out)));
+      }
+    }
+    Ok((guard, WaitTimeoutResult(false)))
+  }
+
+  /// Wakes up one blocked thread on this condvar, if there is one.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Condvar;
+  ///
+  /// // Notifying without waiters is a no-op.
+  /// Condvar::new().notify_one();
+  /// ```
+  pub fn notify_one(&self) {
+    self.queue.unpark_one(|_, _| {});
+  }
+
+  /// Wakes up all blocked threads on this condvar.
+  pub fn notify_all(&self) {
+    self.queue.unpark_all(|| {});
+  }
+
+  /// Parks on the queue, releasing the mutex only once we are enqueued so a
+  /// notification sent after the caller's last check can't be missed.
+  fn wait_until_internal<T: ?Sized>(
+    &self,
+    guard: &MutexGuard<'_, T>,
+    deadline: Option<std::time::Instant>,
+  ) -> WaitTimeoutResult {
+    let raw = &guard.mutex.raw;
+    // SAFETY: The guard holds the lock; it is taken back below before the
+    // guard can be used again.
+    let result =
+      self
+        .queue
+        .park(|| true, || unsafe { raw.unlock() }, |_| {}, deadline);
+    raw.lock();
+    WaitTimeoutResult(result == ParkResult::TimedOut)
+  }
+}
+
+impl Default for Condvar {
+  fn default() -> Condvar {
+    Condvar::new()
+  }
+}
+
+impl std::fmt::Debug for Condvar {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Condvar").finish_non_exhaustive()
+  }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+  use crate::sync::{Arc, Mutex, MutexBuilder};
+
+  use std::thread;
+  use std::time::Duration;
+
+  #[test]
+  fn producer_consumer() {
+    let queue = Arc::new((Mutex::new(Vec::new()), Condvar::new()));
+
+    let producer = {
+      let queue = Arc::clone(&queue);
+      thread::spawn(move || {
+        for i in 0..100 {
+          let (items, cvar) = &*queue;
+          items.lock().push(i);
+          cvar.notify_one();
+        }
+      })
+    };
+
+    let (items, cvar) = &*queue;
+    let mut received = Vec::new();
+    while received.len() < 100 {
+      let mut guard = cvar.wait_while(items.lock(), |items| items.is_empty());
+      received.append(&mut *guard);
+    }
+
+    producer.join().unwrap();
+    assert_eq!(received, (0..100).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn notify_all_wakes_everyone() {
+    let state = Arc::new((Mutex::new((false, 0)), Condvar::new()));
+
+    let handles: Vec<_> = (0..4)
+      .map(|_| {
+        let state = Arc::clone(&state);
+        thread::spawn(move || {
+          let (lock, cvar) = &*state;
+          let mut guard = lock.lock();
+          guard.1 += 1;
+          let _guard = cvar.wait_while(guard, |(go, _)| !*go);
+        })
+      })
+      .collect();
+
+    let (lock, cvar) = &*state;
+    // Wait until everyone is waiting before releasing them.
+    while lock.lock().1 < 4 {
+      thread::yield_now();
+    }
+    lock.lock().0 = true;
+    cvar.notify_all();
+
+    for handle in handles {
+      handle.join().unwrap();
+    }
+  }
+
+  #[test]
+  fn wait_timeout_reacquires_lock() {
+    let lock = Mutex::new(0);
+    let cvar = Condvar::new();
+
+    let (guard, result) =
+      cvar.wait_timeout(lock.lock(), Duration::from_millis(5));
+    assert!(result.timed_out());
+    assert!(lock.is_locked());
+    drop(guard);
+    assert!(!lock.is_locked());
+  }
+
+  #[test]
+  fn wait_timeout_while_succeeds() {
+    let state = Arc::new((Mutex::new(false), Condvar::new()));
+
+    let other = Arc::clone(&state);
+    let setter = thread::spawn(move || {
+      let (lock, cvar) = &*other;
+      *lock.lock() = true;
+      cvar.notify_one();
+    });
+
+    let (lock, cvar) = &*state;
+    let (ready, result) =
+      cvar.wait_timeout_while(lock.lock(), Duration::from_secs(60), |r| !*r);
+    assert!(!result.timed_out());
+    assert!(*ready);
+    drop(ready);
+
+    setter.join().unwrap();
+  }
+
+  /// Sets the flag and panics with the lock held, once the caller waits.
+  fn panic_while_parked(
+    state: &Arc<(Mutex<bool>, Condvar)>,
+  ) -> thread::JoinHandle<()> {
+    let other = Arc::clone(state);
+    thread::spawn(move || {
+      let (lock, cvar) = &*other;
+      let mut ready = lock.lock();
+      *ready = true;
+      cvar.notify_one();
+      panic!("poison the lock");
+    })
+  }
+
+  #[test]
+  fn waits_report_poison() {
+    let state = Arc::new((
+      MutexBuilder::new().poison(true).build(false),
+      Condvar::new(),
+    ));
+    let (lock, cvar) = &*state;
+
+    let guard = lock.lock();
+    let panicker = panic_while_parked(&state);
+    let mut ready = cvar
+      .wait_while_checked(guard, |ready| !*ready)
+      .unwrap_err()
+      .into_inner();
+    assert!(*ready);
+    assert!(panicker.join().is_err());
+
+    *ready = false;
+    lock.clear_poison();
+    let panicker = panic_while_parked(&state);
+    let (ready, result) = cvar
+      .wait_timeout_while_checked(ready, Duration::from_secs(60), |r| !*r)
+      .unwrap_err()
+      .into_inner();
+    assert!(!result.timed_out());
+    assert!(panicker.join().is_err());
+
+    drop(ready);
+    lock.clear_poison();
+    let guard = lock.lock();
+    let panicker = panic_while_parked(&state);
+    let waited = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      drop(cvar.wait(guard));
+    }));
+    assert!(waited.is_err(), "`wait` panics on a poisoned mutex");
+    assert!(panicker.join().is_err());
+    assert!(!lock.is_locked());
+  }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+  use super::*;
+  use crate::sync::{Arc, Mutex};
+
+  use ::loom::thread;
+
+  #[test]
+  fn loom_notify_one() {
+    ::loom::model(|| {
+      let state = Arc::new((Mutex::new(false), Condvar::new()));
+
+      let other = Arc::clone(&state);
+      let t = thread::spawn(move || {
+        let (lock, cvar) = &*other;
+        *lock.lock() = true;
+        cvar.notify_one();
+      });
+
+      let (lock, cvar) = &*state;
+      let ready = cvar.wait_while(lock.lock(), |ready| !*ready);
+      assert!(*ready);
+      drop(ready);
+
+      t.join().unwrap();
+    });
+  }
+}
@@ -0,0 +1,238 @@
+//! A mutex for sharing data between interrupt handlers and the code they interrupt.
+//!
+//! On a single-core microcontroller there is nothing to block on: the only way another context
+//! can observe shared data halfway through an update is by interrupting the code doing it. A
+//! [`CriticalSectionMutex<T>`][CriticalSectionMutex] therefore doesn't lock anything itself.
+//! Access goes through a [`CriticalSection`] token from the [`critical-section`] crate, and
+//! holding one proves that interrupts (or, on multi-core targets, the other cores) are held off.
+//!
+//! Several borrows can be taken inside the same critical section, so the mutex only ever hands
+//! out `&T`. Pair it with a [`Cell`] or [`RefCell`] to mutate the protected value.
+//!
+//! This module is only available with the `critical-section` feature. The final binary has to
+//! provide a critical-section implementation for its target, as described in the documentation
+//! of the [`critical-section`] crate.
+//!
+//! ```
+//! use pointer::sync::CriticalSectionMutex;
+//! use pointer::Cell;
+//!
+//! static TICKS: CriticalSectionMutex<Cell<u32>> =
+//!   CriticalSectionMutex::new(Cell::new(0));
+//!
+//! // Called from the timer interrupt.
+//! fn on_tick() {
+//!   critical_section::with(|cs| {
+//!     let ticks = TICKS.borrow(cs);
+//!     ticks.set(ticks.get() + 1);
+//!   });
+//! }
+//!
+//! on_tick();
+//! on_tick();
+//! assert_eq!(TICKS.lock(|ticks| ticks.get()), 2);
+//! ```
+//!
+//! [`CriticalSection`]: ::critical_section::CriticalSection
+//! [`critical-section`]: ::critical_section
+//! [`Cell`]: crate::Cell
+//! [`RefCell`]: crate::RefCell
+
+use ::critical_section::CriticalSection;
+
+use crate::{Ref, RefCell, RefMut};
+
+/// A mutex that is accessed from within a critical section.
+///
+/// See the [module-level documentation](index.html) for more.
+pub struct CriticalSectionMutex<T: ?Sized> {
+  data: T,
+}
+
+// A critical section runs on one context at a time, so `&T` never escapes to
+// two of them at once and `T` doesn't need to be `Sync`.
+unsafe impl<T: ?Sized + Send> Sync for CriticalSectionMutex<T> {}
+
+impl<T> CriticalSectionMutex<T> {
+  /// Creates a new mutex. Being `const`, it can initialize a `static`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::CriticalSectionMutex;
+  ///
+  /// static COUNT: CriticalSectionMutex<u32> = CriticalSectionMutex::new(0);
+  /// ```
+  pub const fn new(t: T) -> CriticalSectionMutex<T> {
+    CriticalSectionMutex { data: t }
+  }
+
+  /// Consumes this mutex, returning the underlying data.
+  pub fn into_inner(self) -> T {
+    self.data
+  }
+}
+
+impl<T: ?Sized> CriticalSectionMutex<T> {
+  /// Borrows the data for the duration of the critical section `cs`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::CriticalSectionMutex;
+  /// use pointer::Cell;
+  ///
+  /// let flag = CriticalSectionMutex::new(Cell::new(false));
+  ///
+  /// critical_section::with(|cs| flag.borrow(cs).set(true));
+  /// assert!(critical_section::with(|cs| flag.borrow(cs).get()));
+  /// ```
+  pub fn borrow<'cs>(&'cs self, _cs: CriticalSection<'cs>) -> &'cs T {
+    &self.data
+  }
+
+  /// Enters a critical section and calls `f` with the data.
+  ///
+  /// Interrupts are held off for as long as `f` runs, so keep it short.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::CriticalSectionMutex;
+  /// use pointer::RefCell;
+  ///
+  /// let queue = CriticalSectionMutex::new(RefCell::new(vec![1, 2]));
+  ///
+  /// let first = queue.lock(|queue| queue.borrow_mut().remove(0));
+  /// assert_eq!(first, 1);
+  /// ```
+  pub fn lock<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+    ::critical_section::with(|cs| f(self.borrow(cs)))
+  }
+
+  /// Returns a mutable reference to the underlying data.
+  ///
+  /// Since this call borrows the `CriticalSectionMutex` mutably, no critical
+  /// section is needed.
+  pub fn get_mut(&mut self) -> &mut T {
+    &mut self.data
+  }
+}
+
+impl<T> CriticalSectionMutex<RefCell<T>> {
+  /// Immutably borrows the wrapped [`RefCell`] for the duration of `cs`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently mutably borrowed.
+  pub fn borrow_ref<'cs>(&'cs self, cs: CriticalSection<'cs>) -> Ref<'cs, T> {
+    self.borrow(cs).borrow()
+  }
+
+  /// Mutably borrows the wrapped [`RefCell`] for the duration of `cs`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::CriticalSectionMutex;
+  /// use pointer::RefCell;
+  ///
+  /// let log = CriticalSectionMutex::new(RefCell::new(Vec::new()));
+  ///
+  /// critical_section::with(|cs| log.borrow_ref_mut(cs).push("irq"));
+  /// assert_eq!(log.into_inner().into_inner(), ["irq"]);
+  /// ```
+  pub fn borrow_ref_mut<'cs>(
+    &'cs self,
+    cs: CriticalSection<'cs>,
+  ) -> RefMut<'cs, T> {
+    self.borrow(cs).borrow_mut()
+  }
+
+  /// Replaces the wrapped value with `t`, returning the old value.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently borrowed.
+  pub fn replace(&self, cs: CriticalSection<'_>, t: T) -> T {
+    self.borrow(cs).replace(t)
+  }
+}
+
+impl<T: Default> CriticalSectionMutex<RefCell<T>> {
+  /// Takes the wrapped value, leaving `Default::default()` in its place.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently borrowed.
+  pub fn take(&self, cs: CriticalSection<'_>) -> T {
+    self.borrow(cs).take()
+  }
+}
+
+impl<T: Default> Default for CriticalSectionMutex<T> {
+  fn default() -> CriticalSectionMutex<T> {
+    CriticalSectionMutex::new(Default::default())
+  }
+}
+
+impl<T> From<T> for CriticalSectionMutex<T> {
+  fn from(t: T) -> CriticalSectionMutex<T> {
+    CriticalSectionMutex::new(t)
+  }
+}
+
+impl<T: ?Sized> std::fmt::Debug for CriticalSectionMutex<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // Printing the data would need a critical section, which a formatter
+    // running inside an interrupt handler may not expect.
+    f.debug_struct("CriticalSectionMutex")
+      .finish_non_exhaustive()
+  }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+  use crate::Cell;
+
+  use std::thread;
+
+  #[test]
+  fn shared_between_threads() {
+    static COUNT: CriticalSectionMutex<Cell<u32>> =
+      CriticalSectionMutex::new(Cell::new(0));
+
+    let handles: Vec<_> = (0..4)
+      .map(|_| {
+        thread::spawn(|| {
+          for _ in 0..1000 {
+            COUNT.lock(|count| count.set(count.get() + 1));
+          }
+        })
+      })
+      .collect();
+
+    for handle in handles {
+      handle.join().unwrap();
+    }
+    assert_eq!(COUNT.lock(Cell::get), 4000);
+  }
+
+  #[test]
+  fn refcell_helpers() {
+    let mutex = CriticalSectionMutex::new(RefCell::new(vec![1]));
+
+    ::critical_section::with(|cs| {
+      mutex.borrow_ref_mut(cs).push(2);
+      assert_eq!(*mutex.borrow_ref(cs), [1, 2]);
+      assert_eq!(mutex.replace(cs, vec![3]), [1, 2]);
+      assert_eq!(mutex.take(cs), [3]);
+    });
+    assert!(mutex.into_inner().into_inner().is_empty());
+  }
+}
@@ -0,0 +1,99 @@
+//! Self-deadlock detection for [`Mutex`] and [`RwLock`], for the `deadlock-debug` feature.
+//!
+//! With the feature, each raw lock keeps a list of the threads holding it, with the backtrace of
+//! where each one acquired it. A thread that is about to block on a lock it already holds would
+//! wait for itself forever. Instead, it panics with both backtraces: where it acquired the lock
+//! first, and where it tried again. That covers relocking a mutex, taking a write lock while
+//! holding a read or write lock, and taking a read lock that has to wait behind a writer while
+//! holding one already. Backtraces are captured with
+//! [`Backtrace::capture`](std::backtrace::Backtrace::capture), so they are only recorded when
+//! `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` is set.
+//!
+//! Without the feature, [`Holders`] is empty and does nothing.
+//!
+//! [`Mutex`]: super::Mutex
+//! [`RwLock`]: super::RwLock
+
+/// The threads holding a lock.
+#[cfg(feature = "deadlock-debug")]
+pub(crate) struct Holders {
+  held: std::sync::Mutex<Vec<Holder>>,
+}
+
+#[cfg(feature = "deadlock-debug")]
+struct Holder {
+  thread: std::thread::ThreadId,
+  backtrace: std::backtrace::Backtrace,
+}
+
+#[cfg(feature = "deadlock-debug")]
+impl Holders {
+  pub(crate) fn new() -> Holders {
+    Holders {
+      held: std::sync::Mutex::new(Vec::new()),
+    }
+  }
+
+  fn held(&self) -> std::sync::MutexGuard<'_, Vec<Holder>> {
+    // A panic while the list is locked can't leave it half updated.
+    self.held.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  /// Records that the current thread has acquired the lock.
+  pub(crate) fn acquired(&self) {
+    let holder = Holder {
+      thread: std::thread::current().id(),
+      backtrace: std::backtrace::Backtrace::capture(),
+    };
+    self.held().push(holder);
+  }
+
+  /// Records that the current thread has released the lock once.
+  pub(crate) fn released(&self) {
+    let thread = std::thread::current().id();
+    let mut held = self.held();
+    if let Some(i) = held.iter().rposition(|h| h.thread == thread) {
+      held.swap_remove(i);
+    }
+  }
+
+  /// Panics if the current thread, which is about to block on the lock,
+  /// already holds it.
+  #[track_caller]
+  pub(crate) fn check_blocking(&self, lock: &str) {
+    let thread = std::thread::current().id();
+    let held = self.held();
+    if let Some(first) = held.iter().find(|h| h.thread == thread) {
+      let again = std::backtrace::Backtrace::capture();
+      let first = first.backtrace.to_string();
+      drop(held);
+      panic!(
+        "deadlock: this thread already holds this {} and would wait for \
+         itself forever\n\nfirst acquired at:\n{}\ntried again at:\n{}",
+        lock, first, again
+      );
+    }
+  }
+}
+
+/// The threads holding a lock, which aren't tracked without the
+/// `deadlock-debug` feature.
+#[cfg(not(feature = "deadlock-debug"))]
+pub(crate) struct Holders;
+
+#[cfg(not(feature = "deadlock-debug"))]
+impl Holders {
+  #[inline(always)]
+  pub(crate) fn new() -> Holders {
+    Holders
+  }
+
+  #[inline(always)]
+  pub(crate) fn acquired(&self) {}
+
+  #[inline(always)]
+  pub(crate) fn released(&self) {}
+
+  #[inline(always)]
+  pub(crate) fn check_blocking(&self, _lock: &str) {}
+}
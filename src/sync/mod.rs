@@ -0,0 +1,49 @@
+//! Thread-safe shared pointers.
+//!
+//! The types in this module are the multi-threaded counterparts of the ones found at the crate root.
+//! Where [`Rc<T>`][`Rc`] uses plain [`Cell`]s for its reference counts, [`Arc<T>`][`Arc`] uses
+//! [atomic integers][atomic], so it can be shared and sent between threads.
+//!
+//! [`Rc`]: crate::Rc
+//! [`Cell`]: crate::Cell
+//! [atomic]: std::sync::atomic
+
+pub mod arc;
+pub mod arc_cell;
+mod async_lock;
+pub mod async_mutex;
+pub mod async_rwlock;
+pub mod compact_arc;
+pub mod condvar;
+#[cfg(feature = "critical-section")]
+pub mod critical_section;
+mod deadlock_debug;
+pub mod mutex;
+pub mod poison;
+pub mod reentrant_mutex;
+pub mod rwlock;
+pub mod shared_bytes;
+pub mod spin;
+mod wait_queue;
+
+#[cfg(feature = "critical-section")]
+pub use self::critical_section::CriticalSectionMutex;
+pub use crate::shared_string::ArcString;
+pub use arc::{Arc, ArcBorrow, Weak};
+pub use arc_cell::ArcCell;
+pub use async_mutex::{AsyncMutex, AsyncMutexGuard, AsyncMutexLockFuture};
+pub use async_rwlock::{
+  AsyncRwLock, AsyncRwLockReadFuture, AsyncRwLockReadGuard,
+  AsyncRwLockWriteFuture, AsyncRwLockWriteGuard,
+};
+pub use compact_arc::CompactArc;
+pub use condvar::{Condvar, WaitTimeoutResult};
+pub use mutex::{MappedMutexGuard, Mutex, MutexBuilder, MutexGuard};
+pub use poison::{LockResult, PoisonError, TryLockResult};
+pub use reentrant_mutex::{ReentrantMutex, ReentrantMutexGuard};
+pub use rwlock::{
+  MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockBuilder,
+  RwLockReadGuard, RwLockWriteGuard,
+};
+pub use shared_bytes::{SharedBytes, SharedBytesMut};
+pub use spin::{SpinLock, SpinLockGuard};
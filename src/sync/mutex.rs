@@ -0,0 +1,990 @@
+//! A mutual exclusion primitive useful for protecting shared data.
+//!
+//! [`Mutex<T>`][Mutex] is the multi-threaded counterpart of [`RefCell<T>`][refcell]'s
+//! [`borrow_mut`]: it hands out at most one [`MutexGuard`] at a time, and the guard gives
+//! `&mut T` access to the protected value until it is dropped. Where a `RefCell` panics on a
+//! conflicting borrow, a `Mutex` blocks the calling thread until the lock is released.
+//!
+//! Locking is a single compare-and-swap when the lock is free. Under contention a thread spins
+//! briefly and then parks until the holder wakes it in [`MutexGuard`]'s `Drop`, so waiting threads
+//! don't burn CPU time.
+//!
+//! Unlike [`std::sync::Mutex`], this mutex is not poisoned by default when a thread panics while
+//! holding it. A mutex built with [`MutexBuilder::new().poison(true)`][poison] poisons like the
+//! standard one; see the [`poison`](super::poison) module.
+//!
+//! ```
+//! use pointer::sync::{Arc, Mutex};
+//! use std::thread;
+//!
+//! let counter = Arc::new(Mutex::new(0));
+//!
+//! let handles: Vec<_> = (0..8)
+//!   .map(|_| {
+//!     let counter = Arc::clone(&counter);
+//!     thread::spawn(move || *counter.lock() += 1)
+//!   })
+//!   .collect();
+//!
+//! for handle in handles {
+//!   handle.join().unwrap();
+//! }
+//! assert_eq!(*counter.lock(), 8);
+//! ```
+//!
+//! [refcell]: crate::RefCell
+//! [`borrow_mut`]: crate::RefCell::borrow_mut
+//! [poison]: MutexBuilder::poison
+
+use super::deadlock_debug::Holders;
+use super::poison::{self, LockResult, TryLockResult};
+use super::wait_queue::{ParkResult, WaitQueue, SPIN_LIMIT};
+use crate::loom::atomic::AtomicU8;
+use crate::loom::atomic::Ordering::{Acquire, Relaxed, Release};
+use crate::loom::cell::UnsafeCell;
+use crate::loom::hint;
+
+/// The lock is held.
+const LOCKED: u8 = 1;
+/// At least one thread may be parked on the wait queue.
+const PARKED: u8 = 2;
+
+/// The locking protocol behind [`Mutex`], without the data.
+pub(crate) struct RawMutex {
+  state: AtomicU8,
+  queue: WaitQueue,
+  holders: Holders,
+}
+
+impl RawMutex {
+  pub(crate) fn new() -> RawMutex {
+    RawMutex {
+      state: AtomicU8::new(0),
+      queue: WaitQueue::new(),
+      holders: Holders::new(),
+    }
+  }
+
+  /// Acquires the lock, blocking the current thread until it is able to.
+  #[inline]
+  #[track_caller]
+  pub(crate) fn lock(&self) {
+    if self
+      .state
+      .compare_exchange_weak(0, LOCKED, Acquire, Relaxed)
+      .is_ok()
+    {
+      self.holders.acquired();
+    } else {
+      self.holders.check_blocking("Mutex");
+      self.lock_slow(None);
+    }
+  }
+
+  /// Attempts to acquire the lock without blocking.
+  #[inline]
+  pub(crate) fn try_lock(&self) -> bool {
+    let mut state = self.state.load(Relaxed);
+    loop {
+      if state & LOCKED != 0 {
+        return false;
+      }
+      match self.state.compare_exchange_weak(
+        state,
+        state | LOCKED,
+        Acquire,
+        Relaxed,
+      ) {
+        Ok(_) => {
+          self.holders.acquired();
+          return true;
+        }
+        Err(s) => state = s,
+      }
+    }
+  }
+
+  /// Spins, then parks until the lock is acquired or `deadline` passes.
+  /// Returns whether the lock was acquired.
+  #[cold]
+  pub(crate) fn lock_slow(&self, deadline: Option<std::time::Instant>) -> bool {
+    let mut spins = 0;
+    loop {
+      let state = self.state.load(Relaxed);
+
+      if state & LOCKED == 0 {
+        if self
+          .state
+          .compare_exchange_weak(state, state | LOCKED, Acquire, Relaxed)
+          .is_ok()
+        {
+          self.holders.acquired();
+          return true;
+        }
+        continue;
+      }
+
+      // Spin for a while if nobody is parked yet, the lock is probably
+      // held for a short critical section.
+      if state & PARKED == 0 && spins < SPIN_LIMIT {
+        spins += 1;
+        hint::spin_loop();
+        continue;
+      }
+
+      let validate = || loop {
+        // Only sleep while the lock is held, and make sure the unlocking
+        // thread sees the `PARKED` bit so it knows to wake us.
+        let state = self.state.load(Relaxed);
+        if state & LOCKED == 0 {
+          return false;
+        }
+        if state & PARKED != 0
+          || self
+            .state
+            .compare_exchange_weak(state, state | PARKED, Relaxed, Relaxed)
+            .is_ok()
+        {
+          return true;
+        }
+      };
+      let timed_out = |have_more: bool| {
+        if !have_more {
+          self.state.fetch_and(!PARKED, Relaxed);
+        }
+      };
+      let result = self.queue.park(validate, || {}, timed_out, deadline);
+      if result == ParkResult::TimedOut {
+        return false;
+      }
+      spins = 0;
+    }
+  }
+
+  /// Releases the lock.
+  ///
+  /// # Safety
+  ///
+  /// The lock must be held by the current context.
+  #[inline]
+  pub(crate) unsafe fn unlock(&self) {
+    self.holders.released();
+    if self
+      .state
+      .compare_exchange(LOCKED, 0, Release, Relaxed)
+      .is_err()
+    {
+      self.unlock_slow();
+    }
+  }
+
+  #[cold]
+  fn unlock_slow(&self) {
+    // Hand the lock back while the queue is locked, so a thread that is
+    // about to park either sees it is free or gets woken up.
+    self.queue.unpark_one(|_, have_more| {
+      let state = if have_more { PARKED } else { 0 };
+      self.state.store(state, Release);
+    });
+  }
+
+  /// Returns `true` if the lock is currently held.
+  #[inline]
+  pub(crate) fn is_locked(&self) -> bool {
+    self.state.load(Relaxed) & LOCKED != 0
+  }
+}
+
+/// A mutual exclusion primitive useful for protecting shared data.
+///
+/// See the [module-level documentation](index.html) for more.
+pub struct Mutex<T: ?Sized> {
+  pub(crate) raw: RawMutex,
+  pub(crate) poison: poison::Flag,
+  data: UnsafeCell<T>,
+}
+
+/// A builder for a [`Mutex`] with a non-default configuration.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::sync::MutexBuilder;
+///
+/// let mutex = MutexBuilder::new().poison(true).build(0);
+/// assert!(!mutex.is_poisoned());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MutexBuilder {
+  poison: bool,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+/// An RAII guard for a locked [`Mutex`], returned by [`Mutex::lock`] and
+/// [`Mutex::try_lock`].
+///
+/// The protected data can be accessed through this guard via its [`Deref`]
+/// and [`DerefMut`] implementations. The lock is released when the guard is
+/// dropped.
+///
+/// [`Deref`]: std::ops::Deref
+/// [`DerefMut`]: std::ops::DerefMut
+#[must_use = "if unused the Mutex will immediately unlock"]
+pub struct MutexGuard<'a, T: ?Sized> {
+  pub(crate) mutex: &'a Mutex<T>,
+  poison: poison::Guard,
+  // Guards must be dropped on the thread that locked the mutex.
+  marker: std::marker::PhantomData<*const ()>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
+
+/// An RAII guard for a part of the data of a locked [`Mutex`], returned by
+/// [`MutexGuard::map`].
+///
+/// It keeps the whole mutex locked until it is dropped. Unlike a
+/// [`MutexGuard`] it can't be waited on with a [`Condvar`], since that would
+/// release the lock while the mapped reference is alive.
+///
+/// [`Condvar`]: crate::sync::Condvar
+#[must_use = "if unused the Mutex will immediately unlock"]
+pub struct MappedMutexGuard<'a, T: ?Sized> {
+  raw: &'a RawMutex,
+  poison_flag: &'a poison::Flag,
+  poison: poison::Guard,
+  data: *mut T,
+  marker: std::marker::PhantomData<&'a mut T>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for MappedMutexGuard<'_, T> {}
+
+impl MutexBuilder {
+  /// Creates a builder for a mutex that doesn't poison.
+  pub fn new() -> MutexBuilder {
+    MutexBuilder { poison: false }
+  }
+
+  /// Sets whether a panic while holding the lock poisons it.
+  ///
+  /// With `false` (the default) a panic just releases the lock. With `true`
+  /// the lock is marked poisoned, and locking it again reports that.
+  pub fn poison(mut self, poison: bool) -> MutexBuilder {
+    self.poison = poison;
+    self
+  }
+
+  /// Creates an unlocked `Mutex` holding `t` with the configured policy.
+  pub fn build<T>(self, t: T) -> Mutex<T> {
+    Mutex {
+      raw: RawMutex::new(),
+      poison: poison::Flag::new(self.poison),
+      data: UnsafeCell::new(t),
+    }
+  }
+}
+
+impl Default for MutexBuilder {
+  fn default() -> MutexBuilder {
+    MutexBuilder::new()
+  }
+}
+
+impl<T> Mutex<T> {
+  /// Creates a new mutex in an unlocked state ready for use.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Mutex;
+  ///
+  /// let mutex = Mutex::new(0);
+  /// ```
+  pub fn new(t: T) -> Mutex<T> {
+    MutexBuilder::new().build(t)
+  }
+
+  /// Consumes this mutex, returning the underlying data.
+  ///
+  /// The data is returned even if the mutex is poisoned.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Mutex;
+  ///
+  /// let mutex = Mutex::new(0);
+  /// assert_eq!(mutex.into_inner(), 0);
+  /// ```
+  pub fn into_inner(self) -> T {
+    self.data.into_inner()
+  }
+}
+
+impl<T: ?Sized> Mutex<T> {
+  /// Acquires the mutex, blocking the current thread until it is able to.
+  ///
+  /// Locking a mutex that the current thread already holds deadlocks. With
+  /// the `deadlock-debug` feature, it panics instead, with the backtraces of
+  /// both acquisitions.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the mutex is [poisoned](Mutex::is_poisoned). Use
+  /// [`lock_checked`](Mutex::lock_checked) to recover the guard instead.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, Mutex};
+  /// use std::thread;
+  ///
+  /// let mutex = Arc::new(Mutex::new(0));
+  /// let c_mutex = Arc::clone(&mutex);
+  ///
+  /// thread::spawn(move || {
+  ///   *c_mutex.lock() = 10;
+  /// })
+  /// .join()
+  /// .expect("thread::spawn failed");
+  /// assert_eq!(*mutex.lock(), 10);
+  /// ```
+  #[track_caller]
+  pub fn lock(&self) -> MutexGuard<'_, T> {
+    poison::unwrap(self.lock_checked())
+  }
+
+  /// Acquires the mutex like [`lock`](Mutex::lock), returning an error that
+  /// still holds the guard if the mutex is poisoned.
+  ///
+  /// A mutex that doesn't poison always returns `Ok`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, MutexBuilder};
+  /// use std::thread;
+  ///
+  /// let mutex = Arc::new(MutexBuilder::new().poison(true).build(1));
+  /// let c_mutex = Arc::clone(&mutex);
+  ///
+  /// let _ = thread::spawn(move || {
+  ///   let mut guard = c_mutex.lock();
+  ///   *guard = 2;
+  ///   panic!("half-way through an update");
+  /// })
+  /// .join();
+  ///
+  /// let guard = mutex.lock_checked().unwrap_err().into_inner();
+  /// assert_eq!(*guard, 2);
+  /// ```
+  #[track_caller]
+  pub fn lock_checked(&self) -> LockResult<MutexGuard<'_, T>> {
+    self.raw.lock();
+    self.poison.check(MutexGuard::new(self))
+  }
+
+  /// Attempts to acquire this lock without blocking.
+  ///
+  /// Returns [`None`] if the lock is currently held somewhere else.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the mutex is [poisoned](Mutex::is_poisoned). Use
+  /// [`try_lock_checked`](Mutex::try_lock_checked) to handle that instead.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Mutex;
+  ///
+  /// let mutex = Mutex::new(1);
+  ///
+  /// let guard = mutex.try_lock().unwrap();
+  /// assert!(mutex.try_lock().is_none());
+  ///
+  /// drop(guard);
+  /// assert!(mutex.try_lock().is_some());
+  /// ```
+  #[track_caller]
+  pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+    poison::unwrap_try(self.try_lock_checked())
+  }
+
+  /// Attempts to acquire this lock without blocking, like
+  /// [`try_lock`](Mutex::try_lock), returning an error that still holds the
+  /// guard if the mutex is poisoned.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, MutexBuilder};
+  /// use std::thread;
+  ///
+  /// let mutex = Arc::new(MutexBuilder::new().poison(true).build(1));
+  /// let c_mutex = Arc::clone(&mutex);
+  /// let _ = thread::spawn(move || {
+  ///   let _guard = c_mutex.lock();
+  ///   panic!();
+  /// })
+  /// .join();
+  ///
+  /// let guard = mutex.try_lock_checked().unwrap_err().into_inner();
+  /// assert!(mutex.try_lock_checked().unwrap().is_none());
+  /// drop(guard);
+  /// ```
+  pub fn try_lock_checked(&self) -> TryLockResult<MutexGuard<'_, T>> {
+    if self.raw.try_lock() {
+      self.poison.check(MutexGuard::new(self)).map(Some)
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// Attempts to acquire this lock, blocking for at most `timeout`.
+  ///
+  /// Returns [`None`] if the lock is still held elsewhere when the time runs
+  /// out.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the mutex is [poisoned](Mutex::is_poisoned). Use
+  /// [`try_lock_for_checked`](Mutex::try_lock_for_checked) to handle that
+  /// instead.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, Mutex};
+  /// use std::thread;
+  /// use std::time::Duration;
+  ///
+  /// let mutex = Arc::new(Mutex::new(0));
+  /// let _guard = mutex.lock();
+  ///
+  /// let other = Arc::clone(&mutex);
+  /// let locked = thread::spawn(move || {
+  ///   other.try_lock_for(Duration::from_millis(10)).is_some()
+  /// });
+  /// assert!(!locked.join().unwrap());
+  /// ```
+  #[track_caller]
+  pub fn try_lock_for(
+    &self,
+    timeout: std::time::Duration,
+  ) -> Option<MutexGuard<'_, T>> {
+    poison::unwrap_try(self.try_lock_for_checked(timeout))
+  }
+
+  /// Attempts to acquire this lock like [`try_lock_for`](Mutex::try_lock_for),
+  /// returning an error that still holds the guard if the mutex is poisoned.
+  pub fn try_lock_for_checked(
+    &self,
+    timeout: std::time::Duration,
+  ) -> TryLockResult<MutexGuard<'_, T>> {
+    match std::time::Instant::now().checked_add(timeout) {
+      Some(deadline) => self.try_lock_until_checked(deadline),
+      // A deadline that far out is never reached.
+      None => self.lock_checked().map(Some),
+    }
+  }
+
+  /// Attempts to acquire this lock, blocking until `deadline` at the latest.
+  ///
+  /// Returns [`None`] if the lock is still held elsewhere at `deadline`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the mutex is [poisoned](Mutex::is_poisoned). Use
+  /// [`try_lock_until_checked`](Mutex::try_lock_until_checked) to handle that
+  /// instead.
+  #[track_caller]
+  pub fn try_lock_until(
+    &self,
+    deadline: std::time::Instant,
+  ) -> Option<MutexGuard<'_, T>> {
+    poison::unwrap_try(self.try_lock_until_checked(deadline))
+  }
+
+  /// Attempts to acquire this lock like
+  /// [`try_lock_until`](Mutex::try_lock_until), returning an error that still
+  /// holds the guard if the mutex is poisoned.
+  pub fn try_lock_until_checked(
+    &self,
+    deadline: std::time::Instant,
+  ) -> TryLockResult<MutexGuard<'_, T>> {
+    if self.raw.try_lock() || self.raw.lock_slow(Some(deadline)) {
+      self.poison.check(MutexGuard::new(self)).map(Some)
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// Returns `true` if the mutex is currently locked.
+  ///
+  /// Another thread can lock or unlock the mutex at any time, so this is only
+  /// a hint.
+  pub fn is_locked(&self) -> bool {
+    self.raw.is_locked()
+  }
+
+  /// Returns `true` if a thread panicked while holding this mutex.
+  ///
+  /// Always `false` for a mutex that doesn't poison.
+  pub fn is_poisoned(&self) -> bool {
+    self.poison.get()
+  }
+
+  /// Clears the poisoned state, once the caller has made sure the data is
+  /// consistent again.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, MutexBuilder};
+  /// use std::thread;
+  ///
+  /// let mutex = Arc::new(MutexBuilder::new().poison(true).build(0));
+  /// let c_mutex = Arc::clone(&mutex);
+  ///
+  /// let _ = thread::spawn(move || {
+  ///   let _guard = c_mutex.lock();
+  ///   panic!();
+  /// })
+  /// .join();
+  /// assert!(mutex.is_poisoned());
+  ///
+  /// *mutex.lock_checked().unwrap_or_else(|e| e.into_inner()) = 0;
+  /// mutex.clear_poison();
+  /// assert!(!mutex.is_poisoned());
+  /// ```
+  pub fn clear_poison(&self) {
+    self.poison.clear();
+  }
+
+  /// Returns `true` if a panic while holding this mutex poisons it.
+  pub fn is_poisoning(&self) -> bool {
+    self.poison.is_enabled()
+  }
+
+  /// Returns a mutable reference to the underlying data.
+  ///
+  /// Since this call borrows the `Mutex` mutably, no actual locking needs to
+  /// take place: the mutable borrow statically guarantees no locks exist.
+  /// Poisoning is ignored.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::Mutex;
+  ///
+  /// let mut mutex = Mutex::new(0);
+  /// *mutex.get_mut() = 10;
+  /// assert_eq!(*mutex.lock(), 10);
+  /// ```
+  pub fn get_mut(&mut self) -> &mut T {
+    // SAFETY: `&mut self` guarantees there is no guard alive.
+    self.data.with_mut(|data| unsafe { &mut *data })
+  }
+}
+
+impl<T: Default> Default for Mutex<T> {
+  fn default() -> Mutex<T> {
+    Mutex::new(Default::default())
+  }
+}
+
+impl<T> From<T> for Mutex<T> {
+  fn from(t: T) -> Mutex<T> {
+    Mutex::new(t)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for Mutex<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut d = f.debug_struct("Mutex");
+    if self.raw.try_lock() {
+      let guard = MutexGuard::new(self);
+      d.field("data", &&*guard);
+    } else {
+      d.field("data", &format_args!("<locked>"));
+    }
+    if self.poison.is_enabled() {
+      d.field("poisoned", &self.poison.get());
+    }
+    d.finish()
+  }
+}
+
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+  fn new(mutex: &'a Mutex<T>) -> MutexGuard<'a, T> {
+    MutexGuard {
+      mutex,
+      poison: mutex.poison.guard(),
+      marker: std::marker::PhantomData,
+    }
+  }
+
+  /// Makes a [`MappedMutexGuard`] for a component of the locked data.
+  ///
+  /// The mutex stays locked. This is an associated function that needs to
+  /// be used as `MutexGuard::map(...)`, so it doesn't shadow a method of the
+  /// same name on the contents.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Mutex, MutexGuard};
+  ///
+  /// let mutex = Mutex::new((String::new(), 0));
+  ///
+  /// let mut name = MutexGuard::map(mutex.lock(), |pair| &mut pair.0);
+  /// name.push_str("hello");
+  /// drop(name);
+  ///
+  /// assert_eq!(mutex.lock().0, "hello");
+  /// ```
+  pub fn map<U: ?Sized>(
+    mut orig: Self,
+    f: impl FnOnce(&mut T) -> &mut U,
+  ) -> MappedMutexGuard<'a, U> {
+    // If `f` panics, `orig` still unlocks the mutex on the way out.
+    let data = f(&mut *orig) as *mut U;
+    let mutex = orig.mutex;
+    let poison = orig.poison;
+    std::mem::forget(orig);
+    MappedMutexGuard {
+      raw: &mutex.raw,
+      poison_flag: &mutex.poison,
+      poison,
+      data,
+      marker: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for MutexGuard<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    // SAFETY: The guard holds the lock.
+    self.mutex.data.with(|data| unsafe { &*data })
+  }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for MutexGuard<'_, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    // SAFETY: The guard holds the lock, and `&mut self` makes this the only
+    // reference handed out by it.
+    self.mutex.data.with_mut(|data| unsafe { &mut *data })
+  }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+  #[inline]
+  fn drop(&mut self) {
+    self.mutex.poison.done(&self.poison);
+    // SAFETY: The guard holds the lock.
+    unsafe { self.mutex.raw.unlock() }
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for MutexGuard<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display for MutexGuard<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<'a, T: ?Sized> MappedMutexGuard<'a, T> {
+  /// Makes a new [`MappedMutexGuard`] for a component of the data this
+  /// guard already points to.
+  ///
+  /// Like [`MutexGuard::map`], this is an associated function.
+  pub fn map<U: ?Sized>(
+    mut orig: Self,
+    f: impl FnOnce(&mut T) -> &mut U,
+  ) -> MappedMutexGuard<'a, U> {
+    let data = f(&mut *orig) as *mut U;
+    let (raw, poison_flag, poison) = (orig.raw, orig.poison_flag, orig.poison);
+    std::mem::forget(orig);
+    MappedMutexGuard {
+      raw,
+      poison_flag,
+      poison,
+      data,
+      marker: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for MappedMutexGuard<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    // SAFETY: `data` points into the locked mutex, which stays locked for
+    // as long as the guard lives.
+    unsafe { &*self.data }
+  }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for MappedMutexGuard<'_, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    // SAFETY: As above, and `&mut self` makes this the only reference handed
+    // out by the guard.
+    unsafe { &mut *self.data }
+  }
+}
+
+impl<T: ?Sized> Drop for MappedMutexGuard<'_, T> {
+  #[inline]
+  fn drop(&mut self) {
+    self.poison_flag.done(&self.poison);
+    // SAFETY: The guard holds the lock.
+    unsafe { self.raw.unlock() }
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for MappedMutexGuard<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display
+  for MappedMutexGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+  use crate::sync::Arc;
+
+  use std::thread;
+  use std::time::{Duration, Instant};
+
+  #[test]
+  fn lock_unlock() {
+    let mutex = Mutex::new(vec![1]);
+
+    mutex.lock().push(2);
+    assert!(!mutex.is_locked());
+
+    let guard = mutex.lock();
+    assert!(mutex.is_locked());
+    assert_eq!(*guard, [1, 2]);
+  }
+
+  #[test]
+  fn try_lock_while_held() {
+    let mutex = Arc::new(Mutex::new(()));
+    let _guard = mutex.lock();
+
+    let other = Arc::clone(&mutex);
+    let locked = thread::spawn(move || other.try_lock().is_some());
+    assert!(!locked.join().unwrap());
+  }
+
+  #[test]
+  fn contended_increments() {
+    const THREADS: usize = 8;
+    const ITERS: usize = 1000;
+
+    let mutex = Arc::new(Mutex::new(0));
+    let handles: Vec<_> = (0..THREADS)
+      .map(|_| {
+        let mutex = Arc::clone(&mutex);
+        thread::spawn(move || {
+          for _ in 0..ITERS {
+            *mutex.lock() += 1;
+          }
+        })
+      })
+      .collect();
+
+    for handle in handles {
+      handle.join().unwrap();
+    }
+    assert_eq!(*mutex.lock(), THREADS * ITERS);
+  }
+
+  #[test]
+  fn parked_waiter_is_woken() {
+    let mutex = Arc::new(Mutex::new(0));
+    let guard = mutex.lock();
+
+    let other = Arc::clone(&mutex);
+    let handle = thread::spawn(move || *other.lock() += 1);
+
+    // Give the waiter time to stop spinning and park.
+    while mutex.raw.queue.is_empty() {
+      thread::yield_now();
+    }
+    drop(guard);
+
+    handle.join().unwrap();
+    assert_eq!(*mutex.lock(), 1);
+  }
+
+  #[test]
+  fn unsized_data() {
+    let mutex: &Mutex<[i32]> = &Mutex::new([1, 2, 3]);
+    mutex.lock()[0] = 4;
+    assert_eq!(*mutex.lock(), [4, 2, 3]);
+  }
+
+  #[test]
+  fn debug_shows_locked() {
+    let mutex = Mutex::new(5);
+    assert_eq!(format!("{:?}", mutex), "Mutex { data: 5 }");
+
+    let _guard = mutex.lock();
+    assert_eq!(format!("{:?}", mutex), "Mutex { data: <locked> }");
+  }
+
+  fn panic_while_locked(mutex: &Arc<Mutex<i32>>) {
+    let other = Arc::clone(mutex);
+    let result = thread::spawn(move || {
+      *other.lock() += 1;
+      let _guard = other.lock();
+      panic!("poison the lock");
+    })
+    .join();
+    assert!(result.is_err());
+  }
+
+  #[test]
+  fn poisoning_is_opt_in() {
+    let mutex = Arc::new(Mutex::new(0));
+    panic_while_locked(&mutex);
+    assert!(!mutex.is_poisoned());
+    assert_eq!(*mutex.lock(), 1);
+
+    let mutex = Arc::new(MutexBuilder::new().poison(true).build(0));
+    panic_while_locked(&mutex);
+    assert!(mutex.is_poisoned());
+    assert!(!mutex.is_locked());
+    assert_eq!(format!("{:?}", mutex), "Mutex { data: 1, poisoned: true }");
+
+    let recovered = thread::spawn({
+      let mutex = Arc::clone(&mutex);
+      move || *mutex.lock()
+    })
+    .join();
+    assert!(recovered.is_err(), "`lock` panics on a poisoned mutex");
+
+    assert_eq!(*mutex.lock_checked().unwrap_err().into_inner(), 1);
+    mutex.clear_poison();
+    assert_eq!(*mutex.lock(), 1);
+  }
+
+  #[test]
+  fn checked_try_locks_report_poison() {
+    let mutex = Arc::new(MutexBuilder::new().poison(true).build(0));
+    assert_eq!(*mutex.try_lock_checked().unwrap().unwrap(), 0);
+    panic_while_locked(&mutex);
+
+    let timeout = Duration::from_millis(1);
+    let deadline = Instant::now() + timeout;
+    let guard = mutex.try_lock_checked().unwrap_err().into_inner();
+    assert_eq!(*guard, 1);
+    assert!(mutex.try_lock_checked().unwrap().is_none());
+    assert!(mutex.try_lock_for_checked(timeout).unwrap().is_none());
+    assert!(mutex.try_lock_until_checked(deadline).unwrap().is_none());
+    drop(guard);
+
+    let guard = mutex
+      .try_lock_for_checked(timeout)
+      .unwrap_err()
+      .into_inner();
+    drop(guard);
+    let guard = mutex.try_lock_until_checked(deadline).unwrap_err();
+    drop(guard);
+
+    let panicked = thread::spawn({
+      let mutex = Arc::clone(&mutex);
+      move || mutex.try_lock().is_some()
+    })
+    .join();
+    assert!(panicked.is_err(), "`try_lock` panics on a poisoned mutex");
+  }
+
+  #[test]
+  fn timed_lock() {
+    let mutex = Arc::new(Mutex::new(0));
+    let guard = mutex.lock();
+
+    let other = Arc::clone(&mutex);
+    let timed_out = thread::spawn(move || {
+      other.try_lock_for(Duration::from_millis(10)).is_none()
+    });
+    assert!(timed_out.join().unwrap());
+
+    let other = Arc::clone(&mutex);
+    let waiter = thread::spawn(move || {
+      *other.try_lock_for(Duration::from_secs(60)).unwrap() += 1;
+    });
+    thread::sleep(Duration::from_millis(10));
+    drop(guard);
+    waiter.join().unwrap();
+
+    let past = std::time::Instant::now();
+    assert_eq!(*mutex.try_lock_until(past).unwrap(), 1);
+  }
+
+  #[cfg(feature = "deadlock-debug")]
+  #[test]
+  #[should_panic(expected = "already holds this Mutex")]
+  fn relocking_on_the_same_thread_panics() {
+    let mutex = Mutex::new(0);
+    let _guard = mutex.lock();
+    let _again = mutex.lock();
+  }
+
+  #[test]
+  fn mapped_guard_keeps_lock() {
+    let mutex = Mutex::new((vec![1], 0));
+
+    let guard = MutexGuard::map(mutex.lock(), |pair| &mut pair.0);
+    let mut first = MappedMutexGuard::map(guard, |v| &mut v[0]);
+    *first += 1;
+    assert!(mutex.is_locked());
+    assert_eq!(format!("{:?}", first), "2");
+
+    drop(first);
+    assert!(!mutex.is_locked());
+    assert_eq!(mutex.lock().0, [2]);
+  }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+  use super::*;
+  use crate::sync::Arc;
+
+  use ::loom::thread;
+
+  #[test]
+  fn loom_mutex_increments() {
+    ::loom::model(|| {
+      let mutex = Arc::new(Mutex::new(0));
+
+      let other = Arc::clone(&mutex);
+      let t = thread::spawn(move || *other.lock() += 1);
+      *mutex.lock() += 1;
+      t.join().unwrap();
+
+      assert_eq!(*mutex.lock(), 2);
+    });
+  }
+}
@@ -0,0 +1,167 @@
+//! Lock poisoning, for the locks that opt into it.
+//!
+//! A lock that poisons remembers that a thread panicked while holding one of its guards, since
+//! the protected data may have been left halfway through an update. Later attempts to lock it
+//! report the poisoning: the `*_checked` methods return a [`PoisonError`] that still carries the
+//! guard, so the caller can inspect the data and recover, while the plain ones panic.
+//!
+//! Poisoning is off by default, like in `parking_lot`. Turn it on with
+//! [`MutexBuilder::poison`] or [`RwLockBuilder::poison`].
+//!
+//! ```
+//! use pointer::sync::{Arc, MutexBuilder};
+//! use std::thread;
+//!
+//! let mutex = Arc::new(MutexBuilder::new().poison(true).build(0));
+//!
+//! let c_mutex = Arc::clone(&mutex);
+//! let _ = thread::spawn(move || {
+//!   let _guard = c_mutex.lock();
+//!   panic!();
+//! })
+//! .join();
+//!
+//! assert!(mutex.is_poisoned());
+//! let guard = mutex.lock_checked().unwrap_or_else(|e| e.into_inner());
+//! assert_eq!(*guard, 0);
+//! ```
+//!
+//! [`MutexBuilder::poison`]: crate::sync::MutexBuilder::poison
+//! [`RwLockBuilder::poison`]: crate::sync::RwLockBuilder::poison
+
+use crate::loom::atomic::AtomicBool;
+use crate::loom::atomic::Ordering::Relaxed;
+
+/// A type alias for the result of a lock method which can be poisoned.
+pub type LockResult<G> = Result<G, PoisonError<G>>;
+
+/// A type alias for the result of a non-blocking lock method which can be
+/// poisoned.
+///
+/// `Ok(None)` means the lock is held somewhere else; an error means it was
+/// acquired but is poisoned.
+pub type TryLockResult<G> = Result<Option<G>, PoisonError<G>>;
+
+/// An error returned when acquiring a poisoned lock.
+///
+/// The lock was still acquired; the guard can be taken back out with
+/// [`into_inner`](PoisonError::into_inner).
+pub struct PoisonError<G> {
+  guard: G,
+}
+
+impl<G> PoisonError<G> {
+  /// Creates a `PoisonError` holding `guard`.
+  pub fn new(guard: G) -> PoisonError<G> {
+    PoisonError { guard }
+  }
+
+  /// Consumes this error, returning the guard.
+  pub fn into_inner(self) -> G {
+    self.guard
+  }
+
+  /// Returns a reference to the guard.
+  pub fn get_ref(&self) -> &G {
+    &self.guard
+  }
+
+  /// Returns a mutable reference to the guard.
+  pub fn get_mut(&mut self) -> &mut G {
+    &mut self.guard
+  }
+}
+
+impl<G> std::fmt::Debug for PoisonError<G> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("PoisonError").finish_non_exhaustive()
+  }
+}
+
+impl<G> std::fmt::Display for PoisonError<G> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("poisoned lock: another thread panicked while holding it")
+  }
+}
+
+impl<G> std::error::Error for PoisonError<G> {}
+
+/// The poison state of a lock.
+pub(crate) struct Flag {
+  enabled: bool,
+  poisoned: AtomicBool,
+}
+
+/// What a guard remembers about its thread when it was created.
+#[derive(Clone, Copy)]
+pub(crate) struct Guard {
+  /// Whether a panic that is running when the guard drops is a new one.
+  poison_on_panic: bool,
+}
+
+impl Flag {
+  pub(crate) fn new(enabled: bool) -> Flag {
+    Flag {
+      enabled,
+      poisoned: AtomicBool::new(false),
+    }
+  }
+
+  /// Returns `true` if this lock poisons at all.
+  pub(crate) fn is_enabled(&self) -> bool {
+    self.enabled
+  }
+
+  /// Starts tracking a new guard. Call this once the lock is held.
+  #[inline]
+  pub(crate) fn guard(&self) -> Guard {
+    // A guard taken while already unwinding doesn't poison the lock again.
+    Guard {
+      poison_on_panic: self.enabled && !std::thread::panicking(),
+    }
+  }
+
+  /// Poisons the lock if the thread started panicking while `guard` was
+  /// alive. Call this before releasing the lock.
+  #[inline]
+  pub(crate) fn done(&self, guard: &Guard) {
+    if guard.poison_on_panic && std::thread::panicking() {
+      self.poisoned.store(true, Relaxed);
+    }
+  }
+
+  pub(crate) fn get(&self) -> bool {
+    self.poisoned.load(Relaxed)
+  }
+
+  pub(crate) fn clear(&self) {
+    self.poisoned.store(false, Relaxed);
+  }
+
+  /// Wraps a freshly acquired lock guard in a [`LockResult`].
+  pub(crate) fn check<G>(&self, guard: G) -> LockResult<G> {
+    if self.get() {
+      Err(PoisonError::new(guard))
+    } else {
+      Ok(guard)
+    }
+  }
+}
+
+/// Returns the guard, panicking if the lock was poisoned.
+#[track_caller]
+pub(crate) fn unwrap<G>(result: LockResult<G>) -> G {
+  match result {
+    Ok(guard) => guard,
+    Err(_) => panic!("lock poisoned: another thread panicked while holding it"),
+  }
+}
+
+/// Returns the guard, if any, panicking if the lock was poisoned.
+#[track_caller]
+pub(crate) fn unwrap_try<G>(result: TryLockResult<G>) -> Option<G> {
+  match result {
+    Ok(guard) => guard,
+    Err(e) => Some(unwrap(Err(e))),
+  }
+}
@@ -0,0 +1,374 @@
+//! A mutex that the owning thread may lock again while it already holds it.
+//!
+//! Locking a [`Mutex`] twice on the same thread deadlocks. That is easy to do by accident in
+//! callback-heavy code, where a callback invoked under the lock calls back into the API that took
+//! it. A [`ReentrantMutex<T>`][ReentrantMutex] instead counts how many times the owning thread has
+//! locked it and only releases the lock once every [`ReentrantMutexGuard`] is gone.
+//!
+//! Because several guards may be alive on the same thread at once, a guard only gives out `&T`.
+//! Pair it with a [`Cell`] or [`RefCell`] to mutate the protected value.
+//!
+//! ```
+//! use pointer::sync::ReentrantMutex;
+//! use pointer::RefCell;
+//!
+//! let log = ReentrantMutex::new(RefCell::new(Vec::new()));
+//!
+//! let outer = log.lock();
+//! outer.borrow_mut().push("outer");
+//! {
+//!   // Locking again on the same thread doesn't block.
+//!   let inner = log.lock();
+//!   inner.borrow_mut().push("inner");
+//! }
+//! drop(outer);
+//!
+//! assert_eq!(*log.lock().borrow(), ["outer", "inner"]);
+//! ```
+//!
+//! [`Mutex`]: crate::sync::Mutex
+//! [`Cell`]: crate::Cell
+//! [`RefCell`]: crate::RefCell
+
+use super::mutex::RawMutex;
+use crate::loom::atomic::AtomicUsize;
+use crate::loom::atomic::Ordering::Relaxed;
+use crate::loom::cell::UnsafeCell;
+
+/// Returns a non-zero id for the current thread, unique among the threads
+/// that are alive.
+// loom's `thread_local!` doesn't accept `const` initializers.
+#[allow(clippy::missing_const_for_thread_local)]
+fn current_thread_id() -> usize {
+  crate::loom::thread_local! {
+    static ID: u8 = 0;
+  }
+  // The address of a thread local is unique for as long as its thread lives,
+  // and a thread can't exit while its guards are alive.
+  ID.with(|id| std::ptr::from_ref(id).addr())
+}
+
+/// A mutex which can be recursively locked by a single thread.
+///
+/// See the [module-level documentation](index.html) for more.
+pub struct ReentrantMutex<T: ?Sized> {
+  raw: RawMutex,
+  /// The id of the thread holding `raw`, or 0.
+  owner: AtomicUsize,
+  /// How many guards the owner holds. Only the owner touches it.
+  lock_count: UnsafeCell<usize>,
+  data: T,
+}
+
+// `&T` is only reachable from the thread that owns the lock, so `T` doesn't
+// need to be `Sync`.
+unsafe impl<T: ?Sized + Send> Send for ReentrantMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for ReentrantMutex<T> {}
+
+/// An RAII guard for a locked [`ReentrantMutex`], returned by
+/// [`ReentrantMutex::lock`] and [`ReentrantMutex::try_lock`].
+///
+/// The lock is released once the last guard of the owning thread is dropped.
+#[must_use = "if unused the ReentrantMutex will immediately unlock"]
+pub struct ReentrantMutexGuard<'a, T: ?Sized> {
+  mutex: &'a ReentrantMutex<T>,
+  // Guards must be dropped on the thread that locked the mutex.
+  marker: std::marker::PhantomData<*const ()>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for ReentrantMutexGuard<'_, T> {}
+
+impl<T> ReentrantMutex<T> {
+  /// Creates a new reentrant mutex in an unlocked state.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::ReentrantMutex;
+  ///
+  /// let mutex = ReentrantMutex::new(0);
+  /// ```
+  pub fn new(t: T) -> ReentrantMutex<T> {
+    ReentrantMutex {
+      raw: RawMutex::new(),
+      owner: AtomicUsize::new(0),
+      lock_count: UnsafeCell::new(0),
+      data: t,
+    }
+  }
+
+  /// Consumes this mutex, returning the underlying data.
+  pub fn into_inner(self) -> T {
+    self.data
+  }
+}
+
+impl<T: ?Sized> ReentrantMutex<T> {
+  /// Acquires the mutex, blocking the current thread until it is able to.
+  ///
+  /// If the current thread already holds the lock this returns right away
+  /// with another guard.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the lock count would overflow.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::ReentrantMutex;
+  ///
+  /// let mutex = ReentrantMutex::new(5);
+  ///
+  /// let first = mutex.lock();
+  /// let second = mutex.lock();
+  /// assert_eq!(*first + *second, 10);
+  /// ```
+  pub fn lock(&self) -> ReentrantMutexGuard<'_, T> {
+    let this_thread = current_thread_id();
+    // SAFETY: Only the owning thread touches the lock count.
+    unsafe {
+      if self.owner.load(Relaxed) == this_thread {
+        self.increment_lock_count();
+      } else {
+        self.raw.lock();
+        self.owner.store(this_thread, Relaxed);
+        self.lock_count.with_mut(|count| *count = 1);
+      }
+    }
+    ReentrantMutexGuard::new(self)
+  }
+
+  /// Attempts to acquire this lock without blocking.
+  ///
+  /// Succeeds if the lock is free or already held by the current thread.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, ReentrantMutex};
+  /// use std::thread;
+  ///
+  /// let mutex = Arc::new(ReentrantMutex::new(()));
+  /// let _guard = mutex.lock();
+  /// assert!(mutex.try_lock().is_some());
+  ///
+  /// let other = Arc::clone(&mutex);
+  /// let locked = thread::spawn(move || other.try_lock().is_some());
+  /// assert!(!locked.join().unwrap());
+  /// ```
+  pub fn try_lock(&self) -> Option<ReentrantMutexGuard<'_, T>> {
+    let this_thread = current_thread_id();
+    // SAFETY: Only the owning thread touches the lock count.
+    unsafe {
+      if self.owner.load(Relaxed) == this_thread {
+        self.increment_lock_count();
+      } else if self.raw.try_lock() {
+        self.owner.store(this_thread, Relaxed);
+        self.lock_count.with_mut(|count| *count = 1);
+      } else {
+        return None;
+      }
+    }
+    Some(ReentrantMutexGuard::new(self))
+  }
+
+  /// Returns `true` if the current thread holds the lock.
+  pub fn is_owned_by_current_thread(&self) -> bool {
+    self.owner.load(Relaxed) == current_thread_id()
+  }
+
+  /// Returns a mutable reference to the underlying data.
+  ///
+  /// Since this call borrows the `ReentrantMutex` mutably, no actual locking
+  /// needs to take place.
+  pub fn get_mut(&mut self) -> &mut T {
+    &mut self.data
+  }
+
+  /// # Safety
+  ///
+  /// The current thread must own the lock.
+  unsafe fn increment_lock_count(&self) {
+    self.lock_count.with_mut(|count| {
+      *count = (*count)
+        .checked_add(1)
+        .expect("lock count overflow in reentrant mutex");
+    });
+  }
+}
+
+impl<T: Default> Default for ReentrantMutex<T> {
+  fn default() -> ReentrantMutex<T> {
+    ReentrantMutex::new(Default::default())
+  }
+}
+
+impl<T> From<T> for ReentrantMutex<T> {
+  fn from(t: T) -> ReentrantMutex<T> {
+    ReentrantMutex::new(t)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for ReentrantMutex<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut d = f.debug_struct("ReentrantMutex");
+    match self.try_lock() {
+      Some(guard) => d.field("data", &&*guard),
+      None => d.field("data", &format_args!("<locked>")),
+    };
+    d.finish()
+  }
+}
+
+impl<'a, T: ?Sized> ReentrantMutexGuard<'a, T> {
+  fn new(mutex: &'a ReentrantMutex<T>) -> ReentrantMutexGuard<'a, T> {
+    ReentrantMutexGuard {
+      mutex,
+      marker: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for ReentrantMutexGuard<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    &self.mutex.data
+  }
+}
+
+impl<T: ?Sized> Drop for ReentrantMutexGuard<'_, T> {
+  #[inline]
+  fn drop(&mut self) {
+    let mutex = self.mutex;
+    // SAFETY: This thread owns the lock while any of its guards are alive.
+    unsafe {
+      let remaining = mutex.lock_count.with_mut(|count| {
+        *count -= 1;
+        *count
+      });
+      if remaining == 0 {
+        mutex.owner.store(0, Relaxed);
+        mutex.raw.unlock();
+      }
+    }
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug
+  for ReentrantMutexGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display
+  for ReentrantMutexGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+  use crate::sync::Arc;
+  use crate::Cell;
+
+  use std::thread;
+
+  #[test]
+  fn recursive_lock() {
+    let mutex = ReentrantMutex::new(Cell::new(0));
+
+    fn recurse(mutex: &ReentrantMutex<Cell<i32>>, depth: i32) {
+      let guard = mutex.lock();
+      guard.set(guard.get() + 1);
+      if depth > 0 {
+        recurse(mutex, depth - 1);
+      }
+    }
+
+    recurse(&mutex, 9);
+    assert_eq!(mutex.lock().get(), 10);
+    assert!(!mutex.is_owned_by_current_thread());
+  }
+
+  #[test]
+  fn released_after_last_guard() {
+    let mutex = Arc::new(ReentrantMutex::new(()));
+
+    let first = mutex.lock();
+    let second = mutex.lock();
+
+    let try_other = |mutex: &Arc<ReentrantMutex<()>>| {
+      let other = Arc::clone(mutex);
+      thread::spawn(move || other.try_lock().is_some())
+        .join()
+        .unwrap()
+    };
+
+    drop(first);
+    assert!(!try_other(&mutex));
+    drop(second);
+    assert!(try_other(&mutex));
+  }
+
+  #[test]
+  fn contended_between_threads() {
+    let mutex = Arc::new(ReentrantMutex::new(Cell::new(0)));
+
+    let handles: Vec<_> = (0..4)
+      .map(|_| {
+        let mutex = Arc::clone(&mutex);
+        thread::spawn(move || {
+          for _ in 0..500 {
+            let outer = mutex.lock();
+            let inner = mutex.lock();
+            inner.set(outer.get() + 1);
+          }
+        })
+      })
+      .collect();
+
+    for handle in handles {
+      handle.join().unwrap();
+    }
+    assert_eq!(mutex.lock().get(), 2000);
+  }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+  use super::*;
+  use crate::sync::Arc;
+  use crate::Cell;
+
+  use ::loom::thread;
+
+  #[test]
+  fn loom_reentrant_lock() {
+    ::loom::model(|| {
+      let mutex = Arc::new(ReentrantMutex::new(Cell::new(0)));
+
+      let other = Arc::clone(&mutex);
+      let t = thread::spawn(move || {
+        let outer = other.lock();
+        let inner = other.lock();
+        inner.set(outer.get() + 1);
+      });
+
+      {
+        let outer = mutex.lock();
+        let inner = mutex.lock();
+        inner.set(outer.get() + 1);
+      }
+      t.join().unwrap();
+
+      assert_eq!(mutex.lock().get(), 2);
+    });
+  }
+}
@@ -0,0 +1,1447 @@
+//! A reader-writer lock.
+//!
+//! [`RwLock<T>`][RwLock] is the multi-threaded counterpart of [`RefCell<T>`][refcell]: any number
+//! of threads may hold a [`read`] guard at the same time, or a single thread may hold a [`write`]
+//! guard. Where a `RefCell` panics on a conflicting borrow, an `RwLock` blocks until the
+//! conflicting guards are dropped.
+//!
+//! By default the lock prefers writers: once a writer is waiting, new readers queue up behind it
+//! instead of keeping the lock busy forever. A lock built with
+//! [`RwLockBuilder::new().writer_priority(false)`][writer_priority] lets readers in whenever no writer
+//! holds the lock, which gives more read throughput at the risk of starving writers.
+//!
+//! Like [`Mutex`], the lock isn't poisoned by default. A lock built with
+//! [`RwLockBuilder::new().poison(true)`][poison] is poisoned when a writer panics, as in the
+//! standard library; readers never poison it.
+//!
+//! ```
+//! use pointer::sync::RwLock;
+//!
+//! let lock = RwLock::new(5);
+//!
+//! // many reader locks can be held at once
+//! {
+//!   let r1 = lock.read();
+//!   let r2 = lock.read();
+//!   assert_eq!(*r1, 5);
+//!   assert_eq!(*r2, 5);
+//! } // read locks are dropped at this point
+//!
+//! // only one write lock may be held, however
+//! {
+//!   let mut w = lock.write();
+//!   *w += 1;
+//!   assert_eq!(*w, 6);
+//! } // write lock is dropped here
+//! ```
+//!
+//! [refcell]: crate::RefCell
+//! [`read`]: RwLock::read
+//! [`write`]: RwLock::write
+//! [writer_priority]: RwLockBuilder::writer_priority
+//! [`Mutex`]: crate::sync::Mutex
+//! [poison]: RwLockBuilder::poison
+
+use super::deadlock_debug::Holders;
+use super::poison::{self, LockResult, TryLockResult};
+use super::wait_queue::{ParkResult, WaitQueue, SPIN_LIMIT};
+use crate::loom::atomic::AtomicUsize;
+use crate::loom::atomic::Ordering::{Acquire, Relaxed, Release};
+use crate::loom::cell::UnsafeCell;
+use crate::loom::hint;
+
+/// A writer holds the lock.
+const WRITER: usize = 1;
+/// At least one writer may be parked on the writer queue.
+const WRITERS_PARKED: usize = 2;
+/// At least one reader may be parked on the reader queue.
+const READERS_PARKED: usize = 4;
+/// One active reader; the reader count lives in the remaining bits.
+const READER: usize = 8;
+const READERS_MASK: usize = !(READER - 1);
+
+/// The locking protocol behind [`RwLock`], without the data.
+pub(crate) struct RawRwLock {
+  state: AtomicUsize,
+  writer_priority: bool,
+  readers: WaitQueue,
+  writers: WaitQueue,
+  holders: Holders,
+}
+
+impl RawRwLock {
+  fn new(writer_priority: bool) -> RawRwLock {
+    RawRwLock {
+      state: AtomicUsize::new(0),
+      writer_priority,
+      readers: WaitQueue::new(),
+      writers: WaitQueue::new(),
+      holders: Holders::new(),
+    }
+  }
+
+  /// Returns `true` if a new reader has to wait in `state`.
+  #[inline]
+  fn read_blocked(&self, state: usize) -> bool {
+    state & WRITER != 0 || (self.writer_priority && state & WRITERS_PARKED != 0)
+  }
+
+  #[inline]
+  pub(crate) fn try_read(&self) -> bool {
+    let mut state = self.state.load(Relaxed);
+    loop {
+      if self.read_blocked(state) {
+        return false;
+      }
+      let next = state.checked_add(READER).expect("too many read locks");
+      match self
+        .state
+        .compare_exchange_weak(state, next, Acquire, Relaxed)
+      {
+        Ok(_) => {
+          self.holders.acquired();
+          return true;
+        }
+        Err(s) => state = s,
+      }
+    }
+  }
+
+  #[inline]
+  #[track_caller]
+  pub(crate) fn read(&self) {
+    if !self.try_read() {
+      // Holding a read lock only blocks this one behind a waiting writer,
+      // which in turn waits for the read lock to go.
+      self.holders.check_blocking("RwLock");
+      self.read_slow(None);
+    }
+  }
+
+  /// Spins, then parks until a read lock is acquired or `deadline` passes.
+  /// Returns whether the lock was acquired.
+  #[cold]
+  pub(crate) fn read_slow(&self, deadline: Option<std::time::Instant>) -> bool {
+    let mut spins = 0;
+    loop {
+      if self.try_read() {
+        return true;
+      }
+
+      if spins < SPIN_LIMIT {
+        spins += 1;
+        hint::spin_loop();
+        continue;
+      }
+
+      let validate = || loop {
+        let state = self.state.load(Relaxed);
+        if !self.read_blocked(state) {
+          return false;
+        }
+        if state & READERS_PARKED != 0
+          || self
+            .state
+            .compare_exchange_weak(
+              state,
+              state | READERS_PARKED,
+              Relaxed,
+              Relaxed,
+            )
+            .is_ok()
+        {
+          return true;
+        }
+      };
+      let timed_out = |have_more: bool| {
+        if !have_more {
+          self.state.fetch_and(!READERS_PARKED, Relaxed);
+        }
+      };
+      let result = self.readers.park(validate, || {}, timed_out, deadline);
+      if result == ParkResult::TimedOut {
+        return false;
+      }
+      spins = 0;
+    }
+  }
+
+  #[inline]
+  pub(crate) fn try_write(&self) -> bool {
+    let mut state = self.state.load(Relaxed);
+    loop {
+      if state & (WRITER | READERS_MASK) != 0 {
+        return false;
+      }
+      match self.state.compare_exchange_weak(
+        state,
+        state | WRITER,
+        Acquire,
+        Relaxed,
+      ) {
+        Ok(_) => {
+          self.holders.acquired();
+          return true;
+        }
+        Err(s) => state = s,
+      }
+    }
+  }
+
+  #[inline]
+  #[track_caller]
+  pub(crate) fn write(&self) {
+    if self
+      .state
+      .compare_exchange_weak(0, WRITER, Acquire, Relaxed)
+      .is_ok()
+    {
+      self.holders.acquired();
+    } else {
+      self.holders.check_blocking("RwLock");
+      self.write_slow(None);
+    }
+  }
+
+  /// Spins, then parks until the write lock is acquired or `deadline`
+  /// passes. Returns whether the lock was acquired.
+  #[cold]
+  pub(crate) fn write_slow(
+    &self,
+    deadline: Option<std::time::Instant>,
+  ) -> bool {
+    let mut spins = 0;
+    loop {
+      if self.try_write() {
+        return true;
+      }
+
+      if spins < SPIN_LIMIT {
+        spins += 1;
+        hint::spin_loop();
+        continue;
+      }
+
+      let validate = || loop {
+        let state = self.state.load(Relaxed);
+        if state & (WRITER | READERS_MASK) == 0 {
+          return false;
+        }
+        if state & WRITERS_PARKED != 0
+          || self
+            .state
+            .compare_exchange_weak(
+              state,
+              state | WRITERS_PARKED,
+              Relaxed,
+              Relaxed,
+            )
+            .is_ok()
+        {
+          return true;
+        }
+      };
+      let timed_out = |have_more: bool| {
+        if !have_more {
+          self.state.fetch_and(!WRITERS_PARKED, Relaxed);
+        }
+      };
+      match self.writers.park(validate, || {}, timed_out, deadline) {
+        ParkResult::TimedOut => {
+          // Readers may have queued up behind us; let them in if we were
+          // the last writer keeping them out.
+          self.wake_readers_if_unblocked();
+          return false;
+        }
+        ParkResult::Unparked | ParkResult::Invalid => spins = 0,
+      }
+    }
+  }
+
+  /// Releases a read lock.
+  ///
+  /// # Safety
+  ///
+  /// A read lock must be held by the current context.
+  #[inline]
+  pub(crate) unsafe fn read_unlock(&self) {
+    self.holders.released();
+    let state = self.state.fetch_sub(READER, Release);
+
+    // The last reader hands the lock to a parked writer.
+    if state & READERS_MASK == READER && state & WRITERS_PARKED != 0 {
+      self.wake_writer();
+    }
+  }
+
+  /// Releases the write lock.
+  ///
+  /// # Safety
+  ///
+  /// The write lock must be held by the current context.
+  #[inline]
+  pub(crate) unsafe fn write_unlock(&self) {
+    self.holders.released();
+    let state = self.state.fetch_and(!WRITER, Release);
+    if state & (WRITERS_PARKED | READERS_PARKED) != 0 {
+      self.write_unlock_slow(state);
+    }
+  }
+
+  #[cold]
+  fn write_unlock_slow(&self, state: usize) {
+    if self.writer_priority {
+      if state & WRITERS_PARKED != 0 && self.wake_writer() {
+        return;
+      }
+      self.wake_readers();
+    } else {
+      if state & READERS_PARKED != 0 && self.wake_readers() > 0 {
+        return;
+      }
+      self.wake_writer();
+    }
+  }
+
+  /// Wakes one parked writer, clearing `WRITERS_PARKED` if it was the last.
+  fn wake_writer(&self) -> bool {
+    self.writers.unpark_one(|_, have_more| {
+      if !have_more {
+        self.state.fetch_and(!WRITERS_PARKED, Relaxed);
+      }
+    })
+  }
+
+  /// Wakes every parked reader.
+  fn wake_readers(&self) -> usize {
+    self.readers.unpark_all(|| {
+      self.state.fetch_and(!READERS_PARKED, Relaxed);
+    })
+  }
+
+  /// Wakes the parked readers if nothing keeps them out any more.
+  fn wake_readers_if_unblocked(&self) {
+    let state = self.state.load(Relaxed);
+    if state & READERS_PARKED != 0 && !self.read_blocked(state) {
+      self.wake_readers();
+    }
+  }
+}
+
+/// A reader-writer lock.
+///
+/// See the [module-level documentation](index.html) for more.
+pub struct RwLock<T: ?Sized> {
+  raw: RawRwLock,
+  poison: poison::Flag,
+  data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+/// Configures the policy of an [`RwLock`] before it is created.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::sync::RwLockBuilder;
+///
+/// let lock = RwLockBuilder::new().writer_priority(false).build(0);
+/// assert!(!lock.is_writer_priority());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RwLockBuilder {
+  writer_priority: bool,
+  poison: bool,
+}
+
+/// RAII structure used to release the shared read access of a lock when
+/// dropped, returned by [`RwLock::read`] and [`RwLock::try_read`].
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+  lock: &'a RwLock<T>,
+  marker: std::marker::PhantomData<*const ()>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for RwLockReadGuard<'_, T> {}
+
+/// RAII structure used to release the exclusive write access of a lock when
+/// dropped, returned by [`RwLock::write`] and [`RwLock::try_write`].
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+  lock: &'a RwLock<T>,
+  poison: poison::Guard,
+  marker: std::marker::PhantomData<*const ()>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for RwLockWriteGuard<'_, T> {}
+
+/// RAII structure for shared access to a part of the data of an [`RwLock`],
+/// returned by [`RwLockReadGuard::map`].
+///
+/// It keeps the whole lock read-locked until it is dropped.
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct MappedRwLockReadGuard<'a, T: ?Sized> {
+  raw: &'a RawRwLock,
+  data: *const T,
+  marker: std::marker::PhantomData<&'a T>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for MappedRwLockReadGuard<'_, T> {}
+
+/// RAII structure for exclusive access to a part of the data of an
+/// [`RwLock`], returned by [`RwLockWriteGuard::map`].
+///
+/// It keeps the whole lock write-locked until it is dropped.
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct MappedRwLockWriteGuard<'a, T: ?Sized> {
+  raw: &'a RawRwLock,
+  poison_flag: &'a poison::Flag,
+  poison: poison::Guard,
+  data: *mut T,
+  marker: std::marker::PhantomData<&'a mut T>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for MappedRwLockWriteGuard<'_, T> {}
+
+impl RwLockBuilder {
+  /// Creates a builder for a writer-priority lock that doesn't poison.
+  pub fn new() -> RwLockBuilder {
+    RwLockBuilder {
+      writer_priority: true,
+      poison: false,
+    }
+  }
+
+  /// Sets whether waiting writers keep new readers out.
+  ///
+  /// With `true` (the default) a writer that is waiting for the lock blocks
+  /// readers that arrive after it. With `false` readers only wait while a
+  /// writer actually holds the lock.
+  pub fn writer_priority(mut self, writer_priority: bool) -> RwLockBuilder {
+    self.writer_priority = writer_priority;
+    self
+  }
+
+  /// Sets whether a panic while holding the write lock poisons it.
+  ///
+  /// With `false` (the default) a panic just releases the lock. With `true`
+  /// the lock is marked poisoned, and locking it again reports that.
+  pub fn poison(mut self, poison: bool) -> RwLockBuilder {
+    self.poison = poison;
+    self
+  }
+
+  /// Creates an unlocked `RwLock` holding `t` with the configured policy.
+  pub fn build<T>(self, t: T) -> RwLock<T> {
+    RwLock {
+      raw: RawRwLock::new(self.writer_priority),
+      poison: poison::Flag::new(self.poison),
+      data: UnsafeCell::new(t),
+    }
+  }
+}
+
+impl Default for RwLockBuilder {
+  fn default() -> RwLockBuilder {
+    RwLockBuilder::new()
+  }
+}
+
+impl<T> RwLock<T> {
+  /// Creates a new instance of an `RwLock<T>` which is unlocked and prefers
+  /// writers.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::RwLock;
+  ///
+  /// let lock = RwLock::new(5);
+  /// ```
+  pub fn new(t: T) -> RwLock<T> {
+    RwLockBuilder::new().build(t)
+  }
+
+  /// Consumes this `RwLock`, returning the underlying data.
+  ///
+  /// The data is returned even if the lock is poisoned.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::RwLock;
+  ///
+  /// let lock = RwLock::new(String::new());
+  /// lock.write().push_str("hello");
+  ///
+  /// assert_eq!(lock.into_inner(), "hello");
+  /// ```
+  pub fn into_inner(self) -> T {
+    self.data.into_inner()
+  }
+}
+
+impl<T: ?Sized> RwLock<T> {
+  /// Locks this `RwLock` with shared read access, blocking the current
+  /// thread until it can be acquired.
+  ///
+  /// Acquiring a read lock while the current thread already holds one may
+  /// deadlock with a writer-priority lock if a writer is waiting in between.
+  /// With the `deadlock-debug` feature, it panics instead, with the backtraces
+  /// of both acquisitions.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the lock is [poisoned](RwLock::is_poisoned). Use
+  /// [`read_checked`](RwLock::read_checked) to recover the guard instead.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, RwLock};
+  /// use std::thread;
+  ///
+  /// let lock = Arc::new(RwLock::new(1));
+  /// let c_lock = Arc::clone(&lock);
+  ///
+  /// let n = lock.read();
+  /// assert_eq!(*n, 1);
+  ///
+  /// thread::spawn(move || {
+  ///   let r = c_lock.read();
+  ///   assert_eq!(*r, 1);
+  /// })
+  /// .join()
+  /// .unwrap();
+  /// ```
+  #[track_caller]
+  pub fn read(&self) -> RwLockReadGuard<'_, T> {
+    poison::unwrap(self.read_checked())
+  }
+
+  /// Locks this `RwLock` with shared read access like
+  /// [`read`](RwLock::read), returning an error that still holds the guard
+  /// if the lock is poisoned.
+  ///
+  /// A lock that doesn't poison always returns `Ok`.
+  #[track_caller]
+  pub fn read_checked(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+    self.raw.read();
+    self.poison.check(RwLockReadGuard::new(self))
+  }
+
+  /// Attempts to acquire this `RwLock` with shared read access without
+  /// blocking.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the lock is [poisoned](RwLock::is_poisoned). Use
+  /// [`try_read_checked`](RwLock::try_read_checked) to handle that instead.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::RwLock;
+  ///
+  /// let lock = RwLock::new(1);
+  ///
+  /// let reader = lock.try_read().unwrap();
+  /// assert_eq!(*reader, 1);
+  /// assert!(lock.try_write().is_none());
+  /// ```
+  #[track_caller]
+  pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+    poison::unwrap_try(self.try_read_checked())
+  }
+
+  /// Attempts to acquire this `RwLock` with shared read access like
+  /// [`try_read`](RwLock::try_read), returning an error that still holds
+  /// the guard if the lock is poisoned.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, RwLockBuilder};
+  /// use std::thread;
+  ///
+  /// let lock = Arc::new(RwLockBuilder::new().poison(true).build(1));
+  /// let c_lock = Arc::clone(&lock);
+  /// let _ = thread::spawn(move || {
+  ///   let _guard = c_lock.write();
+  ///   panic!();
+  /// })
+  /// .join();
+  ///
+  /// let guard = lock.try_read_checked().unwrap_err().into_inner();
+  /// assert!(lock.try_write_checked().unwrap().is_none());
+  /// drop(guard);
+  /// ```
+  pub fn try_read_checked(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+    if self.raw.try_read() {
+      self.poison.check(RwLockReadGuard::new(self)).map(Some)
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// Attempts to acquire this `RwLock` with shared read access, blocking for
+  /// at most `timeout`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the lock is [poisoned](RwLock::is_poisoned). Use
+  /// [`try_read_for_checked`](RwLock::try_read_for_checked) to handle that instead.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, RwLock};
+  /// use std::thread;
+  /// use std::time::Duration;
+  ///
+  /// let lock = Arc::new(RwLock::new(0));
+  /// let _writer = lock.write();
+  ///
+  /// let other = Arc::clone(&lock);
+  /// let read = thread::spawn(move || {
+  ///   other.try_read_for(Duration::from_millis(10)).is_some()
+  /// });
+  /// assert!(!read.join().unwrap());
+  /// ```
+  #[track_caller]
+  pub fn try_read_for(
+    &self,
+    timeout: std::time::Duration,
+  ) -> Option<RwLockReadGuard<'_, T>> {
+    poison::unwrap_try(self.try_read_for_checked(timeout))
+  }
+
+  /// Attempts to acquire this `RwLock` with shared read access like
+  /// [`try_read_for`](RwLock::try_read_for), returning an error that still
+  /// holds the guard if the lock is poisoned.
+  pub fn try_read_for_checked(
+    &self,
+    timeout: std::time::Duration,
+  ) -> TryLockResult<RwLockReadGuard<'_, T>> {
+    match std::time::Instant::now().checked_add(timeout) {
+      Some(deadline) => self.try_read_until_checked(deadline),
+      // A deadline that far out is never reached.
+      None => self.read_checked().map(Some),
+    }
+  }
+
+  /// Attempts to acquire this `RwLock` with shared read access, blocking
+  /// until `deadline` at the latest.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the lock is [poisoned](RwLock::is_poisoned). Use
+  /// [`try_read_until_checked`](RwLock::try_read_until_checked) to handle that instead.
+  #[track_caller]
+  pub fn try_read_until(
+    &self,
+    deadline: std::time::Instant,
+  ) -> Option<RwLockReadGuard<'_, T>> {
+    poison::unwrap_try(self.try_read_until_checked(deadline))
+  }
+
+  /// Attempts to acquire this `RwLock` with shared read access like
+  /// [`try_read_until`](RwLock::try_read_until), returning an error that
+  /// still holds the guard if the lock is poisoned.
+  pub fn try_read_until_checked(
+    &self,
+    deadline: std::time::Instant,
+  ) -> TryLockResult<RwLockReadGuard<'_, T>> {
+    if self.raw.try_read() || self.raw.read_slow(Some(deadline)) {
+      self.poison.check(RwLockReadGuard::new(self)).map(Some)
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// Locks this `RwLock` with exclusive write access, blocking the current
+  /// thread until it can be acquired.
+  ///
+  /// Acquiring a write lock while the current thread already holds a read or
+  /// write lock deadlocks. With the `deadlock-debug` feature, it panics
+  /// instead, with the backtraces of both acquisitions.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the lock is [poisoned](RwLock::is_poisoned). Use
+  /// [`write_checked`](RwLock::write_checked) to recover the guard instead.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::RwLock;
+  ///
+  /// let lock = RwLock::new(1);
+  ///
+  /// let mut n = lock.write();
+  /// *n = 2;
+  ///
+  /// assert!(lock.try_read().is_none());
+  /// ```
+  #[track_caller]
+  pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+    poison::unwrap(self.write_checked())
+  }
+
+  /// Locks this `RwLock` with exclusive write access like
+  /// [`write`](RwLock::write), returning an error that still holds the guard
+  /// if the lock is poisoned.
+  ///
+  /// A lock that doesn't poison always returns `Ok`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, RwLockBuilder};
+  /// use std::thread;
+  ///
+  /// let lock = Arc::new(RwLockBuilder::new().poison(true).build(vec![1]));
+  /// let c_lock = Arc::clone(&lock);
+  ///
+  /// let _ = thread::spawn(move || {
+  ///   c_lock.write().push(2);
+  ///   panic!();
+  /// })
+  /// .join();
+  ///
+  /// let mut guard = lock.write_checked().unwrap_or_else(|e| e.into_inner());
+  /// guard.truncate(1);
+  /// drop(guard);
+  /// lock.clear_poison();
+  /// assert_eq!(*lock.read(), [1]);
+  /// ```
+  #[track_caller]
+  pub fn write_checked(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+    self.raw.write();
+    self.poison.check(RwLockWriteGuard::new(self))
+  }
+
+  /// Attempts to lock this `RwLock` with exclusive write access without
+  /// blocking.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the lock is [poisoned](RwLock::is_poisoned). Use
+  /// [`try_write_checked`](RwLock::try_write_checked) to handle that instead.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::RwLock;
+  ///
+  /// let lock = RwLock::new(1);
+  ///
+  /// let n = lock.read();
+  /// assert!(lock.try_write().is_none());
+  /// drop(n);
+  ///
+  /// assert!(lock.try_write().is_some());
+  /// ```
+  #[track_caller]
+  pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+    poison::unwrap_try(self.try_write_checked())
+  }
+
+  /// Attempts to acquire this `RwLock` with exclusive write access like
+  /// [`try_write`](RwLock::try_write), returning an error that still holds
+  /// the guard if the lock is poisoned.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, RwLockBuilder};
+  /// use std::thread;
+  ///
+  /// let lock = Arc::new(RwLockBuilder::new().poison(true).build(1));
+  /// let c_lock = Arc::clone(&lock);
+  /// let _ = thread::spawn(move || {
+  ///   let _guard = c_lock.write();
+  ///   panic!();
+  /// })
+  /// .join();
+  ///
+  /// let guard = lock.try_write_checked().unwrap_err().into_inner();
+  /// assert!(lock.try_read_checked().unwrap().is_none());
+  /// drop(guard);
+  /// ```
+  pub fn try_write_checked(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+    if self.raw.try_write() {
+      self.poison.check(RwLockWriteGuard::new(self)).map(Some)
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// Attempts to lock this `RwLock` with exclusive write access, blocking for
+  /// at most `timeout`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the lock is [poisoned](RwLock::is_poisoned). Use
+  /// [`try_write_for_checked`](RwLock::try_write_for_checked) to handle that instead.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::RwLock;
+  /// use std::time::Duration;
+  ///
+  /// let lock = RwLock::new(0);
+  ///
+  /// let reader = lock.read();
+  /// assert!(lock.try_write_for(Duration::from_millis(10)).is_none());
+  /// drop(reader);
+  /// assert!(lock.try_write_for(Duration::from_millis(10)).is_some());
+  /// ```
+  #[track_caller]
+  pub fn try_write_for(
+    &self,
+    timeout: std::time::Duration,
+  ) -> Option<RwLockWriteGuard<'_, T>> {
+    poison::unwrap_try(self.try_write_for_checked(timeout))
+  }
+
+  /// Attempts to acquire this `RwLock` with exclusive write access like
+  /// [`try_write_for`](RwLock::try_write_for), returning an error that still
+  /// holds the guard if the lock is poisoned.
+  pub fn try_write_for_checked(
+    &self,
+    timeout: std::time::Duration,
+  ) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+    match std::time::Instant::now().checked_add(timeout) {
+      Some(deadline) => self.try_write_until_checked(deadline),
+      // A deadline that far out is never reached.
+      None => self.write_checked().map(Some),
+    }
+  }
+
+  /// Attempts to lock this `RwLock` with exclusive write access, blocking
+  /// until `deadline` at the latest.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the lock is [poisoned](RwLock::is_poisoned). Use
+  /// [`try_write_until_checked`](RwLock::try_write_until_checked) to handle that instead.
+  #[track_caller]
+  pub fn try_write_until(
+    &self,
+    deadline: std::time::Instant,
+  ) -> Option<RwLockWriteGuard<'_, T>> {
+    poison::unwrap_try(self.try_write_until_checked(deadline))
+  }
+
+  /// Attempts to acquire this `RwLock` with exclusive write access like
+  /// [`try_write_until`](RwLock::try_write_until), returning an error that
+  /// still holds the guard if the lock is poisoned.
+  pub fn try_write_until_checked(
+    &self,
+    deadline: std::time::Instant,
+  ) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+    if self.raw.try_write() || self.raw.write_slow(Some(deadline)) {
+      self.poison.check(RwLockWriteGuard::new(self)).map(Some)
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// Returns `true` if waiting writers keep new readers out.
+  pub fn is_writer_priority(&self) -> bool {
+    self.raw.writer_priority
+  }
+
+  /// Returns `true` if a writer panicked while holding this lock.
+  ///
+  /// Always `false` for a lock that doesn't poison.
+  pub fn is_poisoned(&self) -> bool {
+    self.poison.get()
+  }
+
+  /// Clears the poisoned state, once the caller has made sure the data is
+  /// consistent again.
+  pub fn clear_poison(&self) {
+    self.poison.clear();
+  }
+
+  /// Returns `true` if a panic while holding the write lock poisons it.
+  pub fn is_poisoning(&self) -> bool {
+    self.poison.is_enabled()
+  }
+
+  /// Returns a mutable reference to the underlying data.
+  ///
+  /// Since this call borrows the `RwLock` mutably, no actual locking needs to
+  /// take place: the mutable borrow statically guarantees no locks exist.
+  /// Poisoning is ignored.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::RwLock;
+  ///
+  /// let mut lock = RwLock::new(0);
+  /// *lock.get_mut() = 10;
+  /// assert_eq!(*lock.read(), 10);
+  /// ```
+  pub fn get_mut(&mut self) -> &mut T {
+    // SAFETY: `&mut self` guarantees there is no guard alive.
+    self.data.with_mut(|data| unsafe { &mut *data })
+  }
+}
+
+impl<T: Default> Default for RwLock<T> {
+  fn default() -> RwLock<T> {
+    RwLock::new(Default::default())
+  }
+}
+
+impl<T> From<T> for RwLock<T> {
+  fn from(t: T) -> RwLock<T> {
+    RwLock::new(t)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for RwLock<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut d = f.debug_struct("RwLock");
+    if self.raw.try_read() {
+      let guard = RwLockReadGuard::new(self);
+      d.field("data", &&*guard);
+    } else {
+      d.field("data", &format_args!("<locked>"));
+    }
+    if self.poison.is_enabled() {
+      d.field("poisoned", &self.poison.get());
+    }
+    d.finish()
+  }
+}
+
+impl<'a, T: ?Sized> RwLockReadGuard<'a, T> {
+  fn new(lock: &'a RwLock<T>) -> RwLockReadGuard<'a, T> {
+    RwLockReadGuard {
+      lock,
+      marker: std::marker::PhantomData,
+    }
+  }
+
+  /// Makes a [`MappedRwLockReadGuard`] for a component of the locked data.
+  ///
+  /// The lock stays read-locked. This is an associated function that needs
+  /// to be used as `RwLockReadGuard::map(...)`, so it doesn't shadow a method
+  /// of the same name on the contents.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{RwLock, RwLockReadGuard};
+  ///
+  /// let lock = RwLock::new((String::from("hello"), 0));
+  ///
+  /// let name = RwLockReadGuard::map(lock.read(), |pair| &pair.0);
+  /// assert_eq!(*name, "hello");
+  /// ```
+  pub fn map<U: ?Sized>(
+    orig: Self,
+    f: impl FnOnce(&T) -> &U,
+  ) -> MappedRwLockReadGuard<'a, U> {
+    // If `f` panics, `orig` still unlocks the lock on the way out.
+    let data = f(&*orig) as *const U;
+    let raw = &orig.lock.raw;
+    std::mem::forget(orig);
+    MappedRwLockReadGuard {
+      raw,
+      data,
+      marker: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+  fn new(lock: &'a RwLock<T>) -> RwLockWriteGuard<'a, T> {
+    RwLockWriteGuard {
+      lock,
+      poison: lock.poison.guard(),
+      marker: std::marker::PhantomData,
+    }
+  }
+
+  /// Makes a [`MappedRwLockWriteGuard`] for a component of the locked data.
+  ///
+  /// The lock stays write-locked. Like [`RwLockReadGuard::map`], this is an
+  /// associated function.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{RwLock, RwLockWriteGuard};
+  ///
+  /// let lock = RwLock::new((String::new(), 0));
+  ///
+  /// let mut count = RwLockWriteGuard::map(lock.write(), |pair| &mut pair.1);
+  /// *count += 1;
+  /// drop(count);
+  ///
+  /// assert_eq!(lock.read().1, 1);
+  /// ```
+  pub fn map<U: ?Sized>(
+    mut orig: Self,
+    f: impl FnOnce(&mut T) -> &mut U,
+  ) -> MappedRwLockWriteGuard<'a, U> {
+    let data = f(&mut *orig) as *mut U;
+    let lock = orig.lock;
+    let poison = orig.poison;
+    std::mem::forget(orig);
+    MappedRwLockWriteGuard {
+      raw: &lock.raw,
+      poison_flag: &lock.poison,
+      poison,
+      data,
+      marker: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<'a, T: ?Sized> MappedRwLockReadGuard<'a, T> {
+  /// Makes a new [`MappedRwLockReadGuard`] for a component of the data this
+  /// guard already points to.
+  pub fn map<U: ?Sized>(
+    orig: Self,
+    f: impl FnOnce(&T) -> &U,
+  ) -> MappedRwLockReadGuard<'a, U> {
+    let data = f(&*orig) as *const U;
+    let raw = orig.raw;
+    std::mem::forget(orig);
+    MappedRwLockReadGuard {
+      raw,
+      data,
+      marker: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<'a, T: ?Sized> MappedRwLockWriteGuard<'a, T> {
+  /// Makes a new [`MappedRwLockWriteGuard`] for a component of the data this
+  /// guard already points to.
+  pub fn map<U: ?Sized>(
+    mut orig: Self,
+    f: impl FnOnce(&mut T) -> &mut U,
+  ) -> MappedRwLockWriteGuard<'a, U> {
+    let data = f(&mut *orig) as *mut U;
+    let (raw, poison_flag, poison) = (orig.raw, orig.poison_flag, orig.poison);
+    std::mem::forget(orig);
+    MappedRwLockWriteGuard {
+      raw,
+      poison_flag,
+      poison,
+      data,
+      marker: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for RwLockReadGuard<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    // SAFETY: The guard holds a read lock.
+    self.lock.data.with(|data| unsafe { &*data })
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for RwLockWriteGuard<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    // SAFETY: The guard holds the write lock.
+    self.lock.data.with(|data| unsafe { &*data })
+  }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for RwLockWriteGuard<'_, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    // SAFETY: The guard holds the write lock, and `&mut self` makes this the
+    // only reference handed out by it.
+    self.lock.data.with_mut(|data| unsafe { &mut *data })
+  }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+  #[inline]
+  fn drop(&mut self) {
+    // SAFETY: The guard holds a read lock.
+    unsafe { self.lock.raw.read_unlock() }
+  }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+  #[inline]
+  fn drop(&mut self) {
+    self.lock.poison.done(&self.poison);
+    // SAFETY: The guard holds the write lock.
+    unsafe { self.lock.raw.write_unlock() }
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for RwLockReadGuard<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display
+  for RwLockReadGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for RwLockWriteGuard<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display
+  for RwLockWriteGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for MappedRwLockReadGuard<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    // SAFETY: `data` points into the lock, which stays read-locked for as
+    // long as the guard lives.
+    unsafe { &*self.data }
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for MappedRwLockWriteGuard<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    // SAFETY: `data` points into the lock, which stays write-locked for as
+    // long as the guard lives.
+    unsafe { &*self.data }
+  }
+}
+
+impl<T: ?Sized> std::ops::DerefMut for MappedRwLockWriteGuard<'_, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    // SAFETY: As above, and `&mut self` makes this the only reference handed
+    // out by the guard.
+    unsafe { &mut *self.data }
+  }
+}
+
+impl<T: ?Sized> Drop for MappedRwLockReadGuard<'_, T> {
+  #[inline]
+  fn drop(&mut self) {
+    // SAFETY: The guard holds a read lock.
+    unsafe { self.raw.read_unlock() }
+  }
+}
+
+impl<T: ?Sized> Drop for MappedRwLockWriteGuard<'_, T> {
+  #[inline]
+  fn drop(&mut self) {
+    self.poison_flag.done(&self.poison);
+    // SAFETY: The guard holds the write lock.
+    unsafe { self.raw.write_unlock() }
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug
+  for MappedRwLockReadGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display
+  for MappedRwLockReadGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug
+  for MappedRwLockWriteGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display
+  for MappedRwLockWriteGuard<'_, T>
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+  use crate::sync::Arc;
+
+  use std::thread;
+  use std::time::{Duration, Instant};
+
+  #[test]
+  fn shared_and_exclusive() {
+    let lock = RwLock::new(0);
+
+    let r1 = lock.read();
+    let r2 = lock.try_read().unwrap();
+    assert!(lock.try_write().is_none());
+    drop((r1, r2));
+
+    let mut w = lock.try_write().unwrap();
+    *w += 1;
+    assert!(lock.try_read().is_none());
+    drop(w);
+
+    assert_eq!(*lock.read(), 1);
+  }
+
+  #[test]
+  fn waiting_writer_blocks_new_readers() {
+    let lock = Arc::new(RwLock::new(0));
+    let reader = lock.read();
+
+    let other = Arc::clone(&lock);
+    let writer = thread::spawn(move || *other.write() += 1);
+
+    // Wait until the writer has parked.
+    while lock.raw.state.load(Relaxed) & WRITERS_PARKED == 0 {
+      thread::yield_now();
+    }
+    assert!(lock.try_read().is_none());
+
+    drop(reader);
+    writer.join().unwrap();
+    assert_eq!(*lock.read(), 1);
+  }
+
+  #[test]
+  fn reader_priority_lets_readers_in() {
+    let lock = Arc::new(RwLockBuilder::new().writer_priority(false).build(0));
+    let reader = lock.read();
+
+    let other = Arc::clone(&lock);
+    let writer = thread::spawn(move || *other.write() += 1);
+
+    while lock.raw.state.load(Relaxed) & WRITERS_PARKED == 0 {
+      thread::yield_now();
+    }
+    assert!(lock.try_read().is_some());
+
+    drop(reader);
+    writer.join().unwrap();
+    assert_eq!(*lock.read(), 1);
+  }
+
+  #[test]
+  fn contended_readers_and_writers() {
+    for writer_priority in [true, false] {
+      let lock = Arc::new(
+        RwLockBuilder::new()
+          .writer_priority(writer_priority)
+          .build(0),
+      );
+
+      let handles: Vec<_> = (0..8)
+        .map(|i| {
+          let lock = Arc::clone(&lock);
+          thread::spawn(move || {
+            for _ in 0..500 {
+              if i % 2 == 0 {
+                *lock.write() += 1;
+              } else {
+                let n = *lock.read();
+                assert!(n <= 2000);
+              }
+            }
+          })
+        })
+        .collect();
+
+      for handle in handles {
+        handle.join().unwrap();
+      }
+      assert_eq!(*lock.read(), 2000);
+    }
+  }
+
+  #[test]
+  fn debug_shows_locked() {
+    let lock = RwLock::new(5);
+    assert_eq!(format!("{:?}", lock), "RwLock { data: 5 }");
+
+    let _guard = lock.write();
+    assert_eq!(format!("{:?}", lock), "RwLock { data: <locked> }");
+  }
+
+  #[test]
+  fn only_writers_poison() {
+    let lock = Arc::new(RwLockBuilder::new().poison(true).build(0));
+
+    let other = Arc::clone(&lock);
+    let _ = thread::spawn(move || {
+      let _guard = other.read();
+      panic!("reader panics");
+    })
+    .join();
+    assert!(!lock.is_poisoned());
+
+    let other = Arc::clone(&lock);
+    let _ = thread::spawn(move || {
+      *other.write() = 1;
+      let _guard = other.write();
+      panic!("writer panics");
+    })
+    .join();
+    assert!(lock.is_poisoned());
+    assert_eq!(*lock.read_checked().unwrap_err().into_inner(), 1);
+
+    lock.clear_poison();
+    assert!(lock.write_checked().is_ok());
+  }
+
+  #[test]
+  fn checked_try_locks_report_poison() {
+    let lock = Arc::new(RwLockBuilder::new().poison(true).build(0));
+    let other = Arc::clone(&lock);
+    let _ = thread::spawn(move || {
+      let _guard = other.write();
+      panic!("writer panics");
+    })
+    .join();
+
+    let timeout = Duration::from_millis(1);
+    let deadline = Instant::now() + timeout;
+    let reader = lock.try_read_checked().unwrap_err().into_inner();
+    assert!(lock.try_read_for_checked(timeout).is_err());
+    assert!(lock.try_read_until_checked(deadline).is_err());
+    assert!(lock.try_write_checked().unwrap().is_none());
+    assert!(lock.try_write_for_checked(timeout).unwrap().is_none());
+    assert!(lock.try_write_until_checked(deadline).unwrap().is_none());
+    drop(reader);
+
+    let writer = lock.try_write_checked().unwrap_err().into_inner();
+    assert!(lock.try_read_checked().unwrap().is_none());
+    drop(writer);
+    assert!(lock.try_write_for_checked(timeout).is_err());
+    assert!(lock.try_write_until_checked(deadline).is_err());
+
+    let panicked = thread::spawn({
+      let lock = Arc::clone(&lock);
+      move || lock.try_read().is_some()
+    })
+    .join();
+    assert!(panicked.is_err(), "`try_read` panics on a poisoned lock");
+
+    lock.clear_poison();
+    assert!(lock.try_write_checked().unwrap().is_some());
+  }
+
+  #[test]
+  fn timed_write_lets_readers_back_in() {
+    let lock = Arc::new(RwLock::new(0));
+    let reader = lock.read();
+
+    // The timed out writer must not keep blocking readers behind it.
+    let other = Arc::clone(&lock);
+    let timed_out = thread::spawn(move || {
+      other.try_write_for(Duration::from_millis(10)).is_none()
+    });
+    assert!(timed_out.join().unwrap());
+    assert!(lock.try_read().is_some());
+
+    drop(reader);
+    *lock.try_write_until(Instant::now()).unwrap() = 1;
+    assert_eq!(*lock.try_read_for(Duration::from_millis(10)).unwrap(), 1);
+  }
+
+  #[test]
+  fn mapped_guards_keep_lock() {
+    let lock = RwLock::new((vec![1, 2], String::from("a")));
+
+    let first = RwLockReadGuard::map(lock.read(), |pair| &pair.0);
+    let first = MappedRwLockReadGuard::map(first, |v| &v[0]);
+    assert_eq!(*first, 1);
+    assert!(lock.try_write().is_none());
+    assert!(lock.try_read().is_some());
+    drop(first);
+
+    let name = RwLockWriteGuard::map(lock.write(), |pair| &mut pair.1);
+    let mut name = MappedRwLockWriteGuard::map(name, |s| s);
+    name.push('b');
+    assert!(lock.try_read().is_none());
+    drop(name);
+
+    assert_eq!(lock.read().1, "ab");
+  }
+
+  #[cfg(feature = "deadlock-debug")]
+  #[test]
+  #[should_panic(expected = "already holds this RwLock")]
+  fn writing_while_reading_on_the_same_thread_panics() {
+    let lock = RwLock::new(0);
+    let _reader = lock.read();
+    let _writer = lock.write();
+  }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+  use super::*;
+  use crate::sync::Arc;
+
+  use ::loom::thread;
+
+  #[test]
+  fn loom_reader_and_writer() {
+    ::loom::model(|| {
+      let lock = Arc::new(RwLock::new(0));
+
+      let other = Arc::clone(&lock);
+      let t = thread::spawn(move || *other.write() += 1);
+
+      let seen = *lock.read();
+      assert!(seen == 0 || seen == 1);
+
+      t.join().unwrap();
+      assert_eq!(*lock.read(), 1);
+    });
+  }
+
+  #[test]
+  fn loom_two_writers() {
+    ::loom::model(|| {
+      let lock = Arc::new(RwLockBuilder::new().writer_priority(false).build(0));
+
+      let other = Arc::clone(&lock);
+      let t = thread::spawn(move || *other.write() += 1);
+      *lock.write() += 1;
+      t.join().unwrap();
+
+      assert_eq!(*lock.read(), 2);
+    });
+  }
+}
@@ -0,0 +1,511 @@
+//! Cheaply cloneable and sliceable byte buffers.
+//!
+//! [`SharedBytes`] is a view into a reference-counted [`Arc<[u8]>`][Arc] buffer: the buffer itself
+//! plus an offset and a length. Cloning, [slicing] and [splitting] only adjust the view and the
+//! reference count, so a parser can hand out pieces of one network read without copying any bytes.
+//!
+//! When a view turns out to be the last one of its buffer, [`try_into_mut`] hands it back as a
+//! [`SharedBytesMut`] that can be written to in place and later [`freeze`]d again.
+//!
+//! ```
+//! use pointer::sync::SharedBytes;
+//!
+//! let mut packet = SharedBytes::from(&b"GET /index.html"[..]);
+//!
+//! let method = packet.split_to(3);
+//! packet.advance(1);
+//!
+//! assert_eq!(method, b"GET"[..]);
+//! assert_eq!(packet, b"/index.html"[..]);
+//! ```
+//!
+//! [slicing]: SharedBytes::slice
+//! [splitting]: SharedBytes::split_to
+//! [`try_into_mut`]: SharedBytes::try_into_mut
+//! [`freeze`]: SharedBytesMut::freeze
+
+use super::Arc;
+
+/// An immutable view into a shared byte buffer.
+///
+/// See the [module-level documentation](index.html) for more.
+#[derive(Clone)]
+pub struct SharedBytes {
+  data: Arc<[u8]>,
+  offset: usize,
+  len: usize,
+}
+
+/// A uniquely owned view into a byte buffer, obtained from
+/// [`SharedBytes::try_into_mut`].
+///
+/// No other [`SharedBytes`] points into the buffer, so its bytes can be
+/// changed in place through [`DerefMut`](std::ops::DerefMut).
+pub struct SharedBytesMut {
+  // Invariant: this is the only pointer (strong or weak) to the buffer.
+  data: Arc<[u8]>,
+  offset: usize,
+  len: usize,
+}
+
+impl SharedBytes {
+  /// Creates an empty `SharedBytes`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::SharedBytes;
+  ///
+  /// let bytes = SharedBytes::new();
+  /// assert!(bytes.is_empty());
+  /// ```
+  pub fn new() -> SharedBytes {
+    SharedBytes::from(Arc::from(Vec::new()))
+  }
+
+  /// Returns the number of bytes in this view.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Returns `true` if this view contains no bytes.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Returns the bytes of this view.
+  #[inline]
+  pub fn as_slice(&self) -> &[u8] {
+    &self.data[self.offset..self.offset + self.len]
+  }
+
+  /// Returns a new view of `range`, relative to this view, sharing the same
+  /// buffer.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `range` is out of bounds or its start is after its end.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::SharedBytes;
+  ///
+  /// let bytes = SharedBytes::from(&b"hello world"[..]);
+  /// let world = bytes.slice(6..);
+  ///
+  /// assert_eq!(world, b"world"[..]);
+  /// assert!(SharedBytes::ptr_eq_buffer(&bytes, &world));
+  /// ```
+  pub fn slice(&self, range: impl std::ops::RangeBounds<usize>) -> SharedBytes {
+    use std::ops::Bound;
+
+    let start = match range.start_bound() {
+      Bound::Included(&n) => n,
+      Bound::Excluded(&n) => n.checked_add(1).expect("out of range"),
+      Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+      Bound::Included(&n) => n.checked_add(1).expect("out of range"),
+      Bound::Excluded(&n) => n,
+      Bound::Unbounded => self.len,
+    };
+
+    assert!(
+      start <= end,
+      "range start must not be greater than end: {:?} <= {:?}",
+      start,
+      end
+    );
+    assert!(
+      end <= self.len,
+      "range end out of bounds: {:?} <= {:?}",
+      end,
+      self.len
+    );
+
+    SharedBytes {
+      data: Arc::clone(&self.data),
+      offset: self.offset + start,
+      len: end - start,
+    }
+  }
+
+  /// Splits the view in two at `at`.
+  ///
+  /// Afterwards `self` contains `[at, len)` and the returned view contains
+  /// `[0, at)`. Both share the same buffer.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `at > len`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::SharedBytes;
+  ///
+  /// let mut bytes = SharedBytes::from(&b"hello world"[..]);
+  /// let hello = bytes.split_to(5);
+  ///
+  /// assert_eq!(hello, b"hello"[..]);
+  /// assert_eq!(bytes, b" world"[..]);
+  /// ```
+  #[must_use = "consider SharedBytes::advance if you don't need the other half"]
+  pub fn split_to(&mut self, at: usize) -> SharedBytes {
+    assert!(
+      at <= self.len,
+      "split_to out of bounds: {:?} <= {:?}",
+      at,
+      self.len
+    );
+
+    let head = SharedBytes {
+      data: Arc::clone(&self.data),
+      offset: self.offset,
+      len: at,
+    };
+    self.offset += at;
+    self.len -= at;
+    head
+  }
+
+  /// Splits the view in two at `at`.
+  ///
+  /// Afterwards `self` contains `[0, at)` and the returned view contains
+  /// `[at, len)`. Both share the same buffer.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `at > len`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::SharedBytes;
+  ///
+  /// let mut bytes = SharedBytes::from(&b"hello world"[..]);
+  /// let world = bytes.split_off(6);
+  ///
+  /// assert_eq!(bytes, b"hello "[..]);
+  /// assert_eq!(world, b"world"[..]);
+  /// ```
+  #[must_use = "consider SharedBytes::truncate if you don't need the other half"]
+  pub fn split_off(&mut self, at: usize) -> SharedBytes {
+    assert!(
+      at <= self.len,
+      "split_off out of bounds: {:?} <= {:?}",
+      at,
+      self.len
+    );
+
+    let tail = SharedBytes {
+      data: Arc::clone(&self.data),
+      offset: self.offset + at,
+      len: self.len - at,
+    };
+    self.len = at;
+    tail
+  }
+
+  /// Drops the first `cnt` bytes from the view.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `cnt > len`.
+  pub fn advance(&mut self, cnt: usize) {
+    assert!(
+      cnt <= self.len,
+      "cannot advance past the end: {:?} <= {:?}",
+      cnt,
+      self.len
+    );
+    self.offset += cnt;
+    self.len -= cnt;
+  }
+
+  /// Shortens the view to `len` bytes. Has no effect if `len` is greater
+  /// than the current length.
+  pub fn truncate(&mut self, len: usize) {
+    self.len = std::cmp::min(self.len, len);
+  }
+
+  /// Returns `true` if both views point into the same buffer.
+  pub fn ptr_eq_buffer(this: &Self, other: &Self) -> bool {
+    Arc::ptr_eq(&this.data, &other.data)
+  }
+
+  /// Turns this view into a [`SharedBytesMut`] if it is the only one
+  /// pointing into its buffer, without copying. Otherwise the view is given
+  /// back unchanged.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::SharedBytes;
+  ///
+  /// let mut bytes = SharedBytes::from(&b"hello world"[..]);
+  /// let world = bytes.split_off(6);
+  ///
+  /// // `world` still shares the buffer.
+  /// let bytes = bytes.try_into_mut().unwrap_err();
+  /// drop(world);
+  ///
+  /// let mut hello = bytes.try_into_mut().unwrap();
+  /// hello[0] = b'j';
+  /// assert_eq!(hello.freeze(), b"jello "[..]);
+  /// ```
+  pub fn try_into_mut(mut self) -> Result<SharedBytesMut, SharedBytes> {
+    if Arc::get_mut(&mut self.data).is_none() {
+      return Err(self);
+    }
+
+    Ok(SharedBytesMut {
+      data: self.data,
+      offset: self.offset,
+      len: self.len,
+    })
+  }
+}
+
+impl SharedBytesMut {
+  /// Returns the number of bytes in this view.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Returns `true` if this view contains no bytes.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Converts back into an immutable, shareable [`SharedBytes`].
+  pub fn freeze(self) -> SharedBytes {
+    SharedBytes {
+      data: self.data,
+      offset: self.offset,
+      len: self.len,
+    }
+  }
+}
+
+impl std::ops::Deref for SharedBytes {
+  type Target = [u8];
+
+  #[inline]
+  fn deref(&self) -> &[u8] {
+    self.as_slice()
+  }
+}
+
+impl std::ops::Deref for SharedBytesMut {
+  type Target = [u8];
+
+  #[inline]
+  fn deref(&self) -> &[u8] {
+    &self.data[self.offset..self.offset + self.len]
+  }
+}
+
+impl std::ops::DerefMut for SharedBytesMut {
+  #[inline]
+  fn deref_mut(&mut self) -> &mut [u8] {
+    let range = self.offset..self.offset + self.len;
+    // SAFETY: `SharedBytesMut` holds the only pointer to the buffer and
+    // never hands out another one while it is alive.
+    unsafe { &mut Arc::get_mut_unchecked(&mut self.data)[range] }
+  }
+}
+
+impl AsRef<[u8]> for SharedBytes {
+  fn as_ref(&self) -> &[u8] {
+    self.as_slice()
+  }
+}
+
+impl std::borrow::Borrow<[u8]> for SharedBytes {
+  fn borrow(&self) -> &[u8] {
+    self.as_slice()
+  }
+}
+
+impl Default for SharedBytes {
+  fn default() -> SharedBytes {
+    SharedBytes::new()
+  }
+}
+
+impl From<Arc<[u8]>> for SharedBytes {
+  fn from(data: Arc<[u8]>) -> SharedBytes {
+    let len = data.len();
+    SharedBytes {
+      data,
+      offset: 0,
+      len,
+    }
+  }
+}
+
+impl From<Vec<u8>> for SharedBytes {
+  fn from(v: Vec<u8>) -> SharedBytes {
+    SharedBytes::from(Arc::from(v))
+  }
+}
+
+impl From<&[u8]> for SharedBytes {
+  fn from(v: &[u8]) -> SharedBytes {
+    SharedBytes::from(Arc::from(v))
+  }
+}
+
+impl From<String> for SharedBytes {
+  fn from(s: String) -> SharedBytes {
+    SharedBytes::from(s.into_bytes())
+  }
+}
+
+impl From<&str> for SharedBytes {
+  fn from(s: &str) -> SharedBytes {
+    SharedBytes::from(s.as_bytes())
+  }
+}
+
+impl From<SharedBytesMut> for SharedBytes {
+  fn from(bytes: SharedBytesMut) -> SharedBytes {
+    bytes.freeze()
+  }
+}
+
+impl PartialEq for SharedBytes {
+  fn eq(&self, other: &SharedBytes) -> bool {
+    self.as_slice() == other.as_slice()
+  }
+}
+
+impl Eq for SharedBytes {}
+
+impl PartialEq<[u8]> for SharedBytes {
+  fn eq(&self, other: &[u8]) -> bool {
+    self.as_slice() == other
+  }
+}
+
+impl PartialEq<SharedBytes> for [u8] {
+  fn eq(&self, other: &SharedBytes) -> bool {
+    self == other.as_slice()
+  }
+}
+
+impl PartialOrd for SharedBytes {
+  fn partial_cmp(&self, other: &SharedBytes) -> Option<std::cmp::Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for SharedBytes {
+  fn cmp(&self, other: &SharedBytes) -> std::cmp::Ordering {
+    self.as_slice().cmp(other.as_slice())
+  }
+}
+
+impl std::hash::Hash for SharedBytes {
+  fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    self.as_slice().hash(state);
+  }
+}
+
+impl std::fmt::Debug for SharedBytes {
+  /// Formats the bytes like a byte string literal, e.g. `b"GET \r\n"`.
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "b\"")?;
+    for &b in self.as_slice() {
+      write!(f, "{}", std::ascii::escape_default(b))?;
+    }
+    write!(f, "\"")
+  }
+}
+
+impl std::fmt::Debug for SharedBytesMut {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("SharedBytesMut")
+      .field("len", &self.len)
+      .finish()
+  }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn slice_shares_buffer() {
+    let bytes = SharedBytes::from("hello world");
+    let world = bytes.slice(6..);
+    let orl = world.slice(1..=3);
+
+    assert_eq!(orl, b"orl"[..]);
+    assert!(SharedBytes::ptr_eq_buffer(&bytes, &orl));
+    assert_eq!(Arc::strong_count(&bytes.data), 3);
+  }
+
+  #[test]
+  #[should_panic(expected = "range end out of bounds")]
+  fn slice_out_of_bounds() {
+    let bytes = SharedBytes::from("abc").slice(1..);
+    let _ = bytes.slice(..3);
+  }
+
+  #[test]
+  fn split_to_and_off() {
+    let mut bytes = SharedBytes::from(b"0123456789".to_vec());
+
+    let head = bytes.split_to(3);
+    let tail = bytes.split_off(4);
+
+    assert_eq!(head, b"012"[..]);
+    assert_eq!(bytes, b"3456"[..]);
+    assert_eq!(tail, b"789"[..]);
+
+    let empty = bytes.split_to(0);
+    assert!(empty.is_empty());
+  }
+
+  #[test]
+  fn try_into_mut_only_when_unique() {
+    let mut bytes = SharedBytes::from("abcdef");
+    let tail = bytes.split_off(3);
+
+    let bytes = bytes.try_into_mut().unwrap_err();
+    assert_eq!(bytes, b"abc"[..]);
+    drop(tail);
+
+    let mut unique = bytes.try_into_mut().unwrap();
+    unique.copy_from_slice(b"xyz");
+
+    let frozen = unique.freeze();
+    assert_eq!(frozen, b"xyz"[..]);
+    // The buffer was reused, not copied.
+    assert_eq!(frozen.data.len(), 6);
+  }
+
+  #[test]
+  fn debug_escapes() {
+    let bytes = SharedBytes::from(&b"ok\r\n\x00"[..]);
+    assert_eq!(format!("{:?}", bytes), r#"b"ok\r\n\x00""#);
+  }
+
+  #[test]
+  fn send_between_threads() {
+    let mut bytes = SharedBytes::from("left|right");
+    let left = bytes.split_to(4);
+    bytes.advance(1);
+
+    let handle = std::thread::spawn(move || left.len());
+    assert_eq!(handle.join().unwrap(), 4);
+    assert_eq!(bytes, b"right"[..]);
+  }
+}
@@ -0,0 +1,330 @@
+//! A spin lock that needs neither `std` nor an operating system.
+//!
+//! [`SpinLock<T>`][SpinLock] busy-waits on a single atomic flag instead of parking the thread, so
+//! it only relies on `core`: there is no wait queue, no thread handle and no allocation. That
+//! makes it usable on bare-metal targets and inside other low-level primitives, at the cost of
+//! burning CPU time while the lock is contended. Prefer [`Mutex`] whenever an OS is available.
+//!
+//! A lock made with [`SpinLock::with_backoff`] waits exponentially longer between attempts, which
+//! keeps the cache line of a heavily contended lock from bouncing between cores on every spin.
+//!
+//! ```
+//! use pointer::sync::SpinLock;
+//!
+//! let lock = SpinLock::new(0);
+//!
+//! *lock.lock() += 1;
+//! assert_eq!(*lock.lock(), 1);
+//! ```
+//!
+//! [`Mutex`]: crate::sync::Mutex
+
+use crate::loom::atomic::AtomicBool;
+use crate::loom::atomic::Ordering::{Acquire, Relaxed, Release};
+use crate::loom::cell::UnsafeCell;
+use crate::loom::hint;
+
+/// Backoff never waits longer than `2^MAX_BACKOFF_SHIFT` spins per attempt.
+const MAX_BACKOFF_SHIFT: u32 = 6;
+
+/// A mutual exclusion primitive that busy-waits for the lock.
+///
+/// See the [module-level documentation](index.html) for more.
+pub struct SpinLock<T: ?Sized> {
+  locked: AtomicBool,
+  backoff: bool,
+  data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for SpinLock<T> {}
+unsafe impl<T: ?Sized + Send> Sync for SpinLock<T> {}
+
+/// An RAII guard for a locked [`SpinLock`], returned by [`SpinLock::lock`]
+/// and [`SpinLock::try_lock`].
+///
+/// The lock is released when the guard is dropped. Unlike the guards of
+/// the parking locks, a `SpinLockGuard` may be dropped on another thread.
+#[must_use = "if unused the SpinLock will immediately unlock"]
+pub struct SpinLockGuard<'a, T: ?Sized> {
+  lock: &'a SpinLock<T>,
+  marker: core::marker::PhantomData<&'a mut T>,
+}
+
+impl<T> SpinLock<T> {
+  /// Creates a new, unlocked spin lock that spins without backing off.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::SpinLock;
+  ///
+  /// let lock = SpinLock::new(0);
+  /// ```
+  pub fn new(t: T) -> SpinLock<T> {
+    SpinLock {
+      locked: AtomicBool::new(false),
+      backoff: false,
+      data: UnsafeCell::new(t),
+    }
+  }
+
+  /// Creates a new, unlocked spin lock that backs off exponentially while
+  /// the lock is contended.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::SpinLock;
+  ///
+  /// let lock = SpinLock::with_backoff(0);
+  /// assert!(lock.has_backoff());
+  /// ```
+  pub fn with_backoff(t: T) -> SpinLock<T> {
+    SpinLock {
+      locked: AtomicBool::new(false),
+      backoff: true,
+      data: UnsafeCell::new(t),
+    }
+  }
+
+  /// Consumes this lock, returning the underlying data.
+  pub fn into_inner(self) -> T {
+    self.data.into_inner()
+  }
+}
+
+impl<T: ?Sized> SpinLock<T> {
+  /// Acquires the lock, spinning until it is able to.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::{Arc, SpinLock};
+  /// use std::thread;
+  ///
+  /// let lock = Arc::new(SpinLock::new(0));
+  /// let c_lock = Arc::clone(&lock);
+  ///
+  /// thread::spawn(move || *c_lock.lock() = 10).join().unwrap();
+  /// assert_eq!(*lock.lock(), 10);
+  /// ```
+  pub fn lock(&self) -> SpinLockGuard<'_, T> {
+    let mut shift = 0;
+    while self
+      .locked
+      .compare_exchange_weak(false, true, Acquire, Relaxed)
+      .is_err()
+    {
+      // Wait on a plain load so the cache line stays shared while spinning.
+      while self.locked.load(Relaxed) {
+        if self.backoff {
+          for _ in 0..1u32 << shift {
+            hint::spin_loop();
+          }
+          shift = core::cmp::min(shift + 1, MAX_BACKOFF_SHIFT);
+        } else {
+          hint::spin_loop();
+        }
+      }
+    }
+    SpinLockGuard::new(self)
+  }
+
+  /// Attempts to acquire the lock without spinning.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::sync::SpinLock;
+  ///
+  /// let lock = SpinLock::new(());
+  ///
+  /// let guard = lock.try_lock().unwrap();
+  /// assert!(lock.try_lock().is_none());
+  /// drop(guard);
+  /// assert!(lock.try_lock().is_some());
+  /// ```
+  pub fn try_lock(&self) -> Option<SpinLockGuard<'_, T>> {
+    if self
+      .locked
+      .compare_exchange(false, true, Acquire, Relaxed)
+      .is_ok()
+    {
+      Some(SpinLockGuard::new(self))
+    } else {
+      None
+    }
+  }
+
+  /// Returns `true` if the lock is currently held.
+  ///
+  /// Another thread can lock or unlock at any time, so this is only a hint.
+  pub fn is_locked(&self) -> bool {
+    self.locked.load(Relaxed)
+  }
+
+  /// Returns `true` if this lock backs off exponentially under contention.
+  pub fn has_backoff(&self) -> bool {
+    self.backoff
+  }
+
+  /// Returns a mutable reference to the underlying data.
+  ///
+  /// Since this call borrows the `SpinLock` mutably, no actual locking needs
+  /// to take place.
+  pub fn get_mut(&mut self) -> &mut T {
+    // SAFETY: `&mut self` guarantees there is no guard alive.
+    self.data.with_mut(|data| unsafe { &mut *data })
+  }
+}
+
+impl<T: Default> Default for SpinLock<T> {
+  fn default() -> SpinLock<T> {
+    SpinLock::new(Default::default())
+  }
+}
+
+impl<T> From<T> for SpinLock<T> {
+  fn from(t: T) -> SpinLock<T> {
+    SpinLock::new(t)
+  }
+}
+
+impl<T: ?Sized + core::fmt::Debug> core::fmt::Debug for SpinLock<T> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut d = f.debug_struct("SpinLock");
+    match self.try_lock() {
+      Some(guard) => d.field("data", &&*guard),
+      None => d.field("data", &format_args!("<locked>")),
+    };
+    d.finish()
+  }
+}
+
+impl<'a, T: ?Sized> SpinLockGuard<'a, T> {
+  fn new(lock: &'a SpinLock<T>) -> SpinLockGuard<'a, T> {
+    SpinLockGuard {
+      lock,
+      marker: core::marker::PhantomData,
+    }
+  }
+}
+
+impl<T: ?Sized> core::ops::Deref for SpinLockGuard<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    // SAFETY: The guard holds the lock.
+    self.lock.data.with(|data| unsafe { &*data })
+  }
+}
+
+impl<T: ?Sized> core::ops::DerefMut for SpinLockGuard<'_, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    // SAFETY: The guard holds the lock, and `&mut self` makes this the only
+    // reference handed out by it.
+    self.lock.data.with_mut(|data| unsafe { &mut *data })
+  }
+}
+
+impl<T: ?Sized> Drop for SpinLockGuard<'_, T> {
+  #[inline]
+  fn drop(&mut self) {
+    self.lock.locked.store(false, Release);
+  }
+}
+
+impl<T: ?Sized + core::fmt::Debug> core::fmt::Debug for SpinLockGuard<'_, T> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    core::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + core::fmt::Display> core::fmt::Display
+  for SpinLockGuard<'_, T>
+{
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    core::fmt::Display::fmt(&**self, f)
+  }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+  use crate::sync::Arc;
+
+  use std::thread;
+
+  #[test]
+  fn lock_unlock() {
+    let lock = SpinLock::new(vec![1]);
+
+    lock.lock().push(2);
+    assert!(!lock.is_locked());
+
+    let guard = lock.lock();
+    assert!(lock.is_locked());
+    assert!(lock.try_lock().is_none());
+    assert_eq!(*guard, [1, 2]);
+  }
+
+  #[test]
+  fn contended_increments() {
+    for lock in [SpinLock::new(0), SpinLock::with_backoff(0)] {
+      let lock = Arc::new(lock);
+      let handles: Vec<_> = (0..4)
+        .map(|_| {
+          let lock = Arc::clone(&lock);
+          thread::spawn(move || {
+            for _ in 0..1000 {
+              *lock.lock() += 1;
+            }
+          })
+        })
+        .collect();
+
+      for handle in handles {
+        handle.join().unwrap();
+      }
+      assert_eq!(*lock.lock(), 4000);
+    }
+  }
+
+  #[test]
+  fn guard_unlocks_on_other_thread() {
+    let lock = SpinLock::new(1);
+    let mut guard = lock.lock();
+
+    thread::scope(|s| {
+      s.spawn(move || {
+        *guard += 1;
+        drop(guard);
+      });
+    });
+
+    assert!(!lock.is_locked());
+    assert_eq!(lock.into_inner(), 2);
+  }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+  use super::*;
+  use crate::sync::Arc;
+
+  use ::loom::thread;
+
+  #[test]
+  fn loom_spin_increments() {
+    ::loom::model(|| {
+      let lock = Arc::new(SpinLock::with_backoff(0));
+
+      let other = Arc::clone(&lock);
+      let t = thread::spawn(move || *other.lock() += 1);
+      *lock.lock() += 1;
+      t.join().unwrap();
+
+      assert_eq!(*lock.lock(), 2);
+    });
+  }
+}
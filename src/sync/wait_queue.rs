@@ -0,0 +1,259 @@
+//! A FIFO queue of parked threads: the blocking half of the locks in [`sync`](crate::sync).
+//!
+//! A lock keeps its fast path in its own atomic state and only falls back to a `WaitQueue` once
+//! it has to sleep. [`park`](WaitQueue::park) re-checks the lock state through a `validate`
+//! callback while the queue is locked, and [`unpark_one`](WaitQueue::unpark_one) updates the
+//! lock state through a callback under the same queue lock, so a wakeup can never slip in
+//! between a thread deciding to sleep and it being enqueued.
+
+use super::Arc;
+use crate::loom::atomic::AtomicBool;
+use crate::loom::atomic::Ordering::{Acquire, Relaxed, Release};
+use crate::loom::cell::UnsafeCell;
+use crate::loom::{hint, thread};
+
+/// How many times a lock spins on a held lock before parking.
+#[cfg(not(loom))]
+pub(crate) const SPIN_LIMIT: u32 = 100;
+#[cfg(loom)]
+pub(crate) const SPIN_LIMIT: u32 = 1;
+
+/// The outcome of [`WaitQueue::park`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ParkResult {
+  /// Another thread woke us up through `unpark_one` or `unpark_all`.
+  Unparked,
+  /// `validate` returned `false`, so the thread never went to sleep.
+  Invalid,
+  /// The deadline passed before anyone woke us up.
+  TimedOut,
+}
+
+/// A parked thread and the flag that tells it apart from a spurious wakeup.
+struct Waiter {
+  thread: thread::Thread,
+  notified: AtomicBool,
+}
+
+/// A queue of threads waiting for some condition.
+pub(crate) struct WaitQueue {
+  /// A spin lock around `waiters`. It is only ever held for a few
+  /// instructions and never while a thread is parked.
+  locked: AtomicBool,
+  waiters: UnsafeCell<std::collections::VecDeque<Arc<Waiter>>>,
+}
+
+unsafe impl Send for WaitQueue {}
+unsafe impl Sync for WaitQueue {}
+
+impl WaitQueue {
+  pub(crate) fn new() -> WaitQueue {
+    WaitQueue {
+      locked: AtomicBool::new(false),
+      waiters: UnsafeCell::new(std::collections::VecDeque::new()),
+    }
+  }
+
+  /// Runs `f` with the waiter list locked.
+  fn with_waiters<R>(
+    &self,
+    f: impl FnOnce(&mut std::collections::VecDeque<Arc<Waiter>>) -> R,
+  ) -> R {
+    while self
+      .locked
+      .compare_exchange_weak(false, true, Acquire, Relaxed)
+      .is_err()
+    {
+      hint::spin_loop();
+    }
+    // SAFETY: The spin lock gives us exclusive access to the list.
+    let result = self.waiters.with_mut(|waiters| unsafe { f(&mut *waiters) });
+    self.locked.store(false, Release);
+    result
+  }
+
+  /// Parks the current thread until it is woken up or `deadline` passes.
+  ///
+  /// `validate` runs with the queue locked; if it returns `false` the thread
+  /// is not enqueued at all. `before_sleep` runs once the thread is enqueued
+  /// but before it goes to sleep, which is where a condition variable
+  /// releases its mutex. If the deadline passes, `timed_out` runs with the
+  /// queue locked once the thread is taken off it, and is told whether other
+  /// threads are still waiting.
+  pub(crate) fn park(
+    &self,
+    validate: impl FnOnce() -> bool,
+    before_sleep: impl FnOnce(),
+    timed_out: impl FnOnce(bool),
+    deadline: Option<std::time::Instant>,
+  ) -> ParkResult {
+    let waiter = Arc::new(Waiter {
+      thread: thread::current(),
+      notified: AtomicBool::new(false),
+    });
+
+    let enqueued = self.with_waiters(|waiters| {
+      if !validate() {
+        return false;
+      }
+      waiters.push_back(Arc::clone(&waiter));
+      true
+    });
+    if !enqueued {
+      return ParkResult::Invalid;
+    }
+
+    before_sleep();
+
+    loop {
+      if waiter.notified.load(Acquire) {
+        return ParkResult::Unparked;
+      }
+
+      match deadline {
+        None => thread::park(),
+        Some(deadline) => {
+          let now = std::time::Instant::now();
+          if now < deadline {
+            thread::park_timeout(deadline - now);
+            continue;
+          }
+
+          // Take ourselves off the queue, unless an unparker already did.
+          let removed = self.with_waiters(|waiters| {
+            match waiters.iter().position(|w| Arc::ptr_eq(w, &waiter)) {
+              Some(i) => {
+                waiters.remove(i);
+                timed_out(!waiters.is_empty());
+                true
+              }
+              None => false,
+            }
+          });
+          if removed {
+            return ParkResult::TimedOut;
+          }
+          // We were dequeued, so the notification is on its way.
+          while !waiter.notified.load(Acquire) {
+            thread::park();
+          }
+          return ParkResult::Unparked;
+        }
+      }
+    }
+  }
+
+  /// Wakes up the thread that has been waiting the longest, if any.
+  ///
+  /// `callback` runs with the queue locked and is told whether a thread was
+  /// dequeued and whether more are still waiting, so the caller can update
+  /// its own state before anyone else observes the queue.
+  pub(crate) fn unpark_one(&self, callback: impl FnOnce(bool, bool)) -> bool {
+    let waiter = self.with_waiters(|waiters| {
+      let waiter = waiters.pop_front();
+      callback(waiter.is_some(), !waiters.is_empty());
+      waiter
+    });
+
+    match waiter {
+      Some(waiter) => {
+        waiter.notified.store(true, Release);
+        waiter.thread.unpark();
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Wakes up every waiting thread and returns how many there were.
+  ///
+  /// Like in [`unpark_one`](WaitQueue::unpark_one), `callback` runs with the
+  /// queue locked, before any thread is woken.
+  pub(crate) fn unpark_all(&self, callback: impl FnOnce()) -> usize {
+    let waiters = self.with_waiters(|waiters| {
+      callback();
+      std::mem::take(waiters)
+    });
+
+    let count = waiters.len();
+    for waiter in waiters {
+      waiter.notified.store(true, Release);
+      waiter.thread.unpark();
+    }
+    count
+  }
+
+  /// Returns `true` if no thread is waiting.
+  #[cfg(all(test, not(loom)))]
+  pub(crate) fn is_empty(&self) -> bool {
+    self.with_waiters(|waiters| waiters.is_empty())
+  }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+
+  use std::time::{Duration, Instant};
+
+  #[test]
+  fn invalid_never_sleeps() {
+    let queue = WaitQueue::new();
+    let result =
+      queue.park(|| false, || panic!("must not sleep"), |_| {}, None);
+
+    assert_eq!(result, ParkResult::Invalid);
+    assert!(queue.is_empty());
+  }
+
+  #[test]
+  fn times_out_and_dequeues() {
+    let queue = WaitQueue::new();
+    let deadline = Instant::now() + Duration::from_millis(10);
+
+    assert_eq!(
+      queue.park(
+        || true,
+        || {},
+        |have_more| assert!(!have_more),
+        Some(deadline)
+      ),
+      ParkResult::TimedOut
+    );
+    assert!(queue.is_empty());
+    assert!(!queue.unpark_one(|unparked, _| assert!(!unparked)));
+  }
+
+  #[test]
+  fn unpark_one_wakes_in_order() {
+    let queue = Arc::new(WaitQueue::new());
+    let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+    let mut handles = Vec::new();
+    for i in 0..3 {
+      let q = Arc::clone(&queue);
+      let o = Arc::clone(&order);
+      handles.push(std::thread::spawn(move || {
+        assert_eq!(q.park(|| true, || {}, |_| {}, None), ParkResult::Unparked);
+        o.lock().unwrap().push(i);
+      }));
+      // Wait until the thread is enqueued so the order is deterministic.
+      while queue.with_waiters(|waiters| waiters.len()) <= i {
+        std::thread::yield_now();
+      }
+    }
+
+    for _ in 0..3 {
+      let before = order.lock().unwrap().len();
+      assert!(queue.unpark_one(|unparked, _| assert!(unparked)));
+      while order.lock().unwrap().len() == before {
+        std::thread::yield_now();
+      }
+    }
+
+    for handle in handles {
+      handle.join().unwrap();
+    }
+    assert_eq!(*order.lock().unwrap(), [0, 1, 2]);
+  }
+}
@@ -0,0 +1,415 @@
+//! A reference-counting pointer that is one word wide, even for unsized values.
+//!
+//! A pointer to a trait object or a slice is a fat pointer: next to the address it carries the
+//! vtable or the length, so an [`Rc<dyn Trait>`][Rc] takes two words. [`ThinRc<T>`][ThinRc] keeps
+//! that metadata inside the allocation instead, next to the reference count, and the handle is a
+//! single pointer to it. Collections of many shared trait objects get half as big, at the cost of
+//! one extra load from the allocation to find the value.
+//!
+//! Like `LiteRc`, a `ThinRc` has no weak pointers.
+//!
+//! Unsized values come from a [`Box`] or a [`Boxed`] that has been coerced already, or from an
+//! `Rc` that is uniquely owned:
+//!
+//! ```
+//! use pointer::{Rc, ThinRc};
+//! use std::convert::TryFrom;
+//! use std::fmt::Display;
+//!
+//! let shown: ThinRc<dyn Display> = ThinRc::from(Box::new(5) as Box<dyn Display>);
+//! assert_eq!(shown.to_string(), "5");
+//! assert_eq!(std::mem::size_of_val(&shown), std::mem::size_of::<usize>());
+//!
+//! let rc: Rc<[u8]> = Rc::from(pointer::Boxed::from(vec![1, 2, 3]));
+//! let thin = ThinRc::try_from(rc).unwrap();
+//! assert_eq!(*thin, [1, 2, 3]);
+//! ```
+//!
+//! [`Boxed`]: crate::Boxed
+
+use crate::cell::Cell;
+use crate::{Boxed, Rc};
+
+/// The start of every `ThinRc` allocation. The value follows it.
+#[repr(C)]
+struct ThinHeader<T: ?Sized> {
+  count: Cell<usize>,
+  /// Points to the value right after this header, with its metadata.
+  value: *mut T,
+}
+
+/// A single-threaded reference-counting pointer that stores the metadata of
+/// an unsized `T` in its allocation, so that the pointer itself is thin.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct ThinRc<T: ?Sized> {
+  ptr: std::ptr::NonNull<ThinHeader<T>>,
+  phantom: std::marker::PhantomData<T>,
+}
+
+impl<T> ThinRc<T> {
+  /// Constructs a new `ThinRc<T>`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ThinRc;
+  ///
+  /// let five = ThinRc::new(5);
+  /// assert_eq!(*five, 5);
+  /// ```
+  pub fn new(value: T) -> ThinRc<T> {
+    let dangling = std::ptr::NonNull::<T>::dangling().as_ptr();
+    // SAFETY: The allocation has room for a `T` at `value`, which is written
+    // before the `ThinRc` is handed out.
+    unsafe {
+      let thin = ThinRc::allocate_for(std::alloc::Layout::new::<T>(), dangling);
+      (*thin.ptr.as_ptr()).value.write(value);
+      thin
+    }
+  }
+}
+
+impl<T: ?Sized> ThinRc<T> {
+  /// Allocates a header and room for a value of `value_layout`, taking the
+  /// metadata from `template`. The count starts at one.
+  ///
+  /// # Safety
+  ///
+  /// The value must be written before the `ThinRc` is used or dropped, and
+  /// `value_layout` must be its layout.
+  unsafe fn allocate_for(
+    value_layout: std::alloc::Layout,
+    template: *mut T,
+  ) -> ThinRc<T> {
+    let (layout, offset) = layout_for::<T>(value_layout);
+    // A header is never zero-sized.
+    let mem = std::alloc::alloc(layout);
+    if mem.is_null() {
+      std::alloc::handle_alloc_error(layout);
+    }
+    let header = mem as *mut ThinHeader<T>;
+    header.write(ThinHeader {
+      count: Cell::new(1),
+      value: crate::boxed::set_data_ptr(template, mem.add(offset)),
+    });
+    ThinRc {
+      ptr: std::ptr::NonNull::new_unchecked(header),
+      phantom: std::marker::PhantomData,
+    }
+  }
+
+  /// Moves the value behind `src` into a new `ThinRc`, bit for bit. The
+  /// caller must free the memory behind `src` without dropping the value.
+  unsafe fn copy_from(src: *const T) -> ThinRc<T> {
+    let value_layout = std::alloc::Layout::for_value(&*src);
+    let thin = ThinRc::allocate_for(value_layout, src as *mut T);
+    std::ptr::copy_nonoverlapping(
+      src.cast::<u8>(),
+      (*thin.ptr.as_ptr()).value.cast::<u8>(),
+      value_layout.size(),
+    );
+    thin
+  }
+
+  /// Gets the number of pointers to this allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ThinRc;
+  ///
+  /// let five = ThinRc::new(5);
+  /// let _also_five = ThinRc::clone(&five);
+  ///
+  /// assert_eq!(2, ThinRc::strong_count(&five));
+  /// ```
+  #[inline]
+  pub fn strong_count(this: &Self) -> usize {
+    this.header().count.get()
+  }
+
+  /// Returns a mutable reference into the given `ThinRc`, if there are no
+  /// other pointers to the same allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ThinRc;
+  ///
+  /// let mut x = ThinRc::new(3);
+  /// *ThinRc::get_mut(&mut x).unwrap() = 4;
+  /// assert_eq!(*x, 4);
+  ///
+  /// let _y = ThinRc::clone(&x);
+  /// assert!(ThinRc::get_mut(&mut x).is_none());
+  /// ```
+  pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+    if ThinRc::strong_count(this) == 1 {
+      // SAFETY: No other pointer can reach the value.
+      Some(unsafe { &mut *this.header().value })
+    } else {
+      None
+    }
+  }
+
+  /// Returns `true` if the two `ThinRc`s point to the same allocation.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ThinRc;
+  ///
+  /// let five = ThinRc::new(5);
+  /// let same_five = ThinRc::clone(&five);
+  /// let other_five = ThinRc::new(5);
+  ///
+  /// assert!(ThinRc::ptr_eq(&five, &same_five));
+  /// assert!(!ThinRc::ptr_eq(&five, &other_five));
+  /// ```
+  #[inline]
+  pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+    this.ptr == other.ptr
+  }
+
+  #[inline]
+  fn header(&self) -> &ThinHeader<T> {
+    // SAFETY: While this `ThinRc` is alive the allocation is valid.
+    unsafe { self.ptr.as_ref() }
+  }
+}
+
+impl<T: ?Sized> Clone for ThinRc<T> {
+  /// Makes a clone of the `ThinRc` pointer, increasing the count.
+  #[inline]
+  fn clone(&self) -> ThinRc<T> {
+    let count = &self.header().count;
+    // Abort on overflow instead of freeing a value that is still in use.
+    if count.get() == usize::MAX {
+      crate::alloc::abort_on_overflow();
+    }
+    count.set(count.get() + 1);
+    ThinRc {
+      ptr: self.ptr,
+      phantom: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<T: ?Sized> std::ops::Deref for ThinRc<T> {
+  type Target = T;
+
+  #[inline]
+  fn deref(&self) -> &T {
+    // SAFETY: The value lives as long as the allocation, and is only
+    // mutated through `get_mut`, which requires a unique pointer.
+    unsafe { &*self.header().value }
+  }
+}
+
+impl<T: ?Sized> Drop for ThinRc<T> {
+  /// Drops the `ThinRc`, freeing the value with the last pointer.
+  fn drop(&mut self) {
+    let count = &self.header().count;
+    count.set(count.get() - 1);
+    if count.get() == 0 {
+      let value = self.header().value;
+      // SAFETY: This was the last pointer. The layout is found the same way
+      // `allocate_for` computed it.
+      unsafe {
+        let (layout, _) =
+          layout_for::<T>(std::alloc::Layout::for_value(&*value));
+        std::ptr::drop_in_place(value);
+        std::alloc::dealloc(self.ptr.as_ptr().cast::<u8>(), layout);
+      }
+    }
+  }
+}
+
+impl<T: Default> Default for ThinRc<T> {
+  fn default() -> ThinRc<T> {
+    ThinRc::new(Default::default())
+  }
+}
+
+impl<T: ?Sized> From<Box<T>> for ThinRc<T> {
+  /// Moves a boxed value, which may be unsized, into a new `ThinRc<T>`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::ThinRc;
+  ///
+  /// let slice: ThinRc<[i32]> = ThinRc::from(vec![1, 2].into_boxed_slice());
+  /// assert_eq!(*slice, [1, 2]);
+  /// ```
+  fn from(b: Box<T>) -> ThinRc<T> {
+    ThinRc::from(Boxed::from(b))
+  }
+}
+
+impl<T: ?Sized> From<Boxed<T>> for ThinRc<T> {
+  fn from(b: Boxed<T>) -> ThinRc<T> {
+    let value_layout = std::alloc::Layout::for_value(&*b);
+    let bptr = Boxed::into_raw(b);
+    // SAFETY: The value is moved out of the box, whose memory is then freed
+    // without dropping it.
+    unsafe {
+      let thin = ThinRc::copy_from(bptr);
+      if value_layout.size() != 0 {
+        std::alloc::dealloc(bptr.cast::<u8>(), value_layout);
+      }
+      thin
+    }
+  }
+}
+
+impl<T: ?Sized> std::convert::TryFrom<Rc<T>> for ThinRc<T> {
+  type Error = Rc<T>;
+
+  /// Moves the value of a uniquely owned [`Rc`] into a `ThinRc`.
+  ///
+  /// Fails, handing the `Rc` back, if it has other strong or weak pointers.
+  fn try_from(mut rc: Rc<T>) -> Result<ThinRc<T>, Rc<T>> {
+    if Rc::get_mut(&mut rc).is_none() {
+      return Err(rc);
+    }
+    // SAFETY: The `Rc` was the only pointer to its value, which is moved out
+    // before its allocation is freed.
+    unsafe {
+      let thin = ThinRc::copy_from(Rc::as_ptr(&rc));
+      Rc::dealloc_unique(rc);
+      Ok(thin)
+    }
+  }
+}
+
+impl<T: ?Sized> std::convert::TryFrom<ThinRc<T>> for Rc<T> {
+  type Error = ThinRc<T>;
+
+  /// Moves the value of a uniquely owned `ThinRc` into an [`Rc`].
+  ///
+  /// Fails, handing the `ThinRc` back, if it has been cloned.
+  fn try_from(thin: ThinRc<T>) -> Result<Rc<T>, ThinRc<T>> {
+    if ThinRc::strong_count(&thin) != 1 {
+      return Err(thin);
+    }
+    let value = thin.header().value;
+    let header = thin.ptr;
+    std::mem::forget(thin);
+    // SAFETY: The value is moved bit for bit into a box of its own layout,
+    // then the `ThinRc` allocation is freed without dropping it.
+    unsafe {
+      let value_layout = std::alloc::Layout::for_value(&*value);
+      let mem = if value_layout.size() == 0 {
+        std::ptr::without_provenance_mut(value_layout.align())
+      } else {
+        let mem = std::alloc::alloc(value_layout);
+        if mem.is_null() {
+          std::alloc::handle_alloc_error(value_layout);
+        }
+        mem
+      };
+      std::ptr::copy_nonoverlapping(
+        value.cast::<u8>(),
+        mem,
+        value_layout.size(),
+      );
+      let boxed = Boxed::from_raw(crate::boxed::set_data_ptr(value, mem));
+      std::alloc::dealloc(
+        header.as_ptr().cast::<u8>(),
+        layout_for::<T>(value_layout).0,
+      );
+      Ok(Rc::from(boxed))
+    }
+  }
+}
+
+impl<T: ?Sized + std::fmt::Display> std::fmt::Display for ThinRc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Display::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for ThinRc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Debug::fmt(&**self, f)
+  }
+}
+
+impl<T: ?Sized> std::fmt::Pointer for ThinRc<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    std::fmt::Pointer::fmt(&(&**self as *const T), f)
+  }
+}
+
+/// Returns the layout of a `ThinRc` allocation holding a value of
+/// `value_layout`, and the offset of the value in it.
+fn layout_for<T: ?Sized>(
+  value_layout: std::alloc::Layout,
+) -> (std::alloc::Layout, usize) {
+  let (layout, offset) = std::alloc::Layout::new::<ThinHeader<T>>()
+    .extend(value_layout)
+    .expect("ThinRc layout overflow");
+  (layout.pad_to_align(), offset)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::convert::TryFrom;
+
+  trait Shape {
+    fn area(&self) -> u32;
+  }
+
+  struct Square(u32, std::rc::Rc<Cell<u32>>);
+
+  impl Shape for Square {
+    fn area(&self) -> u32 {
+      self.0 * self.0
+    }
+  }
+
+  impl Drop for Square {
+    fn drop(&mut self) {
+      self.1.set(self.1.get() + 1);
+    }
+  }
+
+  #[test]
+  fn thin_trait_objects() {
+    assert_eq!(
+      std::mem::size_of::<ThinRc<dyn Shape>>(),
+      std::mem::size_of::<usize>()
+    );
+
+    let drops = std::rc::Rc::new(Cell::new(0));
+    let shape: ThinRc<dyn Shape> =
+      ThinRc::from(Box::new(Square(3, drops.clone())) as Box<dyn Shape>);
+    let other = ThinRc::clone(&shape);
+    assert_eq!(other.area(), 9);
+
+    drop(shape);
+    assert_eq!(drops.get(), 0);
+    drop(other);
+    assert_eq!(drops.get(), 1);
+  }
+
+  #[test]
+  fn converts_with_rc_when_unique() {
+    let rc: Rc<str> = Rc::from(Boxed::from(String::from("thin")));
+    let shared = Rc::clone(&rc);
+    let rc = ThinRc::try_from(rc).unwrap_err();
+    drop(shared);
+
+    let thin = ThinRc::try_from(rc).unwrap();
+    assert_eq!(&*thin, "thin");
+    let rc: Rc<str> = Rc::try_from(thin).unwrap();
+    assert_eq!(&*rc, "thin");
+
+    let empty: ThinRc<[()]> = ThinRc::from(Vec::new().into_boxed_slice());
+    let empty: Rc<[()]> = Rc::try_from(empty).unwrap();
+    assert!(empty.is_empty());
+  }
+}
@@ -0,0 +1,402 @@
+//! Cells whose writes are staged in a [`Transaction`] and committed together: [`TxCell<T>`][TxCell].
+//!
+//! An invariant that spans several fields can't be kept with a `Cell` or `RefCell` per field: an
+//! early return or a panic half-way through an update leaves some fields written and others not.
+//! [`Transaction::run`] gives its closure a [`Transaction`] to write `TxCell`s through. The writes
+//! are staged, and only the transaction sees them. If the closure returns `Ok`, they are all
+//! committed before `run` returns; if it returns `Err` or panics, they are all dropped. Reading a
+//! `TxCell` outside the transaction always gives the last committed value.
+//!
+//! ```
+//! use pointer::{Transaction, TxCell};
+//!
+//! struct Account {
+//!   balance: TxCell<u32>,
+//!   withdrawn: TxCell<u32>,
+//! }
+//!
+//! let account = Account {
+//!   balance: TxCell::new(100),
+//!   withdrawn: TxCell::new(0),
+//! };
+//!
+//! let withdraw = |amount: u32| {
+//!   Transaction::run(|tx| {
+//!     tx.update(&account.withdrawn, |w| *w += amount);
+//!     let balance = tx.get(&account.balance);
+//!     let left = balance.checked_sub(amount).ok_or("insufficient funds")?;
+//!     tx.set(&account.balance, left);
+//!     Ok(left)
+//!   })
+//! };
+//!
+//! assert_eq!(withdraw(30), Ok(70));
+//! assert_eq!(withdraw(80), Err("insufficient funds"));
+//!
+//! // The failed withdrawal left no trace.
+//! assert_eq!(account.balance.get(), 70);
+//! assert_eq!(account.withdrawn.get(), 30);
+//! ```
+//!
+//! A transaction run inside another one is independent of it: it commits or rolls back on its
+//! own. A cell is staged in only one transaction at a time.
+
+use crate::{Cell, RefCell};
+
+/// A value whose writes go through a [`Transaction`].
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct TxCell<T> {
+  value: RefCell<T>,
+  staged: RefCell<Option<T>>,
+  /// The address of the transaction the cell is staged in, or 0.
+  owner: Cell<usize>,
+}
+
+/// A cell with writes staged in a transaction.
+trait Staged {
+  fn commit(&self);
+  fn rollback(&self);
+}
+
+impl<T> Staged for TxCell<T> {
+  fn commit(&self) {
+    if let Some(value) = self.staged.borrow_mut().take() {
+      *self.value.borrow_mut() = value;
+    }
+    self.owner.set(0);
+  }
+
+  fn rollback(&self) {
+    let staged = self.staged.borrow_mut().take();
+    self.owner.set(0);
+    drop(staged);
+  }
+}
+
+impl<T> TxCell<T> {
+  /// Creates a new `TxCell` containing `value`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TxCell;
+  ///
+  /// let cell = TxCell::new(5);
+  /// ```
+  pub fn new(value: T) -> TxCell<T> {
+    TxCell {
+      value: RefCell::new(value),
+      staged: RefCell::new(None),
+      owner: Cell::new(0),
+    }
+  }
+
+  /// Calls `f` with a reference to the committed value, and returns what it
+  /// returns.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TxCell;
+  ///
+  /// let cell = TxCell::new(vec![1, 2]);
+  /// assert_eq!(cell.with(|v| v.len()), 2);
+  /// ```
+  pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+    f(&self.value.borrow())
+  }
+
+  /// Returns a mutable reference to the committed value.
+  ///
+  /// The `&mut self` means that no transaction has writes staged for the
+  /// cell.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TxCell;
+  ///
+  /// let mut cell = TxCell::new(5);
+  /// *cell.get_mut() += 1;
+  /// assert_eq!(cell.get(), 6);
+  /// ```
+  pub fn get_mut(&mut self) -> &mut T {
+    self.value.get_mut()
+  }
+
+  /// Consumes the cell, returning the committed value.
+  pub fn into_inner(self) -> T {
+    self.value.into_inner()
+  }
+}
+
+impl<T: Clone> TxCell<T> {
+  /// Returns a clone of the committed value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::TxCell;
+  ///
+  /// let cell = TxCell::new(5);
+  /// assert_eq!(cell.get(), 5);
+  /// ```
+  pub fn get(&self) -> T {
+    self.value.borrow().clone()
+  }
+}
+
+impl<T: Default> Default for TxCell<T> {
+  fn default() -> TxCell<T> {
+    TxCell::new(T::default())
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for TxCell<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.value.try_borrow() {
+      Ok(value) => f
+        .debug_struct("TxCell")
+        .field("value", &*value)
+        .field("staged", &(self.owner.get() != 0))
+        .finish(),
+      Err(_) => f.write_str("TxCell { <borrowed> }"),
+    }
+  }
+}
+
+/// The writes to [`TxCell`]s that [`Transaction::run`] commits together.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct Transaction<'a> {
+  staged: RefCell<Vec<&'a dyn Staged>>,
+}
+
+impl<'a> Transaction<'a> {
+  /// Runs `f` with a new transaction, commits its writes if `f` returns
+  /// `Ok`, and drops them if it returns `Err` or panics.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Transaction, TxCell};
+  ///
+  /// let cell = TxCell::new(1);
+  ///
+  /// let failed: Result<(), &str> = Transaction::run(|tx| {
+  ///   tx.set(&cell, 2);
+  ///   Err("rolled back")
+  /// });
+  /// assert!(failed.is_err());
+  /// assert_eq!(cell.get(), 1);
+  ///
+  /// let committed: Result<(), ()> = Transaction::run(|tx| {
+  ///   tx.set(&cell, 3);
+  ///   Ok(())
+  /// });
+  /// assert!(committed.is_ok());
+  /// assert_eq!(cell.get(), 3);
+  /// ```
+  pub fn run<R, E>(
+    f: impl FnOnce(&Transaction<'a>) -> Result<R, E>,
+  ) -> Result<R, E> {
+    /// Rolls the transaction back unless it was committed, even if `f`
+    /// panics.
+    struct Rollback<'t, 'a>(&'t Transaction<'a>);
+
+    impl Drop for Rollback<'_, '_> {
+      fn drop(&mut self) {
+        for cell in self.0.staged.borrow_mut().drain(..).rev() {
+          cell.rollback();
+        }
+      }
+    }
+
+    let tx = Transaction {
+      staged: RefCell::new(Vec::new()),
+    };
+    let rollback = Rollback(&tx);
+    let result = f(&tx);
+    if result.is_ok() {
+      let staged = std::mem::take(&mut *tx.staged.borrow_mut());
+      for cell in staged {
+        cell.commit();
+      }
+    }
+    drop(rollback);
+    result
+  }
+
+  /// Stages `value` for `cell`, or replaces the value staged for it.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `cell` has writes staged in another transaction.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Transaction, TxCell};
+  ///
+  /// let cell = TxCell::new(1);
+  /// let _ = Transaction::run(|tx| {
+  ///   tx.set(&cell, 2);
+  ///   assert_eq!(tx.get(&cell), 2);
+  ///   assert_eq!(cell.get(), 1);
+  ///   Ok::<_, ()>(())
+  /// });
+  /// assert_eq!(cell.get(), 2);
+  /// ```
+  pub fn set<T>(&self, cell: &'a TxCell<T>, value: T) {
+    self.enlist(cell);
+    *cell.staged.borrow_mut() = Some(value);
+  }
+
+  /// Calls `f` with a reference to the value of `cell` in this transaction:
+  /// the value staged for it, or else the committed value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Transaction, TxCell};
+  ///
+  /// let cell = TxCell::new(vec![1]);
+  /// let len = Transaction::run(|tx| Ok::<_, ()>(tx.with(&cell, |v| v.len())));
+  /// assert_eq!(len, Ok(1));
+  /// ```
+  pub fn with<T, R>(&self, cell: &'a TxCell<T>, f: impl FnOnce(&T) -> R) -> R {
+    let id = std::ptr::from_ref(self).addr();
+    if cell.owner.get() == id {
+      if let Some(staged) = &*cell.staged.borrow() {
+        return f(staged);
+      }
+    }
+    f(&cell.value.borrow())
+  }
+
+  /// Returns a clone of the value of `cell` in this transaction: the value
+  /// staged for it, or else the committed value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Transaction, TxCell};
+  ///
+  /// let cell = TxCell::new(1);
+  /// let doubled = Transaction::run(|tx| {
+  ///   tx.set(&cell, tx.get(&cell) * 2);
+  ///   Ok::<_, ()>(tx.get(&cell))
+  /// });
+  /// assert_eq!(doubled, Ok(2));
+  /// ```
+  pub fn get<T: Clone>(&self, cell: &'a TxCell<T>) -> T {
+    self.with(cell, T::clone)
+  }
+
+  /// Changes the value of `cell` in this transaction with `f`, staging a
+  /// clone of the committed value first if nothing is staged for it.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `cell` has writes staged in another transaction.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Transaction, TxCell};
+  ///
+  /// let log = TxCell::new(vec!["opened"]);
+  /// let _ = Transaction::run(|tx| {
+  ///   tx.update(&log, |log| log.push("closed"));
+  ///   Ok::<_, ()>(())
+  /// });
+  /// assert_eq!(log.get(), ["opened", "closed"]);
+  /// ```
+  pub fn update<T: Clone>(&self, cell: &'a TxCell<T>, f: impl FnOnce(&mut T)) {
+    self.enlist(cell);
+    let mut staged = cell.staged.borrow_mut();
+    f(staged.get_or_insert_with(|| cell.value.borrow().clone()));
+  }
+
+  /// Adds `cell` to this transaction, if it isn't in it yet.
+  fn enlist<T>(&self, cell: &'a TxCell<T>) {
+    let id = std::ptr::from_ref(self).addr();
+    match cell.owner.get() {
+      0 => {
+        cell.owner.set(id);
+        self.staged.borrow_mut().push(cell);
+      }
+      owner if owner == id => {}
+      _ => panic!("the `TxCell` has writes staged in another transaction"),
+    }
+  }
+}
+
+impl std::fmt::Debug for Transaction<'_> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Transaction")
+      .field("staged", &self.staged.borrow().len())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn commits_all_or_nothing() {
+    let (a, b) = (TxCell::new(1), TxCell::new(String::from("one")));
+
+    let result: Result<(), &str> = Transaction::run(|tx| {
+      tx.set(&a, 2);
+      tx.update(&b, |b| b.push('!'));
+      assert_eq!((a.get(), b.get()), (1, String::from("one")));
+      Err("no")
+    });
+    assert!(result.is_err());
+    assert_eq!((a.get(), b.get()), (1, String::from("one")));
+
+    let result: Result<(), ()> = Transaction::run(|tx| {
+      tx.set(&a, 2);
+      tx.update(&b, |b| b.push('!'));
+      Ok(())
+    });
+    assert!(result.is_ok());
+    assert_eq!((a.get(), b.get()), (2, String::from("one!")));
+  }
+
+  #[test]
+  fn a_panic_rolls_back() {
+    let cell = TxCell::new(1);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      let _ = Transaction::run(|tx| -> Result<(), ()> {
+        tx.set(&cell, 2);
+        panic!("in a transaction");
+      });
+    }));
+    assert!(result.is_err());
+    assert_eq!(cell.get(), 1);
+
+    // The cell isn't left staged.
+    let _ = Transaction::run(|tx| {
+      tx.set(&cell, 3);
+      Ok::<_, ()>(())
+    });
+    assert_eq!(cell.into_inner(), 3);
+  }
+
+  #[test]
+  #[should_panic(expected = "staged in another transaction")]
+  fn a_cell_is_staged_in_one_transaction_at_a_time() {
+    let cell = TxCell::new(1);
+    let _ = Transaction::run(|outer| {
+      outer.set(&cell, 2);
+      Transaction::run(|inner| {
+        inner.set(&cell, 3);
+        Ok::<_, ()>(())
+      })
+    });
+  }
+}
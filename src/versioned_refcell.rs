@@ -0,0 +1,349 @@
+//! A [`RefCell`] that hands out cheap [`Snapshot`]s of its value: [`VersionedRefCell<T>`][VersionedRefCell].
+//!
+//! The value of a `VersionedRefCell` lives in an [`Rc`], so a [`snapshot`] is one more reference to
+//! it and copies nothing. The snapshot stays readable, and keeps showing the value it was taken of,
+//! while the cell goes on changing: a write to a value that snapshots still share clones it first,
+//! with [`Rc::make_mut`], and only the cell sees the copy. A value no snapshot holds is mutated in
+//! place. Each mutable borrow and each [`set`] starts a new [`version`], which the snapshots taken
+//! after it carry.
+//!
+//! ```
+//! use pointer::{Snapshot, VersionedRefCell};
+//!
+//! let state = VersionedRefCell::new(vec!["boot"]);
+//! let before = state.snapshot();
+//!
+//! state.borrow_mut().push("login");
+//! let after = state.snapshot();
+//!
+//! assert_eq!(*before, ["boot"]);
+//! assert_eq!(*after, ["boot", "login"]);
+//! assert!(Snapshot::version(&after) > Snapshot::version(&before));
+//! ```
+//!
+//! [`Rc`]: crate::Rc
+//! [`Rc::make_mut`]: crate::Rc::make_mut
+//! [`snapshot`]: VersionedRefCell::snapshot
+//! [`set`]: VersionedRefCell::set
+//! [`version`]: VersionedRefCell::version
+
+use crate::{Cell, Rc, Ref, RefCell, RefMut};
+
+/// A mutable memory location with dynamically checked borrow rules, whose
+/// value can be snapshotted without copying it.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct VersionedRefCell<T> {
+  value: RefCell<Rc<T>>,
+  version: Cell<u64>,
+}
+
+impl<T> VersionedRefCell<T> {
+  /// Creates a new `VersionedRefCell` containing `value`, at version 0.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::VersionedRefCell;
+  ///
+  /// let cell = VersionedRefCell::new(5);
+  /// assert_eq!(cell.version(), 0);
+  /// ```
+  pub fn new(value: T) -> VersionedRefCell<T> {
+    VersionedRefCell {
+      value: RefCell::new(Rc::new(value)),
+      version: Cell::new(0),
+    }
+  }
+
+  /// Immutably borrows the current value.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently mutably borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::VersionedRefCell;
+  ///
+  /// let cell = VersionedRefCell::new(5);
+  /// assert_eq!(*cell.borrow(), 5);
+  /// ```
+  pub fn borrow(&self) -> VersionedRef<'_, T> {
+    VersionedRef {
+      guard: self.value.borrow(),
+    }
+  }
+
+  /// Takes a snapshot of the current value, which keeps showing it after
+  /// the cell changes.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently mutably borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::VersionedRefCell;
+  ///
+  /// let cell = VersionedRefCell::new(1);
+  /// let snapshot = cell.snapshot();
+  /// cell.set(2);
+  ///
+  /// assert_eq!(*snapshot, 1);
+  /// assert_eq!(*cell.borrow(), 2);
+  /// ```
+  pub fn snapshot(&self) -> Snapshot<T> {
+    Snapshot {
+      value: Rc::clone(&self.value.borrow()),
+      version: self.version.get(),
+    }
+  }
+
+  /// Sets the value, starting a new version.
+  ///
+  /// Snapshots keep the old value; it is dropped here if none has it.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::VersionedRefCell;
+  ///
+  /// let cell = VersionedRefCell::new(1);
+  /// cell.set(2);
+  ///
+  /// assert_eq!(*cell.borrow(), 2);
+  /// assert_eq!(cell.version(), 1);
+  /// ```
+  pub fn set(&self, value: T) {
+    let old = self.value.replace(Rc::new(value));
+    self.version.set(self.version.get() + 1);
+    drop(old);
+  }
+
+  /// Returns the current version: how many times the value has been set or
+  /// mutably borrowed.
+  pub fn version(&self) -> u64 {
+    self.version.get()
+  }
+}
+
+impl<T: Clone> VersionedRefCell<T> {
+  /// Mutably borrows the value, starting a new version.
+  ///
+  /// The first write through the borrow clones the value if snapshots share
+  /// it, and changes it in place if not.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is currently borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::VersionedRefCell;
+  ///
+  /// let cell = VersionedRefCell::new(String::from("a"));
+  /// cell.borrow_mut().push('b');
+  ///
+  /// assert_eq!(*cell.borrow(), "ab");
+  /// assert_eq!(cell.version(), 1);
+  /// ```
+  pub fn borrow_mut(&self) -> VersionedRefMut<'_, T> {
+    let guard = self.value.borrow_mut();
+    self.version.set(self.version.get() + 1);
+    VersionedRefMut { guard }
+  }
+
+  /// Consumes the cell, returning the current value, which is cloned if
+  /// snapshots share it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::VersionedRefCell;
+  ///
+  /// let cell = VersionedRefCell::new(5);
+  /// let _snapshot = cell.snapshot();
+  /// assert_eq!(cell.into_inner(), 5);
+  /// ```
+  pub fn into_inner(self) -> T {
+    Rc::try_unwrap(self.value.into_inner()).unwrap_or_else(|rc| (*rc).clone())
+  }
+}
+
+impl<T: Default> Default for VersionedRefCell<T> {
+  fn default() -> VersionedRefCell<T> {
+    VersionedRefCell::new(T::default())
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for VersionedRefCell<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.value.try_borrow() {
+      Ok(value) => f
+        .debug_struct("VersionedRefCell")
+        .field("value", &**value)
+        .field("version", &self.version.get())
+        .finish(),
+      Err(_) => f.write_str("VersionedRefCell { <borrowed> }"),
+    }
+  }
+}
+
+/// A borrowed reference to the value of a [`VersionedRefCell`].
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct VersionedRef<'b, T> {
+  guard: Ref<'b, Rc<T>>,
+}
+
+impl<T> std::ops::Deref for VersionedRef<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    &self.guard
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for VersionedRef<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    (**self).fmt(f)
+  }
+}
+
+/// A mutably borrowed reference to the value of a [`VersionedRefCell`].
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct VersionedRefMut<'b, T> {
+  guard: RefMut<'b, Rc<T>>,
+}
+
+impl<T> std::ops::Deref for VersionedRefMut<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    &self.guard
+  }
+}
+
+impl<T: Clone> std::ops::DerefMut for VersionedRefMut<'_, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    // Snapshots can't be taken while the cell is mutably borrowed, so this
+    // clones at most once per borrow.
+    Rc::make_mut(&mut self.guard)
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for VersionedRefMut<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    (**self).fmt(f)
+  }
+}
+
+/// The value of a [`VersionedRefCell`] at one version.
+///
+/// Cloning a snapshot copies nothing.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct Snapshot<T> {
+  value: Rc<T>,
+  version: u64,
+}
+
+impl<T> Snapshot<T> {
+  /// Returns the version of the cell that the snapshot was taken at.
+  ///
+  /// This is an associated function, so that it doesn't shadow a method of
+  /// the same name on `T`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Snapshot, VersionedRefCell};
+  ///
+  /// let cell = VersionedRefCell::new(1);
+  /// cell.set(2);
+  /// assert_eq!(Snapshot::version(&cell.snapshot()), 1);
+  /// ```
+  pub fn version(this: &Self) -> u64 {
+    this.version
+  }
+}
+
+impl<T> Clone for Snapshot<T> {
+  fn clone(&self) -> Snapshot<T> {
+    Snapshot {
+      value: Rc::clone(&self.value),
+      version: self.version,
+    }
+  }
+}
+
+impl<T> std::ops::Deref for Snapshot<T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    &self.value
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Snapshot<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Snapshot")
+      .field("value", &*self.value)
+      .field("version", &self.version)
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn snapshots_keep_their_value() {
+    let cell = VersionedRefCell::new(vec![1]);
+    let first = cell.snapshot();
+    cell.borrow_mut().push(2);
+    let second = cell.snapshot();
+    cell.set(vec![3]);
+
+    assert_eq!((&*first, Snapshot::version(&first)), (&vec![1], 0));
+    assert_eq!((&*second, Snapshot::version(&second)), (&vec![1, 2], 1));
+    assert_eq!((&*cell.borrow(), cell.version()), (&vec![3], 2));
+  }
+
+  #[test]
+  fn writes_copy_only_shared_values() {
+    let allocation = |cell: &VersionedRefCell<String>| -> *const String {
+      Rc::as_ptr(&cell.value.borrow())
+    };
+    let cell = VersionedRefCell::new(String::from("a"));
+    let before = allocation(&cell);
+    cell.borrow_mut().push('b');
+    // Nothing shared the value, so it changed in place.
+    assert_eq!(allocation(&cell), before);
+
+    let snapshot = cell.snapshot();
+    let mut value = cell.borrow_mut();
+    value.push('c');
+    value.push('d');
+    drop(value);
+    let copy = allocation(&cell);
+    assert_ne!(copy, before);
+    assert_eq!(*snapshot, "ab");
+
+    // Once the snapshot is gone, the copy is unshared again.
+    drop(snapshot);
+    cell.borrow_mut().push('e');
+    assert_eq!(allocation(&cell), copy);
+    assert_eq!(cell.into_inner(), "abcde");
+  }
+}
@@ -0,0 +1,518 @@
+//! A single-threaded watch channel: one [`Sender`] publishes a value, and each [`Receiver`] sees
+//! the latest one.
+//!
+//! [`channel`] makes the pair. The channel keeps only the most recent value: a receiver that
+//! falls behind skips to it, rather than seeing every value sent. A receiver remembers the last
+//! version it has seen, and [`Receiver::changed`] returns a future that resolves once there is a
+//! newer one. It resolves with an error once the `Sender` is gone and the receiver has seen
+//! everything.
+//!
+//! Everything here lives on one thread: the channel is an [`Rc`] around a [`RefCell`], and the
+//! wakers of the waiting receivers are woken from [`Sender::send`]. This suits the single-threaded,
+//! `!Send` executors that run local tasks. The crate doesn't ship an executor; the futures work
+//! with any of them. For values shared between threads, use a [`Mutex`] and a [`Condvar`]
+//! instead.
+//!
+//! ```
+//! use pointer::watch;
+//!
+//! async fn show_progress(mut progress: watch::Receiver<u8>) {
+//!   while progress.changed().await.is_ok() {
+//!     println!("{}%", *progress.borrow());
+//!   }
+//! }
+//!
+//! let (sender, receiver) = watch::channel(0);
+//! let _task = show_progress(receiver);
+//! sender.send(50).unwrap();
+//! ```
+//!
+//! [`Rc`]: crate::Rc
+//! [`RefCell`]: crate::RefCell
+//! [`Mutex`]: crate::sync::Mutex
+//! [`Condvar`]: crate::sync::Condvar
+
+use crate::{Cell, Rc, Ref, RefCell};
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+/// The state that the sender and receivers of a channel share.
+struct Shared<T> {
+  value: RefCell<T>,
+  /// How many values have been sent.
+  version: Cell<u64>,
+  /// Whether the sender is gone.
+  closed: Cell<bool>,
+  /// The wakers of the receivers waiting for a change.
+  wakers: RefCell<Vec<Waker>>,
+}
+
+impl<T> Shared<T> {
+  fn wake_all(&self) {
+    let wakers = std::mem::take(&mut *self.wakers.borrow_mut());
+    for waker in wakers {
+      waker.wake();
+    }
+  }
+}
+
+/// Creates a watch channel holding `initial`, and returns its sender and a
+/// receiver.
+///
+/// The receiver counts `initial` as seen.
+///
+/// # Examples
+///
+/// ```
+/// use pointer::watch;
+///
+/// let (sender, receiver) = watch::channel("idle");
+/// sender.send("busy").unwrap();
+/// assert_eq!(*receiver.borrow(), "busy");
+/// ```
+pub fn channel<T>(initial: T) -> (Sender<T>, Receiver<T>) {
+  let shared = Rc::new(Shared {
+    value: RefCell::new(initial),
+    version: Cell::new(0),
+    closed: Cell::new(false),
+    wakers: RefCell::new(Vec::new()),
+  });
+  let receiver = Receiver {
+    shared: Rc::clone(&shared),
+    seen: 0,
+  };
+  (Sender { shared }, receiver)
+}
+
+/// The sending half of a [watch channel](channel).
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct Sender<T> {
+  shared: Rc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+  /// Replaces the value, and wakes the receivers waiting for a change.
+  ///
+  /// # Errors
+  ///
+  /// Returns `value` in a [`SendError`] if every receiver is gone, leaving
+  /// the channel as it was.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::watch;
+  ///
+  /// let (sender, receiver) = watch::channel(1);
+  /// assert!(sender.send(2).is_ok());
+  ///
+  /// drop(receiver);
+  /// assert_eq!(sender.send(3).unwrap_err().0, 3);
+  /// ```
+  pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+    if self.receiver_count() == 0 {
+      return Err(SendError(value));
+    }
+    self.send_replace(value);
+    Ok(())
+  }
+
+  /// Replaces the value, even if no receiver is left, wakes the receivers
+  /// waiting for a change, and returns the old value.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is borrowed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::watch;
+  ///
+  /// let (sender, _) = watch::channel(1);
+  /// assert_eq!(sender.send_replace(2), 1);
+  /// assert_eq!(*sender.borrow(), 2);
+  /// ```
+  pub fn send_replace(&self, value: T) -> T {
+    let old = self.shared.value.replace(value);
+    self.changed();
+    old
+  }
+
+  /// Changes the value in place with `f`, and wakes the receivers waiting
+  /// for a change, even if no receiver is left.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is borrowed, or if `f` borrows it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::watch;
+  ///
+  /// let (sender, receiver) = watch::channel(vec![1]);
+  /// sender.send_modify(|v| v.push(2));
+  /// assert_eq!(*receiver.borrow(), [1, 2]);
+  /// ```
+  pub fn send_modify(&self, f: impl FnOnce(&mut T)) {
+    f(&mut self.shared.value.borrow_mut());
+    self.changed();
+  }
+
+  /// Borrows the current value.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is mutably borrowed, from within
+  /// [`send_modify`](Sender::send_modify).
+  pub fn borrow(&self) -> Ref<'_, T> {
+    self.shared.value.borrow()
+  }
+
+  /// Returns a new receiver, which counts the current value as seen.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::watch;
+  ///
+  /// let (sender, receiver) = watch::channel(1);
+  /// drop(receiver);
+  ///
+  /// let receiver = sender.subscribe();
+  /// assert!(sender.send(2).is_ok());
+  /// assert_eq!(receiver.has_changed(), Ok(true));
+  /// ```
+  pub fn subscribe(&self) -> Receiver<T> {
+    Receiver {
+      shared: Rc::clone(&self.shared),
+      seen: self.shared.version.get(),
+    }
+  }
+
+  /// Returns the number of receivers.
+  pub fn receiver_count(&self) -> usize {
+    Rc::strong_count(&self.shared) - 1
+  }
+
+  fn changed(&self) {
+    self.shared.version.set(self.shared.version.get() + 1);
+    self.shared.wake_all();
+  }
+}
+
+impl<T> Drop for Sender<T> {
+  fn drop(&mut self) {
+    self.shared.closed.set(true);
+    self.shared.wake_all();
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Sender<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Sender")
+      .field("receivers", &self.receiver_count())
+      .finish()
+  }
+}
+
+/// The receiving half of a [watch channel](channel).
+///
+/// Cloning a receiver makes another one that has seen the same version.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct Receiver<T> {
+  shared: Rc<Shared<T>>,
+  /// The last version this receiver has seen.
+  seen: u64,
+}
+
+impl<T> Receiver<T> {
+  /// Borrows the latest value, without marking it as seen.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is mutably borrowed, from within
+  /// [`Sender::send_modify`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::watch;
+  ///
+  /// let (sender, receiver) = watch::channel(1);
+  /// sender.send(2).unwrap();
+  ///
+  /// assert_eq!(*receiver.borrow(), 2);
+  /// assert_eq!(receiver.has_changed(), Ok(true));
+  /// ```
+  pub fn borrow(&self) -> Ref<'_, T> {
+    self.shared.value.borrow()
+  }
+
+  /// Borrows the latest value, and marks it as seen.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is mutably borrowed, from within
+  /// [`Sender::send_modify`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::watch;
+  ///
+  /// let (sender, mut receiver) = watch::channel(1);
+  /// sender.send(2).unwrap();
+  ///
+  /// assert_eq!(*receiver.borrow_and_update(), 2);
+  /// assert_eq!(receiver.has_changed(), Ok(false));
+  /// ```
+  pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+    self.seen = self.shared.version.get();
+    self.shared.value.borrow()
+  }
+
+  /// Returns whether a value newer than the last one seen has been sent.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`RecvError`] if the sender is gone, and no newer value was
+  /// sent before it went.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::watch;
+  ///
+  /// let (sender, receiver) = watch::channel(1);
+  /// assert_eq!(receiver.has_changed(), Ok(false));
+  ///
+  /// drop(sender);
+  /// assert!(receiver.has_changed().is_err());
+  /// ```
+  pub fn has_changed(&self) -> Result<bool, RecvError> {
+    let changed = self.shared.version.get() != self.seen;
+    if !changed && self.shared.closed.get() {
+      return Err(RecvError(()));
+    }
+    Ok(changed)
+  }
+
+  /// Waits for a value newer than the last one seen, and marks it as seen.
+  ///
+  /// The future resolves at once if such a value was already sent.
+  ///
+  /// # Errors
+  ///
+  /// The future resolves to [`RecvError`] once the sender is gone, and
+  /// every value it sent has been seen.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::watch;
+  ///
+  /// async fn wait_for_ready(mut state: watch::Receiver<&'static str>) {
+  ///   while *state.borrow_and_update() != "ready" {
+  ///     if state.changed().await.is_err() {
+  ///       return;
+  ///     }
+  ///   }
+  /// }
+  ///
+  /// let (sender, receiver) = watch::channel("starting");
+  /// let _task = wait_for_ready(receiver);
+  /// sender.send("ready").unwrap();
+  /// ```
+  pub fn changed(&mut self) -> Changed<'_, T> {
+    Changed { receiver: self }
+  }
+
+  /// Returns `true` if both receivers belong to the same channel.
+  pub fn same_channel(&self, other: &Self) -> bool {
+    Rc::ptr_eq(&self.shared, &other.shared)
+  }
+}
+
+impl<T> Clone for Receiver<T> {
+  fn clone(&self) -> Receiver<T> {
+    Receiver {
+      shared: Rc::clone(&self.shared),
+      seen: self.seen,
+    }
+  }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Receiver<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Receiver")
+      .field("has_changed", &self.has_changed().ok())
+      .finish()
+  }
+}
+
+/// The future returned by [`Receiver::changed`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Changed<'a, T> {
+  receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for Changed<'_, T> {
+  type Output = Result<(), RecvError>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let receiver = &mut *self.get_mut().receiver;
+    match receiver.has_changed() {
+      Ok(true) => {
+        receiver.seen = receiver.shared.version.get();
+        Poll::Ready(Ok(()))
+      }
+      Ok(false) => {
+        let mut wakers = receiver.shared.wakers.borrow_mut();
+        if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+          wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+      }
+      Err(e) => Poll::Ready(Err(e)),
+    }
+  }
+}
+
+impl<T> std::fmt::Debug for Changed<'_, T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Changed").finish()
+  }
+}
+
+/// The error returned by [`Sender::send`] when every receiver is gone, with
+/// the value that wasn't sent.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> std::fmt::Debug for SendError<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("SendError").finish_non_exhaustive()
+  }
+}
+
+impl<T> std::fmt::Display for SendError<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("sending on a watch channel without receivers")
+  }
+}
+
+impl<T> std::error::Error for SendError<T> {}
+
+/// The error returned by [`Receiver::changed`] and
+/// [`Receiver::has_changed`] once the sender is gone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecvError(());
+
+impl std::fmt::Display for RecvError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("the sender of the watch channel is gone")
+  }
+}
+
+impl std::error::Error for RecvError {}
+
+/// A waker that counts its wakes, for tests.
+#[cfg(all(test, not(loom)))]
+pub(crate) struct Counting(std::sync::atomic::AtomicUsize);
+
+#[cfg(all(test, not(loom)))]
+impl std::task::Wake for Counting {
+  fn wake(self: std::sync::Arc<Self>) {
+    self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+  }
+}
+
+/// Returns a [`Counting`] waker, and the counter to read its wakes from.
+#[cfg(all(test, not(loom)))]
+pub(crate) fn counting() -> (std::sync::Arc<Counting>, Waker) {
+  let counter = std::sync::Arc::new(Counting(Default::default()));
+  (std::sync::Arc::clone(&counter), Waker::from(counter))
+}
+
+/// Returns how many times the waker of `counter` was woken.
+#[cfg(all(test, not(loom)))]
+pub(crate) fn wakes(counter: &Counting) -> usize {
+  counter.0.load(std::sync::atomic::Ordering::SeqCst)
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn changed_waits_for_a_newer_value() {
+    let (sender, mut receiver) = channel(0);
+    let (counter, waker) = counting();
+    let mut cx = Context::from_waker(&waker);
+
+    {
+      let mut changed = std::pin::pin!(receiver.changed());
+      assert!(changed.as_mut().poll(&mut cx).is_pending());
+
+      // The first send wakes the waiting receiver, and the second has no one
+      // left to wake.
+      sender.send(1).unwrap();
+      sender.send(2).unwrap();
+      assert_eq!(wakes(&counter), 1);
+      assert_eq!(changed.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+    }
+    // The receiver skipped to the latest value, and has seen it.
+    assert_eq!(*receiver.borrow(), 2);
+    assert_eq!(receiver.has_changed(), Ok(false));
+  }
+
+  #[test]
+  fn dropping_the_sender_ends_the_channel() {
+    let (sender, mut receiver) = channel("a");
+    let (counter, waker) = counting();
+    let mut cx = Context::from_waker(&waker);
+
+    sender.send("b").unwrap();
+    let mut late = receiver.clone();
+    {
+      let mut changed = std::pin::pin!(receiver.changed());
+      assert_eq!(changed.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+    }
+    let mut changed = std::pin::pin!(receiver.changed());
+    assert!(changed.as_mut().poll(&mut cx).is_pending());
+    drop(sender);
+    assert_eq!(wakes(&counter), 1);
+    assert!(matches!(
+      changed.as_mut().poll(&mut cx),
+      Poll::Ready(Err(_))
+    ));
+
+    // A receiver that hadn't seen the last value still gets it.
+    assert_eq!(late.has_changed(), Ok(true));
+    assert_eq!(*late.borrow_and_update(), "b");
+    assert!(late.has_changed().is_err());
+  }
+
+  #[test]
+  fn sending_needs_a_receiver() {
+    let (sender, receiver) = channel(1);
+    let second = receiver.clone();
+    assert_eq!(sender.receiver_count(), 2);
+    assert!(receiver.same_channel(&second));
+
+    drop((receiver, second));
+    assert_eq!(sender.send(2), Err(SendError(2)));
+    assert_eq!(sender.send_replace(3), 1);
+    assert_eq!(*sender.subscribe().borrow(), 3);
+  }
+}
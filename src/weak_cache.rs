@@ -0,0 +1,278 @@
+//! A map that shares values while they're in use, and forgets them after: [`WeakCache<K, V>`][WeakCache].
+//!
+//! An asset manager hands out the same texture to every sprite that asks for it by name, but
+//! shouldn't keep the texture loaded once no sprite uses it. A `WeakCache` maps each key to a
+//! [`Weak<V>`][Weak]. [`get_or_create`] upgrades it if the value is still alive, and otherwise
+//! creates the value again and hands out the new [`Rc<V>`][Rc]. The cache owns nothing, so a value
+//! lives exactly as long as its users hold it.
+//!
+//! The entries of dropped values stay in the map until they're swept. The cache sweeps itself
+//! whenever it has doubled in size since the last sweep, which costs constant time per insertion
+//! on average, and keeps a cache whose values come and go within twice the size of its live set.
+//! [`compact`] sweeps on demand.
+//!
+//! ```
+//! use pointer::WeakCache;
+//!
+//! let textures = WeakCache::new();
+//! let loads = pointer::Cell::new(0);
+//! let load = |name: &str| {
+//!   loads.set(loads.get() + 1);
+//!   format!("pixels of {}", name)
+//! };
+//!
+//! let a = textures.get_or_create("grass", || load("grass"));
+//! let b = textures.get_or_create("grass", || load("grass"));
+//! assert!(pointer::Rc::ptr_eq(&a, &b));
+//! assert_eq!(loads.get(), 1);
+//!
+//! drop((a, b));
+//! assert!(textures.get(&"grass").is_none());
+//! textures.get_or_create("grass", || load("grass"));
+//! assert_eq!(loads.get(), 2);
+//! ```
+//!
+//! [`get_or_create`]: WeakCache::get_or_create
+//! [`compact`]: WeakCache::compact
+//! [Rc]: crate::Rc
+//! [Weak]: crate::Weak
+
+use crate::{Cell, Rc, RefCell, Weak};
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// The fewest entries at which the cache sweeps itself.
+const MIN_COMPACT_AT: usize = 16;
+
+/// A map from keys to values that are shared while alive.
+///
+/// See the [module-level documentation](./index.html) for more details.
+pub struct WeakCache<K, V> {
+  entries: RefCell<HashMap<K, Weak<V>>>,
+  /// The number of entries at which the cache next sweeps itself.
+  compact_at: Cell<usize>,
+}
+
+impl<K: Eq + Hash, V> WeakCache<K, V> {
+  /// Creates an empty cache.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::WeakCache;
+  ///
+  /// let cache: WeakCache<String, Vec<u8>> = WeakCache::new();
+  /// assert_eq!(cache.live_count(), 0);
+  /// ```
+  pub fn new() -> WeakCache<K, V> {
+    WeakCache {
+      entries: RefCell::new(HashMap::new()),
+      compact_at: Cell::new(MIN_COMPACT_AT),
+    }
+  }
+
+  /// Returns the value for `key`, if it is alive.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::WeakCache;
+  ///
+  /// let cache = WeakCache::new();
+  /// let one = cache.get_or_create(1, || "one");
+  ///
+  /// assert_eq!(cache.get(&1).as_deref(), Some(&"one"));
+  /// drop(one);
+  /// assert!(cache.get(&1).is_none());
+  /// ```
+  pub fn get(&self, key: &K) -> Option<Rc<V>> {
+    self.entries.borrow().get(key)?.upgrade()
+  }
+
+  /// Returns the value for `key` if it is alive, or creates it with `f`,
+  /// caches it, and returns it.
+  ///
+  /// The cache isn't borrowed while `f` runs, so `f` may get or create
+  /// other values, as loading an asset loads the assets it depends on. If
+  /// `f` creates a value for `key` itself, that value is kept and returned,
+  /// and the one `f` returned is dropped, so there is never more than one
+  /// live value for a key.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::WeakCache;
+  ///
+  /// let cache = WeakCache::new();
+  /// let config = cache.get_or_create("config", || vec![1, 2]);
+  /// let again = cache.get_or_create("config", || unreachable!());
+  /// assert_eq!(*again, [1, 2]);
+  /// ```
+  pub fn get_or_create(&self, key: K, f: impl FnOnce() -> V) -> Rc<V> {
+    if let Some(value) = self.get(&key) {
+      return value;
+    }
+    let value = Rc::new(f());
+    match self.get(&key) {
+      Some(existing) => existing,
+      None => {
+        self.put(key, &value);
+        value
+      }
+    }
+  }
+
+  /// Caches `value` for `key`, and returns the live value it replaces.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::{Rc, WeakCache};
+  ///
+  /// let cache = WeakCache::new();
+  /// let value = Rc::new(5);
+  /// assert!(cache.insert("five", &value).is_none());
+  /// assert!(Rc::ptr_eq(&cache.get(&"five").unwrap(), &value));
+  /// ```
+  pub fn insert(&self, key: K, value: &Rc<V>) -> Option<Rc<V>> {
+    let old = self.get(&key);
+    self.put(key, value);
+    old
+  }
+
+  /// Forgets the value for `key`, and returns it if it is alive.
+  ///
+  /// The value itself lives on while it is held.
+  pub fn remove(&self, key: &K) -> Option<Rc<V>> {
+    self.entries.borrow_mut().remove(key)?.upgrade()
+  }
+
+  /// Returns `true` if the value for `key` is alive.
+  pub fn contains(&self, key: &K) -> bool {
+    self
+      .entries
+      .borrow()
+      .get(key)
+      .is_some_and(|weak| weak.strong_count() > 0)
+  }
+
+  /// Returns the number of values that are alive.
+  ///
+  /// This looks at every entry, including the dead ones not swept yet.
+  pub fn live_count(&self) -> usize {
+    let entries = self.entries.borrow();
+    entries
+      .values()
+      .filter(|weak| weak.strong_count() > 0)
+      .count()
+  }
+
+  /// Sweeps out the entries of values that were dropped, and returns how
+  /// many there were.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use pointer::WeakCache;
+  ///
+  /// let cache = WeakCache::new();
+  /// let kept = cache.get_or_create(1, || ());
+  /// cache.get_or_create(2, || ());
+  ///
+  /// assert_eq!(cache.compact(), 1);
+  /// assert_eq!(cache.compact(), 0);
+  /// ```
+  pub fn compact(&self) -> usize {
+    let mut entries = self.entries.borrow_mut();
+    let before = entries.len();
+    entries.retain(|_, weak| weak.strong_count() > 0);
+    self.compact_at.set(MIN_COMPACT_AT.max(entries.len() * 2));
+    before - entries.len()
+  }
+
+  /// Maps `key` to `value`, sweeping the cache first if it has grown enough.
+  fn put(&self, key: K, value: &Rc<V>) {
+    if self.entries.borrow().len() >= self.compact_at.get() {
+      self.compact();
+    }
+    self.entries.borrow_mut().insert(key, Rc::downgrade(value));
+  }
+}
+
+impl<K: Eq + Hash, V> Default for WeakCache<K, V> {
+  fn default() -> WeakCache<K, V> {
+    WeakCache::new()
+  }
+}
+
+impl<K: std::fmt::Debug, V> std::fmt::Debug for WeakCache<K, V> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.entries.try_borrow() {
+      Ok(entries) => {
+        let live: Vec<_> = entries
+          .iter()
+          .filter(|(_, weak)| weak.strong_count() > 0)
+          .map(|(key, _)| key)
+          .collect();
+        f.debug_struct("WeakCache").field("live", &live).finish()
+      }
+      Err(_) => f.write_str("WeakCache { <borrowed> }"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn dead_entries_are_swept_as_the_cache_grows() {
+    let cache = WeakCache::new();
+    let kept: Vec<_> = (0..4).map(|n| cache.get_or_create(n, || n)).collect();
+    for n in 4..1000 {
+      cache.get_or_create(n, || n);
+    }
+
+    assert_eq!(cache.live_count(), 4);
+    assert!(cache.entries.borrow().len() < MIN_COMPACT_AT * 2);
+    assert!((0..4).all(|n| cache.contains(&n)));
+    drop(kept);
+    assert_eq!(cache.live_count(), 0);
+  }
+
+  #[test]
+  fn loaders_may_use_the_cache() {
+    struct Asset {
+      deps: Vec<Rc<Asset>>,
+    }
+
+    fn load(
+      cache: &WeakCache<&'static str, Asset>,
+      name: &'static str,
+    ) -> Rc<Asset> {
+      cache.get_or_create(name, || {
+        let deps = match name {
+          "level" => vec![load(cache, "tiles"), load(cache, "tiles")],
+          _ => Vec::new(),
+        };
+        Asset { deps }
+      })
+    }
+
+    let cache = WeakCache::new();
+    let level = load(&cache, "level");
+    assert!(Rc::ptr_eq(&level.deps[0], &level.deps[1]));
+    assert_eq!(cache.live_count(), 2);
+
+    // A loader that creates its own key keeps the first value.
+    let inner = RefCell::new(None);
+    let outer = cache.get_or_create("loop", || {
+      let asset = cache.get_or_create("loop", || Asset { deps: Vec::new() });
+      *inner.borrow_mut() = Some(asset);
+      Asset { deps: Vec::new() }
+    });
+    let inner = inner.into_inner().unwrap();
+    assert!(Rc::ptr_eq(&outer, &inner));
+  }
+}